@@ -0,0 +1,333 @@
+//! The activity feed behind the sidebar's "Activity" panel: "4 todos
+//! completed today", "120 todos imported". Entries are materialized from
+//! what the app already does and store an i18n key with its params, not
+//! prose, so the frontend renders them in the current language.
+//!
+//! Noisy sources are coalesced through a rollup key: one entry per import,
+//! one per day for completions and cache syncs. Entries older than
+//! `[backend.activity] retention_days` are pruned by the scheduler.
+
+use chrono::{DateTime, Duration, Local, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::settings::SettingsState;
+use crate::storage::Storage;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ActivityKind {
+    TodosCompleted,
+    TodosImported,
+    BackupCreated,
+    CacheSynced,
+    ValidationWarnings,
+    ConnectorSynced,
+}
+
+impl ActivityKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ActivityKind::TodosCompleted => "todos-completed",
+            ActivityKind::TodosImported => "todos-imported",
+            ActivityKind::BackupCreated => "backup-created",
+            ActivityKind::CacheSynced => "cache-synced",
+            ActivityKind::ValidationWarnings => "validation-warnings",
+            ActivityKind::ConnectorSynced => "connector-synced",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "todos-completed" => Some(ActivityKind::TodosCompleted),
+            "todos-imported" => Some(ActivityKind::TodosImported),
+            "backup-created" => Some(ActivityKind::BackupCreated),
+            "cache-synced" => Some(ActivityKind::CacheSynced),
+            "validation-warnings" => Some(ActivityKind::ValidationWarnings),
+            "connector-synced" => Some(ActivityKind::ConnectorSynced),
+            _ => None,
+        }
+    }
+
+    /// Translation key of the summary; its `count` param is the entry's
+    /// count.
+    pub fn summary_key(self) -> &'static str {
+        match self {
+            ActivityKind::TodosCompleted => "activity.todosCompleted",
+            ActivityKind::TodosImported => "activity.todosImported",
+            ActivityKind::BackupCreated => "activity.backupCreated",
+            ActivityKind::CacheSynced => "activity.cacheSynced",
+            ActivityKind::ValidationWarnings => "activity.validationWarnings",
+            ActivityKind::ConnectorSynced => "activity.connectorSynced",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityEntry {
+    pub id: i64,
+    pub kind: ActivityKind,
+    pub summary_key: String,
+    /// Params of the summary, `count` included.
+    pub params: Map<String, Value>,
+    pub count: u64,
+    /// When the first coalesced event happened.
+    pub started_at: DateTime<Utc>,
+    /// When the last one did; the feed is ordered by it.
+    pub at: DateTime<Utc>,
+}
+
+/// Where a page ended: the `at` and `id` of its last entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedCursor {
+    pub at: DateTime<Utc>,
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityPage {
+    pub entries: Vec<ActivityEntry>,
+    /// Pass as `before` for the next page; `None` on the last one.
+    pub next: Option<FeedCursor>,
+}
+
+/// Adds `count` to the entry with `rollup` as its key, or starts a new
+/// entry. `params` replace the stored ones.
+pub fn record(
+    storage: &Storage,
+    kind: ActivityKind,
+    count: u64,
+    params: Map<String, Value>,
+    rollup: Option<String>,
+) -> rusqlite::Result<()> {
+    let now = storage.clock().now();
+    storage.conn().execute(
+        "INSERT INTO activity_feed (kind, summary_key, count, params, rollup_key, started_at, at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+         ON CONFLICT(rollup_key) DO UPDATE
+         SET count = count + excluded.count, params = excluded.params, at = excluded.at",
+        params![
+            kind.as_str(),
+            kind.summary_key(),
+            count as i64,
+            Value::Object(params).to_string(),
+            rollup,
+            now,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Like [`record`], for callers whose own work succeeded either way: a
+/// failure is only logged, and nothing is recorded in a read-only store.
+fn note(
+    storage: &Storage,
+    kind: ActivityKind,
+    count: u64,
+    params: Map<String, Value>,
+    rollup: Option<String>,
+) {
+    if storage.newer_schema().is_some() {
+        return;
+    }
+    if let Err(e) = record(storage, kind, count, params, rollup) {
+        trace::log(format!("Failed to record activity: {}", e));
+    }
+}
+
+/// Rollup key of `kind` for the local day of `at`.
+fn daily(kind: ActivityKind, at: DateTime<Utc>) -> String {
+    format!(
+        "{}:{}",
+        kind.as_str(),
+        at.with_timezone(&Local).date_naive()
+    )
+}
+
+/// Counts a completion under the local day of `completed_at`.
+pub fn todo_completed(storage: &Storage, completed_at: DateTime<Utc>) {
+    let rollup = daily(ActivityKind::TodosCompleted, completed_at);
+    note(
+        storage,
+        ActivityKind::TodosCompleted,
+        1,
+        Map::new(),
+        Some(rollup),
+    );
+}
+
+/// Todos completed on the local day of `now`, as the feed counts them.
+pub fn completed_on(storage: &Storage, now: DateTime<Utc>) -> rusqlite::Result<u64> {
+    storage
+        .conn()
+        .query_row(
+            "SELECT count FROM activity_feed WHERE rollup_key = ?1",
+            [daily(ActivityKind::TodosCompleted, now)],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map(|count| count.unwrap_or(0) as u64)
+}
+
+/// One entry per import, however many times it was resumed.
+pub fn todos_imported(storage: &Storage, import_id: &str, count: u64, source: &str) {
+    let params = Map::from_iter([("source".to_string(), json!(source))]);
+    let rollup = format!("{}:{}", ActivityKind::TodosImported.as_str(), import_id);
+    note(
+        storage,
+        ActivityKind::TodosImported,
+        count,
+        params,
+        Some(rollup),
+    );
+}
+
+pub fn backup_created(storage: &Storage, name: &str) {
+    let params = Map::from_iter([("name".to_string(), json!(name))]);
+    note(storage, ActivityKind::BackupCreated, 1, params, None);
+}
+
+/// Counts the syncs of the day; `todos` is how many the last one cached.
+pub fn cache_synced(storage: &Storage, todos: usize) {
+    let params = Map::from_iter([("todos".to_string(), json!(todos))]);
+    let rollup = daily(ActivityKind::CacheSynced, storage.clock().now());
+    note(storage, ActivityKind::CacheSynced, 1, params, Some(rollup));
+}
+
+/// Counts the validation warnings of the day that writes were let through with.
+pub fn validation_warned(storage: &Storage, warnings: u64) {
+    let rollup = daily(ActivityKind::ValidationWarnings, storage.clock().now());
+    note(
+        storage,
+        ActivityKind::ValidationWarnings,
+        warnings,
+        Map::new(),
+        Some(rollup),
+    );
+}
+
+/// Counts the todos a connector instance added, updated or removed in the
+/// day's re-syncs; `name` is the instance's.
+pub fn connector_synced(storage: &Storage, instance_id: &str, name: &str, changed: u64) {
+    let params = Map::from_iter([("name".to_string(), json!(name))]);
+    let rollup = format!(
+        "{}:{}",
+        daily(ActivityKind::ConnectorSynced, storage.clock().now()),
+        instance_id
+    );
+    note(
+        storage,
+        ActivityKind::ConnectorSynced,
+        changed,
+        params,
+        Some(rollup),
+    );
+}
+
+fn entry_from_row(row: &Row<'_>) -> rusqlite::Result<Option<ActivityEntry>> {
+    // Kinds from a newer app are left out rather than failing the feed.
+    let Some(kind) = ActivityKind::parse(&row.get::<_, String>(1)?) else {
+        return Ok(None);
+    };
+    let count = row.get::<_, i64>(3)? as u64;
+    let mut params: Map<String, Value> =
+        serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default();
+    params.insert("count".to_string(), json!(count));
+    Ok(Some(ActivityEntry {
+        id: row.get(0)?,
+        kind,
+        summary_key: row.get(2)?,
+        params,
+        count,
+        started_at: row.get(5)?,
+        at: row.get(6)?,
+    }))
+}
+
+/// Up to `limit` entries older than `before`, newest first.
+pub fn page(
+    storage: &Storage,
+    before: Option<&FeedCursor>,
+    limit: usize,
+) -> rusqlite::Result<ActivityPage> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT id, kind, summary_key, count, params, started_at, at FROM activity_feed
+         WHERE ?1 IS NULL OR at < ?1 OR (at = ?1 AND id < ?2)
+         ORDER BY at DESC, id DESC
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            before.map(|cursor| cursor.at),
+            before.map_or(0, |cursor| cursor.id),
+            limit as i64,
+        ],
+        |row| {
+            let cursor = FeedCursor {
+                at: row.get(6)?,
+                id: row.get(0)?,
+            };
+            Ok((cursor, entry_from_row(row)?))
+        },
+    )?;
+    let rows = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    let next = match rows.last() {
+        Some((cursor, _)) if rows.len() == limit => Some(cursor.clone()),
+        _ => None,
+    };
+    Ok(ActivityPage {
+        entries: rows.into_iter().filter_map(|(_, entry)| entry).collect(),
+        next,
+    })
+}
+
+/// Deletes entries last touched more than `retention_days` before `now`.
+pub fn prune(
+    storage: &Storage,
+    now: DateTime<Utc>,
+    retention_days: u32,
+) -> rusqlite::Result<usize> {
+    storage.conn().execute(
+        "DELETE FROM activity_feed WHERE at < ?1",
+        [now - Duration::days(retention_days.into())],
+    )
+}
+
+/// Prunes with the configured retention; run by the scheduler.
+pub fn prune_expired(app: &AppHandle) {
+    let storage = app.state::<Storage>();
+    if storage.newer_schema().is_some() {
+        return;
+    }
+    let retention_days = app.state::<SettingsState>().get().activity.retention_days;
+    if let Err(e) = prune(&storage, storage.clock().now(), retention_days) {
+        trace::log(format!("Failed to prune the activity feed: {}", e));
+    }
+}
+
+/// A page of the activity feed, newest first. Pass the previous page's
+/// `next` as `before` to continue.
+#[tauri::command]
+pub fn get_activity_feed(
+    before: Option<FeedCursor>,
+    limit: Option<usize>,
+    storage: State<'_, Storage>,
+) -> Result<ActivityPage, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    page(&storage, before.as_ref(), limit)
+        .map_err(|e| format!("Failed to read the activity feed: {}", e))
+}
@@ -0,0 +1,94 @@
+use super::*;
+
+use std::sync::Arc;
+
+use chrono::TimeZone;
+
+use crate::clock::MockClock;
+use crate::import::{self, ConflictPolicy};
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 6, 3, 12, 0, 0).unwrap()
+}
+
+fn storage(clock: &Arc<MockClock>) -> Storage {
+    Storage::open_in_memory().unwrap().with_clock(clock.clone())
+}
+
+fn all(storage: &Storage) -> Vec<ActivityEntry> {
+    page(storage, None, MAX_PAGE_SIZE).unwrap().entries
+}
+
+#[test]
+fn test_noisy_sources_are_coalesced() {
+    let clock = Arc::new(MockClock::new(start()));
+    let storage = storage(&clock);
+    for _ in 0..4 {
+        todo_completed(&storage, clock.now());
+        cache_synced(&storage, 10);
+    }
+    clock
+        .advance(std::time::Duration::from_secs(24 * 3600))
+        .unwrap();
+    todo_completed(&storage, clock.now());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todos.csv");
+    let rows: String = (0..120).map(|i| format!("Task {}\n", i)).collect();
+    std::fs::write(&path, format!("title\n{}", rows)).unwrap();
+    let checkpoint = import::begin_import(&storage, &path, ConflictPolicy::Skip).unwrap();
+    import::run_import(
+        &storage,
+        checkpoint,
+        &crate::validation_rules::RuleSet::default(),
+        50,
+        |_| false,
+        |_| {},
+    )
+    .unwrap();
+
+    let entries = all(&storage);
+    let summary: Vec<_> = entries.iter().map(|e| (e.kind, e.count)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (ActivityKind::TodosImported, 120),
+            (ActivityKind::TodosCompleted, 1),
+            (ActivityKind::CacheSynced, 4),
+            (ActivityKind::TodosCompleted, 4),
+        ]
+    );
+    assert_eq!(entries[0].summary_key, "activity.todosImported");
+    assert_eq!(entries[0].params["count"], 120);
+    assert_eq!(entries[2].params["todos"], 10);
+}
+
+#[test]
+fn test_feed_pages_by_keyset_and_prunes_old_entries() {
+    let clock = Arc::new(MockClock::new(start()));
+    let storage = storage(&clock);
+    for i in 0..5 {
+        backup_created(&storage, &format!("backup-{}", i));
+        // Two entries share each timestamp, so pages split on the id too.
+        if i % 2 == 1 {
+            clock.advance(std::time::Duration::from_secs(3600)).unwrap();
+        }
+    }
+
+    let first = page(&storage, None, 2).unwrap();
+    let second = page(&storage, first.next.as_ref(), 2).unwrap();
+    let third = page(&storage, second.next.as_ref(), 2).unwrap();
+    let names: Vec<_> = [&first, &second, &third]
+        .iter()
+        .flat_map(|page| page.entries.iter().map(|e| e.params["name"].clone()))
+        .collect();
+    assert_eq!(
+        names,
+        vec!["backup-4", "backup-3", "backup-2", "backup-1", "backup-0"]
+    );
+    assert!(third.next.is_none());
+
+    let later = start() + Duration::days(30) + Duration::hours(1);
+    assert_eq!(prune(&storage, later, 30).unwrap(), 2);
+    assert_eq!(all(&storage).len(), 3);
+}
@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+
+use crate::storage::Storage;
+
+#[cfg(test)]
+mod tests;
+
+const DEFAULT_AUDIT_LIMIT: usize = 200;
+
+/// One recorded action, as persisted in `audit_log`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: DateTime<Utc>,
+    /// Subsystem that performed the action, e.g. `local-api`.
+    pub source: String,
+    pub action: String,
+    pub detail: String,
+}
+
+pub fn record(storage: &Storage, source: &str, action: &str, detail: &str) -> rusqlite::Result<()> {
+    storage.conn().execute(
+        "INSERT INTO audit_log (at, source, action, detail) VALUES (?1, ?2, ?3, ?4)",
+        params![storage.clock().now(), source, action, detail],
+    )?;
+    Ok(())
+}
+
+/// The most recent `limit` entries, newest first.
+pub fn recent(storage: &Storage, limit: usize) -> rusqlite::Result<Vec<AuditEntry>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT id, at, source, action, detail FROM audit_log ORDER BY id DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map([limit as i64], |row| {
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            at: row.get(1)?,
+            source: row.get(2)?,
+            action: row.get(3)?,
+            detail: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[tauri::command]
+pub fn get_audit_log(
+    limit: Option<usize>,
+    storage: State<'_, Storage>,
+) -> Result<Vec<AuditEntry>, String> {
+    recent(&storage, limit.unwrap_or(DEFAULT_AUDIT_LIMIT))
+        .map_err(|e| format!("Failed to read audit log: {}", e))
+}
@@ -0,0 +1,15 @@
+use super::*;
+
+#[test]
+fn test_recent_returns_newest_first() {
+    crate::storage::for_each_backend(|storage| {
+        record(storage, "local-api", "GET /todos", "200").unwrap();
+        record(storage, "local-api", "POST /todos", "201").unwrap();
+        record(storage, "local-api", "GET /agenda", "401").unwrap();
+
+        let entries = recent(storage, 2).unwrap();
+        let actions: Vec<_> = entries.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, vec!["GET /agenda", "POST /todos"]);
+        assert_eq!(entries[0].detail, "401");
+    });
+}
@@ -0,0 +1,783 @@
+//! Commands the app runs later by itself, e.g. "export at 6pm daily". Only
+//! the operations in [`ScheduledCommand`] can be scheduled; anything else
+//! fails to deserialize. Schedules are kept in the store and run by the
+//! background scheduler at its next wake-up after they come due, so they
+//! may run up to one scheduler interval late.
+//!
+//! Also rules, e.g. "when a todo tagged #urgent is created, set priority
+//! high and notify". They follow the change feed, so they see todos
+//! however they were written, and run in the order they were defined.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::events;
+use crate::export::{self, ExportFormat};
+use crate::file_access::{self, PathError};
+use crate::notifications::{self, NotificationText};
+use crate::privacy::PrivacyState;
+use crate::protocol::EventPayload;
+use crate::query;
+use crate::search::SearchIndex;
+use crate::storage::changes::{ChangeOp, ChangesSince};
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::{Priority, Todo};
+
+#[cfg(test)]
+mod tests;
+
+/// Shortest interval a recurring command may run at.
+const MIN_INTERVAL_SECS: u64 = 60;
+
+/// The operations that may be scheduled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ScheduledCommand {
+    /// Copies the store into the backups directory.
+    Backup,
+    /// Writes every todo to `path`, overwriting the previous export.
+    #[serde(rename_all = "camelCase")]
+    Export {
+        path: PathBuf,
+        format: ExportFormat,
+        #[serde(default)]
+        stable: bool,
+    },
+    /// Deletes completed todos last updated longer ago than this.
+    #[serde(rename_all = "camelCase")]
+    PurgeCompleted { older_than_days: u32 },
+}
+
+/// When a scheduled command runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Schedule {
+    Once {
+        at: DateTime<Utc>,
+    },
+    /// Every day at `time`, local time.
+    Daily {
+        time: NaiveTime,
+    },
+    #[serde(rename_all = "camelCase")]
+    Every {
+        interval_secs: u64,
+    },
+}
+
+impl Schedule {
+    fn validate(&self, now: DateTime<Utc>) -> Result<(), String> {
+        match self {
+            Schedule::Once { at } if *at <= now => Err(format!("{} is in the past", at)),
+            Schedule::Every { interval_secs } if *interval_secs < MIN_INTERVAL_SECS => {
+                Err(format!(
+                    "the interval must be at least {} seconds",
+                    MIN_INTERVAL_SECS
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The first run of `schedule` strictly after `after`, with daily times in
+/// `tz`. A daily time that doesn't exist on some day, as in a daylight
+/// saving gap, skips that day.
+pub fn next_run<Tz: TimeZone>(
+    schedule: &Schedule,
+    after: DateTime<Utc>,
+    tz: &Tz,
+) -> Option<DateTime<Utc>> {
+    match schedule {
+        Schedule::Once { at } => (*at > after).then_some(*at),
+        Schedule::Every { interval_secs } => {
+            Some(after + chrono::Duration::seconds(*interval_secs as i64))
+        }
+        Schedule::Daily { time } => {
+            let today = after.with_timezone(tz).date_naive();
+            (0..=2)
+                .filter_map(|days| today.checked_add_days(Days::new(days)))
+                .filter_map(|date| {
+                    tz.from_local_datetime(&date.and_time(*time))
+                        .earliest()
+                        .map(|run| run.with_timezone(&Utc))
+                })
+                .find(|run| *run > after)
+        }
+    }
+}
+
+/// A scheduled command as `list_scheduled_commands` shows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledEntry {
+    pub id: String,
+    pub command: ScheduledCommand,
+    pub schedule: Schedule,
+    pub next_run: Option<DateTime<Utc>>,
+    pub last_run: Option<DateTime<Utc>>,
+    /// Why the last run failed, if it did.
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+fn json_column<T: for<'de> Deserialize<'de>>(text: String, column: usize) -> rusqlite::Result<T> {
+    serde_json::from_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+pub fn add_entry(
+    conn: &Connection,
+    command: ScheduledCommand,
+    schedule: Schedule,
+    now: DateTime<Utc>,
+    tz: &impl TimeZone,
+) -> Result<ScheduledEntry, String> {
+    schedule.validate(now)?;
+    let entry = ScheduledEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        next_run: next_run(&schedule, now, tz),
+        command,
+        schedule,
+        last_run: None,
+        last_error: None,
+        created_at: now,
+    };
+    conn.execute(
+        "INSERT INTO scheduled_commands (id, command, schedule, next_run, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            entry.id,
+            serde_json::to_string(&entry.command).map_err(|e| e.to_string())?,
+            serde_json::to_string(&entry.schedule).map_err(|e| e.to_string())?,
+            entry.next_run,
+            entry.created_at,
+        ],
+    )
+    .map_err(|e| format!("Failed to schedule command: {}", e))?;
+    Ok(entry)
+}
+
+/// Scheduled commands, the next to run first.
+pub fn list_entries(conn: &Connection) -> rusqlite::Result<Vec<ScheduledEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, command, schedule, next_run, last_run, last_error, created_at
+         FROM scheduled_commands ORDER BY next_run IS NULL, next_run, created_at",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ScheduledEntry {
+            id: row.get(0)?,
+            command: json_column(row.get(1)?, 1)?,
+            schedule: json_column(row.get(2)?, 2)?,
+            next_run: row.get(3)?,
+            last_run: row.get(4)?,
+            last_error: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Deletes the completed todos last updated before `cutoff` and returns how
+/// many there were.
+pub fn purge_completed(conn: &Connection, cutoff: DateTime<Utc>) -> rusqlite::Result<usize> {
+    let purged = conn.execute(
+        "DELETE FROM todos WHERE completed = 1 AND updated_at < ?1",
+        [cutoff],
+    )?;
+    conn.execute(
+        "DELETE FROM todo_field_values WHERE todo_id NOT IN (SELECT id FROM todos)",
+        [],
+    )?;
+    Ok(purged)
+}
+
+/// Runs the entries due at `now` through `execute` and moves each to its
+/// next run. One-off entries are removed once they ran. Returns the ids
+/// that ran with how they went.
+pub fn run_due(
+    storage: &Storage,
+    now: DateTime<Utc>,
+    tz: &impl TimeZone,
+    mut execute: impl FnMut(&ScheduledCommand) -> Result<(), String>,
+) -> rusqlite::Result<Vec<(String, Result<(), String>)>> {
+    let due: Vec<ScheduledEntry> = list_entries(&storage.conn())?
+        .into_iter()
+        .filter(|entry| entry.next_run.is_some_and(|next| next <= now))
+        .collect();
+    let mut ran = Vec::new();
+    for entry in due {
+        // Runs without holding the connection; a backup needs it too.
+        let result = execute(&entry.command);
+        match next_run(&entry.schedule, now, tz) {
+            Some(next) => storage.conn().execute(
+                "UPDATE scheduled_commands SET next_run = ?2, last_run = ?3, last_error = ?4
+                 WHERE id = ?1",
+                params![entry.id, next, now, result.as_ref().err()],
+            )?,
+            None => storage
+                .conn()
+                .execute("DELETE FROM scheduled_commands WHERE id = ?1", [&entry.id])?,
+        };
+        ran.push((entry.id, result));
+    }
+    Ok(ran)
+}
+
+fn execute(app: &AppHandle, command: &ScheduledCommand) -> Result<(), String> {
+    let storage = app.state::<Storage>();
+    match command {
+        ScheduledCommand::Backup => storage::back_up(&storage, "scheduled").map(|_| ()),
+        ScheduledCommand::Export {
+            path,
+            format,
+            stable,
+        } => {
+            // Checked again, in case the allowed directories changed since.
+            let path = file_access::check(app, path).map_err(|e| e.to_string())?;
+            let todos = storage
+                .list_todos()
+                .map_err(|e| format!("Failed to load todos: {}", e))?;
+            let todos = app.state::<PrivacyState>().get().apply(todos);
+            export::write_file(&path, &todos.value, *format, *stable)
+        }
+        ScheduledCommand::PurgeCompleted { older_than_days } => {
+            let cutoff = storage.clock().now() - chrono::Duration::days(*older_than_days as i64);
+            let purged = purge_completed(&storage.conn(), cutoff)
+                .map_err(|e| format!("Failed to purge completed todos: {}", e))?;
+            if purged > 0 {
+                if let Ok(todos) = storage.list_todos() {
+                    app.state::<SearchIndex>().rebuild(&todos);
+                }
+                let _ = events::emit(app, "todo-cache-updated", ());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledCommandRan {
+    pub id: String,
+    pub error: Option<String>,
+}
+
+impl EventPayload for ScheduledCommandRan {}
+
+/// Runs the scheduled commands that are due, emitting
+/// `scheduled-command-ran` for each. Called by the scheduler loop.
+pub fn run_due_commands(app: &AppHandle) {
+    let storage = app.state::<Storage>();
+    if storage.newer_schema().is_some() {
+        return;
+    }
+    let now = storage.clock().now();
+    let ran = match run_due(&storage, now, &Local, |command| execute(app, command)) {
+        Ok(ran) => ran,
+        Err(e) => {
+            trace::log(format!("Failed to run scheduled commands: {}", e));
+            return;
+        }
+    };
+    for (id, result) in ran {
+        if let Err(e) = &result {
+            trace::log(format!("Scheduled command {} failed: {}", id, e));
+        }
+        let _ = events::emit(
+            app,
+            "scheduled-command-ran",
+            ScheduledCommandRan {
+                id,
+                error: result.err(),
+            },
+        );
+    }
+}
+
+/// Schedules `cmd` to run `when`, and returns its id.
+#[tauri::command]
+pub fn schedule_command(
+    cmd: ScheduledCommand,
+    when: Schedule,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<String, PathError> {
+    if let ScheduledCommand::Export { path, .. } = &cmd {
+        file_access::check(&app, path)?;
+    }
+    let entry = add_entry(&storage.conn(), cmd, when, storage.clock().now(), &Local)?;
+    Ok(entry.id)
+}
+
+#[tauri::command]
+pub fn list_scheduled_commands(storage: State<'_, Storage>) -> Result<Vec<ScheduledEntry>, String> {
+    list_entries(&storage.conn()).map_err(|e| format!("Failed to list scheduled commands: {}", e))
+}
+
+#[tauri::command]
+pub fn cancel_scheduled_command(id: String, storage: State<'_, Storage>) -> Result<(), String> {
+    let deleted = storage
+        .conn()
+        .execute("DELETE FROM scheduled_commands WHERE id = ?1", [&id])
+        .map_err(|e| format!("Failed to cancel scheduled command: {}", e))?;
+    if deleted == 0 {
+        return Err(format!("Failed to cancel: no scheduled command '{}'", id));
+    }
+    Ok(())
+}
+
+/// What a rule reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Trigger {
+    Created,
+    /// Any update, completing included.
+    Updated,
+    Completed,
+}
+
+/// The triggers a change of `op` to `changed_fields` sets off, with `todo`
+/// as it is now.
+pub fn triggers(op: ChangeOp, changed_fields: &[String], todo: &Todo) -> Vec<Trigger> {
+    match op {
+        ChangeOp::Insert => vec![Trigger::Created],
+        ChangeOp::Update if todo.completed && changed_fields.iter().any(|f| f == "completed") => {
+            vec![Trigger::Updated, Trigger::Completed]
+        }
+        ChangeOp::Update => vec![Trigger::Updated],
+        ChangeOp::Delete => Vec::new(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Condition {
+    /// With or without the leading `#`, in any case.
+    HasTag {
+        tag: String,
+    },
+    Priority {
+        priority: Priority,
+    },
+    /// In the title, in any case.
+    TitleContains {
+        text: String,
+    },
+    /// A query as smart lists use them.
+    Matches {
+        query: String,
+    },
+}
+
+fn same_tag(a: &str, b: &str) -> bool {
+    a.trim_start_matches('#')
+        .eq_ignore_ascii_case(b.trim_start_matches('#'))
+}
+
+impl Condition {
+    pub fn matches(&self, todo: &Todo, today: NaiveDate) -> bool {
+        match self {
+            Condition::HasTag { tag } => todo.tags.iter().any(|t| same_tag(t, tag)),
+            Condition::Priority { priority } => todo.priority == *priority,
+            Condition::TitleContains { text } => {
+                todo.title.to_lowercase().contains(&text.to_lowercase())
+            }
+            // Checked when the rule was added.
+            Condition::Matches { query } => {
+                query::parse_query(query).is_ok_and(|ast| query::matches(&ast, todo, today))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Action {
+    SetPriority {
+        priority: Priority,
+    },
+    /// Added unless the todo has it already.
+    AddTag {
+        tag: String,
+    },
+    /// Notifies like a reminder, subject to the cooldown and the digest.
+    Notify,
+}
+
+/// A rule; `test_rule` also takes one that wasn't added yet, without the
+/// fields the store assigns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    #[serde(default)]
+    pub id: String,
+    pub trigger: Trigger,
+    /// All must hold.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Applied in this order.
+    pub actions: Vec<Action>,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub created_at: DateTime<Utc>,
+}
+
+/// What the rules did to a todo.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub todo: Todo,
+    /// Rules that asked to notify, in order.
+    pub notify: Vec<String>,
+    /// Every rule that fired, in order.
+    pub fired: Vec<String>,
+}
+
+/// Runs `rules` in order against `todo` for a change that set off
+/// `triggers`. Each rule sees the todo as the rules before it left it;
+/// disabled ones are skipped.
+pub fn apply_rules(rules: &[Rule], triggers: &[Trigger], todo: Todo, today: NaiveDate) -> Outcome {
+    let mut outcome = Outcome {
+        todo,
+        notify: Vec::new(),
+        fired: Vec::new(),
+    };
+    for rule in rules {
+        let fires = rule.enabled
+            && triggers.contains(&rule.trigger)
+            && rule
+                .conditions
+                .iter()
+                .all(|condition| condition.matches(&outcome.todo, today));
+        if !fires {
+            continue;
+        }
+        for action in &rule.actions {
+            match action {
+                Action::SetPriority { priority } => outcome.todo.priority = *priority,
+                Action::AddTag { tag } => {
+                    if !outcome.todo.tags.iter().any(|t| same_tag(t, tag)) {
+                        outcome.todo.tags.push(tag.clone());
+                    }
+                }
+                Action::Notify => outcome.notify.push(rule.id.clone()),
+            }
+        }
+        outcome.fired.push(rule.id.clone());
+    }
+    outcome
+}
+
+/// Todo ids [`RuleTestResult`] lists at most.
+const TEST_SAMPLE: usize = 20;
+
+/// What a rule would do to one todo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleTestMatch {
+    pub todo_id: String,
+    /// Fields the actions would change; empty when they're already set.
+    pub changed_fields: Vec<String>,
+    pub notify: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleTestResult {
+    /// Todos the conditions match.
+    pub matched: usize,
+    /// Of those, the ones the actions would change.
+    pub changed: usize,
+    /// The first matches, in cache order.
+    pub sample: Vec<RuleTestMatch>,
+}
+
+/// What `rule` would do to `todos` if its trigger fired for each of them,
+/// whether or not it is enabled. Nothing is written.
+pub fn test_rule_against(rule: &Rule, todos: &[Todo], today: NaiveDate) -> RuleTestResult {
+    let rule = Rule {
+        enabled: true,
+        ..rule.clone()
+    };
+    let rules = [rule];
+    let mut result = RuleTestResult {
+        matched: 0,
+        changed: 0,
+        sample: Vec::new(),
+    };
+    for todo in todos {
+        let outcome = apply_rules(&rules, &[rules[0].trigger], todo.clone(), today);
+        if outcome.fired.is_empty() {
+            continue;
+        }
+        let changed_fields = outcome.todo.changed_fields(todo);
+        result.matched += 1;
+        if !changed_fields.is_empty() {
+            result.changed += 1;
+        }
+        if result.sample.len() < TEST_SAMPLE {
+            result.sample.push(RuleTestMatch {
+                todo_id: todo.id.clone(),
+                changed_fields,
+                notify: !outcome.notify.is_empty(),
+            });
+        }
+    }
+    result
+}
+
+/// Parses the queries of `conditions`, which are otherwise only parsed
+/// when they're evaluated.
+fn check_queries(conditions: &[Condition]) -> Result<(), query::QueryError> {
+    for condition in conditions {
+        if let Condition::Matches { query } = condition {
+            query::parse_query(query)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn add_rule_entry(
+    conn: &Connection,
+    trigger: Trigger,
+    conditions: Vec<Condition>,
+    actions: Vec<Action>,
+    now: DateTime<Utc>,
+) -> Result<Rule, String> {
+    if actions.is_empty() {
+        return Err("Failed to add rule: it has no actions".to_string());
+    }
+    check_queries(&conditions).map_err(|e| format!("Failed to add rule: {}", e))?;
+    let rule = Rule {
+        id: uuid::Uuid::new_v4().to_string(),
+        trigger,
+        conditions,
+        actions,
+        enabled: true,
+        created_at: now,
+    };
+    conn.execute(
+        "INSERT INTO rules (id, position, trigger, conditions, actions, enabled, created_at)
+         VALUES (?1, (SELECT COALESCE(MAX(position), 0) + 1 FROM rules), ?2, ?3, ?4, 1, ?5)",
+        params![
+            rule.id,
+            serde_json::to_string(&rule.trigger).map_err(|e| e.to_string())?,
+            serde_json::to_string(&rule.conditions).map_err(|e| e.to_string())?,
+            serde_json::to_string(&rule.actions).map_err(|e| e.to_string())?,
+            rule.created_at,
+        ],
+    )
+    .map_err(|e| format!("Failed to add rule: {}", e))?;
+    Ok(rule)
+}
+
+/// Rules in the order they run.
+pub fn list_rule_entries(conn: &Connection) -> rusqlite::Result<Vec<Rule>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, trigger, conditions, actions, enabled, created_at
+         FROM rules ORDER BY position",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Rule {
+            id: row.get(0)?,
+            trigger: json_column(row.get(1)?, 1)?,
+            conditions: json_column(row.get(2)?, 2)?,
+            actions: json_column(row.get(3)?, 3)?,
+            enabled: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Managed state: the writes the rules made themselves, by todo id and
+/// revision, so they don't set the rules off again.
+#[derive(Default)]
+pub struct RulesState {
+    written: Mutex<HashSet<(String, u64)>>,
+}
+
+impl RulesState {
+    fn written(&self) -> MutexGuard<'_, HashSet<(String, u64)>> {
+        self.written.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleNotification {
+    pub rule_id: String,
+    pub todo_id: String,
+    pub text: NotificationText,
+}
+
+impl EventPayload for RuleNotification {}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RulesFired {
+    pub todo_id: String,
+    pub rule_ids: Vec<String>,
+}
+
+impl EventPayload for RulesFired {}
+
+/// Runs the rules for a batch of changes; a consumer of the change feed.
+/// Emits `rules-fired` for each todo they touched and `rule-notification`
+/// for each notification that may show now.
+pub fn run_rules(app: &AppHandle, storage: &Storage, changes: &ChangesSince) {
+    let rules = match list_rule_entries(&storage.conn()) {
+        Ok(rules) if rules.iter().any(|rule| rule.enabled) => rules,
+        Ok(_) => return,
+        Err(e) => {
+            trace::log(format!("Failed to load rules: {}", e));
+            return;
+        }
+    };
+    let state = app.state::<RulesState>();
+    let today = storage.clock().now().with_timezone(&Local).date_naive();
+    for change in &changes.changes {
+        if change.entity != "todo" {
+            continue;
+        }
+        let own = change
+            .revision
+            .is_some_and(|revision| state.written().remove(&(change.id.clone(), revision)));
+        if own {
+            continue;
+        }
+        let Ok(Some(todo)) = storage.get_todo(&change.id) else {
+            continue;
+        };
+        let fired_by = triggers(change.op, &change.changed_fields, &todo);
+        if fired_by.is_empty() {
+            continue;
+        }
+        let outcome = apply_rules(&rules, &fired_by, todo.clone(), today);
+        if outcome.fired.is_empty() {
+            continue;
+        }
+        if outcome.todo != todo {
+            match storage::apply_update(storage, outcome.todo, None) {
+                Ok(Some(update)) => {
+                    state
+                        .written()
+                        .insert((update.todo.id.clone(), update.todo.revision));
+                    storage::announce_update(app, storage, Some(&todo), &update);
+                }
+                Ok(None) => {}
+                // Written again since; the rules run for that change too.
+                Err(e) => trace::log(format!("Rules couldn't update {}: {}", todo.id, e)),
+            }
+        }
+        notify(app, storage, &todo.id, &outcome.notify);
+        let _ = events::emit(
+            app,
+            "rules-fired",
+            RulesFired {
+                todo_id: todo.id,
+                rule_ids: outcome.fired,
+            },
+        );
+    }
+}
+
+fn notify(app: &AppHandle, storage: &Storage, todo_id: &str, rule_ids: &[String]) {
+    let Some(rule_id) = rule_ids.first() else {
+        return;
+    };
+    let ids = [todo_id.to_string()];
+    if notifications::claim(app, &ids).individual.is_empty() {
+        return;
+    }
+    let privacy = app.state::<PrivacyState>().get();
+    match notifications::text_for(storage, &privacy, &ids) {
+        Ok(text) => {
+            let _ = events::emit(
+                app,
+                "rule-notification",
+                RuleNotification {
+                    rule_id: rule_id.clone(),
+                    todo_id: todo_id.to_string(),
+                    text,
+                },
+            );
+        }
+        Err(e) => trace::log(e),
+    }
+}
+
+/// Adds a rule after the existing ones, enabled, and returns it.
+#[tauri::command]
+pub fn add_rule(
+    trigger: Trigger,
+    conditions: Vec<Condition>,
+    actions: Vec<Action>,
+    storage: State<'_, Storage>,
+) -> Result<Rule, String> {
+    add_rule_entry(
+        &storage.conn(),
+        trigger,
+        conditions,
+        actions,
+        storage.clock().now(),
+    )
+}
+
+/// Shows what `rule` would do to the cached todos, without changing any.
+#[tauri::command]
+pub fn test_rule(rule: Rule, storage: State<'_, Storage>) -> Result<RuleTestResult, String> {
+    check_queries(&rule.conditions).map_err(|e| format!("Failed to test rule: {}", e))?;
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    let today = storage.clock().now().with_timezone(&Local).date_naive();
+    Ok(test_rule_against(&rule, &todos, today))
+}
+
+#[tauri::command]
+pub fn list_rules(storage: State<'_, Storage>) -> Result<Vec<Rule>, String> {
+    list_rule_entries(&storage.conn()).map_err(|e| format!("Failed to list rules: {}", e))
+}
+
+/// Turns a rule off or back on; it keeps its place in the order.
+#[tauri::command]
+pub fn set_rule_enabled(
+    id: String,
+    enabled: bool,
+    storage: State<'_, Storage>,
+) -> Result<(), String> {
+    let updated = storage
+        .conn()
+        .execute(
+            "UPDATE rules SET enabled = ?2 WHERE id = ?1",
+            params![id, enabled],
+        )
+        .map_err(|e| format!("Failed to update rule: {}", e))?;
+    if updated == 0 {
+        return Err(format!("Failed to update rule: no rule '{}'", id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_rule(id: String, storage: State<'_, Storage>) -> Result<(), String> {
+    let deleted = storage
+        .conn()
+        .execute("DELETE FROM rules WHERE id = ?1", [&id])
+        .map_err(|e| format!("Failed to delete rule: {}", e))?;
+    if deleted == 0 {
+        return Err(format!("Failed to delete rule: no rule '{}'", id));
+    }
+    Ok(())
+}
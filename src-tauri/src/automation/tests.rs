@@ -0,0 +1,436 @@
+use super::*;
+
+use chrono::FixedOffset;
+
+fn at(text: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(text).unwrap().into()
+}
+
+#[test]
+fn test_next_run() {
+    let six_pm = Schedule::Daily {
+        time: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+    };
+    assert_eq!(
+        next_run(&six_pm, at("2024-06-03T12:00:00Z"), &Utc),
+        Some(at("2024-06-03T18:00:00Z"))
+    );
+    // At or after today's run, tomorrow's is next.
+    assert_eq!(
+        next_run(&six_pm, at("2024-06-03T18:00:00Z"), &Utc),
+        Some(at("2024-06-04T18:00:00Z"))
+    );
+    // 6pm in Tokyo is 9am UTC, and the local day decides which 6pm.
+    let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+    assert_eq!(
+        next_run(&six_pm, at("2024-06-03T10:00:00Z"), &tokyo),
+        Some(at("2024-06-04T09:00:00Z"))
+    );
+    assert_eq!(
+        next_run(&six_pm, at("2024-06-03T16:00:00Z"), &tokyo),
+        Some(at("2024-06-04T09:00:00Z"))
+    );
+
+    let every_hour = Schedule::Every {
+        interval_secs: 3600,
+    };
+    assert_eq!(
+        next_run(&every_hour, at("2024-06-03T12:30:00Z"), &Utc),
+        Some(at("2024-06-03T13:30:00Z"))
+    );
+
+    let once = Schedule::Once {
+        at: at("2024-06-03T18:00:00Z"),
+    };
+    assert_eq!(
+        next_run(&once, at("2024-06-03T12:00:00Z"), &Utc),
+        Some(at("2024-06-03T18:00:00Z"))
+    );
+    assert_eq!(next_run(&once, at("2024-06-03T18:00:00Z"), &Utc), None);
+}
+
+#[test]
+fn test_only_whitelisted_commands_deserialize() {
+    let parse = |json: &str| serde_json::from_str::<ScheduledCommand>(json);
+    assert_eq!(
+        parse(r#"{"kind":"backup"}"#).unwrap(),
+        ScheduledCommand::Backup
+    );
+    assert_eq!(
+        parse(r#"{"kind":"purgeCompleted","olderThanDays":30}"#).unwrap(),
+        ScheduledCommand::PurgeCompleted {
+            older_than_days: 30
+        }
+    );
+    assert!(parse(r#"{"kind":"export","path":"/tmp/todos.csv","format":"csv"}"#).is_ok());
+
+    assert!(parse(r#"{"kind":"resetData"}"#).is_err());
+    assert!(parse(r#"{"kind":"secure_wipe"}"#).is_err());
+    assert!(parse(r#"{"command":"backup"}"#).is_err());
+}
+
+#[test]
+fn test_due_commands_run_and_move_on() {
+    let storage = Storage::open_in_memory().unwrap();
+    let now = at("2024-06-03T12:00:00Z");
+    let conn = || storage.conn();
+
+    assert!(add_entry(
+        &conn(),
+        ScheduledCommand::Backup,
+        Schedule::Once {
+            at: at("2024-06-03T11:00:00Z")
+        },
+        now,
+        &Utc
+    )
+    .is_err());
+    assert!(add_entry(
+        &conn(),
+        ScheduledCommand::Backup,
+        Schedule::Every { interval_secs: 1 },
+        now,
+        &Utc
+    )
+    .is_err());
+
+    let once = add_entry(
+        &conn(),
+        ScheduledCommand::Backup,
+        Schedule::Once {
+            at: at("2024-06-03T12:30:00Z"),
+        },
+        now,
+        &Utc,
+    )
+    .unwrap();
+    let daily = add_entry(
+        &conn(),
+        ScheduledCommand::PurgeCompleted { older_than_days: 7 },
+        Schedule::Daily {
+            time: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+        },
+        now,
+        &Utc,
+    )
+    .unwrap();
+    assert_eq!(
+        list_entries(&conn())
+            .unwrap()
+            .iter()
+            .map(|entry| entry.id.as_str())
+            .collect::<Vec<_>>(),
+        [once.id.as_str(), daily.id.as_str()]
+    );
+
+    let mut executed = Vec::new();
+    let ran = run_due(&storage, now, &Utc, |command| {
+        executed.push(command.clone());
+        Ok(())
+    })
+    .unwrap();
+    assert!(ran.is_empty() && executed.is_empty());
+
+    let evening = at("2024-06-03T18:00:30Z");
+    let ran = run_due(&storage, evening, &Utc, |command| {
+        executed.push(command.clone());
+        match command {
+            ScheduledCommand::Backup => Ok(()),
+            _ => Err("disk full".to_string()),
+        }
+    })
+    .unwrap();
+    assert_eq!(ran.len(), 2);
+    assert_eq!(executed.len(), 2);
+
+    // The one-off is gone; the daily one moved to tomorrow and kept its error.
+    let entries = list_entries(&conn()).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, daily.id);
+    assert_eq!(entries[0].next_run, Some(at("2024-06-04T18:00:00Z")));
+    assert_eq!(entries[0].last_run, Some(evening));
+    assert_eq!(entries[0].last_error.as_deref(), Some("disk full"));
+}
+
+fn todo(title: &str, tags: &[&str], priority: Priority) -> Todo {
+    let created = at("2024-06-01T09:00:00Z");
+    Todo {
+        priority,
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Todo::sample("a", title, created)
+    }
+}
+
+fn rule(id: &str, trigger: Trigger, conditions: Vec<Condition>, actions: Vec<Action>) -> Rule {
+    Rule {
+        id: id.to_string(),
+        trigger,
+        conditions,
+        actions,
+        enabled: true,
+        created_at: at("2024-06-01T09:00:00Z"),
+    }
+}
+
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 3).unwrap()
+}
+
+#[test]
+fn test_condition_matching() {
+    let urgent = todo("Call the Bank", &["Urgent", "work"], Priority::Low);
+    let tag = |tag: &str| Condition::HasTag {
+        tag: tag.to_string(),
+    };
+
+    assert!(tag("#urgent").matches(&urgent, today()));
+    assert!(tag("urgent").matches(&urgent, today()));
+    assert!(!tag("#home").matches(&urgent, today()));
+    assert!(Condition::Priority {
+        priority: Priority::Low
+    }
+    .matches(&urgent, today()));
+    assert!(Condition::TitleContains {
+        text: "bank".to_string()
+    }
+    .matches(&urgent, today()));
+    assert!(Condition::Matches {
+        query: "priority:low AND tag:work".to_string()
+    }
+    .matches(&urgent, today()));
+    assert!(!Condition::Matches {
+        query: "priority:high".to_string()
+    }
+    .matches(&urgent, today()));
+}
+
+#[test]
+fn test_triggers() {
+    let mut done = todo("Report", &[], Priority::Medium);
+    let completed = vec!["completed".to_string()];
+    assert_eq!(triggers(ChangeOp::Insert, &[], &done), [Trigger::Created]);
+    // Reopening is an update but not a completion.
+    assert_eq!(
+        triggers(ChangeOp::Update, &completed, &done),
+        [Trigger::Updated]
+    );
+    done.completed = true;
+    assert_eq!(
+        triggers(ChangeOp::Update, &completed, &done),
+        [Trigger::Updated, Trigger::Completed]
+    );
+    assert!(triggers(ChangeOp::Delete, &[], &done).is_empty());
+}
+
+#[test]
+fn test_rules_apply_in_order() {
+    let rules = [
+        rule(
+            "urgent",
+            Trigger::Created,
+            vec![Condition::HasTag {
+                tag: "#urgent".to_string(),
+            }],
+            vec![
+                Action::SetPriority {
+                    priority: Priority::High,
+                },
+                Action::Notify,
+            ],
+        ),
+        // Sees the priority the rule before it set.
+        rule(
+            "escalate",
+            Trigger::Created,
+            vec![Condition::Priority {
+                priority: Priority::High,
+            }],
+            vec![
+                Action::AddTag {
+                    tag: "escalated".to_string(),
+                },
+                Action::AddTag {
+                    tag: "URGENT".to_string(),
+                },
+            ],
+        ),
+        rule(
+            "on-update",
+            Trigger::Updated,
+            Vec::new(),
+            vec![Action::SetPriority {
+                priority: Priority::Low,
+            }],
+        ),
+    ];
+
+    let outcome = apply_rules(
+        &rules,
+        &[Trigger::Created],
+        todo("Fix prod", &["urgent"], Priority::Low),
+        today(),
+    );
+    assert_eq!(outcome.todo.priority, Priority::High);
+    assert_eq!(outcome.todo.tags, ["urgent", "escalated"]);
+    assert_eq!(outcome.notify, ["urgent"]);
+    assert_eq!(outcome.fired, ["urgent", "escalate"]);
+
+    // In the other order, the priority isn't high yet when checked.
+    let reversed = [rules[1].clone(), rules[0].clone()];
+    let outcome = apply_rules(
+        &reversed,
+        &[Trigger::Created],
+        todo("Fix prod", &["urgent"], Priority::Low),
+        today(),
+    );
+    assert_eq!(outcome.fired, ["urgent"]);
+    assert_eq!(outcome.todo.tags, ["urgent"]);
+}
+
+#[test]
+fn test_disabled_rule_is_skipped() {
+    let mut disabled = rule(
+        "off",
+        Trigger::Created,
+        Vec::new(),
+        vec![Action::SetPriority {
+            priority: Priority::High,
+        }],
+    );
+    disabled.enabled = false;
+    let outcome = apply_rules(
+        &[disabled],
+        &[Trigger::Created],
+        todo("Anything", &[], Priority::Low),
+        today(),
+    );
+    assert_eq!(outcome.todo.priority, Priority::Low);
+    assert!(outcome.fired.is_empty());
+}
+
+#[test]
+fn test_rules_are_stored_in_definition_order() {
+    let storage = Storage::open_in_memory().unwrap();
+    let now = at("2024-06-03T12:00:00Z");
+    let conn = storage.conn();
+    let notify = || vec![Action::Notify];
+
+    assert!(add_rule_entry(&conn, Trigger::Created, Vec::new(), Vec::new(), now).is_err());
+    assert!(add_rule_entry(
+        &conn,
+        Trigger::Created,
+        vec![Condition::Matches {
+            query: "tag:work AND (".to_string()
+        }],
+        notify(),
+        now
+    )
+    .is_err());
+
+    let first = add_rule_entry(&conn, Trigger::Completed, Vec::new(), notify(), now).unwrap();
+    let second = add_rule_entry(
+        &conn,
+        Trigger::Created,
+        vec![Condition::HasTag {
+            tag: "urgent".to_string(),
+        }],
+        notify(),
+        now,
+    )
+    .unwrap();
+    assert_eq!(list_rule_entries(&conn).unwrap(), [first, second]);
+}
+
+#[test]
+fn test_rule_test_reports_matches_and_changes() {
+    let mut todos = vec![
+        todo("Fix prod", &["urgent"], Priority::Low),
+        todo("Already high", &["#Urgent"], Priority::High),
+        todo("Relaxed", &["later"], Priority::Low),
+    ];
+    for (n, todo) in todos.iter_mut().enumerate() {
+        todo.id = format!("t{}", n);
+    }
+    let mut urgent = rule(
+        "",
+        Trigger::Created,
+        vec![Condition::HasTag {
+            tag: "urgent".to_string(),
+        }],
+        vec![
+            Action::SetPriority {
+                priority: Priority::High,
+            },
+            Action::Notify,
+        ],
+    );
+    // Tested before it is enabled.
+    urgent.enabled = false;
+
+    let result = test_rule_against(&urgent, &todos, today());
+
+    assert_eq!(result.matched, 2);
+    assert_eq!(result.changed, 1);
+    assert_eq!(
+        result.sample,
+        [
+            RuleTestMatch {
+                todo_id: "t0".to_string(),
+                changed_fields: vec!["priority".to_string()],
+                notify: true,
+            },
+            RuleTestMatch {
+                todo_id: "t1".to_string(),
+                changed_fields: Vec::new(),
+                notify: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_rule_test_samples_but_counts_everything() {
+    let todos: Vec<Todo> = (0..TEST_SAMPLE + 5)
+        .map(|n| {
+            let mut todo = todo("Same", &[], Priority::Low);
+            todo.id = format!("t{}", n);
+            todo
+        })
+        .collect();
+    let everything = rule(
+        "",
+        Trigger::Updated,
+        Vec::new(),
+        vec![Action::AddTag {
+            tag: "seen".to_string(),
+        }],
+    );
+
+    let result = test_rule_against(&everything, &todos, today());
+
+    assert_eq!(result.matched, TEST_SAMPLE + 5);
+    assert_eq!(result.changed, TEST_SAMPLE + 5);
+    assert_eq!(result.sample.len(), TEST_SAMPLE);
+}
+
+#[test]
+fn test_rule_test_changes_nothing() {
+    let storage = Storage::open_in_memory().unwrap();
+    let todos = vec![todo("Fix prod", &["urgent"], Priority::Low)];
+    storage.replace_todos(&todos).unwrap();
+    let before = storage.list_todos().unwrap();
+    let cursor = storage::changes::head(&storage.conn()).unwrap();
+    let urgent: Rule = serde_json::from_value(serde_json::json!({
+        "trigger": "created",
+        "conditions": [{ "kind": "hasTag", "tag": "urgent" }],
+        "actions": [{ "kind": "setPriority", "priority": "high" }],
+    }))
+    .unwrap();
+
+    let result = test_rule_against(&urgent, &before, today());
+
+    assert_eq!(result.changed, 1);
+    assert_eq!(storage.list_todos().unwrap(), before);
+    assert_eq!(storage::changes::head(&storage.conn()).unwrap(), cursor);
+    assert!(list_rule_entries(&storage.conn()).unwrap().is_empty());
+}
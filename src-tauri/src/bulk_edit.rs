@@ -0,0 +1,654 @@
+//! Bulk editing of todos in the user's own text editor, in the spirit of
+//! `git rebase -i`. See [`HEADER`] for the file format.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::capabilities::{Capability, CapabilityRegistry};
+use crate::custom_fields;
+use crate::events;
+use crate::protocol::EventPayload;
+use crate::query;
+use crate::safety::{self, DestructionScope, DestructiveOperation, SafetyState};
+use crate::search::SearchIndex;
+use crate::settings::SettingsState;
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::{Priority, Todo};
+use crate::validation_rules::{self, RuleSet};
+
+#[cfg(test)]
+mod tests;
+
+const HEADER: &str = "\
+# YuToDo bulk edit. Save and close this file to preview your changes.
+#
+# Each line is one todo:
+#   <id> <status> <priority> <due> <tags> <title>
+#
+#   status    todo | done
+#   priority  low | medium | high
+#   due       YYYY-MM-DD, or - for none
+#   tags      comma-separated, or - for none
+#
+# Removing a line leaves that todo unchanged. To delete a todo, put DROP in
+# front of its line. Lines starting with # are ignored.
+";
+
+/// Keyword that marks a line's todo for deletion.
+const DROP_KEYWORD: &str = "DROP";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// A save counts once the file has stopped changing for this long.
+const STABLE_FOR: Duration = Duration::from_secs(1);
+/// Editors that exit sooner than this probably handed the file to another
+/// process (e.g. `code` without `--wait`), so watching continues.
+const DETACHED_EDITOR_EXIT: Duration = Duration::from_secs(2);
+const SESSION_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(
+    tag = "kind",
+    rename_all = "lowercase",
+    rename_all_fields = "camelCase"
+)]
+pub enum BulkChange {
+    Update {
+        before: Box<Todo>,
+        after: Box<Todo>,
+        changed_fields: Vec<String>,
+    },
+    Delete {
+        todo: Box<Todo>,
+    },
+}
+
+impl EventPayload for BulkChange {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParseError {
+    /// 1-based line number in the edited file.
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Collapses whitespace so a value fits on its line.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn render_due(todo: &Todo) -> String {
+    todo.scheduled_for
+        .map(|due| due.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn render_tags(todo: &Todo) -> String {
+    if todo.tags.is_empty() {
+        return "-".to_string();
+    }
+    let tags: Vec<String> = todo
+        .tags
+        .iter()
+        .map(|tag| one_line(tag).replace(' ', "_"))
+        .collect();
+    tags.join(",")
+}
+
+fn render_line(todo: &Todo) -> String {
+    format!(
+        "{} {} {} {} {} {}",
+        todo.id,
+        if todo.completed { "done" } else { "todo" },
+        todo.priority.as_str(),
+        render_due(todo),
+        render_tags(todo),
+        one_line(&todo.title)
+    )
+}
+
+pub fn render(todos: &[Todo]) -> String {
+    let mut text = HEADER.to_string();
+    text.push('\n');
+    for todo in todos {
+        text.push_str(&render_line(todo));
+        text.push('\n');
+    }
+    text
+}
+
+/// Splits off the next whitespace-separated token.
+fn next_token(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.split_once(char::is_whitespace).unwrap_or((text, "")))
+}
+
+fn parse_line(line: &str, original: &Todo) -> Result<Todo, String> {
+    let mut rest = line;
+    let mut take = |what: &str| {
+        let (token, remaining) = next_token(rest).ok_or_else(|| format!("missing {}", what))?;
+        rest = remaining;
+        Ok::<_, String>(token)
+    };
+    take("id")?;
+    let status = take("status")?;
+    let priority = take("priority")?;
+    let due = take("due date")?;
+    let tags = take("tags")?;
+    let title = rest.trim();
+
+    let mut todo = original.clone();
+    todo.completed = match status {
+        "todo" => false,
+        "done" => true,
+        other => return Err(format!("unknown status '{}' (use todo or done)", other)),
+    };
+    todo.priority = Priority::parse(priority)
+        .ok_or_else(|| format!("unknown priority '{}' (use low, medium or high)", priority))?;
+    // Tokens left as rendered keep the original value, so details the format
+    // can't show (time of day, spaces in tags) survive a round trip.
+    if due != render_due(original) {
+        todo.scheduled_for = match due {
+            "-" => None,
+            _ => Some(
+                NaiveDate::parse_from_str(due, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid due date '{}' (use YYYY-MM-DD or -)", due))?
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap_or_default()
+                    .and_utc(),
+            ),
+        };
+    }
+    if tags != render_tags(original) {
+        todo.tags = match tags {
+            "-" => Vec::new(),
+            _ => tags
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect(),
+        };
+    }
+    if title.is_empty() {
+        return Err("missing title".to_string());
+    }
+    if title != one_line(&original.title) {
+        todo.title = title.to_string();
+    }
+    Ok(todo)
+}
+
+/// Parses an edited file against the todos it was rendered from. Any error
+/// rejects the whole file.
+pub fn parse(text: &str, originals: &[Todo]) -> Result<Vec<BulkChange>, ParseError> {
+    let by_id: HashMap<&str, &Todo> = originals.iter().map(|t| (t.id.as_str(), t)).collect();
+    let mut seen = HashSet::new();
+    let mut changes = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ParseError {
+            line: index + 1,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (drop, line) = match next_token(line) {
+            Some((DROP_KEYWORD, rest)) => (true, rest),
+            _ => (false, line),
+        };
+        let (id, _) = next_token(line).ok_or_else(|| error("missing id".to_string()))?;
+        let original = by_id
+            .get(id)
+            .ok_or_else(|| error(format!("unknown todo id '{}'", id)))?;
+        if !seen.insert(id) {
+            return Err(error(format!("todo '{}' appears more than once", id)));
+        }
+
+        if drop {
+            changes.push(BulkChange::Delete {
+                todo: Box::new((*original).clone()),
+            });
+            continue;
+        }
+        let edited = parse_line(line, original).map_err(error)?;
+        let changed_fields = edited.changed_fields(original);
+        if !changed_fields.is_empty() {
+            changes.push(BulkChange::Update {
+                before: Box::new((*original).clone()),
+                after: Box::new(edited),
+                changed_fields,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+pub(crate) fn restore_todo(conn: &Connection, todo: &Todo) -> rusqlite::Result<()> {
+    storage::insert_todo(conn, todo)?;
+    let fields = custom_fields::load_fields(conn)?;
+    for (name, value) in &todo.fields {
+        if let Some(field) = fields.iter().find(|f| &f.name == name) {
+            custom_fields::store_value(conn, &todo.id, field, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies all changes in one transaction and returns the todos as they were
+/// before, for undo. An update to a todo modified elsewhere after the file was
+/// written is merged field by field; if both sides changed the same field, a
+/// deleted todo was modified, or an update breaks a blocking rule of
+/// `rules`, nothing is changed.
+pub fn apply_changes(
+    storage: &Storage,
+    changes: &[BulkChange],
+    rules: &RuleSet,
+) -> Result<Vec<Todo>, String> {
+    let now = storage.clock().now();
+    let mut previous = Vec::new();
+    let mut writes = Vec::new();
+    let mut warnings = Vec::new();
+    for change in changes {
+        let before = match change {
+            BulkChange::Update { before, .. } => before,
+            BulkChange::Delete { todo } => todo,
+        };
+        let changed_elsewhere = || {
+            format!(
+                "'{}' changed since the bulk edit started; reopen the editor",
+                before.title
+            )
+        };
+        let current = storage
+            .get_todo(&before.id)
+            .map_err(|e| format!("Failed to load todo: {}", e))?
+            .ok_or_else(changed_elsewhere)?;
+        let write = match change {
+            BulkChange::Update { after, .. } if current.revision == before.revision => {
+                Some((**after).clone())
+            }
+            BulkChange::Update { after, .. } => Some(
+                after
+                    .merge_into(before, &current)
+                    .map_err(|_| changed_elsewhere())?,
+            ),
+            BulkChange::Delete { .. } if current.revision == before.revision => None,
+            BulkChange::Delete { .. } => return Err(changed_elsewhere()),
+        };
+        if let Some(after) = &write {
+            let violations = rules.check(after);
+            if validation_rules::is_blocking(&violations) {
+                return Err(format!(
+                    "'{}' breaks validation rules: {}",
+                    after.title,
+                    validation_rules::describe(&violations)
+                ));
+            }
+            warnings.extend(violations);
+        }
+        writes.push((current.id.clone(), write));
+        previous.push(current);
+    }
+
+    let mut conn = storage.conn();
+    let result: rusqlite::Result<()> = (|| {
+        let tx = conn.transaction()?;
+        for (id, write) in &writes {
+            match write {
+                Some(after) => {
+                    let mut after = after.clone();
+                    after.updated_at = now;
+                    storage::insert_todo(&tx, &after)?;
+                }
+                None => {
+                    tx.execute("DELETE FROM todos WHERE id = ?1", [id])?;
+                    tx.execute("DELETE FROM todo_field_values WHERE todo_id = ?1", [id])?;
+                }
+            }
+        }
+        tx.commit()
+    })();
+    result.map_err(|e| format!("Failed to apply bulk edit: {}", e))?;
+    validation_rules::note_warnings(storage, &warnings);
+    Ok(previous)
+}
+
+/// Puts back the todos saved by [`apply_changes`], as one transaction.
+pub fn undo_changes(storage: &Storage, previous: &[Todo]) -> rusqlite::Result<()> {
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    for todo in previous {
+        restore_todo(&tx, todo)?;
+    }
+    tx.commit()
+}
+
+/// Detects completed saves by content, so it works both for editors that
+/// rewrite the file in place and ones that atomically swap in a new file.
+pub struct SaveDetector {
+    saved: Option<[u8; 32]>,
+    pending: Option<([u8; 32], Instant)>,
+}
+
+fn content_hash(path: &Path) -> Option<[u8; 32]> {
+    fs::read(path)
+        .ok()
+        .map(|bytes| Sha256::digest(bytes).into())
+}
+
+impl SaveDetector {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            saved: content_hash(path),
+            pending: None,
+        }
+    }
+
+    /// True once new content has stayed the same for [`STABLE_FOR`].
+    pub fn poll(&mut self, path: &Path, now: Instant) -> bool {
+        let Some(hash) = content_hash(path) else {
+            // Mid-swap: the old file is gone and the new one not yet renamed in.
+            return false;
+        };
+        if Some(hash) == self.saved {
+            self.pending = None;
+            return false;
+        }
+        match self.pending {
+            Some((pending, since)) if pending == hash => {
+                if now.duration_since(since) >= STABLE_FOR {
+                    self.saved = Some(hash);
+                    self.pending = None;
+                    return true;
+                }
+                false
+            }
+            _ => {
+                self.pending = Some((hash, now));
+                false
+            }
+        }
+    }
+}
+
+struct Session {
+    path: PathBuf,
+    originals: Vec<Todo>,
+}
+
+/// Open bulk-edit sessions, plus the last applied edit for undo.
+#[derive(Default)]
+pub struct BulkEditState {
+    sessions: Mutex<HashMap<String, Session>>,
+    last_applied: Mutex<Option<Vec<Todo>>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PreviewEvent {
+    session_id: String,
+    changes: Vec<BulkChange>,
+}
+
+impl EventPayload for PreviewEvent {}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ErrorEvent {
+    session_id: String,
+    #[serde(flatten)]
+    error: ParseError,
+}
+
+impl EventPayload for ErrorEvent {}
+
+fn sessions(app: &AppHandle) -> std::sync::MutexGuard<'_, HashMap<String, Session>> {
+    app.state::<BulkEditState>()
+        .inner()
+        .sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+fn preview(app: &AppHandle, session_id: &str) {
+    let Some((path, originals)) = sessions(app)
+        .get(session_id)
+        .map(|s| (s.path.clone(), s.originals.clone()))
+    else {
+        return;
+    };
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    let session_id = session_id.to_string();
+    let _ = match parse(&text, &originals) {
+        Ok(changes) => events::emit(
+            app,
+            "bulk-edit-preview",
+            PreviewEvent {
+                session_id,
+                changes,
+            },
+        ),
+        Err(error) => events::emit(app, "bulk-edit-error", ErrorEvent { session_id, error }),
+    };
+}
+
+/// Waits for saves or the editor exiting and emits a preview for each save.
+fn watch(app: AppHandle, session_id: String, path: PathBuf, mut editor: Option<Child>) {
+    // Real time, not the app's clock: this follows a real editor process.
+    let started = Instant::now();
+    let mut detector = SaveDetector::new(&path);
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if !sessions(&app).contains_key(&session_id) || started.elapsed() > SESSION_TIMEOUT {
+            break;
+        }
+        if detector.poll(&path, Instant::now()) {
+            preview(&app, &session_id);
+        }
+        let exited = editor
+            .as_mut()
+            .is_some_and(|child| child.try_wait().ok().flatten().is_some());
+        if exited {
+            editor = None;
+            if started.elapsed() >= DETACHED_EDITOR_EXIT {
+                // A save right before exiting may not be stable yet.
+                preview(&app, &session_id);
+                break;
+            }
+        }
+    }
+}
+
+fn configured_editor() -> Option<String> {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+}
+
+pub fn register_capabilities(registry: &CapabilityRegistry) {
+    registry.register("externalEditor", || match configured_editor() {
+        Some(_) => Capability::Available,
+        None => Capability::Degraded {
+            detail:
+                "Neither $VISUAL nor $EDITOR is set; bulk edits open in the default text editor"
+                    .to_string(),
+        },
+    });
+}
+
+/// Starts `$VISUAL`/`$EDITOR` on the file, or the app associated with `.txt`.
+fn open_editor(app: &AppHandle, path: &Path) -> Result<Option<Child>, String> {
+    if let Some(editor) = configured_editor() {
+        let mut parts = editor.split_whitespace();
+        let mut command = Command::new(parts.next().unwrap_or_default());
+        command.args(parts).arg(path);
+        return crate::launch(&mut command)
+            .map(Some)
+            .map_err(|e| format!("Failed to start editor '{}': {}", editor, e));
+    }
+    app.opener()
+        .open_path(path.to_string_lossy(), None::<&str>)
+        .map(|_| None)
+        .map_err(|e| format!("Failed to open editor: {}", e))
+}
+
+/// Writes the selected todos (by id, by smart-filter query, or all) to a temp
+/// file and opens it for editing. Previews arrive as `bulk-edit-preview` or
+/// `bulk-edit-error` events carrying the returned session id.
+#[tauri::command]
+pub fn open_bulk_edit(
+    ids: Option<Vec<String>>,
+    filter: Option<String>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<String, String> {
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    let todos = match (ids, filter) {
+        (Some(ids), _) => todos.into_iter().filter(|t| ids.contains(&t.id)).collect(),
+        (None, Some(filter)) => query::filter_todos(&filter, todos, storage.clock().today())
+            .map_err(|e| format!("Syntax error: {}", e))?,
+        (None, None) => todos,
+    };
+    if todos.is_empty() {
+        return Err("No todos to edit".to_string());
+    }
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let path = std::env::temp_dir().join(format!("yutodo-bulk-edit-{}.txt", session_id));
+    fs::write(&path, render(&todos))
+        .map_err(|e| format!("Failed to write bulk edit file: {}", e))?;
+    let editor = match open_editor(&app, &path) {
+        Ok(editor) => editor,
+        Err(e) => {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+    };
+
+    sessions(&app).insert(
+        session_id.clone(),
+        Session {
+            path: path.clone(),
+            originals: todos,
+        },
+    );
+    let (watch_app, watch_id) = (app.clone(), session_id.clone());
+    std::thread::spawn(trace::bind(move || {
+        watch(watch_app, watch_id, path, editor)
+    }));
+    Ok(session_id)
+}
+
+/// Applies the file's current contents as a single undoable change.
+///
+/// Edits that delete todos need a confirmation token for
+/// [`DestructiveOperation::BulkDelete`] scoped to the session and the number
+/// of deletions.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn apply_bulk_edit(
+    session_id: String,
+    confirmation_token: Option<String>,
+    typed_count: Option<usize>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    index: State<'_, SearchIndex>,
+    state: State<'_, BulkEditState>,
+    safety: State<'_, SafetyState>,
+) -> Result<Vec<BulkChange>, String> {
+    let (path, originals) = sessions(&app)
+        .get(&session_id)
+        .map(|s| (s.path.clone(), s.originals.clone()))
+        .ok_or_else(|| format!("Unknown bulk edit session '{}'", session_id))?;
+    let text =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read bulk edit file: {}", e))?;
+    let changes = parse(&text, &originals).map_err(|e| format!("Parse error at {}", e))?;
+    let deletions = changes
+        .iter()
+        .filter(|c| matches!(c, BulkChange::Delete { .. }))
+        .count();
+    let settings = app.state::<SettingsState>().get();
+    if deletions > 0 {
+        safety::authorize(
+            &safety,
+            &settings.safety,
+            &storage,
+            confirmation_token.as_deref(),
+            typed_count,
+            DestructiveOperation::BulkDelete,
+            &DestructionScope {
+                count: deletions,
+                target: Some(session_id.clone()),
+            },
+        )
+        .map_err(|e| format!("Failed to apply bulk edit: {}", e))?;
+    }
+    let rules = RuleSet::load(&storage, &settings.validation_rules)
+        .map_err(|e| format!("Failed to apply bulk edit: {}", e))?;
+    let previous = apply_changes(&storage, &changes, &rules)?;
+
+    *state.last_applied.lock().unwrap_or_else(|e| e.into_inner()) = Some(previous);
+    sessions(&app).remove(&session_id);
+    let _ = fs::remove_file(&path);
+    if let Ok(todos) = storage.list_todos() {
+        index.rebuild(&todos);
+    }
+    let _ = events::emit(&app, "bulk-edit-applied", &changes);
+    Ok(changes)
+}
+
+#[tauri::command]
+pub fn discard_bulk_edit(session_id: String, app: AppHandle) -> Result<(), String> {
+    let session = sessions(&app)
+        .remove(&session_id)
+        .ok_or_else(|| format!("Unknown bulk edit session '{}'", session_id))?;
+    let _ = fs::remove_file(session.path);
+    Ok(())
+}
+
+/// Reverts the most recently applied bulk edit.
+#[tauri::command]
+pub fn undo_bulk_edit(
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    index: State<'_, SearchIndex>,
+    state: State<'_, BulkEditState>,
+) -> Result<Vec<Todo>, String> {
+    let previous = state
+        .last_applied
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .ok_or("Nothing to undo")?;
+    undo_changes(&storage, &previous).map_err(|e| format!("Failed to undo bulk edit: {}", e))?;
+    if let Ok(todos) = storage.list_todos() {
+        index.rebuild(&todos);
+    }
+    let _ = events::emit(&app, "bulk-edit-undone", &previous);
+    Ok(previous)
+}
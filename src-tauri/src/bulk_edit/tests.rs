@@ -0,0 +1,247 @@
+use super::*;
+use chrono::{DateTime, TimeZone, Utc};
+
+fn todo(id: &str, title: &str, tags: &[&str], due: Option<DateTime<Utc>>) -> Todo {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo {
+        scheduled_for: due,
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Todo::sample(id, title, created)
+    }
+}
+
+fn sample() -> Vec<Todo> {
+    vec![
+        todo(
+            "a",
+            "Write report",
+            &["work", "deep focus"],
+            Some(Utc.with_ymd_and_hms(2024, 6, 3, 15, 30, 0).unwrap()),
+        ),
+        todo("b", "Buy milk", &[], None),
+        todo("c", "Call mom", &["family"], None),
+    ]
+}
+
+fn edit(text: &str, from: &str, to: &str) -> String {
+    assert!(text.contains(from), "{}", text);
+    text.replacen(from, to, 1)
+}
+
+#[test]
+fn test_unedited_file_has_no_changes() {
+    let todos = sample();
+    let text = render(&todos);
+    assert!(text.contains("a todo medium 2024-06-03 work,deep_focus Write report\n"));
+    assert!(text.contains("b todo medium - - Buy milk\n"));
+    assert_eq!(parse(&text, &todos).unwrap(), vec![]);
+}
+
+#[test]
+fn test_edits_become_updates_with_changed_fields() {
+    let todos = sample();
+    let text = render(&todos);
+    let text = edit(&text, "b todo medium - -", "b done high 2024-07-01 errands");
+    let text = edit(&text, "Call mom", "Call mom back");
+
+    let changes = parse(&text, &todos).unwrap();
+    assert_eq!(changes.len(), 2);
+    let BulkChange::Update {
+        after,
+        changed_fields,
+        ..
+    } = &changes[0]
+    else {
+        panic!("expected an update");
+    };
+    assert!(after.completed);
+    assert_eq!(after.priority, Priority::High);
+    assert_eq!(after.tags, vec!["errands"]);
+    assert_eq!(
+        changed_fields,
+        &vec!["completed", "priority", "scheduledFor", "tags"]
+    );
+    assert!(
+        matches!(&changes[1], BulkChange::Update { changed_fields, .. } if changed_fields == &vec!["title"])
+    );
+}
+
+#[test]
+fn test_deleted_lines_mean_no_change_and_drop_deletes() {
+    let todos = sample();
+    let text: String = render(&todos)
+        .lines()
+        .filter(|line| !line.starts_with("b "))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    assert_eq!(parse(&text, &todos).unwrap(), vec![]);
+
+    let text = edit(&text, "c todo", "DROP c todo");
+    assert_eq!(
+        parse(&text, &todos).unwrap(),
+        vec![BulkChange::Delete {
+            todo: Box::new(todos[2].clone())
+        }]
+    );
+}
+
+#[test]
+fn test_parse_errors_point_to_lines() {
+    let todos = sample();
+    let text = render(&todos);
+    let header_lines = HEADER.lines().count() + 1;
+    let cases = [
+        (
+            "b todo medium",
+            "b dne medium",
+            "unknown status 'dne' (use todo or done)",
+        ),
+        (
+            "b todo medium",
+            "b todo urgent",
+            "unknown priority 'urgent' (use low, medium or high)",
+        ),
+        (
+            "b todo medium -",
+            "b todo medium tomorrow",
+            "invalid due date 'tomorrow' (use YYYY-MM-DD or -)",
+        ),
+        ("b todo", "zz todo", "unknown todo id 'zz'"),
+        (
+            "b todo medium - - Buy milk",
+            "b todo medium - -",
+            "missing title",
+        ),
+    ];
+    for (from, to, message) in cases {
+        let error = parse(&edit(&text, from, to), &todos).unwrap_err();
+        assert_eq!(error.line, header_lines + 2, "{}", message);
+        assert_eq!(error.message, message);
+    }
+
+    let duplicated = format!("{}b todo medium - - Buy milk\n", text);
+    let error = parse(&duplicated, &todos).unwrap_err();
+    assert_eq!(error.message, "todo 'b' appears more than once");
+}
+
+#[test]
+fn test_apply_is_one_transaction_and_undoable() {
+    crate::storage::for_each_backend(|storage| {
+        storage.replace_todos(&sample()).unwrap();
+        let todos = storage.list_todos().unwrap();
+        let text = edit(&render(&todos), "Buy milk", "Buy oat milk");
+        let text = edit(&text, "c todo", "DROP c todo");
+        let changes = parse(&text, &todos).unwrap();
+
+        let previous = apply_changes(storage, &changes, &RuleSet::default()).unwrap();
+        let titles: Vec<_> = storage
+            .list_todos()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["Write report", "Buy oat milk"]);
+
+        // Applying again is refused because the cache no longer matches.
+        assert!(apply_changes(storage, &changes, &RuleSet::default()).is_err());
+
+        undo_changes(storage, &previous).unwrap();
+        let without_revision = |todos: Vec<Todo>| -> Vec<Todo> {
+            todos
+                .into_iter()
+                .map(|t| Todo { revision: 0, ..t })
+                .collect()
+        };
+        assert_eq!(
+            without_revision(storage.list_todos().unwrap()),
+            without_revision(todos)
+        );
+    });
+}
+
+#[test]
+fn test_apply_merges_edits_made_elsewhere() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&sample()).unwrap();
+    let todos = storage.list_todos().unwrap();
+    let changes = parse(&edit(&render(&todos), "Buy milk", "Buy oat milk"), &todos).unwrap();
+
+    // Another window completes the todo while the editor is open.
+    let mut elsewhere = storage.get_todo("b").unwrap().unwrap();
+    elsewhere.completed = true;
+    storage.save_todo(&elsewhere).unwrap();
+
+    apply_changes(&storage, &changes, &RuleSet::default()).unwrap();
+    let merged = storage.get_todo("b").unwrap().unwrap();
+    assert_eq!(merged.title, "Buy oat milk");
+    assert!(merged.completed);
+
+    // A title changed on both sides cannot be merged.
+    let todos = storage.list_todos().unwrap();
+    let changes = parse(
+        &edit(&render(&todos), "Buy oat milk", "Buy soy milk"),
+        &todos,
+    )
+    .unwrap();
+    let mut elsewhere = storage.get_todo("b").unwrap().unwrap();
+    elsewhere.title = "Buy almond milk".to_string();
+    storage.save_todo(&elsewhere).unwrap();
+    assert!(apply_changes(&storage, &changes, &RuleSet::default()).is_err());
+    assert_eq!(
+        storage.get_todo("b").unwrap().unwrap().title,
+        "Buy almond milk"
+    );
+}
+
+#[test]
+fn test_apply_is_refused_when_an_update_breaks_a_blocking_rule() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&sample()).unwrap();
+    let todos = storage.list_todos().unwrap();
+    let text = edit(&render(&todos), "Buy milk", "buy oat milk");
+    let text = edit(&text, "c todo", "DROP c todo");
+    let changes = parse(&text, &todos).unwrap();
+    let rules = RuleSet::compile(
+        &storage.conn(),
+        &[validation_rules::ValidationRule {
+            name: "Capitalized".to_string(),
+            list: None,
+            tag: None,
+            check: validation_rules::RuleCheck::TitleMatches {
+                pattern: "^[A-Z]".to_string(),
+            },
+            severity: validation_rules::Severity::Block,
+        }],
+        NaiveDate::MIN,
+    )
+    .unwrap();
+
+    let error = apply_changes(&storage, &changes, &rules).unwrap_err();
+    assert!(error.contains("Capitalized"), "{}", error);
+    assert_eq!(storage.list_todos().unwrap(), todos);
+}
+
+#[test]
+fn test_save_detector_handles_in_place_and_atomic_saves() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("edit.txt");
+    fs::write(&path, "original").unwrap();
+    let start = Instant::now();
+    let mut detector = SaveDetector::new(&path);
+    assert!(!detector.poll(&path, start));
+
+    // In place: the new content must be stable before it counts.
+    fs::write(&path, "edited").unwrap();
+    assert!(!detector.poll(&path, start));
+    assert!(detector.poll(&path, start + STABLE_FOR));
+    assert!(!detector.poll(&path, start + STABLE_FOR * 2));
+
+    // Atomic swap: the file briefly disappears, then another one is renamed over it.
+    fs::remove_file(&path).unwrap();
+    assert!(!detector.poll(&path, start + STABLE_FOR * 3));
+    let swap = dir.path().join(".edit.txt.swp");
+    fs::write(&swap, "swapped").unwrap();
+    fs::rename(&swap, &path).unwrap();
+    assert!(!detector.poll(&path, start + STABLE_FOR * 4));
+    assert!(detector.poll(&path, start + STABLE_FOR * 5));
+}
@@ -0,0 +1,331 @@
+//! Memoized aggregates, such as reports and smart list contents, that
+//! windows ask for many times a minute. Each [`CacheKind`] names the todo
+//! fields its values are computed from, and the entries are dropped by a
+//! consumer of [`crate::storage::changes`] as soon as a change touches one
+//! of them: completing a todo drops the effort reports but keeps the tag
+//! counts.
+//!
+//! Until that consumer has seen the latest change, lookups bypass the cache,
+//! so a command never answers with data older than its own last write.
+//! Entries also expire after a while, and the least recently used ones are
+//! evicted once the cache holds more than [`CAPACITY_BYTES`].
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+
+use crate::storage::changes::{self, Change, ChangeOp, ChangesSince};
+use crate::storage::Storage;
+
+#[cfg(test)]
+mod tests;
+
+/// Estimated size of all entries above which the oldest are evicted.
+pub const CAPACITY_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum CacheKind {
+    EffortReport,
+    TagCounts,
+    SmartList,
+}
+
+impl CacheKind {
+    const ALL: [CacheKind; 3] = [
+        CacheKind::EffortReport,
+        CacheKind::TagCounts,
+        CacheKind::SmartList,
+    ];
+
+    fn ttl(self) -> Duration {
+        match self {
+            // Relative dates in list queries move on with the clock.
+            CacheKind::SmartList => Duration::from_secs(60),
+            CacheKind::EffortReport | CacheKind::TagCounts => Duration::from_secs(300),
+        }
+    }
+
+    /// Todo fields, as named in the change feed, the values are computed
+    /// from; `None` for all of them.
+    fn fields(self) -> Option<&'static [&'static str]> {
+        match self {
+            CacheKind::EffortReport => {
+                Some(&["completedAt", "tags", "estimateMinutes", "actualMinutes"])
+            }
+            CacheKind::TagCounts => Some(&["tags"]),
+            CacheKind::SmartList => None,
+        }
+    }
+
+    fn depends_on(self, change: &Change) -> bool {
+        match (change.op, self.fields()) {
+            (ChangeOp::Update, Some(fields)) => change
+                .changed_fields
+                .iter()
+                .any(|field| fields.contains(&field.as_str())),
+            _ => true,
+        }
+    }
+}
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    bytes: usize,
+    expires: Instant,
+    /// Tick of the last lookup or store, for LRU eviction.
+    used: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<(CacheKind, String), Entry>,
+    counters: HashMap<CacheKind, Counters>,
+    bytes: usize,
+    /// Last change the entries were invalidated for.
+    cursor: i64,
+    tick: u64,
+    evictions: u64,
+}
+
+impl Inner {
+    fn touch(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, key: &(CacheKind, String)) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.bytes;
+        }
+    }
+
+    fn remove_kind(&mut self, kind: CacheKind) {
+        let keys: Vec<_> = self
+            .entries
+            .keys()
+            .filter(|(k, _)| *k == kind)
+            .cloned()
+            .collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    fn lookup<T: Clone + 'static>(&mut self, key: &(CacheKind, String)) -> Option<T> {
+        let tick = self.touch();
+        let entry = self.entries.get_mut(key)?;
+        if entry.expires <= Instant::now() {
+            self.remove(key);
+            return None;
+        }
+        entry.used = tick;
+        entry.value.downcast_ref::<T>().cloned()
+    }
+
+    fn store<T: Serialize + Send + Sync + 'static>(
+        &mut self,
+        key: (CacheKind, String),
+        value: T,
+        capacity: usize,
+    ) {
+        let bytes = key.1.len() + serde_json::to_vec(&value).map_or(0, |json| json.len());
+        if bytes > capacity {
+            return;
+        }
+        self.remove(&key);
+        let entry = Entry {
+            value: Arc::new(value),
+            bytes,
+            expires: Instant::now() + key.0.ttl(),
+            used: self.touch(),
+        };
+        self.bytes += bytes;
+        self.entries.insert(key, entry);
+        while self.bytes > capacity {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+            self.evictions += 1;
+        }
+    }
+}
+
+/// Managed state holding the cached values.
+pub struct Caches {
+    inner: Mutex<Inner>,
+    capacity: usize,
+}
+
+impl Default for Caches {
+    fn default() -> Self {
+        Self::with_capacity(CAPACITY_BYTES)
+    }
+}
+
+impl Caches {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            capacity,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The cached value of `kind` for `signature`, or what `compute` returns,
+    /// which is kept unless the store changed meanwhile. Errors aren't kept.
+    pub fn get_or_compute<T>(
+        &self,
+        storage: &Storage,
+        kind: CacheKind,
+        signature: String,
+        compute: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String>
+    where
+        T: Clone + Serialize + Send + Sync + 'static,
+    {
+        let head =
+            changes::head(&storage.conn()).map_err(|e| format!("Failed to read changes: {}", e))?;
+        let key = (kind, signature);
+        let cursor = {
+            let mut inner = self.lock();
+            let cursor = inner.cursor;
+            let cached = if head == cursor {
+                inner.lookup::<T>(&key)
+            } else {
+                None
+            };
+            let counters = inner.counters.entry(kind).or_default();
+            match cached {
+                Some(value) => {
+                    counters.hits += 1;
+                    return Ok(value);
+                }
+                None => counters.misses += 1,
+            }
+            cursor
+        };
+        let value = compute()?;
+        let mut inner = self.lock();
+        if head == cursor && inner.cursor == cursor {
+            inner.store(key, value.clone(), self.capacity);
+        }
+        Ok(value)
+    }
+
+    /// Drops the entries `changes` make stale and moves past them.
+    pub fn invalidate(&self, changes: &ChangesSince) {
+        let mut inner = self.lock();
+        if changes.truncated {
+            inner.clear();
+        } else {
+            for kind in CacheKind::ALL {
+                if changes.changes.iter().any(|change| kind.depends_on(change)) {
+                    inner.remove_kind(kind);
+                }
+            }
+        }
+        inner.cursor = inner.cursor.max(changes.cursor);
+    }
+
+    /// Drops the entries of `kind`, for changes the feed doesn't carry.
+    pub fn invalidate_kind(&self, kind: CacheKind) {
+        self.lock().remove_kind(kind);
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Starts over at `cursor`, where the invalidating consumer starts.
+    pub fn reset(&self, cursor: i64) {
+        let mut inner = self.lock();
+        inner.clear();
+        inner.cursor = cursor;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.lock();
+        CacheStats {
+            kinds: CacheKind::ALL
+                .into_iter()
+                .map(|kind| {
+                    let entries = inner.entries.iter().filter(|((k, _), _)| *k == kind);
+                    let (hits, misses) = inner
+                        .counters
+                        .get(&kind)
+                        .map_or((0, 0), |c| (c.hits, c.misses));
+                    KindStats {
+                        kind,
+                        entries: entries.clone().count(),
+                        bytes: entries.map(|(_, entry)| entry.bytes).sum(),
+                        hits,
+                        misses,
+                        hit_rate: (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64),
+                    }
+                })
+                .collect(),
+            bytes: inner.bytes,
+            capacity_bytes: self.capacity,
+            evictions: inner.evictions,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KindStats {
+    pub kind: CacheKind,
+    pub entries: usize,
+    /// Estimated from the size of the values as JSON.
+    pub bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// `None` before the first lookup.
+    pub hit_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub kinds: Vec<KindStats>,
+    pub bytes: usize,
+    pub capacity_bytes: usize,
+    pub evictions: u64,
+}
+
+/// Hit rates and memory use, for the diagnostics panel.
+#[tauri::command]
+pub fn get_cache_stats(caches: State<'_, Caches>) -> CacheStats {
+    caches.stats()
+}
+
+#[tauri::command]
+pub fn clear_caches(caches: State<'_, Caches>) {
+    caches.clear();
+}
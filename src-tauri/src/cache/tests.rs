@@ -0,0 +1,251 @@
+use super::*;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+use crate::query;
+use crate::stats::{self, EffortReport, TagCount};
+use crate::types::{DateRange, Todo};
+
+fn at(day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 6, day, 12, 0, 0).unwrap()
+}
+
+fn todo(id: &str, tags: &[&str]) -> Todo {
+    Todo {
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        estimate_minutes: Some(30),
+        actual_minutes: Some(45),
+        ..Todo::sample(id, id, at(1))
+    }
+}
+
+fn june() -> DateRange {
+    DateRange {
+        start: at(1),
+        end: at(30),
+    }
+}
+
+fn today() -> NaiveDate {
+    at(15).date_naive()
+}
+
+/// Follows the change feed the way the app's consumer does.
+struct Invalidator {
+    cursor: i64,
+}
+
+impl Invalidator {
+    fn run(&mut self, storage: &Storage, caches: &Caches) {
+        let changes = changes::changes_since(&storage.conn(), self.cursor, 1000).unwrap();
+        self.cursor = changes.cursor;
+        caches.invalidate(&changes);
+    }
+}
+
+fn effort(storage: &Storage, caches: &Caches) -> EffortReport {
+    caches
+        .get_or_compute(storage, CacheKind::EffortReport, "june".to_string(), || {
+            Ok(stats::effort_report(
+                &storage.list_todos().unwrap(),
+                &june(),
+            ))
+        })
+        .unwrap()
+}
+
+fn tags(storage: &Storage, caches: &Caches) -> Vec<TagCount> {
+    caches
+        .get_or_compute(storage, CacheKind::TagCounts, String::new(), || {
+            Ok(stats::tag_counts(&storage.list_todos().unwrap()))
+        })
+        .unwrap()
+}
+
+fn smart_list(storage: &Storage, caches: &Caches, list_id: &str) -> Vec<Todo> {
+    caches
+        .get_or_compute(storage, CacheKind::SmartList, list_id.to_string(), || {
+            query::smart_list_todos(storage, list_id, today())
+        })
+        .unwrap()
+}
+
+fn stats_of(caches: &Caches, kind: CacheKind) -> KindStats {
+    caches
+        .stats()
+        .kinds
+        .into_iter()
+        .find(|stats| stats.kind == kind)
+        .unwrap()
+}
+
+#[test]
+fn test_completion_drops_effort_reports_but_keeps_tag_counts() {
+    let storage = Storage::open_in_memory().unwrap();
+    let caches = Caches::default();
+    let mut invalidator = Invalidator { cursor: 0 };
+    storage.save_todo(&todo("a", &["work"])).unwrap();
+    invalidator.run(&storage, &caches);
+
+    assert_eq!(effort(&storage, &caches).todo_count, 0);
+    tags(&storage, &caches);
+
+    let mut done = storage.get_todo("a").unwrap().unwrap();
+    done.completed = true;
+    done.updated_at = at(2);
+    storage.save_todo(&done).unwrap();
+    invalidator.run(&storage, &caches);
+
+    assert_eq!(effort(&storage, &caches).todo_count, 1);
+    tags(&storage, &caches);
+    let effort_stats = stats_of(&caches, CacheKind::EffortReport);
+    assert_eq!((effort_stats.hits, effort_stats.misses), (0, 2));
+    let tag_stats = stats_of(&caches, CacheKind::TagCounts);
+    assert_eq!((tag_stats.hits, tag_stats.misses), (1, 1));
+    assert_eq!(tag_stats.hit_rate, Some(0.5));
+}
+
+#[test]
+fn test_lookups_bypass_the_cache_until_changes_are_seen() {
+    let storage = Storage::open_in_memory().unwrap();
+    let caches = Caches::default();
+    let mut invalidator = Invalidator { cursor: 0 };
+    assert!(tags(&storage, &caches).is_empty());
+
+    storage.save_todo(&todo("a", &["work"])).unwrap();
+    let expected = vec![TagCount {
+        tag: "work".to_string(),
+        todo_count: 1,
+    }];
+    assert_eq!(tags(&storage, &caches), expected);
+    assert_eq!(stats_of(&caches, CacheKind::TagCounts).entries, 1);
+
+    invalidator.run(&storage, &caches);
+    assert_eq!(tags(&storage, &caches), expected);
+    assert_eq!(tags(&storage, &caches), expected);
+    assert_eq!(stats_of(&caches, CacheKind::TagCounts).hits, 1);
+
+    caches.clear();
+    assert_eq!(caches.stats().bytes, 0);
+}
+
+#[test]
+fn test_least_recently_used_entries_are_evicted() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.save_todo(&todo("a", &["work"])).unwrap();
+    let entry_bytes = "1".len()
+        + serde_json::to_vec(&stats::tag_counts(&storage.list_todos().unwrap()))
+            .unwrap()
+            .len();
+    let caches = Caches::with_capacity(entry_bytes * 2);
+    let mut invalidator = Invalidator { cursor: 0 };
+    invalidator.run(&storage, &caches);
+
+    let lookup = |signature: &str| {
+        caches
+            .get_or_compute(
+                &storage,
+                CacheKind::TagCounts,
+                signature.to_string(),
+                || Ok(stats::tag_counts(&storage.list_todos().unwrap())),
+            )
+            .unwrap()
+    };
+    lookup("1");
+    lookup("2");
+    lookup("1");
+    lookup("3");
+
+    let stats = caches.stats();
+    assert_eq!(stats.evictions, 1);
+    assert!(stats.bytes <= stats.capacity_bytes);
+    lookup("1");
+    assert_eq!(stats_of(&caches, CacheKind::TagCounts).hits, 2);
+    lookup("2");
+    assert_eq!(stats_of(&caches, CacheKind::TagCounts).hits, 2);
+}
+
+/// Deterministic xorshift, so a failing sequence can be replayed.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
+
+#[test]
+fn test_cached_answers_match_recomputation_across_random_writes() {
+    const TAGS: [&str; 3] = ["work", "home", "errand"];
+    let storage = Storage::open_in_memory().unwrap();
+    let caches = Caches::default();
+    let list = query::create_list(&storage, "Work", "tag:work").unwrap();
+    let mut invalidator = Invalidator { cursor: 0 };
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for step in 0..400 {
+        let id = format!("t{}", rng.below(6));
+        let current = storage.get_todo(&id).unwrap();
+        match (rng.below(6), current) {
+            (0, _) | (_, None) => {
+                let tag = TAGS[rng.below(3) as usize];
+                storage.save_todo(&todo(&id, &[tag])).unwrap();
+            }
+            (1, Some(mut todo)) => {
+                todo.completed = !todo.completed;
+                todo.updated_at = at(1 + rng.below(29) as u32);
+                storage.save_todo(&todo).unwrap();
+            }
+            (2, Some(mut todo)) => {
+                todo.tags = vec![TAGS[rng.below(3) as usize].to_string()];
+                storage.save_todo(&todo).unwrap();
+            }
+            (3, Some(mut todo)) => {
+                todo.estimate_minutes = Some(rng.below(90) as u32);
+                todo.actual_minutes = Some(rng.below(90) as u32);
+                storage.save_todo(&todo).unwrap();
+            }
+            (4, Some(mut todo)) => {
+                todo.rank = Some(format!("{}", rng.below(1000)));
+                storage.save_todo(&todo).unwrap();
+            }
+            (_, Some(todo)) => {
+                storage
+                    .conn()
+                    .execute("DELETE FROM todos WHERE id = ?1", [&todo.id])
+                    .unwrap();
+            }
+        }
+        if rng.below(2) == 0 {
+            invalidator.run(&storage, &caches);
+        }
+
+        // The second round is answered from the cache where it may be.
+        let todos = storage.list_todos().unwrap();
+        for _ in 0..2 {
+            assert_eq!(
+                effort(&storage, &caches),
+                stats::effort_report(&todos, &june()),
+                "step {}",
+                step
+            );
+            assert_eq!(
+                tags(&storage, &caches),
+                stats::tag_counts(&todos),
+                "step {}",
+                step
+            );
+            assert_eq!(
+                smart_list(&storage, &caches, &list.id),
+                query::smart_list_todos(&storage, &list.id, today()).unwrap(),
+                "step {}",
+                step
+            );
+        }
+    }
+    let stats = caches.stats();
+    assert!(stats.kinds.iter().all(|kind| kind.hits > 0));
+}
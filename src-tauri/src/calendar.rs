@@ -0,0 +1,653 @@
+//! One-way sync of scheduled todos into the OS calendar: Apple Calendar
+//! through EventKit on macOS, the appointment store (Outlook, Calendar) on
+//! Windows. Events live in a dedicated calendar and carry the todo id in
+//! their URL, so they are never confused with the user's own events.
+//!
+//! Once enabled, every change to the todo cache schedules a sync, and changes
+//! within [`BATCH_DELAY`] of each other go out together. Each event is saved
+//! on its own: one that fails is reported and retried on the next sync while
+//! the rest go through. Deleting the dedicated calendar in the calendar app
+//! turns the sync off instead of recreating it behind the user's back.
+//!
+//! Other platforms report the `systemCalendar` capability as unsupported;
+//! `export_calendar_ics` writes the same events to an `.ics` file instead.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, OptionalExtension};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::capabilities::{self, Capability};
+use crate::file_access::{self, PathError};
+use crate::privacy::{PrivacyState, Redacted};
+use crate::protocol::EventPayload;
+use crate::storage::Storage;
+use crate::types::Todo;
+use crate::{events, platform, query, retry, trace};
+
+#[cfg(test)]
+mod tests;
+
+pub const DEFAULT_CALENDAR_NAME: &str = "YuToDo";
+/// Events point back at their todo with `yutodo://todo/<id>`.
+pub const EVENT_URL_PREFIX: &str = "yutodo://todo/";
+/// How long a sync waits for more changes before it runs.
+pub const BATCH_DELAY: Duration = Duration::from_secs(2);
+/// Prefixed to the title of completed todos under [`CompletedEvents::Mark`].
+pub const COMPLETED_MARK: &str = "✓ ";
+
+fn default_calendar_name() -> String {
+    DEFAULT_CALENDAR_NAME.to_string()
+}
+
+fn default_duration_minutes() -> u32 {
+    30
+}
+
+/// What happens to the event of a todo that gets completed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum CompletedEvents {
+    #[default]
+    Remove,
+    /// Keep the event, with [`COMPLETED_MARK`] before its title.
+    Mark,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarConfig {
+    pub enabled: bool,
+    /// Name of the dedicated calendar, created on the first sync.
+    #[serde(default = "default_calendar_name")]
+    pub calendar_name: String,
+    /// Only todos matching this query; every scheduled todo when unset.
+    #[serde(default)]
+    pub query: Option<String>,
+    #[serde(default = "default_duration_minutes")]
+    pub duration_minutes: u32,
+    #[serde(default)]
+    pub on_complete: CompletedEvents,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            calendar_name: default_calendar_name(),
+            query: None,
+            duration_minutes: default_duration_minutes(),
+            on_complete: CompletedEvents::default(),
+        }
+    }
+}
+
+/// Whether the app may write to the calendar, as the OS reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(not(any(target_os = "macos", windows)), allow(dead_code))]
+pub enum Authorization {
+    /// Never asked; the first sync shows the OS prompt.
+    NotDetermined,
+    Denied,
+    /// Blocked by a policy the user can't change, such as parental controls.
+    Restricted,
+    /// Can add events but not read calendars, so the dedicated calendar
+    /// can't be found or created (macOS "add events only" access).
+    WriteOnly,
+    Authorized,
+}
+
+/// An event as the calendar should show it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarEvent {
+    pub todo_id: String,
+    pub title: String,
+    pub notes: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl CalendarEvent {
+    pub fn url(&self) -> String {
+        format!("{}{}", EVENT_URL_PREFIX, self.todo_id)
+    }
+
+    /// Changes whenever anything the calendar shows changes.
+    fn fingerprint(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        format!("{:x}", Sha256::digest(json))
+    }
+}
+
+/// The calendar of the OS; see `platform::system_calendar`.
+pub trait SystemCalendar: Send + Sync {
+    fn authorization(&self) -> Authorization;
+    /// Shows the OS permission prompt if the user was never asked.
+    fn request_access(&self) -> Result<Authorization, String>;
+    /// Whether the calendar with this id still exists.
+    fn calendar_exists(&self, calendar_id: &str) -> Result<bool, String>;
+    /// Id of the calendar named `name`, creating it if there is none.
+    fn ensure_calendar(&self, name: &str) -> Result<String, String>;
+    /// Updates the event `event_id`, or creates one if it is `None` or was
+    /// deleted, and returns the id of the saved event.
+    fn save_event(
+        &self,
+        calendar_id: &str,
+        event_id: Option<&str>,
+        event: &CalendarEvent,
+    ) -> Result<String, String>;
+    /// Removing an event that is already gone succeeds.
+    fn remove_event(&self, calendar_id: &str, event_id: &str) -> Result<(), String>;
+}
+
+/// The events `todos` should have in the calendar, by todo id.
+pub fn desired_events(
+    todos: Vec<Todo>,
+    config: &CalendarConfig,
+    today: NaiveDate,
+) -> Result<Vec<CalendarEvent>, String> {
+    let todos = match config.query.as_deref().filter(|q| !q.trim().is_empty()) {
+        Some(q) => {
+            query::filter_todos(q, todos, today).map_err(|e| format!("Syntax error: {}", e))?
+        }
+        None => todos,
+    };
+    let duration = chrono::Duration::minutes(config.duration_minutes.max(1) as i64);
+    Ok(todos
+        .into_iter()
+        .filter(|todo| !todo.completed || config.on_complete == CompletedEvents::Mark)
+        .filter_map(|todo| {
+            let start = todo.scheduled_for?;
+            let title = if todo.completed {
+                format!("{}{}", COMPLETED_MARK, todo.title)
+            } else {
+                todo.title
+            };
+            Some(CalendarEvent {
+                todo_id: todo.id,
+                title,
+                notes: todo.description.filter(|d| !d.is_empty()),
+                start,
+                end: start + duration,
+            })
+        })
+        .collect())
+}
+
+/// An event the calendar got from an earlier sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncedEvent {
+    pub event_id: String,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalendarChange {
+    Save {
+        event: CalendarEvent,
+        event_id: Option<String>,
+    },
+    Remove {
+        todo_id: String,
+        event_id: String,
+    },
+}
+
+/// What turns the calendar from `synced` into `desired`. Unchanged events
+/// are left alone; todos that lost their date or were completed or deleted
+/// have their events removed.
+pub fn plan(
+    desired: Vec<CalendarEvent>,
+    synced: &HashMap<String, SyncedEvent>,
+) -> Vec<CalendarChange> {
+    let mut changes = Vec::new();
+    let mut wanted = std::collections::HashSet::new();
+    for event in desired {
+        wanted.insert(event.todo_id.clone());
+        let previous = synced.get(&event.todo_id);
+        if previous.is_some_and(|p| p.fingerprint == event.fingerprint()) {
+            continue;
+        }
+        changes.push(CalendarChange::Save {
+            event_id: previous.map(|p| p.event_id.clone()),
+            event,
+        });
+    }
+    let mut removed: Vec<_> = synced
+        .iter()
+        .filter(|(todo_id, _)| !wanted.contains(*todo_id))
+        .collect();
+    removed.sort_by_key(|(todo_id, _)| *todo_id);
+    changes.extend(
+        removed
+            .into_iter()
+            .map(|(todo_id, synced)| CalendarChange::Remove {
+                todo_id: todo_id.clone(),
+                event_id: synced.event_id.clone(),
+            }),
+    );
+    changes
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedEvent {
+    pub todo_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarSyncReport {
+    pub saved: usize,
+    pub removed: usize,
+    /// Retried on the next sync.
+    pub failed: Vec<FailedEvent>,
+    /// The dedicated calendar was deleted, so the sync was turned off.
+    pub calendar_deleted: bool,
+}
+
+impl EventPayload for CalendarSyncReport {}
+
+pub fn load_config(storage: &Storage) -> Result<(CalendarConfig, Option<String>), String> {
+    let row: Option<(String, Option<String>)> = storage
+        .conn()
+        .query_row(
+            "SELECT config, calendar_id FROM calendar_sync WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to load calendar sync: {}", e))?;
+    match row {
+        Some((json, calendar_id)) => Ok((
+            serde_json::from_str(&json)
+                .map_err(|e| format!("Invalid calendar sync config: {}", e))?,
+            calendar_id,
+        )),
+        None => Ok((CalendarConfig::default(), None)),
+    }
+}
+
+fn save_config(
+    storage: &Storage,
+    config: &CalendarConfig,
+    calendar_id: Option<&str>,
+) -> Result<(), String> {
+    let json = serde_json::to_string(config).map_err(|e| e.to_string())?;
+    storage
+        .conn()
+        .execute(
+            "INSERT INTO calendar_sync (id, config, calendar_id) VALUES (1, ?1, ?2)
+             ON CONFLICT(id) DO UPDATE SET config = ?1, calendar_id = ?2",
+            params![json, calendar_id],
+        )
+        .map_err(|e| format!("Failed to save calendar sync: {}", e))?;
+    Ok(())
+}
+
+fn synced_events(storage: &Storage) -> Result<HashMap<String, SyncedEvent>, String> {
+    let conn = storage.conn();
+    let mut stmt = conn
+        .prepare("SELECT todo_id, event_id, fingerprint FROM calendar_events")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                SyncedEvent {
+                    event_id: row.get(1)?,
+                    fingerprint: row.get(2)?,
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<_>>()
+        .map_err(|e| format!("Failed to load calendar events: {}", e))
+}
+
+fn forget_events(storage: &Storage) -> Result<(), String> {
+    storage
+        .conn()
+        .execute("DELETE FROM calendar_events", [])
+        .map_err(|e| format!("Failed to clear calendar events: {}", e))?;
+    Ok(())
+}
+
+/// Brings the calendar up to date with the cache. Never prompts; callers
+/// that may show the OS prompt call [`SystemCalendar::request_access`]
+/// first.
+pub fn sync(
+    storage: &Storage,
+    calendar: &dyn SystemCalendar,
+) -> Result<CalendarSyncReport, String> {
+    let (mut config, calendar_id) = load_config(storage)?;
+    if !config.enabled {
+        return Ok(CalendarSyncReport::default());
+    }
+    check_access(calendar.authorization())?;
+    if let Some(id) = &calendar_id {
+        if !calendar.calendar_exists(id)? {
+            config.enabled = false;
+            save_config(storage, &config, None)?;
+            forget_events(storage)?;
+            return Ok(CalendarSyncReport {
+                calendar_deleted: true,
+                ..Default::default()
+            });
+        }
+    }
+    let calendar_id = calendar.ensure_calendar(&config.calendar_name)?;
+    save_config(storage, &config, Some(&calendar_id))?;
+
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    let desired = desired_events(todos, &config, storage.clock().today())?;
+    let mut report = CalendarSyncReport::default();
+    for change in plan(desired, &synced_events(storage)?) {
+        let (todo_id, result) = match change {
+            CalendarChange::Save { event, event_id } => {
+                let saved = calendar
+                    .save_event(&calendar_id, event_id.as_deref(), &event)
+                    .and_then(|event_id| {
+                        storage
+                            .conn()
+                            .execute(
+                                "INSERT OR REPLACE INTO calendar_events
+                                 (todo_id, event_id, fingerprint) VALUES (?1, ?2, ?3)",
+                                params![event.todo_id, event_id, event.fingerprint()],
+                            )
+                            .map_err(|e| e.to_string())
+                    });
+                report.saved += saved.is_ok() as usize;
+                (event.todo_id, saved.map(|_| ()))
+            }
+            CalendarChange::Remove { todo_id, event_id } => {
+                let removed = calendar
+                    .remove_event(&calendar_id, &event_id)
+                    .and_then(|()| {
+                        storage
+                            .conn()
+                            .execute("DELETE FROM calendar_events WHERE todo_id = ?1", [&todo_id])
+                            .map_err(|e| e.to_string())
+                    });
+                report.removed += removed.is_ok() as usize;
+                (todo_id, removed.map(|_| ()))
+            }
+        };
+        if let Err(error) = result {
+            report.failed.push(FailedEvent { todo_id, error });
+        }
+    }
+    Ok(report)
+}
+
+/// Ok when the app may sync, otherwise what the user can do about it.
+fn check_access(authorization: Authorization) -> Result<(), String> {
+    Err(match authorization {
+        Authorization::Authorized => return Ok(()),
+        Authorization::NotDetermined => "Calendar access hasn't been granted yet".to_string(),
+        Authorization::Denied => {
+            "Calendar access was denied; allow YuToDo in the system privacy settings".to_string()
+        }
+        Authorization::Restricted => "Calendar access is restricted on this device".to_string(),
+        Authorization::WriteOnly => "YuToDo can only add events; give it full calendar \
+                                     access in the system privacy settings"
+            .to_string(),
+    })
+}
+
+pub fn capability(authorization: Authorization) -> Capability {
+    match (authorization, check_access(authorization)) {
+        (_, Ok(())) => Capability::Available,
+        (Authorization::NotDetermined, _) => Capability::Degraded {
+            detail: "Not authorized yet; turning on the sync asks for access".to_string(),
+        },
+        (Authorization::Restricted, Err(reason)) => Capability::Unsupported { reason },
+        (_, Err(detail)) => Capability::Degraded { detail },
+    }
+}
+
+/// Batches change notifications into background syncs.
+#[derive(Default)]
+pub struct CalendarSyncState {
+    /// Mirrors the stored config, so cache changes don't query it.
+    enabled: AtomicBool,
+    /// Whether a sync is due, and whether the worker thread is running.
+    pending: Mutex<(bool, bool)>,
+}
+
+impl CalendarSyncState {
+    fn pending(&self) -> std::sync::MutexGuard<'_, (bool, bool)> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+}
+
+/// Queues a sync after the todo cache changed. Cheap when the sync is off.
+pub fn schedule(app: &AppHandle) {
+    let Some(state) = app.try_state::<CalendarSyncState>() else {
+        return;
+    };
+    if !state.enabled.load(Ordering::SeqCst) {
+        return;
+    }
+    let mut pending = state.pending();
+    pending.0 = true;
+    if !pending.1 {
+        pending.1 = true;
+        let app = app.clone();
+        std::thread::spawn(trace::bind(move || run_worker(&app)));
+    }
+}
+
+/// Runs queued syncs until none is left, then exits.
+fn run_worker(app: &AppHandle) {
+    let state = app.state::<CalendarSyncState>();
+    loop {
+        // Real time, not the app's clock: this only gathers a burst of
+        // changes into one sync.
+        std::thread::sleep(BATCH_DELAY);
+        {
+            let mut pending = state.pending();
+            if !pending.0 {
+                pending.1 = false;
+                return;
+            }
+            pending.0 = false;
+        }
+        run_sync(app);
+    }
+}
+
+fn run_sync(app: &AppHandle) {
+    let calendar = match platform::system_calendar() {
+        Ok(calendar) => calendar,
+        Err(e) => return trace::log(e),
+    };
+    match sync(&app.state::<Storage>(), calendar.as_ref()) {
+        Ok(report) => {
+            if report.calendar_deleted {
+                app.state::<CalendarSyncState>().set_enabled(false);
+                capabilities::reprobe(app);
+            }
+            let _ = events::emit(app, "calendar-synced", report);
+        }
+        Err(e) => {
+            trace::log(format!("Calendar sync failed: {}", e));
+            capabilities::reprobe(app);
+        }
+    }
+}
+
+pub fn register_capabilities(registry: &capabilities::CapabilityRegistry) {
+    registry.register("systemCalendar", || match platform::system_calendar() {
+        Ok(calendar) => capability(calendar.authorization()),
+        Err(reason) => Capability::Unsupported { reason },
+    });
+}
+
+/// Escapes a text value for an iCalendar property.
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line to at most 75 bytes, as RFC 5545 requires.
+fn ics_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+pub fn write_ics(
+    events: &[CalendarEvent],
+    now: DateTime<Utc>,
+    mut writer: impl Write,
+) -> Result<(), String> {
+    let mut out = String::new();
+    ics_line(&mut out, "BEGIN:VCALENDAR");
+    ics_line(&mut out, "VERSION:2.0");
+    ics_line(&mut out, "PRODID:-//YuToDo//YuToDo//EN");
+    for event in events {
+        ics_line(&mut out, "BEGIN:VEVENT");
+        ics_line(&mut out, &format!("UID:{}@yutodo", event.todo_id));
+        ics_line(&mut out, &format!("DTSTAMP:{}", ics_time(now)));
+        ics_line(&mut out, &format!("DTSTART:{}", ics_time(event.start)));
+        ics_line(&mut out, &format!("DTEND:{}", ics_time(event.end)));
+        ics_line(&mut out, &format!("SUMMARY:{}", ics_text(&event.title)));
+        if let Some(notes) = &event.notes {
+            ics_line(&mut out, &format!("DESCRIPTION:{}", ics_text(notes)));
+        }
+        ics_line(&mut out, &format!("URL:{}", event.url()));
+        ics_line(&mut out, "END:VEVENT");
+    }
+    ics_line(&mut out, "END:VCALENDAR");
+    writer
+        .write_all(out.as_bytes())
+        .map_err(|e| format!("Failed to write calendar: {}", e))
+}
+
+/// What `sync_to_system_calendar` does, on the calling thread.
+pub fn configure(app: &AppHandle, config: CalendarConfig) -> Result<CalendarSyncReport, String> {
+    let storage = app.state::<Storage>();
+    let state = app.state::<CalendarSyncState>();
+    let (_, calendar_id) = load_config(&storage)?;
+    if !config.enabled {
+        state.set_enabled(false);
+        save_config(&storage, &config, calendar_id.as_deref())?;
+        return Ok(CalendarSyncReport::default());
+    }
+    if config.calendar_name.trim().is_empty() {
+        return Err("Calendar name must not be empty".to_string());
+    }
+    if let Some(q) = config.query.as_deref().filter(|q| !q.trim().is_empty()) {
+        query::parse_query(q).map_err(|e| format!("Syntax error: {}", e))?;
+    }
+    let calendar = platform::system_calendar()?;
+    let authorization = match calendar.authorization() {
+        Authorization::NotDetermined => calendar.request_access()?,
+        other => other,
+    };
+    capabilities::reprobe(app);
+    check_access(authorization)?;
+    save_config(&storage, &config, calendar_id.as_deref())?;
+    let report = sync(&storage, calendar.as_ref())?;
+    state.set_enabled(!report.calendar_deleted);
+    Ok(report)
+}
+
+/// Saves `config` and, when it enables the sync, asks for calendar access if
+/// needed and syncs right away. Platforms without a system calendar fail
+/// with a hint to export an ICS file instead.
+#[tauri::command]
+pub async fn sync_to_system_calendar(
+    config: CalendarConfig,
+    app: AppHandle,
+) -> Result<CalendarSyncReport, String> {
+    // Off the main thread: the permission prompt waits for the user.
+    tauri::async_runtime::spawn_blocking(trace::bind(move || {
+        configure(&app, config.clone()).inspect_err(|e| {
+            retry::record(&app, retry::Operation::SyncToSystemCalendar { config }, e)
+        })
+    }))
+    .await
+    .map_err(|e| format!("Failed to sync the calendar: {}", e))?
+}
+
+#[tauri::command]
+pub fn get_calendar_sync(storage: State<'_, Storage>) -> Result<CalendarConfig, String> {
+    Ok(load_config(&storage)?.0)
+}
+
+/// Writes the events the calendar sync would create to an `.ics` file and
+/// returns how many there were. Privacy mode applies as for other exports.
+#[tauri::command]
+pub fn export_calendar_ics(
+    path: String,
+    config: Option<CalendarConfig>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<Redacted<usize>, PathError> {
+    let path = file_access::check(&app, &path)?;
+    let config = match config {
+        Some(config) => config,
+        None => load_config(&storage)?.0,
+    };
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    let todos = privacy.get().apply(todos);
+    let events = desired_events(todos.value, &config, storage.clock().today())?;
+    let file = std::fs::File::create(&path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    write_ics(
+        &events,
+        storage.clock().now(),
+        std::io::BufWriter::new(file),
+    )?;
+    Ok(Redacted {
+        value: events.len(),
+        warning: todos.warning,
+    })
+}
+
+/// Picks up a sync left enabled by the last run. Requires `Storage`.
+pub fn resume_at_startup(app: &AppHandle) {
+    match load_config(&app.state::<Storage>()) {
+        Ok((config, _)) => {
+            app.state::<CalendarSyncState>().set_enabled(config.enabled);
+            schedule(app);
+        }
+        Err(e) => trace::log(e),
+    }
+}
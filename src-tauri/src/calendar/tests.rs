@@ -0,0 +1,230 @@
+use super::*;
+
+use chrono::TimeZone;
+
+fn todo(id: &str, title: &str, scheduled: Option<DateTime<Utc>>) -> Todo {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo {
+        scheduled_for: scheduled,
+        ..Todo::sample(id, title, created)
+    }
+}
+
+fn at(hour: u32) -> Option<DateTime<Utc>> {
+    Some(Utc.with_ymd_and_hms(2024, 6, 3, hour, 0, 0).unwrap())
+}
+
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 2).unwrap()
+}
+
+fn enabled() -> CalendarConfig {
+    CalendarConfig {
+        enabled: true,
+        ..Default::default()
+    }
+}
+
+/// Keeps events in memory and fails to save the titles in `failing`.
+#[derive(Default)]
+struct FakeCalendar {
+    calendars: Mutex<HashMap<String, String>>,
+    events: Mutex<HashMap<String, CalendarEvent>>,
+    failing: Mutex<Vec<String>>,
+    next_id: Mutex<u32>,
+}
+
+impl FakeCalendar {
+    fn id(&self) -> String {
+        let mut next = self.next_id.lock().unwrap();
+        *next += 1;
+        format!("id-{}", next)
+    }
+
+    fn titles(&self) -> Vec<String> {
+        let mut titles: Vec<_> = self
+            .events
+            .lock()
+            .unwrap()
+            .values()
+            .map(|e| e.title.clone())
+            .collect();
+        titles.sort();
+        titles
+    }
+}
+
+impl SystemCalendar for FakeCalendar {
+    fn authorization(&self) -> Authorization {
+        Authorization::Authorized
+    }
+
+    fn request_access(&self) -> Result<Authorization, String> {
+        Ok(Authorization::Authorized)
+    }
+
+    fn calendar_exists(&self, calendar_id: &str) -> Result<bool, String> {
+        Ok(self.calendars.lock().unwrap().contains_key(calendar_id))
+    }
+
+    fn ensure_calendar(&self, name: &str) -> Result<String, String> {
+        let mut calendars = self.calendars.lock().unwrap();
+        if let Some((id, _)) = calendars.iter().find(|(_, n)| *n == name) {
+            return Ok(id.clone());
+        }
+        let id = self.id();
+        calendars.insert(id.clone(), name.to_string());
+        Ok(id)
+    }
+
+    fn save_event(
+        &self,
+        _calendar_id: &str,
+        event_id: Option<&str>,
+        event: &CalendarEvent,
+    ) -> Result<String, String> {
+        if self.failing.lock().unwrap().contains(&event.title) {
+            return Err("Failed to save the event: busy".to_string());
+        }
+        let id = match event_id {
+            Some(id) => id.to_string(),
+            None => self.id(),
+        };
+        self.events
+            .lock()
+            .unwrap()
+            .insert(id.clone(), event.clone());
+        Ok(id)
+    }
+
+    fn remove_event(&self, _calendar_id: &str, event_id: &str) -> Result<(), String> {
+        self.events.lock().unwrap().remove(event_id);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_desired_events_skip_unscheduled_and_handle_completed() {
+    let mut done = todo("3", "Call the bank", at(11));
+    done.completed = true;
+    let todos = vec![
+        todo("1", "Dentist", at(9)),
+        todo("2", "Someday", None),
+        done,
+    ];
+
+    let events = desired_events(todos.clone(), &enabled(), today()).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].todo_id, "1");
+    assert_eq!(
+        events[0].end - events[0].start,
+        chrono::Duration::minutes(30)
+    );
+    assert_eq!(events[0].url(), "yutodo://todo/1");
+
+    let mark = CalendarConfig {
+        on_complete: CompletedEvents::Mark,
+        query: Some("title:bank".to_string()),
+        ..enabled()
+    };
+    let events = desired_events(todos, &mark, today()).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].title, "✓ Call the bank");
+}
+
+#[test]
+fn test_plan_saves_changes_and_removes_lost_events() {
+    let dentist = desired_events(vec![todo("1", "Dentist", at(9))], &enabled(), today()).unwrap();
+    let synced = HashMap::from([
+        (
+            "1".to_string(),
+            SyncedEvent {
+                event_id: "e1".to_string(),
+                fingerprint: dentist[0].fingerprint(),
+            },
+        ),
+        (
+            "2".to_string(),
+            SyncedEvent {
+                event_id: "e2".to_string(),
+                fingerprint: "old".to_string(),
+            },
+        ),
+    ]);
+    assert_eq!(
+        plan(dentist, &synced),
+        vec![CalendarChange::Remove {
+            todo_id: "2".to_string(),
+            event_id: "e2".to_string(),
+        }]
+    );
+
+    let moved = desired_events(vec![todo("1", "Dentist", at(10))], &enabled(), today()).unwrap();
+    match &plan(moved, &synced)[0] {
+        CalendarChange::Save { event_id, .. } => assert_eq!(event_id.as_deref(), Some("e1")),
+        other => panic!("unexpected change {:?}", other),
+    }
+}
+
+#[test]
+fn test_sync_retries_failed_events_and_stops_when_calendar_is_deleted() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage
+        .replace_todos(&[todo("1", "Dentist", at(9)), todo("2", "Haircut", at(14))])
+        .unwrap();
+    save_config(&storage, &enabled(), None).unwrap();
+    let calendar = FakeCalendar::default();
+    calendar.failing.lock().unwrap().push("Haircut".to_string());
+
+    let report = sync(&storage, &calendar).unwrap();
+    assert_eq!(report.saved, 1);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].todo_id, "2");
+
+    calendar.failing.lock().unwrap().clear();
+    let report = sync(&storage, &calendar).unwrap();
+    assert_eq!((report.saved, report.failed.len()), (1, 0));
+    assert_eq!(calendar.titles(), vec!["Dentist", "Haircut"]);
+
+    storage
+        .replace_todos(&[todo("1", "Dentist", None)])
+        .unwrap();
+    let report = sync(&storage, &calendar).unwrap();
+    assert_eq!((report.saved, report.removed), (0, 2));
+    assert!(calendar.titles().is_empty());
+
+    calendar.calendars.lock().unwrap().clear();
+    let report = sync(&storage, &calendar).unwrap();
+    assert!(report.calendar_deleted);
+    assert!(!load_config(&storage).unwrap().0.enabled);
+    assert!(calendar.calendars.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_write_ics_escapes_and_folds_lines() {
+    let mut dentist = todo("1", "Dentist; bring card, insurance", at(9));
+    dentist.description = Some(format!("Line one\n{}", "x".repeat(100)));
+    let events = desired_events(vec![dentist], &enabled(), today()).unwrap();
+    let mut out = Vec::new();
+    write_ics(&events, at(8).unwrap(), &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+
+    assert!(text.contains("SUMMARY:Dentist\\; bring card\\, insurance\r\n"));
+    assert!(text.contains("DTSTART:20240603T090000Z\r\n"));
+    assert!(text.contains("DESCRIPTION:Line one\\nxxx"));
+    assert!(text.lines().all(|line| line.len() <= 75));
+    assert!(text.contains("\r\n x"));
+}
+
+#[test]
+fn test_capability_explains_missing_access() {
+    assert_eq!(capability(Authorization::Authorized), Capability::Available);
+    assert!(matches!(
+        capability(Authorization::Denied),
+        Capability::Degraded { .. }
+    ));
+    assert!(matches!(
+        capability(Authorization::Restricted),
+        Capability::Unsupported { .. }
+    ));
+}
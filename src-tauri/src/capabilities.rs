@@ -0,0 +1,131 @@
+//! Which optional native features work on this machine. Modules register
+//! their own probes, so the report grows with the modules that exist.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::events;
+use crate::protocol::EventPayload;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum Capability {
+    Available,
+    Unsupported { reason: String },
+    Degraded { detail: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilityChange {
+    pub name: String,
+    pub capability: Capability,
+}
+
+impl EventPayload for CapabilityChange {}
+
+type Probe = Arc<dyn Fn() -> Capability + Send + Sync>;
+
+/// Registered probes and their most recent results.
+#[derive(Default)]
+pub struct CapabilityRegistry {
+    probes: Mutex<BTreeMap<&'static str, Probe>>,
+    results: Mutex<BTreeMap<&'static str, Capability>>,
+}
+
+impl CapabilityRegistry {
+    /// Adds (or replaces) the probe for `name`. Probes should be cheap; they
+    /// run lazily on first use and then every [`REPROBE_INTERVAL`].
+    pub fn register(
+        &self,
+        name: &'static str,
+        probe: impl Fn() -> Capability + Send + Sync + 'static,
+    ) {
+        self.probes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name, Arc::new(probe));
+        self.results
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(name);
+    }
+
+    fn probes(&self) -> Vec<(&'static str, Probe)> {
+        self.probes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(name, probe)| (*name, probe.clone()))
+            .collect()
+    }
+
+    /// Every capability, probing only those without a cached result.
+    pub fn snapshot(&self) -> BTreeMap<String, Capability> {
+        let cached = self
+            .results
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        // Probes run without holding a lock, since some talk to OS services.
+        let fresh: Vec<_> = self
+            .probes()
+            .into_iter()
+            .filter(|(name, _)| !cached.contains_key(name))
+            .map(|(name, probe)| (name, probe()))
+            .collect();
+
+        let mut results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, capability) in fresh {
+            results.entry(name).or_insert(capability);
+        }
+        results
+            .iter()
+            .map(|(name, capability)| (name.to_string(), capability.clone()))
+            .collect()
+    }
+
+    /// Probes everything again and returns the capabilities whose status
+    /// differs from the previous result.
+    pub fn refresh(&self) -> Vec<CapabilityChange> {
+        let fresh: Vec<_> = self
+            .probes()
+            .into_iter()
+            .map(|(name, probe)| (name, probe()))
+            .collect();
+
+        let mut results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        let mut changes = Vec::new();
+        for (name, capability) in fresh {
+            let previous = results.insert(name, capability.clone());
+            if previous.is_some_and(|previous| previous != capability) {
+                changes.push(CapabilityChange {
+                    name: name.to_string(),
+                    capability,
+                });
+            }
+        }
+        changes
+    }
+}
+
+/// Re-probes and emits `capability-changed` for each flip. Runs on every
+/// scheduler tick to catch runtime changes, such as a tray host or keyring
+/// daemon appearing after login.
+pub fn reprobe(app: &AppHandle) {
+    for change in app.state::<CapabilityRegistry>().refresh() {
+        let _ = events::emit(app, "capability-changed", &change);
+    }
+}
+
+#[tauri::command]
+pub fn get_capabilities(registry: State<'_, CapabilityRegistry>) -> BTreeMap<String, Capability> {
+    registry.snapshot()
+}
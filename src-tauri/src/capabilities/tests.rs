@@ -0,0 +1,71 @@
+use super::*;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+#[test]
+fn test_snapshot_probes_lazily_and_caches() {
+    let registry = CapabilityRegistry::default();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    registry.register("tray", move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Capability::Available
+    });
+    registry.register("mdns", || Capability::Unsupported {
+        reason: "no multicast interface".to_string(),
+    });
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    let snapshot = registry.snapshot();
+    assert_eq!(snapshot["tray"], Capability::Available);
+    assert_eq!(
+        snapshot["mdns"],
+        Capability::Unsupported {
+            reason: "no multicast interface".to_string()
+        }
+    );
+    registry.snapshot();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_refresh_reports_only_flips() {
+    let registry = CapabilityRegistry::default();
+    let tray_host = Arc::new(AtomicBool::new(false));
+    let probe_host = tray_host.clone();
+    registry.register("tray", move || {
+        if probe_host.load(Ordering::SeqCst) {
+            Capability::Available
+        } else {
+            Capability::Degraded {
+                detail: "no tray host yet".to_string(),
+            }
+        }
+    });
+    registry.register("idle", || Capability::Available);
+
+    registry.snapshot();
+    assert!(registry.refresh().is_empty());
+
+    tray_host.store(true, Ordering::SeqCst);
+    assert_eq!(
+        registry.refresh(),
+        vec![CapabilityChange {
+            name: "tray".to_string(),
+            capability: Capability::Available,
+        }]
+    );
+    assert!(registry.refresh().is_empty());
+}
+
+#[test]
+fn test_capability_serializes_with_status_tag() {
+    let json = serde_json::to_value(Capability::Degraded {
+        detail: "falls back to polling".to_string(),
+    })
+    .unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "status": "degraded", "detail": "falls back to polling" })
+    );
+}
@@ -0,0 +1,532 @@
+//! Renders a single todo as a PNG card for pasting into chats. Fonts are
+//! bundled so the output is identical on every platform.
+
+use ab_glyph::{Font, FontRef, GlyphId, PxScale, ScaleFont};
+use base64::Engine;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tauri::{AppHandle, State};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tiny_skia::{
+    Color, FillRule, LineCap, Paint, PathBuilder, Pixmap, PremultipliedColorU8, Stroke, Transform,
+};
+
+use crate::file_access::{self, PathError};
+use crate::privacy::{self, PrivacyState, Redacted};
+use crate::storage::Storage;
+use crate::textutil;
+use crate::types::{Priority, Todo};
+
+#[cfg(test)]
+mod tests;
+
+/// DejaVu Sans has no CJK glyphs; those render as its placeholder box.
+const FONT_REGULAR: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+const FONT_BOLD: &[u8] = include_bytes!("../fonts/DejaVuSans-Bold.ttf");
+
+const MIN_WIDTH: u32 = 240;
+const MAX_WIDTH: u32 = 1600;
+const MAX_SCALE: f32 = 4.0;
+const MAX_DESCRIPTION_LINES: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CardTheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CardStyle {
+    pub theme: CardTheme,
+    /// Card width in logical pixels.
+    pub width: u32,
+    /// Device pixels per logical pixel, e.g. 2 for HiDPI screens.
+    pub scale: f32,
+    pub include_description: bool,
+}
+
+impl Default for CardStyle {
+    fn default() -> Self {
+        Self {
+            theme: CardTheme::Light,
+            width: 480,
+            scale: 1.0,
+            include_description: false,
+        }
+    }
+}
+
+struct Palette {
+    background: Color,
+    border: Color,
+    text: Color,
+    muted: Color,
+    accent: Color,
+    chip_text: Color,
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::from_rgba8(r, g, b, 255)
+}
+
+fn palette(theme: CardTheme) -> Palette {
+    match theme {
+        CardTheme::Light => Palette {
+            background: rgb(255, 255, 255),
+            border: rgb(222, 226, 230),
+            text: rgb(33, 37, 41),
+            muted: rgb(108, 117, 125),
+            accent: rgb(59, 130, 246),
+            chip_text: rgb(255, 255, 255),
+        },
+        CardTheme::Dark => Palette {
+            background: rgb(30, 32, 36),
+            border: rgb(58, 62, 70),
+            text: rgb(236, 238, 240),
+            muted: rgb(156, 163, 175),
+            accent: rgb(96, 165, 250),
+            chip_text: rgb(17, 17, 17),
+        },
+    }
+}
+
+fn priority_color(priority: Priority) -> Color {
+    match priority {
+        Priority::High => rgb(229, 72, 77),
+        Priority::Medium => rgb(245, 165, 36),
+        Priority::Low => rgb(70, 167, 88),
+    }
+}
+
+/// Tags have no stored color, so each gets a stable one from its name.
+fn tag_color(tag: &str) -> Color {
+    const COLORS: [(u8, u8, u8); 8] = [
+        (99, 102, 241),
+        (236, 72, 153),
+        (20, 184, 166),
+        (234, 88, 12),
+        (132, 204, 22),
+        (14, 165, 233),
+        (168, 85, 247),
+        (202, 138, 4),
+    ];
+    let hash = tag
+        .to_lowercase()
+        .bytes()
+        .fold(2166136261u32, |h, b| (h ^ b as u32).wrapping_mul(16777619));
+    let (r, g, b) = COLORS[hash as usize % COLORS.len()];
+    rgb(r, g, b)
+}
+
+/// A font at a pixel size.
+struct Face<'a> {
+    font: &'a FontRef<'static>,
+    px: f32,
+}
+
+impl Face<'_> {
+    /// Glyphs with kerning, as `(glyph, x offset)`.
+    fn layout(&self, text: &str) -> (Vec<(GlyphId, f32)>, f32) {
+        let scaled = self.font.as_scaled(PxScale::from(self.px));
+        let mut caret = 0.0;
+        let mut previous = None;
+        let mut glyphs = Vec::new();
+        for c in text.chars().filter(|c| !c.is_control()) {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            glyphs.push((id, caret));
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+        }
+        (glyphs, caret)
+    }
+
+    fn measure(&self, text: &str) -> f32 {
+        self.layout(text).1
+    }
+
+    fn line_height(&self) -> f32 {
+        self.px * 1.35
+    }
+
+    fn wrap(&self, text: &str, width: f32) -> Vec<String> {
+        textutil::wrap(text, width, |s| self.measure(s))
+    }
+
+    fn draw(&self, pixmap: &mut Pixmap, x: f32, baseline: f32, text: &str, color: Color) {
+        for (id, offset) in self.layout(text).0 {
+            let glyph = id.with_scale_and_position(self.px, ab_glyph::point(x + offset, baseline));
+            let Some(outlined) = self.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                blend(
+                    pixmap,
+                    bounds.min.x as i32 + gx as i32,
+                    bounds.min.y as i32 + gy as i32,
+                    color,
+                    coverage,
+                );
+            });
+        }
+    }
+}
+
+fn blend(pixmap: &mut Pixmap, x: i32, y: i32, color: Color, coverage: f32) {
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    if x < 0 || y < 0 || x >= width || y >= height {
+        return;
+    }
+    let pixel = &mut pixmap.pixels_mut()[(y * width + x) as usize];
+    let alpha = color.alpha() * coverage.clamp(0.0, 1.0);
+    let mix = |source: f32, dest: u8| source * 255.0 * alpha + dest as f32 * (1.0 - alpha);
+    let a = mix(1.0, pixel.alpha()).round().min(255.0) as u8;
+    let channel = |source: f32, dest: u8| mix(source, dest).round().min(a as f32) as u8;
+    if let Some(blended) = PremultipliedColorU8::from_rgba(
+        channel(color.red(), pixel.red()),
+        channel(color.green(), pixel.green()),
+        channel(color.blue(), pixel.blue()),
+        a,
+    ) {
+        *pixel = blended;
+    }
+}
+
+fn rounded_rect(x: f32, y: f32, w: f32, h: f32, r: f32) -> Option<tiny_skia::Path> {
+    let r = r.min(w / 2.0).min(h / 2.0);
+    let mut pb = PathBuilder::new();
+    pb.move_to(x + r, y);
+    pb.line_to(x + w - r, y);
+    pb.quad_to(x + w, y, x + w, y + r);
+    pb.line_to(x + w, y + h - r);
+    pb.quad_to(x + w, y + h, x + w - r, y + h);
+    pb.line_to(x + r, y + h);
+    pb.quad_to(x, y + h, x, y + h - r);
+    pb.line_to(x, y + r);
+    pb.quad_to(x, y, x + r, y);
+    pb.close();
+    pb.finish()
+}
+
+fn paint(color: Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    paint
+}
+
+fn fill(pixmap: &mut Pixmap, path: Option<tiny_skia::Path>, color: Color) {
+    if let Some(path) = path {
+        pixmap.fill_path(
+            &path,
+            &paint(color),
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+    }
+}
+
+fn stroke(pixmap: &mut Pixmap, path: Option<tiny_skia::Path>, color: Color, width: f32) {
+    if let Some(path) = path {
+        let stroke = Stroke {
+            width,
+            line_cap: LineCap::Round,
+            ..Stroke::default()
+        };
+        pixmap.stroke_path(&path, &paint(color), &stroke, Transform::identity(), None);
+    }
+}
+
+/// A pill-shaped label, laid out left to right and wrapped like text.
+struct Chip {
+    label: String,
+    color: Color,
+}
+
+/// Positions chips in rows; returns `(x, y, width)` per chip and the height.
+fn layout_chips(
+    chips: &[Chip],
+    face: &Face<'_>,
+    max_width: f32,
+    u: f32,
+) -> (Vec<(f32, f32, f32)>, f32) {
+    let (height, gap, padding) = (22.0 * u, 6.0 * u, 8.0 * u);
+    let mut positions = Vec::new();
+    let (mut x, mut y) = (0.0, 0.0);
+    for chip in chips {
+        let width = (face.measure(&chip.label) + 2.0 * padding).min(max_width);
+        if x > 0.0 && x + width > max_width {
+            x = 0.0;
+            y += height + gap;
+        }
+        positions.push((x, y, width));
+        x += width + gap;
+    }
+    let total = if chips.is_empty() { 0.0 } else { y + height };
+    (positions, total)
+}
+
+fn draw_chips(
+    pixmap: &mut Pixmap,
+    chips: &[Chip],
+    face: &Face<'_>,
+    origin: (f32, f32),
+    max_width: f32,
+    text_color: Color,
+    u: f32,
+) -> f32 {
+    let (positions, height) = layout_chips(chips, face, max_width, u);
+    for (chip, (x, y, width)) in chips.iter().zip(positions) {
+        let (x, y) = (origin.0 + x, origin.1 + y);
+        fill(
+            pixmap,
+            rounded_rect(x, y, width, 22.0 * u, 11.0 * u),
+            chip.color,
+        );
+        face.draw(pixmap, x + 8.0 * u, y + 15.5 * u, &chip.label, text_color);
+    }
+    height
+}
+
+/// Cuts `lines` to `max` lines, ending the last with an ellipsis.
+fn truncate_lines(mut lines: Vec<String>, max: usize, face: &Face<'_>, width: f32) -> Vec<String> {
+    if lines.len() <= max {
+        return lines;
+    }
+    lines.truncate(max);
+    if let Some(last) = lines.last_mut() {
+        while !last.is_empty() && face.measure(&format!("{}…", last)) > width {
+            last.pop();
+        }
+        last.push('…');
+    }
+    lines
+}
+
+pub fn render_card(todo: &Todo, style: &CardStyle) -> Result<Pixmap, String> {
+    let regular = FontRef::try_from_slice(FONT_REGULAR).map_err(|e| e.to_string())?;
+    let bold = FontRef::try_from_slice(FONT_BOLD).map_err(|e| e.to_string())?;
+    let u = if style.scale.is_finite() {
+        style.scale.clamp(0.5, MAX_SCALE)
+    } else {
+        1.0
+    };
+    let colors = palette(style.theme);
+    let width = (style.width.clamp(MIN_WIDTH, MAX_WIDTH) as f32 * u).round();
+    let pad = 24.0 * u;
+    let inner = width - 2.0 * pad;
+
+    let title_face = Face {
+        font: &bold,
+        px: 20.0 * u,
+    };
+    let chip_face = Face {
+        font: &regular,
+        px: 12.0 * u,
+    };
+    let meta_face = Face {
+        font: &regular,
+        px: 13.0 * u,
+    };
+    let body_face = Face {
+        font: &regular,
+        px: 14.0 * u,
+    };
+
+    let check_size = 30.0 * u;
+    let title_lines = title_face.wrap(&todo.title, inner - check_size);
+    let priority_chip = [Chip {
+        label: todo.priority.as_str().to_string(),
+        color: priority_color(todo.priority),
+    }];
+    let tag_chips: Vec<Chip> = todo
+        .tags
+        .iter()
+        .map(|tag| Chip {
+            label: format!("#{}", tag),
+            color: tag_color(tag),
+        })
+        .collect();
+    let description = todo
+        .description
+        .as_deref()
+        .filter(|d| style.include_description && !d.trim().is_empty())
+        .map(|d| {
+            truncate_lines(
+                body_face.wrap(d.trim(), inner),
+                MAX_DESCRIPTION_LINES,
+                &body_face,
+                inner,
+            )
+        })
+        .unwrap_or_default();
+
+    let title_height = title_lines.len() as f32 * title_face.line_height();
+    let meta_height = 22.0 * u;
+    let tags_height = layout_chips(&tag_chips, &chip_face, inner, u).1;
+    let description_height = description.len() as f32 * body_face.line_height();
+    let gap = 12.0 * u;
+    let mut height = pad + title_height + gap + meta_height;
+    if tags_height > 0.0 {
+        height += gap + tags_height;
+    }
+    if description_height > 0.0 {
+        height += gap + description_height;
+    }
+    height += pad;
+
+    let mut pixmap = Pixmap::new(width as u32, height.ceil() as u32)
+        .ok_or_else(|| format!("Card of {}x{} pixels is too large", width, height))?;
+    let card = rounded_rect(u, u, width - 2.0 * u, height - 2.0 * u, 12.0 * u);
+    fill(&mut pixmap, card.clone(), colors.background);
+    stroke(&mut pixmap, card, colors.border, u);
+
+    // Completion circle, with a check mark once done.
+    let (cx, cy, radius) = (pad + 9.0 * u, pad + title_face.line_height() / 2.0, 9.0 * u);
+    if todo.completed {
+        fill(
+            &mut pixmap,
+            PathBuilder::from_circle(cx, cy, radius),
+            colors.accent,
+        );
+        let mut check = PathBuilder::new();
+        check.move_to(cx - 4.0 * u, cy);
+        check.line_to(cx - 1.0 * u, cy + 3.5 * u);
+        check.line_to(cx + 4.5 * u, cy - 3.5 * u);
+        stroke(&mut pixmap, check.finish(), colors.background, 2.0 * u);
+    } else {
+        stroke(
+            &mut pixmap,
+            PathBuilder::from_circle(cx, cy, radius),
+            colors.muted,
+            1.5 * u,
+        );
+    }
+
+    let title_color = if todo.completed {
+        colors.muted
+    } else {
+        colors.text
+    };
+    let mut y = pad;
+    for line in &title_lines {
+        let baseline = y + title_face.px * 1.05;
+        title_face.draw(&mut pixmap, pad + check_size, baseline, line, title_color);
+        if todo.completed && !line.is_empty() {
+            let strike_y = baseline - title_face.px * 0.3;
+            let mut strike = PathBuilder::new();
+            strike.move_to(pad + check_size, strike_y);
+            strike.line_to(pad + check_size + title_face.measure(line), strike_y);
+            stroke(&mut pixmap, strike.finish(), colors.muted, 1.5 * u);
+        }
+        y += title_face.line_height();
+    }
+
+    y += gap;
+    draw_chips(
+        &mut pixmap,
+        &priority_chip,
+        &chip_face,
+        (pad, y),
+        inner,
+        colors.chip_text,
+        u,
+    );
+    let priority_width = chip_face.measure(&priority_chip[0].label) + 16.0 * u;
+    if let Some(due) = todo.scheduled_for {
+        let label = format!("Due {}", due.format("%Y-%m-%d"));
+        meta_face.draw(
+            &mut pixmap,
+            pad + priority_width + 10.0 * u,
+            y + 15.5 * u,
+            &label,
+            colors.muted,
+        );
+    }
+    y += meta_height;
+
+    if tags_height > 0.0 {
+        y += gap;
+        y += draw_chips(
+            &mut pixmap,
+            &tag_chips,
+            &chip_face,
+            (pad, y),
+            inner,
+            colors.chip_text,
+            u,
+        );
+    }
+    if !description.is_empty() {
+        y += gap;
+        for line in &description {
+            body_face.draw(&mut pixmap, pad, y + body_face.px * 1.05, line, colors.text);
+            y += body_face.line_height();
+        }
+    }
+    Ok(pixmap)
+}
+
+pub fn encode_png(pixmap: &Pixmap) -> Result<Vec<u8>, String> {
+    pixmap
+        .encode_png()
+        .map_err(|e| format!("Failed to encode card: {}", e))
+}
+
+/// Renders a todo card and returns the PNG as base64. Optionally also
+/// writes it to `path` and copies it to the clipboard as an image. A todo
+/// that privacy mode hides gets a card with its title masked.
+#[tauri::command]
+pub fn render_todo_card(
+    todo_id: String,
+    style: Option<CardStyle>,
+    path: Option<String>,
+    copy_to_clipboard: Option<bool>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<Redacted<String>, PathError> {
+    let path = path
+        .map(|path| file_access::check(&app, path))
+        .transpose()?;
+    let mut todo = storage
+        .get_todo(&todo_id)
+        .map_err(|e| format!("Failed to load todo: {}", e))?
+        .ok_or_else(|| format!("Unknown todo '{}'", todo_id))?;
+    let mut warning = None;
+    if privacy.get().hides(&todo) {
+        todo.title = privacy::MASK.to_string();
+        warning = Some("Privacy mode is on: the card's title was masked".to_string());
+    }
+    let pixmap = render_card(&todo, &style.unwrap_or_default())?;
+    let png = encode_png(&pixmap)?;
+    if let Some(path) = path {
+        std::fs::write(&path, &png)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    if copy_to_clipboard.unwrap_or(false) {
+        let rgba: Vec<u8> = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let c = pixel.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect();
+        let image = tauri::image::Image::new_owned(rgba, pixmap.width(), pixmap.height());
+        app.clipboard()
+            .write_image(&image)
+            .map_err(|e| format!("Failed to copy card: {}", e))?;
+    }
+    Ok(Redacted {
+        value: base64::engine::general_purpose::STANDARD.encode(png),
+        warning,
+    })
+}
@@ -0,0 +1,97 @@
+use super::*;
+use chrono::{TimeZone, Utc};
+
+fn todo(title: &str) -> Todo {
+    let now = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo {
+        description: Some("Bring the slides and the budget sheet.".to_string()),
+        priority: Priority::High,
+        scheduled_for: Some(Utc.with_ymd_and_hms(2024, 6, 10, 0, 0, 0).unwrap()),
+        tags: vec!["work".to_string(), "会議".to_string()],
+        ..Todo::sample("1", title, now)
+    }
+}
+
+#[test]
+fn test_card_size_follows_width_and_scale() {
+    let style = CardStyle {
+        width: 400,
+        scale: 2.0,
+        ..Default::default()
+    };
+    let pixmap = render_card(&todo("Prepare the meeting"), &style).unwrap();
+
+    assert_eq!(pixmap.width(), 800);
+    let png = encode_png(&pixmap).unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+    let decoded = Pixmap::decode_png(&png).unwrap();
+    assert_eq!(decoded.height(), pixmap.height());
+}
+
+#[test]
+fn test_output_is_deterministic() {
+    let style = CardStyle::default();
+    let a = encode_png(&render_card(&todo("Ship release"), &style).unwrap()).unwrap();
+    let b = encode_png(&render_card(&todo("Ship release"), &style).unwrap()).unwrap();
+    assert_eq!(a, b);
+}
+
+#[test]
+fn test_long_cjk_title_wraps_into_a_taller_card() {
+    let style = CardStyle::default();
+    let short = render_card(&todo("会議"), &style).unwrap();
+    let long = render_card(
+        &todo(&"来週の定例会議で使う資料を準備する".repeat(4)),
+        &style,
+    )
+    .unwrap();
+
+    assert_eq!(short.width(), long.width());
+    assert!(long.height() > short.height());
+}
+
+#[test]
+fn test_description_is_optional() {
+    let without = render_card(&todo("Plan"), &CardStyle::default()).unwrap();
+    let with = render_card(
+        &todo("Plan"),
+        &CardStyle {
+            include_description: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(with.height() > without.height());
+}
+
+#[test]
+fn test_emoji_only_and_empty_titles_render() {
+    let mut done = todo("🎉🎉🎉");
+    done.completed = true;
+    assert!(render_card(&done, &CardStyle::default()).is_ok());
+    assert!(render_card(&todo(""), &CardStyle::default()).is_ok());
+}
+
+#[test]
+fn test_dark_theme_background() {
+    let style = CardStyle {
+        theme: CardTheme::Dark,
+        ..Default::default()
+    };
+    let pixmap = render_card(&todo("Plan"), &style).unwrap();
+    let center = pixmap
+        .pixel(pixmap.width() - 10, pixmap.height() / 2)
+        .unwrap()
+        .demultiply();
+    assert_eq!((center.red(), center.green(), center.blue()), (30, 32, 36));
+}
+
+#[test]
+fn test_out_of_range_style_is_clamped() {
+    let style = CardStyle {
+        width: 10,
+        scale: f32::NAN,
+        ..Default::default()
+    };
+    assert_eq!(render_card(&todo("x"), &style).unwrap().width(), MIN_WIDTH);
+}
@@ -0,0 +1,237 @@
+//! The time source behind everything that depends on the clock: scheduled
+//! publishing and polling, notification cooldowns, focus sessions, "today"
+//! in queries and the timestamps written to the store.
+//!
+//! Builds with the `simulated-clock` feature accept `--simulated-time[=<RFC
+//! 3339>]`, which swaps in a [`MockClock`] that only moves through the
+//! `advance_clock` and `set_clock` commands. Watchdogs for real processes,
+//! such as plugin timeouts, keep using the real clock.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(any(test, feature = "simulated-clock"))]
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDate, Utc};
+#[cfg(feature = "simulated-clock")]
+use tauri::{AppHandle, State};
+
+#[cfg(feature = "simulated-clock")]
+use crate::events;
+#[cfg(feature = "simulated-clock")]
+use crate::scheduler::SchedulerState;
+
+#[cfg(test)]
+mod tests;
+
+/// Command-line flag that starts the app on a [`MockClock`].
+#[cfg(feature = "simulated-clock")]
+pub const SIMULATED_TIME_FLAG: &str = "--simulated-time";
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// For intervals and deadlines; never goes backwards.
+    fn now_monotonic(&self) -> Instant;
+
+    /// The current UTC date, which relative dates in queries count from.
+    fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// A clock that stands still until it is moved.
+#[cfg(any(test, feature = "simulated-clock"))]
+pub struct MockClock {
+    time: Mutex<(DateTime<Utc>, Instant)>,
+}
+
+#[cfg(any(test, feature = "simulated-clock"))]
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            time: Mutex::new((start, Instant::now())),
+        }
+    }
+
+    /// Moves both clocks forward by `by` and returns the new time.
+    pub fn advance(&self, by: Duration) -> Result<DateTime<Utc>, String> {
+        let mut time = self.time.lock().unwrap_or_else(|e| e.into_inner());
+        let wall = chrono::Duration::from_std(by)
+            .ok()
+            .and_then(|by| time.0.checked_add_signed(by))
+            .ok_or_else(|| format!("Cannot advance the clock by {:?}", by))?;
+        *time = (wall, time.1 + by);
+        Ok(wall)
+    }
+
+    /// Jumps to `to`. Going back only moves the wall clock, since monotonic
+    /// time can't go backwards.
+    pub fn set(&self, to: DateTime<Utc>) {
+        let mut time = self.time.lock().unwrap_or_else(|e| e.into_inner());
+        let forward = (to - time.0).to_std().unwrap_or_default();
+        *time = (to, time.1 + forward);
+    }
+}
+
+#[cfg(any(test, feature = "simulated-clock"))]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.time.lock().unwrap_or_else(|e| e.into_inner()).0
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        self.time.lock().unwrap_or_else(|e| e.into_inner()).1
+    }
+}
+
+/// The app's clock, managed as state. [`Storage`](crate::storage::Storage)
+/// holds the same clock for the timestamps it writes.
+pub struct ClockState {
+    clock: SharedClock,
+    #[cfg(feature = "simulated-clock")]
+    simulated: Option<Arc<MockClock>>,
+}
+
+impl Default for ClockState {
+    fn default() -> Self {
+        Self {
+            clock: system(),
+            #[cfg(feature = "simulated-clock")]
+            simulated: None,
+        }
+    }
+}
+
+impl ClockState {
+    /// The real clock, unless the build supports simulated time and
+    /// `--simulated-time` is given: alone it starts at the current time,
+    /// `--simulated-time=2024-06-03T09:00:00Z` at the given one.
+    #[cfg_attr(not(feature = "simulated-clock"), allow(unused_variables))]
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        #[cfg(feature = "simulated-clock")]
+        for arg in args {
+            let start = if arg == SIMULATED_TIME_FLAG {
+                Utc::now()
+            } else if let Some(start) = arg
+                .strip_prefix(SIMULATED_TIME_FLAG)
+                .and_then(|rest| rest.strip_prefix('='))
+            {
+                DateTime::parse_from_rfc3339(start)
+                    .map_err(|e| format!("Invalid {} '{}': {}", SIMULATED_TIME_FLAG, start, e))?
+                    .with_timezone(&Utc)
+            } else {
+                continue;
+            };
+            return Ok(Self::simulated(Arc::new(MockClock::new(start))));
+        }
+        Ok(Self::default())
+    }
+
+    #[cfg(feature = "simulated-clock")]
+    pub fn simulated(clock: Arc<MockClock>) -> Self {
+        Self {
+            clock: clock.clone(),
+            simulated: Some(clock),
+        }
+    }
+
+    pub fn shared(&self) -> SharedClock {
+        self.clock.clone()
+    }
+
+    #[cfg(feature = "simulated-clock")]
+    fn mock(&self) -> Result<&MockClock, String> {
+        self.simulated.as_deref().ok_or_else(|| {
+            format!(
+                "Start the app with {} to move the clock",
+                SIMULATED_TIME_FLAG
+            )
+        })
+    }
+}
+
+impl Clock for ClockState {
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        self.clock.now_monotonic()
+    }
+}
+
+/// Runs `job` every `interval` of `clock` time until `stop` is set, looking
+/// at the clock and the flag every `step`. Intervals count from the start of
+/// each run, so slow runs don't push the later ones back.
+pub fn run_every(
+    clock: &dyn Clock,
+    interval: Duration,
+    stop: &AtomicBool,
+    step: Duration,
+    mut job: impl FnMut(),
+) {
+    while !stop.load(Ordering::SeqCst) {
+        let next = clock.now_monotonic() + interval;
+        job();
+        while clock.now_monotonic() < next && !stop.load(Ordering::SeqCst) {
+            std::thread::sleep(step);
+        }
+    }
+}
+
+/// Tells the app the simulated clock moved: the scheduler re-checks its
+/// deadline, and `clock-changed` lets the frontend refresh.
+#[cfg(feature = "simulated-clock")]
+fn moved(app: &AppHandle, scheduler: &SchedulerState, now: DateTime<Utc>) {
+    scheduler.nudge();
+    let _ = events::emit(app, "clock-changed", now);
+}
+
+/// Moves the simulated clock forward; only with `--simulated-time`.
+#[cfg(feature = "simulated-clock")]
+#[tauri::command]
+pub fn advance_clock(
+    seconds: u64,
+    app: AppHandle,
+    clock: State<'_, ClockState>,
+    scheduler: State<'_, SchedulerState>,
+) -> Result<DateTime<Utc>, String> {
+    let now = clock.mock()?.advance(Duration::from_secs(seconds))?;
+    moved(&app, &scheduler, now);
+    Ok(now)
+}
+
+/// Sets the simulated clock; only with `--simulated-time`.
+#[cfg(feature = "simulated-clock")]
+#[tauri::command]
+pub fn set_clock(
+    datetime: DateTime<Utc>,
+    app: AppHandle,
+    clock: State<'_, ClockState>,
+    scheduler: State<'_, SchedulerState>,
+) -> Result<DateTime<Utc>, String> {
+    clock.mock()?.set(datetime);
+    moved(&app, &scheduler, datetime);
+    Ok(datetime)
+}
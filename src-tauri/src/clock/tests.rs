@@ -0,0 +1,186 @@
+use super::*;
+
+use std::fs;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+use chrono::TimeZone;
+
+use crate::import::{self, ConflictPolicy};
+use crate::scheduler::SchedulerState;
+use crate::site::{self, SiteConfig};
+use crate::storage::Storage;
+
+type Fired = (DateTime<Utc>, &'static str, String);
+
+/// A Monday morning.
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap()
+}
+
+fn hours(hours: u64) -> Duration {
+    Duration::from_secs(hours * 60 * 60)
+}
+
+/// Runs `job` on its own thread like the app's interval loops do,
+/// reporting each run with the simulated time it started at.
+fn spawn_job(
+    name: &'static str,
+    every: Duration,
+    clock: Arc<MockClock>,
+    stop: Arc<AtomicBool>,
+    fired: mpsc::Sender<Fired>,
+    mut job: impl FnMut() -> String + Send + 'static,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        run_every(
+            clock.as_ref(),
+            every,
+            &stop,
+            Duration::from_millis(1),
+            || {
+                let at = clock.now();
+                let _ = fired.send((at, name, job()));
+            },
+        );
+    })
+}
+
+#[test]
+fn test_mock_clock_only_moves_when_told() {
+    let clock = MockClock::new(start());
+    let monotonic = clock.now_monotonic();
+    std::thread::sleep(Duration::from_millis(5));
+    assert_eq!(clock.now(), start());
+    assert_eq!(clock.now_monotonic(), monotonic);
+
+    let later = clock.advance(Duration::from_secs(90 * 60)).unwrap();
+    assert_eq!(later, start() + chrono::Duration::minutes(90));
+    assert_eq!(
+        clock.now_monotonic() - monotonic,
+        Duration::from_secs(90 * 60)
+    );
+
+    // Going back moves the wall clock only.
+    clock.set(start());
+    assert_eq!(clock.now(), start());
+    assert_eq!(
+        clock.now_monotonic() - monotonic,
+        Duration::from_secs(90 * 60)
+    );
+
+    clock.set(start() + chrono::Duration::days(1));
+    assert_eq!(clock.today(), NaiveDate::from_ymd_opt(2024, 6, 4).unwrap());
+    assert_eq!(
+        clock.now_monotonic() - monotonic,
+        Duration::from_secs(90 * 60) + hours(24)
+    );
+}
+
+#[test]
+fn test_scheduler_wakes_when_the_clock_passes_its_interval() {
+    let clock = Arc::new(MockClock::new(start()));
+    let state = Arc::new(SchedulerState::new(Some(hours(1))));
+    let waiter = {
+        let (clock, state) = (clock.clone(), state.clone());
+        std::thread::spawn(move || {
+            state.wait(clock.as_ref());
+            clock.now()
+        })
+    };
+
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(!waiter.is_finished());
+
+    for _ in 0..100 {
+        if waiter.is_finished() {
+            break;
+        }
+        clock.advance(Duration::from_secs(10 * 60)).unwrap();
+        state.nudge();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert!(waiter.join().unwrap() >= start() + chrono::Duration::hours(1));
+}
+
+/// Fast-forwards a week and a day in hourly steps: the daily site publish
+/// and the twice-daily pruning of stale imports run on time and in order,
+/// and what they write is stamped with simulated time.
+#[test]
+fn test_week_of_scheduled_jobs() {
+    let dir = tempfile::tempdir().unwrap();
+    let clock = Arc::new(MockClock::new(start()));
+    let storage = Arc::new(Storage::open_in_memory().unwrap().with_clock(clock.clone()));
+    let source = dir.path().join("todos.csv");
+    fs::write(&source, "title\nWater the plants\n").unwrap();
+    import::begin_import(&storage, &source, ConflictPolicy::Skip).unwrap();
+    let config = SiteConfig {
+        output_dir: dir.path().join("site"),
+        title: "This week".to_string(),
+        smart_lists: Vec::new(),
+        refresh_secs: 60,
+    };
+    let data_file = config.output_dir.join(site::DATA_FILE);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, fired) = mpsc::channel();
+    let jobs = [
+        spawn_job(
+            "prune",
+            hours(12),
+            clock.clone(),
+            stop.clone(),
+            sender.clone(),
+            {
+                let storage = storage.clone();
+                move || {
+                    import::prune_checkpoints(&storage, storage.clock().now())
+                        .unwrap()
+                        .to_string()
+                }
+            },
+        ),
+        spawn_job("publish", hours(24), clock.clone(), stop.clone(), sender, {
+            let (storage, data_dir) = (storage.clone(), dir.path().join("data"));
+            move || {
+                site::publish(&storage, &config, &data_dir).unwrap();
+                "published".to_string()
+            }
+        }),
+    ];
+
+    let mut log = Vec::new();
+    for hour in 0..=8 * 24 {
+        if hour > 0 {
+            clock.advance(hours(1)).unwrap();
+        }
+        let due = [12, 24].iter().filter(|every| hour % *every == 0).count();
+        let mut step: Vec<Fired> = (0..due)
+            .map(|_| fired.recv_timeout(Duration::from_secs(10)).unwrap())
+            .collect();
+        step.sort();
+        log.extend(step);
+    }
+    stop.store(true, Ordering::SeqCst);
+    for job in jobs {
+        job.join().unwrap();
+    }
+    assert!(fired.try_recv().is_err());
+
+    let mut expected: Vec<Fired> = Vec::new();
+    for hour in (0..=8 * 24).step_by(12) {
+        let at = start() + chrono::Duration::hours(hour);
+        // Untouched for more than seven days at the first run past them.
+        let pruned = if hour == 7 * 24 + 12 { "1" } else { "0" };
+        expected.push((at, "prune", pruned.to_string()));
+        if hour % 24 == 0 {
+            expected.push((at, "publish", "published".to_string()));
+        }
+    }
+    assert_eq!(log, expected);
+    let data: serde_json::Value = serde_json::from_slice(&fs::read(data_file).unwrap()).unwrap();
+    assert_eq!(
+        serde_json::from_value::<DateTime<Utc>>(data["generatedAt"].clone()).unwrap(),
+        start() + chrono::Duration::days(8)
+    );
+}
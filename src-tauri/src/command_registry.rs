@@ -0,0 +1,558 @@
+//! Every action the command palette and keybindings can run, with its
+//! title, category, default binding and when it is enabled. Backend features
+//! register theirs at startup along with a handler; actions only a window can
+//! carry out, such as moving the selection, are registered without one and
+//! sent back to the window as `palette-action`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State, Window};
+
+use crate::events;
+use crate::focus::FocusState;
+use crate::protocol::EventPayload;
+use crate::read_only::{self, MUTATING_COMMANDS};
+use crate::search;
+use crate::storage::Storage;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// Queries matching less of a title than this leave it out of the palette.
+const MATCH_THRESHOLD: f32 = 0.7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    File,
+    View,
+    Task,
+    Search,
+    Settings,
+    Navigation,
+}
+
+/// When a command can run, checked against a [`CommandState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum EnabledWhen {
+    Always,
+    TaskSelected,
+    Editing,
+    FocusRunning,
+    NoFocusRunning,
+}
+
+/// What enablement is evaluated against: the backend's own state plus what
+/// only the window knows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandState {
+    pub task_selected: bool,
+    pub editing: bool,
+    pub focus_running: bool,
+    pub read_only: bool,
+}
+
+impl CommandState {
+    pub fn current(app: &AppHandle, task_selected: bool, editing: bool) -> Self {
+        Self {
+            task_selected,
+            editing,
+            focus_running: app.state::<FocusState>().is_active(),
+            read_only: app.state::<Storage>().newer_schema().is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandDescriptor {
+    pub id: &'static str,
+    /// i18n key of the title, e.g. `commandPalette.commands.task.newTask.title`.
+    pub title_key: &'static str,
+    /// Shown when the window has no translation for `title_key`.
+    pub default_title: &'static str,
+    pub category: Category,
+    pub default_binding: Option<&'static str>,
+    pub enabled_when: EnabledWhen,
+    /// The IPC command the handler stands in for. Running it from the
+    /// palette is refused whenever invoking that command would be.
+    #[serde(skip)]
+    pub command: Option<&'static str>,
+}
+
+impl CommandDescriptor {
+    pub fn enabled(&self, state: &CommandState) -> bool {
+        let writes = self
+            .command
+            .is_some_and(|command| MUTATING_COMMANDS.contains(&command));
+        if writes && state.read_only {
+            return false;
+        }
+        match self.enabled_when {
+            EnabledWhen::Always => true,
+            EnabledWhen::TaskSelected => state.task_selected,
+            EnabledWhen::Editing => state.editing,
+            EnabledWhen::FocusRunning => state.focus_running,
+            EnabledWhen::NoFocusRunning => !state.focus_running,
+        }
+    }
+}
+
+pub type Handler = Arc<dyn Fn(&AppHandle, Value) -> Result<Value, String> + Send + Sync>;
+
+#[derive(Clone)]
+struct Registered {
+    descriptor: CommandDescriptor,
+    /// `None` for commands the window runs itself.
+    handler: Option<Handler>,
+}
+
+/// Registered commands by id.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: RwLock<BTreeMap<&'static str, Registered>>,
+}
+
+impl CommandRegistry {
+    /// Adds (or replaces) a command the backend runs.
+    pub fn register(
+        &self,
+        descriptor: CommandDescriptor,
+        handler: impl Fn(&AppHandle, Value) -> Result<Value, String> + Send + Sync + 'static,
+    ) {
+        self.insert(descriptor, Some(Arc::new(handler)));
+    }
+
+    /// Adds (or replaces) a command the window runs when asked to.
+    pub fn register_window(&self, descriptor: CommandDescriptor) {
+        self.insert(descriptor, None);
+    }
+
+    fn insert(&self, descriptor: CommandDescriptor, handler: Option<Handler>) {
+        self.commands
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                descriptor.id,
+                Registered {
+                    descriptor,
+                    handler,
+                },
+            );
+    }
+
+    fn get(&self, id: &str) -> Option<Registered> {
+        self.commands
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.commands
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(id)
+    }
+
+    pub fn descriptors(&self) -> Vec<CommandDescriptor> {
+        self.commands
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .map(|registered| registered.descriptor.clone())
+            .collect()
+    }
+}
+
+/// `(id, title_key, default_title, category, default_binding, enabled_when)`.
+type WindowCommand = (
+    &'static str,
+    &'static str,
+    &'static str,
+    Category,
+    Option<&'static str>,
+    EnabledWhen,
+);
+
+/// Commands handled by the window, mirroring its default keybindings.
+const WINDOW_COMMANDS: &[WindowCommand] = &[
+    (
+        "openCommandPalette",
+        "commandPalette.commands.settings.openCommandPalette.title",
+        "Open Command Palette",
+        Category::Settings,
+        Some("Ctrl+Shift+P"),
+        EnabledWhen::Always,
+    ),
+    (
+        "newTask",
+        "commandPalette.commands.task.newTask.title",
+        "New Task",
+        Category::Task,
+        Some("Ctrl+N"),
+        EnabledWhen::Always,
+    ),
+    (
+        "openSettings",
+        "commandPalette.commands.settings.openSettings.title",
+        "Open Settings",
+        Category::Settings,
+        Some("Ctrl+,"),
+        EnabledWhen::Always,
+    ),
+    (
+        "toggleSearch",
+        "commandPalette.commands.search.toggleSearch.title",
+        "Toggle Search",
+        Category::Search,
+        Some("Ctrl+F"),
+        EnabledWhen::Always,
+    ),
+    (
+        "toggleFilter",
+        "commandPalette.commands.search.toggleFilter.title",
+        "Toggle Filter",
+        Category::Search,
+        Some("Ctrl+Shift+F"),
+        EnabledWhen::Always,
+    ),
+    (
+        "toggleCaseSensitive",
+        "commandPalette.commands.search.toggleCaseSensitive.title",
+        "Toggle Case Sensitive",
+        Category::Search,
+        Some("Alt+C"),
+        EnabledWhen::Always,
+    ),
+    (
+        "toggleRegex",
+        "commandPalette.commands.search.toggleRegex.title",
+        "Toggle Regex Mode",
+        Category::Search,
+        Some("Alt+R"),
+        EnabledWhen::Always,
+    ),
+    (
+        "toggleWholeWord",
+        "commandPalette.commands.search.toggleWholeWord.title",
+        "Toggle Whole Word",
+        Category::Search,
+        Some("Alt+W"),
+        EnabledWhen::Always,
+    ),
+    (
+        "focusSearch",
+        "commandPalette.commands.search.focusSearch.title",
+        "Focus Search",
+        Category::Search,
+        None,
+        EnabledWhen::Always,
+    ),
+    (
+        "showKeybindings",
+        "commandPalette.commands.settings.showShortcuts.title",
+        "Show Keyboard Shortcuts",
+        Category::Settings,
+        Some("Ctrl+K Ctrl+S"),
+        EnabledWhen::Always,
+    ),
+    (
+        "selectAll",
+        "commandPalette.commands.task.selectAll.title",
+        "Select All Tasks",
+        Category::Task,
+        Some("Ctrl+A"),
+        EnabledWhen::Always,
+    ),
+    (
+        "toggleTaskComplete",
+        "commandPalette.commands.task.toggleCompletion.title",
+        "Toggle Task Completion",
+        Category::Task,
+        Some("Ctrl+D"),
+        EnabledWhen::TaskSelected,
+    ),
+    (
+        "deleteSelected",
+        "commandPalette.commands.task.deleteSelected.title",
+        "Delete Selected Tasks",
+        Category::Task,
+        Some("Delete"),
+        EnabledWhen::TaskSelected,
+    ),
+    (
+        "editTask",
+        "commandPalette.commands.task.editSelected.title",
+        "Edit Selected Task",
+        Category::Task,
+        Some("F2"),
+        EnabledWhen::TaskSelected,
+    ),
+    (
+        "confirmEdit",
+        "commandPalette.commands.task.confirmEdit.title",
+        "Confirm Edit",
+        Category::Task,
+        Some("Enter"),
+        EnabledWhen::Editing,
+    ),
+    (
+        "cancelAction",
+        "commandPalette.commands.navigation.cancelAction.title",
+        "Cancel",
+        Category::Navigation,
+        Some("Escape"),
+        EnabledWhen::Always,
+    ),
+    (
+        "nextTask",
+        "commandPalette.commands.navigation.nextTask.title",
+        "Next Task",
+        Category::Navigation,
+        Some("ArrowDown"),
+        EnabledWhen::Always,
+    ),
+    (
+        "previousTask",
+        "commandPalette.commands.navigation.previousTask.title",
+        "Previous Task",
+        Category::Navigation,
+        Some("ArrowUp"),
+        EnabledWhen::Always,
+    ),
+    (
+        "firstTask",
+        "commandPalette.commands.navigation.firstTask.title",
+        "First Task",
+        Category::Navigation,
+        Some("Home"),
+        EnabledWhen::Always,
+    ),
+    (
+        "lastTask",
+        "commandPalette.commands.navigation.lastTask.title",
+        "Last Task",
+        Category::Navigation,
+        Some("End"),
+        EnabledWhen::Always,
+    ),
+    (
+        "showTasksDetailed",
+        "commandPalette.commands.view.switchToTasksDetailed.title",
+        "Switch to Tasks (Detailed)",
+        Category::View,
+        Some("Ctrl+1"),
+        EnabledWhen::Always,
+    ),
+    (
+        "showTasksSimple",
+        "commandPalette.commands.view.switchToTasksSimple.title",
+        "Switch to Tasks (Simple)",
+        Category::View,
+        Some("Ctrl+2"),
+        EnabledWhen::Always,
+    ),
+    (
+        "showSchedules",
+        "commandPalette.commands.view.switchToSchedules.title",
+        "Switch to Schedules",
+        Category::View,
+        Some("Ctrl+3"),
+        EnabledWhen::Always,
+    ),
+    (
+        "showHelp",
+        "commandPalette.commands.settings.showHelp.title",
+        "Show Help",
+        Category::Settings,
+        Some("F1"),
+        EnabledWhen::Always,
+    ),
+];
+
+pub fn register_window_commands(registry: &CommandRegistry) {
+    for &(id, title_key, default_title, category, default_binding, enabled_when) in WINDOW_COMMANDS
+    {
+        registry.register_window(CommandDescriptor {
+            id,
+            title_key,
+            default_title,
+            category,
+            default_binding,
+            enabled_when,
+            command: None,
+        });
+    }
+}
+
+/// What the palette is showing and the window's side of the state.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteContext {
+    #[serde(default)]
+    pub query: String,
+    #[serde(default)]
+    pub task_selected: bool,
+    #[serde(default)]
+    pub editing: bool,
+    /// Translated titles by `title_key`, in the window's language.
+    #[serde(default)]
+    pub titles: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteEntry {
+    #[serde(flatten)]
+    pub descriptor: CommandDescriptor,
+    pub title: String,
+    pub enabled: bool,
+    /// How well the title matches the query; 1.0 without a query.
+    pub score: f32,
+}
+
+/// The commands matching `context.query` by their translated title, best
+/// match first, or all of them by category without a query.
+pub fn palette(
+    descriptors: Vec<CommandDescriptor>,
+    context: &PaletteContext,
+    state: &CommandState,
+) -> Vec<PaletteEntry> {
+    let query = context.query.trim();
+    let mut entries: Vec<PaletteEntry> = descriptors
+        .into_iter()
+        .filter_map(|descriptor| {
+            let title = context
+                .titles
+                .get(descriptor.title_key)
+                .cloned()
+                .unwrap_or_else(|| descriptor.default_title.to_string());
+            let score = if query.is_empty() {
+                1.0
+            } else {
+                search::containment(query, &title)
+            };
+            (score >= MATCH_THRESHOLD).then(|| PaletteEntry {
+                enabled: descriptor.enabled(state),
+                descriptor,
+                title,
+                score,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.descriptor.category.cmp(&b.descriptor.category))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    entries
+}
+
+/// Ids in a keybindings file that no registered command has, with the
+/// binding's key, in file order.
+pub fn unknown_bindings(registry: &CommandRegistry, path: &Path) -> Vec<(String, String)> {
+    // Missing or unparsable files are reported by the self-check already.
+    let Some(table) = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+    else {
+        return Vec::new();
+    };
+    let Some(bindings) = table.get("keybindings").and_then(|b| b.as_array()) else {
+        return Vec::new();
+    };
+    bindings
+        .iter()
+        .filter_map(|binding| {
+            let command = binding.get("command")?.as_str()?;
+            let key = binding.get("key").and_then(|k| k.as_str()).unwrap_or("");
+            (!registry.contains(command)).then(|| (key.to_string(), command.to_string()))
+        })
+        .collect()
+}
+
+/// Sent to the window that ran a command it handles itself.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteAction {
+    pub id: String,
+    pub args: Value,
+}
+
+impl EventPayload for PaletteAction {}
+
+/// Named apart from `list_commands`, which lists the IPC commands.
+#[tauri::command]
+pub fn list_palette_commands(
+    context: Option<PaletteContext>,
+    app: AppHandle,
+    registry: State<'_, CommandRegistry>,
+) -> Vec<PaletteEntry> {
+    let context = context.unwrap_or_default();
+    let state = CommandState::current(&app, context.task_selected, context.editing);
+    palette(registry.descriptors(), &context, &state)
+}
+
+/// Runs a palette command or keybinding. Backend commands go through the
+/// same read-only check as invoking them directly, and run as part of this
+/// request, so their logs and events carry its id.
+#[tauri::command]
+pub fn execute_command(
+    id: String,
+    args: Option<Value>,
+    task_selected: Option<bool>,
+    editing: Option<bool>,
+    window: Window,
+    app: AppHandle,
+    registry: State<'_, CommandRegistry>,
+) -> Result<Value, String> {
+    let registered = registry
+        .get(&id)
+        .ok_or_else(|| format!("Failed to run command: no command '{}'", id))?;
+    let descriptor = &registered.descriptor;
+    let state = CommandState::current(
+        &app,
+        task_selected.unwrap_or(false),
+        editing.unwrap_or(false),
+    );
+    if let Some(command) = descriptor.command {
+        read_only::check(&app.state::<Storage>(), command).map_err(|newer| {
+            format!(
+                "Failed to run command '{}': the store is read-only until the app \
+                     supports schema version {}",
+                id, newer.db_version
+            )
+        })?;
+    }
+    if !descriptor.enabled(&state) {
+        return Err(format!(
+            "Failed to run command: '{}' is not available right now",
+            id
+        ));
+    }
+    let args = args.unwrap_or(Value::Null);
+    trace::log(format!("Running command '{}'", id));
+    match registered.handler {
+        Some(handler) => handler(&app, args),
+        None => {
+            events::emit_to(
+                &app,
+                window.label(),
+                "palette-action",
+                &PaletteAction { id, args },
+            )
+            .map_err(|e| format!("Failed to run command: {}", e))?;
+            Ok(Value::Null)
+        }
+    }
+}
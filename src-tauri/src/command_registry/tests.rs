@@ -0,0 +1,186 @@
+use super::*;
+
+fn descriptor(
+    id: &'static str,
+    title: &'static str,
+    enabled_when: EnabledWhen,
+) -> CommandDescriptor {
+    CommandDescriptor {
+        id,
+        title_key: id,
+        default_title: title,
+        category: Category::Task,
+        default_binding: None,
+        enabled_when,
+        command: None,
+    }
+}
+
+fn registry() -> CommandRegistry {
+    let registry = CommandRegistry::default();
+    register_window_commands(&registry);
+    registry.register(
+        descriptor(
+            "startFocusSession",
+            "Start Focus Session",
+            EnabledWhen::NoFocusRunning,
+        ),
+        |_, _| Ok(Value::Null),
+    );
+    registry.register(
+        descriptor(
+            "stopFocusSession",
+            "Stop Focus Session",
+            EnabledWhen::FocusRunning,
+        ),
+        |_, _| Ok(Value::Null),
+    );
+    registry
+}
+
+fn query(query: &str) -> PaletteContext {
+    PaletteContext {
+        query: query.to_string(),
+        ..Default::default()
+    }
+}
+
+fn ids(entries: &[PaletteEntry]) -> Vec<&str> {
+    entries.iter().map(|entry| entry.descriptor.id).collect()
+}
+
+#[test]
+fn test_stop_focus_is_only_enabled_while_a_session_runs() {
+    let registry = registry();
+    let idle = CommandState::default();
+    let running = CommandState {
+        focus_running: true,
+        ..Default::default()
+    };
+
+    let entries = palette(registry.descriptors(), &query("focus session"), &idle);
+    let enabled: Vec<_> = entries
+        .iter()
+        .map(|e| (e.descriptor.id, e.enabled))
+        .collect();
+    assert!(enabled.contains(&("startFocusSession", true)));
+    assert!(enabled.contains(&("stopFocusSession", false)));
+
+    let entries = palette(registry.descriptors(), &query("focus session"), &running);
+    let enabled: Vec<_> = entries
+        .iter()
+        .map(|e| (e.descriptor.id, e.enabled))
+        .collect();
+    assert!(enabled.contains(&("startFocusSession", false)));
+    assert!(enabled.contains(&("stopFocusSession", true)));
+}
+
+#[test]
+fn test_palette_matches_translated_titles() {
+    let registry = registry();
+    let context = PaletteContext {
+        query: "タスク".to_string(),
+        titles: HashMap::from([(
+            "commandPalette.commands.task.newTask.title".to_string(),
+            "新しいタスク".to_string(),
+        )]),
+        ..Default::default()
+    };
+
+    let entries = palette(registry.descriptors(), &context, &CommandState::default());
+
+    assert_eq!(ids(&entries), ["newTask"]);
+    assert_eq!(entries[0].title, "新しいタスク");
+}
+
+#[test]
+fn test_palette_falls_back_to_default_titles_and_ranks_by_score() {
+    let registry = registry();
+
+    let entries = palette(
+        registry.descriptors(),
+        &query("toggle reg"),
+        &CommandState::default(),
+    );
+
+    assert_eq!(ids(&entries)[0], "toggleRegex");
+    assert!(entries.windows(2).all(|w| w[0].score >= w[1].score));
+    assert!(!ids(&entries).contains(&"newTask"));
+}
+
+#[test]
+fn test_empty_query_lists_everything_by_category() {
+    let registry = registry();
+
+    let entries = palette(registry.descriptors(), &query(""), &CommandState::default());
+
+    assert_eq!(entries.len(), registry.descriptors().len());
+    assert!(entries
+        .windows(2)
+        .all(|w| w[0].descriptor.category <= w[1].descriptor.category));
+}
+
+#[test]
+fn test_writing_commands_are_disabled_while_read_only() {
+    let mut writes = descriptor("stopFocusSession", "Stop", EnabledWhen::Always);
+    writes.command = Some("stop_focus_session");
+    let mut reads = descriptor("runSelfCheck", "Check", EnabledWhen::Always);
+    reads.command = Some("run_self_check");
+    let read_only = CommandState {
+        read_only: true,
+        ..Default::default()
+    };
+
+    assert!(!writes.enabled(&read_only));
+    assert!(reads.enabled(&read_only));
+    assert!(writes.enabled(&CommandState::default()));
+}
+
+#[test]
+fn test_registering_an_id_again_replaces_it() {
+    let registry = registry();
+    let count = registry.descriptors().len();
+
+    registry.register_window(descriptor("newTask", "Add", EnabledWhen::TaskSelected));
+
+    assert_eq!(registry.descriptors().len(), count);
+    let replaced = registry.get("newTask").unwrap();
+    assert_eq!(replaced.descriptor.default_title, "Add");
+    assert!(replaced.handler.is_none());
+}
+
+#[test]
+fn test_unknown_bindings_are_found_in_file_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keybindings.toml");
+    std::fs::write(
+        &path,
+        r#"
+[[keybindings]]
+key = "Ctrl+N"
+command = "newTask"
+
+[[keybindings]]
+key = "Ctrl+Q"
+command = "quitEverything"
+
+[[keybindings]]
+key = "F9"
+command = "stopFocusSession"
+
+[[keybindings]]
+key = "F10"
+command = "togglePomodoro"
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        unknown_bindings(&registry(), &path),
+        [
+            ("Ctrl+Q".to_string(), "quitEverything".to_string()),
+            ("F10".to_string(), "togglePomodoro".to_string()),
+        ]
+    );
+    assert!(unknown_bindings(&registry(), &dir.path().join("missing.toml")).is_empty());
+}
@@ -0,0 +1,93 @@
+//! Context sent along when a todo is completed, so a window can tell a
+//! routine check-off from finishing the last todo due today. The activity
+//! consumer of [`crate::storage::changes`] counts the completion and sends
+//! it, however the todo was completed. Both go by the `completed_at` the
+//! completing write recorded, not by when the consumer gets to it, and
+//! completions caught up on after a restart are counted without being sent.
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::activity;
+use crate::events;
+use crate::focus::FocusState;
+use crate::local_api;
+use crate::protocol::EventPayload;
+use crate::storage::Storage;
+use crate::trace;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionContext {
+    /// Scheduled for a day before the one it was completed on.
+    pub was_overdue: bool,
+    /// Incomplete todos still due today or earlier, as on the agenda.
+    pub remaining_due_today: usize,
+    /// Completions on this local day, this one included.
+    pub completed_today_count: u64,
+    pub focus_session_active: bool,
+}
+
+/// Payload of `todo-completed`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoCompleted {
+    pub todo_id: String,
+    pub context: CompletionContext,
+}
+
+impl EventPayload for TodoCompleted {}
+
+/// The context of completing `todo` at `completed_at`, given the cached
+/// `todos` afterwards. Days are those of `tz`, except that the agenda
+/// counts UTC days.
+pub fn completion_context<Tz: TimeZone>(
+    todo: &Todo,
+    todos: Vec<Todo>,
+    completed_at: DateTime<Utc>,
+    tz: &Tz,
+    completed_today_count: u64,
+    focus_session_active: bool,
+) -> CompletionContext {
+    let local_day = |at: DateTime<Utc>| at.with_timezone(tz).date_naive();
+    CompletionContext {
+        was_overdue: todo
+            .scheduled_for
+            .is_some_and(|due| local_day(due) < local_day(completed_at)),
+        remaining_due_today: local_api::agenda_for(todos, completed_at.date_naive()).len(),
+        completed_today_count,
+        focus_session_active,
+    }
+}
+
+/// Emits `todo-completed` for `todo`, which was completed at `completed_at`.
+pub fn announce(app: &AppHandle, storage: &Storage, todo: &Todo, completed_at: DateTime<Utc>) {
+    let context = storage.list_todos().and_then(|todos| {
+        Ok(completion_context(
+            todo,
+            todos,
+            completed_at,
+            &Local,
+            activity::completed_on(storage, completed_at)?,
+            app.state::<FocusState>().is_active(),
+        ))
+    });
+    match context {
+        Ok(context) => {
+            let _ = events::emit(
+                app,
+                "todo-completed",
+                TodoCompleted {
+                    todo_id: todo.id.clone(),
+                    context,
+                },
+            );
+        }
+        Err(e) => trace::log(format!("Failed to describe a completion: {}", e)),
+    }
+}
@@ -0,0 +1,87 @@
+use super::*;
+
+use std::sync::Arc;
+
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+
+use crate::clock::MockClock;
+
+fn at(day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 6, day, hour, 0, 0).unwrap()
+}
+
+fn todo(id: &str, due: Option<DateTime<Utc>>, completed: bool) -> Todo {
+    Todo {
+        completed,
+        scheduled_for: due,
+        updated_at: at(3, 15),
+        ..Todo::sample(id, id, at(1, 9))
+    }
+}
+
+#[test]
+fn test_last_todo_due_today_leaves_nothing_remaining() {
+    let done = todo("done", Some(at(3, 9)), true);
+    let todos = vec![
+        done.clone(),
+        todo("tomorrow", Some(at(4, 9)), false),
+        todo("someday", None, false),
+        todo("earlier", Some(at(2, 9)), true),
+    ];
+
+    let context = completion_context(&done, todos, at(3, 15), &Utc, 4, true);
+    assert_eq!(
+        context,
+        CompletionContext {
+            was_overdue: false,
+            remaining_due_today: 0,
+            completed_today_count: 4,
+            focus_session_active: true,
+        }
+    );
+}
+
+#[test]
+fn test_overdue_completion_counts_what_is_still_due() {
+    let done = todo("done", Some(at(1, 18)), true);
+    let todos = vec![
+        done.clone(),
+        todo("late", Some(at(2, 9)), false),
+        todo("today", Some(at(3, 20)), false),
+        todo("tomorrow", Some(at(4, 9)), false),
+    ];
+
+    let context = completion_context(&done, todos, at(3, 15), &Utc, 1, false);
+    assert!(context.was_overdue);
+    assert_eq!(context.remaining_due_today, 2);
+}
+
+#[test]
+fn test_overdue_goes_by_local_days() {
+    // Due 8pm and done 10am the next morning in New York: 00:00 and 14:00
+    // UTC, the same UTC day.
+    let new_york = FixedOffset::west_opt(4 * 3600).unwrap();
+    let done = todo("done", Some(at(3, 0)), true);
+    let context = completion_context(&done, vec![], at(3, 14), &new_york, 1, false);
+    assert!(context.was_overdue);
+
+    // Due 1am and done 11am the same day in Tokyo: different UTC days.
+    let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+    let done = todo("done", Some(at(2, 16)), true);
+    let context = completion_context(&done, vec![], at(3, 2), &tokyo, 1, false);
+    assert!(!context.was_overdue);
+}
+
+#[test]
+fn test_completions_count_on_the_day_they_happened() {
+    let clock = Arc::new(MockClock::new(at(3, 12)));
+    let storage = Storage::open_in_memory().unwrap().with_clock(clock.clone());
+    assert_eq!(activity::completed_on(&storage, clock.now()).unwrap(), 0);
+
+    activity::todo_completed(&storage, clock.now());
+    activity::todo_completed(&storage, clock.now());
+    // Caught up on later, e.g. after a restart.
+    activity::todo_completed(&storage, at(1, 12));
+    assert_eq!(activity::completed_on(&storage, clock.now()).unwrap(), 2);
+    assert_eq!(activity::completed_on(&storage, at(1, 12)).unwrap(), 1);
+}
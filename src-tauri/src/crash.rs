@@ -0,0 +1,177 @@
+//! Crash reports for panics. The hook installed by `run()` writes the panic
+//! message, a backtrace, the app version and the tail of the log to
+//! `crash-reports` in the data directory. On the next launch the frontend
+//! asks `pending_crash_report` for it, offers to show or send it, and
+//! discards it with `dismiss_crash_report`.
+//!
+//! Panic messages and log lines can quote todos, so titles are scrubbed
+//! before a report leaves the backend. The hook itself can't do that: the
+//! store may be what panicked.
+
+use std::fs;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::storage::Storage;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// Under the data directory.
+pub const CRASH_DIR: &str = "crash-reports";
+/// Replaces todo titles in reports.
+pub const SCRUBBED: &str = "[todo]";
+/// Titles shorter than this are left alone; scrubbing every "a" or "To"
+/// would leave the report unreadable.
+const MIN_SCRUBBED_CHARS: usize = 3;
+
+/// Set at startup once the data directory is known; panics before that are
+/// only printed.
+static REPORT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    /// File name without extension; what `dismiss_crash_report` takes.
+    pub id: String,
+    pub at: DateTime<Utc>,
+    pub version: String,
+    pub message: String,
+    /// `file:line:column` of the panic.
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: String,
+    pub log_tail: Vec<String>,
+}
+
+impl CrashReport {
+    fn from_panic(info: &PanicHookInfo<'_>, at: DateTime<Utc>) -> Self {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_string());
+        Self {
+            id: format!("crash-{}", at.format("%Y%m%dT%H%M%S%3fZ")),
+            at,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            message,
+            location: info.location().map(|l| l.to_string()),
+            thread: std::thread::current().name().map(str::to_string),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            log_tail: trace::tail(),
+        }
+    }
+
+    /// The report with every todo title in `titles` replaced by
+    /// [`SCRUBBED`], longest first so a title containing another goes whole.
+    pub fn scrubbed(mut self, titles: &[String]) -> Self {
+        let mut titles: Vec<&str> = titles
+            .iter()
+            .map(|title| title.trim())
+            .filter(|title| title.chars().count() >= MIN_SCRUBBED_CHARS)
+            .collect();
+        titles.sort_by_key(|title| std::cmp::Reverse(title.len()));
+        titles.dedup();
+        let scrub = |text: &mut String| {
+            for title in &titles {
+                if text.contains(title) {
+                    *text = text.replace(title, SCRUBBED);
+                }
+            }
+        };
+        scrub(&mut self.message);
+        scrub(&mut self.backtrace);
+        self.log_tail.iter_mut().for_each(scrub);
+        self
+    }
+}
+
+pub fn write_report(dir: &Path, report: &CrashReport) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.json", report.id));
+    let json = serde_json::to_vec_pretty(report).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// The newest report in `dir`. Unreadable files are skipped.
+pub fn latest_report(dir: &Path) -> Option<CrashReport> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| fs::read(entry.ok()?.path()).ok())
+        .filter_map(|bytes| serde_json::from_slice::<CrashReport>(&bytes).ok())
+        .max_by_key(|report| report.at)
+}
+
+/// Writes a report for every panic, then runs the default hook.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(dir) = REPORT_DIR.get() {
+            // Real time: the app's clock lives in state that may be gone.
+            let report = CrashReport::from_panic(info, Utc::now());
+            match write_report(dir, &report) {
+                Ok(path) => eprintln!("Crash report written to {}", path.display()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        previous(info);
+    }));
+}
+
+/// Where reports go from now on; `data_dir/crash-reports`.
+pub fn set_report_dir(data_dir: &Path) {
+    let _ = REPORT_DIR.set(data_dir.join(CRASH_DIR));
+}
+
+/// The report of the last crash, with todo titles scrubbed, until it is
+/// dismissed.
+#[tauri::command]
+pub fn pending_crash_report(storage: State<'_, Storage>) -> Result<Option<CrashReport>, String> {
+    let Some(report) = REPORT_DIR.get().and_then(|dir| latest_report(dir)) else {
+        return Ok(None);
+    };
+    let titles: Vec<String> = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect();
+    Ok(Some(report.scrubbed(&titles)))
+}
+
+/// Deletes report `id` and any older ones, once the user viewed or sent it.
+#[tauri::command]
+pub fn dismiss_crash_report(id: String) -> Result<(), String> {
+    let Some(dir) = REPORT_DIR.get() else {
+        return Ok(());
+    };
+    let Some(report) = Some(&id)
+        .filter(|id| !id.contains(['/', '\\', '.']))
+        .and_then(|id| fs::read(dir.join(format!("{}.json", id))).ok())
+        .and_then(|bytes| serde_json::from_slice::<CrashReport>(&bytes).ok())
+    else {
+        return Err(format!("Unknown crash report '{}'", id));
+    };
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        let older = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CrashReport>(&bytes).ok())
+            .is_some_and(|other| other.at <= report.at);
+        if older {
+            fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
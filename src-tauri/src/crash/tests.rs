@@ -0,0 +1,64 @@
+use super::*;
+
+use chrono::TimeZone;
+
+fn report(at: DateTime<Utc>) -> CrashReport {
+    CrashReport {
+        id: format!("crash-{}", at.format("%Y%m%dT%H%M%S%3fZ")),
+        at,
+        version: "1.2.3".to_string(),
+        message: "Unknown todo 'Call the dentist about Tuesday'".to_string(),
+        location: Some("src/storage.rs:10:5".to_string()),
+        thread: Some("main".to_string()),
+        backtrace: "0: yutodo::storage::update_todo".to_string(),
+        log_tail: vec![
+            "Failed to write draft of 'Call the dentist': busy".to_string(),
+            "Recovered unsaved edits of 1 todo(s)".to_string(),
+        ],
+    }
+}
+
+#[test]
+fn test_report_serializes_for_the_frontend() {
+    let at = Utc.with_ymd_and_hms(2024, 6, 1, 9, 30, 0).unwrap();
+    let json = serde_json::to_value(report(at)).unwrap();
+    assert_eq!(json["id"], "crash-20240601T093000000Z");
+    assert_eq!(json["version"], "1.2.3");
+    assert_eq!(json["location"], "src/storage.rs:10:5");
+    assert_eq!(json["logTail"][1], "Recovered unsaved edits of 1 todo(s)");
+    assert_eq!(
+        serde_json::from_value::<CrashReport>(json).unwrap(),
+        report(at)
+    );
+}
+
+#[test]
+fn test_scrubbing_replaces_titles_longest_first() {
+    let at = Utc.with_ymd_and_hms(2024, 6, 1, 9, 30, 0).unwrap();
+    let titles = [
+        "Call the dentist".to_string(),
+        "Call the dentist about Tuesday".to_string(),
+        "To".to_string(),
+    ];
+    let scrubbed = report(at).scrubbed(&titles);
+    assert_eq!(scrubbed.message, "Unknown todo '[todo]'");
+    assert_eq!(
+        scrubbed.log_tail[0],
+        "Failed to write draft of '[todo]': busy"
+    );
+    // Too short to scrub without mangling the rest.
+    assert_eq!(scrubbed.log_tail[1], "Recovered unsaved edits of 1 todo(s)");
+    assert_eq!(scrubbed.backtrace, report(at).backtrace);
+}
+
+#[test]
+fn test_latest_report_skips_unreadable_files() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(latest_report(dir.path()), None);
+    let older = report(Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap());
+    let newer = report(Utc.with_ymd_and_hms(2024, 6, 2, 9, 0, 0).unwrap());
+    write_report(dir.path(), &newer).unwrap();
+    write_report(dir.path(), &older).unwrap();
+    fs::write(dir.path().join("crash-broken.json"), "{").unwrap();
+    assert_eq!(latest_report(dir.path()), Some(newer));
+}
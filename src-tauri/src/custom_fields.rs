@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::safety::{self, DestructionScope, DestructiveOperation, SafetyState};
+use crate::settings::SettingsState;
+use crate::storage::Storage;
+use crate::types::{FieldValue, Todo};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FieldType {
+    Text,
+    Number,
+    Date,
+    Url,
+    Select { options: Vec<String> },
+}
+
+/// A user-defined field that can be set on any todo.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomField {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub field_type: FieldType,
+    pub required: bool,
+    pub default: Option<FieldValue>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FieldError {
+    /// Rejected input; the message is shown to the user as is.
+    #[error("{0}")]
+    Invalid(String),
+    #[error("database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl std::fmt::Display for FieldValue {
+    /// Canonical text form, as stored in the database and parsed by
+    /// [`CustomField::parse_value`].
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldValue::Number(n) => write!(f, "{}", n),
+            FieldValue::Date(d) => write!(f, "{}", d.format("%Y-%m-%d")),
+            FieldValue::Text(s) => f.write_str(s),
+        }
+    }
+}
+
+impl CustomField {
+    /// Validates free-form input against the field type.
+    pub fn parse_value(&self, raw: &str) -> Result<FieldValue, FieldError> {
+        let raw = raw.trim();
+        let invalid = |expected: &str| {
+            FieldError::Invalid(format!(
+                "Field '{}' expects {}, got '{}'",
+                self.name, expected, raw
+            ))
+        };
+        match &self.field_type {
+            FieldType::Text => Ok(FieldValue::Text(raw.to_string())),
+            FieldType::Number => raw
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(FieldValue::Number)
+                .ok_or_else(|| invalid("a number")),
+            FieldType::Date => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .map(FieldValue::Date)
+                .map_err(|_| invalid("a date like 2024-05-31")),
+            FieldType::Url => match url::Url::parse(raw) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                    Ok(FieldValue::Text(raw.to_string()))
+                }
+                _ => Err(invalid("an http(s) URL")),
+            },
+            FieldType::Select { options } => options
+                .iter()
+                .find(|option| *option == raw)
+                .map(|option| FieldValue::Text(option.clone()))
+                .ok_or_else(|| {
+                    FieldError::Invalid(format!(
+                        "Field '{}' must be one of: {}; got '{}'",
+                        self.name,
+                        options.join(", "),
+                        raw
+                    ))
+                }),
+        }
+    }
+}
+
+fn field_from_row(row: &Row<'_>) -> rusqlite::Result<CustomField> {
+    let field_type: String = row.get(2)?;
+    let field_type = serde_json::from_str(&field_type).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    let mut field = CustomField {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        field_type,
+        required: row.get(3)?,
+        default: None,
+    };
+    // Stored defaults were validated on insert; a definition edited by hand just loses it.
+    field.default = row
+        .get::<_, Option<String>>(4)?
+        .and_then(|raw| field.parse_value(&raw).ok());
+    Ok(field)
+}
+
+pub fn load_fields(conn: &Connection) -> rusqlite::Result<Vec<CustomField>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, field_type, required, default_value FROM custom_fields ORDER BY name",
+    )?;
+    let rows = stmt.query_map([], field_from_row)?;
+    rows.collect()
+}
+
+fn find_field(conn: &Connection, field_id: &str) -> Result<CustomField, FieldError> {
+    conn.query_row(
+        "SELECT id, name, field_type, required, default_value FROM custom_fields WHERE id = ?1",
+        [field_id],
+        field_from_row,
+    )
+    .optional()?
+    .ok_or_else(|| FieldError::Invalid(format!("Unknown custom field '{}'", field_id)))
+}
+
+pub fn list_fields(storage: &Storage) -> rusqlite::Result<Vec<CustomField>> {
+    load_fields(&storage.conn())
+}
+
+pub fn define_field(
+    storage: &Storage,
+    name: &str,
+    field_type: FieldType,
+    required: bool,
+    default: Option<&str>,
+) -> Result<CustomField, FieldError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(FieldError::Invalid(
+            "Field name must not be empty".to_string(),
+        ));
+    }
+    if let FieldType::Select { options } = &field_type {
+        if options.is_empty() {
+            return Err(FieldError::Invalid(format!(
+                "Select field '{}' needs at least one option",
+                name
+            )));
+        }
+    }
+
+    let mut field = CustomField {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        field_type,
+        required,
+        default: None,
+    };
+    field.default = default
+        .filter(|raw| !raw.trim().is_empty())
+        .map(|raw| field.parse_value(raw))
+        .transpose()?;
+
+    let conn = storage.conn();
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM custom_fields WHERE name = ?1)",
+        [&field.name],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Err(FieldError::Invalid(format!(
+            "A field named '{}' already exists",
+            field.name
+        )));
+    }
+    conn.execute(
+        "INSERT INTO custom_fields (id, name, field_type, required, default_value)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            field.id,
+            field.name,
+            serde_json::to_string(&field.field_type).unwrap_or_default(),
+            field.required,
+            field.default.as_ref().map(FieldValue::to_string),
+        ],
+    )?;
+    Ok(field)
+}
+
+/// Sets (or, for blank input, clears) one field on one todo and returns the
+/// value now in effect.
+pub fn set_value(
+    storage: &Storage,
+    todo_id: &str,
+    field_id: &str,
+    raw: Option<&str>,
+) -> Result<Option<FieldValue>, FieldError> {
+    let conn = storage.conn();
+    let field = find_field(&conn, field_id)?;
+    let todo_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM todos WHERE id = ?1)",
+        [todo_id],
+        |row| row.get(0),
+    )?;
+    if !todo_exists {
+        return Err(FieldError::Invalid(format!("Unknown todo '{}'", todo_id)));
+    }
+
+    match raw.filter(|raw| !raw.trim().is_empty()) {
+        Some(raw) => {
+            let value = field.parse_value(raw)?;
+            store_value(&conn, todo_id, &field, &value)?;
+            Ok(Some(value))
+        }
+        None if field.required && field.default.is_none() => Err(FieldError::Invalid(format!(
+            "Field '{}' is required",
+            field.name
+        ))),
+        None => {
+            conn.execute(
+                "DELETE FROM todo_field_values WHERE todo_id = ?1 AND field_id = ?2",
+                params![todo_id, field.id],
+            )?;
+            Ok(field.default)
+        }
+    }
+}
+
+/// Writes an already validated value.
+pub fn store_value(
+    conn: &Connection,
+    todo_id: &str,
+    field: &CustomField,
+    value: &FieldValue,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO todo_field_values (todo_id, field_id, value) VALUES (?1, ?2, ?3)",
+        params![todo_id, field.id, value.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Number of todos that have a value set for the field.
+pub fn count_values(conn: &Connection, field_id: &str) -> rusqlite::Result<usize> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM todo_field_values WHERE field_id = ?1",
+        [field_id],
+        |row| row.get(0),
+    )
+}
+
+/// Removes a definition. Values already set on todos are only deleted along
+/// with it when `cascade` is true; otherwise the call is refused.
+pub fn delete_field(storage: &Storage, field_id: &str, cascade: bool) -> Result<(), FieldError> {
+    let mut conn = storage.conn();
+    let field = find_field(&conn, field_id)?;
+    let tx = conn.transaction()?;
+    let in_use = count_values(&tx, &field.id)?;
+    if in_use > 0 && !cascade {
+        return Err(FieldError::Invalid(format!(
+            "Field '{}' is set on {} todo(s); delete with cascade to remove those values too",
+            field.name, in_use
+        )));
+    }
+    tx.execute(
+        "DELETE FROM todo_field_values WHERE field_id = ?1",
+        [&field.id],
+    )?;
+    tx.execute("DELETE FROM custom_fields WHERE id = ?1", [&field.id])?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Fills `Todo::fields` from the stored values, falling back to field defaults.
+pub fn attach_field_values(conn: &Connection, todos: &mut [Todo]) -> rusqlite::Result<()> {
+    let fields = load_fields(conn)?;
+    if fields.is_empty() {
+        return Ok(());
+    }
+    let by_id: HashMap<&str, &CustomField> = fields.iter().map(|f| (f.id.as_str(), f)).collect();
+
+    let mut values: HashMap<String, Vec<(&CustomField, FieldValue)>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT todo_id, field_id, value FROM todo_field_values")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let field_id: String = row.get(1)?;
+        let Some(field) = by_id.get(field_id.as_str()) else {
+            continue;
+        };
+        if let Ok(value) = field.parse_value(&row.get::<_, String>(2)?) {
+            values.entry(row.get(0)?).or_default().push((field, value));
+        }
+    }
+
+    for todo in todos {
+        for field in &fields {
+            if let Some(default) = &field.default {
+                todo.fields.insert(field.name.clone(), default.clone());
+            }
+        }
+        for (field, value) in values.remove(&todo.id).into_iter().flatten() {
+            todo.fields.insert(field.name.clone(), value);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn define_custom_field(
+    name: String,
+    field_type: FieldType,
+    required: Option<bool>,
+    default: Option<String>,
+    storage: State<'_, Storage>,
+) -> Result<CustomField, String> {
+    define_field(
+        &storage,
+        &name,
+        field_type,
+        required.unwrap_or(false),
+        default.as_deref(),
+    )
+    .map_err(|e| format!("Failed to define custom field: {}", e))
+}
+
+#[tauri::command]
+pub fn list_custom_fields(storage: State<'_, Storage>) -> Result<Vec<CustomField>, String> {
+    list_fields(&storage).map_err(|e| format!("Failed to list custom fields: {}", e))
+}
+
+#[tauri::command]
+pub fn set_todo_field_value(
+    todo_id: String,
+    field_id: String,
+    value: Option<String>,
+    storage: State<'_, Storage>,
+) -> Result<Option<FieldValue>, String> {
+    set_value(&storage, &todo_id, &field_id, value.as_deref())
+        .map_err(|e| format!("Failed to set field value: {}", e))
+}
+
+/// Cascading deletes of a field that is in use need a confirmation token for
+/// [`DestructiveOperation::DeleteCustomField`] scoped to the value count.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn delete_custom_field(
+    field_id: String,
+    cascade: bool,
+    confirmation_token: Option<String>,
+    typed_count: Option<usize>,
+    storage: State<'_, Storage>,
+    safety: State<'_, SafetyState>,
+    settings: State<'_, SettingsState>,
+) -> Result<(), String> {
+    let in_use = count_values(&storage.conn(), &field_id)
+        .map_err(|e| format!("Failed to delete custom field: {}", e))?;
+    if cascade && in_use > 0 {
+        safety::authorize(
+            &safety,
+            &settings.get().safety,
+            &storage,
+            confirmation_token.as_deref(),
+            typed_count,
+            DestructiveOperation::DeleteCustomField,
+            &DestructionScope {
+                count: in_use,
+                target: Some(field_id.clone()),
+            },
+        )
+        .map_err(|e| format!("Failed to delete custom field: {}", e))?;
+    }
+    delete_field(&storage, &field_id, cascade)
+        .map_err(|e| format!("Failed to delete custom field: {}", e))?;
+    Ok(())
+}
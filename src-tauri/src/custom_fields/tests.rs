@@ -0,0 +1,173 @@
+use super::*;
+use crate::storage::for_each_backend;
+use chrono::{TimeZone, Utc};
+
+fn todo(id: &str) -> Todo {
+    let now = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo::sample(id, format!("Todo {}", id), now)
+}
+
+fn message(result: Result<impl std::fmt::Debug, FieldError>) -> String {
+    result.unwrap_err().to_string()
+}
+
+#[test]
+fn test_values_round_trip_through_list_todos() {
+    for_each_backend(|storage| {
+        storage.replace_todos(&[todo("a"), todo("b")]).unwrap();
+        let estimate =
+            define_field(storage, "estimate (hours)", FieldType::Number, false, None).unwrap();
+        let due = define_field(storage, "review by", FieldType::Date, false, None).unwrap();
+        let stage = define_field(
+            storage,
+            "stage",
+            FieldType::Select {
+                options: vec!["draft".to_string(), "done".to_string()],
+            },
+            false,
+            Some("draft"),
+        )
+        .unwrap();
+
+        set_value(storage, "a", &estimate.id, Some(" 2.5 ")).unwrap();
+        set_value(storage, "a", &due.id, Some("2024-05-31")).unwrap();
+        set_value(storage, "a", &stage.id, Some("done")).unwrap();
+
+        let todos = storage.list_todos().unwrap();
+        let a = &todos[0].fields;
+        assert_eq!(a["estimate (hours)"], FieldValue::Number(2.5));
+        assert_eq!(
+            a["review by"],
+            FieldValue::Date(NaiveDate::from_ymd_opt(2024, 5, 31).unwrap())
+        );
+        assert_eq!(a["stage"], FieldValue::Text("done".to_string()));
+        // Unset fields fall back to their default.
+        assert_eq!(todos[1].fields.len(), 1);
+        assert_eq!(
+            todos[1].fields["stage"],
+            FieldValue::Text("draft".to_string())
+        );
+    });
+}
+
+#[test]
+fn test_type_validation_messages() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a")]).unwrap();
+    let number = define_field(&storage, "estimate", FieldType::Number, false, None).unwrap();
+    let date = define_field(&storage, "deadline", FieldType::Date, false, None).unwrap();
+    let url = define_field(&storage, "PR link", FieldType::Url, false, None).unwrap();
+    let select = define_field(
+        &storage,
+        "size",
+        FieldType::Select {
+            options: vec!["S".to_string(), "M".to_string()],
+        },
+        false,
+        None,
+    )
+    .unwrap();
+
+    assert_eq!(
+        message(set_value(&storage, "a", &number.id, Some("abc"))),
+        "Field 'estimate' expects a number, got 'abc'"
+    );
+    assert_eq!(
+        message(set_value(&storage, "a", &date.id, Some("31/05/2024"))),
+        "Field 'deadline' expects a date like 2024-05-31, got '31/05/2024'"
+    );
+    assert_eq!(
+        message(set_value(&storage, "a", &url.id, Some("ftp://example.com"))),
+        "Field 'PR link' expects an http(s) URL, got 'ftp://example.com'"
+    );
+    assert_eq!(
+        message(set_value(&storage, "a", &select.id, Some("XL"))),
+        "Field 'size' must be one of: S, M; got 'XL'"
+    );
+    assert_eq!(
+        message(set_value(&storage, "missing", &number.id, Some("1"))),
+        "Unknown todo 'missing'"
+    );
+    assert!(set_value(
+        &storage,
+        "a",
+        &url.id,
+        Some("https://github.com/x/y/pull/1")
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_define_rejects_duplicates_and_bad_defaults() {
+    let storage = Storage::open_in_memory().unwrap();
+    define_field(&storage, "customer", FieldType::Text, false, None).unwrap();
+    assert_eq!(
+        message(define_field(
+            &storage,
+            "customer",
+            FieldType::Text,
+            false,
+            None
+        )),
+        "A field named 'customer' already exists"
+    );
+    assert_eq!(
+        message(define_field(
+            &storage,
+            "points",
+            FieldType::Number,
+            false,
+            Some("many")
+        )),
+        "Field 'points' expects a number, got 'many'"
+    );
+    assert!(define_field(
+        &storage,
+        "tier",
+        FieldType::Select { options: vec![] },
+        false,
+        None
+    )
+    .is_err());
+    assert_eq!(list_fields(&storage).unwrap().len(), 1);
+}
+
+#[test]
+fn test_required_field_cannot_be_cleared() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a")]).unwrap();
+    let field = define_field(&storage, "customer", FieldType::Text, true, None).unwrap();
+    set_value(&storage, "a", &field.id, Some("Acme")).unwrap();
+    assert_eq!(
+        message(set_value(&storage, "a", &field.id, Some("  "))),
+        "Field 'customer' is required"
+    );
+}
+
+#[test]
+fn test_delete_requires_cascade_when_in_use() {
+    for_each_backend(|storage| {
+        storage.replace_todos(&[todo("a")]).unwrap();
+        let field = define_field(storage, "customer", FieldType::Text, false, None).unwrap();
+        set_value(storage, "a", &field.id, Some("Acme")).unwrap();
+
+        assert!(delete_field(storage, &field.id, false).is_err());
+        assert_eq!(list_fields(storage).unwrap().len(), 1);
+
+        delete_field(storage, &field.id, true).unwrap();
+        assert!(list_fields(storage).unwrap().is_empty());
+        assert!(storage.list_todos().unwrap()[0].fields.is_empty());
+    });
+}
+
+#[test]
+fn test_values_of_removed_todos_are_dropped_on_sync() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a")]).unwrap();
+    let field = define_field(&storage, "customer", FieldType::Text, false, None).unwrap();
+    set_value(&storage, "a", &field.id, Some("Acme")).unwrap();
+
+    storage.replace_todos(&[todo("b")]).unwrap();
+    // Unused now, so deleting without cascade succeeds.
+    delete_field(&storage, &field.id, false).unwrap();
+}
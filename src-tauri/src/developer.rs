@@ -0,0 +1,391 @@
+//! Developer mode and the SQL console it unlocks, so support can ask for the
+//! result of a query instead of walking the user through a SQLite browser.
+//! The console only reads: each query gets its own read-only connection, and
+//! it must be a single statement that SQLite itself reports as read-only.
+//!
+//! Developer mode takes both `developer_mode = true` in the settings and a
+//! confirmation token for this session, so neither a stray setting nor a
+//! skipped dialog opens the database on its own. Every query that runs is
+//! written to the audit log.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::audit;
+use crate::capabilities::{self, Capability, CapabilityRegistry};
+use crate::clock::Clock;
+use crate::safety::{DestructionScope, DestructiveOperation, SafetyState};
+use crate::settings::{Settings, SettingsReactions, SettingsState};
+use crate::storage::Storage;
+
+#[cfg(test)]
+mod tests;
+
+const AUDIT_SOURCE: &str = "developer";
+pub const DEFAULT_ROW_LIMIT: usize = 500;
+pub const MAX_ROW_LIMIT: usize = 10_000;
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Virtual machine steps between checks of the timeout.
+const PROGRESS_STEPS: i32 = 1_000;
+/// Statements the console accepts, by first keyword.
+const SELECT_KEYWORDS: &[&str] = &["SELECT", "WITH"];
+
+/// Whether this session confirmed developer mode. It is only on while the
+/// setting is too.
+#[derive(Default)]
+pub struct DeveloperMode {
+    confirmed: AtomicBool,
+}
+
+impl DeveloperMode {
+    pub fn is_on(&self, settings: &Settings) -> bool {
+        settings.developer_mode && self.confirmed.load(Ordering::Relaxed)
+    }
+
+    fn set_confirmed(&self, confirmed: bool) {
+        self.confirmed.store(confirmed, Ordering::Relaxed);
+    }
+}
+
+/// What a confirmation token for developer mode is issued for.
+pub fn confirmation_scope() -> DestructionScope {
+    DestructionScope {
+        count: 1,
+        target: Some("developer-mode".to_string()),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum SqlConsoleError {
+    #[error("Developer mode is off")]
+    DeveloperModeOff,
+    #[error("The console runs one statement at a time")]
+    MultipleStatements,
+    #[error("The console only runs SELECT statements")]
+    NotSelect,
+    #[error("Parameter {0} must be null, a boolean, a number or a string")]
+    InvalidParam(usize),
+    #[error("The query ran for more than {0} s and was stopped")]
+    TimedOut(u64),
+    #[error("Query failed: {0}")]
+    Sqlite(String),
+}
+
+impl From<rusqlite::Error> for SqlConsoleError {
+    fn from(e: rusqlite::Error) -> Self {
+        SqlConsoleError::Sqlite(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SqlColumn {
+    pub name: String,
+    /// The type the column was declared with, if it comes from a table.
+    pub decl_type: Option<String>,
+}
+
+/// Rows as JSON values in column order. Blobs are base64 strings.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SqlResult {
+    pub columns: Vec<SqlColumn>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Set when more rows matched than `limit`.
+    pub truncated: bool,
+    pub elapsed_ms: u64,
+}
+
+/// A connection to the store for the console: opened read-only, and with
+/// `query_only` on in case the database is shared with a writer.
+pub fn open_console(storage: &Storage) -> rusqlite::Result<Connection> {
+    let conn = storage.connect_read_only()?;
+    conn.pragma_update(None, "query_only", true)?;
+    Ok(conn)
+}
+
+/// Index just past the comment starting at `i`, or `i` if none starts there.
+fn skip_comment(bytes: &[u8], i: usize) -> usize {
+    match (bytes[i], bytes.get(i + 1)) {
+        (b'-', Some(b'-')) => bytes[i..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(bytes.len(), |end| i + end + 1),
+        (b'/', Some(b'*')) => bytes[i + 2..]
+            .windows(2)
+            .position(|pair| pair == b"*/")
+            .map_or(bytes.len(), |end| i + 2 + end + 2),
+        _ => i,
+    }
+}
+
+/// `query` up to its first `;`, or `None` when another statement follows.
+/// Semicolons in quotes, identifiers and comments don't count.
+fn single_statement(query: &str) -> Option<&str> {
+    let bytes = query.as_bytes();
+    let mut end = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let after_comment = skip_comment(bytes, i);
+        if after_comment > i {
+            i = after_comment;
+            continue;
+        }
+        match bytes[i] {
+            b';' => {
+                end.get_or_insert(i);
+            }
+            b if b.is_ascii_whitespace() => {}
+            _ if end.is_some() => return None,
+            open @ (b'\'' | b'"' | b'`' | b'[') => {
+                let close = if open == b'[' { b']' } else { open };
+                // A doubled quote closes and reopens, which comes to the same.
+                i = bytes[i + 1..]
+                    .iter()
+                    .position(|&b| b == close)
+                    .map_or(bytes.len(), |at| i + 1 + at);
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Some(&query[..end.unwrap_or(query.len())])
+}
+
+/// The first keyword of `statement`, after any comments.
+fn first_keyword(statement: &str) -> String {
+    let bytes = statement.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let after_comment = skip_comment(bytes, i);
+        if after_comment > i {
+            i = after_comment;
+        } else if bytes[i].is_ascii_whitespace() {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    statement[i..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+fn sql_param(index: usize, value: &serde_json::Value) -> Result<Value, SqlConsoleError> {
+    match value {
+        serde_json::Value::Null => Ok(Value::Null),
+        serde_json::Value::Bool(b) => Ok(Value::Integer(*b as i64)),
+        serde_json::Value::Number(n) => n
+            .as_i64()
+            .map(Value::Integer)
+            .or_else(|| n.as_f64().map(Value::Real))
+            .ok_or(SqlConsoleError::InvalidParam(index + 1)),
+        serde_json::Value::String(s) => Ok(Value::Text(s.clone())),
+        _ => Err(SqlConsoleError::InvalidParam(index + 1)),
+    }
+}
+
+fn json_value(value: ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f)
+            .map_or(serde_json::Value::Null, serde_json::Value::Number),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned().into(),
+        ValueRef::Blob(blob) => base64::engine::general_purpose::STANDARD
+            .encode(blob)
+            .into(),
+    }
+}
+
+/// Runs `query` on `conn` with `params` bound to its `?` placeholders,
+/// returning at most `limit` rows. It is refused unless it is a single
+/// statement that `sqlite3_stmt_readonly` reports as read-only and that
+/// returns rows, and it is interrupted after `timeout`.
+pub fn run_query(
+    conn: &Connection,
+    query: &str,
+    params: &[serde_json::Value],
+    limit: usize,
+    timeout: Duration,
+) -> Result<SqlResult, SqlConsoleError> {
+    let statement = single_statement(query).ok_or(SqlConsoleError::MultipleStatements)?;
+    if !SELECT_KEYWORDS.contains(&first_keyword(statement).as_str()) {
+        return Err(SqlConsoleError::NotSelect);
+    }
+    let mut stmt = conn.prepare(statement)?;
+    if !stmt.readonly() || stmt.column_count() == 0 {
+        return Err(SqlConsoleError::NotSelect);
+    }
+    let columns: Vec<SqlColumn> = stmt
+        .columns()
+        .iter()
+        .map(|column| SqlColumn {
+            name: column.name().to_string(),
+            decl_type: column.decl_type().map(str::to_string),
+        })
+        .collect();
+    let values = params
+        .iter()
+        .enumerate()
+        .map(|(index, value)| sql_param(index, value))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Real time, not the app's clock: this bounds how long the query blocks.
+    let started = Instant::now();
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let flag = timed_out.clone();
+    conn.progress_handler(
+        PROGRESS_STEPS,
+        Some(move || {
+            let expired = started.elapsed() > timeout;
+            if expired {
+                flag.store(true, Ordering::Relaxed);
+            }
+            expired
+        }),
+    );
+    let result = collect_rows(&mut stmt, values, columns.len(), limit);
+    conn.progress_handler(0, None::<fn() -> bool>);
+    if timed_out.load(Ordering::Relaxed) {
+        return Err(SqlConsoleError::TimedOut(timeout.as_secs()));
+    }
+    let (rows, truncated) = result?;
+    Ok(SqlResult {
+        columns,
+        rows,
+        truncated,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+type Rows = (Vec<Vec<serde_json::Value>>, bool);
+
+fn collect_rows(
+    stmt: &mut rusqlite::Statement<'_>,
+    values: Vec<Value>,
+    width: usize,
+    limit: usize,
+) -> rusqlite::Result<Rows> {
+    let mut rows = stmt.query(rusqlite::params_from_iter(values))?;
+    let mut collected = Vec::new();
+    while let Some(row) = rows.next()? {
+        if collected.len() == limit {
+            return Ok((collected, true));
+        }
+        collected.push(
+            (0..width)
+                .map(|index| row.get_ref(index).map(json_value))
+                .collect::<rusqlite::Result<_>>()?,
+        );
+    }
+    Ok((collected, false))
+}
+
+/// Turns developer mode off again when the setting is cleared.
+pub fn register_settings_reactions(app: &AppHandle, reactions: &SettingsReactions) {
+    let app = app.clone();
+    reactions.register("developer", &["developer_mode"], move |_, new| {
+        if !new.developer_mode {
+            app.state::<DeveloperMode>().set_confirmed(false);
+        }
+        capabilities::reprobe(&app);
+    });
+}
+
+/// Reports whether developer mode is on, so the UI knows to offer the console.
+pub fn register_capabilities(app: &AppHandle, registry: &CapabilityRegistry) {
+    let app = app.clone();
+    registry.register("developerMode", move || {
+        let Some(settings) = app.try_state::<SettingsState>().map(|s| s.get()) else {
+            return Capability::Available;
+        };
+        if !settings.developer_mode {
+            Capability::Unsupported {
+                reason: "Disabled in settings".to_string(),
+            }
+        } else if !app.state::<DeveloperMode>().is_on(&settings) {
+            Capability::Unsupported {
+                reason: "Not confirmed in this session".to_string(),
+            }
+        } else {
+            Capability::Available
+        }
+    });
+}
+
+/// Turns developer mode on for this session. The setting must already be
+/// on, and `confirmation_token` must come from `request_destruction_token`
+/// for `developerMode` with the scope `{ count: 1, target: "developer-mode" }`.
+#[tauri::command]
+pub fn enable_developer_mode(
+    confirmation_token: String,
+    app: AppHandle,
+    developer: State<'_, DeveloperMode>,
+    safety: State<'_, SafetyState>,
+    settings: State<'_, SettingsState>,
+    storage: State<'_, Storage>,
+) -> Result<(), String> {
+    if !settings.get().developer_mode {
+        return Err("Turn on developer_mode in the settings first".to_string());
+    }
+    safety
+        .consume(
+            &confirmation_token,
+            DestructiveOperation::DeveloperMode,
+            &confirmation_scope(),
+            storage.clock().now_monotonic(),
+        )
+        .map_err(|e| e.to_string())?;
+    developer.set_confirmed(true);
+    let _ = audit::record(&storage, AUDIT_SOURCE, "enable", "developer mode");
+    capabilities::reprobe(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn disable_developer_mode(
+    app: AppHandle,
+    developer: State<'_, DeveloperMode>,
+    storage: State<'_, Storage>,
+) {
+    developer.set_confirmed(false);
+    let _ = audit::record(&storage, AUDIT_SOURCE, "disable", "developer mode");
+    capabilities::reprobe(&app);
+}
+
+/// Runs a read-only query against the store for debugging. `limit` caps the
+/// rows returned, up to [`MAX_ROW_LIMIT`].
+#[tauri::command]
+pub fn execute_readonly_sql(
+    query: String,
+    params: Option<Vec<serde_json::Value>>,
+    limit: Option<usize>,
+    developer: State<'_, DeveloperMode>,
+    settings: State<'_, SettingsState>,
+    storage: State<'_, Storage>,
+) -> Result<SqlResult, String> {
+    if !developer.is_on(&settings.get()) {
+        return Err(SqlConsoleError::DeveloperModeOff.to_string());
+    }
+    let _ = audit::record(&storage, AUDIT_SOURCE, "sql", &query);
+    let conn = open_console(&storage).map_err(|e| format!("Failed to open the database: {}", e))?;
+    run_query(
+        &conn,
+        &query,
+        &params.unwrap_or_default(),
+        limit.unwrap_or(DEFAULT_ROW_LIMIT).min(MAX_ROW_LIMIT),
+        QUERY_TIMEOUT,
+    )
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,196 @@
+use super::*;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn run(conn: &Connection, query: &str) -> Result<SqlResult, SqlConsoleError> {
+    run_query(conn, query, &[], DEFAULT_ROW_LIMIT, TIMEOUT)
+}
+
+fn audit_rows(storage: &Storage) -> i64 {
+    storage
+        .conn()
+        .query_row("SELECT COUNT(*) FROM audit_log", [], |row| row.get(0))
+        .unwrap()
+}
+
+fn with_console(test: impl Fn(&Storage, &Connection)) {
+    crate::storage::for_each_backend(|storage| {
+        audit::record(storage, "test", "first", "a").unwrap();
+        audit::record(storage, "test", "second", "b").unwrap();
+        test(storage, &open_console(storage).unwrap());
+    });
+}
+
+#[test]
+fn test_select_returns_columns_and_rows() {
+    with_console(|_, conn| {
+        let result = run(conn, "SELECT action, id FROM audit_log ORDER BY id").unwrap();
+
+        assert_eq!(
+            result.columns,
+            vec![
+                SqlColumn {
+                    name: "action".to_string(),
+                    decl_type: Some("TEXT".to_string()),
+                },
+                SqlColumn {
+                    name: "id".to_string(),
+                    decl_type: Some("INTEGER".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![serde_json::json!("first"), serde_json::json!(1)],
+                vec![serde_json::json!("second"), serde_json::json!(2)],
+            ]
+        );
+        assert!(!result.truncated);
+    });
+}
+
+#[test]
+fn test_params_are_bound_and_values_converted() {
+    with_console(|_, conn| {
+        let result = run_query(
+            conn,
+            "SELECT ?1 AS text, ?2 AS flag, ?3 AS ratio, NULL AS nothing, x'ff00' AS bytes",
+            &[
+                serde_json::json!("hi"),
+                serde_json::json!(true),
+                serde_json::json!(0.5),
+            ],
+            DEFAULT_ROW_LIMIT,
+            TIMEOUT,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.rows,
+            vec![vec![
+                serde_json::json!("hi"),
+                serde_json::json!(1),
+                serde_json::json!(0.5),
+                serde_json::Value::Null,
+                serde_json::json!("/wA="),
+            ]]
+        );
+    });
+}
+
+#[test]
+fn test_object_params_are_rejected() {
+    with_console(|_, conn| {
+        assert_eq!(
+            run_query(conn, "SELECT ?", &[serde_json::json!({})], 10, TIMEOUT),
+            Err(SqlConsoleError::InvalidParam(1))
+        );
+    });
+}
+
+#[test]
+fn test_rows_past_the_limit_are_cut() {
+    with_console(|_, conn| {
+        let result = run_query(conn, "SELECT id FROM audit_log", &[], 1, TIMEOUT).unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert!(result.truncated);
+    });
+}
+
+#[test]
+fn test_writes_are_rejected() {
+    with_console(|storage, conn| {
+        for query in [
+            "INSERT INTO audit_log (at, source, action, detail) VALUES ('', '', '', '')",
+            "UPDATE audit_log SET detail = 'x'",
+            "DELETE FROM audit_log",
+            "WITH doomed AS (SELECT id FROM audit_log) DELETE FROM audit_log",
+            "DROP TABLE audit_log",
+            "PRAGMA user_version = 99",
+            "PRAGMA journal_mode = DELETE",
+            "ATTACH DATABASE ':memory:' AS other",
+            "BEGIN",
+        ] {
+            assert_eq!(
+                run(conn, query),
+                Err(SqlConsoleError::NotSelect),
+                "{}",
+                query
+            );
+        }
+        assert_eq!(audit_rows(storage), 2);
+        let detail: String = storage
+            .conn()
+            .query_row("SELECT detail FROM audit_log WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(detail, "a");
+    });
+}
+
+#[test]
+fn test_multiple_statements_are_rejected() {
+    with_console(|storage, conn| {
+        for query in [
+            "SELECT 1; DELETE FROM audit_log",
+            "SELECT 1;DELETE FROM audit_log;",
+            "SELECT ';'; DELETE FROM audit_log",
+            "SELECT 1 /* ; */; SELECT 2",
+        ] {
+            assert_eq!(
+                run(conn, query),
+                Err(SqlConsoleError::MultipleStatements),
+                "{}",
+                query
+            );
+        }
+        assert_eq!(audit_rows(storage), 2);
+    });
+}
+
+#[test]
+fn test_semicolons_in_quotes_and_comments_are_not_statements() {
+    with_console(|_, conn| {
+        let result = run(conn, "-- count; twice\nSELECT ';' AS \"a;b\"; -- done;\n").unwrap();
+        assert_eq!(result.columns[0].name, "a;b");
+        assert_eq!(result.rows, vec![vec![serde_json::json!(";")]]);
+    });
+}
+
+#[test]
+fn test_console_connection_cannot_write() {
+    with_console(|storage, conn| {
+        assert!(conn.execute("DELETE FROM audit_log", []).is_err());
+        assert_eq!(audit_rows(storage), 2);
+    });
+}
+
+#[test]
+fn test_long_queries_are_interrupted() {
+    with_console(|_, conn| {
+        let endless =
+            "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n) SELECT COUNT(*) FROM n";
+        assert_eq!(
+            run_query(conn, endless, &[], 10, Duration::from_millis(50)),
+            Err(SqlConsoleError::TimedOut(0))
+        );
+    });
+}
+
+#[test]
+fn test_developer_mode_needs_setting_and_confirmation() {
+    let mode = DeveloperMode::default();
+    let mut settings = Settings {
+        developer_mode: true,
+        ..Default::default()
+    };
+    assert!(!mode.is_on(&settings));
+
+    mode.set_confirmed(true);
+    assert!(mode.is_on(&settings));
+
+    settings.developer_mode = false;
+    assert!(!mode.is_on(&settings));
+}
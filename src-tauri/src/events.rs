@@ -0,0 +1,214 @@
+//! Opt-in recording of emitted events to a JSONL file, for reproducing bugs.
+//! Modules emit through [`emit`] so a running recording sees every event.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::clock::{Clock, ClockState, SharedClock};
+use crate::file_access::{self, PathError};
+use crate::plugins::PluginHost;
+use crate::protocol::{self, EventPayload};
+use crate::quota::{self, WriteError};
+use crate::storage::Storage;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// One line of an event log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub event: String,
+    pub timestamp: DateTime<Utc>,
+    pub payload: serde_json::Value,
+}
+
+impl RecordedEvent {
+    pub fn to_line(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Parses a whole log. Blank lines are skipped; a malformed line fails the
+/// read with its line number, since replaying a partial log would mislead.
+pub fn read_log(reader: impl BufRead) -> Result<Vec<RecordedEvent>, String> {
+    let mut events = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read event log: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid event on line {}: {}", index + 1, e))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+struct Recording {
+    path: PathBuf,
+    writer: LineWriter<File>,
+    clock: SharedClock,
+}
+
+#[derive(Default)]
+pub struct EventRecorder {
+    recording: Mutex<Option<Recording>>,
+}
+
+impl EventRecorder {
+    /// Starts writing to `path`, replacing any recording in progress.
+    pub fn start(&self, path: &Path, clock: SharedClock) -> std::io::Result<()> {
+        let writer = LineWriter::new(File::create(path)?);
+        *self.recording.lock().unwrap_or_else(|e| e.into_inner()) = Some(Recording {
+            path: path.to_path_buf(),
+            writer,
+            clock,
+        });
+        Ok(())
+    }
+
+    /// Ends the recording and returns the file it was written to.
+    pub fn stop(&self) -> Option<PathBuf> {
+        self.recording
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .map(|recording| recording.path)
+    }
+
+    pub fn record(&self, event: &str, payload: impl Serialize) {
+        let mut recording = self.recording.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(recording) = recording.as_mut() else {
+            return;
+        };
+        let line = RecordedEvent {
+            event: event.to_string(),
+            timestamp: recording.clock.now(),
+            payload: serde_json::to_value(payload).unwrap_or_default(),
+        }
+        .to_line();
+        // A full disk must not break the app; the log just ends early.
+        let _ = writeln!(recording.writer, "{}", line);
+    }
+}
+
+/// Emits `event` to every window, recording it first if a recording is on,
+/// and hands it to subscribed plugins. Object payloads carry their protocol
+/// version and, during a command, the command's request id.
+pub fn emit<S: EventPayload>(app: &AppHandle, event: &str, payload: S) -> tauri::Result<()> {
+    deliver(app, event, versioned(&payload)?)
+}
+
+fn versioned<S: EventPayload>(payload: &S) -> tauri::Result<serde_json::Value> {
+    let mut payload = protocol::to_value(payload)?;
+    if trace::in_request() {
+        trace::tag_payload(&mut payload);
+    }
+    Ok(payload)
+}
+
+fn deliver(app: &AppHandle, event: &str, payload: serde_json::Value) -> tauri::Result<()> {
+    if let Some(recorder) = app.try_state::<EventRecorder>() {
+        recorder.record(event, &payload);
+    }
+    if let Some(plugins) = app.try_state::<PluginHost>() {
+        plugins.dispatch(app, event, &payload);
+    }
+    app.emit(event, payload)
+}
+
+/// Last payload of each event sent with [`emit_sticky`].
+#[derive(Default)]
+pub struct StickyEvents {
+    last: Mutex<HashMap<String, serde_json::Value>>,
+}
+
+impl StickyEvents {
+    fn last(&self) -> std::sync::MutexGuard<'_, HashMap<String, serde_json::Value>> {
+        self.last.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Like [`emit`] for events that describe a state, such as a mode being on.
+/// The payload is kept so windows opened later can start from it.
+pub fn emit_sticky<S: EventPayload>(app: &AppHandle, event: &str, payload: S) -> tauri::Result<()> {
+    let payload = versioned(&payload)?;
+    if let Some(sticky) = app.try_state::<StickyEvents>() {
+        sticky.last().insert(event.to_string(), payload.clone());
+    }
+    deliver(app, event, payload)
+}
+
+/// Like [`emit`], but only to the window or webview labelled `target`.
+pub fn emit_to<S: EventPayload>(
+    app: &AppHandle,
+    target: &str,
+    event: &str,
+    payload: S,
+) -> tauri::Result<()> {
+    let payload = versioned(&payload)?;
+    if let Some(recorder) = app.try_state::<EventRecorder>() {
+        recorder.record(event, &payload);
+    }
+    app.emit_to(target, event, payload)
+}
+
+/// The last payload of every sticky event, by event name.
+#[tauri::command]
+pub fn get_sticky_events(sticky: State<'_, StickyEvents>) -> HashMap<String, serde_json::Value> {
+    sticky.last().clone()
+}
+
+#[tauri::command]
+pub fn start_event_recording(
+    app: AppHandle,
+    recorder: State<'_, EventRecorder>,
+    storage: State<'_, Storage>,
+    clock: State<'_, ClockState>,
+) -> Result<String, WriteError> {
+    // Logs go with the store's attachments, so a full quota refuses them.
+    quota::check_attachments(&app, 0)?;
+    let path = storage.files_dir().join(format!(
+        "events-{}.jsonl",
+        clock.now().format("%Y%m%dT%H%M%S")
+    ));
+    recorder
+        .start(&path, clock.shared())
+        .map_err(|e| format!("Failed to start event recording: {}", e))?;
+    Ok(path.display().to_string())
+}
+
+#[tauri::command]
+pub fn stop_event_recording(
+    app: AppHandle,
+    recorder: State<'_, EventRecorder>,
+) -> Result<String, String> {
+    let path = recorder
+        .stop()
+        .ok_or_else(|| "No event recording is running".to_string())?;
+    quota::notify(&app);
+    Ok(path.display().to_string())
+}
+
+/// Re-emits a recorded log in order and returns how many events were sent.
+/// Replayed events bypass the recorder so a replay can't record itself.
+#[tauri::command]
+pub fn replay_event_log(path: String, app: AppHandle) -> Result<usize, PathError> {
+    let path = file_access::check(&app, &path)?;
+    let file =
+        File::open(&path).map_err(|e| format!("Failed to open '{}': {}", path.display(), e))?;
+    let events = read_log(BufReader::new(file))?;
+    for recorded in &events {
+        app.emit(&recorded.event, &recorded.payload)
+            .map_err(|e| format!("Failed to replay '{}': {}", recorded.event, e))?;
+    }
+    Ok(events.len())
+}
@@ -0,0 +1,62 @@
+use super::*;
+use chrono::TimeZone;
+use serde_json::json;
+
+fn sample() -> RecordedEvent {
+    RecordedEvent {
+        event: "todo-updated".to_string(),
+        timestamp: Utc.with_ymd_and_hms(2024, 5, 31, 12, 0, 0).unwrap(),
+        payload: json!({ "todo": { "id": "1", "title": "会議" }, "changedFields": ["title"] }),
+    }
+}
+
+#[test]
+fn test_line_format_is_single_line_json() {
+    let line = sample().to_line();
+
+    assert!(!line.contains('\n'));
+    let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["event"], "todo-updated");
+    assert_eq!(value["timestamp"], "2024-05-31T12:00:00Z");
+    assert_eq!(value["payload"]["todo"]["title"], "会議");
+}
+
+#[test]
+fn test_read_log_round_trips_and_skips_blank_lines() {
+    let mut unit = sample();
+    unit.event = "todo-cache-updated".to_string();
+    unit.payload = serde_json::Value::Null;
+    let log = format!("{}\n\n{}\n", sample().to_line(), unit.to_line());
+
+    let events = read_log(log.as_bytes()).unwrap();
+
+    assert_eq!(events, vec![sample(), unit]);
+}
+
+#[test]
+fn test_read_log_reports_malformed_line() {
+    let log = format!("{}\nnot json\n", sample().to_line());
+
+    let error = read_log(log.as_bytes()).unwrap_err();
+
+    assert!(error.contains("line 2"), "{}", error);
+}
+
+#[test]
+fn test_recorder_writes_only_while_recording() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.jsonl");
+    let recorder = EventRecorder::default();
+
+    recorder.record("before", 1);
+    recorder.start(&path, crate::clock::system()).unwrap();
+    recorder.record("during", json!({ "n": 2 }));
+    assert_eq!(recorder.stop(), Some(path.clone()));
+    recorder.record("after", 3);
+
+    let events = read_log(BufReader::new(File::open(&path).unwrap())).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event, "during");
+    assert_eq!(events[0].payload, json!({ "n": 2 }));
+    assert_eq!(recorder.stop(), None);
+}
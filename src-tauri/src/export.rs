@@ -0,0 +1,443 @@
+//! Serializes the todo list in the formats the importer reads back, and in
+//! signed bundles whose recipients can check who exported them.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+
+use base64::Engine;
+use chrono::{DateTime, Days, NaiveDate, SecondsFormat, SubsecRound, Utc, Weekday};
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+
+use crate::file_access::{self, PathError};
+use crate::import::CSV_FIELD_PREFIX;
+use crate::privacy::{PrivacyState, Redacted};
+use crate::stats::{self, ReviewItem, WeeklyReview};
+use crate::storage::Storage;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+}
+
+/// Serialized fields that change without the todo being edited, left out
+/// of stable exports.
+const VOLATILE_FIELDS: &[&str] = &["order", "rank", "languages", "revision"];
+
+/// `todos` sorted by id with timestamps cut to whole seconds, so exporting
+/// unchanged data twice gives the same bytes.
+fn stable_todos(todos: &[Todo]) -> Vec<Todo> {
+    let mut todos: Vec<Todo> = todos
+        .iter()
+        .cloned()
+        .map(|todo| Todo {
+            scheduled_for: todo.scheduled_for.map(|d| d.trunc_subsecs(0)),
+            created_at: todo.created_at.trunc_subsecs(0),
+            updated_at: todo.updated_at.trunc_subsecs(0),
+            ..todo
+        })
+        .collect();
+    todos.sort_by(|a, b| a.id.cmp(&b.id));
+    todos
+}
+
+/// Writes `todos` as CSV (with a `field:<name>` column per custom field in
+/// use) or as a JSON array of `Todo` objects. `stable` makes the output
+/// diff-friendly: see [`stable_todos`] and [`VOLATILE_FIELDS`].
+pub fn write_todos(
+    todos: &[Todo],
+    format: ExportFormat,
+    stable: bool,
+    mut writer: impl Write,
+) -> Result<(), String> {
+    let json_error = |e: serde_json::Error| format!("Failed to write JSON: {}", e);
+    let write_error = |e: std::io::Error| format!("Failed to write export: {}", e);
+    match format {
+        ExportFormat::Json if stable => {
+            let mut values = Vec::new();
+            for todo in stable_todos(todos) {
+                let mut value = serde_json::to_value(todo).map_err(json_error)?;
+                if let Some(object) = value.as_object_mut() {
+                    for field in VOLATILE_FIELDS {
+                        object.remove(*field);
+                    }
+                }
+                values.push(value);
+            }
+            serde_json::to_writer_pretty(&mut writer, &values).map_err(json_error)?;
+            writer.write_all(b"\n").map_err(write_error)?;
+        }
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, todos).map_err(json_error)?
+        }
+        ExportFormat::Csv if stable => write_csv(&stable_todos(todos), true, &mut writer)?,
+        ExportFormat::Csv => write_csv(todos, false, &mut writer)?,
+    }
+    writer.flush().map_err(write_error)
+}
+
+fn write_csv(todos: &[Todo], stable: bool, writer: impl Write) -> Result<(), String> {
+    let timestamp = |d: DateTime<Utc>| {
+        if stable {
+            d.to_rfc3339_opts(SecondsFormat::Secs, true)
+        } else {
+            d.to_rfc3339()
+        }
+    };
+    let field_names: BTreeSet<&str> = todos
+        .iter()
+        .flat_map(|t| t.fields.keys().map(String::as_str))
+        .collect();
+    let mut csv = csv::Writer::from_writer(writer);
+    let write_error = |e: csv::Error| format!("Failed to write CSV: {}", e);
+
+    let mut header: Vec<String> = [
+        "id",
+        "title",
+        "description",
+        "completed",
+        "priority",
+        "scheduled_for",
+        "created_at",
+        "updated_at",
+        "tags",
+    ]
+    .map(str::to_string)
+    .to_vec();
+    header.extend(
+        field_names
+            .iter()
+            .map(|name| format!("{}{}", CSV_FIELD_PREFIX, name)),
+    );
+    csv.write_record(&header).map_err(write_error)?;
+
+    for todo in todos {
+        let mut record = vec![
+            todo.id.clone(),
+            todo.title.clone(),
+            todo.description.clone().unwrap_or_default(),
+            todo.completed.to_string(),
+            todo.priority.as_str().to_string(),
+            todo.scheduled_for.map(timestamp).unwrap_or_default(),
+            timestamp(todo.created_at),
+            timestamp(todo.updated_at),
+            todo.tags.join(";"),
+        ];
+        record.extend(field_names.iter().map(|name| {
+            todo.fields
+                .get(*name)
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        }));
+        csv.write_record(&record).map_err(write_error)?;
+    }
+    csv.flush()
+        .map_err(|e| format!("Failed to write CSV: {}", e))
+}
+
+pub const SIGNED_EXPORT_FORMAT: &str = "yutodo-signed-export";
+const SIGNED_EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedManifest {
+    pub format: String,
+    pub version: u32,
+    pub count: usize,
+    /// `sha256:` and the hex digest of the `todos` text exactly as written.
+    pub checksum: String,
+    /// Base64 ed25519 signature of `checksum`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// A JSON export with a manifest. `todos` is kept as written, so the checksum
+/// covers the exact bytes in the file rather than a re-serialization.
+#[derive(Serialize, Deserialize)]
+struct SignedBundle {
+    manifest: SignedManifest,
+    todos: Box<RawValue>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Valid,
+    /// The todos or checksum were changed, or signed with another key.
+    Invalid,
+    /// A plain export, or a bundle without a signature.
+    Unsigned,
+}
+
+fn checksum(todos: &RawValue) -> String {
+    format!("sha256:{:x}", Sha256::digest(todos.get().as_bytes()))
+}
+
+/// Writes `todos` as a bundle signed with `key`.
+pub fn write_signed(
+    todos: &[Todo],
+    key: &SigningKey,
+    mut writer: impl Write,
+) -> Result<(), String> {
+    let text =
+        serde_json::to_string_pretty(todos).map_err(|e| format!("Failed to write JSON: {}", e))?;
+    let todos_raw =
+        RawValue::from_string(text).map_err(|e| format!("Failed to write JSON: {}", e))?;
+    let checksum = checksum(&todos_raw);
+    let signature = key.sign(checksum.as_bytes());
+    let bundle = SignedBundle {
+        manifest: SignedManifest {
+            format: SIGNED_EXPORT_FORMAT.to_string(),
+            version: SIGNED_EXPORT_VERSION,
+            count: todos.len(),
+            checksum,
+            signature: Some(base64::engine::general_purpose::STANDARD.encode(signature.to_bytes())),
+        },
+        todos: todos_raw,
+    };
+    serde_json::to_writer_pretty(&mut writer, &bundle)
+        .map_err(|e| format!("Failed to write JSON: {}", e))?;
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write export: {}", e))
+}
+
+/// Checks an export's signature against `key`. Fails only if `bytes` is not
+/// an export at all.
+pub fn verify_signed(bytes: &[u8], key: &VerifyingKey) -> Result<Verification, String> {
+    let value: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Not a JSON export: {}", e))?;
+    if value.is_array() {
+        return Ok(Verification::Unsigned);
+    }
+    let bundle: SignedBundle =
+        serde_json::from_slice(bytes).map_err(|e| format!("Not a signed export: {}", e))?;
+    if bundle.manifest.format != SIGNED_EXPORT_FORMAT {
+        return Err(format!(
+            "Not a signed export: unknown format {}",
+            bundle.manifest.format
+        ));
+    }
+    let Some(signature) = bundle.manifest.signature else {
+        return Ok(Verification::Unsigned);
+    };
+    let signature = base64::engine::general_purpose::STANDARD
+        .decode(signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok());
+    let valid = signature.is_some_and(|signature| {
+        checksum(&bundle.todos) == bundle.manifest.checksum
+            && key
+                .verify(bundle.manifest.checksum.as_bytes(), &signature)
+                .is_ok()
+    });
+    Ok(if valid {
+        Verification::Valid
+    } else {
+        Verification::Invalid
+    })
+}
+
+/// Reads a PKCS#8 PEM private key, as written by
+/// `openssl genpkey -algorithm ed25519`.
+fn read_signing_key(path: &Path) -> Result<SigningKey, String> {
+    let pem = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    SigningKey::from_pkcs8_pem(&pem)
+        .map_err(|e| format!("Invalid ed25519 key {}: {}", path.display(), e))
+}
+
+/// Reads a PEM public key, as written by `openssl pkey -pubout`.
+fn read_verifying_key(path: &Path) -> Result<VerifyingKey, String> {
+    let pem = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    VerifyingKey::from_public_key_pem(&pem)
+        .map_err(|e| format!("Invalid ed25519 public key {}: {}", path.display(), e))
+}
+
+/// [`write_todos`] into a new file at `path`, replacing what was there.
+pub fn write_file(
+    path: &Path,
+    todos: &[Todo],
+    format: ExportFormat,
+    stable: bool,
+) -> Result<(), String> {
+    let file = std::fs::File::create(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    write_todos(todos, format, stable, std::io::BufWriter::new(file))
+}
+
+/// Writes the list to `path`; with `stable`, repeated exports of unchanged
+/// data are byte-identical. Returns how many todos were written; privacy
+/// mode may leave some out.
+#[tauri::command]
+pub fn export_todos(
+    path: String,
+    format: ExportFormat,
+    stable: Option<bool>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<Redacted<usize>, PathError> {
+    let path = file_access::check(&app, &path)?;
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    let todos = privacy.get().apply(todos);
+    write_file(&path, &todos.value, format, stable.unwrap_or(false))?;
+    Ok(Redacted {
+        value: todos.value.len(),
+        warning: todos.warning,
+    })
+}
+
+/// Returns how many todos were written; privacy mode may leave some out.
+#[tauri::command]
+pub fn export_signed(
+    path: String,
+    key_path: String,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<Redacted<usize>, PathError> {
+    let path = file_access::check(&app, &path)?;
+    let key = read_signing_key(&file_access::check(&app, &key_path)?)?;
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    let todos = privacy.get().apply(todos);
+    let file = std::fs::File::create(&path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    write_signed(&todos.value, &key, std::io::BufWriter::new(file))?;
+    Ok(Redacted {
+        value: todos.value.len(),
+        warning: todos.warning,
+    })
+}
+
+/// `true` for a valid signature and `false` for a tampered or foreign one.
+/// Exports without a signature are an error, so they can't pass as invalid.
+#[tauri::command]
+pub fn verify_signed_export(
+    path: String,
+    pubkey_path: String,
+    app: AppHandle,
+) -> Result<bool, PathError> {
+    let path = file_access::check(&app, &path)?;
+    let key = read_verifying_key(&file_access::check(&app, &pubkey_path)?)?;
+    let bytes =
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    match verify_signed(&bytes, &key)? {
+        Verification::Valid => Ok(true),
+        Verification::Invalid => Ok(false),
+        Verification::Unsigned => Err(format!("{} has no signature", path.display()).into()),
+    }
+}
+
+/// Date format of weekly reviews unless the window passes its locale's.
+pub const REVIEW_DATE_FORMAT: &str = "%Y-%m-%d";
+
+fn review_items(out: &mut String, items: &[ReviewItem], line: impl Fn(&ReviewItem) -> String) {
+    if items.is_empty() {
+        out.push_str("_None_\n");
+    }
+    for item in items {
+        let _ = writeln!(out, "- {}", line(item));
+    }
+}
+
+/// Renders `review` as Markdown for pasting into a journal, with dates in
+/// the strftime `date_format`.
+pub fn render_weekly_review(review: &WeeklyReview, date_format: &str) -> String {
+    let date = |date: NaiveDate| date.format(date_format).to_string();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# Weekly review: {} – {}\n",
+        date(review.starts_on),
+        date(review.starts_on + Days::new(6))
+    );
+
+    let _ = writeln!(out, "## Completed ({})\n", review.completed);
+    review_items(&mut out, &review.completed_todos, |item| item.title.clone());
+
+    let _ = writeln!(out, "\n## Carried over ({})\n", review.overdue_carry_over);
+    review_items(&mut out, &review.carried_over, |item| {
+        match item.scheduled_for {
+            Some(due) => format!("{} (due {})", item.title, date(due.date_naive())),
+            None => item.title.clone(),
+        }
+    });
+
+    let _ = writeln!(out, "\n## Stats\n");
+    let _ = writeln!(out, "- Created: {}", review.created);
+    let _ = writeln!(
+        out,
+        "- Focus: {} min in {} session(s)",
+        review.focus_secs / 60,
+        review.focus_sessions
+    );
+    let tags: Vec<String> = review
+        .top_tags
+        .iter()
+        .map(|tag| format!("{} ({})", tag.tag, tag.todo_count))
+        .collect();
+    if !tags.is_empty() {
+        let _ = writeln!(out, "- Top tags: {}", tags.join(", "));
+    }
+    out
+}
+
+/// Writes the weekly review of the week containing `week_of` to `path` as
+/// Markdown. `date_format` is a strftime pattern, e.g. the UI locale's;
+/// the week options are those of [`stats::weekly_review`].
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn export_weekly_review(
+    week_of: NaiveDate,
+    path: String,
+    date_format: Option<String>,
+    week_starts_on: Option<Weekday>,
+    utc_offset_minutes: Option<i32>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<(), PathError> {
+    let path = file_access::check(&app, &path)?;
+    let date_format = date_format.as_deref().unwrap_or(REVIEW_DATE_FORMAT);
+    // Formatting fails on unknown specifiers and on ones a date lacks.
+    if write!(String::new(), "{}", NaiveDate::MIN.format(date_format)).is_err() {
+        return Err(format!("Invalid date format '{}'", date_format).into());
+    }
+    let review = stats::review_visible_week(
+        &storage,
+        &privacy,
+        week_of,
+        week_starts_on,
+        utc_offset_minutes,
+    )?;
+    std::fs::write(&path, render_weekly_review(&review, date_format))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
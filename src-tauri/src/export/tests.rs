@@ -0,0 +1,197 @@
+use super::*;
+
+use chrono::{TimeZone, Utc};
+
+use crate::import::{self, ImportFormat};
+use crate::types::{FieldValue, Priority};
+
+fn sample() -> Vec<Todo> {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    let mut with_field = Todo {
+        description: Some("multi\nline".to_string()),
+        completed: true,
+        priority: Priority::High,
+        scheduled_for: Some(Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap()),
+        tags: vec!["work".to_string(), "q3".to_string()],
+        ..Todo::sample("a", "Plan \"launch\", phase 1", created)
+    };
+    with_field
+        .fields
+        .insert("size".to_string(), FieldValue::Text("L".to_string()));
+    let plain = Todo {
+        id: "b".to_string(),
+        title: "Buy milk".to_string(),
+        description: None,
+        completed: false,
+        priority: Priority::Low,
+        scheduled_for: None,
+        tags: Vec::new(),
+        languages: Default::default(),
+        fields: Default::default(),
+        ..with_field.clone()
+    };
+    vec![with_field, plain]
+}
+
+#[test]
+fn test_exports_read_back_through_importer() {
+    let dir = tempfile::tempdir().unwrap();
+    for (format, import_format, file) in [
+        (ExportFormat::Csv, ImportFormat::Csv, "todos.csv"),
+        (ExportFormat::Json, ImportFormat::Json, "todos.json"),
+    ] {
+        let path = dir.path().join(file);
+        write_todos(
+            &sample(),
+            format,
+            false,
+            std::fs::File::create(&path).unwrap(),
+        )
+        .unwrap();
+
+        let read = import::read_todos(&path, import_format).unwrap();
+        let expected: Vec<Todo> = sample()
+            .into_iter()
+            .map(|t| Todo {
+                languages: Default::default(),
+                fields: Default::default(),
+                ..t
+            })
+            .collect();
+        assert_eq!(read, expected, "{:?}", format);
+    }
+
+    let mut csv = Vec::new();
+    write_todos(&sample(), ExportFormat::Csv, false, &mut csv).unwrap();
+    let header = String::from_utf8(csv).unwrap();
+    assert!(header.starts_with(
+        "id,title,description,completed,priority,scheduled_for,created_at,updated_at,tags,field:size\n"
+    ));
+}
+
+fn signed(key: &SigningKey) -> String {
+    let mut bundle = Vec::new();
+    write_signed(&sample(), key, &mut bundle).unwrap();
+    String::from_utf8(bundle).unwrap()
+}
+
+#[test]
+fn test_signed_export_verifies_with_its_key() {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let bundle = signed(&key);
+    assert_eq!(
+        verify_signed(bundle.as_bytes(), &key.verifying_key()),
+        Ok(Verification::Valid)
+    );
+
+    let other = SigningKey::from_bytes(&[8; 32]);
+    assert_eq!(
+        verify_signed(bundle.as_bytes(), &other.verifying_key()),
+        Ok(Verification::Invalid)
+    );
+}
+
+#[test]
+fn test_tampered_signed_export_fails_verification() {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let tampered = signed(&key).replace("Buy milk", "Buy wine");
+    assert_eq!(
+        verify_signed(tampered.as_bytes(), &key.verifying_key()),
+        Ok(Verification::Invalid)
+    );
+}
+
+#[test]
+fn test_unsigned_exports_are_reported_as_such() {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let mut plain = Vec::new();
+    write_todos(&sample(), ExportFormat::Json, false, &mut plain).unwrap();
+    assert_eq!(
+        verify_signed(&plain, &key.verifying_key()),
+        Ok(Verification::Unsigned)
+    );
+
+    let mut bundle: serde_json::Value = serde_json::from_str(&signed(&key)).unwrap();
+    bundle["manifest"]
+        .as_object_mut()
+        .unwrap()
+        .remove("signature");
+    assert_eq!(
+        verify_signed(bundle.to_string().as_bytes(), &key.verifying_key()),
+        Ok(Verification::Unsigned)
+    );
+}
+
+#[test]
+fn test_stable_exports_survive_a_reimport_byte_for_byte() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut todos = sample();
+    todos.reverse();
+    todos[0].created_at += chrono::Duration::nanoseconds(123_456);
+    todos[0].revision = 7;
+    todos[0].rank = Some("m".to_string());
+
+    for (format, file) in [
+        (ExportFormat::Json, "todos.json"),
+        (ExportFormat::Csv, "todos.csv"),
+    ] {
+        let mut first = Vec::new();
+        write_todos(&todos, format, true, &mut first).unwrap();
+        assert!(first.ends_with(b"\n"));
+        let path = dir.path().join(file);
+        std::fs::write(&path, &first).unwrap();
+
+        let scratch = Storage::open_in_memory().unwrap();
+        crate::custom_fields::define_field(
+            &scratch,
+            "size",
+            crate::custom_fields::FieldType::Text,
+            false,
+            None,
+        )
+        .unwrap();
+        let checkpoint =
+            import::begin_import(&scratch, &path, import::ConflictPolicy::Skip).unwrap();
+        import::run_import(
+            &scratch,
+            checkpoint,
+            &crate::validation_rules::RuleSet::default(),
+            1000,
+            |_| false,
+            |_| {},
+        )
+        .unwrap();
+
+        let mut second = Vec::new();
+        write_todos(&scratch.list_todos().unwrap(), format, true, &mut second).unwrap();
+        assert_eq!(
+            String::from_utf8(first).unwrap(),
+            String::from_utf8(second).unwrap(),
+            "{:?}",
+            format
+        );
+    }
+}
+
+#[test]
+fn test_weekly_review_renders_as_markdown_sections() {
+    let week_of = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+    let range = stats::week_range(week_of, Weekday::Mon, &Utc);
+    let mut todos = sample();
+    todos[1].scheduled_for = Some(Utc.with_ymd_and_hms(2024, 5, 30, 9, 0, 0).unwrap());
+    let review = stats::assemble_review(
+        &todos,
+        &Default::default(),
+        stats::week_start(week_of, Weekday::Mon),
+        range,
+    );
+
+    let markdown = render_weekly_review(&review, "%d/%m/%Y");
+    assert!(markdown.starts_with("# Weekly review: 27/05/2024 – 02/06/2024\n"));
+    for header in ["## Completed (1)", "## Carried over (1)", "## Stats"] {
+        assert!(markdown.contains(header), "{}", markdown);
+    }
+    assert!(markdown.contains("- Plan \"launch\", phase 1\n"));
+    assert!(markdown.contains("- Buy milk (due 30/05/2024)\n"));
+    assert!(markdown.contains("- Top tags: q3 (1), work (1)\n"));
+}
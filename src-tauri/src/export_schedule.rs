@@ -0,0 +1,677 @@
+//! Exports written to a folder on a schedule, e.g. a fresh `todos.csv` in a
+//! shared folder every night, or a static site republished every few
+//! minutes. Schedules are kept in the store and run by the background
+//! scheduler at its next wake-up after they come due; every run goes into
+//! the schedule's history.
+//!
+//! A failed run, e.g. because the destination is a network share that is
+//! offline, emits `export-schedule-failed` and is retried after each of
+//! [`RETRY_DELAY_MINUTES`] before the schedule waits for its next regular
+//! run. Nothing runs while a restore or migration is in progress: the store
+//! then doesn't hold the whole list, and the export would replace a good
+//! file with a partial one.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use rusqlite::{params, Connection, OptionalExtension};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::events;
+use crate::export::{self, ExportFormat};
+use crate::file_access::{self, PathError};
+use crate::privacy::PrivacyState;
+use crate::progress::ProgressRegistry;
+use crate::protocol::EventPayload;
+use crate::retry::{self, LastFailed, Operation};
+use crate::site::{self, SiteConfig};
+use crate::storage::recovery::RecoveryState;
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+/// How long after a failed run each retry comes; one retry per entry.
+const RETRY_DELAY_MINUTES: &[i64] = &[5, 15, 60];
+/// Runs kept in each schedule's history.
+const HISTORY_LIMIT: usize = 50;
+/// Kinds of [`ProgressRegistry`] operations that hold scheduled exports back.
+const PAUSING_KINDS: &[&str] = &["migration"];
+/// Placeholders of file name templates, with what they stand for.
+const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("date", "%Y-%m-%d"),
+    ("time", "%H%M%S"),
+    ("year", "%Y"),
+    ("month", "%m"),
+    ("day", "%d"),
+];
+
+/// When a schedule runs, in local time; `Every` counts from the previous run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ExportInterval {
+    Daily { time: NaiveTime },
+    Weekly { weekday: Weekday, time: NaiveTime },
+    Every { minutes: u32 },
+}
+
+/// What a schedule writes into its destination.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ExportTarget {
+    /// A file of todos, as `format`, `filename` and `naming` say.
+    #[default]
+    Todos,
+    /// A static site, with the destination as its output folder, which is
+    /// created if missing; the file settings don't apply. See [`site`].
+    StaticSite {
+        #[serde(default = "site::default_title")]
+        title: String,
+        #[serde(default)]
+        smart_lists: Vec<String>,
+        #[serde(default = "site::default_refresh_secs")]
+        refresh_secs: u64,
+    },
+}
+
+/// What a run does with the file of the previous one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportNaming {
+    /// Replaces it when the file name comes out the same.
+    #[default]
+    Overwrite,
+    /// Keeps it: the time of the run goes into every file name.
+    Timestamped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportScheduleConfig {
+    #[serde(default)]
+    pub target: ExportTarget,
+    pub format: ExportFormat,
+    /// As `export_todos` takes it.
+    #[serde(default)]
+    pub stable: bool,
+    /// The folder the files go into; it isn't created if missing.
+    pub destination: PathBuf,
+    /// The file name, with `{date}`, `{time}`, `{year}`, `{month}` and
+    /// `{day}` standing for when the export runs, e.g. `todos-{date}.csv`.
+    #[serde(default)]
+    pub filename: String,
+    pub interval: ExportInterval,
+    #[serde(default)]
+    pub naming: ExportNaming,
+}
+
+impl ExportScheduleConfig {
+    /// A schedule that publishes `config` every `minutes`.
+    pub fn static_site(config: SiteConfig, minutes: u32) -> Self {
+        Self {
+            target: ExportTarget::StaticSite {
+                title: config.title,
+                smart_lists: config.smart_lists,
+                refresh_secs: config.refresh_secs,
+            },
+            format: ExportFormat::Json,
+            stable: false,
+            destination: config.output_dir,
+            filename: String::new(),
+            interval: ExportInterval::Every { minutes },
+            naming: ExportNaming::Overwrite,
+        }
+    }
+
+    /// The site this schedule publishes, if it publishes one.
+    pub fn site(&self) -> Option<SiteConfig> {
+        match &self.target {
+            ExportTarget::Todos => None,
+            ExportTarget::StaticSite {
+                title,
+                smart_lists,
+                refresh_secs,
+            } => Some(SiteConfig {
+                output_dir: self.destination.clone(),
+                title: title.clone(),
+                smart_lists: smart_lists.clone(),
+                refresh_secs: *refresh_secs,
+            }),
+        }
+    }
+
+    /// The folder that must be there for a run to write: the destination,
+    /// or for a site, which creates its own folder, the one that holds it.
+    fn required_dir(&self) -> &Path {
+        match self.target {
+            ExportTarget::Todos => &self.destination,
+            ExportTarget::StaticSite { .. } => {
+                self.destination.parent().unwrap_or(&self.destination)
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.interval == (ExportInterval::Every { minutes: 0 }) {
+            return Err("Interval must be at least one minute".to_string());
+        }
+        if self.site().is_some() {
+            return Ok(());
+        }
+        let name = render_template(&self.filename, NaiveDateTime::default())?;
+        if name.trim().is_empty() || name == "." || name == ".." {
+            return Err(format!("'{}' is not a file name", self.filename));
+        }
+        if name.contains(['/', '\\']) {
+            return Err(format!(
+                "'{}' is not a file name; the folder goes in the destination",
+                self.filename
+            ));
+        }
+        Ok(())
+    }
+
+    /// Where a run at `at`, local time, writes. A site is written into the
+    /// destination itself.
+    pub fn path_at(&self, at: NaiveDateTime) -> Result<PathBuf, String> {
+        if self.site().is_some() {
+            return Ok(self.destination.clone());
+        }
+        let name = render_template(&self.filename, at)?;
+        let name = match self.naming {
+            ExportNaming::Overwrite => name,
+            ExportNaming::Timestamped => timestamped(&name, at),
+        };
+        Ok(self.destination.join(name))
+    }
+}
+
+/// `template` with its placeholders filled in for `at`.
+pub fn render_template(template: &str, at: NaiveDateTime) -> Result<String, String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in '{}'", template))?;
+        let (_, format) = PLACEHOLDERS
+            .iter()
+            .find(|(placeholder, _)| *placeholder == &after[..end])
+            .ok_or_else(|| {
+                format!(
+                    "Unknown placeholder '{{{}}}' in '{}'",
+                    &after[..end],
+                    template
+                )
+            })?;
+        name.push_str(&at.format(format).to_string());
+        rest = &after[end + 1..];
+    }
+    name.push_str(rest);
+    Ok(name)
+}
+
+/// `name` with the date and time of `at` before its extension.
+fn timestamped(name: &str, at: NaiveDateTime) -> String {
+    let stamp = at.format("%Y%m%d-%H%M%S");
+    match name.rfind('.').filter(|&dot| dot > 0) {
+        Some(dot) => format!("{}-{}{}", &name[..dot], stamp, &name[dot..]),
+        None => format!("{}-{}", name, stamp),
+    }
+}
+
+/// The first run of `interval` strictly after `after`, with times in `tz`.
+/// A time that doesn't exist on some day, as in a daylight saving gap,
+/// skips that day.
+pub fn next_run<Tz: TimeZone>(
+    interval: &ExportInterval,
+    after: DateTime<Utc>,
+    tz: &Tz,
+) -> Option<DateTime<Utc>> {
+    let (weekday, time) = match interval {
+        ExportInterval::Daily { time } => (None, *time),
+        ExportInterval::Weekly { weekday, time } => (Some(*weekday), *time),
+        ExportInterval::Every { minutes } => {
+            return Some(after + chrono::Duration::minutes(i64::from(*minutes)))
+        }
+    };
+    let today = after.with_timezone(tz).date_naive();
+    (0..=14)
+        .filter_map(|days| today.checked_add_days(Days::new(days)))
+        .filter(|date| weekday.is_none_or(|weekday| date.weekday() == weekday))
+        .filter_map(|date| {
+            tz.from_local_datetime(&date.and_time(time))
+                .earliest()
+                .map(|run| run.with_timezone(&Utc))
+        })
+        .find(|run| *run > after)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSchedule {
+    pub id: String,
+    pub config: ExportScheduleConfig,
+    pub next_run: Option<DateTime<Utc>>,
+    /// When the last failed run is tried again, if a retry is queued.
+    pub retry_at: Option<DateTime<Utc>>,
+    /// Retries the last regular run has had.
+    pub retries: u32,
+    pub last_run: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ExportSchedule {
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_run.is_some_and(|next| next <= now)
+            || self.retry_at.is_some_and(|retry| retry <= now)
+    }
+}
+
+/// One run of a schedule, as `get_export_schedule_history` lists it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportRun {
+    pub schedule_id: String,
+    pub ran_at: DateTime<Utc>,
+    /// The file it wrote, or was to write.
+    pub path: PathBuf,
+    /// 0 for a regular run, n for its nth retry.
+    pub retry: u32,
+    /// Todos written, if it succeeded; privacy mode may leave some out.
+    pub exported: Option<usize>,
+    pub error: Option<String>,
+}
+
+fn json_column<T: for<'de> Deserialize<'de>>(text: String, column: usize) -> rusqlite::Result<T> {
+    serde_json::from_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+pub fn add_schedule(
+    conn: &Connection,
+    config: ExportScheduleConfig,
+    now: DateTime<Utc>,
+    tz: &impl TimeZone,
+) -> Result<ExportSchedule, String> {
+    config.validate()?;
+    let schedule = ExportSchedule {
+        id: uuid::Uuid::new_v4().to_string(),
+        next_run: next_run(&config.interval, now, tz),
+        config,
+        retry_at: None,
+        retries: 0,
+        last_run: None,
+        created_at: now,
+    };
+    conn.execute(
+        "INSERT INTO export_schedules (id, config, next_run, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            schedule.id,
+            serde_json::to_string(&schedule.config).map_err(|e| e.to_string())?,
+            schedule.next_run,
+            schedule.created_at,
+        ],
+    )
+    .map_err(|e| format!("Failed to schedule export: {}", e))?;
+    Ok(schedule)
+}
+
+const SCHEDULE_COLUMNS: &str = "id, config, next_run, retry_at, retries, last_run, created_at";
+
+fn schedule_from_row(row: &rusqlite::Row) -> rusqlite::Result<ExportSchedule> {
+    Ok(ExportSchedule {
+        id: row.get(0)?,
+        config: json_column(row.get(1)?, 1)?,
+        next_run: row.get(2)?,
+        retry_at: row.get(3)?,
+        retries: row.get(4)?,
+        last_run: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+/// Export schedules, the oldest first.
+pub fn list_schedules(conn: &Connection) -> rusqlite::Result<Vec<ExportSchedule>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM export_schedules ORDER BY created_at, id",
+        SCHEDULE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], schedule_from_row)?;
+    rows.collect()
+}
+
+pub fn find_schedule(conn: &Connection, id: &str) -> rusqlite::Result<Option<ExportSchedule>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM export_schedules WHERE id = ?1",
+            SCHEDULE_COLUMNS
+        ),
+        [id],
+        schedule_from_row,
+    )
+    .optional()
+}
+
+/// Deletes a schedule with its history, which also drops its queued retry.
+pub fn remove_schedule(conn: &Connection, id: &str) -> Result<(), String> {
+    let deleted = conn
+        .execute("DELETE FROM export_schedules WHERE id = ?1", [id])
+        .and_then(|deleted| {
+            conn.execute("DELETE FROM export_runs WHERE schedule_id = ?1", [id])?;
+            Ok(deleted)
+        })
+        .map_err(|e| format!("Failed to remove export schedule: {}", e))?;
+    if deleted == 0 {
+        return Err(format!("Failed to remove: no export schedule '{}'", id));
+    }
+    Ok(())
+}
+
+/// The runs of schedule `id`, the latest first.
+pub fn history(conn: &Connection, id: &str) -> rusqlite::Result<Vec<ExportRun>> {
+    let mut stmt = conn.prepare(
+        "SELECT schedule_id, ran_at, path, retry, exported, error FROM export_runs
+         WHERE schedule_id = ?1 ORDER BY ran_at DESC, rowid DESC",
+    )?;
+    let rows = stmt.query_map([id], |row| {
+        Ok(ExportRun {
+            schedule_id: row.get(0)?,
+            ran_at: row.get(1)?,
+            path: PathBuf::from(row.get::<_, String>(2)?),
+            retry: row.get(3)?,
+            exported: row.get(4)?,
+            error: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// [`export::write_todos`] into `path` through a temporary file beside it,
+/// so a run that fails halfway leaves the previous export whole.
+pub fn write_export(
+    path: &Path,
+    todos: &[Todo],
+    format: ExportFormat,
+    stable: bool,
+) -> Result<(), String> {
+    let failed = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir).map_err(failed)?;
+    export::write_todos(
+        todos,
+        format,
+        stable,
+        std::io::BufWriter::new(file.as_file_mut()),
+    )?;
+    file.as_file().sync_all().map_err(failed)?;
+    file.persist(path).map_err(|e| failed(e.error))?;
+    Ok(())
+}
+
+/// Runs `schedule` at `now` through `export`, which writes the file at the
+/// path it is given and returns how many todos it wrote. Moves the schedule
+/// on to its next regular run if this was it, and queues a retry if it
+/// failed. Returns the run with when it is retried.
+pub fn run_schedule(
+    storage: &Storage,
+    schedule: &ExportSchedule,
+    now: DateTime<Utc>,
+    tz: &impl TimeZone,
+    export: impl FnOnce(&ExportScheduleConfig, &Path) -> Result<usize, String>,
+) -> rusqlite::Result<(ExportRun, Option<DateTime<Utc>>)> {
+    let regular = schedule.next_run.is_some_and(|next| next <= now);
+    let retry = if regular { 0 } else { schedule.retries + 1 };
+    let config = &schedule.config;
+    let local = now.with_timezone(tz).naive_local();
+    let path = config.path_at(local);
+    let result = path.as_ref().map_err(Clone::clone).and_then(|path| {
+        if !config.required_dir().is_dir() {
+            return Err(format!(
+                "Destination {} is unavailable",
+                config.required_dir().display()
+            ));
+        }
+        // Runs without holding the connection; loading the todos needs it.
+        export(config, path)
+    });
+    let retry_at = match &result {
+        Err(_) => RETRY_DELAY_MINUTES
+            .get(retry as usize)
+            .map(|minutes| now + chrono::Duration::minutes(*minutes)),
+        Ok(_) => None,
+    };
+    let next = if regular {
+        next_run(&config.interval, now, tz)
+    } else {
+        schedule.next_run
+    };
+    let run = ExportRun {
+        schedule_id: schedule.id.clone(),
+        ran_at: now,
+        path: path.unwrap_or_else(|_| config.destination.clone()),
+        retry,
+        exported: result.as_ref().ok().copied(),
+        error: result.err(),
+    };
+    let conn = storage.conn();
+    let updated = conn.execute(
+        "UPDATE export_schedules SET next_run = ?2, retry_at = ?3, retries = ?4, last_run = ?5
+         WHERE id = ?1",
+        params![schedule.id, next, retry_at, retry, now],
+    )?;
+    // Removed while it ran.
+    if updated == 0 {
+        return Ok((run, None));
+    }
+    conn.execute(
+        "INSERT INTO export_runs (schedule_id, ran_at, path, retry, exported, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            run.schedule_id,
+            run.ran_at,
+            run.path.to_string_lossy(),
+            run.retry,
+            run.exported,
+            run.error,
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM export_runs WHERE schedule_id = ?1 AND rowid NOT IN (
+            SELECT rowid FROM export_runs WHERE schedule_id = ?1
+            ORDER BY ran_at DESC, rowid DESC LIMIT ?2)",
+        params![run.schedule_id, HISTORY_LIMIT],
+    )?;
+    Ok((run, retry_at))
+}
+
+/// Runs the schedules due at `now`, regular runs and retries alike; see
+/// [`run_schedule`].
+pub fn run_due(
+    storage: &Storage,
+    now: DateTime<Utc>,
+    tz: &impl TimeZone,
+    mut export: impl FnMut(&ExportScheduleConfig, &Path) -> Result<usize, String>,
+) -> rusqlite::Result<Vec<(ExportRun, Option<DateTime<Utc>>)>> {
+    let due: Vec<ExportSchedule> = list_schedules(&storage.conn())?
+        .into_iter()
+        .filter(|schedule| schedule.is_due(now))
+        .collect();
+    due.iter()
+        .map(|schedule| run_schedule(storage, schedule, now, tz, &mut export))
+        .collect()
+}
+
+fn export(app: &AppHandle, config: &ExportScheduleConfig, path: &Path) -> Result<usize, String> {
+    // Checked again, in case the allowed directories changed since.
+    let path = file_access::check(app, path).map_err(|e| e.to_string())?;
+    let storage = app.state::<Storage>();
+    if let Some(site) = config.site() {
+        let data_dir = storage::data_dir(app).map_err(|e| e.to_string())?;
+        return site::publish(&storage, &site, &data_dir);
+    }
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    let todos = app.state::<PrivacyState>().get().apply(todos);
+    write_export(&path, &todos.value, config.format, config.stable)?;
+    Ok(todos.value.len())
+}
+
+/// Whether a restore or migration is in progress.
+fn paused(app: &AppHandle) -> bool {
+    app.state::<RecoveryState>().is_pending()
+        || app
+            .state::<ProgressRegistry>()
+            .list()
+            .iter()
+            .any(|operation| PAUSING_KINDS.contains(&operation.kind))
+}
+
+/// Payload of `export-schedule-failed`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportScheduleFailed {
+    pub id: String,
+    pub path: PathBuf,
+    pub error: String,
+    /// `None` once the retries are used up.
+    pub retry_at: Option<DateTime<Utc>>,
+}
+
+impl EventPayload for ExportScheduleFailed {}
+
+/// Emits `export-schedule-failed` if `run` failed, and returns why.
+fn announce_failure(
+    app: &AppHandle,
+    run: ExportRun,
+    retry_at: Option<DateTime<Utc>>,
+) -> Option<String> {
+    let error = run.error?;
+    trace::log(format!(
+        "Scheduled export {} failed: {}",
+        run.schedule_id, error
+    ));
+    let _ = events::emit(
+        app,
+        "export-schedule-failed",
+        ExportScheduleFailed {
+            id: run.schedule_id,
+            path: run.path,
+            error: error.clone(),
+            retry_at,
+        },
+    );
+    Some(error)
+}
+
+/// Runs the export schedules that are due, unless the store is read-only
+/// or a restore or migration is in progress. Called by the scheduler loop.
+pub fn run_due_exports(app: &AppHandle) {
+    let storage = app.state::<Storage>();
+    if storage.newer_schema().is_some() || paused(app) {
+        return;
+    }
+    let now = storage.clock().now();
+    let ran = match run_due(&storage, now, &Local, |config, path| {
+        export(app, config, path)
+    }) {
+        Ok(ran) => ran,
+        Err(e) => {
+            trace::log(format!("Failed to run export schedules: {}", e));
+            return;
+        }
+    };
+    for (run, retry_at) in ran {
+        let id = run.schedule_id.clone();
+        if let Some(error) = announce_failure(app, run, retry_at) {
+            retry::record(app, Operation::RunExportSchedule { id }, error);
+        }
+    }
+}
+
+/// Runs schedule `id` now, whether or not it is due.
+pub fn run_now(app: &AppHandle, id: &str) -> Result<(), String> {
+    if paused(app) {
+        return Err("Failed to export: a restore or migration is in progress".to_string());
+    }
+    let storage = app.state::<Storage>();
+    let schedule = find_schedule(&storage.conn(), id)
+        .map_err(|e| format!("Failed to load export schedule: {}", e))?
+        .ok_or_else(|| format!("No export schedule '{}'", id))?;
+    let (run, retry_at) = run_schedule(
+        &storage,
+        &schedule,
+        storage.clock().now(),
+        &Local,
+        |config, path| export(app, config, path),
+    )
+    .map_err(|e| format!("Failed to run export schedule: {}", e))?;
+    announce_failure(app, run, retry_at).map_or(Ok(()), Err)
+}
+
+/// Schedules a recurring export into `config.destination`. It first runs
+/// at the next time `config.interval` names.
+#[tauri::command]
+pub fn add_export_schedule(
+    config: ExportScheduleConfig,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<ExportSchedule, PathError> {
+    file_access::check(&app, &config.destination)?;
+    Ok(add_schedule(
+        &storage.conn(),
+        config,
+        storage.clock().now(),
+        &Local,
+    )?)
+}
+
+#[tauri::command]
+pub fn list_export_schedules(storage: State<'_, Storage>) -> Result<Vec<ExportSchedule>, String> {
+    list_schedules(&storage.conn()).map_err(|e| format!("Failed to list export schedules: {}", e))
+}
+
+#[tauri::command]
+pub fn run_export_schedule_now(id: String, app: AppHandle) -> Result<(), String> {
+    run_now(&app, &id)
+        .inspect_err(|e| retry::record(&app, Operation::RunExportSchedule { id: id.clone() }, e))
+}
+
+/// The last runs of schedule `id`, the latest first.
+#[tauri::command]
+pub fn get_export_schedule_history(
+    id: String,
+    storage: State<'_, Storage>,
+) -> Result<Vec<ExportRun>, String> {
+    let conn = storage.conn();
+    let failed = |e: rusqlite::Error| format!("Failed to load export history: {}", e);
+    if find_schedule(&conn, &id).map_err(failed)?.is_none() {
+        return Err(format!("No export schedule '{}'", id));
+    }
+    history(&conn, &id).map_err(failed)
+}
+
+/// Removes schedule `id` with its history and any retry queued for it.
+#[tauri::command]
+pub fn remove_export_schedule(
+    id: String,
+    storage: State<'_, Storage>,
+    last_failed: State<'_, LastFailed>,
+) -> Result<(), String> {
+    remove_schedule(&storage.conn(), &id)?;
+    last_failed.forget(&Operation::RunExportSchedule { id });
+    Ok(())
+}
@@ -0,0 +1,276 @@
+use super::*;
+
+use chrono::{FixedOffset, NaiveDate};
+
+fn at(text: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(text).unwrap().into()
+}
+
+fn six_pm() -> NaiveTime {
+    NaiveTime::from_hms_opt(18, 0, 0).unwrap()
+}
+
+fn config(destination: &Path, filename: &str, naming: ExportNaming) -> ExportScheduleConfig {
+    ExportScheduleConfig {
+        format: ExportFormat::Csv,
+        stable: true,
+        destination: destination.to_path_buf(),
+        filename: filename.to_string(),
+        interval: ExportInterval::Daily { time: six_pm() },
+        naming,
+    }
+}
+
+/// Exports the stored todos like the app does, minus privacy mode.
+fn export_all(
+    storage: &Storage,
+) -> impl FnMut(&ExportScheduleConfig, &Path) -> Result<usize, String> + '_ {
+    |config, path| {
+        let todos = storage.list_todos().map_err(|e| e.to_string())?;
+        write_export(path, &todos, config.format, config.stable)?;
+        Ok(todos.len())
+    }
+}
+
+fn files(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_next_run() {
+    let daily = ExportInterval::Daily { time: six_pm() };
+    assert_eq!(
+        next_run(&daily, at("2024-06-03T12:00:00Z"), &Utc),
+        Some(at("2024-06-03T18:00:00Z"))
+    );
+    assert_eq!(
+        next_run(&daily, at("2024-06-03T18:00:00Z"), &Utc),
+        Some(at("2024-06-04T18:00:00Z"))
+    );
+
+    // 2024-06-03 is a Monday.
+    let fridays = ExportInterval::Weekly {
+        weekday: Weekday::Fri,
+        time: six_pm(),
+    };
+    assert_eq!(
+        next_run(&fridays, at("2024-06-03T12:00:00Z"), &Utc),
+        Some(at("2024-06-07T18:00:00Z"))
+    );
+    assert_eq!(
+        next_run(&fridays, at("2024-06-07T18:00:00Z"), &Utc),
+        Some(at("2024-06-14T18:00:00Z"))
+    );
+    // The local day decides: Friday 6pm in Tokyo is 9am UTC.
+    let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+    assert_eq!(
+        next_run(&fridays, at("2024-06-07T10:00:00Z"), &tokyo),
+        Some(at("2024-06-14T09:00:00Z"))
+    );
+}
+
+#[test]
+fn test_file_names() {
+    let local = NaiveDate::from_ymd_opt(2024, 6, 3)
+        .unwrap()
+        .and_hms_opt(18, 0, 5)
+        .unwrap();
+    assert_eq!(
+        render_template("todos-{date}.csv", local),
+        Ok("todos-2024-06-03.csv".to_string())
+    );
+    assert_eq!(
+        render_template("{year}/{month}/{day} {time}", local),
+        Ok("2024/06/03 180005".to_string())
+    );
+    assert_eq!(
+        render_template("todos-{week}.csv", local),
+        Err("Unknown placeholder '{week}' in 'todos-{week}.csv'".to_string())
+    );
+    assert!(render_template("todos-{date.csv", local).is_err());
+
+    let dir = Path::new("/exports");
+    let overwrite = config(dir, "todos.csv", ExportNaming::Overwrite);
+    assert_eq!(overwrite.path_at(local), Ok(dir.join("todos.csv")));
+    let timestamped = config(dir, "todos.csv", ExportNaming::Timestamped);
+    assert_eq!(
+        timestamped.path_at(local),
+        Ok(dir.join("todos-20240603-180005.csv"))
+    );
+    let no_extension = config(dir, "todos", ExportNaming::Timestamped);
+    assert_eq!(
+        no_extension.path_at(local),
+        Ok(dir.join("todos-20240603-180005"))
+    );
+
+    assert!(
+        config(dir, "todos-{year}/{month}.csv", ExportNaming::Overwrite)
+            .validate()
+            .is_err()
+    );
+    assert!(config(dir, "..", ExportNaming::Overwrite)
+        .validate()
+        .is_err());
+    assert!(config(dir, "", ExportNaming::Overwrite).validate().is_err());
+}
+
+#[test]
+fn test_due_schedules_write_and_record_history() {
+    let storage = Storage::open_in_memory().unwrap();
+    let created = at("2024-06-03T12:00:00Z");
+    let dir = tempfile::tempdir().unwrap();
+    let overwrite = add_schedule(
+        &storage.conn(),
+        config(dir.path(), "todos.csv", ExportNaming::Overwrite),
+        created,
+        &Utc,
+    )
+    .unwrap();
+    let timestamped = add_schedule(
+        &storage.conn(),
+        config(dir.path(), "todos-{date}.csv", ExportNaming::Timestamped),
+        created,
+        &Utc,
+    )
+    .unwrap();
+    assert_eq!(overwrite.next_run, Some(at("2024-06-03T18:00:00Z")));
+
+    let ran = run_due(
+        &storage,
+        at("2024-06-03T17:00:00Z"),
+        &Utc,
+        export_all(&storage),
+    )
+    .unwrap();
+    assert!(ran.is_empty());
+
+    for day in ["2024-06-03T18:00:30Z", "2024-06-04T18:00:30Z"] {
+        let ran = run_due(&storage, at(day), &Utc, export_all(&storage)).unwrap();
+        assert_eq!(ran.len(), 2);
+        assert!(ran
+            .iter()
+            .all(|(run, retry_at)| run.exported == Some(0) && retry_at.is_none()));
+    }
+    assert_eq!(
+        files(dir.path()),
+        [
+            "todos-2024-06-03-20240603-180030.csv",
+            "todos-2024-06-04-20240604-180030.csv",
+            "todos.csv",
+        ]
+    );
+    let csv = std::fs::read_to_string(dir.path().join("todos.csv")).unwrap();
+    assert!(csv.starts_with("id,title,"));
+
+    let schedule = find_schedule(&storage.conn(), &overwrite.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(schedule.next_run, Some(at("2024-06-05T18:00:00Z")));
+    assert_eq!(schedule.last_run, Some(at("2024-06-04T18:00:30Z")));
+    let runs = history(&storage.conn(), &overwrite.id).unwrap();
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0].ran_at, at("2024-06-04T18:00:30Z"));
+    assert_eq!(runs[0].path, dir.path().join("todos.csv"));
+    assert_eq!((runs[0].retry, runs[0].error.as_ref()), (0, None));
+    assert_eq!(history(&storage.conn(), &timestamped.id).unwrap().len(), 2);
+}
+
+#[test]
+fn test_unavailable_destination_is_retried() {
+    let storage = Storage::open_in_memory().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let share = dir.path().join("share");
+    let created = at("2024-06-03T12:00:00Z");
+    let schedule = add_schedule(
+        &storage.conn(),
+        config(&share, "todos.csv", ExportNaming::Overwrite),
+        created,
+        &Utc,
+    )
+    .unwrap();
+
+    let mut now = at("2024-06-03T18:00:00Z");
+    for (retry, delay) in [(0, Some(5)), (1, Some(15)), (2, Some(60)), (3, None)] {
+        let ran = run_due(&storage, now, &Utc, export_all(&storage)).unwrap();
+        let [(run, retry_at)] = ran.as_slice() else {
+            panic!("expected one run, got {:?}", ran);
+        };
+        assert_eq!(run.retry, retry);
+        assert_eq!(
+            run.error,
+            Some(format!("Destination {} is unavailable", share.display()))
+        );
+        assert_eq!(
+            *retry_at,
+            delay.map(|minutes| now + chrono::Duration::minutes(minutes))
+        );
+        if let Some(retry_at) = retry_at {
+            assert!(run_due(
+                &storage,
+                *retry_at - chrono::Duration::seconds(1),
+                &Utc,
+                export_all(&storage)
+            )
+            .unwrap()
+            .is_empty());
+            now = *retry_at;
+        }
+    }
+    let failed = find_schedule(&storage.conn(), &schedule.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!((failed.retry_at, failed.retries), (None, 3));
+    assert_eq!(failed.next_run, Some(at("2024-06-04T18:00:00Z")));
+
+    // Back online by the next regular run, which starts over.
+    std::fs::create_dir(&share).unwrap();
+    let ran = run_due(
+        &storage,
+        at("2024-06-04T18:00:00Z"),
+        &Utc,
+        export_all(&storage),
+    )
+    .unwrap();
+    assert_eq!(ran[0].0.retry, 0);
+    assert_eq!(ran[0].0.error, None);
+    assert_eq!(files(&share), ["todos.csv"]);
+    assert_eq!(history(&storage.conn(), &schedule.id).unwrap().len(), 5);
+}
+
+#[test]
+fn test_removing_a_schedule_cancels_its_retry() {
+    let storage = Storage::open_in_memory().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let share = dir.path().join("share");
+    let schedule = add_schedule(
+        &storage.conn(),
+        config(&share, "todos.csv", ExportNaming::Overwrite),
+        at("2024-06-03T12:00:00Z"),
+        &Utc,
+    )
+    .unwrap();
+    let ran = run_due(
+        &storage,
+        at("2024-06-03T18:00:00Z"),
+        &Utc,
+        export_all(&storage),
+    )
+    .unwrap();
+    let retry_at = ran[0].1.unwrap();
+
+    remove_schedule(&storage.conn(), &schedule.id).unwrap();
+    std::fs::create_dir(&share).unwrap();
+    assert!(run_due(&storage, retry_at, &Utc, export_all(&storage))
+        .unwrap()
+        .is_empty());
+    assert!(files(&share).is_empty());
+    assert!(history(&storage.conn(), &schedule.id).unwrap().is_empty());
+    assert!(remove_schedule(&storage.conn(), &schedule.id)
+        .unwrap_err()
+        .contains("no export schedule"));
+}
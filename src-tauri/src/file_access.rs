@@ -0,0 +1,158 @@
+//! Which directories import and export commands may read from and write to.
+//!
+//! The app data, config, downloads and documents directories are always
+//! allowed; `[backend.file_access] allowed_dirs` in `settings.toml` adds
+//! more. Paths are compared after resolving symlinks and `..` with
+//! [`paths::safe_path_in`], so neither can lead outside an allowed directory.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::paths::{self, BaseDir};
+use crate::settings::{self, SettingsState, SETTINGS_FILE};
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// Error of commands that take a path from the frontend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema, thiserror::Error)]
+#[serde(
+    tag = "kind",
+    rename_all = "kebab-case",
+    rename_all_fields = "camelCase"
+)]
+pub enum PathError {
+    #[error("{} is outside the directories import and export may use", path.display())]
+    PathNotAllowed { path: PathBuf },
+    #[error("{message}")]
+    Failed { message: String },
+}
+
+impl From<String> for PathError {
+    fn from(message: String) -> Self {
+        PathError::Failed { message }
+    }
+}
+
+impl trace::Annotate for PathError {
+    fn annotate(self, id: &str) -> Self {
+        match self {
+            PathError::Failed { message } => PathError::Failed {
+                message: message.annotate(id),
+            },
+            e => e,
+        }
+    }
+}
+
+/// Directories paths are checked against, resolved once when built.
+#[derive(Debug, Clone, Default)]
+pub struct AllowList {
+    dirs: Vec<PathBuf>,
+}
+
+impl AllowList {
+    /// Directories that don't exist are left out; nothing can be inside them.
+    pub fn new(dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            dirs: dirs
+                .into_iter()
+                .filter_map(|dir| fs::canonicalize(dir).ok())
+                .collect(),
+        }
+    }
+
+    /// Resolves `path` and returns it if it lies in an allowed directory.
+    /// The path itself need not exist yet, so export destinations pass.
+    pub fn check(&self, path: &Path) -> Result<PathBuf, PathError> {
+        let not_allowed = || PathError::PathNotAllowed {
+            path: path.to_path_buf(),
+        };
+        let input = path.to_string_lossy();
+        if !paths::normalize_separators(&input).is_absolute() {
+            return Err(not_allowed());
+        }
+        self.dirs
+            .iter()
+            .find_map(|dir| paths::safe_path_in(&input, dir).ok())
+            .ok_or_else(not_allowed)
+    }
+}
+
+/// The allow-list in effect: the default directories plus the configured ones.
+pub fn allow_list(app: &AppHandle) -> AllowList {
+    let defaults = BaseDir::ALL
+        .into_iter()
+        .filter_map(|base| base.resolve(app).ok());
+    let configured = app.state::<SettingsState>().get().file_access.allowed_dirs;
+    AllowList::new(defaults.chain(configured))
+}
+
+/// Checks a path given to an import or export command; see [`AllowList::check`].
+pub fn check(app: &AppHandle, path: impl AsRef<Path>) -> Result<PathBuf, PathError> {
+    allow_list(app).check(path.as_ref())
+}
+
+/// Appends `dir` to `[backend.file_access] allowed_dirs` in the settings file,
+/// keeping everything else in it.
+pub fn add_to_settings_file(settings_path: &Path, dir: &Path) -> Result<(), String> {
+    let content = match fs::read_to_string(settings_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read settings: {}", e)),
+    };
+    let mut table: toml::Table = content
+        .parse()
+        .map_err(|e| format!("Failed to parse settings: {}", e))?;
+    let mut backend = settings::take_table(&mut table, "backend")?;
+    let mut file_access = settings::take_table(&mut backend, "file_access")?;
+    let dirs = file_access
+        .entry("allowed_dirs")
+        .or_insert_with(|| toml::Value::Array(Vec::new()));
+    let toml::Value::Array(dirs) = dirs else {
+        return Err("Settings key 'allowed_dirs' is not an array".to_string());
+    };
+    let dir = toml::Value::String(dir.to_string_lossy().into_owned());
+    if !dirs.contains(&dir) {
+        dirs.push(dir);
+    }
+    backend.insert("file_access".to_string(), file_access.into());
+    table.insert("backend".to_string(), backend.into());
+    let content =
+        toml::to_string(&table).map_err(|e| format!("Failed to write settings: {}", e))?;
+    fs::write(settings_path, content).map_err(|e| format!("Failed to write settings: {}", e))
+}
+
+/// Allows import and export in `path` and its subdirectories from now on,
+/// saving it to `settings.toml`. Returns the directory as it was stored.
+#[tauri::command]
+pub fn add_allowed_dir(
+    path: PathBuf,
+    app: AppHandle,
+    state: State<'_, SettingsState>,
+) -> Result<PathBuf, String> {
+    if !path.is_absolute() {
+        return Err(format!("{} is not an absolute path", path.display()));
+    }
+    let dir =
+        fs::canonicalize(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    let config_dir =
+        settings::config_dir(&app).map_err(|e| format!("Failed to locate settings: {}", e))?;
+    fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to write settings: {}", e))?;
+    add_to_settings_file(&config_dir.join(SETTINGS_FILE), &dir)?;
+    let mut settings = state.get();
+    if !settings.file_access.allowed_dirs.contains(&dir) {
+        settings.file_access.allowed_dirs.push(dir.clone());
+    }
+    state.replace(settings);
+    Ok(dir)
+}
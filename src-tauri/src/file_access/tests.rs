@@ -0,0 +1,95 @@
+use super::*;
+
+use crate::settings::Settings;
+
+fn setup() -> (tempfile::TempDir, PathBuf, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = fs::canonicalize(dir.path()).unwrap();
+    let (allowed, outside) = (root.join("allowed"), root.join("outside"));
+    fs::create_dir_all(&allowed).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    (dir, allowed, outside)
+}
+
+fn is_not_allowed(result: Result<PathBuf, PathError>) -> bool {
+    matches!(result, Err(PathError::PathNotAllowed { .. }))
+}
+
+#[test]
+fn test_paths_in_allowed_dirs_pass() {
+    let (_dir, allowed, _) = setup();
+    fs::write(allowed.join("todos.json"), "[]").unwrap();
+    let list = AllowList::new([allowed.clone()]);
+
+    assert_eq!(
+        list.check(&allowed.join("todos.json")).unwrap(),
+        allowed.join("todos.json")
+    );
+    // Export destinations don't exist yet, nor need their folder.
+    assert_eq!(
+        list.check(&allowed.join("site").join("index.html"))
+            .unwrap(),
+        allowed.join("site").join("index.html")
+    );
+    assert_eq!(list.check(&allowed).unwrap(), allowed);
+}
+
+#[test]
+fn test_traversal_is_rejected_after_resolving() {
+    let (_dir, allowed, outside) = setup();
+    fs::write(outside.join("secret.json"), "[]").unwrap();
+    let list = AllowList::new([allowed.clone()]);
+
+    for path in [
+        allowed.join("..").join("outside").join("secret.json"),
+        allowed.join("..").join("outside").join("new.json"),
+        allowed
+            .join("missing")
+            .join("..")
+            .join("..")
+            .join("outside"),
+        outside.join("secret.json"),
+        PathBuf::from("todos.json"),
+    ] {
+        assert!(is_not_allowed(list.check(&path)), "{}", path.display());
+    }
+    let error = list.check(&outside).unwrap_err();
+    assert_eq!(
+        serde_json::to_value(&error).unwrap()["kind"],
+        "path-not-allowed"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlinks_out_of_allowed_dirs_are_rejected() {
+    let (_dir, allowed, outside) = setup();
+    std::os::unix::fs::symlink(&outside, allowed.join("link")).unwrap();
+    let list = AllowList::new([allowed.clone()]);
+
+    assert!(is_not_allowed(
+        list.check(&allowed.join("link").join("todos.json"))
+    ));
+}
+
+#[test]
+fn test_added_dir_is_saved_and_allowed() {
+    let (_dir, allowed, outside) = setup();
+    let settings_path = allowed.join(SETTINGS_FILE);
+    fs::write(&settings_path, "[app]\ntheme = \"dark\"\n").unwrap();
+    let target = outside.join("todos.json");
+    assert!(is_not_allowed(
+        AllowList::new([allowed.clone()]).check(&target)
+    ));
+
+    add_to_settings_file(&settings_path, &outside).unwrap();
+    add_to_settings_file(&settings_path, &outside).unwrap();
+
+    let settings = Settings::load(&settings_path);
+    assert_eq!(settings.file_access.allowed_dirs, vec![outside.clone()]);
+    let list = AllowList::new(std::iter::once(allowed).chain(settings.file_access.allowed_dirs));
+    assert_eq!(list.check(&target).unwrap(), target);
+    // The frontend's settings are left as they were.
+    let table: toml::Table = fs::read_to_string(&settings_path).unwrap().parse().unwrap();
+    assert_eq!(table["app"]["theme"].as_str(), Some("dark"));
+}
@@ -0,0 +1,208 @@
+//! Opening `.todos` files from the desktop. Registration writes a per-user
+//! association so it needs no admin rights; installed bundles also declare
+//! the type through `bundle.fileAssociations` in `tauri.conf.json`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::State;
+
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+pub const EXTENSION: &str = "todos";
+pub const MIME_TYPE: &str = "application/x-yutodo-todos";
+#[cfg(target_os = "linux")]
+const DESKTOP_FILE: &str = "yutodo-open.desktop";
+#[cfg(target_os = "linux")]
+const MIME_PACKAGE_FILE: &str = "yutodo-todos.xml";
+#[cfg(windows)]
+const PROG_ID: &str = "YuTodo.todos";
+
+/// `.todos` files passed on the command line. Flags (ours, or the `-psn_`
+/// one macOS adds) and other files are ignored.
+pub fn files_from_args(args: impl IntoIterator<Item = String>) -> Vec<PathBuf> {
+    args.into_iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(PathBuf::from)
+        .filter(|path| is_todos_file(path))
+        .collect()
+}
+
+pub fn is_todos_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(EXTENSION))
+}
+
+/// Files the app was launched with, until the frontend is ready to open them.
+#[derive(Default)]
+pub struct OpenedFiles(Mutex<Vec<PathBuf>>);
+
+impl OpenedFiles {
+    pub fn push(&self, files: impl IntoIterator<Item = PathBuf>) {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(files);
+    }
+
+    pub fn take(&self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Returns the `.todos` files to open (each once); the frontend imports them
+/// with `import_todos`.
+#[tauri::command]
+pub fn take_opened_files(state: State<'_, OpenedFiles>) -> Vec<PathBuf> {
+    state.take()
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn desktop_entry(exe: &Path) -> String {
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=YuTodo\n\
+         Exec=\"{}\" %f\n\
+         MimeType={};\n\
+         NoDisplay=true\n",
+        exe.display(),
+        MIME_TYPE
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn mime_package() -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <mime-info xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\">\n  \
+         <mime-type type=\"{}\">\n    \
+         <comment>YuTodo list</comment>\n    \
+         <glob pattern=\"*.{}\"/>\n  \
+         </mime-type>\n\
+         </mime-info>\n",
+        MIME_TYPE, EXTENSION
+    )
+}
+
+/// Runs a desktop database tool. They're absent on minimal systems, where the
+/// files alone are picked up on the next login.
+#[cfg(target_os = "linux")]
+fn run_optional(program: &str, args: &[&str]) {
+    if let Err(e) = std::process::Command::new(program).args(args).status() {
+        trace::log(format!("Skipping {}: {}", program, e));
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn register(app: &tauri::AppHandle, exe: &Path) -> Result<(), String> {
+    use tauri::Manager;
+    let data_dir = app.path().data_dir().map_err(|e| e.to_string())?;
+    let applications = data_dir.join("applications");
+    let mime = data_dir.join("mime");
+    let packages = mime.join("packages");
+    std::fs::create_dir_all(&applications).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&packages).map_err(|e| e.to_string())?;
+    std::fs::write(applications.join(DESKTOP_FILE), desktop_entry(exe))
+        .map_err(|e| e.to_string())?;
+    std::fs::write(packages.join(MIME_PACKAGE_FILE), mime_package()).map_err(|e| e.to_string())?;
+    run_optional("update-mime-database", &[&mime.to_string_lossy()]);
+    run_optional(
+        "update-desktop-database",
+        &[&applications.to_string_lossy()],
+    );
+    run_optional("xdg-mime", &["default", DESKTOP_FILE, MIME_TYPE]);
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn unregister(app: &tauri::AppHandle) -> Result<(), String> {
+    use tauri::Manager;
+    let data_dir = app.path().data_dir().map_err(|e| e.to_string())?;
+    let applications = data_dir.join("applications");
+    let mime = data_dir.join("mime");
+    for path in [
+        applications.join(DESKTOP_FILE),
+        mime.join("packages").join(MIME_PACKAGE_FILE),
+    ] {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.to_string()),
+            _ => {}
+        }
+    }
+    run_optional("update-mime-database", &[&mime.to_string_lossy()]);
+    run_optional(
+        "update-desktop-database",
+        &[&applications.to_string_lossy()],
+    );
+    Ok(())
+}
+
+#[cfg(windows)]
+fn register(_app: &tauri::AppHandle, exe: &Path) -> Result<(), String> {
+    use crate::platform::windows::reg;
+    let classes = r"HKCU\Software\Classes";
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    reg(&[
+        "add",
+        &format!(r"{}\.{}", classes, EXTENSION),
+        "/ve",
+        "/d",
+        PROG_ID,
+        "/f",
+    ])?;
+    reg(&[
+        "add",
+        &format!(r"{}\{}", classes, PROG_ID),
+        "/ve",
+        "/d",
+        "YuTodo list",
+        "/f",
+    ])?;
+    reg(&[
+        "add",
+        &format!(r"{}\{}\shell\open\command", classes, PROG_ID),
+        "/ve",
+        "/d",
+        &command,
+        "/f",
+    ])
+    .map(|_| ())
+}
+
+#[cfg(windows)]
+fn unregister(_app: &tauri::AppHandle) -> Result<(), String> {
+    use crate::platform::windows::reg;
+    let classes = r"HKCU\Software\Classes";
+    reg(&["delete", &format!(r"{}\{}", classes, PROG_ID), "/f"])?;
+    reg(&["delete", &format!(r"{}\.{}", classes, EXTENSION), "/f"]).map(|_| ())
+}
+
+/// On macOS the association comes from `CFBundleDocumentTypes` in the app
+/// bundle's Info.plist, which the bundler generates from `fileAssociations`.
+#[cfg(not(any(target_os = "linux", windows)))]
+fn register(_app: &tauri::AppHandle, _exe: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn unregister(_app: &tauri::AppHandle) -> Result<(), String> {
+    Err("The .todos association is part of the app bundle; remove the app to drop it".to_string())
+}
+
+#[tauri::command]
+pub fn register_file_association(app: tauri::AppHandle) -> Result<(), String> {
+    let exe =
+        std::env::current_exe().map_err(|e| format!("Failed to locate the executable: {}", e))?;
+    register(&app, &exe).map_err(|e| format!("Failed to register .todos files: {}", e))
+}
+
+#[tauri::command]
+pub fn unregister_file_association(app: tauri::AppHandle) -> Result<(), String> {
+    unregister(&app).map_err(|e| format!("Failed to unregister .todos files: {}", e))
+}
@@ -0,0 +1,39 @@
+use super::*;
+
+fn args(values: &[&str]) -> Vec<String> {
+    values.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_todos_files_in_argv_are_opened() {
+    assert_eq!(
+        files_from_args(args(&[
+            "/usr/bin/yutodo",
+            "--ephemeral",
+            "-psn_0_12345",
+            "/home/me/Work.TODOS",
+            "notes.txt",
+            "groceries.todos",
+        ])),
+        vec![
+            PathBuf::from("/home/me/Work.TODOS"),
+            PathBuf::from("groceries.todos")
+        ]
+    );
+    assert!(files_from_args(args(&["yutodo.todos"])).is_empty());
+}
+
+#[test]
+fn test_opened_files_are_taken_once() {
+    let opened = OpenedFiles::default();
+    opened.push(files_from_args(args(&["yutodo", "a.todos"])));
+    assert_eq!(opened.take(), vec![PathBuf::from("a.todos")]);
+    assert!(opened.take().is_empty());
+}
+
+#[test]
+fn test_desktop_entry_passes_the_file() {
+    let entry = desktop_entry(Path::new("/opt/yutodo/yutodo"));
+    assert!(entry.contains("Exec=\"/opt/yutodo/yutodo\" %f\n"));
+    assert!(entry.contains(&format!("MimeType={};\n", MIME_TYPE)));
+}
@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::params;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+
+use crate::clock::{Clock, ClockState};
+use crate::command_registry::{Category, CommandDescriptor, CommandRegistry, EnabledWhen};
+use crate::file_access::{self, PathError};
+use crate::storage::Storage;
+use crate::types::DateRange;
+
+#[cfg(test)]
+mod tests;
+
+/// A completed focus session as persisted in `focus_sessions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusSession {
+    pub id: i64,
+    pub todo_id: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoFocusTotal {
+    pub todo_id: String,
+    pub sessions: usize,
+    pub total_secs: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyFocus {
+    pub date: NaiveDate,
+    pub total_secs: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusReport {
+    pub total_secs: i64,
+    pub session_count: usize,
+    pub per_todo: Vec<TodoFocusTotal>,
+    pub daily: Vec<DailyFocus>,
+}
+
+#[derive(Debug, Clone)]
+struct ActiveFocus {
+    todo_id: String,
+    started_at: DateTime<Utc>,
+}
+
+/// The focus session currently running, if any.
+#[derive(Default)]
+pub struct FocusState {
+    active: Mutex<Option<ActiveFocus>>,
+}
+
+impl FocusState {
+    pub fn is_active(&self) -> bool {
+        self.active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+}
+
+pub fn record_session(
+    storage: &Storage,
+    todo_id: &str,
+    started_at: DateTime<Utc>,
+    duration_secs: i64,
+) -> rusqlite::Result<FocusSession> {
+    let conn = storage.conn();
+    conn.execute(
+        "INSERT INTO focus_sessions (todo_id, started_at, duration_secs) VALUES (?1, ?2, ?3)",
+        params![todo_id, started_at, duration_secs],
+    )?;
+    Ok(FocusSession {
+        id: conn.last_insert_rowid(),
+        todo_id: todo_id.to_string(),
+        started_at,
+        duration_secs,
+    })
+}
+
+pub fn sessions_in_range(
+    storage: &Storage,
+    range: &DateRange,
+) -> rusqlite::Result<Vec<FocusSession>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT id, todo_id, started_at, duration_secs FROM focus_sessions
+         WHERE started_at >= ?1 AND started_at < ?2
+         ORDER BY started_at",
+    )?;
+    let rows = stmt.query_map(params![range.start, range.end], |row| {
+        Ok(FocusSession {
+            id: row.get(0)?,
+            todo_id: row.get(1)?,
+            started_at: row.get(2)?,
+            duration_secs: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Aggregates sessions into totals per todo and per (UTC) day.
+pub fn summarize(sessions: &[FocusSession]) -> FocusReport {
+    let mut per_todo: BTreeMap<&str, (usize, i64)> = BTreeMap::new();
+    let mut daily: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+
+    for session in sessions {
+        let entry = per_todo.entry(&session.todo_id).or_default();
+        entry.0 += 1;
+        entry.1 += session.duration_secs;
+        *daily.entry(session.started_at.date_naive()).or_default() += session.duration_secs;
+    }
+
+    FocusReport {
+        total_secs: sessions.iter().map(|s| s.duration_secs).sum(),
+        session_count: sessions.len(),
+        per_todo: per_todo
+            .into_iter()
+            .map(|(todo_id, (sessions, total_secs))| TodoFocusTotal {
+                todo_id: todo_id.to_string(),
+                sessions,
+                total_secs,
+            })
+            .collect(),
+        daily: daily
+            .into_iter()
+            .map(|(date, total_secs)| DailyFocus { date, total_secs })
+            .collect(),
+    }
+}
+
+#[tauri::command]
+pub fn start_focus_session(
+    todo_id: String,
+    focus: State<'_, FocusState>,
+    clock: State<'_, ClockState>,
+) -> Result<(), String> {
+    let mut active = focus.active.lock().unwrap_or_else(|e| e.into_inner());
+    if active.is_some() {
+        return Err("Failed to start focus session: a session is already running".to_string());
+    }
+    *active = Some(ActiveFocus {
+        todo_id,
+        started_at: clock.now(),
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn stop_focus_session(
+    focus: State<'_, FocusState>,
+    storage: State<'_, Storage>,
+    clock: State<'_, ClockState>,
+) -> Result<FocusSession, String> {
+    let session = focus
+        .active
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .ok_or_else(|| "Failed to stop focus session: no session is running".to_string())?;
+    let duration_secs = (clock.now() - session.started_at).num_seconds().max(0);
+
+    record_session(
+        &storage,
+        &session.todo_id,
+        session.started_at,
+        duration_secs,
+    )
+    .map_err(|e| format!("Failed to record focus session: {}", e))
+}
+
+/// Palette commands for starting a session on the selected todo (passed
+/// as `todoId`) and stopping the running one.
+pub fn register_commands(registry: &CommandRegistry) {
+    registry.register(
+        CommandDescriptor {
+            id: "startFocusSession",
+            title_key: "commandPalette.commands.task.startFocusSession.title",
+            default_title: "Start Focus Session",
+            category: Category::Task,
+            default_binding: None,
+            enabled_when: EnabledWhen::NoFocusRunning,
+            command: Some("start_focus_session"),
+        },
+        |app, args| {
+            let todo_id = args
+                .get("todoId")
+                .and_then(Value::as_str)
+                .ok_or("Failed to start focus session: no todo given")?;
+            start_focus_session(todo_id.to_string(), app.state(), app.state())?;
+            Ok(Value::Null)
+        },
+    );
+    registry.register(
+        CommandDescriptor {
+            id: "stopFocusSession",
+            title_key: "commandPalette.commands.task.stopFocusSession.title",
+            default_title: "Stop Focus Session",
+            category: Category::Task,
+            default_binding: None,
+            enabled_when: EnabledWhen::FocusRunning,
+            command: Some("stop_focus_session"),
+        },
+        |app, _| {
+            let session = stop_focus_session(app.state(), app.state(), app.state())?;
+            serde_json::to_value(session).map_err(|e| e.to_string())
+        },
+    );
+}
+
+#[tauri::command]
+pub fn focus_report(range: DateRange, storage: State<'_, Storage>) -> Result<FocusReport, String> {
+    let sessions = sessions_in_range(&storage, &range)
+        .map_err(|e| format!("Failed to load focus sessions: {}", e))?;
+    Ok(summarize(&sessions))
+}
+
+/// Sessions in `range` as `(started_at, todo title, duration_secs)`; the title is
+/// empty when the todo is no longer in the cache.
+fn titled_sessions_in_range(
+    storage: &Storage,
+    range: &DateRange,
+) -> rusqlite::Result<Vec<(DateTime<Utc>, String, i64)>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT s.started_at, COALESCE(t.title, ''), s.duration_secs
+         FROM focus_sessions s LEFT JOIN todos t ON t.id = s.todo_id
+         WHERE s.started_at >= ?1 AND s.started_at < ?2
+         ORDER BY s.started_at",
+    )?;
+    let rows = stmt.query_map(params![range.start, range.end], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })?;
+    rows.collect()
+}
+
+/// Writes the sessions in `range` as CSV rows and returns how many were written.
+pub fn write_focus_csv(
+    storage: &Storage,
+    range: &DateRange,
+    writer: impl std::io::Write,
+) -> Result<usize, String> {
+    let rows = titled_sessions_in_range(storage, range)
+        .map_err(|e| format!("Failed to load focus sessions: {}", e))?;
+
+    let mut csv = csv::Writer::from_writer(writer);
+    let write_error = |e: csv::Error| format!("Failed to write focus CSV: {}", e);
+    // Written explicitly so an empty range still produces a header row.
+    csv.write_record(["date", "todo_title", "duration_minutes"])
+        .map_err(write_error)?;
+    for (started_at, title, duration_secs) in &rows {
+        csv.write_record([
+            started_at.date_naive().to_string(),
+            title.clone(),
+            ((duration_secs + 30) / 60).to_string(),
+        ])
+        .map_err(write_error)?;
+    }
+    csv.flush()
+        .map_err(|e| format!("Failed to write focus CSV: {}", e))?;
+    Ok(rows.len())
+}
+
+#[tauri::command]
+pub fn export_focus_csv(
+    range: DateRange,
+    path: String,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<usize, PathError> {
+    let path = file_access::check(&app, &path)?;
+    let file = std::fs::File::create(&path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    Ok(write_focus_csv(&storage, &range, file)?)
+}
@@ -0,0 +1,131 @@
+use super::*;
+use chrono::TimeZone;
+
+fn at(day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap()
+}
+
+#[test]
+fn test_focus_report_aggregates_sessions_in_range() {
+    crate::storage::for_each_backend(|storage| {
+        record_session(storage, "a", at(1, 9), 1500).unwrap();
+        record_session(storage, "a", at(1, 14), 600).unwrap();
+        record_session(storage, "b", at(2, 10), 900).unwrap();
+
+        let range = DateRange {
+            start: at(1, 0),
+            end: at(3, 0),
+        };
+        let report = summarize(&sessions_in_range(storage, &range).unwrap());
+
+        assert_eq!(report.total_secs, 3000);
+        assert_eq!(report.session_count, 3);
+        assert_eq!(
+            report.per_todo,
+            vec![
+                TodoFocusTotal {
+                    todo_id: "a".to_string(),
+                    sessions: 2,
+                    total_secs: 2100,
+                },
+                TodoFocusTotal {
+                    todo_id: "b".to_string(),
+                    sessions: 1,
+                    total_secs: 900,
+                },
+            ]
+        );
+        assert_eq!(
+            report.daily,
+            vec![
+                DailyFocus {
+                    date: at(1, 0).date_naive(),
+                    total_secs: 2100,
+                },
+                DailyFocus {
+                    date: at(2, 0).date_naive(),
+                    total_secs: 900,
+                },
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_focus_report_excludes_sessions_outside_range() {
+    crate::storage::for_each_backend(|storage| {
+        record_session(storage, "a", at(1, 23), 300).unwrap();
+        record_session(storage, "a", at(2, 0), 400).unwrap();
+        record_session(storage, "a", at(2, 23), 500).unwrap();
+        record_session(storage, "a", at(3, 0), 600).unwrap();
+
+        let range = DateRange {
+            start: at(2, 0),
+            end: at(3, 0),
+        };
+        let sessions = sessions_in_range(storage, &range).unwrap();
+
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions
+            .iter()
+            .all(|s| range.start <= s.started_at && s.started_at < range.end));
+        assert_eq!(summarize(&sessions).total_secs, 900);
+    });
+}
+
+#[test]
+fn test_focus_report_empty_range() {
+    let report = summarize(&[]);
+    assert_eq!(report, FocusReport::default());
+}
+
+fn todo(id: &str, title: &str) -> crate::types::Todo {
+    crate::types::Todo::sample(id, title, at(1, 0))
+}
+
+#[test]
+fn test_export_focus_csv_round_trip() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage
+        .replace_todos(&[todo("a", "Write report"), todo("b", "Review, then merge")])
+        .unwrap();
+    record_session(&storage, "a", at(1, 9), 1500).unwrap();
+    record_session(&storage, "b", at(2, 10), 600).unwrap();
+    record_session(&storage, "a", at(5, 10), 600).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("focus.csv");
+    let range = DateRange {
+        start: at(1, 0),
+        end: at(3, 0),
+    };
+    let written = write_focus_csv(&storage, &range, std::fs::File::create(&path).unwrap()).unwrap();
+    assert_eq!(written, 2);
+
+    let mut reader = csv::Reader::from_path(&path).unwrap();
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec!["date", "todo_title", "duration_minutes"]
+    );
+    let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1], vec!["2024-03-02", "Review, then merge", "10"]);
+}
+
+#[test]
+fn test_export_focus_csv_empty_range_writes_header() {
+    let storage = Storage::open_in_memory().unwrap();
+    let mut output = Vec::new();
+    let range = DateRange {
+        start: at(1, 0),
+        end: at(2, 0),
+    };
+
+    let written = write_focus_csv(&storage, &range, &mut output).unwrap();
+
+    assert_eq!(written, 0);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "date,todo_title,duration_minutes\n"
+    );
+}
@@ -0,0 +1,1342 @@
+//! Resumable CSV and JSON import into the local todo cache.
+//!
+//! Rows are committed in batches of `[backend.import] batch_rows`. Each
+//! batch transaction also advances the import's checkpoint (source offset,
+//! rows committed, decisions so far), so after a crash or cancellation the
+//! import resumes exactly after the last committed row.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::activity;
+use crate::bulk_edit;
+use crate::custom_fields::{self, CustomField};
+use crate::events;
+use crate::file_access::{self, PathError};
+use crate::operations::{self, CancelToken, Operations};
+use crate::progress::ProgressRegistry;
+use crate::protocol::EventPayload;
+use crate::quota::{self, QuotaDimension, QuotaExceeded, WriteError};
+use crate::retry;
+use crate::search::SearchIndex;
+use crate::settings::SettingsState;
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::{FieldValue, Priority, Todo};
+use crate::validation_rules::{self, RuleSet, Violation};
+
+#[cfg(test)]
+mod tests;
+
+/// Unfinished imports older than this are forgotten.
+const CHECKPOINT_MAX_AGE_DAYS: i64 = 7;
+/// Per-row error messages kept for the summary; later ones are only counted.
+const MAX_REPORTED_ERRORS: usize = 20;
+/// Rows a chunk commits at least before ending early for interactive
+/// callers, so a busy UI can't reduce the import to one row per commit.
+const MIN_YIELDING_CHUNK_ROWS: u64 = 50;
+/// CSV column prefix for custom field values, e.g. `field:customer`.
+pub const CSV_FIELD_PREFIX: &str = "field:";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Quota(#[from] QuotaExceeded),
+    #[error("cancelled")]
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Json,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Result<Self, ImportError> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("csv") => Ok(ImportFormat::Csv),
+            // `.todos` files are JSON exports associated with the app.
+            Some("json") | Some("jsonl") | Some("ndjson") | Some("todos") => Ok(ImportFormat::Json),
+            _ => Err(ImportError::Invalid(format!(
+                "Unsupported import file type: {}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Like [`Self::from_path`], but also recognizes JSON files without a
+    /// known extension by their first character.
+    pub fn detect(path: &Path) -> Option<Self> {
+        if let Ok(format) = Self::from_path(path) {
+            return Some(format);
+        }
+        let mut reader = BufReader::new(File::open(path).ok()?);
+        loop {
+            let first = *reader.fill_buf().ok()?.first()?;
+            match first {
+                b'[' | b'{' => return Some(ImportFormat::Json),
+                b if b.is_ascii_whitespace() => reader.consume(1),
+                _ => return None,
+            }
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ImportFormat::Csv => "csv",
+            ImportFormat::Json => "json",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "csv" => Some(ImportFormat::Csv),
+            "json" => Some(ImportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// What to do when an imported todo has the id of one already cached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+    #[default]
+    Skip,
+    Replace,
+    /// Replace only when the imported copy was updated more recently.
+    KeepNewer,
+}
+
+impl ConflictPolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Replace => "replace",
+            ConflictPolicy::KeepNewer => "keepNewer",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "skip" => Some(ConflictPolicy::Skip),
+            "replace" => Some(ConflictPolicy::Replace),
+            "keepNewer" => Some(ConflictPolicy::KeepNewer),
+            _ => None,
+        }
+    }
+}
+
+/// Running tally of what happened to each row.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ImportDecisions {
+    pub inserted: u64,
+    pub replaced: u64,
+    pub skipped: u64,
+    pub invalid: u64,
+    /// Rows imported despite breaking a `warn` validation rule.
+    pub warnings: u64,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportCheckpoint {
+    pub id: String,
+    pub source_path: PathBuf,
+    pub format: ImportFormat,
+    pub source_hash: String,
+    pub policy: ConflictPolicy,
+    /// Where the first uncommitted row starts in the source file.
+    pub byte_offset: u64,
+    pub rows_committed: u64,
+    pub decisions: ImportDecisions,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl EventPayload for ImportCheckpoint {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    #[serde(flatten)]
+    pub checkpoint: ImportCheckpoint,
+    /// False when the import was interrupted and can be resumed.
+    pub finished: bool,
+}
+
+/// One todo as read from an import file. JSON objects use the `Todo` field
+/// names; CSV columns are mapped onto it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportRecord {
+    #[serde(default)]
+    id: Option<String>,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    completed: bool,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    scheduled_for: Option<DateTime<Utc>>,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    fields: BTreeMap<String, FieldValue>,
+}
+
+impl ImportRecord {
+    /// Missing timestamps default to now, and `updated_at` to `created_at`.
+    fn into_todo(self, id: String) -> Todo {
+        let created_at = self.created_at.unwrap_or_else(Utc::now);
+        Todo {
+            id,
+            title: self.title.trim().to_string(),
+            description: self.description,
+            completed: self.completed,
+            priority: self.priority,
+            scheduled_for: self.scheduled_for,
+            created_at,
+            updated_at: self.updated_at.unwrap_or(created_at),
+            completed_at: None,
+            order: None,
+            rank: None,
+            schedule_id: None,
+            tags: self.tags,
+            languages: Default::default(),
+            fields: Default::default(),
+            estimate_minutes: None,
+            actual_minutes: None,
+            revision: 0,
+        }
+    }
+}
+
+/// A parsed row, or why it was rejected.
+type RecordResult = Result<ImportRecord, String>;
+
+trait RecordSource {
+    /// The next row and the offset just past it. I/O and framing errors are
+    /// fatal; problems with a single row are returned inside.
+    fn next_record(&mut self) -> Result<Option<(RecordResult, u64)>, ImportError>;
+}
+
+fn parse_datetime(raw: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw)
+        .map(|d| d.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|d| d.and_utc())
+        })
+}
+
+struct CsvSource {
+    reader: csv::Reader<File>,
+    columns: HashMap<String, usize>,
+    field_columns: Vec<(String, usize)>,
+    record: csv::StringRecord,
+}
+
+impl CsvSource {
+    fn open(path: &Path, offset: u64) -> Result<Self, ImportError> {
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+        let headers = reader.headers()?.clone();
+        let mut columns = HashMap::new();
+        let mut field_columns = Vec::new();
+        for (index, header) in headers.iter().enumerate() {
+            match header.strip_prefix(CSV_FIELD_PREFIX) {
+                Some(name) => field_columns.push((name.to_string(), index)),
+                None => {
+                    columns.insert(header.trim().to_ascii_lowercase(), index);
+                }
+            }
+        }
+        if !columns.contains_key("title") {
+            return Err(ImportError::Invalid(
+                "CSV file has no 'title' column".to_string(),
+            ));
+        }
+        if offset > 0 {
+            let mut position = csv::Position::new();
+            position.set_byte(offset);
+            reader.seek(position)?;
+        }
+        Ok(Self {
+            reader,
+            columns,
+            field_columns,
+            record: csv::StringRecord::new(),
+        })
+    }
+
+    fn cell(&self, name: &str) -> Option<&str> {
+        self.columns
+            .get(name)
+            .and_then(|&index| self.record.get(index))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    }
+
+    fn date_cell(&self, name: &str) -> Result<Option<DateTime<Utc>>, String> {
+        self.cell(name)
+            .map(|raw| {
+                parse_datetime(raw)
+                    .ok_or_else(|| format!("invalid date '{}' in column '{}'", raw, name))
+            })
+            .transpose()
+    }
+
+    fn parse_record(&self) -> RecordResult {
+        let title = self.cell("title").ok_or("missing title")?;
+        let completed = match self.cell("completed").map(str::to_ascii_lowercase) {
+            None => false,
+            Some(value) => match value.as_str() {
+                "true" | "1" | "yes" => true,
+                "false" | "0" | "no" => false,
+                _ => return Err(format!("invalid completed value '{}'", value)),
+            },
+        };
+        let priority = match self.cell("priority") {
+            None => Priority::default(),
+            Some(raw) => Priority::parse(&raw.to_ascii_lowercase())
+                .ok_or_else(|| format!("invalid priority '{}'", raw))?,
+        };
+        let fields = self
+            .field_columns
+            .iter()
+            .filter_map(|(name, index)| {
+                let value = self.record.get(*index)?.trim();
+                (!value.is_empty()).then(|| (name.clone(), FieldValue::Text(value.to_string())))
+            })
+            .collect();
+
+        Ok(ImportRecord {
+            id: self.cell("id").map(str::to_string),
+            title: title.to_string(),
+            description: self.cell("description").map(str::to_string),
+            completed,
+            priority,
+            scheduled_for: self.date_cell("scheduled_for")?,
+            created_at: self.date_cell("created_at")?,
+            updated_at: self.date_cell("updated_at")?,
+            tags: self
+                .cell("tags")
+                .map(|tags| {
+                    tags.split(';')
+                        .map(str::trim)
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            fields,
+        })
+    }
+}
+
+impl RecordSource for CsvSource {
+    fn next_record(&mut self) -> Result<Option<(RecordResult, u64)>, ImportError> {
+        if !self.reader.read_record(&mut self.record)? {
+            return Ok(None);
+        }
+        Ok(Some((self.parse_record(), self.reader.position().byte())))
+    }
+}
+
+/// Reads top-level JSON objects one at a time, from either an array or
+/// newline-delimited JSON, without loading the whole file.
+struct JsonSource {
+    reader: BufReader<File>,
+    offset: u64,
+}
+
+impl JsonSource {
+    fn open(path: &Path, offset: u64) -> Result<Self, ImportError> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Self {
+            reader: BufReader::new(file),
+            offset,
+        })
+    }
+
+    fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.peek_byte()?;
+        if byte.is_some() {
+            self.reader.consume(1);
+            self.offset += 1;
+        }
+        Ok(byte)
+    }
+}
+
+impl RecordSource for JsonSource {
+    fn next_record(&mut self) -> Result<Option<(RecordResult, u64)>, ImportError> {
+        // Skip array brackets, commas and whitespace between records.
+        loop {
+            match self.peek_byte()? {
+                None => return Ok(None),
+                Some(b'{') => break,
+                Some(b' ' | b'\t' | b'\r' | b'\n' | b',' | b'[' | b']') => {
+                    self.next_byte()?;
+                }
+                Some(other) => {
+                    return Err(ImportError::Invalid(format!(
+                        "Unexpected '{}' at byte {}",
+                        other as char, self.offset
+                    )))
+                }
+            }
+        }
+
+        let mut buffer = Vec::new();
+        let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+        loop {
+            let byte = self.next_byte()?.ok_or_else(|| {
+                ImportError::Invalid("Unexpected end of file inside a record".to_string())
+            })?;
+            buffer.push(byte);
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let record = serde_json::from_slice(&buffer).map_err(|e| e.to_string());
+        Ok(Some((record, self.offset)))
+    }
+}
+
+fn open_source(
+    path: &Path,
+    format: ImportFormat,
+    offset: u64,
+) -> Result<Box<dyn RecordSource>, ImportError> {
+    Ok(match format {
+        ImportFormat::Csv => Box::new(CsvSource::open(path, offset)?),
+        ImportFormat::Json => Box::new(JsonSource::open(path, offset)?),
+    })
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn checkpoint_from_row(row: &Row<'_>) -> rusqlite::Result<ImportCheckpoint> {
+    let invalid = |index: usize, value: String| {
+        rusqlite::Error::FromSqlConversionFailure(
+            index,
+            rusqlite::types::Type::Text,
+            format!("invalid value '{}'", value).into(),
+        )
+    };
+    let format: String = row.get(2)?;
+    let policy: String = row.get(4)?;
+    Ok(ImportCheckpoint {
+        id: row.get(0)?,
+        source_path: PathBuf::from(row.get::<_, String>(1)?),
+        format: ImportFormat::parse(&format).ok_or_else(|| invalid(2, format.clone()))?,
+        source_hash: row.get(3)?,
+        policy: ConflictPolicy::parse(&policy).ok_or_else(|| invalid(4, policy.clone()))?,
+        byte_offset: row.get::<_, i64>(5)? as u64,
+        rows_committed: row.get::<_, i64>(6)? as u64,
+        // A damaged tally shouldn't block resuming; only the counts are lost.
+        decisions: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or_default(),
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+const CHECKPOINT_COLUMNS: &str = "id, source_path, format, source_hash, policy, byte_offset, \
+     rows_committed, decisions, created_at, updated_at";
+
+pub fn find_checkpoint(storage: &Storage, id: &str) -> Result<ImportCheckpoint, ImportError> {
+    storage
+        .conn()
+        .query_row(
+            &format!(
+                "SELECT {} FROM import_checkpoints WHERE id = ?1",
+                CHECKPOINT_COLUMNS
+            ),
+            [id],
+            checkpoint_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| ImportError::Invalid(format!("Unknown import '{}'", id)))
+}
+
+pub fn list_checkpoints(storage: &Storage) -> rusqlite::Result<Vec<ImportCheckpoint>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM import_checkpoints ORDER BY created_at",
+        CHECKPOINT_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], checkpoint_from_row)?;
+    rows.collect()
+}
+
+/// Forgets unfinished imports not touched for a week.
+pub fn prune_checkpoints(storage: &Storage, now: DateTime<Utc>) -> rusqlite::Result<usize> {
+    storage.conn().execute(
+        "DELETE FROM import_checkpoints WHERE updated_at < ?1",
+        [now - Duration::days(CHECKPOINT_MAX_AGE_DAYS)],
+    )
+}
+
+/// Records a new import of `path`; nothing is read past the hash yet.
+pub fn begin_import(
+    storage: &Storage,
+    path: &Path,
+    policy: ConflictPolicy,
+) -> Result<ImportCheckpoint, ImportError> {
+    let format = ImportFormat::detect(path).map_or_else(|| ImportFormat::from_path(path), Ok)?;
+    let now = storage.clock().now();
+    let checkpoint = ImportCheckpoint {
+        id: uuid::Uuid::new_v4().to_string(),
+        source_path: path.to_path_buf(),
+        format,
+        source_hash: hash_file(path)?,
+        policy,
+        byte_offset: 0,
+        rows_committed: 0,
+        decisions: ImportDecisions::default(),
+        created_at: now,
+        updated_at: now,
+    };
+    storage.conn().execute(
+        &format!(
+            "INSERT INTO import_checkpoints ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            CHECKPOINT_COLUMNS
+        ),
+        params![
+            checkpoint.id,
+            checkpoint.source_path.to_string_lossy(),
+            format.as_str(),
+            checkpoint.source_hash,
+            policy.as_str(),
+            0i64,
+            0i64,
+            "{}",
+            now,
+            now,
+        ],
+    )?;
+    Ok(checkpoint)
+}
+
+/// Id of an imported row. Rows without one get an id derived from their
+/// position in this exact file, so re-reading a row can never create a second
+/// copy of it.
+fn record_id(record: &ImportRecord, source_hash: &str, record_offset: u64) -> String {
+    record
+        .id
+        .clone()
+        .unwrap_or_else(|| format!("import-{}-{}", &source_hash[..16], record_offset))
+}
+
+/// A write of a cancellable import, undone in reverse order.
+enum Undo {
+    Inserted(String),
+    Replaced(Box<Todo>),
+}
+
+/// Applies one row inside the batch transaction, updating `decisions` and
+/// `todos`, the number of cached todos. A new row that would take that past
+/// `max_todos` fails the batch. Rows breaking a blocking rule of `rules`
+/// are rejected. With `undo`, records how to take it back.
+#[allow(clippy::too_many_arguments)]
+fn apply_record(
+    conn: &Connection,
+    checkpoint: &mut ImportCheckpoint,
+    fields: &[CustomField],
+    rules: &RuleSet,
+    record: RecordResult,
+    record_offset: u64,
+    todos: &mut u64,
+    max_todos: u64,
+    undo: Option<&mut Vec<Undo>>,
+) -> Result<(), ImportError> {
+    let row_number = checkpoint.rows_committed + 1;
+    let decisions = &mut checkpoint.decisions;
+    let mut reject = |message: String| {
+        decisions.invalid += 1;
+        if decisions.errors.len() < MAX_REPORTED_ERRORS {
+            decisions
+                .errors
+                .push(format!("Row {}: {}", row_number, message));
+        }
+    };
+
+    let record = match record {
+        Ok(record) if record.title.trim().is_empty() => {
+            reject("missing title".to_string());
+            return Ok(());
+        }
+        Ok(record) => record,
+        Err(message) => {
+            reject(message);
+            return Ok(());
+        }
+    };
+    let mut values = Vec::new();
+    for (name, value) in &record.fields {
+        let Some(field) = fields.iter().find(|f| &f.name == name) else {
+            reject(format!("unknown custom field '{}'", name));
+            return Ok(());
+        };
+        match field.parse_value(&value.to_string()) {
+            Ok(value) => values.push((field, value)),
+            Err(e) => {
+                reject(e.to_string());
+                return Ok(());
+            }
+        }
+    }
+
+    let id = record_id(&record, &checkpoint.source_hash, record_offset);
+    let todo = record.into_todo(id);
+    let violations = rules.check(&todo);
+    if validation_rules::is_blocking(&violations) {
+        reject(validation_rules::describe(&violations));
+        return Ok(());
+    }
+    let warned = !violations.is_empty();
+
+    let existing: Option<DateTime<Utc>> = conn
+        .query_row(
+            "SELECT updated_at FROM todos WHERE id = ?1",
+            [&todo.id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let replace = match (existing, checkpoint.policy) {
+        (None, _) => {
+            quota::check(QuotaDimension::Todos, *todos + 1, max_todos)?;
+            *todos += 1;
+            false
+        }
+        (Some(_), ConflictPolicy::Replace) => true,
+        (Some(current), ConflictPolicy::KeepNewer) if todo.updated_at > current => true,
+        (Some(_), _) => {
+            checkpoint.decisions.skipped += 1;
+            return Ok(());
+        }
+    };
+
+    if let Some(undo) = undo {
+        match storage::get_todo_with(conn, &todo.id)? {
+            Some(previous) => undo.push(Undo::Replaced(Box::new(previous))),
+            None => undo.push(Undo::Inserted(todo.id.clone())),
+        }
+    }
+    storage::insert_todo(conn, &todo)?;
+    for (field, value) in values {
+        custom_fields::store_value(conn, &todo.id, field, &value)?;
+    }
+    if replace {
+        checkpoint.decisions.replaced += 1;
+    } else {
+        checkpoint.decisions.inserted += 1;
+    }
+    if warned {
+        checkpoint.decisions.warnings += 1;
+    }
+    Ok(())
+}
+
+/// Imports from where `checkpoint` left off until the file ends or
+/// `interrupt(rows_seen)` returns true, committing `batch_rows` rows per
+/// transaction and calling `on_checkpoint` after each. Each batch is read
+/// from the file before the store is locked for its transaction. An
+/// interrupted batch is never written, so the stored checkpoint always
+/// matches what was committed. A batch that would pass the store's todo
+/// limit is rolled back, failing with [`ImportError::Quota`]; the import
+/// resumes from there once the limit is raised.
+pub fn run_import(
+    storage: &Storage,
+    checkpoint: ImportCheckpoint,
+    rules: &RuleSet,
+    batch_rows: u64,
+    interrupt: impl Fn(u64) -> bool,
+    on_checkpoint: impl FnMut(&ImportCheckpoint),
+) -> Result<ImportSummary, ImportError> {
+    import_batches(
+        storage,
+        checkpoint,
+        rules,
+        batch_rows,
+        interrupt,
+        on_checkpoint,
+        None,
+    )
+}
+
+/// Like [`run_import`], but once `token` is cancelled the import stops at
+/// its next row and everything it committed is rolled back, including its
+/// checkpoint, failing with [`ImportError::Cancelled`].
+pub fn run_cancellable_import(
+    storage: &Storage,
+    checkpoint: ImportCheckpoint,
+    rules: &RuleSet,
+    batch_rows: u64,
+    token: &CancelToken,
+    interrupt: impl Fn(u64) -> bool,
+    on_checkpoint: impl FnMut(&ImportCheckpoint),
+) -> Result<ImportSummary, ImportError> {
+    let id = checkpoint.id.clone();
+    let mut undo = Vec::new();
+    let result = import_batches(
+        storage,
+        checkpoint,
+        rules,
+        batch_rows,
+        |rows| token.is_cancelled() || interrupt(rows),
+        on_checkpoint,
+        Some(&mut undo),
+    );
+    match result {
+        Ok(summary) if !summary.finished && token.is_cancelled() => {
+            roll_back(storage, &id, undo)?;
+            Err(ImportError::Cancelled)
+        }
+        result => result,
+    }
+}
+
+/// Takes back the writes in `undo` and forgets the import, in one
+/// transaction.
+fn roll_back(storage: &Storage, checkpoint_id: &str, undo: Vec<Undo>) -> rusqlite::Result<()> {
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    for write in undo.into_iter().rev() {
+        let id = match &write {
+            Undo::Inserted(id) => id,
+            Undo::Replaced(previous) => &previous.id,
+        };
+        tx.execute("DELETE FROM todo_field_values WHERE todo_id = ?1", [id])?;
+        match write {
+            Undo::Inserted(id) => {
+                tx.execute("DELETE FROM todos WHERE id = ?1", [&id])?;
+            }
+            Undo::Replaced(previous) => bulk_edit::restore_todo(&tx, &previous)?,
+        }
+    }
+    tx.execute(
+        "DELETE FROM import_checkpoints WHERE id = ?1",
+        [checkpoint_id],
+    )?;
+    tx.commit()
+}
+
+fn import_batches(
+    storage: &Storage,
+    mut checkpoint: ImportCheckpoint,
+    rules: &RuleSet,
+    batch_rows: u64,
+    interrupt: impl Fn(u64) -> bool,
+    mut on_checkpoint: impl FnMut(&ImportCheckpoint),
+    mut undo: Option<&mut Vec<Undo>>,
+) -> Result<ImportSummary, ImportError> {
+    if hash_file(&checkpoint.source_path)? != checkpoint.source_hash {
+        return Err(ImportError::Invalid(format!(
+            "{} changed since the import started",
+            checkpoint.source_path.display()
+        )));
+    }
+    let mut source = open_source(
+        &checkpoint.source_path,
+        checkpoint.format,
+        checkpoint.byte_offset,
+    )?;
+    let fields = custom_fields::load_fields(&storage.conn())?;
+    // Rows read but not yet applied, with the offset each ends at.
+    let mut read: VecDeque<(RecordResult, u64)> = VecDeque::new();
+    let mut source_done = false;
+
+    loop {
+        // The batch is read before the connection is taken, so file reads
+        // don't hold up other commands.
+        while !source_done && (read.len() as u64) < batch_rows.max(1) {
+            if interrupt(checkpoint.rows_committed + read.len() as u64) {
+                return Ok(ImportSummary {
+                    checkpoint,
+                    finished: false,
+                });
+            }
+            match source.next_record()? {
+                Some(record) => read.push_back(record),
+                None => source_done = true,
+            }
+        }
+
+        let mut pending = checkpoint.clone();
+        let mut conn = storage.batch_conn();
+        let tx = conn.transaction()?;
+        let mut todos = quota::count_todos(&tx)?;
+        loop {
+            let chunk_rows = pending.rows_committed - checkpoint.rows_committed;
+            if chunk_rows >= MIN_YIELDING_CHUNK_ROWS && storage.should_yield() {
+                break;
+            }
+            let Some((record, end_offset)) = read.pop_front() else {
+                break;
+            };
+            let record_offset = pending.byte_offset;
+            apply_record(
+                &tx,
+                &mut pending,
+                &fields,
+                rules,
+                record,
+                record_offset,
+                &mut todos,
+                storage.max_todos(),
+                undo.as_deref_mut(),
+            )?;
+            pending.byte_offset = end_offset;
+            pending.rows_committed += 1;
+        }
+        let finished = source_done && read.is_empty();
+
+        pending.updated_at = storage.clock().now();
+        if finished {
+            tx.execute(
+                "DELETE FROM import_checkpoints WHERE id = ?1",
+                [&pending.id],
+            )?;
+        } else {
+            tx.execute(
+                "UPDATE import_checkpoints
+                 SET byte_offset = ?2, rows_committed = ?3, decisions = ?4, updated_at = ?5
+                 WHERE id = ?1",
+                params![
+                    pending.id,
+                    pending.byte_offset as i64,
+                    pending.rows_committed as i64,
+                    serde_json::to_string(&pending.decisions).unwrap_or_default(),
+                    pending.updated_at,
+                ],
+            )?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        checkpoint = pending;
+        on_checkpoint(&checkpoint);
+        if finished {
+            // One checkpoint for the whole import rather than one per batch;
+            // it does nothing unless the store runs in WAL mode, and is
+            // skipped while another connection, such as the change feed's,
+            // is reading.
+            let _ = storage
+                .conn()
+                .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()));
+            let decisions = &checkpoint.decisions;
+            activity::todos_imported(
+                storage,
+                &checkpoint.id,
+                decisions.inserted + decisions.replaced,
+                &checkpoint.source_path.to_string_lossy(),
+            );
+            if decisions.warnings > 0 {
+                activity::validation_warned(storage, decisions.warnings);
+            }
+            return Ok(ImportSummary {
+                checkpoint,
+                finished: true,
+            });
+        }
+    }
+}
+
+/// Reads every valid row of a file without storing anything. Custom field
+/// columns are ignored, and rows without an id get one derived from their
+/// title, so the same row keeps its id when the file is edited around it.
+pub fn read_todos(path: &Path, format: ImportFormat) -> Result<Vec<Todo>, ImportError> {
+    let mut source = open_source(path, format, 0)?;
+    let mut todos = Vec::new();
+    while let Some((record, _)) = source.next_record()? {
+        let Ok(record) = record else { continue };
+        if record.title.trim().is_empty() {
+            continue;
+        }
+        let id = record.id.clone().unwrap_or_else(|| {
+            let digest = Sha256::digest(record.title.trim().as_bytes());
+            format!("title-{:x}", digest)[..22].to_string()
+        });
+        todos.push(record.into_todo(id));
+    }
+    Ok(todos)
+}
+
+/// Validation rules a row of an import file breaks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RowViolations {
+    /// 1-based, as in the errors of [`ImportDecisions`].
+    pub row: u64,
+    pub violations: Vec<Violation>,
+}
+
+/// What importing a file would do, worked out without writing anything.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPreview {
+    pub rows: u64,
+    /// Valid rows whose id is not cached yet.
+    pub new_todos: u64,
+    pub invalid: u64,
+    /// Set when committing would pass a hard limit.
+    pub quota_exceeded: Option<QuotaExceeded>,
+    /// Rows that break `rules`; those with a blocking violation would be
+    /// rejected, and are not counted as new.
+    pub violations: Vec<RowViolations>,
+}
+
+/// Dry run of importing `path`: counts the rows it would add, using the same
+/// ids a real import would, checks them against `rules` and the result
+/// against the todo limit.
+pub fn preview_import(
+    storage: &Storage,
+    path: &Path,
+    rules: &RuleSet,
+) -> Result<ImportPreview, ImportError> {
+    let format = ImportFormat::detect(path).map_or_else(|| ImportFormat::from_path(path), Ok)?;
+    let source_hash = hash_file(path)?;
+    let mut source = open_source(path, format, 0)?;
+    let conn = storage.conn();
+    let mut seen = HashSet::new();
+    let mut preview = ImportPreview {
+        rows: 0,
+        new_todos: 0,
+        invalid: 0,
+        quota_exceeded: None,
+        violations: Vec::new(),
+    };
+    let mut offset = 0;
+    while let Some((record, end_offset)) = source.next_record()? {
+        preview.rows += 1;
+        match record {
+            Ok(record) if !record.title.trim().is_empty() => {
+                let id = record_id(&record, &source_hash, offset);
+                let cached = conn
+                    .query_row("SELECT 1 FROM todos WHERE id = ?1", [&id], |_| Ok(()))
+                    .optional()?
+                    .is_some();
+                let violations = rules.check(&record.into_todo(id.clone()));
+                let blocked = validation_rules::is_blocking(&violations);
+                if !violations.is_empty() {
+                    preview.violations.push(RowViolations {
+                        row: preview.rows,
+                        violations,
+                    });
+                }
+                if !blocked && !cached && seen.insert(id) {
+                    preview.new_todos += 1;
+                }
+            }
+            _ => preview.invalid += 1,
+        }
+        offset = end_offset;
+    }
+    let total = quota::count_todos(&conn)? + preview.new_todos;
+    preview.quota_exceeded = quota::check_todos(storage, total).err();
+    Ok(preview)
+}
+
+/// Outcome of importing one file of a directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FileImportReport {
+    pub path: PathBuf,
+    pub decisions: Option<ImportDecisions>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DirImportReport {
+    pub files: Vec<FileImportReport>,
+    /// Files that were neither hidden nor temporary but not importable either.
+    pub unrecognized: Vec<PathBuf>,
+}
+
+/// Dotfiles and the leftovers of editors, office suites and browsers.
+fn is_hidden_or_temp(name: &str) -> bool {
+    const TEMP_SUFFIXES: &[&str] = &["~", ".tmp", ".swp", ".part", ".crdownload", ".bak"];
+    name.starts_with('.')
+        || name.starts_with("~$")
+        || TEMP_SUFFIXES
+            .iter()
+            .any(|suffix| name.to_ascii_lowercase().ends_with(suffix))
+}
+
+fn collect_files(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if is_hidden_or_temp(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive {
+                collect_files(&path, recursive, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Imports every recognized file under `dir`, carrying on past files that fail.
+pub fn import_dir(
+    storage: &Storage,
+    dir: &Path,
+    recursive: bool,
+    policy: ConflictPolicy,
+    rules: &RuleSet,
+    batch_rows: u64,
+) -> Result<DirImportReport, ImportError> {
+    let mut paths = Vec::new();
+    collect_files(dir, recursive, &mut paths)?;
+
+    let mut report = DirImportReport::default();
+    for path in paths {
+        if ImportFormat::detect(&path).is_none() {
+            report.unrecognized.push(path);
+            continue;
+        }
+        let result = begin_import(storage, &path, policy).and_then(|checkpoint| {
+            run_import(storage, checkpoint, rules, batch_rows, |_| false, |_| {})
+        });
+        report.files.push(match result {
+            Ok(summary) => FileImportReport {
+                path,
+                decisions: Some(summary.checkpoint.decisions),
+                error: None,
+            },
+            Err(e) => FileImportReport {
+                path,
+                decisions: None,
+                error: Some(e.to_string()),
+            },
+        });
+    }
+    Ok(report)
+}
+
+/// Cancellation flags of the imports running in this process.
+#[derive(Default)]
+pub struct ImportState {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl ImportState {
+    fn running(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<AtomicBool>>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Cancels every running import and waits until all have stopped after
+    /// their current row.
+    pub fn cancel_all(&self) {
+        for cancel in self.running().values() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        // Real time, not the app's clock: this waits on other threads.
+        while !self.running().is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+}
+
+/// Runs an import, cancellable with `cancel_operation(operation_id)` if
+/// given, in addition to the resumable stop of `cancel_import`.
+fn drive(
+    app: &AppHandle,
+    checkpoint: ImportCheckpoint,
+    operation_id: Option<String>,
+) -> Result<ImportSummary, WriteError> {
+    let operations = app.state::<Operations>();
+    let operation = match &operation_id {
+        Some(id) => Some(
+            operations
+                .start(id)
+                .map_err(|e| format!("Failed to import: {}", e))?,
+        ),
+        None => None,
+    };
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let state = app.state::<ImportState>();
+        let mut running = state.running();
+        if running.contains_key(&checkpoint.id) {
+            return Err("Failed to import: this import is already running"
+                .to_string()
+                .into());
+        }
+        running.insert(checkpoint.id.clone(), cancel.clone());
+    }
+
+    let id = checkpoint.id.clone();
+    let storage = app.state::<Storage>();
+    let settings = app.state::<SettingsState>().get();
+    let batch_rows = settings.import.batch_rows;
+    let rules = RuleSet::load(&storage, &settings.validation_rules)
+        .map_err(|e| format!("Failed to import: {}", e))?;
+    // Bytes of the source read so far, which is what the checkpoint tracks.
+    let total = std::fs::metadata(&checkpoint.source_path)
+        .ok()
+        .map(|metadata| metadata.len());
+    let registry = app.state::<ProgressRegistry>();
+    let progress = registry.register(
+        operation_id.as_deref().unwrap_or(&id),
+        "import",
+        total,
+        storage.clock().now(),
+    );
+    if let Ok(progress) = &progress {
+        progress.update(checkpoint.byte_offset, total);
+    }
+    let interrupt = |_| cancel.load(Ordering::Relaxed);
+    let on_checkpoint = |checkpoint: &ImportCheckpoint| {
+        if let Ok(progress) = &progress {
+            progress.update(checkpoint.byte_offset, total);
+        }
+        let _ = events::emit(app, "import-progress", checkpoint);
+    };
+    let result = match &operation {
+        Some(operation) => run_cancellable_import(
+            &storage,
+            checkpoint,
+            &rules,
+            batch_rows,
+            &operation.token,
+            interrupt,
+            on_checkpoint,
+        ),
+        None => run_import(
+            &storage,
+            checkpoint,
+            &rules,
+            batch_rows,
+            interrupt,
+            on_checkpoint,
+        ),
+    };
+    app.state::<ImportState>().running().remove(&id);
+    drop(progress);
+    drop(operation);
+    if let (Err(ImportError::Cancelled), Some(operation_id)) = (&result, &operation_id) {
+        operations::announce_cancelled(app, operation_id);
+    }
+
+    if let Ok(todos) = storage.list_todos() {
+        app.state::<SearchIndex>().rebuild(&todos);
+    }
+    let _ = events::emit(app, "todo-cache-updated", ());
+    quota::notify(app);
+    result.map_err(|e| match e {
+        ImportError::Quota(e) => e.into(),
+        e => format!("Failed to import: {}", e).into(),
+    })
+}
+
+#[tauri::command]
+pub async fn import_todos(
+    path: PathBuf,
+    policy: Option<ConflictPolicy>,
+    operation_id: Option<String>,
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<ImportSummary, WriteError> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        let import = || {
+            let path = file_access::check(&app, &path)?;
+            let checkpoint =
+                begin_import(&app.state::<Storage>(), &path, policy.unwrap_or_default())
+                    .map_err(|e| format!("Failed to start import: {}", e))?;
+            drive(&app, checkpoint, operation_id)
+        };
+        import().inspect_err(|e| {
+            retry::record(
+                &app,
+                retry::Operation::ImportTodos { path: path.clone() },
+                e,
+            )
+        })
+    }))
+    .await
+    .map_err(|e| format!("Failed to import: {}", e))?
+}
+
+#[tauri::command]
+pub async fn resume_import(
+    checkpoint_id: String,
+    operation_id: Option<String>,
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<ImportSummary, WriteError> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        let checkpoint = find_checkpoint(&app.state::<Storage>(), &checkpoint_id)
+            .map_err(|e| format!("Failed to resume import: {}", e))?;
+        drive(&app, checkpoint, operation_id)
+    }))
+    .await
+    .map_err(|e| format!("Failed to import: {}", e))?
+}
+
+#[tauri::command]
+pub async fn import_directory(
+    folder: String,
+    recursive: bool,
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<DirImportReport, PathError> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        let dir = file_access::check(&app, &folder)?;
+        let storage = app.state::<Storage>();
+        let settings = app.state::<SettingsState>().get();
+        let rules = RuleSet::load(&storage, &settings.validation_rules)
+            .map_err(|e| format!("Failed to import {}: {}", folder, e))?;
+        let registry = app.state::<ProgressRegistry>();
+        let _progress = registry.register(
+            &format!("import-directory-{}", dir.display()),
+            "import-directory",
+            None,
+            storage.clock().now(),
+        )?;
+        let report = import_dir(
+            &storage,
+            &dir,
+            recursive,
+            ConflictPolicy::default(),
+            &rules,
+            settings.import.batch_rows,
+        )
+        .map_err(|e| format!("Failed to import {}: {}", folder, e))
+        .inspect_err(|e| {
+            let folder = folder.clone();
+            retry::record(&app, retry::Operation::ImportDirectory { folder }, e)
+        })?;
+        if let Ok(todos) = storage.list_todos() {
+            app.state::<SearchIndex>().rebuild(&todos);
+        }
+        let _ = events::emit(&app, "todo-cache-updated", ());
+        quota::notify(&app);
+        Ok(report)
+    }))
+    .await
+    .map_err(|e| format!("Failed to import: {}", e))?
+}
+
+#[tauri::command]
+pub async fn preview_import_file(
+    path: PathBuf,
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<ImportPreview, PathError> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        let path = file_access::check(&app, &path)?;
+        let storage = app.state::<Storage>();
+        let rules = RuleSet::load(
+            &storage,
+            &app.state::<SettingsState>().get().validation_rules,
+        )
+        .map_err(|e| format!("Failed to preview import: {}", e))?;
+        Ok(preview_import(&storage, &path, &rules)
+            .map_err(|e| format!("Failed to preview import: {}", e))?)
+    }))
+    .await
+    .map_err(|e| format!("Failed to preview import: {}", e))?
+}
+
+/// Unfinished imports that are not currently running, e.g. after a crash.
+#[tauri::command]
+pub fn list_interrupted_imports(
+    storage: State<'_, Storage>,
+    state: State<'_, ImportState>,
+) -> Result<Vec<ImportCheckpoint>, String> {
+    if storage.newer_schema().is_none() {
+        prune_checkpoints(&storage, storage.clock().now())
+            .map_err(|e| format!("Failed to prune imports: {}", e))?;
+    }
+    let running = state.running();
+    Ok(list_checkpoints(&storage)
+        .map_err(|e| format!("Failed to list imports: {}", e))?
+        .into_iter()
+        .filter(|c| !running.contains_key(&c.id))
+        .collect())
+}
+
+/// Stops a running import after its current row; it can be resumed later.
+#[tauri::command]
+pub fn cancel_import(checkpoint_id: String, state: State<'_, ImportState>) -> Result<(), String> {
+    let running = state.running();
+    let cancel = running.get(&checkpoint_id).ok_or_else(|| {
+        format!(
+            "Failed to cancel import: '{}' is not running",
+            checkpoint_id
+        )
+    })?;
+    cancel.store(true, Ordering::Relaxed);
+    Ok(())
+}
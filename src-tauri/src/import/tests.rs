@@ -0,0 +1,454 @@
+use super::*;
+use crate::custom_fields::{define_field, FieldType};
+use crate::storage::for_each_backend;
+use std::fs;
+
+/// What the batch arithmetic in these tests assumes.
+const BATCH_ROWS: u64 = 1000;
+
+fn write_csv(dir: &Path, rows: usize) -> PathBuf {
+    let mut content = String::from("title,priority,tags\n");
+    for i in 0..rows {
+        content.push_str(&format!("Task {},low,bulk;import\n", i));
+    }
+    let path = dir.join("todos.csv");
+    fs::write(&path, content).unwrap();
+    path
+}
+
+fn write_json(dir: &Path, rows: usize) -> PathBuf {
+    let items: Vec<String> = (0..rows)
+        .map(|i| {
+            format!(
+                r#"{{"title": "Task {} {{\"quoted\"}}", "priority": "high"}}"#,
+                i
+            )
+        })
+        .collect();
+    let path = dir.join("todos.json");
+    fs::write(&path, format!("[\n{}\n]\n", items.join(",\n"))).unwrap();
+    path
+}
+
+fn import_all(storage: &Storage, path: &Path) -> ImportSummary {
+    let checkpoint = begin_import(storage, path, ConflictPolicy::Skip).unwrap();
+    run_import(
+        storage,
+        checkpoint,
+        &RuleSet::default(),
+        BATCH_ROWS,
+        |_| false,
+        |_| {},
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_csv_import_maps_columns_and_custom_fields() {
+    let storage = Storage::open_in_memory().unwrap();
+    define_field(&storage, "estimate", FieldType::Number, false, None).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todos.csv");
+    fs::write(
+        &path,
+        "id,title,completed,priority,scheduled_for,tags,field:estimate\n\
+         a,Write report,true,high,2024-05-31,work;writing,2.5\n\
+         b,Bad priority,,urgent,,,\n\
+         c,Bad estimate,,,,,lots\n\
+         ,No id,,,,,\n",
+    )
+    .unwrap();
+
+    let summary = import_all(&storage, &path);
+    assert!(summary.finished);
+    let decisions = &summary.checkpoint.decisions;
+    assert_eq!((decisions.inserted, decisions.invalid), (2, 2));
+    assert_eq!(
+        decisions.errors,
+        vec![
+            "Row 2: invalid priority 'urgent'",
+            "Row 3: Field 'estimate' expects a number, got 'lots'",
+        ]
+    );
+
+    let todos = storage.list_todos().unwrap();
+    let report = todos.iter().find(|t| t.id == "a").unwrap();
+    assert!(report.completed);
+    assert_eq!(report.priority, Priority::High);
+    assert_eq!(report.tags, vec!["work", "writing"]);
+    assert_eq!(report.fields["estimate"], FieldValue::Number(2.5));
+    assert_eq!(report.scheduled_for, parse_datetime("2024-05-31"));
+    assert!(todos
+        .iter()
+        .any(|t| t.title == "No id" && t.id.starts_with("import-")));
+    // Finished imports leave no checkpoint behind.
+    assert!(list_checkpoints(&storage).unwrap().is_empty());
+}
+
+#[test]
+fn test_json_import_reads_arrays_and_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::open_in_memory().unwrap();
+    let summary = import_all(&storage, &write_json(dir.path(), 3));
+    assert_eq!(summary.checkpoint.decisions.inserted, 3);
+    assert_eq!(
+        storage.list_todos().unwrap()[0].title,
+        r#"Task 0 {"quoted"}"#
+    );
+
+    let lines = dir.path().join("todos.jsonl");
+    fs::write(
+        &lines,
+        "{\"id\": \"x\", \"title\": \"One\"}\n{\"title\": 5}\n",
+    )
+    .unwrap();
+    let summary = import_all(&storage, &lines);
+    assert_eq!(summary.checkpoint.decisions.inserted, 1);
+    assert_eq!(summary.checkpoint.decisions.invalid, 1);
+}
+
+#[test]
+fn test_interrupted_import_resumes_without_duplicates() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in [write_csv(dir.path(), 2500), write_json(dir.path(), 2500)] {
+        for_each_backend(|storage| {
+            let checkpoint = begin_import(storage, &path, ConflictPolicy::Skip).unwrap();
+            // Killed halfway through the second batch.
+            let summary = run_import(
+                storage,
+                checkpoint,
+                &RuleSet::default(),
+                BATCH_ROWS,
+                |rows| rows == 1500,
+                |_| {},
+            )
+            .unwrap();
+            assert!(!summary.finished);
+            assert_eq!(storage.list_todos().unwrap().len(), 1000);
+
+            let interrupted = list_checkpoints(storage).unwrap();
+            assert_eq!(interrupted.len(), 1);
+            assert_eq!(interrupted[0].rows_committed, 1000);
+            assert_eq!(interrupted[0], summary.checkpoint);
+
+            let resumed = run_import(
+                storage,
+                interrupted[0].clone(),
+                &RuleSet::default(),
+                BATCH_ROWS,
+                |_| false,
+                |_| {},
+            )
+            .unwrap();
+            assert!(resumed.finished);
+            assert_eq!(resumed.checkpoint.rows_committed, 2500);
+            assert_eq!(resumed.checkpoint.decisions.inserted, 2500);
+            assert_eq!(resumed.checkpoint.decisions.skipped, 0);
+            assert_eq!(storage.list_todos().unwrap().len(), 2500);
+            assert!(list_checkpoints(storage).unwrap().is_empty());
+        });
+    }
+}
+
+#[test]
+fn test_resume_refuses_changed_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::open_in_memory().unwrap();
+    let path = write_csv(dir.path(), 10);
+    let checkpoint = begin_import(&storage, &path, ConflictPolicy::Skip).unwrap();
+    run_import(
+        &storage,
+        checkpoint,
+        &RuleSet::default(),
+        BATCH_ROWS,
+        |rows| rows == 5,
+        |_| {},
+    )
+    .unwrap();
+
+    write_csv(dir.path(), 11);
+    let checkpoint = list_checkpoints(&storage).unwrap().remove(0);
+    let error = run_import(
+        &storage,
+        checkpoint,
+        &RuleSet::default(),
+        BATCH_ROWS,
+        |_| false,
+        |_| {},
+    )
+    .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("changed since the import started"));
+}
+
+#[test]
+fn test_conflict_policies() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::open_in_memory().unwrap();
+    let path = dir.path().join("todos.csv");
+    fs::write(&path, "id,title,updated_at\na,Original,2024-01-01\n").unwrap();
+    import_all(&storage, &path);
+
+    fs::write(&path, "id,title,updated_at\na,Older,2023-01-01\n").unwrap();
+    let checkpoint = begin_import(&storage, &path, ConflictPolicy::KeepNewer).unwrap();
+    let summary = run_import(
+        &storage,
+        checkpoint,
+        &RuleSet::default(),
+        BATCH_ROWS,
+        |_| false,
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(summary.checkpoint.decisions.skipped, 1);
+    assert_eq!(storage.list_todos().unwrap()[0].title, "Original");
+
+    let checkpoint = begin_import(&storage, &path, ConflictPolicy::Replace).unwrap();
+    let summary = run_import(
+        &storage,
+        checkpoint,
+        &RuleSet::default(),
+        BATCH_ROWS,
+        |_| false,
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(summary.checkpoint.decisions.replaced, 1);
+    assert_eq!(storage.list_todos().unwrap()[0].title, "Older");
+}
+
+#[test]
+fn test_old_checkpoints_are_pruned() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::open_in_memory().unwrap();
+    begin_import(&storage, &write_csv(dir.path(), 1), ConflictPolicy::Skip).unwrap();
+
+    assert_eq!(prune_checkpoints(&storage, Utc::now()).unwrap(), 0);
+    assert_eq!(
+        prune_checkpoints(&storage, Utc::now() + Duration::days(8)).unwrap(),
+        1
+    );
+    assert!(list_checkpoints(&storage).unwrap().is_empty());
+}
+
+#[test]
+fn test_directory_import_walks_and_collects_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("nested/deeper")).unwrap();
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::write(root.join("a.csv"), "title\nTop level\n").unwrap();
+    fs::write(root.join("broken.csv"), "name\nNo title column\n").unwrap();
+    fs::write(root.join("notes.txt"), "just some notes\n").unwrap();
+    fs::write(root.join(".hidden.csv"), "title\nHidden\n").unwrap();
+    fs::write(root.join("a.csv~"), "title\nBackup\n").unwrap();
+    fs::write(root.join(".git/config.json"), "[]").unwrap();
+    fs::write(root.join("nested/b.json"), r#"[{"title": "Nested"}]"#).unwrap();
+    fs::write(root.join("nested/export"), r#"{"title": "Sniffed"}"#).unwrap();
+    fs::write(root.join("nested/deeper/bad.json"), r#"[{"title": "#).unwrap();
+
+    let storage = Storage::open_in_memory().unwrap();
+    let report = import_dir(
+        &storage,
+        root,
+        false,
+        ConflictPolicy::Skip,
+        &RuleSet::default(),
+        BATCH_ROWS,
+    )
+    .unwrap();
+    let names: Vec<_> = report
+        .files
+        .iter()
+        .map(|f| f.path.file_name().unwrap())
+        .collect();
+    assert_eq!(names, vec!["a.csv", "broken.csv"]);
+    assert_eq!(report.unrecognized, vec![root.join("notes.txt")]);
+
+    let report = import_dir(
+        &storage,
+        root,
+        true,
+        ConflictPolicy::Skip,
+        &RuleSet::default(),
+        BATCH_ROWS,
+    )
+    .unwrap();
+    let outcome: Vec<_> = report
+        .files
+        .iter()
+        .map(|f| {
+            (
+                f.path
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned(),
+                f.decisions.as_ref().map(|d| d.inserted + d.skipped),
+                f.error.is_some(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        outcome,
+        vec![
+            ("a.csv".to_string(), Some(1), false),
+            ("broken.csv".to_string(), None, true),
+            ("nested/b.json".to_string(), Some(1), false),
+            ("nested/deeper/bad.json".to_string(), None, true),
+            ("nested/export".to_string(), Some(1), false),
+        ]
+    );
+    assert_eq!(
+        report.files[1].error.as_deref(),
+        Some("CSV file has no 'title' column")
+    );
+
+    let mut titles: Vec<_> = storage
+        .list_todos()
+        .unwrap()
+        .into_iter()
+        .map(|t| t.title)
+        .collect();
+    titles.sort();
+    assert_eq!(titles, vec!["Nested", "Sniffed", "Top level"]);
+}
+
+#[test]
+fn test_import_stops_at_todo_limit_and_resumes() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::open_in_memory().unwrap();
+    let path = write_csv(dir.path(), 2500);
+    storage.set_max_todos(1200);
+
+    let preview = preview_import(&storage, &path, &RuleSet::default()).unwrap();
+    assert_eq!((preview.rows, preview.new_todos), (2500, 2500));
+    assert_eq!(
+        preview.quota_exceeded,
+        Some(QuotaExceeded {
+            dimension: QuotaDimension::Todos,
+            limit: 1200,
+            requested: 2500,
+        })
+    );
+
+    let checkpoint = begin_import(&storage, &path, ConflictPolicy::Skip).unwrap();
+    let error = run_import(
+        &storage,
+        checkpoint,
+        &RuleSet::default(),
+        BATCH_ROWS,
+        |_| false,
+        |_| {},
+    )
+    .unwrap_err();
+    assert!(matches!(error, ImportError::Quota(e) if e.requested == 1201));
+    // The batch that hit the limit was rolled back whole.
+    assert_eq!(storage.list_todos().unwrap().len(), 1000);
+
+    storage.set_max_todos(u64::MAX);
+    let checkpoint = list_checkpoints(&storage).unwrap().remove(0);
+    assert!(
+        run_import(
+            &storage,
+            checkpoint,
+            &RuleSet::default(),
+            BATCH_ROWS,
+            |_| false,
+            |_| {}
+        )
+        .unwrap()
+        .finished
+    );
+    assert_eq!(storage.list_todos().unwrap().len(), 2500);
+    let preview = preview_import(&storage, &path, &RuleSet::default()).unwrap();
+    assert_eq!((preview.new_todos, preview.quota_exceeded), (0, None));
+}
+
+#[test]
+fn test_batched_import_matches_row_by_row_import() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todos.csv");
+    let mut content = String::from("title,priority,tags,created_at\n");
+    for i in 0..1200 {
+        content.push_str(&format!("Task {},low,bulk;import,2024-05-01\n", i));
+    }
+    fs::write(&path, content).unwrap();
+    let import = |batch_rows| {
+        let storage = Storage::open_in_memory().unwrap();
+        let checkpoint = begin_import(&storage, &path, ConflictPolicy::Skip).unwrap();
+        let mut batches = 0;
+        let summary = run_import(
+            &storage,
+            checkpoint,
+            &RuleSet::default(),
+            batch_rows,
+            |_| false,
+            |_| batches += 1,
+        )
+        .unwrap();
+        assert!(summary.finished);
+        (storage.list_todos().unwrap(), batches)
+    };
+
+    let (row_by_row, batches) = import(1);
+    assert_eq!(batches, 1201);
+    let (batched, batches) = import(500);
+    assert_eq!(batches, 3);
+    assert_eq!(batched.len(), 1200);
+    assert_eq!(batched, row_by_row);
+}
+
+#[test]
+fn test_cancelled_import_rolls_back_and_returns_promptly() {
+    for_each_backend(|storage| {
+        let dir = tempfile::tempdir().unwrap();
+        let created = Utc::now() - Duration::days(30);
+        let existing = crate::types::Todo::sample("row-1", "Keep me", created);
+        storage.save_todo(&existing).unwrap();
+        let before = storage.list_todos().unwrap();
+
+        let mut content = String::from("id,title\n");
+        for i in 0..5000 {
+            content.push_str(&format!("row-{},Task {}\n", i, i));
+        }
+        let path = dir.path().join("todos.csv");
+        fs::write(&path, content).unwrap();
+
+        let operations = crate::operations::Operations::default();
+        let operation = operations.start("big-import").unwrap();
+        let checkpoint = begin_import(storage, &path, ConflictPolicy::Replace).unwrap();
+        let cancelled_at = std::cell::Cell::new(None);
+        let error = run_cancellable_import(
+            storage,
+            checkpoint,
+            &RuleSet::default(),
+            BATCH_ROWS,
+            &operation.token,
+            |rows| {
+                if rows == 2500 {
+                    operations.cancel("big-import").unwrap();
+                    cancelled_at.set(Some(std::time::Instant::now()));
+                }
+                false
+            },
+            |_| {},
+        )
+        .unwrap_err();
+        let stopped_after = cancelled_at.get().unwrap().elapsed();
+
+        assert!(matches!(error, ImportError::Cancelled));
+        assert!(
+            stopped_after < std::time::Duration::from_secs(2),
+            "{:?}",
+            stopped_after
+        );
+        // Restoring the replaced todo is a write of its own.
+        let after = storage.list_todos().unwrap();
+        let mut expected = before.clone();
+        expected[0].revision = after[0].revision;
+        assert_eq!(after, expected);
+        assert!(list_checkpoints(storage).unwrap().is_empty());
+    });
+}
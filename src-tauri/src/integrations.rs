@@ -0,0 +1,4 @@
+//! Importers for what other apps export.
+
+pub mod bookmarks;
+pub mod connectors;
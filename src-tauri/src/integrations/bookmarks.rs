@@ -0,0 +1,253 @@
+//! Browser bookmarks as read-later todos, from the Netscape bookmark file
+//! every major browser exports. Each link becomes a todo titled with the
+//! link text, with the URL as its description and the folders it was in as
+//! tags.
+
+use std::fs;
+
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use tauri::{AppHandle, Manager};
+
+use crate::events;
+use crate::file_access;
+use crate::quota;
+use crate::search::SearchIndex;
+use crate::settings::SettingsState;
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::{Priority, Todo};
+use crate::validation_rules::{self, RuleSet};
+
+#[cfg(test)]
+mod tests;
+
+/// Links that only mean something inside the browser that exported them.
+const IGNORED_SCHEMES: &[&str] = &["place:", "javascript:", "chrome:", "about:"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    /// Enclosing folders, outermost first.
+    pub folders: Vec<String>,
+}
+
+/// The links in a bookmark file, in file order. Anything that isn't a link
+/// or a folder heading is skipped, so files from any browser parse.
+pub fn parse(html: &str) -> Vec<Bookmark> {
+    let mut bookmarks = Vec::new();
+    // One entry per open <DL>: the folder it lists, if it had a heading.
+    let mut open: Vec<Option<String>> = Vec::new();
+    let mut heading = None;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        let text = decode(rest[..rest.find('<').unwrap_or(rest.len())].trim());
+        match tag_name(tag).as_str() {
+            "dl" => open.push(heading.take()),
+            "/dl" => {
+                open.pop();
+            }
+            "h3" => heading = Some(text),
+            "a" => {
+                let Some(url) = attribute(tag, "href").filter(|url| !ignored(url)) else {
+                    continue;
+                };
+                bookmarks.push(Bookmark {
+                    title: text,
+                    url,
+                    folders: open.iter().flatten().cloned().collect(),
+                });
+            }
+            _ => {}
+        }
+    }
+    bookmarks
+}
+
+fn ignored(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    url.is_empty() || IGNORED_SCHEMES.iter().any(|scheme| url.starts_with(scheme))
+}
+
+/// Lowercased, with a leading `/` for closing tags.
+fn tag_name(tag: &str) -> String {
+    let end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    tag[..end].to_ascii_lowercase()
+}
+
+/// The decoded value of attribute `wanted` in the inside of a tag.
+fn attribute(tag: &str, wanted: &str) -> Option<String> {
+    let mut rest = tag.split_once(char::is_whitespace)?.1;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            (value, rest) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let close = inner.find(quote).unwrap_or(inner.len());
+                    (&inner[..close], inner.get(close + 1..).unwrap_or(""))
+                }
+                _ => after.split_at(after.find(char::is_whitespace).unwrap_or(after.len())),
+            };
+        }
+        if key.eq_ignore_ascii_case(wanted) {
+            return Some(decode(value));
+        }
+    }
+}
+
+/// Replaces the character references browsers write in titles and URLs.
+fn decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let character = reference.and_then(|name| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let number = name.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (character, reference) {
+            (Some(character), Some(name)) => {
+                decoded.push(character);
+                rest = &rest[name.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// A new todo for `bookmark`, titled with its URL when the link has no text.
+pub fn to_todo(bookmark: Bookmark, now: DateTime<Utc>) -> Todo {
+    let title = if bookmark.title.is_empty() {
+        bookmark.url.clone()
+    } else {
+        bookmark.title
+    };
+    let mut tags: Vec<String> = Vec::new();
+    for folder in bookmark.folders {
+        if !folder.is_empty() && !tags.contains(&folder) {
+            tags.push(folder);
+        }
+    }
+    Todo {
+        id: uuid::Uuid::new_v4().to_string(),
+        title,
+        description: Some(bookmark.url),
+        completed: false,
+        priority: Priority::default(),
+        scheduled_for: None,
+        created_at: now,
+        updated_at: now,
+        completed_at: None,
+        order: None,
+        rank: None,
+        schedule_id: None,
+        tags,
+        languages: Default::default(),
+        fields: Default::default(),
+        estimate_minutes: None,
+        actual_minutes: None,
+        revision: 0,
+    }
+}
+
+/// Adds a todo for every bookmark in the file, in one transaction, and
+/// returns them as stored. Bookmarks whose todo breaks a blocking rule of
+/// `rules` are left out, as rejected rows of an import are.
+pub fn import(storage: &Storage, html: &str, rules: &RuleSet) -> Result<Vec<Todo>, String> {
+    let now = storage.clock().now();
+    let mut warnings = Vec::new();
+    let mut todos = Vec::new();
+    for todo in parse(html)
+        .into_iter()
+        .map(|bookmark| to_todo(bookmark, now))
+    {
+        let violations = rules.check(&todo);
+        if validation_rules::is_blocking(&violations) {
+            trace::log(format!(
+                "Skipped bookmark '{}': {}",
+                todo.title,
+                validation_rules::describe(&violations)
+            ));
+            continue;
+        }
+        warnings.extend(violations);
+        todos.push(todo);
+    }
+    let mut conn = storage.conn();
+    let total = quota::count_todos(&conn).map_err(|e| format!("Failed to count todos: {}", e))?
+        + todos.len() as u64;
+    quota::check_todos(storage, total).map_err(|e| e.to_string())?;
+    insert_all(&mut conn, &todos).map_err(|e| format!("Failed to save bookmarks: {}", e))?;
+    drop(conn);
+    validation_rules::note_warnings(storage, &warnings);
+    // Read back the order keys the cache gave them.
+    todos
+        .iter()
+        .map(|todo| Ok(storage.get_todo(&todo.id)?.unwrap_or_else(|| todo.clone())))
+        .collect::<rusqlite::Result<_>>()
+        .map_err(|e| format!("Failed to load todos: {}", e))
+}
+
+fn insert_all(conn: &mut Connection, todos: &[Todo]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for todo in todos {
+        storage::insert_todo(&tx, todo)?;
+    }
+    tx.commit()
+}
+
+#[tauri::command]
+pub fn import_bookmarks(path: String, app: AppHandle) -> Result<Vec<Todo>, String> {
+    let path = file_access::check(&app, &path).map_err(|e| e.to_string())?;
+    let html = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let storage = app.state::<Storage>();
+    let rules = RuleSet::load(
+        &storage,
+        &app.state::<SettingsState>().get().validation_rules,
+    )
+    .map_err(|e| format!("Failed to import bookmarks: {}", e))?;
+    let todos = import(&storage, &html, &rules)?;
+    if let Ok(all) = storage.list_todos() {
+        app.state::<SearchIndex>().rebuild(&all);
+    }
+    let _ = events::emit(&app, "todo-cache-updated", ());
+    quota::notify(&app);
+    Ok(todos)
+}
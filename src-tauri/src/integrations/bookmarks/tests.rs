@@ -0,0 +1,128 @@
+use super::*;
+
+const EXPORT: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000" PERSONAL_TOOLBAR_FOLDER="true">Bookmarks bar</H3>
+    <DL><p>
+        <DT><A HREF="https://example.com/top" ADD_DATE="1700000001">Top level</A>
+        <DT><H3>Rust</H3>
+        <DL><p>
+            <DT><A HREF="https://doc.rust-lang.org/book/" ICON="data:image/png;base64,AAAA">The Book</A>
+            <DT><H3>Async &amp; Await</H3>
+            <DL><p>
+                <DT><A HREF="https://tokio.rs/tokio/tutorial">Tokio tutorial</A>
+            </DL><p>
+        </DL><p>
+        <DT><A HREF="https://example.com/after">Back in the bar</A>
+    </DL><p>
+    <DT><a href='https://example.com/loose'>Loose</a>
+    <DT><A HREF="place:sort=8&amp;maxResults=10">Recent tags</A>
+</DL><p>
+"#;
+
+fn folders(bookmarks: &[Bookmark], url: &str) -> Vec<String> {
+    bookmarks
+        .iter()
+        .find(|bookmark| bookmark.url == url)
+        .unwrap()
+        .folders
+        .clone()
+}
+
+#[test]
+fn test_nested_folders_become_tags() {
+    let bookmarks = parse(EXPORT);
+
+    assert_eq!(bookmarks.len(), 5);
+    assert_eq!(
+        folders(&bookmarks, "https://tokio.rs/tokio/tutorial"),
+        ["Bookmarks bar", "Rust", "Async & Await"]
+    );
+    assert_eq!(
+        folders(&bookmarks, "https://doc.rust-lang.org/book/"),
+        ["Bookmarks bar", "Rust"]
+    );
+    // Closing a folder returns to its parent.
+    assert_eq!(
+        folders(&bookmarks, "https://example.com/after"),
+        ["Bookmarks bar"]
+    );
+    assert!(folders(&bookmarks, "https://example.com/loose").is_empty());
+
+    let todo = to_todo(bookmarks[2].clone(), chrono::Utc::now());
+    assert_eq!(todo.title, "Tokio tutorial");
+    assert_eq!(
+        todo.description.as_deref(),
+        Some("https://tokio.rs/tokio/tutorial")
+    );
+    assert_eq!(todo.tags, ["Bookmarks bar", "Rust", "Async & Await"]);
+}
+
+#[test]
+fn test_bookmark_without_title_is_titled_with_its_url() {
+    let bookmarks = parse(r#"<DL><p><DT><A HREF="https://example.com/?a=1&amp;b=2"></A></DL>"#);
+
+    let todo = to_todo(bookmarks[0].clone(), chrono::Utc::now());
+
+    assert_eq!(todo.title, "https://example.com/?a=1&b=2");
+    assert_eq!(
+        todo.description.as_deref(),
+        Some("https://example.com/?a=1&b=2")
+    );
+    assert!(todo.tags.is_empty());
+}
+
+#[test]
+fn test_browser_internal_links_are_skipped() {
+    let urls: Vec<String> = parse(EXPORT).into_iter().map(|b| b.url).collect();
+
+    assert!(urls.iter().all(|url| !url.starts_with("place:")));
+}
+
+#[test]
+fn test_character_references_are_decoded() {
+    assert_eq!(decode("Q&amp;A &#8212; &#x41;&lt;&gt;"), "Q&A \u{2014} A<>");
+    assert_eq!(decode("AT&T & co &unknown;"), "AT&T & co &unknown;");
+}
+
+#[test]
+fn test_import_stores_every_link() {
+    let storage = Storage::open_in_memory().unwrap();
+
+    let todos = import(&storage, EXPORT, &RuleSet::default()).unwrap();
+
+    assert_eq!(todos.len(), 5);
+    assert!(todos.iter().all(|todo| todo.revision > 0));
+    assert_eq!(storage.list_todos().unwrap().len(), 5);
+}
+
+#[test]
+fn test_import_leaves_out_links_breaking_a_blocking_rule() {
+    let storage = Storage::open_in_memory().unwrap();
+    let rules = RuleSet::compile(
+        &storage.conn(),
+        &[validation_rules::ValidationRule {
+            name: "Starts with T".to_string(),
+            list: None,
+            tag: None,
+            check: validation_rules::RuleCheck::TitleMatches {
+                pattern: "^T".to_string(),
+            },
+            severity: validation_rules::Severity::Block,
+        }],
+        chrono::NaiveDate::MIN,
+    )
+    .unwrap();
+
+    let todos = import(&storage, EXPORT, &rules).unwrap();
+
+    let titles: Vec<&str> = todos.iter().map(|todo| todo.title.as_str()).collect();
+    assert_eq!(titles, ["Top level", "The Book", "Tokio tutorial"]);
+    assert_eq!(storage.list_todos().unwrap().len(), 3);
+}
@@ -0,0 +1,730 @@
+//! Sources todos are pulled from again and again, each kind behind the
+//! [`Connector`] trait. An instance is one configured source, such as a
+//! bookmark file, that the scheduler re-syncs every `interval_minutes`.
+//!
+//! The todos an instance created are remembered by the item's dedup key in
+//! `connector_items`, which marks them as coming from that source: a re-sync
+//! updates them in place and removes them once the source no longer has
+//! them. A todo deleted locally stays deleted while the source still has it.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::activity;
+use crate::events;
+use crate::file_access::{self, PathError};
+use crate::integrations::bookmarks::{self, Bookmark};
+use crate::local_api::KEYCHAIN_SERVICE;
+use crate::quota;
+use crate::retry;
+use crate::search::SearchIndex;
+use crate::storage::{self, Storage};
+use crate::sync;
+use crate::trace;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+/// Every connector instances can be added for.
+const CONNECTORS: &[&dyn Source] = &[&BookmarkFile, &RemoteList];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum CredentialRequirement {
+    None,
+    Optional,
+    Required,
+}
+
+/// What a fetch found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fetched<T> {
+    /// The source is unchanged since the cursor the fetch was given.
+    NotModified,
+    /// All items the source has, and the cursor to fetch with next time.
+    Items {
+        items: Vec<T>,
+        cursor: Option<String>,
+    },
+}
+
+pub struct FetchRequest<'a> {
+    pub storage: &'a Storage,
+    pub config: &'a Value,
+    /// What the last fetch returned as its cursor, e.g. an ETag.
+    pub cursor: Option<&'a str>,
+    pub credential: Option<&'a str>,
+}
+
+/// A kind of source. Implementing it and listing the connector in
+/// `CONNECTORS` is all a new kind takes.
+pub trait Connector: Sync {
+    type Item;
+
+    /// Name instances are stored with.
+    fn kind(&self) -> &'static str;
+
+    fn credential(&self) -> CredentialRequirement {
+        CredentialRequirement::None
+    }
+
+    /// Rejects a config that can't be fetched with.
+    fn check_config(&self, config: &Value) -> Result<(), String>;
+
+    fn fetch(&self, request: &FetchRequest<'_>) -> Result<Fetched<Self::Item>, String>;
+
+    /// A new todo for `item`.
+    fn to_todo(&self, item: Self::Item, now: DateTime<Utc>) -> Todo;
+
+    /// Identifies an item across fetches; of items sharing one, the first
+    /// wins.
+    fn dedup_key(&self, item: &Self::Item) -> String;
+
+    /// Whether the source says which items are done. If not, completing
+    /// one of its todos locally sticks across re-syncs.
+    fn syncs_completion(&self) -> bool {
+        true
+    }
+}
+
+/// A connector with its items mapped, so different kinds fit in one list.
+trait Source: Sync {
+    fn kind(&self) -> &'static str;
+    fn credential(&self) -> CredentialRequirement;
+    fn check_config(&self, config: &Value) -> Result<(), String>;
+    fn syncs_completion(&self) -> bool;
+    fn pull(
+        &self,
+        request: &FetchRequest<'_>,
+        now: DateTime<Utc>,
+    ) -> Result<Fetched<(String, Todo)>, String>;
+}
+
+impl<C: Connector> Source for C {
+    fn kind(&self) -> &'static str {
+        Connector::kind(self)
+    }
+
+    fn credential(&self) -> CredentialRequirement {
+        Connector::credential(self)
+    }
+
+    fn check_config(&self, config: &Value) -> Result<(), String> {
+        Connector::check_config(self, config)
+    }
+
+    fn syncs_completion(&self) -> bool {
+        Connector::syncs_completion(self)
+    }
+
+    fn pull(
+        &self,
+        request: &FetchRequest<'_>,
+        now: DateTime<Utc>,
+    ) -> Result<Fetched<(String, Todo)>, String> {
+        Ok(match self.fetch(request)? {
+            Fetched::NotModified => Fetched::NotModified,
+            Fetched::Items { items, cursor } => Fetched::Items {
+                items: items
+                    .into_iter()
+                    .map(|item| (self.dedup_key(&item), self.to_todo(item, now)))
+                    .collect(),
+                cursor,
+            },
+        })
+    }
+}
+
+fn find(kind: &str) -> Option<&'static dyn Source> {
+    CONNECTORS
+        .iter()
+        .copied()
+        .find(|source| source.kind() == kind)
+}
+
+fn parse_config<T: DeserializeOwned>(config: &Value) -> Result<T, String> {
+    T::deserialize(config).map_err(|e| format!("Invalid connector config: {}", e))
+}
+
+#[derive(Deserialize)]
+struct FileConfig {
+    path: PathBuf,
+}
+
+/// A bookmark file a browser or an extension keeps exporting to. Bookmarks
+/// are matched by URL.
+pub struct BookmarkFile;
+
+impl Connector for BookmarkFile {
+    type Item = Bookmark;
+
+    fn kind(&self) -> &'static str {
+        "bookmark-file"
+    }
+
+    fn check_config(&self, config: &Value) -> Result<(), String> {
+        parse_config::<FileConfig>(config).map(|_| ())
+    }
+
+    /// The cursor is a hash of the file, as files have no ETag.
+    fn fetch(&self, request: &FetchRequest<'_>) -> Result<Fetched<Bookmark>, String> {
+        let config: FileConfig = parse_config(request.config)?;
+        let html = fs::read_to_string(&config.path)
+            .map_err(|e| format!("Failed to read {}: {}", config.path.display(), e))?;
+        let fingerprint = format!("{:x}", Sha256::digest(html.as_bytes()));
+        if request.cursor == Some(fingerprint.as_str()) {
+            return Ok(Fetched::NotModified);
+        }
+        Ok(Fetched::Items {
+            items: bookmarks::parse(&html),
+            cursor: Some(fingerprint),
+        })
+    }
+
+    fn to_todo(&self, item: Bookmark, now: DateTime<Utc>) -> Todo {
+        bookmarks::to_todo(item, now)
+    }
+
+    fn dedup_key(&self, item: &Bookmark) -> String {
+        item.url.clone()
+    }
+
+    fn syncs_completion(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Deserialize)]
+struct UrlConfig {
+    url: String,
+}
+
+/// A CSV or JSON list published over HTTP, fetched with the bearer token
+/// stored for the instance, if any. Todos are matched by their remote id.
+pub struct RemoteList;
+
+impl Connector for RemoteList {
+    type Item = Todo;
+
+    fn kind(&self) -> &'static str {
+        "remote-list"
+    }
+
+    fn credential(&self) -> CredentialRequirement {
+        CredentialRequirement::Optional
+    }
+
+    fn check_config(&self, config: &Value) -> Result<(), String> {
+        let config: UrlConfig = parse_config(config)?;
+        sync::parse_url(&config.url)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn fetch(&self, request: &FetchRequest<'_>) -> Result<Fetched<Todo>, String> {
+        let config: UrlConfig = parse_config(request.config)?;
+        let fetched = sync::fetch_list(
+            request.storage,
+            &config.url,
+            request.cursor,
+            request.credential,
+        )
+        .map_err(|e| format!("Failed to fetch {}: {}", config.url, e))?;
+        Ok(match fetched {
+            None => Fetched::NotModified,
+            Some((etag, todos)) => Fetched::Items {
+                items: todos,
+                cursor: etag,
+            },
+        })
+    }
+
+    fn to_todo(&self, item: Todo, _now: DateTime<Utc>) -> Todo {
+        // Remote ids may clash with local ones.
+        Todo {
+            id: uuid::Uuid::new_v4().to_string(),
+            ..item
+        }
+    }
+
+    fn dedup_key(&self, item: &Todo) -> String {
+        item.id.clone()
+    }
+}
+
+/// A connector kind as `list_connector_kinds` shows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectorKind {
+    pub kind: &'static str,
+    pub credential: CredentialRequirement,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectorInstance {
+    pub id: String,
+    pub kind: String,
+    pub name: String,
+    pub config: Value,
+    pub interval_minutes: u64,
+    pub cursor: Option<String>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Why the last run failed, if it did.
+    pub last_error: Option<String>,
+    /// How many of the source's items have a todo.
+    pub item_count: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ConnectorInstance {
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.last_run_at
+            .is_none_or(|last| last + Duration::minutes(self.interval_minutes as i64) <= now)
+    }
+}
+
+/// What a run changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RunOutcome {
+    pub not_modified: bool,
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl RunOutcome {
+    fn changed(&self) -> usize {
+        self.added + self.updated + self.removed
+    }
+}
+
+const INSTANCE_COLUMNS: &str = "id, kind, name, config, interval_minutes, cursor, last_run_at, \
+     last_error, created_at, \
+     (SELECT COUNT(*) FROM connector_items WHERE instance_id = connector_instances.id)";
+
+fn instance_from_row(row: &Row<'_>) -> rusqlite::Result<ConnectorInstance> {
+    let config: String = row.get(3)?;
+    Ok(ConnectorInstance {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        name: row.get(2)?,
+        config: serde_json::from_str(&config).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        interval_minutes: row.get::<_, i64>(4)? as u64,
+        cursor: row.get(5)?,
+        last_run_at: row.get(6)?,
+        last_error: row.get(7)?,
+        created_at: row.get(8)?,
+        item_count: row.get::<_, i64>(9)? as u64,
+    })
+}
+
+pub fn list_instances(conn: &Connection) -> rusqlite::Result<Vec<ConnectorInstance>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM connector_instances ORDER BY created_at",
+        INSTANCE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], instance_from_row)?;
+    rows.collect()
+}
+
+fn find_instance(conn: &Connection, id: &str) -> Result<ConnectorInstance, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM connector_instances WHERE id = ?1",
+            INSTANCE_COLUMNS
+        ),
+        [id],
+        instance_from_row,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to load connector instance: {}", e))?
+    .ok_or_else(|| format!("No connector instance '{}'", id))
+}
+
+/// Saves an instance of connector `kind`. `credential` is only checked
+/// against what the connector requires; storing it is up to the caller.
+pub fn add_instance(
+    storage: &Storage,
+    kind: &str,
+    name: &str,
+    config: Value,
+    interval_minutes: u64,
+    credential: Option<&str>,
+) -> Result<ConnectorInstance, String> {
+    let source = find(kind).ok_or_else(|| format!("Unknown connector '{}'", kind))?;
+    if name.trim().is_empty() {
+        return Err("Connector instance needs a name".to_string());
+    }
+    if interval_minutes == 0 {
+        return Err("Re-sync interval must be at least one minute".to_string());
+    }
+    if source.credential() == CredentialRequirement::Required && credential.is_none() {
+        return Err(format!("Connector '{}' needs a credential", kind));
+    }
+    source.check_config(&config)?;
+    let instance = ConnectorInstance {
+        id: uuid::Uuid::new_v4().to_string(),
+        kind: kind.to_string(),
+        name: name.trim().to_string(),
+        config,
+        interval_minutes,
+        cursor: None,
+        last_run_at: None,
+        last_error: None,
+        item_count: 0,
+        created_at: storage.clock().now(),
+    };
+    storage
+        .conn()
+        .execute(
+            "INSERT INTO connector_instances (id, kind, name, config, interval_minutes, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                instance.id,
+                instance.kind,
+                instance.name,
+                instance.config.to_string(),
+                interval_minutes as i64,
+                instance.created_at,
+            ],
+        )
+        .map_err(|e| format!("Failed to save connector instance: {}", e))?;
+    Ok(instance)
+}
+
+/// Removes an instance. The todos it created stay, no longer re-synced.
+pub fn remove_instance(storage: &Storage, id: &str) -> Result<(), String> {
+    let mut conn = storage.conn();
+    let remove = |conn: &mut Connection| -> rusqlite::Result<usize> {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM connector_items WHERE instance_id = ?1", [id])?;
+        let removed = tx.execute("DELETE FROM connector_instances WHERE id = ?1", [id])?;
+        tx.commit()?;
+        Ok(removed)
+    };
+    match remove(&mut conn) {
+        Ok(0) => Err(format!("No connector instance '{}'", id)),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to remove connector instance: {}", e)),
+    }
+}
+
+/// Fetches the instance's source and makes its todos match, remembering
+/// when it ran and how it went.
+pub fn run(
+    storage: &Storage,
+    instance: &ConnectorInstance,
+    credential: Option<&str>,
+) -> Result<RunOutcome, String> {
+    let source =
+        find(&instance.kind).ok_or_else(|| format!("Unknown connector '{}'", instance.kind))?;
+    let now = storage.clock().now();
+    let request = FetchRequest {
+        storage,
+        config: &instance.config,
+        cursor: instance.cursor.as_deref(),
+        credential,
+    };
+    let result = source
+        .pull(&request, now)
+        .and_then(|fetched| match fetched {
+            Fetched::NotModified => Ok(RunOutcome {
+                not_modified: true,
+                ..Default::default()
+            }),
+            Fetched::Items { items, cursor } => apply(
+                storage,
+                instance,
+                items,
+                source.syncs_completion(),
+                cursor,
+                now,
+            ),
+        });
+    // A successful `apply` moved the cursor along with the todos.
+    storage
+        .conn()
+        .execute(
+            "UPDATE connector_instances SET last_run_at = ?2, last_error = ?3 WHERE id = ?1",
+            params![instance.id, now, result.as_ref().err()],
+        )
+        .map_err(|e| format!("Failed to save connector instance: {}", e))?;
+    result
+}
+
+/// Makes the instance's todos match `items`, in one transaction.
+fn apply(
+    storage: &Storage,
+    instance: &ConnectorInstance,
+    items: Vec<(String, Todo)>,
+    syncs_completion: bool,
+    cursor: Option<String>,
+    now: DateTime<Utc>,
+) -> Result<RunOutcome, String> {
+    let db = |e: rusqlite::Error| format!("Failed to sync connector items: {}", e);
+    let mut conn = storage.conn();
+    let known: HashMap<String, String> = {
+        let mut stmt = conn
+            .prepare("SELECT item_key, todo_id FROM connector_items WHERE instance_id = ?1")
+            .map_err(db)?;
+        let rows = stmt
+            .query_map([&instance.id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(db)?
+    };
+
+    let mut seen = HashSet::new();
+    let mut added = Vec::new();
+    let mut updated = Vec::new();
+    for (key, mut todo) in items {
+        if !seen.insert(key.clone()) {
+            continue;
+        }
+        let Some(todo_id) = known.get(&key) else {
+            added.push((key, todo));
+            continue;
+        };
+        let Some(current) = storage::get_todo_with(&conn, todo_id).map_err(db)? else {
+            continue;
+        };
+        // Local-only state is kept from the cached copy.
+        todo.id = current.id.clone();
+        todo.order = current.order;
+        todo.rank = current.rank.clone();
+        todo.schedule_id = current.schedule_id.clone();
+        todo.fields = current.fields.clone();
+        todo.created_at = current.created_at;
+        todo.updated_at = current.updated_at;
+        todo.revision = current.revision;
+        if !syncs_completion {
+            todo.completed = current.completed;
+        }
+        if !todo.changed_fields(&current).is_empty() {
+            todo.updated_at = now;
+            updated.push(todo);
+        }
+    }
+    let gone: Vec<(&String, &String)> = known
+        .iter()
+        .filter(|(key, _)| !seen.contains(*key))
+        .collect();
+
+    let total = quota::count_todos(&conn).map_err(db)? + added.len() as u64;
+    quota::check_todos(storage, total.saturating_sub(gone.len() as u64))
+        .map_err(|e| e.to_string())?;
+
+    let tx = conn.transaction().map_err(db)?;
+    let mut removed = 0;
+    let mut write = || -> rusqlite::Result<()> {
+        for (key, todo) in &added {
+            storage::insert_todo(&tx, todo)?;
+            tx.execute(
+                "INSERT INTO connector_items (instance_id, item_key, todo_id) VALUES (?1, ?2, ?3)",
+                params![instance.id, key, todo.id],
+            )?;
+        }
+        for todo in &updated {
+            storage::insert_todo(&tx, todo)?;
+        }
+        for (key, todo_id) in &gone {
+            removed += tx.execute("DELETE FROM todos WHERE id = ?1", [todo_id])?;
+            tx.execute(
+                "DELETE FROM todo_field_values WHERE todo_id = ?1",
+                [todo_id],
+            )?;
+            tx.execute(
+                "DELETE FROM connector_items WHERE instance_id = ?1 AND item_key = ?2",
+                params![instance.id, key],
+            )?;
+        }
+        tx.execute(
+            "UPDATE connector_instances SET cursor = ?2 WHERE id = ?1",
+            params![instance.id, cursor],
+        )?;
+        Ok(())
+    };
+    write().map_err(db)?;
+    tx.commit().map_err(db)?;
+    Ok(RunOutcome {
+        not_modified: false,
+        added: added.len(),
+        updated: updated.len(),
+        removed,
+    })
+}
+
+fn credential_entry(id: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, &format!("connector:{}", id))
+        .map_err(|e| format!("Failed to open keychain: {}", e))
+}
+
+fn stored_credential(id: &str) -> Result<Option<String>, String> {
+    match credential_entry(id)?.get_password() {
+        Ok(credential) => Ok(Some(credential)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read credential from keychain: {}", e)),
+    }
+}
+
+fn delete_credential(id: &str) -> Result<(), String> {
+    match credential_entry(id)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to remove credential: {}", e)),
+    }
+}
+
+/// Removes the credentials of every connector instance.
+pub fn delete_credentials(storage: &Storage) -> Result<(), String> {
+    let instances = list_instances(&storage.conn()).map_err(|e| e.to_string())?;
+    for instance in instances {
+        delete_credential(&instance.id)?;
+    }
+    Ok(())
+}
+
+/// Runs instance `id` with its stored credential, and tells the windows and
+/// the activity feed about the todos it changed.
+pub fn run_now(app: &AppHandle, id: &str) -> Result<RunOutcome, String> {
+    let storage = app.state::<Storage>();
+    let instance = find_instance(&storage.conn(), id)?;
+    let credential = match find(&instance.kind).map(|source| source.credential()) {
+        Some(CredentialRequirement::None) | None => None,
+        Some(_) => stored_credential(&instance.id)?,
+    };
+    let outcome = run(&storage, &instance, credential.as_deref())?;
+    if outcome.changed() > 0 {
+        if let Ok(todos) = storage.list_todos() {
+            app.state::<SearchIndex>().rebuild(&todos);
+        }
+        let _ = events::emit(app, "todo-cache-updated", ());
+        quota::notify(app);
+        activity::connector_synced(
+            &storage,
+            &instance.id,
+            &instance.name,
+            outcome.changed() as u64,
+        );
+    }
+    Ok(outcome)
+}
+
+/// Re-syncs the instances whose interval has passed, recording failures for
+/// `retry_last_failed`. Called by the scheduler loop.
+pub fn run_due(app: &AppHandle) {
+    let storage = app.state::<Storage>();
+    if storage.newer_schema().is_some() {
+        return;
+    }
+    let now = storage.clock().now();
+    let due: Vec<ConnectorInstance> = match list_instances(&storage.conn()) {
+        Ok(instances) => instances
+            .into_iter()
+            .filter(|instance| instance.is_due(now))
+            .collect(),
+        Err(e) => {
+            trace::log(format!("Failed to load connector instances: {}", e));
+            return;
+        }
+    };
+    for instance in due {
+        if let Err(e) = run_now(app, &instance.id) {
+            trace::log(format!("Connector {} failed: {}", instance.name, e));
+            retry::record(app, retry::Operation::RunConnector { id: instance.id }, e);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_connector_kinds() -> Vec<ConnectorKind> {
+    CONNECTORS
+        .iter()
+        .map(|source| ConnectorKind {
+            kind: source.kind(),
+            credential: source.credential(),
+        })
+        .collect()
+}
+
+/// Saves an instance, with `credential` in the keychain. It first runs when
+/// the scheduler wakes up next.
+#[tauri::command]
+pub fn add_connector_instance(
+    kind: String,
+    name: String,
+    mut config: Value,
+    interval_minutes: u64,
+    credential: Option<String>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<ConnectorInstance, PathError> {
+    // Connectors reading a file name it `path`.
+    if let Some(path) = config.get("path").and_then(Value::as_str) {
+        let path = file_access::check(&app, path)?;
+        config["path"] = Value::from(path.to_string_lossy().into_owned());
+    }
+    let instance = add_instance(
+        &storage,
+        &kind,
+        &name,
+        config,
+        interval_minutes,
+        credential.as_deref(),
+    )?;
+    if let Some(credential) = credential {
+        let stored = credential_entry(&instance.id).and_then(|entry| {
+            entry
+                .set_password(&credential)
+                .map_err(|e| format!("Failed to store credential in keychain: {}", e))
+        });
+        if let Err(e) = stored {
+            let _ = remove_instance(&storage, &instance.id);
+            return Err(e.into());
+        }
+    }
+    Ok(instance)
+}
+
+#[tauri::command]
+pub fn list_connector_instances(
+    storage: State<'_, Storage>,
+) -> Result<Vec<ConnectorInstance>, String> {
+    list_instances(&storage.conn())
+        .map_err(|e| format!("Failed to list connector instances: {}", e))
+}
+
+#[tauri::command]
+pub async fn run_connector_now(
+    id: String,
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<RunOutcome, String> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        run_now(&app, &id).inspect_err(|e| {
+            retry::record(&app, retry::Operation::RunConnector { id: id.clone() }, e)
+        })
+    }))
+    .await
+    .map_err(|e| format!("Failed to run connector: {}", e))?
+}
+
+/// Removes an instance and its credential; the todos it created stay.
+#[tauri::command]
+pub fn remove_connector_instance(id: String, storage: State<'_, Storage>) -> Result<(), String> {
+    remove_instance(&storage, &id)?;
+    delete_credential(&id)
+}
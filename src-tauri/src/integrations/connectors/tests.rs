@@ -0,0 +1,238 @@
+use super::*;
+
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+use tiny_http::{Header, Response, Server};
+
+fn bookmark_file(links: &[(&str, &str)]) -> String {
+    let mut html = String::from("<!DOCTYPE NETSCAPE-Bookmark-file-1>\n<DL><p>\n");
+    for (url, title) in links {
+        html.push_str(&format!("<DT><A HREF=\"{}\">{}</A>\n", url, title));
+    }
+    html.push_str("</DL><p>\n");
+    html
+}
+
+fn titles(storage: &Storage) -> Vec<String> {
+    let mut titles: Vec<_> = storage
+        .list_todos()
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect();
+    titles.sort();
+    titles
+}
+
+fn reload(storage: &Storage, id: &str) -> ConnectorInstance {
+    find_instance(&storage.conn(), id).unwrap()
+}
+
+#[test]
+fn test_bookmark_file_resyncs_in_place() {
+    let storage = Storage::open_in_memory().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.html");
+    fs::write(
+        &path,
+        bookmark_file(&[
+            ("https://example.com/a", "Article"),
+            ("https://example.com/b", "Talk"),
+            ("https://example.com/a", "Article again"),
+        ]),
+    )
+    .unwrap();
+    let instance = add_instance(
+        &storage,
+        "bookmark-file",
+        "Reading list",
+        json!({ "path": path }),
+        30,
+        None,
+    )
+    .unwrap();
+
+    let outcome = run(&storage, &instance, None).unwrap();
+    assert_eq!((outcome.added, outcome.updated, outcome.removed), (2, 0, 0));
+    assert_eq!(titles(&storage), vec!["Article", "Talk"]);
+    let instance = reload(&storage, &instance.id);
+    assert_eq!(instance.item_count, 2);
+    assert!(run(&storage, &instance, None).unwrap().not_modified);
+
+    // Completing a bookmark sticks, as the file doesn't say what was read.
+    let mut talk = storage
+        .list_todos()
+        .unwrap()
+        .into_iter()
+        .find(|todo| todo.title == "Talk")
+        .unwrap();
+    talk.completed = true;
+    storage.save_todo(&talk).unwrap();
+
+    fs::write(
+        &path,
+        bookmark_file(&[
+            ("https://example.com/b", "Conference talk"),
+            ("https://example.com/c", "Paper"),
+        ]),
+    )
+    .unwrap();
+    let instance = reload(&storage, &instance.id);
+    let outcome = run(&storage, &instance, None).unwrap();
+    assert_eq!((outcome.added, outcome.updated, outcome.removed), (1, 1, 1));
+    assert_eq!(titles(&storage), vec!["Conference talk", "Paper"]);
+    let renamed = storage.get_todo(&talk.id).unwrap().unwrap();
+    assert_eq!(renamed.title, "Conference talk");
+    assert!(renamed.completed);
+
+    // Deleted locally, it isn't brought back.
+    storage
+        .conn()
+        .execute("DELETE FROM todos WHERE id = ?1", [&talk.id])
+        .unwrap();
+    fs::write(
+        &path,
+        bookmark_file(&[
+            ("https://example.com/b", "Conference talk"),
+            ("https://example.com/c", "Paper (PDF)"),
+        ]),
+    )
+    .unwrap();
+    let instance = reload(&storage, &instance.id);
+    let outcome = run(&storage, &instance, None).unwrap();
+    assert_eq!((outcome.added, outcome.updated, outcome.removed), (0, 1, 0));
+    assert_eq!(titles(&storage), vec!["Paper (PDF)"]);
+}
+
+#[test]
+fn test_failed_run_is_recorded_and_keeps_todos() {
+    let storage = Storage::open_in_memory().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.html");
+    fs::write(
+        &path,
+        bookmark_file(&[("https://example.com/a", "Article")]),
+    )
+    .unwrap();
+    let instance = add_instance(
+        &storage,
+        "bookmark-file",
+        "Reading list",
+        json!({ "path": path }),
+        30,
+        None,
+    )
+    .unwrap();
+    run(&storage, &instance, None).unwrap();
+
+    fs::remove_file(&path).unwrap();
+    let instance = reload(&storage, &instance.id);
+    let error = run(&storage, &instance, None).unwrap_err();
+    assert!(error.starts_with("Failed to read"), "{}", error);
+    let instance = reload(&storage, &instance.id);
+    assert_eq!(instance.last_error, Some(error));
+    assert_eq!(titles(&storage), vec!["Article"]);
+    let now = storage.clock().now();
+    assert!(!instance.is_due(now));
+    assert!(instance.is_due(now + Duration::minutes(30)));
+}
+
+#[test]
+fn test_remote_list_sends_token_and_etag() {
+    let requests: Arc<Mutex<Vec<(Option<String>, Option<String>)>>> = Arc::default();
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/team/todos.json", server.server_addr());
+    let seen = requests.clone();
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let header = |name: &str| {
+                request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv(name))
+                    .map(|h| h.value.as_str().to_string())
+            };
+            let if_none_match = header("If-None-Match");
+            seen.lock()
+                .unwrap()
+                .push((header("Authorization"), if_none_match.clone()));
+            let response = match if_none_match {
+                Some(_) => Response::from_string("").with_status_code(304),
+                None => Response::from_string(r#"[{"id": "1", "title": "Book venue"}]"#)
+                    .with_header(Header::from_bytes("ETag", "\"v1\"").unwrap()),
+            };
+            let _ = request.respond(response);
+        }
+    });
+    let storage = Storage::open_in_memory().unwrap();
+    let instance = add_instance(
+        &storage,
+        "remote-list",
+        "Team",
+        json!({ "url": url }),
+        15,
+        Some("secret"),
+    )
+    .unwrap();
+
+    let outcome = run(&storage, &instance, Some("secret")).unwrap();
+    assert_eq!(outcome.added, 1);
+    let todo = storage.list_todos().unwrap().remove(0);
+    assert_eq!(todo.title, "Book venue");
+    assert_ne!(todo.id, "1");
+    let instance = reload(&storage, &instance.id);
+    assert_eq!(instance.cursor.as_deref(), Some("\"v1\""));
+    assert!(
+        run(&storage, &instance, Some("secret"))
+            .unwrap()
+            .not_modified
+    );
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![
+            (Some("Bearer secret".to_string()), None),
+            (
+                Some("Bearer secret".to_string()),
+                Some("\"v1\"".to_string())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_instances_are_checked_when_added() {
+    let storage = Storage::open_in_memory().unwrap();
+    let add = |kind: &str, config: Value, interval: u64| {
+        add_instance(&storage, kind, "Source", config, interval, None).unwrap_err()
+    };
+    assert_eq!(add("todoist", json!({}), 15), "Unknown connector 'todoist'");
+    assert!(
+        add("remote-list", json!({ "url": "ftp://example.com" }), 15)
+            .contains("is not an http(s) URL")
+    );
+    assert!(add("bookmark-file", json!({}), 15).starts_with("Invalid connector config"));
+    assert_eq!(
+        add("bookmark-file", json!({ "path": "/tmp/b.html" }), 0),
+        "Re-sync interval must be at least one minute"
+    );
+    assert!(list_instances(&storage.conn()).unwrap().is_empty());
+
+    let instance = add_instance(
+        &storage,
+        "bookmark-file",
+        "Reading list",
+        json!({ "path": "/tmp/b.html" }),
+        15,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        list_instances(&storage.conn()).unwrap(),
+        vec![instance.clone()]
+    );
+    remove_instance(&storage, &instance.id).unwrap();
+    assert!(remove_instance(&storage, &instance.id)
+        .unwrap_err()
+        .starts_with("No connector instance"));
+}
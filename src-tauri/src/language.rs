@@ -0,0 +1,199 @@
+//! Language detection for todo text. The cache stores the language of each
+//! todo's title and description so search can pick a matching analyzer and
+//! queries can filter by language (`lang:jpn`).
+//!
+//! Short text is often ambiguous; anything below [`MIN_CONFIDENCE`] is stored
+//! as unknown rather than guessed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use whatlang::Lang;
+
+use crate::events;
+use crate::protocol::EventPayload;
+use crate::storage::Storage;
+use crate::trace;
+use crate::types::TextLanguages;
+
+#[cfg(test)]
+mod tests;
+
+/// Detections less confident than this count as unknown.
+pub const MIN_CONFIDENCE: f64 = 0.5;
+/// Todos detected per transaction when backfilling.
+const BACKFILL_BATCH: usize = 500;
+const UPDATE_LANGUAGES: &str = "UPDATE todos
+     SET title_language = ?2, description_language = ?3, languages_pending = 0
+     WHERE id = ?1";
+
+/// Set while a backfill runs, so the scheduler doesn't start a second one.
+static BACKFILLING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Detection {
+    /// ISO 639-3 code, or `None` below [`MIN_CONFIDENCE`].
+    pub language: Option<String>,
+    pub confidence: f64,
+}
+
+pub fn detect(text: &str) -> Detection {
+    match whatlang::detect(text) {
+        Some(info) => Detection {
+            language: (info.confidence() >= MIN_CONFIDENCE).then(|| info.lang().code().to_string()),
+            confidence: info.confidence(),
+        },
+        None => Detection {
+            language: None,
+            confidence: 0.0,
+        },
+    }
+}
+
+/// Detects the title and the description separately; they often differ,
+/// e.g. a Japanese title with notes copied from an English page.
+pub fn detect_todo(title: &str, description: Option<&str>) -> TextLanguages {
+    TextLanguages {
+        title: detect(title).language,
+        description: description.and_then(|d| detect(d).language),
+    }
+}
+
+/// The ISO 639-3 code for a language given as a code (`jpn`) or an English
+/// name (`Japanese`).
+pub fn parse_language(name: &str) -> Option<&'static str> {
+    let name = name.to_ascii_lowercase();
+    Lang::from_code(name.as_str())
+        .or_else(|| {
+            Lang::all()
+                .iter()
+                .copied()
+                .find(|lang| lang.eng_name().eq_ignore_ascii_case(&name))
+        })
+        .map(|lang| lang.code())
+}
+
+/// Detects the language of `text`, e.g. to pick a spellcheck dictionary.
+#[tauri::command]
+pub fn detect_language(text: String) -> Detection {
+    detect(&text)
+}
+
+/// Payload of the `language-backfill-progress` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl EventPayload for BackfillProgress {}
+
+/// Detects the languages of one cached todo right away, for edits.
+pub fn detect_now(conn: &Connection, id: &str) -> rusqlite::Result<()> {
+    let text = conn
+        .query_row(
+            "SELECT title, description FROM todos WHERE id = ?1",
+            [id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional()?;
+    if let Some((title, description)) = text {
+        let languages = detect_todo(&title, description.as_deref());
+        conn.execute(
+            UPDATE_LANGUAGES,
+            params![id, languages.title, languages.description],
+        )?;
+    }
+    Ok(())
+}
+
+/// Todos whose languages the backfill still has to detect.
+pub fn pending_count(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM todos WHERE languages_pending = 1",
+        [],
+        |row| row.get::<_, i64>(0).map(|count| count as usize),
+    )
+}
+
+/// Detects the languages of up to `limit` pending todos in one transaction
+/// and returns how many were done.
+pub fn backfill_batch(conn: &mut Connection, limit: usize) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+    let pending = {
+        let mut stmt = tx.prepare(
+            "SELECT id, title, description FROM todos WHERE languages_pending = 1 LIMIT ?1",
+        )?;
+        let rows = stmt.query_map([limit as i64], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    {
+        let mut stmt = tx.prepare(UPDATE_LANGUAGES)?;
+        for (id, title, description) in &pending {
+            let languages = detect_todo(title, description.as_deref());
+            stmt.execute(params![id, languages.title, languages.description])?;
+        }
+    }
+    tx.commit()?;
+    Ok(pending.len())
+}
+
+/// Detects pending languages in batches on a background thread, emitting
+/// `language-backfill-progress` after each. Pending are todos cached before
+/// detection existed and those written in bulk, by sync or imports, which
+/// would be slowed down by detecting each todo. Runs at startup and on every
+/// scheduler wake-up.
+pub fn spawn_backfill(app: &AppHandle) {
+    if app.state::<Storage>().newer_schema().is_some() {
+        return;
+    }
+    let total = match pending_count(&app.state::<Storage>().conn()) {
+        Ok(0) => return,
+        Ok(total) => total,
+        Err(e) => {
+            trace::log(format!("Failed to count todos without languages: {}", e));
+            return;
+        }
+    };
+    if BACKFILLING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let storage = app.state::<Storage>();
+        let mut done = 0;
+        loop {
+            // The lock is released between batches.
+            match backfill_batch(&mut storage.batch_conn(), BACKFILL_BATCH) {
+                Ok(0) => break,
+                Ok(count) => {
+                    done = (done + count).min(total);
+                    let _ = events::emit(
+                        &app,
+                        "language-backfill-progress",
+                        BackfillProgress { done, total },
+                    );
+                }
+                Err(e) => {
+                    trace::log(format!("Failed to detect todo languages: {}", e));
+                    break;
+                }
+            }
+        }
+        BACKFILLING.store(false, Ordering::Release);
+        if done > 0 {
+            let _ = events::emit(&app, "todo-cache-updated", ());
+        }
+    });
+}
@@ -0,0 +1,121 @@
+use super::*;
+
+use crate::storage::{self, StorageBackend};
+use crate::types::Todo;
+
+fn todo(id: &str, title: &str, description: Option<&str>) -> Todo {
+    let now = chrono::Utc::now();
+    Todo {
+        description: description.map(str::to_string),
+        ..Todo::sample(id, title, now)
+    }
+}
+
+#[test]
+fn test_confident_detections_name_the_language() {
+    assert_eq!(
+        detect("会議の資料を準備する").language.as_deref(),
+        Some("jpn")
+    );
+    assert_eq!(
+        detect("Remember the organic one from the corner shop")
+            .language
+            .as_deref(),
+        Some("eng")
+    );
+}
+
+#[test]
+fn test_low_confidence_detections_are_unknown() {
+    for text in ["Draft", "ok", "Pay rent", "", "12:30"] {
+        let detection = detect(text);
+        assert_eq!(detection.language, None, "{:?}", text);
+        assert!(detection.confidence < MIN_CONFIDENCE);
+    }
+}
+
+#[test]
+fn test_title_and_description_are_detected_separately() {
+    let languages = detect_todo(
+        "牛乳を買う",
+        Some("Remember the organic one from the corner shop"),
+    );
+    assert_eq!(languages.title.as_deref(), Some("jpn"));
+    assert_eq!(languages.description.as_deref(), Some("eng"));
+    assert!(languages.contains("jpn") && languages.contains("eng"));
+
+    let languages = detect_todo("Write the quarterly report for the team", Some("メモ"));
+    assert_eq!(languages.title.as_deref(), Some("eng"));
+    assert_eq!(languages.description.as_deref(), Some("jpn"));
+}
+
+#[test]
+fn test_languages_by_code_or_name() {
+    assert_eq!(parse_language("jpn"), Some("jpn"));
+    assert_eq!(parse_language("Japanese"), Some("jpn"));
+    assert_eq!(parse_language("ENG"), Some("eng"));
+    assert_eq!(parse_language("klingon"), None);
+}
+
+#[test]
+fn test_writes_leave_detection_pending_and_edits_detect_at_once() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage
+        .save_todo(&todo(
+            "a",
+            "会議の資料を準備する",
+            Some("Remember the organic one from the corner shop"),
+        ))
+        .unwrap();
+    assert_eq!(pending_count(&storage.conn()).unwrap(), 1);
+    backfill_batch(&mut storage.conn(), BACKFILL_BATCH).unwrap();
+    let cached = storage.get_todo("a").unwrap().unwrap();
+    assert_eq!(cached.languages.title.as_deref(), Some("jpn"));
+    assert_eq!(cached.languages.description.as_deref(), Some("eng"));
+
+    // Rewriting the same text keeps the languages.
+    storage.save_todo(&cached).unwrap();
+    assert_eq!(pending_count(&storage.conn()).unwrap(), 0);
+
+    let mut edit = storage.get_todo("a").unwrap().unwrap();
+    edit.title = "Write the quarterly report for the team".to_string();
+    edit.languages = Default::default();
+    let update = storage::apply_update(&storage, edit, None)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(update.changed_fields, vec!["title"]);
+    assert_eq!(update.todo.languages.title.as_deref(), Some("eng"));
+    assert_eq!(update.todo.languages.description.as_deref(), Some("eng"));
+    assert_eq!(pending_count(&storage.conn()).unwrap(), 0);
+}
+
+#[test]
+fn test_backfill_detects_in_batches() {
+    let dir = tempfile::tempdir().unwrap();
+    let backend = StorageBackend::Disk(dir.path().to_path_buf());
+    {
+        let storage = Storage::open(backend.clone()).unwrap();
+        let todos: Vec<Todo> = (0..1200)
+            .map(|i| todo(&i.to_string(), "会議の資料を準備する", None))
+            .collect();
+        storage.replace_todos(&todos).unwrap();
+    }
+    let storage = Storage::open(backend).unwrap();
+    let mut conn = storage.conn();
+    assert_eq!(pending_count(&conn).unwrap(), 1200);
+
+    let batches: Vec<usize> = std::iter::from_fn(|| {
+        Some(backfill_batch(&mut conn, BACKFILL_BATCH).unwrap()).filter(|&n| n > 0)
+    })
+    .collect();
+    drop(conn);
+
+    assert_eq!(batches, vec![500, 500, 200]);
+    assert_eq!(pending_count(&storage.conn()).unwrap(), 0);
+    assert!(storage
+        .list_todos()
+        .unwrap()
+        .iter()
+        .all(|t| t.languages.title.as_deref() == Some("jpn")));
+}
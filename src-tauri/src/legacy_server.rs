@@ -0,0 +1,652 @@
+//! One-shot import of the Node server's own database. The server kept its
+//! todos and its schedules in a `todos.db` of its own; this opens that file
+//! read-only, maps both tables onto the store's schema and reports whatever
+//! didn't translate. Ids and timestamps are kept, so importing the same
+//! file again only adds what the store doesn't have yet.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::events;
+use crate::file_access::{self, PathError};
+use crate::progress::ProgressRegistry;
+use crate::quota::{self, QuotaExceeded};
+use crate::search::SearchIndex;
+use crate::storage::{self, Storage};
+use crate::types::{Priority, Schedule, Todo};
+
+#[cfg(test)]
+mod tests;
+
+/// Where the server kept its data under the OS data directory.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const SERVER_DATA_DIR: &[&str] = &["YuToDo Server", "Data"];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const SERVER_DATA_DIR: &[&str] = &["yutodo-server"];
+
+/// Days of the week as the server numbered them, from Sunday.
+const WEEKDAYS: [&str; 7] = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"];
+
+/// Directories the server and older versions of the app kept `todos.db`
+/// in, given the OS data directory.
+pub fn candidate_dirs(data_dir: &Path) -> Vec<PathBuf> {
+    vec![
+        SERVER_DATA_DIR
+            .iter()
+            .fold(data_dir.to_path_buf(), |dir, part| dir.join(part)),
+        data_dir.join(storage::LEGACY_DIR),
+    ]
+}
+
+/// Something in the source that was left out or changed on the way in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Untranslatable {
+    /// `todos` or `schedules`.
+    pub table: String,
+    pub id: Option<String>,
+    pub reason: String,
+    /// The whole row was left out, rather than one of its details.
+    pub skipped: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TableReport {
+    /// Rows that translated.
+    pub read: usize,
+    /// Rows written, or in a dry run the rows a commit would write.
+    pub imported: usize,
+    /// Rows whose id the store already has; those are kept as they are.
+    pub existing: usize,
+}
+
+/// Result of `import_legacy_server_db`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyServerReport {
+    pub source: PathBuf,
+    pub dry_run: bool,
+    /// Copy of the store taken before a commit wrote anything.
+    pub backup: Option<PathBuf>,
+    pub todos: TableReport,
+    pub schedules: TableReport,
+    pub untranslatable: Vec<Untranslatable>,
+    /// Set when committing would pass a hard limit.
+    pub quota_exceeded: Option<QuotaExceeded>,
+}
+
+/// The translated contents of a server database.
+#[derive(Debug, Default)]
+pub struct LegacyServerData {
+    pub todos: Vec<Todo>,
+    pub schedules: Vec<Schedule>,
+    pub untranslatable: Vec<Untranslatable>,
+}
+
+fn note(table: &str, id: Option<&str>, reason: impl Into<String>, skipped: bool) -> Untranslatable {
+    Untranslatable {
+        table: table.to_string(),
+        id: id.map(str::to_string),
+        reason: reason.into(),
+        skipped,
+    }
+}
+
+fn describe(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => "null".to_string(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(n) => n.to_string(),
+        ValueRef::Text(text) => format!("'{}'", String::from_utf8_lossy(text)),
+        ValueRef::Blob(_) => "a blob".to_string(),
+    }
+}
+
+fn known_priority(value: ValueRef<'_>) -> bool {
+    match value {
+        ValueRef::Null => true,
+        ValueRef::Integer(n) => (0..=2).contains(&n),
+        ValueRef::Text(text) => std::str::from_utf8(text)
+            .ok()
+            .and_then(Priority::parse)
+            .is_some(),
+        _ => false,
+    }
+}
+
+/// Reads and translates the server database at `path`, which is only ever
+/// opened read-only.
+pub fn read_server_database(path: &Path, now: DateTime<Utc>) -> rusqlite::Result<LegacyServerData> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut data = LegacyServerData {
+        todos: storage::read_legacy_todos(path, now)?,
+        ..Default::default()
+    };
+    check_todos(&conn, &mut data.untranslatable)?;
+    read_schedules(&conn, now, &mut data)?;
+    Ok(data)
+}
+
+/// Notes what `storage::read_legacy_todos` drops or falls back on.
+fn check_todos(conn: &Connection, notes: &mut Vec<Untranslatable>) -> rusqlite::Result<()> {
+    let columns = storage::legacy_columns(conn)?;
+    let optional = |column: &str| {
+        if columns.contains(column) {
+            column.to_string()
+        } else {
+            format!("NULL AS {}", column)
+        }
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, title, {}, createdAt, {} FROM todos",
+        optional("priority"),
+        optional("scheduledFor"),
+    ))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: Option<String> = row.get(0)?;
+        let title: Option<String> = row.get(1)?;
+        let id = id.as_deref();
+        if id.is_none() || title.is_none() {
+            let missing = if id.is_none() { "id" } else { "title" };
+            notes.push(note("todos", id, format!("no {}", missing), true));
+            continue;
+        }
+        if !known_priority(row.get_ref(2)?) {
+            let reason = format!("priority {} became medium", describe(row.get_ref(2)?));
+            notes.push(note("todos", id, reason, false));
+        }
+        if storage::legacy_timestamp(row.get_ref(3)?).is_none() {
+            let reason = format!(
+                "createdAt {} became the time of the import",
+                describe(row.get_ref(3)?)
+            );
+            notes.push(note("todos", id, reason, false));
+        }
+        let scheduled_for = row.get_ref(4)?;
+        if scheduled_for != ValueRef::Null && storage::legacy_timestamp(scheduled_for).is_none() {
+            let reason = format!("scheduledFor {} was dropped", describe(scheduled_for));
+            notes.push(note("todos", id, reason, false));
+        }
+    }
+    Ok(())
+}
+
+/// A row of the server's `schedules` table. The `*Config` columns and
+/// `excludeDates` hold JSON.
+#[derive(Debug, Clone, Default)]
+pub struct LegacySchedule {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub kind: String,
+    pub start_date: String,
+    pub end_date: Option<String>,
+    pub time: Option<String>,
+    pub weekly_config: Option<String>,
+    pub monthly_config: Option<String>,
+    pub custom_config: Option<String>,
+    pub exclude_weekends: bool,
+    pub exclude_dates: Option<String>,
+    pub active: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WeeklyConfig {
+    #[serde(default)]
+    days_of_week: Vec<i64>,
+    time: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MonthlyConfig {
+    #[serde(rename = "type")]
+    kind: String,
+    date: Option<i64>,
+    week_number: Option<i64>,
+    day_of_week: Option<i64>,
+    days_from_end: Option<i64>,
+    time: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CustomConfig {
+    interval: i64,
+    unit: String,
+    time: Option<String>,
+    end_date: Option<String>,
+    max_occurrences: Option<i64>,
+}
+
+fn config<T: for<'de> Deserialize<'de>>(json: &Option<String>, name: &str) -> Result<T, String> {
+    let json = json.as_deref().ok_or_else(|| format!("no {}", name))?;
+    serde_json::from_str(json).map_err(|e| format!("unreadable {}: {}", name, e))
+}
+
+/// Dates were written as `2024-06-03`, sometimes as a full ISO timestamp.
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text.get(..10)?, "%Y-%m-%d").ok()
+}
+
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M:%S"))
+        .ok()
+}
+
+fn weekday(day: i64) -> Result<&'static str, String> {
+    usize::try_from(day)
+        .ok()
+        .and_then(|day| WEEKDAYS.get(day).copied())
+        .ok_or_else(|| format!("day of week {} is out of range", day))
+}
+
+/// The RRULE for a server schedule, and the time of day it names, if any.
+/// Details that have no equivalent are pushed to `lost`; an `Err` means
+/// the schedule can't be expressed at all.
+pub fn to_rrule(
+    schedule: &LegacySchedule,
+    lost: &mut Vec<String>,
+) -> Result<(String, Option<String>), String> {
+    let weekends_lost = |lost: &mut Vec<String>| {
+        if schedule.exclude_weekends {
+            lost.push(format!(
+                "excludeWeekends has no equivalent for a {} schedule",
+                schedule.kind
+            ));
+        }
+    };
+    let mut end_date = schedule.end_date.clone();
+    let (mut rule, time) = match schedule.kind.as_str() {
+        "once" => ("FREQ=DAILY;COUNT=1".to_string(), None),
+        "daily" if schedule.exclude_weekends => {
+            ("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR".to_string(), None)
+        }
+        "daily" => ("FREQ=DAILY".to_string(), None),
+        "weekly" => {
+            let config: WeeklyConfig = config(&schedule.weekly_config, "weeklyConfig")?;
+            let mut days = config.days_of_week;
+            days.sort_unstable();
+            days.dedup();
+            if schedule.exclude_weekends {
+                days.retain(|day| !matches!(day, 0 | 6));
+            }
+            if days.is_empty() {
+                return Err("no days of the week".to_string());
+            }
+            let days = days
+                .into_iter()
+                .map(weekday)
+                .collect::<Result<Vec<_>, _>>()?;
+            (format!("FREQ=WEEKLY;BYDAY={}", days.join(",")), config.time)
+        }
+        "monthly" => {
+            let config: MonthlyConfig = config(&schedule.monthly_config, "monthlyConfig")?;
+            let by = match config.kind.as_str() {
+                "date" => match config.date {
+                    Some(date @ 1..=31) => format!("BYMONTHDAY={}", date),
+                    date => return Err(format!("day of month {:?} is out of range", date)),
+                },
+                "weekday" => {
+                    let week = match config.week_number {
+                        Some(week @ (1..=5 | -1)) => week,
+                        week => return Err(format!("week {:?} is out of range", week)),
+                    };
+                    let day = weekday(config.day_of_week.unwrap_or(-1))?;
+                    if schedule.exclude_weekends && matches!(day, "SA" | "SU") {
+                        return Err("excludeWeekends rules out its only day".to_string());
+                    }
+                    format!("BYDAY={}{}", week, day)
+                }
+                "lastDay" => match config.days_from_end.unwrap_or(0) {
+                    days @ 0..=30 => format!("BYMONTHDAY=-{}", days + 1),
+                    days => return Err(format!("{} days from the end is out of range", days)),
+                },
+                kind => return Err(format!("unknown monthly type '{}'", kind)),
+            };
+            if config.kind != "weekday" {
+                weekends_lost(lost);
+            }
+            (format!("FREQ=MONTHLY;{}", by), config.time)
+        }
+        "custom" => {
+            let config: CustomConfig = config(&schedule.custom_config, "customConfig")?;
+            let freq = match config.unit.as_str() {
+                "days" => "DAILY",
+                "weeks" => "WEEKLY",
+                "months" => "MONTHLY",
+                unit => return Err(format!("unknown unit '{}'", unit)),
+            };
+            if config.interval < 1 {
+                return Err(format!("interval {} is out of range", config.interval));
+            }
+            weekends_lost(lost);
+            let mut rule = format!("FREQ={};INTERVAL={}", freq, config.interval);
+            match config.max_occurrences {
+                Some(count) if count > 0 => rule.push_str(&format!(";COUNT={}", count)),
+                Some(count) => lost.push(format!("maxOccurrences {} was ignored", count)),
+                None => {}
+            }
+            end_date = end_date.or(config.end_date);
+            (rule, config.time)
+        }
+        kind => return Err(format!("unknown schedule type '{}'", kind)),
+    };
+    // RFC 5545 doesn't allow both; the server stopped at whichever came first.
+    if let Some(end_date) = end_date.filter(|_| !rule.contains("COUNT=")) {
+        match parse_date(&end_date) {
+            Some(date) => rule.push_str(&format!(";UNTIL={}T235959", date.format("%Y%m%d"))),
+            None => lost.push(format!("endDate '{}' was ignored", end_date)),
+        }
+    }
+    Ok((rule, time))
+}
+
+/// Translates one server schedule; `Err` holds why it was left out.
+pub fn translate_schedule(
+    schedule: &LegacySchedule,
+    priority: ValueRef<'_>,
+    created_at: ValueRef<'_>,
+    updated_at: ValueRef<'_>,
+    now: DateTime<Utc>,
+    lost: &mut Vec<String>,
+) -> Result<Schedule, String> {
+    let start = parse_date(&schedule.start_date)
+        .ok_or_else(|| format!("startDate '{}' is not a date", schedule.start_date))?;
+    let (rrule, config_time) = to_rrule(schedule, lost)?;
+    let time = match schedule.time.clone().or(config_time) {
+        Some(time) => parse_time(&time).ok_or_else(|| format!("time '{}' is not a time", time))?,
+        None => NaiveTime::MIN,
+    };
+    let excluded_dates = match &schedule.exclude_dates {
+        None => Vec::new(),
+        Some(json) => match serde_json::from_str::<Vec<String>>(json) {
+            Ok(dates) => dates
+                .into_iter()
+                .filter_map(|date| {
+                    let parsed = parse_date(&date);
+                    if parsed.is_none() {
+                        lost.push(format!("excluded date '{}' was ignored", date));
+                    }
+                    parsed
+                })
+                .collect(),
+            Err(e) => {
+                lost.push(format!("unreadable excludeDates: {}", e));
+                Vec::new()
+            }
+        },
+    };
+    if !known_priority(priority) {
+        lost.push(format!("priority {} became medium", describe(priority)));
+    }
+    let created = storage::legacy_timestamp(created_at);
+    if created.is_none() {
+        lost.push(format!(
+            "createdAt {} became the time of the import",
+            describe(created_at)
+        ));
+    }
+    let created_at = created.unwrap_or(now);
+    Ok(Schedule {
+        id: schedule.id.clone(),
+        title: schedule.title.clone(),
+        description: schedule.description.clone(),
+        priority: storage::legacy_priority(priority),
+        starts_at: NaiveDateTime::new(start, time),
+        rrule,
+        excluded_dates,
+        active: schedule.active,
+        created_at,
+        updated_at: storage::legacy_timestamp(updated_at).unwrap_or(created_at),
+    })
+}
+
+fn read_schedules(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    data: &mut LegacyServerData,
+) -> rusqlite::Result<()> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schedules'",
+            [],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !exists {
+        return Ok(());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT id, title, description, type, startDate, endDate, time, weeklyConfig,
+             monthlyConfig, customConfig, excludeWeekends, excludeDates, isActive,
+             priority, createdAt, updatedAt
+         FROM schedules ORDER BY createdAt, rowid",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let (Some(id), Some(title)) = (
+            row.get::<_, Option<String>>(0)?,
+            row.get::<_, Option<String>>(1)?,
+        ) else {
+            let id: Option<String> = row.get(0)?;
+            let reason = if id.is_none() { "no id" } else { "no title" };
+            data.untranslatable
+                .push(note("schedules", id.as_deref(), reason, true));
+            continue;
+        };
+        let schedule = LegacySchedule {
+            id,
+            title,
+            description: row.get(2)?,
+            kind: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            start_date: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            end_date: row.get(5)?,
+            time: row.get(6)?,
+            weekly_config: row.get(7)?,
+            monthly_config: row.get(8)?,
+            custom_config: row.get(9)?,
+            exclude_weekends: row.get::<_, Option<bool>>(10)?.unwrap_or(false),
+            exclude_dates: row.get(11)?,
+            active: row.get::<_, Option<bool>>(12)?.unwrap_or(true),
+        };
+        let mut lost = Vec::new();
+        let translated = translate_schedule(
+            &schedule,
+            row.get_ref(13)?,
+            row.get_ref(14)?,
+            row.get_ref(15)?,
+            now,
+            &mut lost,
+        );
+        let id = Some(schedule.id.as_str());
+        data.untranslatable.extend(
+            lost.into_iter()
+                .map(|reason| note("schedules", id, reason, false)),
+        );
+        match translated {
+            Ok(schedule) => data.schedules.push(schedule),
+            Err(reason) => data
+                .untranslatable
+                .push(note("schedules", id, reason, true)),
+        }
+    }
+    Ok(())
+}
+
+fn schedule_exists(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    conn.query_row("SELECT 1 FROM schedules WHERE id = ?1", [id], |_| Ok(()))
+        .optional()
+        .map(|row| row.is_some())
+}
+
+fn insert_schedule(conn: &Connection, schedule: &Schedule) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO schedules (id, title, description, priority, starts_at, rrule,
+             excluded_dates, active, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            schedule.id,
+            schedule.title,
+            schedule.description,
+            schedule.priority,
+            schedule.starts_at,
+            schedule.rrule,
+            serde_json::to_string(&schedule.excluded_dates).unwrap_or_else(|_| "[]".to_string()),
+            schedule.active,
+            schedule.created_at,
+            schedule.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Counts what is new against the store, and writes it unless `dry_run`.
+fn merge(
+    conn: &Connection,
+    data: &LegacyServerData,
+    dry_run: bool,
+    report: &mut LegacyServerReport,
+) -> rusqlite::Result<()> {
+    for todo in &data.todos {
+        if storage::get_todo_with(conn, &todo.id)?.is_some() {
+            report.todos.existing += 1;
+        } else {
+            if !dry_run {
+                storage::insert_todo(conn, todo)?;
+            }
+            report.todos.imported += 1;
+        }
+    }
+    for schedule in &data.schedules {
+        if schedule_exists(conn, &schedule.id)? {
+            report.schedules.existing += 1;
+        } else {
+            if !dry_run {
+                insert_schedule(conn, schedule)?;
+            }
+            report.schedules.imported += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Imports the server database at `source` into the store. A dry run only
+/// reports what a commit would do; a commit backs the store up first and
+/// writes everything in one transaction, skipping ids the store has.
+pub fn import_server_database(
+    storage: &Storage,
+    source: &Path,
+    dry_run: bool,
+) -> Result<LegacyServerReport, String> {
+    if !storage::is_legacy_database(source) {
+        return Err(format!(
+            "Failed to read {}: not a database of the server",
+            source.display()
+        ));
+    }
+    let data = read_server_database(source, storage.clock().now())
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    let mut report = LegacyServerReport {
+        source: source.to_path_buf(),
+        dry_run,
+        backup: None,
+        todos: TableReport {
+            read: data.todos.len(),
+            ..Default::default()
+        },
+        schedules: TableReport {
+            read: data.schedules.len(),
+            ..Default::default()
+        },
+        untranslatable: data.untranslatable.clone(),
+        quota_exceeded: None,
+    };
+
+    let failed = |e: rusqlite::Error| format!("Failed to import {}: {}", source.display(), e);
+    if dry_run {
+        let conn = storage.conn();
+        merge(&conn, &data, true, &mut report).map_err(failed)?;
+        let total = quota::count_todos(&conn).map_err(failed)? + report.todos.imported as u64;
+        report.quota_exceeded = quota::check_todos(storage, total).err();
+        return Ok(report);
+    }
+    // Nothing new: a re-run neither writes nor takes another backup.
+    let mut probe = report.clone();
+    merge(&storage.conn(), &data, true, &mut probe).map_err(failed)?;
+    if probe.todos.imported + probe.schedules.imported == 0 {
+        return Ok(probe);
+    }
+    report.backup = Some(storage::back_up(storage, "before-server-import")?);
+    let mut conn = storage.conn();
+    let tx = conn.transaction().map_err(failed)?;
+    merge(&tx, &data, false, &mut report).map_err(failed)?;
+    let total = quota::count_todos(&tx).map_err(failed)?;
+    quota::check_todos(storage, total).map_err(|e| e.to_string())?;
+    tx.commit().map_err(failed)?;
+    Ok(report)
+}
+
+/// Server databases found in the default locations, the server's own
+/// data directory first.
+#[tauri::command]
+pub fn find_legacy_databases(app: AppHandle) -> Vec<PathBuf> {
+    let Ok(data_dir) = app.path().data_dir() else {
+        return Vec::new();
+    };
+    candidate_dirs(&data_dir)
+        .iter()
+        .filter_map(|dir| storage::detect_legacy_database(dir))
+        .collect()
+}
+
+/// Moves the todos and schedules of the server database at `path` into
+/// the store; see [`import_server_database`]. Pass `dry_run` to see the
+/// report without writing anything. The path must be one of
+/// [`find_legacy_databases`] or pass the import allow-list.
+#[tauri::command]
+pub fn import_legacy_server_db(
+    path: PathBuf,
+    dry_run: bool,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    index: State<'_, SearchIndex>,
+    progress: State<'_, ProgressRegistry>,
+) -> Result<LegacyServerReport, PathError> {
+    let path = if find_legacy_databases(app.clone()).contains(&path) {
+        path
+    } else {
+        file_access::check(&app, &path)?
+    };
+    // A dry run writes nothing, so it needn't hold scheduled exports back.
+    let _progress = (!dry_run)
+        .then(|| {
+            progress.register(
+                "import-legacy-server-db",
+                "migration",
+                None,
+                storage.clock().now(),
+            )
+        })
+        .transpose()?;
+    let report = import_server_database(&storage, &path, dry_run)?;
+    if !dry_run && report.todos.imported > 0 {
+        if let Ok(todos) = storage.list_todos() {
+            index.rebuild(&todos);
+        }
+        let _ = events::emit(&app, "todo-cache-updated", ());
+        quota::notify(&app);
+    }
+    Ok(report)
+}
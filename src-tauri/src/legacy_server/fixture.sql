@@ -0,0 +1,69 @@
+-- A server database as `server.ts` writes it, with titles and notes
+-- replaced. Kept as SQL so changes to it show up in review.
+CREATE TABLE todos (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    description TEXT,
+    completed BOOLEAN DEFAULT FALSE,
+    priority INTEGER DEFAULT 0,
+    scheduledFor DATETIME,
+    createdAt DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updatedAt DATETIME DEFAULT CURRENT_TIMESTAMP,
+    order_index INTEGER DEFAULT 0
+);
+CREATE TABLE schedules (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    description TEXT,
+    priority INTEGER DEFAULT 0,
+    type TEXT NOT NULL,
+    startDate TEXT NOT NULL,
+    endDate TEXT,
+    time TEXT,
+    weeklyConfig TEXT,
+    monthlyConfig TEXT,
+    customConfig TEXT,
+    excludeWeekends BOOLEAN DEFAULT FALSE,
+    excludeDates TEXT,
+    isActive BOOLEAN DEFAULT TRUE,
+    createdAt DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updatedAt DATETIME DEFAULT CURRENT_TIMESTAMP,
+    lastExecuted DATETIME,
+    nextExecution DATETIME
+);
+
+INSERT INTO todos VALUES ('8c1f0e2a-0001', 'Task one', NULL, 0, 0,
+    NULL, '2024-03-01 08:00:00', '2024-03-01 08:00:00', 0);
+INSERT INTO todos VALUES ('8c1f0e2a-0002', 'Task two', 'Note two', 1, 2,
+    '2024-03-05T09:30:00.000Z', '2024-03-01T08:05:00.000Z', '2024-03-04T17:00:00.000Z', 1);
+INSERT INTO todos VALUES ('8c1f0e2a-0003', 'Task three', NULL, 0, 'high',
+    NULL, '2024-03-02 10:00:00', '2024-03-02 10:00:00', 2);
+INSERT INTO todos VALUES ('8c1f0e2a-0004', 'Task four', NULL, 0, 7,
+    'next week', '2024-03-02 11:00:00', '2024-03-02 11:00:00', 3);
+INSERT INTO todos VALUES (NULL, 'Task without id', NULL, 0, 1,
+    NULL, '2024-03-03 12:00:00', '2024-03-03 12:00:00', 4);
+
+INSERT INTO schedules VALUES ('5d0a-once', 'Schedule once', NULL, 1, 'once',
+    '2024-04-01', NULL, '09:00', NULL, NULL, NULL, 0, NULL, 1,
+    '2024-03-10T08:00:00.000Z', '2024-03-10T08:00:00.000Z', NULL, '2024-04-01T09:00:00.000Z');
+INSERT INTO schedules VALUES ('5d0a-daily', 'Schedule daily', 'Weekdays only', 'low', 'daily',
+    '2024-04-01', '2024-06-30', '07:30', NULL, NULL, NULL, 1, '["2024-05-03","someday"]', 1,
+    '2024-03-10T08:01:00.000Z', '2024-03-11T08:00:00.000Z', '2024-04-02T07:30:00.000Z', NULL);
+INSERT INTO schedules VALUES ('5d0a-weekly', 'Schedule weekly', NULL, 2, 'weekly',
+    '2024-04-01', NULL, NULL, '{"daysOfWeek":[5,1],"time":"18:00"}', NULL, NULL, 0, '[]', 0,
+    '2024-03-10T08:02:00.000Z', '2024-03-10T08:02:00.000Z', NULL, NULL);
+INSERT INTO schedules VALUES ('5d0a-monthly-weekday', 'Schedule second Tuesday', NULL, 1, 'monthly',
+    '2024-04-01', NULL, '10:00', NULL, '{"type":"weekday","weekNumber":2,"dayOfWeek":2}', NULL, 0, NULL, 1,
+    '2024-03-10T08:03:00.000Z', '2024-03-10T08:03:00.000Z', NULL, NULL);
+INSERT INTO schedules VALUES ('5d0a-monthly-date', 'Schedule on the 15th', NULL, 1, 'monthly',
+    '2024-04-01', NULL, '10:00', NULL, '{"type":"date","date":15}', NULL, 1, NULL, 1,
+    '2024-03-10T08:04:00.000Z', '2024-03-10T08:04:00.000Z', NULL, NULL);
+INSERT INTO schedules VALUES ('5d0a-custom', 'Schedule every other week', NULL, 1, 'custom',
+    '2024-04-01', NULL, NULL, NULL, NULL, '{"interval":2,"unit":"weeks","time":"08:15","maxOccurrences":10}', 0, NULL, 1,
+    '2024-03-10T08:05:00.000Z', '2024-03-10T08:05:00.000Z', NULL, NULL);
+INSERT INTO schedules VALUES ('5d0a-yearly', 'Schedule yearly', NULL, 1, 'yearly',
+    '2024-04-01', NULL, NULL, NULL, NULL, NULL, 0, NULL, 1,
+    '2024-03-10T08:06:00.000Z', '2024-03-10T08:06:00.000Z', NULL, NULL);
+INSERT INTO schedules VALUES ('5d0a-weekly-empty', 'Schedule without days', NULL, 1, 'weekly',
+    '2024-04-01', NULL, NULL, '{"daysOfWeek":[]}', NULL, NULL, 0, NULL, 1,
+    '2024-03-10T08:07:00.000Z', '2024-03-10T08:07:00.000Z', NULL, NULL);
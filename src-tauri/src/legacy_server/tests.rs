@@ -0,0 +1,228 @@
+use super::*;
+
+use std::fs;
+
+use crate::storage::StorageBackend;
+
+/// Writes the sanitized server database in `fixture.sql` to `dir`.
+fn write_server_database(dir: &Path) -> PathBuf {
+    let path = dir.join("todos.db");
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch(include_str!("fixture.sql"))
+        .unwrap();
+    path
+}
+
+/// Reads back the schedules in the store, oldest first.
+fn list_schedules(conn: &Connection) -> rusqlite::Result<Vec<Schedule>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, title, description, priority, starts_at, rrule, excluded_dates, active,
+             created_at, updated_at
+         FROM schedules ORDER BY created_at, id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let excluded_dates: String = row.get(6)?;
+        Ok(Schedule {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            description: row.get(2)?,
+            priority: row.get(3)?,
+            starts_at: row.get(4)?,
+            rrule: row.get(5)?,
+            excluded_dates: serde_json::from_str(&excluded_dates).unwrap_or_default(),
+            active: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn schedule(kind: &str) -> LegacySchedule {
+    LegacySchedule {
+        id: "s".to_string(),
+        title: "Schedule".to_string(),
+        kind: kind.to_string(),
+        start_date: "2024-04-01".to_string(),
+        active: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_schedules_become_rrules() {
+    let mut lost = Vec::new();
+    let last_day = LegacySchedule {
+        monthly_config: Some(r#"{"type":"lastDay","daysFromEnd":2}"#.to_string()),
+        ..schedule("monthly")
+    };
+    assert_eq!(
+        to_rrule(&last_day, &mut lost).unwrap().0,
+        "FREQ=MONTHLY;BYMONTHDAY=-3"
+    );
+    let every_three_days = LegacySchedule {
+        custom_config: Some(
+            r#"{"interval":3,"unit":"days","endDate":"2024-05-31T00:00:00.000Z"}"#.to_string(),
+        ),
+        ..schedule("custom")
+    };
+    assert_eq!(
+        to_rrule(&every_three_days, &mut lost).unwrap().0,
+        "FREQ=DAILY;INTERVAL=3;UNTIL=20240531T235959"
+    );
+    assert!(lost.is_empty());
+
+    // Only weekend days, minus the weekends, leaves nothing to repeat on.
+    let weekends = LegacySchedule {
+        weekly_config: Some(r#"{"daysOfWeek":[0,6]}"#.to_string()),
+        exclude_weekends: true,
+        ..schedule("weekly")
+    };
+    assert!(to_rrule(&weekends, &mut lost).is_err());
+    let bad_day = LegacySchedule {
+        weekly_config: Some(r#"{"daysOfWeek":[7]}"#.to_string()),
+        ..schedule("weekly")
+    };
+    assert!(to_rrule(&bad_day, &mut lost).is_err());
+    assert!(to_rrule(&schedule("monthly"), &mut lost).is_err());
+}
+
+#[test]
+fn test_server_database_is_found_in_default_places() {
+    let dir = tempfile::tempdir().unwrap();
+    let dirs = candidate_dirs(dir.path());
+    assert_eq!(dirs.len(), 2);
+    assert!(dirs
+        .iter()
+        .all(|candidate| candidate.starts_with(dir.path())));
+    assert!(dirs
+        .iter()
+        .all(|candidate| storage::detect_legacy_database(candidate).is_none()));
+
+    fs::create_dir_all(&dirs[0]).unwrap();
+    let path = write_server_database(&dirs[0]);
+    let found: Vec<_> = dirs
+        .iter()
+        .filter_map(|candidate| storage::detect_legacy_database(candidate))
+        .collect();
+    assert_eq!(found, vec![path]);
+}
+
+#[test]
+fn test_server_database_import() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = write_server_database(dir.path());
+    let original = fs::read(&source).unwrap();
+    let storage = Storage::open(StorageBackend::Disk(dir.path().join("data"))).unwrap();
+
+    let preview = import_server_database(&storage, &source, true).unwrap();
+    assert!(preview.dry_run);
+    assert_eq!((preview.todos.read, preview.todos.imported), (4, 4));
+    assert_eq!((preview.schedules.read, preview.schedules.imported), (6, 6));
+    assert_eq!((preview.backup, preview.quota_exceeded), (None, None));
+    assert!(storage.list_todos().unwrap().is_empty());
+    assert!(list_schedules(&storage.conn()).unwrap().is_empty());
+
+    let skipped: Vec<_> = preview
+        .untranslatable
+        .iter()
+        .filter(|item| item.skipped)
+        .map(|item| (item.table.as_str(), item.id.as_deref()))
+        .collect();
+    assert_eq!(
+        skipped,
+        vec![
+            ("todos", None),
+            ("schedules", Some("5d0a-yearly")),
+            ("schedules", Some("5d0a-weekly-empty")),
+        ]
+    );
+    let changed = |id: &str| {
+        preview
+            .untranslatable
+            .iter()
+            .filter(|item| !item.skipped && item.id.as_deref() == Some(id))
+            .map(|item| item.reason.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        changed("8c1f0e2a-0004"),
+        vec![
+            "priority 7 became medium",
+            "scheduledFor 'next week' was dropped"
+        ]
+    );
+    assert_eq!(
+        changed("5d0a-daily"),
+        vec!["excluded date 'someday' was ignored"]
+    );
+    assert_eq!(
+        changed("5d0a-monthly-date"),
+        vec!["excludeWeekends has no equivalent for a monthly schedule"]
+    );
+
+    let report = import_server_database(&storage, &source, false).unwrap();
+    assert!(!report.dry_run);
+    assert_eq!((report.todos.imported, report.schedules.imported), (4, 6));
+    assert!(report.backup.as_ref().unwrap().is_file());
+
+    let todo = storage.get_todo("8c1f0e2a-0002").unwrap().unwrap();
+    assert_eq!(todo.priority, Priority::High);
+    assert!(todo.completed);
+    assert_eq!(todo.created_at.to_rfc3339(), "2024-03-01T08:05:00+00:00");
+    assert_eq!(todo.updated_at.to_rfc3339(), "2024-03-04T17:00:00+00:00");
+    let rules: Vec<_> = list_schedules(&storage.conn())
+        .unwrap()
+        .into_iter()
+        .map(|schedule| (schedule.id, schedule.rrule))
+        .collect();
+    assert_eq!(
+        rules,
+        [
+            ("5d0a-once", "FREQ=DAILY;COUNT=1"),
+            (
+                "5d0a-daily",
+                "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR;UNTIL=20240630T235959"
+            ),
+            ("5d0a-weekly", "FREQ=WEEKLY;BYDAY=MO,FR"),
+            ("5d0a-monthly-weekday", "FREQ=MONTHLY;BYDAY=2TU"),
+            ("5d0a-monthly-date", "FREQ=MONTHLY;BYMONTHDAY=15"),
+            ("5d0a-custom", "FREQ=WEEKLY;INTERVAL=2;COUNT=10"),
+        ]
+        .map(|(id, rule)| (id.to_string(), rule.to_string()))
+    );
+    let schedules = list_schedules(&storage.conn()).unwrap();
+    let weekly = &schedules[2];
+    assert_eq!(weekly.starts_at.to_string(), "2024-04-01 18:00:00");
+    assert_eq!(weekly.priority, Priority::High);
+    assert!(!weekly.active);
+    let daily = &schedules[1];
+    assert_eq!(daily.priority, Priority::Low);
+    assert_eq!(
+        daily.excluded_dates,
+        vec![NaiveDate::from_ymd_opt(2024, 5, 3).unwrap()]
+    );
+    assert_eq!(daily.updated_at.to_rfc3339(), "2024-03-11T08:00:00+00:00");
+
+    // A re-run finds every id and neither writes nor backs up again.
+    let todos = storage.list_todos().unwrap();
+    let again = import_server_database(&storage, &source, false).unwrap();
+    assert_eq!((again.todos.imported, again.todos.existing), (0, 4));
+    assert_eq!((again.schedules.imported, again.schedules.existing), (0, 6));
+    assert_eq!(again.backup, None);
+    assert_eq!(storage.list_todos().unwrap(), todos);
+    assert_eq!(list_schedules(&storage.conn()).unwrap(), schedules);
+
+    assert_eq!(fs::read(&source).unwrap(), original);
+}
+
+#[test]
+fn test_other_files_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::open_in_memory().unwrap();
+    let path = dir.path().join("todos.db");
+    fs::write(&path, "not a database").unwrap();
+    assert!(import_server_database(&storage, &path, true).is_err());
+    assert!(import_server_database(&storage, &dir.path().join("missing.db"), true).is_err());
+}
@@ -1,8 +1,77 @@
-use std::process::Command;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+use std::time::Instant;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+mod activity;
+mod audit;
+mod automation;
+mod bulk_edit;
+mod cache;
+mod calendar;
+mod capabilities;
+mod card;
+mod clock;
+mod command_registry;
+mod completion;
+mod crash;
+mod custom_fields;
+mod developer;
+mod events;
+mod export;
+mod export_schedule;
+mod file_access;
+mod file_assoc;
+mod focus;
+mod import;
+mod integrations;
+mod language;
+mod legacy_server;
+mod live_query;
+mod local_api;
+mod nlp;
+mod notifications;
+mod operations;
+mod paths;
+mod platform;
+mod plugins;
+mod privacy;
+mod progress;
+mod protocol;
+mod query;
+mod quota;
+mod rank;
+mod read_only;
+mod references;
+mod reset;
+mod resources;
+mod retry;
+mod safety;
+mod scheduler;
+mod schema;
+mod search;
+mod self_check;
+mod settings;
+mod shutdown;
+mod site;
+mod stats;
+mod storage;
+mod sync;
+mod textutil;
+mod trace;
+mod trust;
+mod types;
+mod validation_rules;
+
 #[cfg(test)]
 mod tests;
 
@@ -12,88 +81,761 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// When the backend started, for `ping`'s uptime.
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Answer to `ping`, identifying the build that answered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PingResponse {
+    pub echo: String,
+    pub app_version: String,
+    pub uptime_secs: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+fn ping_response(echo: String, started: Instant) -> PingResponse {
+    PingResponse {
+        echo,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        // Real time, not the app's clock: this is the process's uptime.
+        uptime_secs: started.elapsed().as_secs(),
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+/// Health probe for automated tests and external tools: echoes `echo` back
+/// along with the version and uptime of the backend.
 #[tauri::command]
-fn spawn_new_instance() -> Result<String, String> {
-    // 現在の実行ファイルのパスを取得
-    let current_exe = std::env::current_exe()
-        .map_err(|e| format!("Failed to get current executable path: {}", e))?;
-    
-    println!("Attempting to spawn new instance from: {:?}", current_exe);
-    
+fn ping(echo: String) -> PingResponse {
+    ping_response(echo, *STARTED.get_or_init(Instant::now))
+}
+
+/// Launches `command`. Under `cargo test` this refuses to start anything, since the
+/// "current executable" is the test binary itself and would re-run the whole suite.
+fn launch(command: &mut Command) -> std::io::Result<Child> {
+    if cfg!(test) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "process spawning is disabled in tests",
+        ));
+    }
+    command.spawn()
+}
+
+/// Set in the environment of instances started by `spawn_new_instance`,
+/// which run alongside this one instead of handing their launch over.
+const SPAWNED_INSTANCE_ENV: &str = "YUTODO_SPAWNED_INSTANCE";
+
+/// Prefix of the error returned while a focus session blocks new windows.
+const FOCUS_ACTIVE_ERROR: &str = "focus-active";
+
+/// Rejects spawning while a focus session runs, if the user asked for that.
+fn check_spawn_allowed(
+    settings: &settings::Settings,
+    focus_active: bool,
+    force: bool,
+) -> Result<(), String> {
+    if settings.block_spawn_during_focus && focus_active && !force {
+        return Err(format!(
+            "{}: New windows are blocked while a focus session is running",
+            FOCUS_ACTIVE_ERROR
+        ));
+    }
+    Ok(())
+}
+
+/// Variables that change what code a process loads, passed to a new
+/// instance only with `allow_unsafe_env`.
+const UNSAFE_ENV: &[&str] = &["LD_PRELOAD", "LD_LIBRARY_PATH", "LD_AUDIT"];
+const UNSAFE_ENV_PREFIXES: &[&str] = &["DYLD_"];
+
+/// The variables of `env` a new instance gets, sorted by name. Names that
+/// can't be set are dropped, and so are unsafe ones unless `allow_unsafe`.
+fn instance_env(env: HashMap<String, String>, allow_unsafe: bool) -> Vec<(String, String)> {
+    let mut kept: Vec<_> = env
+        .into_iter()
+        .filter(|(key, _)| {
+            let upper = key.to_ascii_uppercase();
+            let valid = !key.is_empty() && !key.contains(['=', '\0']);
+            let unsafe_env = UNSAFE_ENV.contains(&upper.as_str())
+                || UNSAFE_ENV_PREFIXES.iter().any(|prefix| upper.starts_with(prefix));
+            if !valid || (unsafe_env && !allow_unsafe) {
+                trace::warn(format!("Not passing {} to the new instance", key));
+                return false;
+            }
+            true
+        })
+        .collect();
+    kept.sort();
+    kept
+}
+
+/// Starts another instance. It runs in `cwd` if given, otherwise in the
+/// data directory, so relative paths it is handed resolve the same way no
+/// matter where the file manager launched this one from. `env` is added to
+/// its environment, e.g. `RUST_LOG` for debugging.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn spawn_new_instance(
+    force: Option<bool>,
+    cwd: Option<std::path::PathBuf>,
+    env: Option<HashMap<String, String>>,
+    allow_unsafe_env: Option<bool>,
+    app: AppHandle,
+    focus: State<'_, focus::FocusState>,
+    settings: State<'_, settings::SettingsState>,
+    children: State<'_, resources::ChildTracker>,
+) -> Result<String, String> {
+    let allowed = check_spawn_allowed(&settings.get(), focus.is_active(), force.unwrap_or(false));
+    if let Err(e) = allowed {
+        let _ = events::emit(&app, "spawn-blocked", &e);
+        return Err(e);
+    }
+    let cwd = match cwd {
+        Some(cwd) => cwd,
+        None => storage::data_dir(&app)
+            .map_err(|e| format!("Failed to resolve the data directory: {}", e))?,
+    };
+    if !cwd.is_dir() {
+        return Err(format!(
+            "Failed to spawn new process: {} is not a directory",
+            cwd.display()
+        ));
+    }
+    let mut env = instance_env(env.unwrap_or_default(), allow_unsafe_env.unwrap_or(false));
+    env.push((SPAWNED_INSTANCE_ENV.to_string(), "1".to_string()));
+    launch_instance(&children, &cwd, &env, &trust::restrict(&app, settings.get()))
+}
+
+/// The command that starts `current_exe` again, in `cwd` and with `env`
+/// added to the environment.
+fn instance_command(
+    current_exe: &std::path::Path,
+    cwd: &std::path::Path,
+    env: &[(String, String)],
+) -> Command {
     // プラットフォーム別の処理
     #[cfg(target_os = "windows")]
-    {
+    let mut command = {
         // Windowsでは、新しいプロセスを独立して起動
-        match Command::new(&current_exe)
-            .creation_flags(0x00000010) // CREATE_NEW_CONSOLE
-            .spawn()
-        {
-            Ok(child) => {
-                let pid = child.id();
-                println!("Successfully spawned new process with PID: {}", pid);
-                Ok(format!("New process spawned with PID: {}", pid))
-            }
-            Err(e) => {
-                eprintln!("Failed to spawn new process: {}", e);
-                Err(format!("Failed to spawn new process: {}", e))
-            }
-        }
-    }
-    
+        let mut command = Command::new(current_exe);
+        command.creation_flags(0x00000010); // CREATE_NEW_CONSOLE
+        command.envs(env.iter().cloned());
+        command
+    };
+
     #[cfg(target_os = "macos")]
-    {
+    let mut command = {
         // macOSでは、openコマンドを使用して新しいインスタンスを起動
-        match Command::new("open")
-            .arg("-n") // 新しいインスタンスを起動
+        let mut command = Command::new("open");
+        command.arg("-n"); // 新しいインスタンスを起動
+        // The app is started by launchd, not as a child of `open`.
+        for (key, value) in env {
+            command.arg("--env").arg(format!("{}={}", key, value));
+        }
+        command
             .arg("-a") // アプリケーションを指定
-            .arg(&current_exe)
-            .spawn()
-        {
-            Ok(child) => {
-                let pid = child.id();
-                println!("Successfully spawned new process with PID: {}", pid);
-                Ok(format!("New process spawned with PID: {}", pid))
-            }
-            Err(e) => {
-                eprintln!("Failed to spawn new process: {}", e);
-                Err(format!("Failed to spawn new process: {}", e))
+            .arg(current_exe);
+        command
+    };
+
+    // Linuxでは、通常のspawnを使用
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = {
+        let mut command = Command::new(current_exe);
+        command.envs(env.iter().cloned());
+        command
+    };
+
+    command.current_dir(cwd);
+    command
+}
+
+/// Finds the binary to start again: `current_exe`, else `arg0` as a path
+/// or looked up in `path_var`, else the `instance_binary` setting. Some
+/// sandboxes hide `current_exe`; failing, the error lists every attempt.
+fn resolve_instance_binary(
+    current_exe: std::io::Result<PathBuf>,
+    arg0: Option<OsString>,
+    path_var: Option<OsString>,
+    configured: Option<&Path>,
+) -> Result<PathBuf, String> {
+    let mut failures = Vec::new();
+    match current_exe {
+        Ok(path) => return Ok(path),
+        Err(e) => failures.push(format!("current executable: {}", e)),
+    }
+    match arg0.map(PathBuf::from) {
+        // A bare name is looked up in PATH, like the shell that started us.
+        Some(arg0) if arg0.components().count() == 1 => {
+            let found = path_var
+                .iter()
+                .flat_map(std::env::split_paths)
+                .map(|dir| dir.join(&arg0))
+                .find(|candidate| candidate.is_file());
+            match found {
+                Some(path) => return Ok(path),
+                None => failures.push(format!("{}: not found in PATH", arg0.display())),
             }
         }
+        Some(arg0) if arg0.is_file() => return Ok(arg0),
+        Some(arg0) => failures.push(format!("{}: not a file", arg0.display())),
+        None => failures.push("argv[0]: not set".to_string()),
+    }
+    match configured {
+        Some(path) if path.is_file() => return Ok(path.to_path_buf()),
+        Some(path) => failures.push(format!("instance_binary {}: not a file", path.display())),
+        None => failures.push("instance_binary: not set".to_string()),
     }
+    Err(format!(
+        "Failed to find the executable to start: {}",
+        failures.join("; ")
+    ))
+}
+
+/// Whether this build can start instances of itself at all.
+const SPAWN_PLATFORM_SUPPORTED: bool =
+    cfg!(any(target_os = "windows", target_os = "macos", target_os = "linux"));
+
+/// The binary a new instance would run, resolved the way
+/// [`launch_instance`] does.
+fn instance_binary(
+    platform_supported: bool,
+    settings: &settings::Settings,
+) -> Result<PathBuf, String> {
+    if !platform_supported {
+        return Err("Unsupported platform".to_string());
+    }
+    resolve_instance_binary(
+        std::env::current_exe(),
+        std::env::args_os().next(),
+        std::env::var_os("PATH"),
+        settings.instance_binary.as_deref(),
+    )
+}
+
+/// Whether `spawn_new_instance` can work, and why not.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SpawnCapability {
+    pub supported: bool,
+    pub reason: Option<String>,
+}
+
+fn spawn_capability(platform_supported: bool, settings: &settings::Settings) -> SpawnCapability {
+    match instance_binary(platform_supported, settings) {
+        Ok(_) => SpawnCapability {
+            supported: true,
+            reason: None,
+        },
+        Err(reason) => SpawnCapability {
+            supported: false,
+            reason: Some(reason),
+        },
+    }
+}
+
+/// Lets the UI disable "New Window" up front: resolves what
+/// `spawn_new_instance` would start, without starting it.
+#[tauri::command]
+fn can_spawn_instance(
+    app: AppHandle,
+    settings: State<'_, settings::SettingsState>,
+) -> SpawnCapability {
+    spawn_capability(
+        SPAWN_PLATFORM_SUPPORTED,
+        &trust::restrict(&app, settings.get()),
+    )
+}
+
+/// Starts another instance. With `capture_child_output` its stdout and
+/// stderr go to this process's log; otherwise it inherits them.
+fn launch_instance(
+    children: &resources::ChildTracker,
+    cwd: &Path,
+    env: &[(String, String)],
+    settings: &settings::Settings,
+) -> Result<String, String> {
+    // 現在の実行ファイルのパスを取得
+    let current_exe = instance_binary(SPAWN_PLATFORM_SUPPORTED, settings)?;
     
-    #[cfg(target_os = "linux")]
-    {
-        // Linuxでは、通常のspawnを使用
-        match Command::new(&current_exe)
-            .spawn()
-        {
-            Ok(child) => {
-                let pid = child.id();
-                println!("Successfully spawned new process with PID: {}", pid);
-                Ok(format!("New process spawned with PID: {}", pid))
-            }
-            Err(e) => {
-                eprintln!("Failed to spawn new process: {}", e);
-                Err(format!("Failed to spawn new process: {}", e))
+    trace::log(format!("Attempting to spawn new instance from: {:?}", current_exe));
+    
+    let mut command = instance_command(&current_exe, cwd, env);
+    let capture_output = settings.capture_child_output;
+    if capture_output {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    match launch(&mut command) {
+        Ok(mut child) => {
+            let pid = child.id();
+            if capture_output {
+                resources::capture_output(&mut child);
             }
+            children.track(child);
+            trace::log(format!("Successfully spawned new process with PID: {}", pid));
+            Ok(format!("New process spawned with PID: {}", pid))
+        }
+        Err(e) => {
+            trace::log(format!("Failed to spawn new process: {}", e));
+            Err(format!("Failed to spawn new process: {}", e))
         }
     }
-    
-    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-    {
-        Err("Unsupported platform".to_string())
+}
+
+/// Builds the invoke handler and the list `list_commands` reports from one
+/// table, so the two can't drift apart.
+macro_rules! commands {
+    ($($(#[cfg($($cfg:tt)*)])? $($segment:ident)::+ => $description:literal,)*) => {
+        /// Every command the webview can invoke, as `(path, description)`.
+        const COMMANDS: &[(&str, &str)] = &[
+            $($(#[cfg($($cfg)*)])? (stringify!($($segment)::+), $description),)*
+        ];
+
+        fn invoke_handler() -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+            tauri::generate_handler![$($(#[cfg($($cfg)*)])? $($segment)::+),*]
+        }
+    };
+}
+
+/// A command in the registry `list_commands` returns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandInfo {
+    pub name: String,
+    pub description: String,
+}
+
+/// The registered commands by name, for tooling and scripts.
+#[tauri::command]
+fn list_commands() -> Vec<CommandInfo> {
+    COMMANDS
+        .iter()
+        .map(|(path, description)| CommandInfo {
+            name: path.rsplit("::").next().unwrap_or(path).trim().to_string(),
+            description: description.to_string(),
+        })
+        .collect()
+}
+
+/// JSON Schema of every command's arguments, result and error, for
+/// generating the frontend's bindings.
+#[tauri::command]
+fn api_schema() -> String {
+    serde_json::to_string_pretty(&schema::generate()).expect("schemas serialize to JSON")
+}
+
+commands! {
+    greet => "Demo greeting kept for older frontends",
+    ping => "Health probe echoing the version and uptime",
+    spawn_new_instance => "Start another instance of the app",
+    can_spawn_instance => "Whether a new instance could be started",
+    activity::get_activity_feed => "Page through the activity feed",
+    storage::changes::get_changes_since => "Changes to todos after a cursor",
+    audit::get_audit_log => "Recent audit log entries",
+    bulk_edit::open_bulk_edit => "Open the todo list in an external editor",
+    bulk_edit::apply_bulk_edit => "Apply the edits made in the editor",
+    bulk_edit::discard_bulk_edit => "Drop a bulk edit without applying it",
+    bulk_edit::undo_bulk_edit => "Undo the last applied bulk edit",
+    capabilities::get_capabilities => "Which features work on this system",
+    protocol::negotiate_protocol => "Agree on the event protocol with a window",
+    custom_fields::define_custom_field => "Create a custom field",
+    custom_fields::list_custom_fields => "List the custom fields",
+    custom_fields::set_todo_field_value => "Set or clear a custom field on a todo",
+    custom_fields::delete_custom_field => "Delete a custom field",
+    focus::start_focus_session => "Start a focus session on a todo",
+    focus::stop_focus_session => "Stop the running focus session",
+    focus::focus_report => "Focus time per todo and day",
+    stats::effort_accuracy_report => "Estimated against actual effort",
+    stats::list_tags => "The tags in use with their todo counts",
+    stats::weekly_review => "Digest of a week for the weekly review",
+    cache::get_cache_stats => "Hit rates and memory use of the caches",
+    cache::clear_caches => "Drop every cached value",
+    validation_rules::lint_existing_todos => "Check todos against the validation rules",
+    focus::export_focus_csv => "Write focus sessions to a CSV file",
+    import::import_todos => "Import todos from a CSV or JSON file",
+    import::resume_import => "Resume an interrupted import",
+    import::list_interrupted_imports => "Imports that can be resumed",
+    import::cancel_import => "Cancel an interrupted import",
+    operations::cancel_operation => "Cancel a long operation and roll back its writes",
+    progress::list_operations => "Progress of the long operations in flight",
+    automation::schedule_command => "Run a backup, export or purge later or repeatedly",
+    automation::list_scheduled_commands => "List the scheduled commands",
+    automation::cancel_scheduled_command => "Cancel a scheduled command",
+    automation::add_rule => "Define a rule that acts on todo changes",
+    automation::test_rule => "What a rule would do to the todos, without applying it",
+    automation::list_rules => "The rules, in the order they run",
+    automation::set_rule_enabled => "Turn a rule off or on",
+    automation::delete_rule => "Delete a rule",
+    import::import_directory => "Import every file in a directory",
+    integrations::bookmarks::import_bookmarks => "Import browser bookmarks as todos",
+    integrations::connectors::list_connector_kinds => "The kinds of sources todos can be re-synced from",
+    integrations::connectors::add_connector_instance => "Add a source todos are re-synced from",
+    integrations::connectors::list_connector_instances => "List the sources todos are re-synced from",
+    integrations::connectors::run_connector_now => "Re-sync a source now",
+    integrations::connectors::remove_connector_instance => "Stop re-syncing a source",
+    export_schedule::add_export_schedule => "Export to a folder daily or weekly",
+    export_schedule::list_export_schedules => "List the recurring exports",
+    export_schedule::run_export_schedule_now => "Run a recurring export now",
+    export_schedule::get_export_schedule_history => "The last runs of a recurring export",
+    export_schedule::remove_export_schedule => "Stop a recurring export",
+    local_api::get_local_api_info => "Address and token of the local API",
+    settings::reload_settings => "Reread the settings file",
+    file_access::add_allowed_dir => "Allow file access under a directory",
+    paths::safe_path => "Check a path against the allowed directories",
+    settings::get_pending_restart_reasons => "Settings waiting for a restart",
+    settings::explain_settings => "Where each setting's value comes from",
+    notifications::set_notification_cooldown => "Set the minimum time between notifications",
+    notifications::claim_notification => "Claim a notification for this window",
+    notifications::claim_overdue_notifications => "Claim the notifications for overdue todos",
+    notifications::get_notification_text => "Text of a todo's notification",
+    notifications::activation::show_reminder_notification => "Show a reminder whose actions work while the app is closed",
+    notifications::activation::register_notification_activation => "Let notification actions launch the app",
+    privacy::set_privacy_mode => "Turn privacy mode on or off",
+    privacy::get_privacy_mode => "Whether privacy mode is on",
+    events::get_sticky_events => "Events a new window missed",
+    events::start_event_recording => "Start recording emitted events",
+    events::stop_event_recording => "Stop recording events and save the log",
+    events::replay_event_log => "Emit a recorded event log again",
+    safety::request_destruction_token => "Token confirming a destructive command",
+    resources::resource_usage => "Memory and process usage",
+    developer::enable_developer_mode => "Confirm developer mode for this session",
+    developer::disable_developer_mode => "Turn developer mode off",
+    developer::execute_readonly_sql => "Run a read-only SQL query in developer mode",
+    live_query::subscribe_query => "Subscribe to a live query",
+    live_query::unsubscribe_query => "End a live query subscription",
+    live_query::get_query_page => "A page of a live query's results",
+    scheduler::get_power_mode => "Current power mode of the scheduler",
+    scheduler::set_scheduler_interval => "Change how often a job runs",
+    card::render_todo_card => "Render a todo as an image",
+    crash::pending_crash_report => "Report left by the last crash",
+    crash::dismiss_crash_report => "Delete the pending crash report",
+    retry::last_failed_operation => "The last operation that failed",
+    retry::retry_last_failed => "Retry the last failed operation",
+    calendar::sync_to_system_calendar => "Sync scheduled todos into the OS calendar",
+    calendar::get_calendar_sync => "State of the calendar sync",
+    calendar::export_calendar_ics => "Write scheduled todos to an ICS file",
+    #[cfg(feature = "simulated-clock")]
+    clock::advance_clock => "Move the simulated clock forward",
+    #[cfg(feature = "simulated-clock")]
+    clock::set_clock => "Set the simulated clock",
+    command_registry::list_palette_commands => "Commands for the command palette",
+    command_registry::execute_command => "Run a palette command or keybinding",
+    self_check::run_self_check => "Check the store and settings for problems",
+    self_check::repair => "Fix a problem found by the self-check",
+    reset::reset_data => "Delete all local data",
+    reset::secure_wipe => "Overwrite and delete all local data",
+    storage::get_storage_mode => "Where the store lives",
+    storage::find_legacy_data => "Look for data from older versions",
+    storage::migrate_legacy_data => "Move data from older versions into the store",
+    legacy_server::find_legacy_databases => "Look for the server's database in its default places",
+    legacy_server::import_legacy_server_db => "Move the server's todos and schedules into the store",
+    storage::sync_todo_cache => "Mirror the server's todos into the cache",
+    storage::update_todo => "Update a cached todo",
+    storage::drafts::update_todo_draft => "Save an unfinished edit",
+    storage::drafts::flush_drafts => "Write pending drafts to the store",
+    storage::reorder_todo => "Move a todo between two others",
+    storage::merge_todos => "Merge duplicate todos into one",
+    storage::split_todo => "Split a todo's checklist into todos",
+    storage::lanes::get_contention_stats => "Wait times for the database per priority lane",
+    storage::recovery::apply_recovery => "Recover a damaged store from a backup or start empty",
+    search::check_similar_before_create => "Todos similar to one about to be created",
+    search::related_todos => "Todos sharing keywords with a todo",
+    nlp::extract_action_items => "Todos for the action items in meeting notes",
+    sync::subscribe_remote_list => "Follow a list published elsewhere",
+    sync::unsubscribe_remote_list => "Stop following a remote list",
+    sync::list_remote_lists => "The followed remote lists",
+    sync::preview_remote_merge => "What merging a remote list would change",
+    sync::publish_list => "Upload the list to a URL",
+    sync::set_publish_token => "Store the token for publishing to a URL",
+    sync::set_sync_scope => "Choose which todos publishing may send",
+    sync::get_sync_scope_report => "How many todos are in the sync scope",
+    site::publish_static_site => "Publish the list as a static site",
+    site::preview_static_site => "Render a static site without publishing",
+    site::schedule_static_site => "Publish a static site on a schedule",
+    site::unschedule_static_site => "Stop publishing a static site",
+    site::list_static_sites => "The configured static sites",
+    query::validate_query => "Check a query for errors",
+    query::eval_query => "Whether a todo matches a query",
+    query::query_todos => "Todos matching a query",
+    language::detect_language => "Detect the language of a text",
+    query::create_smart_list => "Save a query as a smart list",
+    query::list_smart_lists => "The saved smart lists",
+    query::get_smart_list_todos => "Todos in a smart list",
+    query::delete_smart_list => "Delete a smart list",
+    references::parse_todo_references => "References to other todos in a text",
+    references::resolve_todo_references => "Look up the todos a text refers to",
+    references::broken_references_report => "References to todos that are gone",
+    references::update_reference_text => "Rewrite references after a rename",
+    file_assoc::register_file_association => "Open export files with the app",
+    file_assoc::unregister_file_association => "Stop opening export files with the app",
+    export::export_todos => "Write the list to a CSV or JSON file",
+    export::export_weekly_review => "Write a weekly review as Markdown",
+    export::export_signed => "Write a signed JSON export",
+    export::verify_signed_export => "Check a signed export's signature",
+    file_assoc::take_opened_files => "Files the app was asked to open",
+    quota::get_data_quota_status => "Usage against the data limits",
+    import::preview_import_file => "What importing a file would do",
+    plugins::list_plugins => "The loaded plugins",
+    plugins::reload_plugins => "Reload the plugins",
+    trust::get_trust_status => "Where the profile came from and what is held back",
+    trust::grant_trust => "Allow or keep blocking what a foreign profile turns on",
+    list_commands => "The commands the backend accepts",
+    api_schema => "JSON Schema of the commands' arguments and results",
+    shutdown::quit_app => "Quit after finishing pending work",
+}
+
+/// Takes over a launch the single-instance plugin forwarded from a second
+/// process, so only this one writes the store: runs its notification
+/// action, or opens its `.todos` files, and brings the windows forward
+/// unless the action completes or snoozes.
+#[cfg(desktop)]
+fn second_launch(app: &AppHandle, args: Vec<String>) {
+    let show = match notifications::activation::action_from_args(args.iter().cloned()) {
+        Ok(Some(action)) => {
+            let opens = matches!(
+                action,
+                notifications::activation::NotificationAction::Open { .. }
+            );
+            notifications::activation::run_forwarded(app, action);
+            opens
+        }
+        Ok(None) => {
+            app.state::<file_assoc::OpenedFiles>()
+                .push(file_assoc::files_from_args(args));
+            true
+        }
+        Err(e) => {
+            trace::log(e);
+            false
+        }
+    };
+    if show {
+        for window in app.webview_windows().into_values() {
+            let _ = window.unminimize();
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
     }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    STARTED.get_or_init(Instant::now);
+    crash::install_panic_hook();
+    let builder = tauri::Builder::default();
+    // First, so a second launch is handed over before it starts anything.
+    #[cfg(desktop)]
+    let builder = if std::env::var_os(SPAWNED_INSTANCE_ENV).is_none() {
+        builder.plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+            second_launch(app, args)
+        }))
+    } else {
+        builder
+    };
+    builder
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![greet, spawn_new_instance])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .manage(focus::FocusState::default())
+        .manage(self_check::SelfCheckState::default())
+        .manage(local_api::LocalApiState::default())
+        .manage(import::ImportState::default())
+        .manage(operations::Operations::default())
+        .manage(progress::ProgressRegistry::default())
+        .manage(bulk_edit::BulkEditState::default())
+        .manage(capabilities::CapabilityRegistry::default())
+        .manage(command_registry::CommandRegistry::default())
+        .manage(protocol::ProtocolState::default())
+        .manage(sync::SyncState::default())
+        .manage(notifications::NotificationState::default())
+        .manage(file_assoc::OpenedFiles::default())
+        .manage(quota::QuotaWatch::default())
+        .manage(plugins::PluginHost::default())
+        .manage(events::EventRecorder::default())
+        .manage(safety::SafetyState::default())
+        .manage(developer::DeveloperMode::default())
+        .manage(resources::ChildTracker::default())
+        .manage(live_query::LiveQueries::default())
+        .manage(cache::Caches::default())
+        .manage(automation::RulesState::default())
+        .manage(storage::LegacyData::default())
+        .manage(privacy::PrivacyState::default())
+        .manage(events::StickyEvents::default())
+        .manage(calendar::CalendarSyncState::default())
+        .manage(retry::LastFailed::default())
+        .manage(shutdown::Shutdown::default())
+        .setup(|app| {
+            #[cfg(desktop)]
+            app.handle()
+                .plugin(tauri_plugin_global_shortcut::Builder::new().build())?;
+            let clock = clock::ClockState::from_args(std::env::args())?;
+            let shared_clock = clock.shared();
+            app.manage(clock);
+            app.state::<file_assoc::OpenedFiles>()
+                .push(file_assoc::files_from_args(std::env::args()));
+            let config_dir = settings::config_dir(app.handle())?;
+            // Also finishes a reset left pending by the last run, before
+            // anything opens the files it removes.
+            let settings = reset::finish_pending_at_startup(
+                app.handle(),
+                &config_dir.join(settings::SETTINGS_FILE),
+            )?;
+            // Opened before anything the profile turns on can start.
+            app.manage(trust::TrustState::open(
+                config_dir.clone(),
+                app.path().app_local_data_dir()?.join(trust::TRUST_FILE),
+            ));
+            trace::set_level(settings.log_level);
+            let data_dir = match settings.data_dir.clone() {
+                Some(dir) => dir,
+                None => storage::data_dir(app.handle())?,
+            };
+            crash::set_report_dir(&data_dir);
+            let backend = storage::StorageBackend::from_args(std::env::args(), data_dir);
+            let (storage, recovery) = storage::recovery::open(backend, shared_clock)?;
+            let index = search::SearchIndex::default();
+            index.rebuild(&storage.list_todos()?);
+            let journal = storage
+                .mode()
+                .data_dir
+                .map(|dir| dir.join(storage::drafts::JOURNAL_FILE));
+            app.manage(storage);
+            app.manage(recovery);
+            app.manage(index);
+            app.manage(storage::drafts::Drafts::new(journal));
+            storage::changes::start_consumers(app.handle());
+            let read_only = app.state::<storage::Storage>().newer_schema().is_some();
+            // A read-only store keeps the journal until an updated app can
+            // write it back.
+            if !read_only {
+                storage::drafts::recover_at_startup(app.handle(), &settings.validation_rules);
+            }
+            // Launched by a notification action: run it, and quit unless it
+            // opens a todo.
+            match notifications::activation::action_from_args(std::env::args()) {
+                Ok(Some(action))
+                    if notifications::activation::run_at_startup(
+                        app.handle(),
+                        &settings.validation_rules,
+                        action,
+                    ) =>
+                {
+                    return Ok(());
+                }
+                Ok(_) => {}
+                Err(e) => trace::log(e),
+            }
+            read_only::announce_at_startup(app.handle());
+            storage::recovery::announce_at_startup(app.handle());
+            storage::detect_legacy_data_at_startup(app.handle());
+            app.state::<storage::Storage>()
+                .set_max_todos(settings.quota.hard_todos);
+            if let Err(e) = local_api::start(app.handle(), &settings.local_api) {
+                trace::log(e);
+            }
+            app.manage(scheduler::SchedulerState::new(
+                settings
+                    .scheduler
+                    .interval_secs
+                    .map(std::time::Duration::from_secs),
+            ));
+            let reactions = settings::SettingsReactions::new(settings.clone());
+            // Read from SettingsState whenever they're needed.
+            reactions.register_live(&[
+                "block_spawn_during_focus",
+                "safety",
+                "allow_plugins",
+                "enabled_plugins",
+                "file_access",
+                "import",
+                "activity",
+                "notifications",
+                "capture_child_output",
+                "server_url",
+                "sync_scope",
+                "validation_rules",
+            ]);
+            reactions.register("trace", &["log_level"], |_, new| {
+                trace::set_level(new.log_level)
+            });
+            local_api::register_settings_reactions(app.handle(), &reactions);
+            scheduler::register_settings_reactions(app.handle(), &reactions);
+            quota::register_settings_reactions(app.handle(), &reactions);
+            privacy::register_settings_reactions(app.handle(), &reactions);
+            trust::register_settings_reactions(app.handle(), &reactions);
+            developer::register_settings_reactions(app.handle(), &reactions);
+            privacy::register_toggle_shortcut(app.handle(), &settings);
+            app.manage(reactions);
+            app.manage(settings::SettingsState::new(settings));
+            let commands = app.state::<command_registry::CommandRegistry>();
+            command_registry::register_window_commands(&commands);
+            focus::register_commands(&commands);
+            privacy::register_commands(&commands);
+            self_check::register_commands(&commands);
+            shutdown::register_commands(&commands);
+            // Registered first, so the keybindings check sees every command.
+            self_check::spawn_startup_check(app.handle().clone());
+            // All of these write to the store.
+            if !read_only {
+                sync::resume_subscriptions(app.handle());
+                calendar::resume_at_startup(app.handle());
+            }
+            language::spawn_backfill(app.handle());
+            let registry = app.state::<capabilities::CapabilityRegistry>();
+            local_api::register_capabilities(app.handle(), &registry);
+            bulk_edit::register_capabilities(&registry);
+            platform::register_capabilities(&registry);
+            calendar::register_capabilities(&registry);
+            notifications::activation::register_capabilities(&registry);
+            protocol::register_capabilities(app.handle(), &registry);
+            trust::register_capabilities(app.handle(), &registry);
+            developer::register_capabilities(app.handle(), &registry);
+            scheduler::spawn(app.handle().clone());
+            notifications::start_lock_watcher(app.handle().clone());
+            plugins::load_at_startup(app.handle());
+            trust::announce_blocked(app.handle());
+            shutdown::register_app_hooks(app.handle());
+            Ok(())
+        })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::CloseRequested { api, .. } => {
+                let app = window.app_handle();
+                // Closing the last window quits, so it goes through the hooks.
+                let last = app.webview_windows().len() <= 1;
+                if last && !app.state::<shutdown::Shutdown>().started() {
+                    api.prevent_close();
+                    shutdown::quit(app);
+                } else {
+                    storage::drafts::flush_all(app);
+                }
+            }
+            tauri::WindowEvent::Destroyed => {
+                window
+                    .state::<live_query::LiveQueries>()
+                    .drop_window(window.label());
+            }
+            _ => {}
+        })
+        .invoke_handler(trace::wrap(shutdown::guard(read_only::guard(invoke_handler()))))
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| match event {
+            // Already done if the app quit through `shutdown::quit`.
+            tauri::RunEvent::Exit => shutdown::run(app),
+            // Finder delivers opened documents as an event rather than argv.
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::Opened { urls } => {
+                let files = urls
+                    .into_iter()
+                    .filter_map(|url| url.to_file_path().ok())
+                    .filter(|path| file_assoc::is_todos_file(path));
+                app.state::<file_assoc::OpenedFiles>().push(files);
+                let _ = events::emit(app, "files-opened", ());
+            }
+            _ => {}
+        });
 }
@@ -0,0 +1,388 @@
+//! Live query subscriptions. A window subscribes to a filtered, sorted page
+//! of todos once, and after each mutation receives a `query-delta` with only
+//! what changed on that page, instead of refetching the whole list.
+//!
+//! Each subscription keeps every matching todo in order, so a mutation only
+//! costs evaluating the query against the mutated rows. A delta turns the
+//! old page into the new one: drop `removed`, patch `updated`, then insert
+//! `added` at their positions in ascending order. That is exact unless
+//! `positions_invalidated` is set, meaning todos that stayed on the page
+//! were reordered; the window then rereads the page with
+//! `get_query_page`.
+//!
+//! Subscriptions only ever hold todos as [`PrivacyMode`] lets windows see
+//! them, and queries match against that redacted copy, so a hidden
+//! description can't be probed for with a text query either.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State, WebviewWindow};
+
+use crate::calendar;
+use crate::clock::{Clock, ClockState};
+use crate::events;
+use crate::privacy::PrivacyMode;
+use crate::protocol::EventPayload;
+use crate::query::{self, QueryAst, TodoOrder};
+use crate::storage::Storage;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+/// What a window subscribes to; the same options as `query_todos` plus a page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryParams {
+    pub query: Option<String>,
+    pub sort_by: Option<String>,
+    #[serde(default)]
+    pub descending: bool,
+    #[serde(default)]
+    pub offset: usize,
+    /// Page size; `None` subscribes to everything from `offset` on.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionedTodo {
+    /// Index within the page after the change.
+    pub position: usize,
+    pub todo: Todo,
+}
+
+/// Payload of `query-delta`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryDelta {
+    pub subscription_id: String,
+    pub added: Vec<PositionedTodo>,
+    pub removed: Vec<String>,
+    pub updated: Vec<PositionedTodo>,
+    pub positions_invalidated: bool,
+    /// Matches across all pages.
+    pub total: usize,
+}
+
+impl EventPayload for QueryDelta {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryPage {
+    pub subscription_id: String,
+    pub todos: Vec<Todo>,
+    pub total: usize,
+}
+
+struct Subscription {
+    window: String,
+    filter: Option<QueryAst>,
+    order: TodoOrder,
+    offset: usize,
+    limit: Option<usize>,
+    /// Every matching todo, sorted by `order`.
+    matches: Vec<Todo>,
+}
+
+impl Subscription {
+    fn new(window: &str, params: &QueryParams) -> Result<Self, String> {
+        let filter = params
+            .query
+            .as_deref()
+            .filter(|q| !q.trim().is_empty())
+            .map(query::parse_query)
+            .transpose()
+            .map_err(|e| format!("Syntax error: {}", e))?;
+        Ok(Self {
+            window: window.to_string(),
+            filter,
+            order: TodoOrder::new(params.sort_by.as_deref(), params.descending)?,
+            offset: params.offset,
+            limit: params.limit,
+            matches: Vec::new(),
+        })
+    }
+
+    fn accepts(&self, todo: &Todo, today: NaiveDate) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| query::matches(filter, todo, today))
+    }
+
+    fn reset(&mut self, todos: &[Todo], today: NaiveDate, privacy: &PrivacyMode) {
+        self.matches = todos
+            .iter()
+            .filter_map(|todo| privacy.redact(todo))
+            .filter(|todo| self.accepts(todo, today))
+            .collect();
+        let order = &self.order;
+        self.matches.sort_by(|a, b| order.cmp(a, b));
+    }
+
+    fn upsert(&mut self, todo: &Todo, today: NaiveDate, privacy: &PrivacyMode) {
+        if let Some(index) = self.matches.iter().position(|t| t.id == todo.id) {
+            self.matches.remove(index);
+        }
+        let Some(todo) = privacy.redact(todo) else {
+            return;
+        };
+        if self.accepts(&todo, today) {
+            let order = &self.order;
+            let index = self
+                .matches
+                .partition_point(|t| order.cmp(t, &todo).is_lt());
+            self.matches.insert(index, todo);
+        }
+    }
+
+    fn page(&self) -> &[Todo] {
+        let start = self.offset.min(self.matches.len());
+        let end = self.limit.map_or(self.matches.len(), |limit| {
+            (start + limit).min(self.matches.len())
+        });
+        &self.matches[start..end]
+    }
+}
+
+/// Describes how `old` became `new`, or `None` if the page and total are
+/// unchanged.
+fn diff_pages(
+    subscription_id: &str,
+    old: &[Todo],
+    old_total: usize,
+    new: &[Todo],
+    new_total: usize,
+) -> Option<QueryDelta> {
+    let old_by_id: HashMap<&str, &Todo> = old.iter().map(|t| (t.id.as_str(), t)).collect();
+    let new_ids: HashMap<&str, usize> = new
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.id.as_str(), i))
+        .collect();
+
+    let removed: Vec<String> = old
+        .iter()
+        .filter(|t| !new_ids.contains_key(t.id.as_str()))
+        .map(|t| t.id.clone())
+        .collect();
+    let mut added = Vec::new();
+    let mut updated = Vec::new();
+    for (position, todo) in new.iter().enumerate() {
+        let positioned = || PositionedTodo {
+            position,
+            todo: todo.clone(),
+        };
+        match old_by_id.get(todo.id.as_str()) {
+            None => added.push(positioned()),
+            Some(before) if *before != todo => updated.push(positioned()),
+            Some(_) => {}
+        }
+    }
+    let kept_old: Vec<&str> = old
+        .iter()
+        .map(|t| t.id.as_str())
+        .filter(|id| new_ids.contains_key(id))
+        .collect();
+    let kept_new: Vec<&str> = new
+        .iter()
+        .map(|t| t.id.as_str())
+        .filter(|id| old_by_id.contains_key(id))
+        .collect();
+    let positions_invalidated = kept_old != kept_new;
+
+    let unchanged = added.is_empty()
+        && removed.is_empty()
+        && updated.is_empty()
+        && !positions_invalidated
+        && old_total == new_total;
+    (!unchanged).then(|| QueryDelta {
+        subscription_id: subscription_id.to_string(),
+        added,
+        removed,
+        updated,
+        positions_invalidated,
+        total: new_total,
+    })
+}
+
+#[derive(Default)]
+pub struct LiveQueries {
+    subscriptions: Mutex<HashMap<String, Subscription>>,
+    /// Copy of [`PrivacyState`](crate::privacy::PrivacyState), changed only
+    /// through [`set_privacy`](Self::set_privacy) so every subscription
+    /// follows it.
+    privacy: Mutex<PrivacyMode>,
+}
+
+impl LiveQueries {
+    fn subscriptions(&self) -> std::sync::MutexGuard<'_, HashMap<String, Subscription>> {
+        self.subscriptions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn privacy(&self) -> PrivacyMode {
+        self.privacy
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn subscribe(
+        &self,
+        window: &str,
+        params: &QueryParams,
+        todos: &[Todo],
+        today: NaiveDate,
+    ) -> Result<QueryPage, String> {
+        let mut subscription = Subscription::new(window, params)?;
+        subscription.reset(todos, today, &self.privacy());
+        let page = QueryPage {
+            subscription_id: uuid::Uuid::new_v4().to_string(),
+            todos: subscription.page().to_vec(),
+            total: subscription.matches.len(),
+        };
+        self.subscriptions()
+            .insert(page.subscription_id.clone(), subscription);
+        Ok(page)
+    }
+
+    pub fn unsubscribe(&self, id: &str) -> bool {
+        self.subscriptions().remove(id).is_some()
+    }
+
+    /// Drops every subscription of a closed window.
+    pub fn drop_window(&self, window: &str) {
+        self.subscriptions().retain(|_, s| s.window != window);
+    }
+
+    pub fn page(&self, id: &str) -> Option<QueryPage> {
+        self.subscriptions().get(id).map(|s| QueryPage {
+            subscription_id: id.to_string(),
+            todos: s.page().to_vec(),
+            total: s.matches.len(),
+        })
+    }
+
+    /// Applies `update` to every subscription and returns the non-empty
+    /// deltas with the window each belongs to.
+    fn update(
+        &self,
+        mut update: impl FnMut(&mut Subscription, &PrivacyMode),
+    ) -> Vec<(String, QueryDelta)> {
+        let privacy = self.privacy();
+        let mut deltas = Vec::new();
+        for (id, subscription) in self.subscriptions().iter_mut() {
+            let old_page = subscription.page().to_vec();
+            let old_total = subscription.matches.len();
+            update(subscription, &privacy);
+            if let Some(delta) = diff_pages(
+                id,
+                &old_page,
+                old_total,
+                subscription.page(),
+                subscription.matches.len(),
+            ) {
+                deltas.push((subscription.window.clone(), delta));
+            }
+        }
+        deltas
+    }
+
+    /// Incremental path for todos that were created or updated; deletions
+    /// always come with a [`reset`](Self::reset).
+    pub fn apply(&self, upserted: &[Todo], today: NaiveDate) -> Vec<(String, QueryDelta)> {
+        self.update(|subscription, privacy| {
+            for todo in upserted {
+                subscription.upsert(todo, today, privacy);
+            }
+        })
+    }
+
+    /// Recomputes from the whole cache, for bulk changes like a sync or import.
+    pub fn reset(&self, todos: &[Todo], today: NaiveDate) -> Vec<(String, QueryDelta)> {
+        self.update(|subscription, privacy| subscription.reset(todos, today, privacy))
+    }
+
+    /// Switches privacy mode and recomputes every subscription under it.
+    pub fn set_privacy(
+        &self,
+        mode: &PrivacyMode,
+        todos: &[Todo],
+        today: NaiveDate,
+    ) -> Vec<(String, QueryDelta)> {
+        *self.privacy.lock().unwrap_or_else(|e| e.into_inner()) = mode.clone();
+        self.reset(todos, today)
+    }
+}
+
+fn emit_deltas(app: &AppHandle, deltas: Vec<(String, QueryDelta)>) {
+    for (window, delta) in deltas {
+        let _ = events::emit_to(app, &window, "query-delta", &delta);
+    }
+}
+
+/// Tells subscribers about created or updated todos. The calendar sync
+/// hears about every cache change through here as well.
+pub fn notify_upserted(app: &AppHandle, upserted: &[Todo]) {
+    calendar::schedule(app);
+    if let Some(live) = app.try_state::<LiveQueries>() {
+        emit_deltas(app, live.apply(upserted, app.state::<ClockState>().today()));
+    }
+}
+
+/// Tells subscribers the whole cache may have changed.
+pub fn notify_reset(app: &AppHandle, todos: &[Todo]) {
+    calendar::schedule(app);
+    if let Some(live) = app.try_state::<LiveQueries>() {
+        emit_deltas(app, live.reset(todos, app.state::<ClockState>().today()));
+    }
+}
+
+/// Re-filters every subscription after privacy mode changed.
+pub fn notify_privacy_changed(
+    app: &AppHandle,
+    mode: &PrivacyMode,
+    todos: &[Todo],
+    today: NaiveDate,
+) {
+    if let Some(live) = app.try_state::<LiveQueries>() {
+        emit_deltas(app, live.set_privacy(mode, todos, today));
+    }
+}
+
+/// Subscribes the calling window and returns the first page.
+#[tauri::command]
+pub fn subscribe_query(
+    query_params: QueryParams,
+    window: WebviewWindow,
+    storage: State<'_, Storage>,
+    live: State<'_, LiveQueries>,
+) -> Result<QueryPage, String> {
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    live.subscribe(
+        window.label(),
+        &query_params,
+        &todos,
+        storage.clock().today(),
+    )
+}
+
+#[tauri::command]
+pub fn unsubscribe_query(subscription_id: String, live: State<'_, LiveQueries>) -> bool {
+    live.unsubscribe(&subscription_id)
+}
+
+#[tauri::command]
+pub fn get_query_page(
+    subscription_id: String,
+    live: State<'_, LiveQueries>,
+) -> Result<QueryPage, String> {
+    live.page(&subscription_id)
+        .ok_or_else(|| format!("Unknown query subscription '{}'", subscription_id))
+}
@@ -0,0 +1,312 @@
+use super::*;
+use chrono::{Duration, TimeZone, Utc};
+
+use crate::types::Priority;
+
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 10).unwrap()
+}
+
+fn todo(n: usize, priority: Priority, tags: &[&str]) -> Todo {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap() + Duration::minutes(n as i64);
+    Todo {
+        priority,
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Todo::sample(format!("t{:03}", n), format!("Todo {}", n), created)
+    }
+}
+
+fn params(query: &str, sort_by: Option<&str>, offset: usize, limit: Option<usize>) -> QueryParams {
+    QueryParams {
+        query: Some(query.to_string()),
+        sort_by: sort_by.map(str::to_string),
+        descending: false,
+        offset,
+        limit,
+    }
+}
+
+/// What `query_todos` would return for the page, computed from scratch.
+fn oracle(todos: &[Todo], params: &QueryParams) -> Vec<Todo> {
+    let mut todos = todos.to_vec();
+    todos.sort_by(query::cmp_list_order);
+    let mut todos = match params.query.as_deref().filter(|q| !q.trim().is_empty()) {
+        Some(q) => query::filter_todos(q, todos, today()).unwrap(),
+        None => todos,
+    };
+    if let Some(sort_by) = &params.sort_by {
+        query::sort_todos(&mut todos, sort_by, params.descending).unwrap();
+    }
+    todos
+        .into_iter()
+        .skip(params.offset)
+        .take(params.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Applies a delta the way the frontend does.
+fn apply_delta(page: &mut Vec<Todo>, delta: &QueryDelta) {
+    page.retain(|t| !delta.removed.contains(&t.id));
+    for updated in &delta.updated {
+        let todo = page.iter_mut().find(|t| t.id == updated.todo.id).unwrap();
+        *todo = updated.todo.clone();
+    }
+    for added in &delta.added {
+        page.insert(added.position, added.todo.clone());
+    }
+}
+
+fn ids(todos: &[Todo]) -> Vec<&str> {
+    todos.iter().map(|t| t.id.as_str()).collect()
+}
+
+#[test]
+fn test_subscribe_returns_first_page() {
+    let todos: Vec<Todo> = (0..6)
+        .map(|n| {
+            todo(
+                n,
+                Priority::Medium,
+                if n % 2 == 0 { &["work"] } else { &[] },
+            )
+        })
+        .collect();
+    let live = LiveQueries::default();
+
+    let page = live
+        .subscribe(
+            "main",
+            &params("tag:work", None, 1, Some(1)),
+            &todos,
+            today(),
+        )
+        .unwrap();
+
+    assert_eq!(ids(&page.todos), vec!["t002"]);
+    assert_eq!(page.total, 3);
+}
+
+#[test]
+fn test_invalid_query_or_sort_is_rejected() {
+    let live = LiveQueries::default();
+    assert!(live
+        .subscribe("main", &params("tag:", None, 0, None), &[], today())
+        .is_err());
+    assert!(live
+        .subscribe("main", &params("tag:x", Some("tag"), 0, None), &[], today())
+        .is_err());
+}
+
+#[test]
+fn test_update_outside_the_filter_emits_nothing() {
+    let mut todos: Vec<Todo> = (0..4).map(|n| todo(n, Priority::Low, &[])).collect();
+    todos[0].tags = vec!["work".to_string()];
+    let live = LiveQueries::default();
+    live.subscribe("main", &params("tag:work", None, 0, None), &todos, today())
+        .unwrap();
+
+    todos[1].title = "Renamed".to_string();
+    assert!(live.apply(&todos[1..2], today()).is_empty());
+}
+
+#[test]
+fn test_update_entering_the_page_is_added_at_its_position() {
+    let mut todos: Vec<Todo> = (0..4).map(|n| todo(n, Priority::Low, &[])).collect();
+    let live = LiveQueries::default();
+    let page = live
+        .subscribe(
+            "main",
+            &params("priority>=medium", None, 0, None),
+            &todos,
+            today(),
+        )
+        .unwrap();
+    assert!(page.todos.is_empty());
+
+    todos[2].priority = Priority::High;
+    let deltas = live.apply(&todos[2..3], today());
+
+    assert_eq!(deltas.len(), 1);
+    let (window, delta) = &deltas[0];
+    assert_eq!(window, "main");
+    assert_eq!(delta.added[0].position, 0);
+    assert_eq!(delta.added[0].todo.id, "t002");
+    assert_eq!(delta.total, 1);
+    assert!(!delta.positions_invalidated);
+}
+
+#[test]
+fn test_reordering_within_the_page_invalidates_positions() {
+    let mut todos = vec![
+        todo(0, Priority::High, &[]),
+        todo(1, Priority::Medium, &[]),
+        todo(2, Priority::Low, &[]),
+    ];
+    let mut sorted = params("", Some("priority"), 0, None);
+    sorted.query = None;
+    sorted.descending = true;
+    let live = LiveQueries::default();
+    live.subscribe("main", &sorted, &todos, today()).unwrap();
+
+    todos[2].priority = Priority::High;
+    let (_, delta) = live.apply(&todos[2..3], today()).remove(0);
+
+    assert!(delta.positions_invalidated);
+    assert_eq!(delta.updated[0].position, 1);
+    let page = live.page(&delta.subscription_id).unwrap();
+    assert_eq!(ids(&page.todos), vec!["t000", "t002", "t001"]);
+}
+
+#[test]
+fn test_drop_window_removes_its_subscriptions() {
+    let live = LiveQueries::default();
+    let a = live
+        .subscribe("a", &QueryParams::default(), &[], today())
+        .unwrap();
+    let b = live
+        .subscribe("b", &QueryParams::default(), &[], today())
+        .unwrap();
+
+    live.drop_window("a");
+
+    assert!(live.page(&a.subscription_id).is_none());
+    assert!(live.page(&b.subscription_id).is_some());
+    assert!(live.unsubscribe(&b.subscription_id));
+    assert!(!live.unsubscribe(&b.subscription_id));
+}
+
+/// Deterministic pseudo-random numbers, so failures are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+#[test]
+fn test_deltas_match_brute_force_requery() {
+    let priorities = [Priority::Low, Priority::Medium, Priority::High];
+    let cases = [
+        params("priority>=medium", Some("priority"), 2, Some(5)),
+        params("tag:work OR priority:high", Some("due"), 0, Some(4)),
+        params("NOT completed:true", Some("title"), 3, None),
+        params("", None, 5, Some(6)),
+    ];
+
+    for (case, subscribed) in cases.iter().enumerate() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15 + case as u64);
+        let mut todos: Vec<Todo> = (0..30)
+            .map(|n| {
+                todo(
+                    n,
+                    priorities[n % 3],
+                    if n % 4 == 0 { &["work"] } else { &[] },
+                )
+            })
+            .collect();
+        let live = LiveQueries::default();
+        let first = live.subscribe("main", subscribed, &todos, today()).unwrap();
+        let mut client_page = first.todos;
+        let mut next_id = todos.len();
+
+        for step in 0..300 {
+            let deltas = match rng.below(10) {
+                // Deletions reach live queries as a reset.
+                0 => {
+                    let index = rng.below(todos.len());
+                    todos.remove(index);
+                    live.reset(&todos, today())
+                }
+                1 => {
+                    let new = todo(next_id, priorities[rng.below(3)], &[]);
+                    next_id += 1;
+                    todos.push(new);
+                    live.apply(&todos[todos.len() - 1..], today())
+                }
+                _ => {
+                    let index = rng.below(todos.len());
+                    let todo = &mut todos[index];
+                    match rng.below(5) {
+                        0 => todo.priority = priorities[rng.below(3)],
+                        1 => todo.completed = !todo.completed,
+                        2 => todo.title = format!("Todo {}", rng.below(100)),
+                        3 => {
+                            todo.scheduled_for = Some(
+                                Utc.with_ymd_and_hms(2024, 6, 1 + rng.below(20) as u32, 0, 0, 0)
+                                    .unwrap(),
+                            )
+                        }
+                        _ => todo.tags = vec!["work".to_string()],
+                    }
+                    live.apply(std::slice::from_ref(&todos[index]), today())
+                }
+            };
+
+            let expected = oracle(&todos, subscribed);
+            for (_, delta) in &deltas {
+                apply_delta(&mut client_page, delta);
+                if delta.positions_invalidated {
+                    let mut sorted = client_page.clone();
+                    sorted.sort_by_key(|t| expected.iter().position(|e| e.id == t.id));
+                    assert_eq!(sorted, expected, "case {} step {}", case, step);
+                    client_page = live.page(&first.subscription_id).unwrap().todos;
+                }
+            }
+            assert_eq!(
+                client_page, expected,
+                "case {} step {}: delta applied to the old page differs from a re-query",
+                case, step
+            );
+            assert_eq!(live.page(&first.subscription_id).unwrap().todos, expected);
+        }
+    }
+}
+
+#[test]
+fn test_privacy_mode_removes_and_masks_subscribed_todos() {
+    let mut todos = vec![
+        todo(1, Priority::High, &["work"]),
+        todo(2, Priority::High, &["Private"]),
+        todo(3, Priority::High, &[]),
+    ];
+    todos[0].description = Some("salary review".to_string());
+    todos[2].completed = true;
+    let live = LiveQueries::default();
+    let page = live
+        .subscribe("main", &params("", None, 0, None), &todos, today())
+        .unwrap();
+    assert_eq!(page.total, 3);
+
+    let on = PrivacyMode {
+        enabled: true,
+        ..Default::default()
+    };
+    let deltas = live.set_privacy(&on, &todos, today());
+    let (_, delta) = &deltas[0];
+    assert_eq!(delta.removed, ["t002", "t003"]);
+    assert_eq!(delta.updated[0].todo.description.as_deref(), Some("•••"));
+
+    // A hidden description can't be found by searching for it.
+    let probe = live
+        .subscribe(
+            "main",
+            &params("text:salary", None, 0, None),
+            &todos,
+            today(),
+        )
+        .unwrap();
+    assert_eq!(probe.total, 0);
+    live.unsubscribe(&probe.subscription_id);
+
+    // Todos becoming private later leave the page too.
+    todos[0].tags.push("private".to_string());
+    let deltas = live.apply(&todos[..1], today());
+    assert_eq!(deltas[0].1.removed, ["t001"]);
+
+    let deltas = live.set_privacy(&PrivacyMode::default(), &todos, today());
+    assert_eq!(deltas[0].1.total, 3);
+}
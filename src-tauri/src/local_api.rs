@@ -0,0 +1,547 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::audit;
+use crate::capabilities::{self, Capability, CapabilityRegistry};
+use crate::events;
+use crate::language;
+use crate::query;
+use crate::quota;
+use crate::search::SearchIndex;
+use crate::settings::{LocalApiSettings, SettingsReactions, SettingsState};
+use crate::storage::{self, Storage, UpdateError};
+use crate::trace;
+use crate::trust::{self, Feature};
+use crate::types::{Priority, Todo};
+use crate::validation_rules::{self, RuleSet, ValidationRule, Violation};
+
+#[cfg(test)]
+mod tests;
+
+pub const KEYCHAIN_SERVICE: &str = "YuToDo";
+const KEYCHAIN_TOKEN_ENTRY: &str = "local-api-token";
+/// Entry that is looked up, never written, to see whether the keychain answers.
+const KEYCHAIN_PROBE_ENTRY: &str = "capability-probe";
+const AUDIT_SOURCE: &str = "local-api";
+/// Requests with a larger body are rejected with 413.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalApiInfo {
+    pub enabled: bool,
+    pub url: Option<String>,
+    /// Ready-to-paste request against the running API, token included.
+    pub curl_example: Option<String>,
+}
+
+/// Body of `POST /todos`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewTodo {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    scheduled_for: Option<DateTime<Utc>>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Body of a created todo: the todo plus the rules it breaks with `warn`.
+#[derive(Serialize)]
+struct CreatedTodo<'a> {
+    #[serde(flatten)]
+    todo: &'a Todo,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<Violation>,
+}
+
+/// An HTTP request reduced to what the router needs.
+struct ApiRequest<'a> {
+    method: &'a str,
+    /// Path plus query string, e.g. `/todos?filter=tag:work`.
+    target: &'a str,
+    authorization: Option<&'a str>,
+    body: &'a [u8],
+}
+
+struct ApiResponse {
+    status: u16,
+    body: String,
+    /// Event to emit so the frontend pushes a local change on to the server.
+    event: Option<(&'static str, Todo)>,
+}
+
+impl ApiResponse {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Self {
+            status,
+            body: serde_json::to_string(value).unwrap_or_default(),
+            event: None,
+        }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self::json(status, &serde_json::json!({ "error": message.to_string() }))
+    }
+}
+
+fn token_matches(provided: &str, expected: &str) -> bool {
+    // Compare in constant time so response timing doesn't leak the token.
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn handle(
+    storage: &Storage,
+    index: &SearchIndex,
+    rules: &[ValidationRule],
+    token: &str,
+    request: &ApiRequest,
+) -> ApiResponse {
+    let authorized = request
+        .authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| token_matches(provided.trim(), token));
+    if !authorized {
+        return ApiResponse::error(401, "Missing or invalid bearer token");
+    }
+
+    let Ok(url) = url::Url::parse(&format!("http://127.0.0.1{}", request.target)) else {
+        return ApiResponse::error(400, "Malformed request target");
+    };
+    let segments: Vec<&str> = url.path().trim_matches('/').split('/').collect();
+    let result = match (request.method, segments.as_slice()) {
+        ("GET", ["todos"]) => {
+            let filter = url
+                .query_pairs()
+                .find(|(key, _)| key == "filter")
+                .map(|(_, value)| value.into_owned());
+            list_todos(storage, filter.as_deref())
+        }
+        ("POST", ["todos"]) => create_todo(storage, index, rules, request.body),
+        ("POST", ["todos", id, "complete"]) => complete_todo(storage, index, rules, id),
+        ("GET", ["agenda"]) => agenda(storage),
+        (_, ["todos"] | ["todos", _, "complete"] | ["agenda"]) => {
+            Ok(ApiResponse::error(405, "Method not allowed"))
+        }
+        _ => Ok(ApiResponse::error(404, "Not found")),
+    };
+    result.unwrap_or_else(|e| ApiResponse::error(500, format!("Storage error: {}", e)))
+}
+
+fn list_todos(storage: &Storage, filter: Option<&str>) -> rusqlite::Result<ApiResponse> {
+    let todos = storage.list_todos()?;
+    let today = storage.clock().today();
+    Ok(match filter.filter(|f| !f.trim().is_empty()) {
+        Some(filter) => match query::filter_todos(filter, todos, today) {
+            Ok(todos) => ApiResponse::json(200, &todos),
+            Err(e) => ApiResponse::error(400, format!("Syntax error: {}", e)),
+        },
+        None => ApiResponse::json(200, &todos),
+    })
+}
+
+fn create_todo(
+    storage: &Storage,
+    index: &SearchIndex,
+    rules: &[ValidationRule],
+    body: &[u8],
+) -> rusqlite::Result<ApiResponse> {
+    let new: NewTodo = match serde_json::from_slice(body) {
+        Ok(new) => new,
+        Err(e) => return Ok(ApiResponse::error(400, format!("Invalid todo: {}", e))),
+    };
+    let title = new.title.trim();
+    if title.is_empty() {
+        return Ok(ApiResponse::error(
+            400,
+            "Invalid todo: title must not be empty",
+        ));
+    }
+
+    if let Err(e) = quota::check_todos(storage, quota::count_todos(&storage.conn())? + 1) {
+        return Ok(ApiResponse::error(507, e.to_string()));
+    }
+
+    let now = storage.clock().now();
+    let mut todo = Todo {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: title.to_string(),
+        description: new.description,
+        completed: false,
+        priority: new.priority,
+        scheduled_for: new.scheduled_for,
+        created_at: now,
+        updated_at: now,
+        completed_at: None,
+        order: None,
+        rank: None,
+        schedule_id: None,
+        tags: new.tags,
+        languages: Default::default(),
+        fields: Default::default(),
+        estimate_minutes: None,
+        actual_minutes: None,
+        revision: 0,
+    };
+    let rules = match RuleSet::load(storage, rules) {
+        Ok(rules) => rules,
+        Err(e) => return Ok(ApiResponse::error(500, e)),
+    };
+    let warnings = rules.check(&todo);
+    if validation_rules::is_blocking(&warnings) {
+        return Ok(rules_broken(&warnings));
+    }
+    todo.revision = storage.save_todo(&todo)?;
+    language::detect_now(&storage.conn(), &todo.id)?;
+    // Read back the order key and languages the cache gave it.
+    let todo = storage.get_todo(&todo.id)?.unwrap_or(todo);
+    index.rebuild(&storage.list_todos()?);
+    validation_rules::note_warnings(storage, &warnings);
+
+    let mut response = ApiResponse::json(
+        201,
+        &CreatedTodo {
+            todo: &todo,
+            warnings,
+        },
+    );
+    response.event = Some(("local-api-todo-created", todo));
+    Ok(response)
+}
+
+/// 422 with the rules a write breaks, as `update_todo` would reject it.
+fn rules_broken(violations: &[Violation]) -> ApiResponse {
+    ApiResponse::json(
+        422,
+        &serde_json::json!({
+            "error": format!(
+                "Todo breaks validation rules: {}",
+                validation_rules::describe(violations)
+            ),
+            "violations": violations,
+        }),
+    )
+}
+
+fn complete_todo(
+    storage: &Storage,
+    index: &SearchIndex,
+    rules: &[ValidationRule],
+    id: &str,
+) -> rusqlite::Result<ApiResponse> {
+    let Some(mut todo) = storage.get_todo(id)? else {
+        return Ok(ApiResponse::error(404, format!("Unknown todo '{}'", id)));
+    };
+    let rules = match RuleSet::load(storage, rules) {
+        Ok(rules) => rules,
+        Err(e) => return Ok(ApiResponse::error(500, e)),
+    };
+    todo.completed = true;
+    todo.updated_at = storage.clock().now();
+    let update = match storage::apply_checked_update(storage, todo.clone(), None, &rules) {
+        Ok(Some(update)) => update,
+        // Already completed.
+        Ok(None) => return Ok(ApiResponse::json(200, &todo)),
+        Err(UpdateError::ValidationFailed { violations }) => return Ok(rules_broken(&violations)),
+        Err(e) => return Ok(ApiResponse::error(500, e.to_string())),
+    };
+    index.rebuild(&storage.list_todos()?);
+    validation_rules::note_warnings(storage, &update.warnings);
+
+    let mut response = ApiResponse::json(200, &update.todo);
+    response.event = Some(("local-api-todo-updated", update.todo));
+    Ok(response)
+}
+
+/// Incomplete todos due today or earlier, soonest first.
+pub fn agenda_for(todos: Vec<Todo>, today: NaiveDate) -> Vec<Todo> {
+    let mut due: Vec<Todo> = todos
+        .into_iter()
+        .filter(|t| !t.completed && t.scheduled_for.is_some_and(|d| d.date_naive() <= today))
+        .collect();
+    due.sort_by_key(|t| t.scheduled_for);
+    due
+}
+
+fn agenda(storage: &Storage) -> rusqlite::Result<ApiResponse> {
+    let todos = agenda_for(storage.list_todos()?, storage.clock().today());
+    Ok(ApiResponse::json(200, &todos))
+}
+
+/// Reads at most `MAX_BODY_BYTES`, or `None` when the body is larger.
+fn read_body(request: &mut Request) -> Option<Vec<u8>> {
+    if request
+        .body_length()
+        .is_some_and(|len| len > MAX_BODY_BYTES)
+    {
+        return None;
+    }
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES as u64 + 1)
+        .read_to_end(&mut body)
+        .ok()?;
+    (body.len() <= MAX_BODY_BYTES).then_some(body)
+}
+
+fn respond(
+    mut request: Request,
+    storage: &Storage,
+    index: &SearchIndex,
+    rules: &[ValidationRule],
+    token: &str,
+) -> Option<(&'static str, Todo)> {
+    let method = request.method().to_string();
+    let target = request.url().to_string();
+    let authorization = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .map(|h| h.value.as_str().to_string());
+
+    let response = match read_body(&mut request) {
+        Some(body) => handle(
+            storage,
+            index,
+            rules,
+            token,
+            &ApiRequest {
+                method: &method,
+                target: &target,
+                authorization: authorization.as_deref(),
+                body: &body,
+            },
+        ),
+        None => ApiResponse::error(413, "Request body too large"),
+    };
+
+    // Query strings may contain todo text, so only the path is logged.
+    let path = target.split('?').next().unwrap_or_default();
+    let _ = audit::record(
+        storage,
+        AUDIT_SOURCE,
+        &format!("{} {}", method, path),
+        &response.status.to_string(),
+    );
+
+    // No Access-Control-* headers: browsers must not be able to call the API.
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let http_response = Response::from_string(response.body)
+        .with_status_code(response.status)
+        .with_header(content_type);
+    let _ = request.respond(http_response);
+    response.event
+}
+
+/// Answers requests until the server is unblocked. `rules` is read for each
+/// request, so edited validation rules apply without a restart.
+fn serve(
+    server: &Server,
+    token: &str,
+    storage: &Storage,
+    index: &SearchIndex,
+    rules: impl Fn() -> Vec<ValidationRule>,
+    notify: impl Fn(&str, &Todo),
+) {
+    for request in server.incoming_requests() {
+        if let Some((event, todo)) = respond(request, storage, index, &rules(), token) {
+            notify(event, &todo);
+        }
+    }
+}
+
+/// Reads the API token from the OS keychain, creating it on first use.
+fn load_or_create_token() -> Result<String, String> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_TOKEN_ENTRY)
+        .map_err(|e| format!("Failed to open keychain: {}", e))?;
+    match entry.get_password() {
+        Ok(token) => Ok(token),
+        Err(keyring::Error::NoEntry) => {
+            let token = format!(
+                "{}{}",
+                uuid::Uuid::new_v4().simple(),
+                uuid::Uuid::new_v4().simple()
+            );
+            entry
+                .set_password(&token)
+                .map_err(|e| format!("Failed to store API token in keychain: {}", e))?;
+            Ok(token)
+        }
+        Err(e) => Err(format!("Failed to read API token from keychain: {}", e)),
+    }
+}
+
+/// Removes the API token from the keychain; the next start creates a new one.
+pub fn delete_token() -> Result<(), String> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_TOKEN_ENTRY)
+        .map_err(|e| format!("Failed to open keychain: {}", e))?;
+    match entry.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to remove API token: {}", e)),
+    }
+}
+
+fn probe_keychain() -> Capability {
+    let lookup =
+        keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_PROBE_ENTRY).and_then(|e| e.get_password());
+    match lookup {
+        Ok(_) | Err(keyring::Error::NoEntry) => Capability::Available,
+        Err(e) => Capability::Unsupported {
+            reason: e.to_string(),
+        },
+    }
+}
+
+/// Restarts the listener when `[backend.local_api]` changes.
+pub fn register_settings_reactions(app: &AppHandle, reactions: &SettingsReactions) {
+    let app = app.clone();
+    reactions.register("local_api", &["local_api"], move |_, new| {
+        app.state::<LocalApiState>().stop();
+        if let Err(e) = start(&app, &new.local_api) {
+            trace::log(e);
+        }
+        capabilities::reprobe(&app);
+    });
+}
+
+pub fn register_capabilities(app: &AppHandle, registry: &CapabilityRegistry) {
+    registry.register("secretService", probe_keychain);
+    let app = app.clone();
+    registry.register("localApi", move || {
+        let running = app
+            .state::<LocalApiState>()
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some();
+        let enabled = app
+            .try_state::<SettingsState>()
+            .is_some_and(|settings| settings.get().local_api.enabled);
+        match (running, enabled) {
+            (true, _) => Capability::Available,
+            (false, true) if !trust::allows(&app, Feature::LocalApi) => trust::untrusted(),
+            (false, true) => Capability::Degraded {
+                detail: "Enabled in settings but failed to start; see the log".to_string(),
+            },
+            (false, false) => Capability::Unsupported {
+                reason: "Disabled in settings".to_string(),
+            },
+        }
+    });
+}
+
+struct RunningApi {
+    server: Arc<Server>,
+    worker: JoinHandle<()>,
+    url: String,
+    token: String,
+}
+
+/// The local API server, when enabled.
+#[derive(Default)]
+pub struct LocalApiState {
+    running: Mutex<Option<RunningApi>>,
+}
+
+impl LocalApiState {
+    /// Stops accepting requests and waits for the one in flight to finish.
+    pub fn stop(&self) {
+        let running = self
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(api) = running {
+            api.server.unblock();
+            let _ = api.worker.join();
+        }
+    }
+}
+
+/// Starts the API if the settings enable it. Requires `Storage` and
+/// `SearchIndex` to be managed already.
+pub fn start(app: &AppHandle, settings: &LocalApiSettings) -> Result<(), String> {
+    if !settings.enabled {
+        return Ok(());
+    }
+    if !trust::allows(app, Feature::LocalApi) {
+        return Err("Local API held back: this profile isn't trusted yet".to_string());
+    }
+    let token = load_or_create_token()?;
+    let server = Server::http(("127.0.0.1", settings.port))
+        .map(Arc::new)
+        .map_err(|e| format!("Failed to start local API on port {}: {}", settings.port, e))?;
+    let url = format!("http://127.0.0.1:{}", settings.port);
+
+    let worker = {
+        let app = app.clone();
+        let server = server.clone();
+        let token = token.clone();
+        std::thread::spawn(move || {
+            let storage = app.state::<Storage>();
+            let index = app.state::<SearchIndex>();
+            let settings = app.state::<SettingsState>();
+            serve(
+                &server,
+                &token,
+                &storage,
+                &index,
+                || settings.get().validation_rules,
+                |event, todo| {
+                    let _ = events::emit(&app, event, todo);
+                    if event == "local-api-todo-created" {
+                        quota::notify(&app);
+                    }
+                },
+            );
+        })
+    };
+    let _ = audit::record(&app.state::<Storage>(), AUDIT_SOURCE, "start", &url);
+    *app.state::<LocalApiState>()
+        .running
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(RunningApi {
+        server,
+        worker,
+        url,
+        token,
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_local_api_info(state: State<'_, LocalApiState>) -> LocalApiInfo {
+    let running = state.running.lock().unwrap_or_else(|e| e.into_inner());
+    match running.as_ref() {
+        Some(api) => LocalApiInfo {
+            enabled: true,
+            url: Some(api.url.clone()),
+            curl_example: Some(format!(
+                "curl -H \"Authorization: Bearer {}\" {}/agenda",
+                api.token, api.url
+            )),
+        },
+        None => LocalApiInfo {
+            enabled: false,
+            url: None,
+            curl_example: None,
+        },
+    }
+}
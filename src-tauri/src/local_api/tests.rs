@@ -0,0 +1,226 @@
+use super::*;
+use chrono::{Duration, TimeZone};
+use std::io::Write;
+use std::net::TcpStream;
+
+const TOKEN: &str = "0123456789abcdef";
+
+fn todo(id: &str, title: &str, due: Option<DateTime<Utc>>) -> Todo {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo {
+        scheduled_for: due,
+        tags: vec!["work".to_string()],
+        ..Todo::sample(id, title, created)
+    }
+}
+
+fn call(storage: &Storage, method: &str, target: &str, body: &str) -> ApiResponse {
+    let authorization = format!("Bearer {}", TOKEN);
+    handle(
+        storage,
+        &SearchIndex::default(),
+        &[],
+        TOKEN,
+        &ApiRequest {
+            method,
+            target,
+            authorization: Some(&authorization),
+            body: body.as_bytes(),
+        },
+    )
+}
+
+fn titles(response: &ApiResponse) -> Vec<String> {
+    let todos: Vec<Todo> = serde_json::from_str(&response.body).unwrap();
+    todos.into_iter().map(|t| t.title).collect()
+}
+
+#[test]
+fn test_requests_without_valid_token_are_rejected() {
+    let storage = Storage::open_in_memory().unwrap();
+    for authorization in [None, Some("Bearer wrong"), Some(TOKEN)] {
+        let response = handle(
+            &storage,
+            &SearchIndex::default(),
+            &[],
+            TOKEN,
+            &ApiRequest {
+                method: "GET",
+                target: "/todos",
+                authorization,
+                body: b"",
+            },
+        );
+        assert_eq!(response.status, 401);
+    }
+}
+
+#[test]
+fn test_list_todos_applies_filter() {
+    let storage = Storage::open_in_memory().unwrap();
+    let mut done = todo("2", "Ship release", None);
+    done.completed = true;
+    storage
+        .replace_todos(&[todo("1", "Write notes", None), done])
+        .unwrap();
+
+    assert_eq!(titles(&call(&storage, "GET", "/todos", "")).len(), 2);
+    let response = call(&storage, "GET", "/todos?filter=completed%3Atrue", "");
+    assert_eq!(titles(&response), vec!["Ship release"]);
+    assert_eq!(
+        call(&storage, "GET", "/todos?filter=owner%3Ame", "").status,
+        400
+    );
+}
+
+#[test]
+fn test_create_and_complete_todo() {
+    let storage = Storage::open_in_memory().unwrap();
+    let response = call(
+        &storage,
+        "POST",
+        "/todos",
+        r#"{"title": " Buy milk ", "priority": "high"}"#,
+    );
+    assert_eq!(response.status, 201);
+    let created: Todo = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(created.title, "Buy milk");
+    assert_eq!(created.priority, Priority::High);
+    assert!(matches!(
+        response.event,
+        Some(("local-api-todo-created", _))
+    ));
+    assert_eq!(storage.list_todos().unwrap(), vec![created.clone()]);
+
+    let response = call(
+        &storage,
+        "POST",
+        &format!("/todos/{}/complete", created.id),
+        "",
+    );
+    assert_eq!(response.status, 200);
+    assert!(storage.list_todos().unwrap()[0].completed);
+
+    assert_eq!(
+        call(&storage, "POST", "/todos/missing/complete", "").status,
+        404
+    );
+    assert_eq!(
+        call(&storage, "POST", "/todos", r#"{"title": ""}"#).status,
+        400
+    );
+    assert_eq!(call(&storage, "POST", "/todos", "not json").status, 400);
+}
+
+#[test]
+fn test_complete_checks_validation_rules() {
+    let storage = Storage::open_in_memory().unwrap();
+    // Saved before the rule existed.
+    storage.save_todo(&todo("1", "Write notes", None)).unwrap();
+    let rules = [ValidationRule {
+        name: "work-due".to_string(),
+        list: None,
+        tag: Some("work".to_string()),
+        check: validation_rules::RuleCheck::Require {
+            field: validation_rules::RequiredField::DueDate,
+        },
+        severity: validation_rules::Severity::Block,
+    }];
+    let authorization = format!("Bearer {}", TOKEN);
+    let response = handle(
+        &storage,
+        &SearchIndex::default(),
+        &rules,
+        TOKEN,
+        &ApiRequest {
+            method: "POST",
+            target: "/todos/1/complete",
+            authorization: Some(&authorization),
+            body: b"",
+        },
+    );
+    assert_eq!(response.status, 422);
+    assert!(response.body.contains("work-due"), "{}", response.body);
+    assert!(response.event.is_none());
+    assert!(!storage.get_todo("1").unwrap().unwrap().completed);
+}
+
+#[test]
+fn test_unknown_routes_and_methods() {
+    let storage = Storage::open_in_memory().unwrap();
+    assert_eq!(call(&storage, "DELETE", "/todos", "").status, 405);
+    assert_eq!(call(&storage, "OPTIONS", "/agenda", "").status, 405);
+    assert_eq!(call(&storage, "GET", "/settings", "").status, 404);
+}
+
+#[test]
+fn test_agenda_lists_due_and_overdue_todos() {
+    let now = Utc::now();
+    let todos = vec![
+        todo("1", "Tomorrow", Some(now + Duration::days(1))),
+        todo("2", "Today", Some(now)),
+        todo("3", "Overdue", Some(now - Duration::days(3))),
+        todo("4", "Someday", None),
+    ];
+    let agenda = agenda_for(todos, now.date_naive());
+    let titles: Vec<_> = agenda.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(titles, vec!["Overdue", "Today"]);
+}
+
+fn raw_request(addr: std::net::SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_server_caps_bodies_audits_and_shuts_down() {
+    let storage = Storage::open_in_memory().unwrap();
+    let index = SearchIndex::default();
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let addr = server.server_addr().to_ip().unwrap();
+
+    std::thread::scope(|scope| {
+        let worker = scope.spawn(|| serve(&server, TOKEN, &storage, &index, Vec::new, |_, _| {}));
+
+        let ok = raw_request(
+            addr,
+            &format!(
+                "GET /agenda HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer {}\r\nOrigin: https://evil.example\r\nConnection: close\r\n\r\n",
+                TOKEN
+            ),
+        );
+        assert!(ok.starts_with("HTTP/1.1 200"), "{}", ok);
+        assert!(!ok.to_ascii_lowercase().contains("access-control-allow"));
+
+        let body = "x".repeat(MAX_BODY_BYTES + 1);
+        let too_large = raw_request(
+            addr,
+            &format!(
+                "POST /todos HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                TOKEN,
+                body.len(),
+                body
+            ),
+        );
+        assert!(too_large.starts_with("HTTP/1.1 413"), "{}", too_large);
+
+        server.unblock();
+        worker.join().unwrap();
+    });
+
+    let actions: Vec<_> = audit::recent(&storage, 10)
+        .unwrap()
+        .into_iter()
+        .map(|e| (e.action, e.detail))
+        .collect();
+    assert_eq!(
+        actions,
+        vec![
+            ("POST /todos".to_string(), "413".to_string()),
+            ("GET /agenda".to_string(), "200".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,164 @@
+//! Action items in free-form notes, found line by line with simple
+//! heuristics: `TODO:` and `ACTION:` markers, unchecked `- [ ]` boxes, and
+//! `@name to …` assignments inside prose. Mentioned names become tags.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use tauri::State;
+
+use crate::clock::{Clock, ClockState};
+use crate::search;
+use crate::types::{Priority, Todo};
+
+#[cfg(test)]
+mod tests;
+
+/// Markers starting an action item anywhere in a line, matched ignoring case.
+const MARKERS: &[&str] = &["TODO:", "ACTION:"];
+/// List bullets that may precede a `[ ]` checkbox.
+const BULLETS: &[&str] = &["- ", "* ", "+ "];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionItem {
+    pub text: String,
+    pub assignees: Vec<String>,
+}
+
+/// Every action item in `text`, in order, skipping ones whose text repeats
+/// an earlier item.
+pub fn action_items(text: &str) -> Vec<ActionItem> {
+    let mut seen = HashSet::new();
+    text.lines()
+        .flat_map(|line| {
+            let line = line.trim();
+            match checkbox(line).or_else(|| marked(line)) {
+                Some(text) => vec![ActionItem {
+                    assignees: mentions(text),
+                    text: text.to_string(),
+                }],
+                None => assignments(line),
+            }
+        })
+        .filter(|item| seen.insert(search::normalize_title(&item.text)))
+        .collect()
+}
+
+/// The text of an unchecked `- [ ]` item.
+pub fn checkbox(line: &str) -> Option<&str> {
+    let rest = BULLETS
+        .iter()
+        .find_map(|bullet| line.strip_prefix(bullet))?;
+    non_empty(rest.trim_start().strip_prefix("[ ]")?)
+}
+
+/// The text after the first marker that starts a word.
+fn marked(line: &str) -> Option<&str> {
+    let upper = line.to_ascii_uppercase();
+    MARKERS.iter().find_map(|marker| {
+        let start = upper
+            .match_indices(marker)
+            .map(|(i, _)| i)
+            .find(|&i| starts_word(line, i))?;
+        non_empty(&line[start + marker.len()..])
+    })
+}
+
+/// `@name to <action>` up to the end of its sentence, as the action
+/// assigned to `name`.
+fn assignments(line: &str) -> Vec<ActionItem> {
+    line.match_indices('@')
+        .filter(|&(i, _)| starts_word(line, i))
+        .filter_map(|(i, _)| {
+            let (name, rest) = name_at(&line[i + 1..])?;
+            let rest = rest.trim_start().strip_prefix("to ")?;
+            let action = non_empty(&rest[..sentence_end(rest)])?;
+            Some(ActionItem {
+                text: action.to_string(),
+                assignees: vec![name.to_string()],
+            })
+        })
+        .collect()
+}
+
+/// Names mentioned as `@name`, each once.
+fn mentions(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (i, _) in text.match_indices('@') {
+        if let Some((name, _)) = name_at(&text[i + 1..]).filter(|_| starts_word(text, i)) {
+            if !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// The name at the start of `text` and what follows it. Trailing dots and
+/// dashes end the sentence rather than the name.
+fn name_at(text: &str) -> Option<(&str, &str)> {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        .unwrap_or(text.len());
+    let name = text[..end].trim_end_matches(['.', '-']);
+    (!name.is_empty()).then(|| (name, &text[name.len()..]))
+}
+
+/// Where the sentence starting `text` ends: at `.`, `!`, `?` or `;`
+/// followed by a space, or at the end of the line.
+fn sentence_end(text: &str) -> usize {
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let at_break = chars.peek().is_none_or(|&(_, next)| next.is_whitespace());
+        if matches!(c, '.' | '!' | '?' | ';') && at_break {
+            return i;
+        }
+    }
+    text.len()
+}
+
+fn starts_word(line: &str, i: usize) -> bool {
+    line[..i]
+        .chars()
+        .next_back()
+        .is_none_or(|c| !c.is_alphanumeric())
+}
+
+fn non_empty(text: &str) -> Option<&str> {
+    let text = text.trim();
+    (!text.is_empty()).then_some(text)
+}
+
+pub fn to_todo(item: ActionItem, now: DateTime<Utc>) -> Todo {
+    Todo {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: item.text,
+        description: None,
+        completed: false,
+        priority: Priority::default(),
+        scheduled_for: None,
+        created_at: now,
+        updated_at: now,
+        completed_at: None,
+        order: None,
+        rank: None,
+        schedule_id: None,
+        tags: item.assignees,
+        languages: Default::default(),
+        fields: Default::default(),
+        estimate_minutes: None,
+        actual_minutes: None,
+        revision: 0,
+    }
+}
+
+/// Todos for the action items in `text`. Nothing is saved; the window adds
+/// the ones the user keeps.
+#[tauri::command]
+pub fn extract_action_items(text: String, clock: State<'_, ClockState>) -> Vec<Todo> {
+    let now = clock.now();
+    action_items(&text)
+        .into_iter()
+        .map(|item| to_todo(item, now))
+        .collect()
+}
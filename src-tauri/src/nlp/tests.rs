@@ -0,0 +1,76 @@
+use super::*;
+
+fn texts(notes: &str) -> Vec<String> {
+    action_items(notes)
+        .into_iter()
+        .map(|item| item.text)
+        .collect()
+}
+
+#[test]
+fn test_todo_markers_anywhere_in_a_line() {
+    assert_eq!(
+        texts(
+            "Budget looks fine. TODO: send the invoice\ntodo: book a room\nmastodon: not a marker"
+        ),
+        ["send the invoice", "book a room"]
+    );
+}
+
+#[test]
+fn test_action_markers() {
+    assert_eq!(
+        texts("ACTION: update the roadmap\n  Action: close old tickets\nREACTION: none"),
+        ["update the roadmap", "close old tickets"]
+    );
+}
+
+#[test]
+fn test_unchecked_boxes_only() {
+    assert_eq!(
+        texts("- [ ] draft agenda\n- [x] sent minutes\n* [ ] call vendor\n- [ ]\nnot - [ ] a box"),
+        ["draft agenda", "call vendor"]
+    );
+}
+
+#[test]
+fn test_assignments_in_prose_tag_the_assignee() {
+    let items = action_items(
+        "We discussed the launch. @alice to send the slides. Then @bob to book flights",
+    );
+
+    assert_eq!(
+        items,
+        [
+            ActionItem {
+                text: "send the slides".to_string(),
+                assignees: vec!["alice".to_string()],
+            },
+            ActionItem {
+                text: "book flights".to_string(),
+                assignees: vec!["bob".to_string()],
+            },
+        ]
+    );
+    // A mention without "to" is not an assignment, nor is an email address.
+    assert!(action_items("Thanks @carol for the notes, mail dave@example.com to ask").is_empty());
+}
+
+#[test]
+fn test_mentions_in_marked_items_become_tags() {
+    let todos: Vec<Todo> = action_items("TODO: @dana and @eve.m review the PR @dana.")
+        .into_iter()
+        .map(|item| to_todo(item, chrono::Utc::now()))
+        .collect();
+
+    assert_eq!(todos[0].title, "@dana and @eve.m review the PR @dana.");
+    assert_eq!(todos[0].tags, ["dana", "eve.m"]);
+}
+
+#[test]
+fn test_identical_lines_are_extracted_once() {
+    assert_eq!(
+        texts("TODO: Send invoice\n- [ ] send  invoice\nACTION: send invoice\nTODO: pay rent"),
+        ["Send invoice", "pay rent"]
+    );
+}
@@ -0,0 +1,501 @@
+//! Throttling for todo notifications. Whoever shows a notification claims it
+//! here first, so the same todo can't re-notify within the cooldown, e.g.
+//! after being snoozed and un-snoozed.
+//!
+//! While the screen is locked, claims are held back and flushed together on
+//! unlock so reminders don't go unseen behind the lock screen. Platforms
+//! without lock detection never report a lock, so claims fire immediately.
+//!
+//! What a notification says comes from [`notification_text`], which keeps
+//! only a count like "1 reminder" while privacy mode is on.
+//!
+//! Bursts are batched by [`Batcher`]: past `[backend.notifications]
+//! digest_threshold` notifications within the window, only the few
+//! highest-priority todos still show on their own and the rest collapse
+//! into one digest. Claims deferred while locked count toward the window
+//! when they are flushed, not when they were made. Catch-up after a resume
+//! or at startup goes through the same path and also emits
+//! `missed-reminders` with everything that was past due.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::clock::{Clock, ClockState};
+use crate::privacy::{PrivacyMode, PrivacyState};
+use crate::protocol::EventPayload;
+use crate::settings::{NotificationSettings, SettingsState};
+use crate::storage::{self, Storage};
+use crate::types::{Priority, Todo};
+use crate::{events, platform, trace};
+
+pub mod activation;
+#[cfg(test)]
+mod tests;
+
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+/// What clicking a digest notification does: open the review view.
+pub const DIGEST_ACTION: &str = "review-reminders";
+
+pub struct Cooldown {
+    window: Duration,
+    last_fired: HashMap<String, Instant>,
+}
+
+impl Cooldown {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_fired: HashMap::new(),
+        }
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Records a notification for `todo_id` and returns true, or returns
+    /// false if it already fired less than the cooldown ago.
+    pub fn try_fire(&mut self, todo_id: &str, now: Instant) -> bool {
+        // Entries past their cooldown no longer matter.
+        let window = self.window;
+        self.last_fired
+            .retain(|_, fired| now.saturating_duration_since(*fired) < window);
+        if self.last_fired.contains_key(todo_id) {
+            return false;
+        }
+        self.last_fired.insert(todo_id.to_string(), now);
+        true
+    }
+
+    /// [`Self::try_fire`] for each of `todo_ids`, returning those allowed.
+    pub fn try_fire_all(&mut self, todo_ids: &[String], now: Instant) -> Vec<String> {
+        todo_ids
+            .iter()
+            .filter(|id| self.try_fire(id, now))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Notifications claimed while the screen is locked, in claim order.
+#[derive(Default)]
+pub struct LockQueue {
+    locked: bool,
+    queued: Vec<String>,
+}
+
+impl LockQueue {
+    /// Queues `todo_ids` if the screen is locked and returns true; a todo
+    /// claimed repeatedly while locked is queued once.
+    pub fn defer(&mut self, todo_ids: &[String]) -> bool {
+        if !self.locked {
+            return false;
+        }
+        for id in todo_ids {
+            if !self.queued.contains(id) {
+                self.queued.push(id.clone());
+            }
+        }
+        true
+    }
+
+    /// Records the lock state, returning the queued todos on unlock.
+    pub fn set_locked(&mut self, locked: bool) -> Vec<String> {
+        self.locked = locked;
+        if locked {
+            Vec::new()
+        } else {
+            std::mem::take(&mut self.queued)
+        }
+    }
+}
+
+/// One notification standing in for several.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Digest {
+    /// Everything collapsed since the window started, so a later digest
+    /// replaces the earlier one.
+    pub todo_ids: Vec<String>,
+    pub text: NotificationText,
+    pub action: &'static str,
+}
+
+/// How claimed notifications are to be shown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    /// Shown one by one, highest priority first.
+    pub individual: Vec<String>,
+    pub digest: Option<Digest>,
+}
+
+/// Sliding window over the notifications shown individually.
+#[derive(Default)]
+pub struct Batcher {
+    shown: VecDeque<Instant>,
+    digested: Vec<String>,
+    digest_started: Option<Instant>,
+}
+
+impl Batcher {
+    /// Splits `claimed` into what shows individually and what joins the
+    /// digest. Within a window, up to `digest_threshold` notifications show
+    /// individually; a claim that would pass that still shows its
+    /// `individual_top` highest-priority todos, fewer if that many already
+    /// went past the threshold, and collapses the rest.
+    pub fn plan(
+        &mut self,
+        claimed: &[(String, Priority)],
+        now: Instant,
+        settings: &NotificationSettings,
+    ) -> Delivery {
+        let window = Duration::from_secs(settings.digest_window_secs);
+        self.shown
+            .retain(|shown| now.saturating_duration_since(*shown) < window);
+        if self
+            .digest_started
+            .is_some_and(|started| now.saturating_duration_since(started) >= window)
+        {
+            self.digested.clear();
+            self.digest_started = None;
+        }
+
+        let mut ranked: Vec<_> = claimed.iter().collect();
+        if self.shown.len() + claimed.len() > settings.digest_threshold {
+            // Stable, so equal priorities keep their claim order.
+            ranked.sort_by_key(|(_, priority)| Reverse(*priority));
+        }
+        let budget = if self.shown.len() + claimed.len() <= settings.digest_threshold {
+            claimed.len()
+        } else {
+            (settings.digest_threshold + settings.individual_top)
+                .saturating_sub(self.shown.len())
+                .min(settings.individual_top)
+        };
+        let (individual, collapsed) = ranked.split_at(budget.min(ranked.len()));
+        self.shown.extend(individual.iter().map(|_| now));
+        if collapsed.is_empty() {
+            return Delivery {
+                individual: individual.iter().map(|(id, _)| id.clone()).collect(),
+                digest: None,
+            };
+        }
+        for (id, _) in collapsed {
+            if !self.digested.contains(id) {
+                self.digested.push(id.clone());
+            }
+        }
+        self.digest_started.get_or_insert(now);
+        Delivery {
+            individual: individual.iter().map(|(id, _)| id.clone()).collect(),
+            digest: Some(Digest {
+                todo_ids: self.digested.clone(),
+                text: digest_text(self.digested.len()),
+                action: DIGEST_ACTION,
+            }),
+        }
+    }
+}
+
+pub struct NotificationState {
+    cooldown: Mutex<Cooldown>,
+    lock: Mutex<LockQueue>,
+    batcher: Mutex<Batcher>,
+}
+
+impl Default for NotificationState {
+    fn default() -> Self {
+        Self {
+            cooldown: Mutex::new(Cooldown::new(DEFAULT_COOLDOWN)),
+            lock: Mutex::new(LockQueue::default()),
+            batcher: Mutex::new(Batcher::default()),
+        }
+    }
+}
+
+impl NotificationState {
+    fn cooldown(&self) -> std::sync::MutexGuard<'_, Cooldown> {
+        self.cooldown.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_queue(&self) -> std::sync::MutexGuard<'_, LockQueue> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn batcher(&self) -> std::sync::MutexGuard<'_, Batcher> {
+        self.batcher.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Claims `todo_ids` for showing now, or defers them while locked.
+    pub fn claim_all(&self, todo_ids: &[String], now: Instant) -> Vec<String> {
+        if self.lock_queue().defer(todo_ids) {
+            return Vec::new();
+        }
+        self.cooldown().try_fire_all(todo_ids, now)
+    }
+
+    /// Applies a lock-state change and returns the deferred todos that may
+    /// notify now. The cooldown is checked at flush time, since that's when
+    /// they're actually shown.
+    pub fn set_locked(&self, locked: bool, now: Instant) -> Vec<String> {
+        let queued = self.lock_queue().set_locked(locked);
+        self.cooldown().try_fire_all(&queued, now)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationText {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+fn reminder_count(count: usize) -> String {
+    match count {
+        1 => "1 reminder".to_string(),
+        n => format!("{} reminders", n),
+    }
+}
+
+/// Names no todo, so privacy mode needs no variant of it.
+fn digest_text(count: usize) -> NotificationText {
+    NotificationText {
+        title: format!("{} \u{2014} open YuToDo to review", reminder_count(count)),
+        body: None,
+    }
+}
+
+/// One todo shows its title and description; several show a count and
+/// their titles. Privacy mode drops the body and shows only the count.
+pub fn notification_text(todos: &[Todo], privacy: &PrivacyMode) -> NotificationText {
+    match todos {
+        _ if privacy.enabled => NotificationText {
+            title: reminder_count(todos.len()),
+            body: None,
+        },
+        [todo] => NotificationText {
+            title: todo.title.clone(),
+            body: todo.description.clone().filter(|d| !d.is_empty()),
+        },
+        _ => NotificationText {
+            title: reminder_count(todos.len()),
+            body: Some(
+                todos
+                    .iter()
+                    .map(|todo| todo.title.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        },
+    }
+}
+
+/// [`notification_text`] for todos by id; unknown ids are skipped.
+pub fn text_for(
+    storage: &Storage,
+    privacy: &PrivacyMode,
+    todo_ids: &[String],
+) -> Result<NotificationText, String> {
+    let mut todos = Vec::new();
+    for id in todo_ids {
+        if let Some(todo) = storage
+            .get_todo(id)
+            .map_err(|e| format!("Failed to load todo: {}", e))?
+        {
+            todos.push(todo);
+        }
+    }
+    Ok(notification_text(&todos, privacy))
+}
+
+/// Pairs each of `todo_ids` with its priority; unknown ids get the default.
+fn with_priorities(storage: &Storage, todo_ids: &[String]) -> Vec<(String, Priority)> {
+    todo_ids
+        .iter()
+        .map(|id| {
+            let priority = storage
+                .get_todo(id)
+                .ok()
+                .flatten()
+                .map(|todo| todo.priority)
+                .unwrap_or_default();
+            (id.clone(), priority)
+        })
+        .collect()
+}
+
+/// Batches notifications that were just claimed.
+fn plan(app: &AppHandle, claimed: &[String]) -> Delivery {
+    if claimed.is_empty() {
+        return Delivery::default();
+    }
+    let claimed = with_priorities(&app.state::<Storage>(), claimed);
+    let settings = app.state::<SettingsState>().get().notifications;
+    let now = app.state::<ClockState>().now_monotonic();
+    app.state::<NotificationState>()
+        .batcher()
+        .plan(&claimed, now, &settings)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationsFlushed {
+    pub count: usize,
+    pub todo_ids: Vec<String>,
+    pub text: NotificationText,
+    /// The deferred claims that collapsed instead of showing individually.
+    pub digest: Option<Digest>,
+}
+
+impl EventPayload for NotificationsFlushed {}
+
+impl EventPayload for Digest {}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissedReminders {
+    /// Everything that came due while the app wasn't looking, for the
+    /// review view, whether or not it notifies now.
+    pub todo_ids: Vec<String>,
+    pub delivery: Delivery,
+}
+
+impl EventPayload for MissedReminders {}
+
+/// Claims the notifications of todos that came due while the app was
+/// asleep or closed and emits `missed-reminders` with all of them. While
+/// the screen is locked they're deferred like any other claim.
+pub fn catch_up(app: &AppHandle, todo_ids: Vec<String>) -> Delivery {
+    let now = app.state::<ClockState>().now_monotonic();
+    let claimed = app.state::<NotificationState>().claim_all(&todo_ids, now);
+    let delivery = plan(app, &claimed);
+    if !claimed.is_empty() {
+        let _ = events::emit(
+            app,
+            "missed-reminders",
+            MissedReminders {
+                todo_ids,
+                delivery: delivery.clone(),
+            },
+        );
+    }
+    delivery
+}
+
+/// [`catch_up`] for the open todos scheduled after `since` and up to `now`.
+pub fn catch_up_since(app: &AppHandle, since: DateTime<Utc>, now: DateTime<Utc>) {
+    let todos = match app.state::<Storage>().list_todos() {
+        Ok(todos) => todos,
+        Err(e) => {
+            trace::log(format!("Failed to look for missed reminders: {}", e));
+            return;
+        }
+    };
+    let due: Vec<String> = todos
+        .into_iter()
+        .filter(|todo| {
+            !todo.completed && todo.scheduled_for.is_some_and(|at| since < at && at <= now)
+        })
+        .map(|todo| todo.id)
+        .collect();
+    if !due.is_empty() {
+        catch_up(app, due);
+    }
+}
+/// Follows the screen lock state, flushing deferred notifications on unlock
+/// as a single `notifications-flushed` event for the frontend to coalesce.
+pub fn start_lock_watcher(app: AppHandle) {
+    let handle = app.clone();
+    let result = platform::watch_screen_lock(move |locked| {
+        if locked {
+            // Locking usually comes right before a suspend.
+            storage::drafts::flush_all(&handle);
+        }
+        let state = handle.state::<NotificationState>();
+        let now = handle.state::<ClockState>().now_monotonic();
+        let flushed = state.set_locked(locked, now);
+        let Delivery {
+            individual: todo_ids,
+            digest,
+        } = plan(&handle, &flushed);
+        if !todo_ids.is_empty() || digest.is_some() {
+            let privacy = handle.state::<PrivacyState>().get();
+            let text = match text_for(&handle.state::<Storage>(), &privacy, &todo_ids) {
+                Ok(text) => text,
+                Err(e) => {
+                    trace::log(e);
+                    NotificationText {
+                        title: reminder_count(todo_ids.len()),
+                        body: None,
+                    }
+                }
+            };
+            let _ = events::emit(
+                &handle,
+                "notifications-flushed",
+                NotificationsFlushed {
+                    count: todo_ids.len(),
+                    todo_ids,
+                    text,
+                    digest,
+                },
+            );
+        }
+    });
+    if let Err(e) = result {
+        trace::log(format!("Screen lock detection unavailable: {}", e));
+    }
+}
+
+#[tauri::command]
+pub fn set_notification_cooldown(seconds: u64, state: State<'_, NotificationState>) {
+    state.cooldown().set_window(Duration::from_secs(seconds));
+}
+
+/// Returns whether a notification for `todo_id` may be shown now. While the
+/// screen is locked this is always false and the todo is flushed on unlock.
+/// In a burst it may collapse instead, which emits `notification-digest`.
+#[tauri::command]
+pub fn claim_notification(todo_id: String, app: AppHandle) -> bool {
+    !claim(&app, &[todo_id]).individual.is_empty()
+}
+
+/// Claims notifications for `todo_ids` and batches them, emitting
+/// `notification-digest` for the ones that collapse.
+pub fn claim(app: &AppHandle, todo_ids: &[String]) -> Delivery {
+    let now = app.state::<ClockState>().now_monotonic();
+    let claimed = app.state::<NotificationState>().claim_all(todo_ids, now);
+    let delivery = plan(app, &claimed);
+    if let Some(digest) = &delivery.digest {
+        let _ = events::emit(app, "notification-digest", digest.clone());
+    }
+    delivery
+}
+
+/// Overdue catch-up at startup: returns what to show for the todos that
+/// may notify now, skipping any still within their cooldown; see
+/// [`catch_up`].
+#[tauri::command]
+pub fn claim_overdue_notifications(todo_ids: Vec<String>, app: AppHandle) -> Delivery {
+    catch_up(&app, todo_ids)
+}
+
+/// What to show for a notification about `todo_ids`.
+#[tauri::command]
+pub fn get_notification_text(
+    todo_ids: Vec<String>,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<NotificationText, String> {
+    text_for(&storage, &privacy.get(), &todo_ids)
+}
@@ -0,0 +1,398 @@
+//! Actions run from a notification, also when the app isn't running: the OS
+//! launches it with `--notification-action <action>`, e.g.
+//! `complete:<todo id>`, and the action runs against the store before any
+//! window shows. Completing and snoozing then quit again; opening goes on
+//! starting the app and emits the sticky `notification-activated` event for
+//! the window to show the todo. While the app is running, the
+//! single-instance plugin hands the launch over to it instead, so only one
+//! process writes the store; see [`run_forwarded`].
+//!
+//! Completing goes through [`storage::update_and_announce`] like any update.
+//! Snoozing leaves the todo alone and records when to remind again, which
+//! the scheduler picks up through [`remind_snoozed`].
+//!
+//! On Windows, toasts carry the action as a [`PROTOCOL`] URI. The protocol
+//! and the app's AUMID are registered per user by
+//! `register_notification_activation`, so it needs no installer step.
+
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::params;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::capabilities::{self, Capability, CapabilityRegistry};
+use crate::events;
+use crate::platform;
+use crate::privacy::PrivacyState;
+use crate::protocol::EventPayload;
+use crate::settings::SettingsState;
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::Todo;
+use crate::validation_rules::ValidationRule;
+
+#[cfg(any(windows, test))]
+use super::NotificationText;
+
+#[cfg(test)]
+mod tests;
+
+pub const ACTION_FLAG: &str = "--notification-action";
+/// URI scheme of toast actions on Windows. The OS passes the whole URI as
+/// the action.
+pub const PROTOCOL: &str = "yutodo-action";
+/// How long the Snooze button puts a reminder off.
+pub const DEFAULT_SNOOZE_MINUTES: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationAction {
+    Open {
+        todo_id: String,
+    },
+    Complete {
+        todo_id: String,
+    },
+    /// Reminds again `minutes` from now.
+    Snooze {
+        todo_id: String,
+        minutes: u32,
+    },
+}
+
+impl NotificationAction {
+    /// Parses `open:<id>`, `complete:<id>`, `snooze:<id>` or
+    /// `snooze:<minutes>:<id>`, optionally as a [`PROTOCOL`] URI.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let action = value
+            .strip_prefix(PROTOCOL)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .map_or(value, |rest| rest.trim_start_matches('/'));
+        let invalid = || format!("Invalid notification action '{}'", value);
+        let (kind, rest) = action.split_once(':').ok_or_else(invalid)?;
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+        match kind {
+            "open" => Ok(NotificationAction::Open {
+                todo_id: rest.to_string(),
+            }),
+            "complete" => Ok(NotificationAction::Complete {
+                todo_id: rest.to_string(),
+            }),
+            "snooze" => {
+                let (minutes, todo_id) = match rest.split_once(':') {
+                    Some((minutes, id)) if !id.is_empty() => match minutes.parse::<u32>() {
+                        Ok(minutes) => (minutes.max(1), id),
+                        Err(_) => (DEFAULT_SNOOZE_MINUTES, rest),
+                    },
+                    _ => (DEFAULT_SNOOZE_MINUTES, rest),
+                };
+                Ok(NotificationAction::Snooze {
+                    todo_id: todo_id.to_string(),
+                    minutes,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for NotificationAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotificationAction::Open { todo_id } => write!(f, "open:{}", todo_id),
+            NotificationAction::Complete { todo_id } => write!(f, "complete:{}", todo_id),
+            NotificationAction::Snooze { todo_id, minutes } => {
+                write!(f, "snooze:{}:{}", minutes, todo_id)
+            }
+        }
+    }
+}
+
+/// The action passed with [`ACTION_FLAG`], as `--notification-action <action>`
+/// or `--notification-action=<action>`.
+pub fn action_from_args(
+    args: impl IntoIterator<Item = String>,
+) -> Result<Option<NotificationAction>, String> {
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        let value = if arg == ACTION_FLAG {
+            args.next()
+                .ok_or_else(|| format!("{} needs an action", ACTION_FLAG))?
+        } else if let Some(value) = arg
+            .strip_prefix(ACTION_FLAG)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            value.to_string()
+        } else {
+            continue;
+        };
+        return NotificationAction::parse(&value).map(Some);
+    }
+    Ok(None)
+}
+
+/// `todo_id` as completing it writes it.
+fn completed(storage: &Storage, todo_id: &str) -> Result<Todo, String> {
+    let mut todo = storage
+        .get_todo(todo_id)
+        .map_err(|e| format!("Failed to load todo: {}", e))?
+        .ok_or_else(|| format!("Unknown todo '{}'", todo_id))?;
+    todo.completed = true;
+    todo.updated_at = storage.clock().now();
+    Ok(todo)
+}
+
+/// Puts the reminder of `todo_id` off until `until`, replacing an earlier
+/// snooze. The todo's schedule stays as it is.
+pub fn snooze(storage: &Storage, todo_id: &str, until: DateTime<Utc>) -> Result<(), String> {
+    if storage
+        .get_todo(todo_id)
+        .map_err(|e| format!("Failed to load todo: {}", e))?
+        .is_none()
+    {
+        return Err(format!("Unknown todo '{}'", todo_id));
+    }
+    storage
+        .conn()
+        .execute(
+            "INSERT OR REPLACE INTO snoozes (todo_id, until) VALUES (?1, ?2)",
+            params![todo_id, until],
+        )
+        .map_err(|e| format!("Failed to snooze the reminder: {}", e))?;
+    Ok(())
+}
+
+/// Removes the snoozes that ran out by `now`, returning the todos still
+/// open and due by then, whose reminders are to show again.
+pub fn take_elapsed_snoozes(storage: &Storage, now: DateTime<Utc>) -> Result<Vec<String>, String> {
+    let elapsed = {
+        let conn = storage.conn();
+        let mut stmt = conn
+            .prepare("DELETE FROM snoozes WHERE until <= ?1 RETURNING todo_id, until")
+            .map_err(|e| format!("Failed to read snoozes: {}", e))?;
+        let rows = stmt
+            .query_map([now], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, DateTime<Utc>>(1)?))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>());
+        rows.map_err(|e| format!("Failed to read snoozes: {}", e))?
+    };
+    let mut due = Vec::new();
+    for (todo_id, until) in elapsed {
+        let todo = storage
+            .get_todo(&todo_id)
+            .map_err(|e| format!("Failed to load todo: {}", e))?;
+        // Completed, deleted or moved past the snooze since.
+        if todo
+            .is_some_and(|todo| !todo.completed && todo.scheduled_for.is_some_and(|at| at <= until))
+        {
+            due.push(todo_id);
+        }
+    }
+    due.sort();
+    Ok(due)
+}
+
+/// Runs `action` in the running app, checking a completion against
+/// `rules`; opening changes nothing. Requires `Storage`, `Drafts` and
+/// `SearchIndex`.
+pub fn execute(
+    app: &AppHandle,
+    rules: &[ValidationRule],
+    action: &NotificationAction,
+) -> Result<(), String> {
+    let storage = app.state::<Storage>();
+    match action {
+        NotificationAction::Open { .. } => Ok(()),
+        NotificationAction::Complete { todo_id } => {
+            let todo = completed(&storage, todo_id)?;
+            storage::update_and_announce(app, rules, todo, None)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+        NotificationAction::Snooze { todo_id, minutes } => {
+            let until = storage.clock().now() + Duration::minutes(*minutes as i64);
+            snooze(&storage, todo_id, until)
+        }
+    }
+}
+
+/// [`execute`], logging a failure; a read-only store is left alone.
+fn run(app: &AppHandle, rules: &[ValidationRule], action: &NotificationAction) {
+    if app.state::<Storage>().newer_schema().is_some() {
+        trace::log(format!(
+            "Not running notification action {}: the store is read-only",
+            action
+        ));
+    } else if let Err(e) = execute(app, rules, action) {
+        trace::log(format!(
+            "Failed to run notification action {}: {}",
+            action, e
+        ));
+    }
+}
+
+/// Payload of `notification-activated`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationActivated {
+    pub todo_id: String,
+}
+
+impl EventPayload for NotificationActivated {}
+
+/// Runs the action the app was launched with, checking what it writes
+/// against `rules`. Returns whether the app is quitting rather than
+/// starting up.
+pub fn run_at_startup(
+    app: &AppHandle,
+    rules: &[ValidationRule],
+    action: NotificationAction,
+) -> bool {
+    run(app, rules, &action);
+    if let NotificationAction::Open { todo_id } = action {
+        let _ = events::emit_sticky(
+            app,
+            "notification-activated",
+            NotificationActivated { todo_id },
+        );
+        return false;
+    }
+    for window in app.webview_windows().into_values() {
+        let _ = window.hide();
+    }
+    app.exit(0);
+    true
+}
+
+/// Runs the action of a launch the single-instance plugin forwarded here,
+/// as [`run_at_startup`] would have in the launched process.
+pub fn run_forwarded(app: &AppHandle, action: NotificationAction) {
+    run(
+        app,
+        &app.state::<SettingsState>().get().validation_rules,
+        &action,
+    );
+    if let NotificationAction::Open { todo_id } = action {
+        let _ = events::emit_sticky(
+            app,
+            "notification-activated",
+            NotificationActivated { todo_id },
+        );
+    }
+}
+
+/// Payload of `reminder-due`, for a window to show a snoozed reminder where
+/// there are no system notifications with actions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReminderDue {
+    pub todo_id: String,
+}
+
+impl EventPayload for ReminderDue {}
+
+/// Shows the reminders whose snooze ran out by `now` again, claimed like
+/// any other notification.
+pub fn remind_snoozed(app: &AppHandle, now: DateTime<Utc>) {
+    let storage = app.state::<Storage>();
+    if storage.newer_schema().is_some() {
+        return;
+    }
+    let due = match take_elapsed_snoozes(&storage, now) {
+        Ok(due) => due,
+        Err(e) => {
+            trace::log(e);
+            return;
+        }
+    };
+    if due.is_empty() {
+        return;
+    }
+    let privacy = app.state::<PrivacyState>().get();
+    for todo_id in super::claim(app, &due).individual {
+        let shown = super::text_for(&storage, &privacy, std::slice::from_ref(&todo_id))
+            .and_then(|text| platform::show_reminder(&todo_id, &text));
+        match shown {
+            Ok(true) => {}
+            Ok(false) => {
+                let _ = events::emit(app, "reminder-due", ReminderDue { todo_id });
+            }
+            Err(e) => trace::log(format!("Failed to show a snoozed reminder: {}", e)),
+        }
+    }
+}
+
+/// Toast XML whose body opens the todo and whose buttons complete or
+/// snooze it, all through [`PROTOCOL`].
+#[cfg(any(windows, test))]
+pub fn toast_xml(todo_id: &str, text: &NotificationText) -> String {
+    use crate::site::escape_html;
+
+    let uri = |action: NotificationAction| escape_html(&format!("{}:{}", PROTOCOL, action));
+    let todo_id = todo_id.to_string();
+    let body = text
+        .body
+        .as_deref()
+        .map(|body| format!("<text>{}</text>", escape_html(body)))
+        .unwrap_or_default();
+    format!(
+        "<toast activationType=\"protocol\" launch=\"{}\">\
+         <visual><binding template=\"ToastGeneric\"><text>{}</text>{}</binding></visual>\
+         <actions>\
+         <action content=\"Complete\" activationType=\"protocol\" arguments=\"{}\"/>\
+         <action content=\"Snooze\" activationType=\"protocol\" arguments=\"{}\"/>\
+         </actions>\
+         </toast>",
+        uri(NotificationAction::Open {
+            todo_id: todo_id.clone()
+        }),
+        escape_html(&text.title),
+        body,
+        uri(NotificationAction::Complete {
+            todo_id: todo_id.clone()
+        }),
+        uri(NotificationAction::Snooze {
+            todo_id,
+            minutes: DEFAULT_SNOOZE_MINUTES
+        }),
+    )
+}
+
+fn current_exe() -> Result<std::path::PathBuf, String> {
+    std::env::current_exe().map_err(|e| format!("Failed to locate the executable: {}", e))
+}
+
+pub fn register_capabilities(registry: &CapabilityRegistry) {
+    registry.register("notificationActivation", || match current_exe() {
+        Ok(exe) => platform::notification_activation(&exe),
+        Err(reason) => Capability::Unsupported { reason },
+    });
+}
+
+/// Registers what lets notification actions launch the app while it isn't
+/// running, for installs whose installer didn't.
+#[tauri::command]
+pub fn register_notification_activation(app: AppHandle) -> Result<(), String> {
+    platform::register_notification_activation(&current_exe()?)
+        .map_err(|e| format!("Failed to register notification actions: {}", e))?;
+    capabilities::reprobe(&app);
+    Ok(())
+}
+
+/// Shows the reminder for `todo_id` as a system notification whose actions
+/// work while the app is closed. Returns false where there is none, for the
+/// window to show its own.
+#[tauri::command]
+pub fn show_reminder_notification(
+    todo_id: String,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<bool, String> {
+    let text = super::text_for(&storage, &privacy.get(), std::slice::from_ref(&todo_id))?;
+    platform::show_reminder(&todo_id, &text)
+}
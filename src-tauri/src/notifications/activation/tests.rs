@@ -0,0 +1,144 @@
+use super::*;
+
+use chrono::TimeZone;
+
+fn args(args: &[&str]) -> Vec<String> {
+    std::iter::once("yutodo")
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect()
+}
+
+fn todo(id: &str) -> Todo {
+    let at = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+    Todo {
+        scheduled_for: Some(at),
+        ..Todo::sample(id, "Call the bank", at)
+    }
+}
+
+#[test]
+fn test_action_from_args() {
+    let complete = NotificationAction::Complete {
+        todo_id: "t1".to_string(),
+    };
+    assert_eq!(
+        action_from_args(args(&["--notification-action", "complete:t1"])),
+        Ok(Some(complete.clone()))
+    );
+    assert_eq!(
+        action_from_args(args(&["--verbose", "--notification-action=complete:t1"])),
+        Ok(Some(complete.clone()))
+    );
+    // As launched through the protocol on Windows.
+    assert_eq!(
+        action_from_args(args(&[
+            "--notification-action",
+            "yutodo-action:complete:t1"
+        ])),
+        Ok(Some(complete))
+    );
+    assert_eq!(action_from_args(args(&["list.yutodo"])), Ok(None));
+    assert!(action_from_args(args(&["--notification-action"])).is_err());
+}
+
+#[test]
+fn test_parse_actions() {
+    assert_eq!(
+        NotificationAction::parse("yutodo-action://open:t1"),
+        Ok(NotificationAction::Open {
+            todo_id: "t1".to_string()
+        })
+    );
+    assert_eq!(
+        NotificationAction::parse("snooze:t1"),
+        Ok(NotificationAction::Snooze {
+            todo_id: "t1".to_string(),
+            minutes: DEFAULT_SNOOZE_MINUTES
+        })
+    );
+    let snooze = NotificationAction::Snooze {
+        todo_id: "t1".to_string(),
+        minutes: 30,
+    };
+    assert_eq!(
+        NotificationAction::parse("snooze:30:t1"),
+        Ok(snooze.clone())
+    );
+    assert_eq!(NotificationAction::parse(&snooze.to_string()), Ok(snooze));
+    assert_eq!(
+        NotificationAction::parse("delete:t1"),
+        Err("Invalid notification action 'delete:t1'".to_string())
+    );
+    assert!(NotificationAction::parse("complete:").is_err());
+    assert!(NotificationAction::parse("t1").is_err());
+}
+
+#[test]
+fn test_completing_marks_the_stored_todo() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.save_todo(&todo("t1")).unwrap();
+
+    let todo = completed(&storage, "t1").unwrap();
+    assert!(todo.completed);
+    assert_eq!(todo.updated_at, storage.clock().now());
+    assert_eq!(
+        completed(&storage, "gone"),
+        Err("Unknown todo 'gone'".to_string())
+    );
+}
+
+#[test]
+fn test_snoozing_keeps_the_schedule_and_reminds_later() {
+    let storage = Storage::open_in_memory().unwrap();
+    let original = todo("t1");
+    storage.save_todo(&original).unwrap();
+    storage.save_todo(&todo("done")).unwrap();
+    let due = original.scheduled_for.unwrap();
+
+    snooze(&storage, "t1", due + Duration::minutes(10)).unwrap();
+    // A second snooze replaces the first.
+    snooze(&storage, "t1", due + Duration::minutes(30)).unwrap();
+    snooze(&storage, "done", due + Duration::minutes(10)).unwrap();
+    assert_eq!(
+        snooze(&storage, "gone", due),
+        Err("Unknown todo 'gone'".to_string())
+    );
+    let stored = storage.get_todo("t1").unwrap().unwrap();
+    assert_eq!(stored.scheduled_for, Some(due));
+    assert_eq!(stored.revision, 1);
+
+    storage
+        .save_todo(&Todo {
+            completed: true,
+            ..todo("done")
+        })
+        .unwrap();
+    assert!(take_elapsed_snoozes(&storage, due + Duration::minutes(29))
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        take_elapsed_snoozes(&storage, due + Duration::minutes(30)).unwrap(),
+        vec!["t1".to_string()]
+    );
+    // Each snooze reminds once.
+    assert!(take_elapsed_snoozes(&storage, due + Duration::hours(1))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_toast_xml_escapes_text() {
+    let xml = toast_xml(
+        "t1",
+        &NotificationText {
+            title: "Pay <rent> & bills".to_string(),
+            body: None,
+        },
+    );
+    assert!(xml.contains("<text>Pay &lt;rent&gt; &amp; bills</text>"));
+    assert!(xml.contains("launch=\"yutodo-action:open:t1\""));
+    assert!(xml.contains("arguments=\"yutodo-action:complete:t1\""));
+    assert!(xml.contains("arguments=\"yutodo-action:snooze:10:t1\""));
+    assert!(!xml.contains("<text></text>"));
+}
@@ -0,0 +1,192 @@
+use super::*;
+
+#[test]
+fn test_refire_within_cooldown_is_suppressed() {
+    let start = Instant::now();
+    let mut cooldown = Cooldown::new(DEFAULT_COOLDOWN);
+
+    assert!(cooldown.try_fire("a", start));
+    assert!(!cooldown.try_fire("a", start + Duration::from_secs(59)));
+    assert!(cooldown.try_fire("b", start + Duration::from_secs(59)));
+    assert!(cooldown.try_fire("a", start + Duration::from_secs(60)));
+}
+
+#[test]
+fn test_catch_up_respects_cooldown() {
+    let start = Instant::now();
+    let mut cooldown = Cooldown::new(Duration::from_secs(30));
+    assert!(cooldown.try_fire("a", start));
+
+    let ids = vec!["a".to_string(), "b".to_string(), "b".to_string()];
+    assert_eq!(
+        cooldown.try_fire_all(&ids, start + Duration::from_secs(10)),
+        vec!["b"]
+    );
+
+    cooldown.set_window(Duration::from_secs(5));
+    assert_eq!(
+        cooldown.try_fire_all(&ids, start + Duration::from_secs(16)),
+        vec!["a", "b"]
+    );
+}
+
+#[test]
+fn test_claims_while_locked_flush_once_on_unlock() {
+    let start = Instant::now();
+    let state = NotificationState::default();
+    let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(state.claim_all(&ids(&["a"]), start), vec!["a"]);
+    assert!(state.set_locked(true, start).is_empty());
+
+    // Repeats while locked are coalesced; nothing fires yet.
+    assert!(state.claim_all(&ids(&["b"]), start).is_empty());
+    assert!(state.claim_all(&ids(&["c", "b"]), start).is_empty());
+    assert!(state.claim_all(&ids(&["a"]), start).is_empty());
+
+    // "a" fired just before the lock and is still within its cooldown.
+    let later = start + Duration::from_secs(30);
+    assert_eq!(state.set_locked(false, later), vec!["b", "c"]);
+    assert!(state.set_locked(false, later).is_empty());
+
+    assert_eq!(
+        state.claim_all(&ids(&["d"]), later),
+        vec!["d"],
+        "claims fire immediately once unlocked"
+    );
+}
+
+#[test]
+fn test_privacy_mode_reduces_notifications_to_a_count() {
+    let at = chrono::Utc::now();
+    let todo = |title: &str| Todo {
+        description: Some("Room 4, bring the contract".to_string()),
+        ..Todo::sample(title, title, at)
+    };
+    let off = PrivacyMode::default();
+    let on = PrivacyMode {
+        enabled: true,
+        ..Default::default()
+    };
+
+    let one = [todo("Meet lawyer")];
+    assert_eq!(
+        notification_text(&one, &off),
+        NotificationText {
+            title: "Meet lawyer".to_string(),
+            body: Some("Room 4, bring the contract".to_string()),
+        }
+    );
+    assert_eq!(
+        notification_text(&one, &on),
+        NotificationText {
+            title: "1 reminder".to_string(),
+            body: None,
+        }
+    );
+
+    let two = [todo("A"), todo("B")];
+    assert_eq!(notification_text(&two, &off).body.as_deref(), Some("A\nB"));
+    assert_eq!(notification_text(&two, &on).title, "2 reminders");
+}
+
+fn claimed(todos: &[(&str, Priority)]) -> Vec<(String, Priority)> {
+    todos
+        .iter()
+        .map(|(id, priority)| (id.to_string(), *priority))
+        .collect()
+}
+
+#[test]
+fn test_burst_collapses_into_digest_keeping_top_priorities() {
+    let start = Instant::now();
+    let settings = NotificationSettings::default();
+    let mut batcher = Batcher::default();
+
+    let few = claimed(&[("a", Priority::Low), ("b", Priority::Low)]);
+    assert_eq!(
+        batcher.plan(&few, start, &settings),
+        Delivery {
+            individual: vec!["a".to_string(), "b".to_string()],
+            digest: None,
+        }
+    );
+
+    // 2 shown + 14 claimed passes 5: the 3 highest go out, the rest collapse.
+    let mut burst: Vec<_> = (0..14)
+        .map(|n| (format!("t{}", n), Priority::Medium))
+        .collect();
+    burst[9].1 = Priority::High;
+    burst[4].1 = Priority::High;
+    burst[0].1 = Priority::Low;
+    let delivery = batcher.plan(&burst, start, &settings);
+    assert_eq!(delivery.individual, vec!["t4", "t9", "t1"]);
+    let digest = delivery.digest.unwrap();
+    assert_eq!(digest.todo_ids.len(), 11);
+    assert!(digest.todo_ids.contains(&"t0".to_string()));
+    assert_eq!(
+        digest.text.title,
+        "11 reminders \u{2014} open YuToDo to review"
+    );
+    assert_eq!(digest.text.body, None);
+    assert_eq!(digest.action, DIGEST_ACTION);
+}
+
+#[test]
+fn test_digest_grows_within_window_and_resets_after() {
+    let start = Instant::now();
+    let settings = NotificationSettings {
+        digest_threshold: 2,
+        digest_window_secs: 60,
+        individual_top: 1,
+    };
+    let mut batcher = Batcher::default();
+    let one = |id: &str| claimed(&[(id, Priority::High)]);
+
+    assert_eq!(batcher.plan(&one("a"), start, &settings).individual, ["a"]);
+    assert_eq!(batcher.plan(&one("b"), start, &settings).individual, ["b"]);
+    // Past the threshold, one more high-priority todo still shows...
+    assert_eq!(batcher.plan(&one("c"), start, &settings).individual, ["c"]);
+    // ...and from then on claims join the digest, which replaces itself.
+    let later = start + Duration::from_secs(30);
+    let d = batcher.plan(&one("d"), later, &settings);
+    assert!(d.individual.is_empty());
+    assert_eq!(d.digest.unwrap().todo_ids, ["d"]);
+    let e = batcher.plan(&one("e"), later, &settings);
+    assert_eq!(e.digest.unwrap().todo_ids, ["d", "e"]);
+
+    // A quiet window later, notifications show one by one again.
+    let quiet = later + Duration::from_secs(60);
+    assert_eq!(
+        batcher.plan(&one("f"), quiet, &settings),
+        Delivery {
+            individual: vec!["f".to_string()],
+            digest: None,
+        }
+    );
+}
+
+#[test]
+fn test_claims_deferred_while_locked_are_batched_at_flush() {
+    let start = Instant::now();
+    let settings = NotificationSettings::default();
+    let state = NotificationState::default();
+    let mut batcher = Batcher::default();
+    let ids: Vec<String> = (0..8).map(|n| format!("t{}", n)).collect();
+
+    state.set_locked(true, start);
+    for id in &ids {
+        assert!(state.claim_all(std::slice::from_ref(id), start).is_empty());
+    }
+    // Locked for an hour: one by one they'd have been spread out, but they
+    // all show at unlock, so they count as one burst then.
+    let unlocked = start + Duration::from_secs(3600);
+    let flushed = state.set_locked(false, unlocked);
+    let flushed: Vec<_> = flushed
+        .into_iter()
+        .map(|id| (id, Priority::Medium))
+        .collect();
+    let delivery = batcher.plan(&flushed, unlocked, &settings);
+    assert_eq!(delivery.individual, ["t0", "t1", "t2"]);
+    assert_eq!(delivery.digest.unwrap().todo_ids.len(), 5);
+}
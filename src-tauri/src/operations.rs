@@ -0,0 +1,109 @@
+//! Cancellation of long operations. A command that may run for minutes
+//! accepts an `operation_id`, registers it here for as long as it runs and
+//! checks the token between batches; `cancel_operation` sets the token, and
+//! the operation rolls back what it wrote and emits `operation-cancelled`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+use crate::events;
+use crate::protocol::EventPayload;
+
+#[cfg(test)]
+mod tests;
+
+/// Set once the operation holding it should stop.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Managed state holding the tokens of the running operations by id.
+#[derive(Default)]
+pub struct Operations {
+    running: Mutex<HashMap<String, CancelToken>>,
+}
+
+impl Operations {
+    fn running(&self) -> MutexGuard<'_, HashMap<String, CancelToken>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Registers `id` until the returned guard is dropped.
+    pub fn start(&self, id: &str) -> Result<RunningOperation<'_>, String> {
+        let mut running = self.running();
+        if running.contains_key(id) {
+            return Err(format!("Operation '{}' is already running", id));
+        }
+        let token = CancelToken::default();
+        running.insert(id.to_string(), token.clone());
+        Ok(RunningOperation {
+            operations: self,
+            id: id.to_string(),
+            token,
+        })
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        let running = self.running();
+        let token = running
+            .get(id)
+            .ok_or_else(|| format!("Failed to cancel: operation '{}' is not running", id))?;
+        token.cancel();
+        Ok(())
+    }
+}
+
+/// An operation registered with [`Operations::start`].
+pub struct RunningOperation<'a> {
+    operations: &'a Operations,
+    pub id: String,
+    pub token: CancelToken,
+}
+
+impl Drop for RunningOperation<'_> {
+    fn drop(&mut self) {
+        self.operations.running().remove(&self.id);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationCancelled {
+    pub operation_id: String,
+}
+
+impl EventPayload for OperationCancelled {}
+
+/// Emits `operation-cancelled` once a cancelled operation has rolled back.
+pub fn announce_cancelled(app: &AppHandle, operation_id: &str) {
+    let _ = events::emit(
+        app,
+        "operation-cancelled",
+        OperationCancelled {
+            operation_id: operation_id.to_string(),
+        },
+    );
+}
+
+/// Stops the operation started with `operation_id` at its next batch and
+/// rolls back what it wrote.
+#[tauri::command]
+pub fn cancel_operation(
+    operation_id: String,
+    operations: State<'_, Operations>,
+) -> Result<(), String> {
+    operations.cancel(&operation_id)
+}
@@ -0,0 +1,15 @@
+use super::*;
+
+#[test]
+fn test_operations_are_registered_while_running() {
+    let operations = Operations::default();
+    let running = operations.start("import-1").unwrap();
+    assert!(operations.start("import-1").is_err());
+
+    operations.cancel("import-1").unwrap();
+    assert!(running.token.is_cancelled());
+
+    drop(running);
+    assert!(operations.cancel("import-1").is_err());
+    assert!(operations.start("import-1").is_ok());
+}
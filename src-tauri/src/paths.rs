@@ -0,0 +1,141 @@
+//! Turning path strings from the frontend into paths that are safe to open.
+//!
+//! [`safe_path_in`] normalizes separators, resolves `..` and symlinks, and
+//! refuses anything that ends up outside its base directory. Commands that
+//! touch files check their paths through [`crate::file_access`], which uses
+//! it for each allowed directory.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::settings;
+
+#[cfg(test)]
+mod tests;
+
+/// Directory a relative path from the frontend is resolved against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum BaseDir {
+    AppData,
+    Config,
+    Downloads,
+    Documents,
+}
+
+impl BaseDir {
+    pub const ALL: [BaseDir; 4] = [
+        BaseDir::AppData,
+        BaseDir::Config,
+        BaseDir::Downloads,
+        BaseDir::Documents,
+    ];
+
+    pub fn resolve(self, app: &AppHandle) -> tauri::Result<PathBuf> {
+        match self {
+            BaseDir::AppData => app.path().app_data_dir(),
+            BaseDir::Config => settings::config_dir(app),
+            BaseDir::Downloads => app.path().download_dir(),
+            BaseDir::Documents => app.path().document_dir(),
+        }
+    }
+}
+
+/// Reads both `/` and `\` as separators, so paths typed on one platform
+/// work on the other.
+pub fn normalize_separators(input: &str) -> PathBuf {
+    let separator = std::path::MAIN_SEPARATOR.to_string();
+    PathBuf::from(input.replace(['/', '\\'], &separator))
+}
+
+/// Drops `.` and applies `..` without touching the disk, so a `..` that
+/// leaves the path (`a/../../b`) is an error rather than resolved.
+fn normalize_lexically(path: &Path) -> Result<PathBuf, String> {
+    let mut normalized = PathBuf::new();
+    let mut depth = 0;
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => {
+                normalized.pop();
+                depth -= 1;
+            }
+            Component::ParentDir => {
+                return Err(format!("{} leaves its directory", path.display()));
+            }
+            Component::Normal(name) => {
+                normalized.push(name);
+                depth += 1;
+            }
+            Component::Prefix(_) | Component::RootDir => normalized.push(component),
+        }
+    }
+    Ok(normalized)
+}
+
+/// Canonicalizes the longest existing ancestor of `path` and appends the
+/// rest, so paths that are about to be created resolve too.
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        match fs::canonicalize(existing) {
+            Ok(resolved) => {
+                return Ok(rest
+                    .into_iter()
+                    .rev()
+                    .fold(resolved, |path, name| path.join(name)));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let (Some(parent), Some(Component::Normal(name))) =
+                    (existing.parent(), existing.components().next_back())
+                else {
+                    return Err(e);
+                };
+                rest.push(name);
+                existing = parent;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Resolves `input`, relative to `base` unless absolute, and returns it if
+/// it stays inside `base`. The path itself need not exist yet.
+pub fn safe_path_in(input: &str, base: &Path) -> Result<PathBuf, String> {
+    let outside = || format!("{} is outside {}", input, base.display());
+    if input.trim().is_empty() {
+        return Err("Path is empty".to_string());
+    }
+    let base =
+        fs::canonicalize(base).map_err(|e| format!("Failed to open {}: {}", base.display(), e))?;
+    let path = normalize_separators(input);
+    let joined = if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    };
+    let resolved = normalize_lexically(&joined).and_then(|path| {
+        canonicalize(&path).map_err(|e| format!("Failed to open {}: {}", input, e))
+    })?;
+    if resolved.starts_with(&base) {
+        Ok(resolved)
+    } else {
+        Err(outside())
+    }
+}
+
+/// Resolves a path the frontend built relative to one of the app's
+/// directories; see [`safe_path_in`].
+#[tauri::command]
+pub fn safe_path(input: String, base: BaseDir, app: AppHandle) -> Result<PathBuf, String> {
+    let base = base
+        .resolve(&app)
+        .map_err(|e| format!("Failed to locate {:?} directory: {}", base, e))?;
+    safe_path_in(&input, &base)
+}
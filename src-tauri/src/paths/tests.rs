@@ -0,0 +1,68 @@
+use super::*;
+
+fn setup() -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let base = fs::canonicalize(dir.path()).unwrap().join("base");
+    fs::create_dir_all(base.join("exports")).unwrap();
+    (dir, base)
+}
+
+#[test]
+fn test_relative_paths_resolve_inside_base() {
+    let (_dir, base) = setup();
+
+    assert_eq!(
+        safe_path_in("exports/todos.json", &base).unwrap(),
+        base.join("exports").join("todos.json")
+    );
+    assert_eq!(
+        safe_path_in("./exports/./new/../todos.json", &base).unwrap(),
+        base.join("exports").join("todos.json")
+    );
+    // Both separators work everywhere.
+    assert_eq!(
+        safe_path_in("exports\\2024\\todos.json", &base).unwrap(),
+        base.join("exports").join("2024").join("todos.json")
+    );
+}
+
+#[test]
+fn test_traversal_out_of_base_is_rejected() {
+    let (_dir, base) = setup();
+    fs::create_dir_all(base.with_file_name("sibling")).unwrap();
+
+    for input in [
+        "../sibling/todos.json",
+        "exports/../../sibling",
+        "..\\sibling",
+        "exports/missing/../../../sibling",
+        "../../../../../../../../etc/passwd",
+    ] {
+        assert!(safe_path_in(input, &base).is_err(), "{}", input);
+    }
+}
+
+#[test]
+fn test_absolute_paths_must_be_inside_base() {
+    let (_dir, base) = setup();
+    let inside = base.join("exports").join("todos.json");
+    let outside = base.with_file_name("todos.json");
+
+    assert_eq!(
+        safe_path_in(&inside.to_string_lossy(), &base).unwrap(),
+        inside
+    );
+    assert!(safe_path_in(&outside.to_string_lossy(), &base).is_err());
+    assert!(safe_path_in("", &base).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlinks_are_followed_before_checking() {
+    let (_dir, base) = setup();
+    let elsewhere = base.with_file_name("elsewhere");
+    fs::create_dir_all(&elsewhere).unwrap();
+    std::os::unix::fs::symlink(&elsewhere, base.join("link")).unwrap();
+
+    assert!(safe_path_in("link/todos.json", &base).is_err());
+}
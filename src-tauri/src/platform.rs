@@ -0,0 +1,91 @@
+//! Platform-specific behaviour that the feature modules shouldn't need to
+//! know about.
+
+use std::path::Path;
+
+use crate::calendar::SystemCalendar;
+use crate::capabilities::{Capability, CapabilityRegistry};
+use crate::notifications::NotificationText;
+
+#[cfg(target_os = "linux")]
+pub mod linux;
+#[cfg(target_os = "macos")]
+pub mod macos;
+#[cfg(windows)]
+pub mod windows;
+
+/// Registers probes for capabilities that depend on the OS or desktop session.
+pub fn register_capabilities(registry: &CapabilityRegistry) {
+    #[cfg(target_os = "linux")]
+    linux::register_capabilities(
+        registry,
+        linux::Session::detect(|name| std::env::var(name).ok()),
+        std::sync::Arc::new(linux::DBusSessionBus),
+    );
+    #[cfg(not(target_os = "linux"))]
+    let _ = registry;
+}
+
+/// Calls `on_change` with the current screen lock state and again whenever it
+/// changes. Errors if the platform or session has no lock detection.
+pub fn watch_screen_lock(on_change: impl FnMut(bool) + Send + 'static) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    return linux::watch_screen_lock(on_change);
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = on_change;
+        Err("Screen lock detection is not supported on this platform".to_string())
+    }
+}
+
+/// The OS calendar that scheduled todos sync into, or why there is none.
+pub fn system_calendar() -> Result<Box<dyn SystemCalendar>, String> {
+    #[cfg(target_os = "macos")]
+    return Ok(Box::new(macos::EventKitCalendar::new()));
+    #[cfg(windows)]
+    return Ok(Box::new(windows::AppointmentsCalendar));
+    #[cfg(not(any(target_os = "macos", windows)))]
+    Err(
+        "Syncing to the system calendar is only supported on macOS and Windows; \
+         export an ICS file instead"
+            .to_string(),
+    )
+}
+
+/// Registers what lets notification actions launch `exe` while the app
+/// isn't running.
+pub fn register_notification_activation(exe: &Path) -> Result<(), String> {
+    #[cfg(windows)]
+    return windows::register_notification_activation(exe);
+    #[cfg(not(windows))]
+    {
+        let _ = exe;
+        Err("Notification actions can only launch the app on Windows".to_string())
+    }
+}
+
+/// Whether notification actions launch `exe` while the app isn't running.
+pub fn notification_activation(exe: &Path) -> Capability {
+    #[cfg(windows)]
+    return windows::notification_activation(exe);
+    #[cfg(not(windows))]
+    {
+        let _ = exe;
+        Capability::Unsupported {
+            reason: "Notification actions can only launch the app on Windows".to_string(),
+        }
+    }
+}
+
+/// Shows a reminder whose actions work while the app is closed. Returns
+/// false where the platform has no such notifications.
+pub fn show_reminder(todo_id: &str, text: &NotificationText) -> Result<bool, String> {
+    #[cfg(windows)]
+    return windows::show_toast(&crate::notifications::activation::toast_xml(todo_id, text))
+        .map(|()| true);
+    #[cfg(not(windows))]
+    {
+        let _ = (todo_id, text);
+        Ok(false)
+    }
+}
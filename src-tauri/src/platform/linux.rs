@@ -0,0 +1,219 @@
+//! Wayland and X11 differences. Under Wayland the compositor owns window
+//! placement and global key grabs, so those features go through XDG portals
+//! or are reported as unavailable instead of silently failing.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::capabilities::{Capability, CapabilityRegistry};
+
+#[cfg(test)]
+mod tests;
+
+const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const GLOBAL_SHORTCUTS_PORTAL: &str = "org.freedesktop.portal.GlobalShortcuts";
+const SCREENSAVER_DESTINATION: &str = "org.freedesktop.ScreenSaver";
+const SCREENSAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
+/// Bus name owned by the tray host of StatusNotifierItem-based desktops.
+const STATUS_NOTIFIER_WATCHER: &str = "org.kde.StatusNotifierWatcher";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionType {
+    Wayland,
+    X11,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub session_type: SessionType,
+    /// First entry of `XDG_CURRENT_DESKTOP`, e.g. `GNOME` or `KDE`.
+    pub desktop: Option<String>,
+}
+
+impl Session {
+    /// Reads the session from environment variables looked up through `var`.
+    /// `WAYLAND_DISPLAY` wins over `DISPLAY`, which XWayland also sets.
+    pub fn detect(var: impl Fn(&str) -> Option<String>) -> Self {
+        let set = |name: &str| var(name).filter(|value| !value.trim().is_empty());
+        let session_type = if set("WAYLAND_DISPLAY").is_some() {
+            SessionType::Wayland
+        } else if set("DISPLAY").is_some() {
+            SessionType::X11
+        } else {
+            match set("XDG_SESSION_TYPE").as_deref() {
+                Some("wayland") => SessionType::Wayland,
+                Some("x11") => SessionType::X11,
+                _ => SessionType::Unknown,
+            }
+        };
+        let desktop = set("XDG_CURRENT_DESKTOP")
+            .and_then(|desktops| desktops.split(':').next().map(str::to_string))
+            .or_else(|| set("DESKTOP_SESSION"));
+        Self {
+            session_type,
+            desktop,
+        }
+    }
+}
+
+/// The D-Bus calls the probes make, so they can be tested off a real desktop.
+pub trait SessionBus: Send + Sync {
+    /// Whether some process currently owns `name`.
+    fn name_has_owner(&self, name: &str) -> Result<bool, String>;
+    /// The `version` property of a portal interface, or `None` when the
+    /// desktop's portal backend doesn't implement it.
+    fn portal_version(&self, interface: &str) -> Result<Option<u32>, String>;
+}
+
+/// The real session bus. Connects per call, since probes run rarely and the
+/// bus may only become available after login.
+pub struct DBusSessionBus;
+
+impl SessionBus for DBusSessionBus {
+    fn name_has_owner(&self, name: &str) -> Result<bool, String> {
+        let conn = zbus::blocking::Connection::session().map_err(|e| e.to_string())?;
+        let name = zbus::names::BusName::try_from(name).map_err(|e| e.to_string())?;
+        zbus::blocking::fdo::DBusProxy::new(&conn)
+            .and_then(|dbus| Ok(dbus.name_has_owner(name)?))
+            .map_err(|e| e.to_string())
+    }
+
+    fn portal_version(&self, interface: &str) -> Result<Option<u32>, String> {
+        let conn = zbus::blocking::Connection::session().map_err(|e| e.to_string())?;
+        let proxy = zbus::blocking::Proxy::new(&conn, PORTAL_DESTINATION, PORTAL_PATH, interface)
+            .map_err(|e| e.to_string())?;
+        match proxy.get_property::<u32>("version") {
+            Ok(version) => Ok(Some(version)),
+            // No portal service, or one without this interface.
+            Err(zbus::Error::FDO(_)) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Follows the session's screensaver, which desktops activate when they lock
+/// the screen. The initial state is reported before this returns.
+pub fn watch_screen_lock(mut on_change: impl FnMut(bool) + Send + 'static) -> Result<(), String> {
+    let conn = zbus::blocking::Connection::session().map_err(|e| e.to_string())?;
+    let proxy = zbus::blocking::Proxy::new(
+        &conn,
+        SCREENSAVER_DESTINATION,
+        SCREENSAVER_PATH,
+        SCREENSAVER_DESTINATION,
+    )
+    .map_err(|e| e.to_string())?;
+    let signals = proxy
+        .receive_signal("ActiveChanged")
+        .map_err(|e| e.to_string())?;
+    let active: bool = proxy.call("GetActive", &()).map_err(|e| e.to_string())?;
+    on_change(active);
+    std::thread::spawn(move || {
+        for message in signals {
+            if let Ok(active) = message.body().deserialize::<bool>() {
+                on_change(active);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// How global shortcuts can be registered in this session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShortcutRoute {
+    /// X11 key grabs.
+    X11,
+    /// The XDG GlobalShortcuts portal; the desktop asks the user to confirm.
+    Portal,
+    Unavailable(String),
+}
+
+pub fn shortcut_route(session: &Session, bus: &dyn SessionBus) -> ShortcutRoute {
+    match session.session_type {
+        SessionType::X11 => ShortcutRoute::X11,
+        SessionType::Wayland => match bus.portal_version(GLOBAL_SHORTCUTS_PORTAL) {
+            Ok(Some(_)) => ShortcutRoute::Portal,
+            Ok(None) => ShortcutRoute::Unavailable(format!(
+                "{} has no GlobalShortcuts portal; shortcuts only work while the window is focused",
+                session.desktop.as_deref().unwrap_or("This Wayland desktop")
+            )),
+            Err(e) => ShortcutRoute::Unavailable(format!(
+                "Could not reach the desktop portal ({}); shortcuts only work while the window is focused",
+                e
+            )),
+        },
+        SessionType::Unknown => {
+            ShortcutRoute::Unavailable("No graphical session detected".to_string())
+        }
+    }
+}
+
+fn probe_global_shortcuts(session: &Session, bus: &dyn SessionBus) -> Capability {
+    match shortcut_route(session, bus) {
+        ShortcutRoute::X11 | ShortcutRoute::Portal => Capability::Available,
+        ShortcutRoute::Unavailable(detail) => Capability::Degraded { detail },
+    }
+}
+
+/// Absolute positions, and with them snap presets, only work on X11.
+fn probe_window_placement(session: &Session) -> Capability {
+    match session.session_type {
+        SessionType::X11 => Capability::Available,
+        SessionType::Wayland => Capability::Unsupported {
+            reason:
+                "Wayland compositors place windows themselves; only the window size is remembered"
+                    .to_string(),
+        },
+        SessionType::Unknown => Capability::Unsupported {
+            reason: "No graphical session detected".to_string(),
+        },
+    }
+}
+
+fn probe_tray(session: &Session, bus: &dyn SessionBus) -> Capability {
+    match bus.name_has_owner(STATUS_NOTIFIER_WATCHER) {
+        Ok(true) => Capability::Available,
+        // X11 desktops may still offer a legacy XEmbed tray.
+        Ok(false) if session.session_type == SessionType::X11 => Capability::Degraded {
+            detail: "No StatusNotifier host; relying on a legacy system tray".to_string(),
+        },
+        Ok(false) => Capability::Unsupported {
+            reason: format!(
+                "{} shows no tray icons; a shell extension such as AppIndicator is needed",
+                session.desktop.as_deref().unwrap_or("This desktop")
+            ),
+        },
+        Err(e) => Capability::Unsupported {
+            reason: format!("Could not reach the session bus: {}", e),
+        },
+    }
+}
+
+pub fn register_capabilities(
+    registry: &CapabilityRegistry,
+    session: Session,
+    bus: Arc<dyn SessionBus>,
+) {
+    let session = Arc::new(session);
+    {
+        let (session, bus) = (session.clone(), bus.clone());
+        registry.register("globalShortcuts", move || {
+            probe_global_shortcuts(&session, bus.as_ref())
+        });
+    }
+    {
+        let session = session.clone();
+        registry.register("windowPositioning", move || {
+            probe_window_placement(&session)
+        });
+    }
+    {
+        let session = session.clone();
+        registry.register("snapPresets", move || probe_window_placement(&session));
+    }
+    registry.register("tray", move || probe_tray(&session, bus.as_ref()));
+}
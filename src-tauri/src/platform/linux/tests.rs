@@ -0,0 +1,143 @@
+use super::*;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+fn session(vars: &[(&str, &str)]) -> Session {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    Session::detect(|name| vars.get(name).cloned())
+}
+
+/// A session bus whose answers are set by the test.
+#[derive(Default)]
+struct FakeBus {
+    shortcuts_portal: Option<u32>,
+    tray_host: AtomicBool,
+    unreachable: bool,
+}
+
+impl SessionBus for FakeBus {
+    fn name_has_owner(&self, name: &str) -> Result<bool, String> {
+        if self.unreachable {
+            return Err("connection refused".to_string());
+        }
+        Ok(name == STATUS_NOTIFIER_WATCHER && self.tray_host.load(Ordering::SeqCst))
+    }
+
+    fn portal_version(&self, interface: &str) -> Result<Option<u32>, String> {
+        if self.unreachable {
+            return Err("connection refused".to_string());
+        }
+        Ok(self
+            .shortcuts_portal
+            .filter(|_| interface == GLOBAL_SHORTCUTS_PORTAL))
+    }
+}
+
+#[test]
+fn test_session_detection() {
+    let wayland = session(&[
+        ("WAYLAND_DISPLAY", "wayland-0"),
+        ("DISPLAY", ":0"),
+        ("XDG_CURRENT_DESKTOP", "ubuntu:GNOME"),
+    ]);
+    assert_eq!(wayland.session_type, SessionType::Wayland);
+    assert_eq!(wayland.desktop.as_deref(), Some("ubuntu"));
+
+    let x11 = session(&[("DISPLAY", ":0"), ("DESKTOP_SESSION", "xfce")]);
+    assert_eq!(x11.session_type, SessionType::X11);
+    assert_eq!(x11.desktop.as_deref(), Some("xfce"));
+
+    assert_eq!(
+        session(&[("XDG_SESSION_TYPE", "wayland")]).session_type,
+        SessionType::Wayland
+    );
+    assert_eq!(
+        session(&[("WAYLAND_DISPLAY", "")]).session_type,
+        SessionType::Unknown
+    );
+}
+
+#[test]
+fn test_shortcuts_use_portal_on_wayland() {
+    let wayland = session(&[
+        ("WAYLAND_DISPLAY", "wayland-0"),
+        ("XDG_CURRENT_DESKTOP", "KDE"),
+    ]);
+    let with_portal = FakeBus {
+        shortcuts_portal: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(
+        shortcut_route(&wayland, &with_portal),
+        ShortcutRoute::Portal
+    );
+
+    let without_portal = FakeBus::default();
+    assert_eq!(
+        probe_global_shortcuts(&wayland, &without_portal),
+        Capability::Degraded {
+            detail:
+                "KDE has no GlobalShortcuts portal; shortcuts only work while the window is focused"
+                    .to_string()
+        }
+    );
+
+    let x11 = session(&[("DISPLAY", ":0")]);
+    assert_eq!(shortcut_route(&x11, &without_portal), ShortcutRoute::X11);
+}
+
+#[test]
+fn test_capabilities_reported_for_wayland() {
+    let bus = Arc::new(FakeBus::default());
+    let registry = CapabilityRegistry::default();
+    register_capabilities(
+        &registry,
+        session(&[
+            ("WAYLAND_DISPLAY", "wayland-0"),
+            ("XDG_CURRENT_DESKTOP", "GNOME"),
+        ]),
+        bus.clone(),
+    );
+
+    let report = registry.snapshot();
+    assert!(matches!(
+        report["windowPositioning"],
+        Capability::Unsupported { .. }
+    ));
+    assert!(matches!(
+        report["snapPresets"],
+        Capability::Unsupported { .. }
+    ));
+    assert!(matches!(
+        report["globalShortcuts"],
+        Capability::Degraded { .. }
+    ));
+    assert!(matches!(report["tray"], Capability::Unsupported { .. }));
+
+    // A tray host appearing after login is picked up on the next refresh.
+    bus.tray_host.store(true, Ordering::SeqCst);
+    let changes = registry.refresh();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].name, "tray");
+    assert_eq!(changes[0].capability, Capability::Available);
+}
+
+#[test]
+fn test_unreachable_bus_is_reported() {
+    let bus = FakeBus {
+        unreachable: true,
+        ..Default::default()
+    };
+    let x11 = session(&[("DISPLAY", ":0")]);
+    assert_eq!(probe_global_shortcuts(&x11, &bus), Capability::Available);
+    assert_eq!(
+        probe_tray(&x11, &bus),
+        Capability::Unsupported {
+            reason: "Could not reach the session bus: connection refused".to_string()
+        }
+    );
+}
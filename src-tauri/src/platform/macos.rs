@@ -0,0 +1,175 @@
+//! Apple Calendar through EventKit. Events go into a calendar of their own,
+//! created next to the default calendar so it syncs the same way (iCloud or
+//! on this Mac only).
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use block2::RcBlock;
+use objc2::rc::Retained;
+use objc2::runtime::{Bool, NSObjectProtocol};
+use objc2::sel;
+use objc2_event_kit::{
+    EKAuthorizationStatus, EKCalendar, EKEntityType, EKEvent, EKEventStore, EKSourceType, EKSpan,
+};
+use objc2_foundation::{NSDate, NSError, NSString, NSURL};
+
+use crate::calendar::{Authorization, CalendarEvent, SystemCalendar};
+
+/// How long `request_access` waits for the user to answer the prompt.
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+fn ns_error(action: &str, error: Retained<NSError>) -> String {
+    format!("Failed to {}: {}", action, error.localizedDescription())
+}
+
+fn ns_date(time: chrono::DateTime<chrono::Utc>) -> Retained<NSDate> {
+    NSDate::dateWithTimeIntervalSince1970(time.timestamp_millis() as f64 / 1000.0)
+}
+
+pub struct EventKitCalendar {
+    // A new store per call would miss changes not yet committed by this one,
+    // and EventKit stores are meant to be long-lived.
+    store: Retained<EKEventStore>,
+}
+
+// EventKit stores may be used from any thread as long as objects fetched
+// from one aren't shared with another store.
+unsafe impl Send for EventKitCalendar {}
+unsafe impl Sync for EventKitCalendar {}
+
+impl EventKitCalendar {
+    pub fn new() -> Self {
+        Self {
+            store: unsafe { EKEventStore::new() },
+        }
+    }
+
+    fn calendar(&self, calendar_id: &str) -> Option<Retained<EKCalendar>> {
+        unsafe {
+            self.store
+                .calendarWithIdentifier(&NSString::from_str(calendar_id))
+        }
+    }
+}
+
+impl SystemCalendar for EventKitCalendar {
+    fn authorization(&self) -> Authorization {
+        let status = unsafe { EKEventStore::authorizationStatusForEntityType(EKEntityType::Event) };
+        match status {
+            EKAuthorizationStatus::NotDetermined => Authorization::NotDetermined,
+            EKAuthorizationStatus::Restricted => Authorization::Restricted,
+            EKAuthorizationStatus::Denied => Authorization::Denied,
+            EKAuthorizationStatus::WriteOnly => Authorization::WriteOnly,
+            _ => Authorization::Authorized,
+        }
+    }
+
+    fn request_access(&self) -> Result<Authorization, String> {
+        let (sender, receiver) = mpsc::channel();
+        let completion = RcBlock::new(move |_granted: Bool, _error: *mut NSError| {
+            let _ = sender.send(());
+        });
+        unsafe {
+            // macOS 14 split access into full and write-only; the old request
+            // asks for what is now full access.
+            if self
+                .store
+                .respondsToSelector(sel!(requestFullAccessToEventsWithCompletion:))
+            {
+                self.store
+                    .requestFullAccessToEventsWithCompletion(RcBlock::as_ptr(&completion));
+            } else {
+                #[allow(deprecated)]
+                self.store.requestAccessToEntityType_completion(
+                    EKEntityType::Event,
+                    RcBlock::as_ptr(&completion),
+                );
+            }
+        }
+        receiver
+            .recv_timeout(PROMPT_TIMEOUT)
+            .map_err(|_| "No answer to the calendar access prompt".to_string())?;
+        Ok(self.authorization())
+    }
+
+    fn calendar_exists(&self, calendar_id: &str) -> Result<bool, String> {
+        Ok(self.calendar(calendar_id).is_some())
+    }
+
+    fn ensure_calendar(&self, name: &str) -> Result<String, String> {
+        unsafe {
+            let calendars = self.store.calendarsForEntityType(EKEntityType::Event);
+            if let Some(calendar) = calendars
+                .iter()
+                .find(|calendar| calendar.title().to_string() == name)
+            {
+                return Ok(calendar.calendarIdentifier().to_string());
+            }
+
+            let source = self
+                .store
+                .defaultCalendarForNewEvents()
+                .and_then(|calendar| calendar.source())
+                .or_else(|| {
+                    self.store
+                        .sources()
+                        .iter()
+                        .find(|source| source.sourceType() == EKSourceType::Local)
+                })
+                .ok_or_else(|| "No calendar account to create the calendar in".to_string())?;
+            let calendar =
+                EKCalendar::calendarForEntityType_eventStore(EKEntityType::Event, &self.store);
+            calendar.setTitle(&NSString::from_str(name));
+            calendar.setSource(Some(&source));
+            self.store
+                .saveCalendar_commit_error(&calendar, true)
+                .map_err(|e| ns_error("create the calendar", e))?;
+            Ok(calendar.calendarIdentifier().to_string())
+        }
+    }
+
+    fn save_event(
+        &self,
+        calendar_id: &str,
+        event_id: Option<&str>,
+        event: &CalendarEvent,
+    ) -> Result<String, String> {
+        let calendar = self
+            .calendar(calendar_id)
+            .ok_or_else(|| "The calendar no longer exists".to_string())?;
+        unsafe {
+            let existing =
+                event_id.and_then(|id| self.store.eventWithIdentifier(&NSString::from_str(id)));
+            let ek_event = existing.unwrap_or_else(|| EKEvent::eventWithEventStore(&self.store));
+            ek_event.setCalendar(Some(&calendar));
+            ek_event.setTitle(Some(&NSString::from_str(&event.title)));
+            ek_event.setNotes(event.notes.as_deref().map(NSString::from_str).as_deref());
+            ek_event.setURL(NSURL::URLWithString(&NSString::from_str(&event.url())).as_deref());
+            ek_event.setAllDay(false);
+            ek_event.setStartDate(Some(&ns_date(event.start)));
+            ek_event.setEndDate(Some(&ns_date(event.end)));
+            self.store
+                .saveEvent_span_commit_error(&ek_event, EKSpan::ThisEvent, true)
+                .map_err(|e| ns_error("save the event", e))?;
+            ek_event
+                .eventIdentifier()
+                .map(|id| id.to_string())
+                .ok_or_else(|| "The saved event has no identifier".to_string())
+        }
+    }
+
+    fn remove_event(&self, _calendar_id: &str, event_id: &str) -> Result<(), String> {
+        unsafe {
+            let Some(event) = self
+                .store
+                .eventWithIdentifier(&NSString::from_str(event_id))
+            else {
+                return Ok(());
+            };
+            self.store
+                .removeEvent_span_commit_error(&event, EKSpan::ThisEvent, true)
+                .map_err(|e| ns_error("remove the event", e))
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! The Windows appointment store, which the Calendar app and Outlook show.
+//! The app only sees calendars it created itself, so the dedicated calendar
+//! can't collide with one of the user's.
+//!
+//! Also toasts whose actions work while the app isn't running: they launch
+//! it through a per-user protocol, registered along with the AUMID the
+//! toasts are shown under.
+
+use std::path::Path;
+use std::process::Command;
+
+use windows::core::{Error, HSTRING};
+use windows::ApplicationModel::Appointments::{
+    Appointment, AppointmentCalendar, AppointmentManager, AppointmentStore,
+    AppointmentStoreAccessType,
+};
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::Foundation::{DateTime, TimeSpan, Uri};
+use windows::Win32::Foundation::E_ACCESSDENIED;
+use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
+
+use crate::calendar::{Authorization, CalendarEvent, SystemCalendar};
+use crate::capabilities::Capability;
+use crate::notifications::activation::{ACTION_FLAG, PROTOCOL};
+
+/// Names the app in the notification center; toasts are shown under it.
+pub const AUMID: &str = "yutotnh.YuTodo";
+const CLASSES: &str = r"HKCU\Software\Classes";
+
+/// Seconds from 1601-01-01, where WinRT time starts, to the Unix epoch.
+const UNIX_EPOCH_OFFSET_SECS: i64 = 11_644_473_600;
+const TICKS_PER_SEC: i64 = 10_000_000;
+
+fn win_error(action: &str, error: Error) -> String {
+    format!("Failed to {}: {}", action, error.message())
+}
+
+fn win_time(time: chrono::DateTime<chrono::Utc>) -> DateTime {
+    DateTime {
+        UniversalTime: (time.timestamp() + UNIX_EPOCH_OFFSET_SECS) * TICKS_PER_SEC
+            + time.timestamp_subsec_nanos() as i64 / 100,
+    }
+}
+
+/// WinRT returns a missing object as an error without a failure code.
+fn optional<T>(result: windows::core::Result<T>) -> windows::core::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.code().is_ok() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub struct AppointmentsCalendar;
+
+impl AppointmentsCalendar {
+    fn store(&self) -> windows::core::Result<AppointmentStore> {
+        AppointmentManager::RequestStoreAsync(AppointmentStoreAccessType::AppCalendarsReadWrite)?
+            .get()
+    }
+
+    fn calendar(&self, calendar_id: &str) -> Result<Option<AppointmentCalendar>, String> {
+        let store = self
+            .store()
+            .map_err(|e| win_error("open the calendar", e))?;
+        optional(
+            store
+                .GetAppointmentCalendarAsync(&HSTRING::from(calendar_id))
+                .and_then(|operation| operation.get()),
+        )
+        .map_err(|e| win_error("open the calendar", e))
+    }
+
+    fn appointment(
+        calendar: &AppointmentCalendar,
+        event_id: &str,
+    ) -> Result<Option<Appointment>, String> {
+        optional(
+            calendar
+                .GetAppointmentAsync(&HSTRING::from(event_id))
+                .and_then(|operation| operation.get()),
+        )
+        .map_err(|e| win_error("find the event", e))
+    }
+}
+
+impl SystemCalendar for AppointmentsCalendar {
+    /// Windows has no prompt for calendars; access follows the "Calendar"
+    /// privacy setting, and opening the store is how to find out.
+    fn authorization(&self) -> Authorization {
+        match self.store() {
+            Ok(_) => Authorization::Authorized,
+            Err(e) if e.code() == E_ACCESSDENIED => Authorization::Denied,
+            Err(_) => Authorization::Restricted,
+        }
+    }
+
+    fn request_access(&self) -> Result<Authorization, String> {
+        Ok(self.authorization())
+    }
+
+    fn calendar_exists(&self, calendar_id: &str) -> Result<bool, String> {
+        Ok(self.calendar(calendar_id)?.is_some())
+    }
+
+    fn ensure_calendar(&self, name: &str) -> Result<String, String> {
+        let create = || -> windows::core::Result<HSTRING> {
+            let store = self.store()?;
+            for calendar in store.FindAppointmentCalendarsAsync()?.get()? {
+                if calendar.DisplayName()? == *name {
+                    return calendar.LocalId();
+                }
+            }
+            store
+                .CreateAppointmentCalendarAsync(&HSTRING::from(name))?
+                .get()?
+                .LocalId()
+        };
+        create()
+            .map(|id| id.to_string())
+            .map_err(|e| win_error("create the calendar", e))
+    }
+
+    fn save_event(
+        &self,
+        calendar_id: &str,
+        event_id: Option<&str>,
+        event: &CalendarEvent,
+    ) -> Result<String, String> {
+        let calendar = self
+            .calendar(calendar_id)?
+            .ok_or_else(|| "The calendar no longer exists".to_string())?;
+        let existing = match event_id {
+            Some(id) => Self::appointment(&calendar, id)?,
+            None => None,
+        };
+        let save = || -> windows::core::Result<HSTRING> {
+            let appointment = match existing {
+                Some(appointment) => appointment,
+                None => Appointment::new()?,
+            };
+            appointment.SetSubject(&HSTRING::from(&event.title))?;
+            appointment.SetDetails(&HSTRING::from(event.notes.as_deref().unwrap_or_default()))?;
+            appointment.SetAllDay(false)?;
+            appointment.SetStartTime(win_time(event.start))?;
+            appointment.SetDuration(TimeSpan {
+                Duration: (event.end - event.start).num_seconds() * TICKS_PER_SEC,
+            })?;
+            appointment.SetUri(&Uri::CreateUri(&HSTRING::from(event.url()))?)?;
+            calendar.SaveAppointmentAsync(&appointment)?.get()?;
+            appointment.LocalId()
+        };
+        save()
+            .map(|id| id.to_string())
+            .map_err(|e| win_error("save the event", e))
+    }
+
+    fn remove_event(&self, calendar_id: &str, event_id: &str) -> Result<(), String> {
+        let Some(calendar) = self.calendar(calendar_id)? else {
+            return Ok(());
+        };
+        if Self::appointment(&calendar, event_id)?.is_none() {
+            return Ok(());
+        }
+        calendar
+            .DeleteAppointmentAsync(&HSTRING::from(event_id))
+            .and_then(|operation| operation.get())
+            .map_err(|e| win_error("remove the event", e))
+    }
+}
+
+/// Runs `reg` and returns what it printed.
+pub fn reg(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("reg")
+        .args(args)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format!("reg {} exited with {}", args[0], output.status))
+    }
+}
+
+fn aumid_key() -> String {
+    format!(r"{}\AppUserModelId\{}", CLASSES, AUMID)
+}
+
+fn action_command(exe: &Path) -> String {
+    format!("\"{}\" {} \"%1\"", exe.display(), ACTION_FLAG)
+}
+
+pub fn register_notification_activation(exe: &Path) -> Result<(), String> {
+    reg(&[
+        "add",
+        &aumid_key(),
+        "/v",
+        "DisplayName",
+        "/d",
+        "YuTodo",
+        "/f",
+    ])?;
+    let protocol = format!(r"{}\{}", CLASSES, PROTOCOL);
+    reg(&[
+        "add",
+        &protocol,
+        "/ve",
+        "/d",
+        "URL:YuTodo notification action",
+        "/f",
+    ])?;
+    reg(&["add", &protocol, "/v", "URL Protocol", "/d", "", "/f"])?;
+    reg(&[
+        "add",
+        &format!(r"{}\shell\open\command", protocol),
+        "/ve",
+        "/d",
+        &action_command(exe),
+        "/f",
+    ])
+    .map(|_| ())
+}
+
+pub fn notification_activation(exe: &Path) -> Capability {
+    let command = reg(&[
+        "query",
+        &format!(r"{}\{}\shell\open\command", CLASSES, PROTOCOL),
+        "/ve",
+    ]);
+    match (reg(&["query", &aumid_key()]), command) {
+        (Ok(_), Ok(command)) if command.contains(&action_command(exe)) => Capability::Available,
+        (Ok(_), Ok(_)) => Capability::Degraded {
+            detail: "Notification actions launch another copy of the app; register them again"
+                .to_string(),
+        },
+        _ => Capability::Unsupported {
+            reason: "Notification actions are not registered, so they do nothing while the app \
+                     is closed"
+                .to_string(),
+        },
+    }
+}
+
+/// Shows a toast from `xml` under [`AUMID`].
+pub fn show_toast(xml: &str) -> Result<(), String> {
+    let show = || -> windows::core::Result<()> {
+        let document = XmlDocument::new()?;
+        document.LoadXml(&HSTRING::from(xml))?;
+        let toast = ToastNotification::CreateToastNotification(&document)?;
+        ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(AUMID))?.Show(&toast)
+    };
+    show().map_err(|e| win_error("show the notification", e))
+}
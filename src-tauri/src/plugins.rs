@@ -0,0 +1,481 @@
+//! User plugins: scripts in `<config dir>/plugins/<name>/` that subscribe to
+//! backend events. Each gets the event as JSON on stdin and may print a JSON
+//! array of suggested mutations, which reach the frontend as
+//! `plugin-suggestions` for the user to accept. Plugins never write to the
+//! store themselves.
+//!
+//! A plugin directory holds a `plugin.toml` manifest:
+//!
+//! ```toml
+//! name = "auto-tagger"
+//! command = ["python3", "main.py"]
+//! events = ["todo-updated", "local-api-todo-*"]
+//! ```
+//!
+//! Nothing runs unless `allow_plugins` is set and the plugin is listed in
+//! `enabled_plugins`.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::events;
+use crate::protocol::EventPayload;
+use crate::settings::{self, Settings, SettingsState};
+use crate::trace;
+use crate::trust::{self, Feature};
+use crate::types::Priority;
+
+#[cfg(test)]
+mod tests;
+
+pub const PLUGINS_DIR: &str = "plugins";
+pub const MANIFEST_FILE: &str = "plugin.toml";
+/// A plugin still running after this is killed and its output dropped.
+const RUN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Output past this is cut off, which makes it invalid JSON.
+const MAX_OUTPUT_BYTES: u64 = 1024 * 1024;
+/// Events plugins can't subscribe to, so their own results don't feed back.
+const OWN_EVENT_PREFIX: &str = "plugin-";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("invalid manifest: {0}")]
+    Manifest(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("timed out after {} seconds", RUN_TIMEOUT.as_secs())]
+    Timeout,
+    #[error("exited with {status}: {stderr}")]
+    Failed { status: String, stderr: String },
+    #[error("invalid output: {0}")]
+    Output(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Program and arguments, run in the plugin's directory. A program given
+    /// as a path is relative to that directory.
+    pub command: Vec<String>,
+    /// Event names, or prefixes ending in `*`.
+    pub events: Vec<String>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self, PluginError> {
+        let manifest: Manifest =
+            toml::from_str(text).map_err(|e| PluginError::Manifest(e.message().to_string()))?;
+        let valid_name = !manifest.name.is_empty()
+            && manifest
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(PluginError::Manifest(format!(
+                "name '{}' may only use letters, digits, '-' and '_'",
+                manifest.name
+            )));
+        }
+        if manifest
+            .command
+            .first()
+            .is_none_or(|program| program.is_empty())
+        {
+            return Err(PluginError::Manifest("command is empty".to_string()));
+        }
+        if manifest.events.is_empty() {
+            return Err(PluginError::Manifest(
+                "events must list at least one event".to_string(),
+            ));
+        }
+        Ok(manifest)
+    }
+
+    pub fn subscribes_to(&self, event: &str) -> bool {
+        !event.starts_with(OWN_EVENT_PREFIX)
+            && self
+                .events
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => event.starts_with(prefix),
+                    None => pattern == event,
+                })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plugin {
+    pub manifest: Manifest,
+    pub dir: PathBuf,
+}
+
+/// A plugin directory that could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadError {
+    pub dir: PathBuf,
+    pub message: String,
+}
+
+/// Reads the manifest of every subdirectory of `dir`. A missing directory
+/// just means no plugins.
+pub fn load(dir: &Path) -> (Vec<Plugin>, Vec<LoadError>) {
+    let mut plugins: Vec<Plugin> = Vec::new();
+    let mut errors = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (plugins, errors);
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    for dir in dirs {
+        let manifest = std::fs::read_to_string(dir.join(MANIFEST_FILE))
+            .map_err(PluginError::from)
+            .and_then(|text| Manifest::parse(&text));
+        match manifest {
+            Ok(manifest) if plugins.iter().any(|p| p.manifest.name == manifest.name) => errors
+                .push(LoadError {
+                    dir,
+                    message: format!("another plugin is already named '{}'", manifest.name),
+                }),
+            Ok(manifest) => plugins.push(Plugin { manifest, dir }),
+            Err(e) => errors.push(LoadError {
+                dir,
+                message: e.to_string(),
+            }),
+        }
+    }
+    (plugins, errors)
+}
+
+/// The plugins that should receive `event` under `settings`.
+pub fn subscribers<'a>(
+    plugins: &'a [Plugin],
+    settings: &'a Settings,
+    event: &'a str,
+) -> impl Iterator<Item = &'a Plugin> + 'a {
+    plugins.iter().filter(move |plugin| {
+        settings.allow_plugins
+            && settings.enabled_plugins.contains(&plugin.manifest.name)
+            && plugin.manifest.subscribes_to(event)
+    })
+}
+
+/// A change a plugin proposes; the frontend decides whether to apply it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "op",
+    rename_all = "lowercase",
+    rename_all_fields = "camelCase",
+    deny_unknown_fields
+)]
+pub enum SuggestedMutation {
+    Create {
+        title: String,
+        #[serde(default)]
+        priority: Priority,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    Update {
+        todo_id: String,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        completed: Option<bool>,
+        #[serde(default)]
+        priority: Option<Priority>,
+        #[serde(default)]
+        tags: Option<Vec<String>>,
+    },
+    Delete {
+        todo_id: String,
+    },
+}
+
+/// What a plugin reads on stdin.
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInput<'a> {
+    pub event: &'a str,
+    pub payload: &'a serde_json::Value,
+}
+
+/// Parses a plugin's stdout; printing nothing means no suggestions.
+pub fn parse_output(output: &[u8]) -> Result<Vec<SuggestedMutation>, PluginError> {
+    if output.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(output)?)
+}
+
+/// Runs the plugin with `input` on stdin and returns its stdout.
+fn run(plugin: &Plugin, input: &[u8]) -> Result<Vec<u8>, PluginError> {
+    let (program, args) = plugin
+        .manifest
+        .command
+        .split_first()
+        .ok_or_else(|| PluginError::Manifest("command is empty".to_string()))?;
+    let program = if program.contains('/') || program.contains('\\') {
+        plugin.dir.join(program)
+    } else {
+        PathBuf::from(program)
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(&plugin.dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Real time, not the app's clock: this limits a real process.
+    let deadline = Instant::now() + RUN_TIMEOUT;
+    // Pipes are drained on their own threads so a chatty plugin can't block.
+    // Output past the cap is read and dropped rather than left in the pipe.
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = (&mut pipe).take(MAX_OUTPUT_BYTES).read_to_end(&mut buffer);
+                let _ = std::io::copy(&mut pipe, &mut std::io::sink());
+            }
+            buffer
+        })
+    };
+    let stdout = drain(
+        child
+            .stdout
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+    let stderr = drain(
+        child
+            .stderr
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+    // Written from its own thread too, so a plugin that never reads stdin
+    // still runs into the deadline. One that closes it early isn't an error.
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_vec();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(PluginError::Timeout);
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Err(PluginError::Failed {
+            status: status.to_string(),
+            stderr: String::from_utf8_lossy(&stderr).trim().to_string(),
+        });
+    }
+    Ok(stdout)
+}
+
+/// Payload of the `plugin-suggestions` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginSuggestions {
+    pub plugin: String,
+    pub event: String,
+    pub mutations: Vec<SuggestedMutation>,
+}
+
+impl EventPayload for PluginSuggestions {}
+
+/// Payload of the `plugin-failed` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginFailed {
+    pub plugin: String,
+    pub event: String,
+    pub error: String,
+}
+
+impl EventPayload for PluginFailed {}
+
+struct Job {
+    plugin: Plugin,
+    event: String,
+    payload: serde_json::Value,
+}
+
+fn run_job(app: &AppHandle, job: Job) {
+    let input = PluginInput {
+        event: &job.event,
+        payload: &job.payload,
+    };
+    let input = serde_json::to_vec(&input).unwrap_or_default();
+    let plugin = job.plugin.manifest.name.clone();
+    match run(&job.plugin, &input).and_then(|output| parse_output(&output)) {
+        Ok(mutations) if mutations.is_empty() => {}
+        Ok(mutations) => {
+            let _ = events::emit(
+                app,
+                "plugin-suggestions",
+                PluginSuggestions {
+                    plugin,
+                    event: job.event,
+                    mutations,
+                },
+            );
+        }
+        Err(e) => {
+            let _ = events::emit(
+                app,
+                "plugin-failed",
+                PluginFailed {
+                    plugin,
+                    event: job.event,
+                    error: e.to_string(),
+                },
+            );
+        }
+    }
+}
+
+/// Loaded plugins and the worker that runs them, one at a time.
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: RwLock<(Vec<Plugin>, Vec<LoadError>)>,
+    worker: Mutex<Option<Sender<Job>>>,
+}
+
+impl PluginHost {
+    /// Hands `event` to the worker for each subscribed plugin. Cheap when
+    /// plugins are off, since every emitted event passes through here.
+    pub fn dispatch(&self, app: &AppHandle, event: &str, payload: impl Serialize) {
+        let Some(settings) = app.try_state::<SettingsState>().map(|s| s.get()) else {
+            return;
+        };
+        if !settings.allow_plugins || !trust::allows(app, Feature::Plugins) {
+            return;
+        }
+        let plugins = self.plugins.read().unwrap_or_else(|e| e.into_inner());
+        let jobs: Vec<Plugin> = subscribers(&plugins.0, &settings, event).cloned().collect();
+        if jobs.is_empty() {
+            return;
+        }
+        let payload = serde_json::to_value(payload).unwrap_or_default();
+        let mut worker = self.worker.lock().unwrap_or_else(|e| e.into_inner());
+        let sender = worker.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let app = app.clone();
+            std::thread::spawn(move || {
+                for job in receiver {
+                    run_job(&app, job);
+                }
+            });
+            sender
+        });
+        for plugin in jobs {
+            let _ = sender.send(Job {
+                plugin,
+                event: event.to_string(),
+                payload: payload.clone(),
+            });
+        }
+    }
+
+    fn reload(&self, dir: &Path) {
+        *self.plugins.write().unwrap_or_else(|e| e.into_inner()) = load(dir);
+    }
+}
+
+fn plugins_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(settings::config_dir(app)
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))?
+        .join(PLUGINS_DIR))
+}
+
+/// Loads the plugins found at startup. Later changes need `reload_plugins`.
+pub fn load_at_startup(app: &AppHandle) {
+    match plugins_dir(app) {
+        Ok(dir) => app.state::<PluginHost>().reload(&dir),
+        Err(e) => trace::log(e),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub events: Vec<String>,
+    pub dir: PathBuf,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginList {
+    /// Whether `allow_plugins` is on; when off no plugin runs.
+    pub allowed: bool,
+    /// Whether this profile may run plugins; see `grant_trust`.
+    pub trusted: bool,
+    pub plugins: Vec<PluginInfo>,
+    pub errors: Vec<LoadError>,
+}
+
+#[tauri::command]
+pub fn list_plugins(
+    app: AppHandle,
+    host: State<'_, PluginHost>,
+    settings: State<'_, SettingsState>,
+) -> PluginList {
+    let settings = settings.get();
+    let loaded = host.plugins.read().unwrap_or_else(|e| e.into_inner());
+    PluginList {
+        allowed: settings.allow_plugins,
+        trusted: trust::allows(&app, Feature::Plugins),
+        plugins: loaded
+            .0
+            .iter()
+            .map(|plugin| PluginInfo {
+                name: plugin.manifest.name.clone(),
+                description: plugin.manifest.description.clone(),
+                events: plugin.manifest.events.clone(),
+                dir: plugin.dir.clone(),
+                enabled: settings.enabled_plugins.contains(&plugin.manifest.name),
+            })
+            .collect(),
+        errors: loaded.1.clone(),
+    }
+}
+
+/// Re-reads the plugins directory, e.g. after the user added a plugin.
+#[tauri::command]
+pub fn reload_plugins(
+    app: AppHandle,
+    host: State<'_, PluginHost>,
+    settings: State<'_, SettingsState>,
+) -> Result<PluginList, String> {
+    host.reload(&plugins_dir(&app)?);
+    Ok(list_plugins(app, host, settings))
+}
@@ -0,0 +1,218 @@
+use super::*;
+
+fn manifest(name: &str, events: &[&str]) -> Manifest {
+    Manifest {
+        name: name.to_string(),
+        description: None,
+        command: vec!["true".to_string()],
+        events: events.iter().map(|e| e.to_string()).collect(),
+    }
+}
+
+fn plugin(name: &str, events: &[&str]) -> Plugin {
+    Plugin {
+        manifest: manifest(name, events),
+        dir: PathBuf::from(name),
+    }
+}
+
+#[test]
+fn test_manifest_parsing() {
+    let parsed = Manifest::parse(
+        r#"
+        name = "auto-tagger"
+        description = "Tags todos by keyword"
+        command = ["python3", "main.py"]
+        events = ["todo-updated"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(parsed.name, "auto-tagger");
+    assert_eq!(parsed.command, vec!["python3", "main.py"]);
+    assert_eq!(parsed.description.as_deref(), Some("Tags todos by keyword"));
+
+    for (text, expected) in [
+        (
+            r#"name = "x y"
+            command = ["a"]
+            events = ["e"]"#,
+            "may only use letters",
+        ),
+        (
+            r#"name = "x"
+            command = []
+            events = ["e"]"#,
+            "command is empty",
+        ),
+        (
+            r#"name = "x"
+            command = ["a"]
+            events = []"#,
+            "at least one event",
+        ),
+        (
+            r#"name = "x"
+            command = ["a"]
+            events = ["e"]
+            mutate = true"#,
+            "unknown field",
+        ),
+        (r#"name = "x""#, "missing field"),
+    ] {
+        let error = Manifest::parse(text).unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", text, error);
+    }
+}
+
+#[test]
+fn test_dispatch_follows_subscriptions_and_flags() {
+    let plugins = vec![
+        plugin("exact", &["todo-updated"]),
+        plugin("prefix", &["local-api-todo-*"]),
+        plugin("greedy", &["*"]),
+    ];
+    let mut settings = Settings {
+        allow_plugins: true,
+        enabled_plugins: vec![
+            "exact".to_string(),
+            "prefix".to_string(),
+            "greedy".to_string(),
+        ],
+        ..Settings::default()
+    };
+    let names = |settings: &Settings, event: &str| {
+        subscribers(&plugins, settings, event)
+            .map(|p| p.manifest.name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(&settings, "todo-updated"), vec!["exact", "greedy"]);
+    assert_eq!(
+        names(&settings, "local-api-todo-created"),
+        vec!["prefix", "greedy"]
+    );
+    assert_eq!(names(&settings, "todo-updated-later"), vec!["greedy"]);
+    // Plugin results never loop back into plugins.
+    assert!(names(&settings, "plugin-suggestions").is_empty());
+
+    settings.enabled_plugins.retain(|name| name != "greedy");
+    assert_eq!(names(&settings, "todo-updated"), vec!["exact"]);
+
+    settings.allow_plugins = false;
+    assert!(names(&settings, "todo-updated").is_empty());
+}
+
+#[test]
+fn test_load_reports_broken_and_duplicate_plugins() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |sub: &str, manifest: &str| {
+        std::fs::create_dir_all(dir.path().join(sub)).unwrap();
+        std::fs::write(dir.path().join(sub).join(MANIFEST_FILE), manifest).unwrap();
+    };
+    let valid = "name = \"tagger\"\ncommand = [\"./run\"]\nevents = [\"todo-updated\"]\n";
+    write("a", valid);
+    write("b", valid);
+    write("c", "name = ");
+    std::fs::create_dir_all(dir.path().join("d")).unwrap();
+
+    let (plugins, errors) = load(dir.path());
+    assert_eq!(plugins.len(), 1);
+    assert_eq!(plugins[0].dir, dir.path().join("a"));
+    let failed: Vec<_> = errors.iter().map(|e| e.dir.clone()).collect();
+    assert_eq!(
+        failed,
+        vec![
+            dir.path().join("b"),
+            dir.path().join("c"),
+            dir.path().join("d")
+        ]
+    );
+    assert!(load(&dir.path().join("missing")).0.is_empty());
+}
+
+#[test]
+fn test_output_is_a_list_of_suggestions() {
+    assert!(parse_output(b"  \n").unwrap().is_empty());
+    assert_eq!(
+        parse_output(br#"[{"op": "update", "todoId": "a", "tags": ["work"]}, {"op": "delete", "todoId": "b"}]"#)
+            .unwrap(),
+        vec![
+            SuggestedMutation::Update {
+                todo_id: "a".to_string(),
+                title: None,
+                completed: None,
+                priority: None,
+                tags: Some(vec!["work".to_string()]),
+            },
+            SuggestedMutation::Delete {
+                todo_id: "b".to_string()
+            },
+        ]
+    );
+    assert!(parse_output(br#"[{"op": "truncate"}]"#).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_passes_event_on_stdin() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("echo.sh"),
+        "cat > input.json\nprintf '[{\"op\":\"create\",\"title\":\"Seen\"}]'\n",
+    )
+    .unwrap();
+    let plugin = Plugin {
+        manifest: Manifest {
+            command: vec!["sh".to_string(), "echo.sh".to_string()],
+            ..manifest("echo", &["*"])
+        },
+        dir: dir.path().to_path_buf(),
+    };
+    let output = run(&plugin, b"{\"event\":\"x\"}").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("input.json")).unwrap(),
+        "{\"event\":\"x\"}"
+    );
+    assert_eq!(
+        parse_output(&output).unwrap(),
+        vec![SuggestedMutation::Create {
+            title: "Seen".to_string(),
+            priority: Priority::default(),
+            tags: Vec::new(),
+        }]
+    );
+
+    let failing = Plugin {
+        manifest: Manifest {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo oops >&2; exit 3".to_string(),
+            ],
+            ..manifest("fail", &["*"])
+        },
+        dir: dir.path().to_path_buf(),
+    };
+    let error = run(&failing, b"").unwrap_err().to_string();
+    assert!(error.contains("oops"), "{}", error);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_survives_unread_input_and_oversized_output() {
+    let dir = tempfile::tempdir().unwrap();
+    // Fills stdout before touching stdin, and writes past the output cap.
+    let plugin = Plugin {
+        manifest: Manifest {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "head -c 2000000 /dev/zero; cat > /dev/null".to_string(),
+            ],
+            ..manifest("flood", &["*"])
+        },
+        dir: dir.path().to_path_buf(),
+    };
+    let output = run(&plugin, &vec![b' '; 1_000_000]).unwrap();
+    assert_eq!(output.len() as u64, MAX_OUTPUT_BYTES);
+}
@@ -0,0 +1,238 @@
+//! Privacy mode for screen sharing: one switch that keeps private todos,
+//! descriptions and completed history out of every window. Query and
+//! subscription results are filtered here before they are sent, so hidden
+//! content never reaches a webview while the mode is on.
+//!
+//! `set_privacy_mode` and the optional `[backend.privacy] toggle_shortcut`
+//! both announce changes with a sticky `privacy-mode-changed` event, which
+//! windows opened later read back through `get_sticky_events`.
+
+use std::sync::Mutex;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::clock::{Clock, ClockState};
+use crate::command_registry::{Category, CommandDescriptor, CommandRegistry, EnabledWhen};
+use crate::events;
+use crate::live_query;
+use crate::protocol::EventPayload;
+use crate::settings::{Settings, SettingsReactions};
+use crate::storage::Storage;
+use crate::trace;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+/// Shown instead of a hidden description.
+pub const MASK: &str = "•••";
+
+/// What privacy mode hides while it is on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PrivacyRules {
+    /// Todos with any of these tags are left out; matched case-insensitively.
+    pub private_tags: Vec<String>,
+    pub hide_descriptions: bool,
+    pub hide_completed: bool,
+}
+
+impl Default for PrivacyRules {
+    fn default() -> Self {
+        Self {
+            private_tags: vec!["private".to_string()],
+            hide_descriptions: true,
+            hide_completed: true,
+        }
+    }
+}
+
+/// Payload of `privacy-mode-changed`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PrivacyMode {
+    pub enabled: bool,
+    pub rules: PrivacyRules,
+}
+
+impl EventPayload for PrivacyMode {}
+
+/// A result of an export or clipboard command, with a warning when privacy
+/// mode left something out of it.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Redacted<T> {
+    pub value: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl PrivacyMode {
+    /// Whether `todo` is left out entirely.
+    pub fn hides(&self, todo: &Todo) -> bool {
+        self.enabled
+            && ((self.rules.hide_completed && todo.completed)
+                || todo.tags.iter().any(|tag| {
+                    self.rules
+                        .private_tags
+                        .iter()
+                        .any(|private| private.eq_ignore_ascii_case(tag))
+                }))
+    }
+
+    fn masks_description(&self, todo: &Todo) -> bool {
+        self.enabled
+            && self.rules.hide_descriptions
+            && todo.description.as_deref().is_some_and(|d| !d.is_empty())
+    }
+
+    /// `todo` as windows may see it, or `None` if it is hidden.
+    pub fn redact(&self, todo: &Todo) -> Option<Todo> {
+        if self.hides(todo) {
+            return None;
+        }
+        let mut todo = todo.clone();
+        if self.masks_description(&todo) {
+            todo.description = Some(MASK.to_string());
+            todo.languages.description = None;
+        }
+        Some(todo)
+    }
+
+    /// Redacts every todo, with a warning saying what was hidden or masked.
+    pub fn apply(&self, todos: Vec<Todo>) -> Redacted<Vec<Todo>> {
+        let total = todos.len();
+        let masked = todos
+            .iter()
+            .filter(|todo| !self.hides(todo) && self.masks_description(todo))
+            .count();
+        let value: Vec<Todo> = todos.iter().filter_map(|todo| self.redact(todo)).collect();
+        let hidden = total - value.len();
+        let warning = (hidden > 0 || masked > 0).then(|| {
+            format!(
+                "Privacy mode is on: {} todo(s) left out and {} description(s) masked",
+                hidden, masked
+            )
+        });
+        Redacted { value, warning }
+    }
+}
+
+#[derive(Default)]
+pub struct PrivacyState {
+    mode: Mutex<PrivacyMode>,
+}
+
+impl PrivacyState {
+    pub fn get(&self) -> PrivacyMode {
+        self.mode.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set(&self, mode: PrivacyMode) {
+        *self.mode.lock().unwrap_or_else(|e| e.into_inner()) = mode;
+    }
+}
+
+/// Stores `mode`, re-filters every live query and tells all windows.
+fn change(app: &AppHandle, mode: PrivacyMode) -> Result<(), String> {
+    app.state::<PrivacyState>().set(mode.clone());
+    let storage = app.state::<Storage>();
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    live_query::notify_privacy_changed(app, &mode, &todos, app.state::<ClockState>().today());
+    let _ = events::emit_sticky(app, "privacy-mode-changed", mode);
+    Ok(())
+}
+
+/// Turns privacy mode on or off with the current rules, for the shortcut.
+pub fn toggle(app: &AppHandle) {
+    let mut mode = app.state::<PrivacyState>().get();
+    mode.enabled = !mode.enabled;
+    if let Err(e) = change(app, mode) {
+        trace::log(e);
+    }
+}
+
+#[cfg(desktop)]
+fn register_shortcut(app: &AppHandle, shortcut: &str) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+    app.global_shortcut()
+        .on_shortcut(shortcut, |app, _, event| {
+            if event.state == ShortcutState::Pressed {
+                toggle(app);
+            }
+        })
+        .map_err(|e| format!("Failed to register shortcut '{}': {}", shortcut, e))
+}
+
+#[cfg(desktop)]
+fn unregister_shortcut(app: &AppHandle, shortcut: &str) {
+    use tauri_plugin_global_shortcut::GlobalShortcutExt;
+    if let Err(e) = app.global_shortcut().unregister(shortcut) {
+        trace::log(format!(
+            "Failed to unregister shortcut '{}': {}",
+            shortcut, e
+        ));
+    }
+}
+
+/// Registers the toggle shortcut from settings, if one is set.
+#[cfg_attr(not(desktop), allow(unused_variables))]
+pub fn register_toggle_shortcut(app: &AppHandle, settings: &Settings) {
+    #[cfg(desktop)]
+    if let Some(shortcut) = &settings.privacy.toggle_shortcut {
+        if let Err(e) = register_shortcut(app, shortcut) {
+            trace::log(e);
+        }
+    }
+}
+
+pub fn register_commands(registry: &CommandRegistry) {
+    registry.register(
+        CommandDescriptor {
+            id: "togglePrivacyMode",
+            title_key: "commandPalette.commands.view.togglePrivacyMode.title",
+            default_title: "Toggle Privacy Mode",
+            category: Category::View,
+            default_binding: None,
+            enabled_when: EnabledWhen::Always,
+            command: Some("set_privacy_mode"),
+        },
+        |app, _| {
+            toggle(app);
+            Ok(serde_json::Value::Null)
+        },
+    );
+}
+
+#[cfg_attr(not(desktop), allow(unused_variables))]
+pub fn register_settings_reactions(app: &AppHandle, reactions: &SettingsReactions) {
+    let app = app.clone();
+    reactions.register("privacy", &["privacy"], move |old, new| {
+        #[cfg(desktop)]
+        if let Some(shortcut) = &old.privacy.toggle_shortcut {
+            unregister_shortcut(&app, shortcut);
+        }
+        register_toggle_shortcut(&app, new);
+    });
+}
+
+/// Turns privacy mode on or off for every window.
+#[tauri::command]
+pub fn set_privacy_mode(
+    enabled: bool,
+    rules: PrivacyRules,
+    app: AppHandle,
+) -> Result<PrivacyMode, String> {
+    let mode = PrivacyMode { enabled, rules };
+    change(&app, mode.clone())?;
+    Ok(mode)
+}
+
+#[tauri::command]
+pub fn get_privacy_mode(privacy: State<'_, PrivacyState>) -> PrivacyMode {
+    privacy.get()
+}
@@ -0,0 +1,63 @@
+use super::*;
+use chrono::{TimeZone, Utc};
+
+fn todo(id: &str, tags: &[&str], completed: bool, description: Option<&str>) -> Todo {
+    let at = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo {
+        description: description.map(str::to_string),
+        completed,
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Todo::sample(id, format!("Todo {}", id), at)
+    }
+}
+
+fn on(rules: PrivacyRules) -> PrivacyMode {
+    PrivacyMode {
+        enabled: true,
+        rules,
+    }
+}
+
+#[test]
+fn test_disabled_mode_changes_nothing() {
+    let todos = vec![todo("a", &["private"], true, Some("secret"))];
+    let redacted = PrivacyMode::default().apply(todos.clone());
+    assert_eq!(redacted.value, todos);
+    assert_eq!(redacted.warning, None);
+}
+
+#[test]
+fn test_default_rules_hide_private_and_completed_and_mask_descriptions() {
+    let todos = vec![
+        todo("a", &["PRIVATE"], false, None),
+        todo("b", &[], true, None),
+        todo("c", &["work"], false, Some("call the bank")),
+        todo("d", &[], false, Some("")),
+    ];
+    let redacted = on(PrivacyRules::default()).apply(todos);
+
+    let ids: Vec<_> = redacted.value.iter().map(|t| t.id.as_str()).collect();
+    assert_eq!(ids, ["c", "d"]);
+    assert_eq!(redacted.value[0].description.as_deref(), Some(MASK));
+    assert_eq!(redacted.value[1].description.as_deref(), Some(""));
+    assert_eq!(
+        redacted.warning.as_deref(),
+        Some("Privacy mode is on: 2 todo(s) left out and 1 description(s) masked")
+    );
+}
+
+#[test]
+fn test_rules_can_keep_completed_and_descriptions() {
+    let mode = on(PrivacyRules {
+        private_tags: vec!["hr".to_string()],
+        hide_descriptions: false,
+        hide_completed: false,
+    });
+    let todos = vec![
+        todo("a", &["private"], true, Some("kept")),
+        todo("b", &["hr"], false, None),
+    ];
+    let redacted = mode.apply(todos);
+    assert_eq!(redacted.value.len(), 1);
+    assert_eq!(redacted.value[0].description.as_deref(), Some("kept"));
+}
@@ -0,0 +1,103 @@
+//! Progress of the long-running commands, for a single view of everything
+//! in flight. A command registers itself for as long as it runs, updates
+//! its handle as it goes, and disappears from `list_operations` when the
+//! handle is dropped.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationProgress {
+    pub operation_id: String,
+    /// What is running, e.g. `import`.
+    pub kind: &'static str,
+    /// Units done so far, in whatever `total` counts.
+    pub current: u64,
+    /// `None` while the size of the work isn't known.
+    pub total: Option<u64>,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Managed state holding the progress of the running operations by id.
+#[derive(Default)]
+pub struct ProgressRegistry {
+    running: Mutex<HashMap<String, OperationProgress>>,
+}
+
+impl ProgressRegistry {
+    fn running(&self) -> MutexGuard<'_, HashMap<String, OperationProgress>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Lists `operation_id` until the returned handle is dropped.
+    pub fn register(
+        &self,
+        operation_id: &str,
+        kind: &'static str,
+        total: Option<u64>,
+        started_at: DateTime<Utc>,
+    ) -> Result<ProgressHandle<'_>, String> {
+        let mut running = self.running();
+        if running.contains_key(operation_id) {
+            return Err(format!("Operation '{}' is already running", operation_id));
+        }
+        running.insert(
+            operation_id.to_string(),
+            OperationProgress {
+                operation_id: operation_id.to_string(),
+                kind,
+                current: 0,
+                total,
+                started_at,
+            },
+        );
+        Ok(ProgressHandle {
+            registry: self,
+            operation_id: operation_id.to_string(),
+        })
+    }
+
+    /// The running operations, oldest first.
+    pub fn list(&self) -> Vec<OperationProgress> {
+        let mut operations: Vec<_> = self.running().values().cloned().collect();
+        operations
+            .sort_by(|a, b| (a.started_at, &a.operation_id).cmp(&(b.started_at, &b.operation_id)));
+        operations
+    }
+}
+
+/// An operation registered with [`ProgressRegistry::register`].
+pub struct ProgressHandle<'a> {
+    registry: &'a ProgressRegistry,
+    operation_id: String,
+}
+
+impl ProgressHandle<'_> {
+    pub fn update(&self, current: u64, total: Option<u64>) {
+        if let Some(progress) = self.registry.running().get_mut(&self.operation_id) {
+            progress.current = current;
+            progress.total = total;
+        }
+    }
+}
+
+impl Drop for ProgressHandle<'_> {
+    fn drop(&mut self) {
+        self.registry.running().remove(&self.operation_id);
+    }
+}
+
+/// Everything long-running that is in flight, oldest first.
+#[tauri::command]
+pub fn list_operations(progress: State<'_, ProgressRegistry>) -> Vec<OperationProgress> {
+    progress.list()
+}
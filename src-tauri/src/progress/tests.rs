@@ -0,0 +1,52 @@
+use super::*;
+
+#[test]
+fn test_operations_are_listed_while_running() {
+    let registry = ProgressRegistry::default();
+    let start = Utc::now();
+    assert!(registry.list().is_empty());
+
+    let import = registry
+        .register("import-1", "import", Some(1000), start)
+        .unwrap();
+    let later = registry
+        .register(
+            "dir-1",
+            "import-directory",
+            None,
+            start + chrono::Duration::seconds(1),
+        )
+        .unwrap();
+    assert!(registry
+        .register("import-1", "import", None, start)
+        .is_err());
+
+    import.update(250, Some(1000));
+    let listed = registry.list();
+    assert_eq!(
+        listed[0],
+        OperationProgress {
+            operation_id: "import-1".to_string(),
+            kind: "import",
+            current: 250,
+            total: Some(1000),
+            started_at: start,
+        }
+    );
+    assert_eq!(listed[1].operation_id, "dir-1");
+    assert_eq!((listed[1].current, listed[1].total), (0, None));
+
+    // Finishing, by returning or by unwinding, drops the handle.
+    drop(import);
+    assert_eq!(
+        registry
+            .list()
+            .iter()
+            .map(|p| p.operation_id.as_str())
+            .collect::<Vec<_>>(),
+        ["dir-1"]
+    );
+    drop(later);
+    assert!(registry.list().is_empty());
+    assert!(registry.register("import-1", "import", None, start).is_ok());
+}
@@ -0,0 +1,176 @@
+//! Versioning of what the backend and the webview say to each other. The
+//! frontend announces its protocol version with `negotiate_protocol` on
+//! startup and gets back the features both sides support, so a dev build
+//! or a sidecar setup that updated only one side degrades instead of
+//! silently misreading payloads.
+//!
+//! Every event payload implements [`EventPayload`], which [`crate::events`]
+//! requires, and object payloads carry the version of their shape under
+//! [`VERSION_FIELD`]. When a field is renamed or removed, the payload bumps
+//! its version and keeps the old field through [`EventPayload::shim`] for
+//! at least one protocol version.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager, State, WebviewWindow};
+
+use crate::capabilities::{self, Capability, CapabilityRegistry};
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+/// The protocol this backend speaks.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// The oldest frontend protocol whose payloads are still shimmed.
+pub const OLDEST_SUPPORTED_VERSION: u32 = 1;
+/// The field of object payloads holding their shape's version.
+pub const VERSION_FIELD: &str = "protocolVersion";
+
+/// Optional features and the protocol version that introduced them.
+pub const FEATURES: &[(&str, u32)] = &[
+    ("activityFeed", 1),
+    ("eventRequestIds", 1),
+    ("liveQueries", 1),
+    ("readOnlyMode", 1),
+    ("shutdownProgress", 1),
+    ("stickyEvents", 1),
+];
+
+/// A payload sent with [`crate::events::emit`].
+pub trait EventPayload: Serialize + Clone {
+    /// The protocol version that introduced the payload's current shape.
+    const VERSION: u32 = 1;
+
+    /// Adds the fields older frontends still read, e.g. the old name of a
+    /// renamed field. Only called for object payloads.
+    fn shim(&self, _payload: &mut Map<String, Value>) {}
+}
+
+impl<T: EventPayload> EventPayload for &T {
+    const VERSION: u32 = T::VERSION;
+
+    fn shim(&self, payload: &mut Map<String, Value>) {
+        T::shim(self, payload)
+    }
+}
+
+impl<T: EventPayload> EventPayload for Vec<T> {
+    const VERSION: u32 = T::VERSION;
+}
+
+impl EventPayload for () {}
+impl EventPayload for usize {}
+impl EventPayload for String {}
+impl EventPayload for DateTime<Utc> {}
+impl EventPayload for PathBuf {}
+impl EventPayload for Todo {}
+
+/// Serializes `payload` with its version marker and shims.
+pub fn to_value<P: EventPayload>(payload: &P) -> serde_json::Result<Value> {
+    let mut value = serde_json::to_value(payload)?;
+    if let Some(object) = value.as_object_mut() {
+        payload.shim(object);
+        object.insert(VERSION_FIELD.to_string(), Value::from(P::VERSION));
+    }
+    Ok(value)
+}
+
+/// What `negotiate_protocol` settled on for one window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NegotiatedProtocol {
+    pub backend_version: u32,
+    pub frontend_version: u32,
+    /// Whether the backend still shims payloads for the frontend's version.
+    pub compatible: bool,
+    /// The features both sides support.
+    pub features: Vec<String>,
+}
+
+/// Settles on the features a frontend speaking `frontend_version` and
+/// knowing `frontend_features` can use with this backend.
+pub fn negotiate(frontend_version: u32, frontend_features: &[String]) -> NegotiatedProtocol {
+    let common_version = frontend_version.min(PROTOCOL_VERSION);
+    NegotiatedProtocol {
+        backend_version: PROTOCOL_VERSION,
+        frontend_version,
+        compatible: frontend_version >= OLDEST_SUPPORTED_VERSION,
+        features: FEATURES
+            .iter()
+            .filter(|(name, since)| {
+                *since <= common_version && frontend_features.iter().any(|f| f == name)
+            })
+            .map(|(name, _)| name.to_string())
+            .collect(),
+    }
+}
+
+/// Managed state holding what each window negotiated, by window label.
+#[derive(Default)]
+pub struct ProtocolState(Mutex<BTreeMap<String, NegotiatedProtocol>>);
+
+impl ProtocolState {
+    fn windows(&self) -> MutexGuard<'_, BTreeMap<String, NegotiatedProtocol>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The `protocol` capability: degraded while a window speaks another
+    /// version, unsupported once one is too old to be shimmed.
+    pub fn capability(&self) -> Capability {
+        let windows = self.windows();
+        if let Some((label, negotiated)) = windows.iter().find(|(_, n)| !n.compatible) {
+            return Capability::Unsupported {
+                reason: format!(
+                    "Window '{}' speaks protocol {}, older than the oldest supported {}",
+                    label, negotiated.frontend_version, OLDEST_SUPPORTED_VERSION
+                ),
+            };
+        }
+        let mismatched: Vec<String> = windows
+            .iter()
+            .filter(|(_, n)| n.frontend_version != n.backend_version)
+            .map(|(label, n)| format!("{} speaks {}", label, n.frontend_version))
+            .collect();
+        if mismatched.is_empty() {
+            return Capability::Available;
+        }
+        Capability::Degraded {
+            detail: format!(
+                "Backend speaks protocol {}; {}",
+                PROTOCOL_VERSION,
+                mismatched.join(", ")
+            ),
+        }
+    }
+}
+
+pub fn register_capabilities(app: &AppHandle, registry: &CapabilityRegistry) {
+    let app = app.clone();
+    registry.register("protocol", move || {
+        app.state::<ProtocolState>().capability()
+    });
+}
+
+/// Called by each webview on startup with the protocol it speaks and the
+/// features it knows. The result shows up in the `protocol` capability.
+#[tauri::command]
+pub fn negotiate_protocol(
+    frontend_version: u32,
+    features: Option<Vec<String>>,
+    window: WebviewWindow,
+    state: State<'_, ProtocolState>,
+) -> NegotiatedProtocol {
+    let negotiated = negotiate(frontend_version, &features.unwrap_or_default());
+    state
+        .windows()
+        .insert(window.label().to_string(), negotiated.clone());
+    capabilities::reprobe(window.app_handle());
+    negotiated
+}
@@ -0,0 +1,162 @@
+use super::*;
+
+use serde_json::json;
+
+fn version_of<P: EventPayload>() -> u32 {
+    P::VERSION
+}
+
+/// Every type handed to `events::emit`. The emit functions only accept
+/// [`EventPayload`], so a new event type without a marker fails to build;
+/// this list catches a marker from a protocol this backend doesn't speak.
+#[test]
+fn test_every_event_payload_carries_a_supported_version() {
+    let versions = [
+        ("()", version_of::<()>()),
+        ("count", version_of::<usize>()),
+        ("ids", version_of::<Vec<String>>()),
+        ("clock", version_of::<DateTime<Utc>>()),
+        ("legacy source", version_of::<PathBuf>()),
+        ("todo", version_of::<Todo>()),
+        (
+            "bulk-edit-preview",
+            version_of::<crate::bulk_edit::PreviewEvent>(),
+        ),
+        (
+            "bulk-edit-error",
+            version_of::<crate::bulk_edit::ErrorEvent>(),
+        ),
+        (
+            "bulk-edit-applied",
+            version_of::<Vec<crate::bulk_edit::BulkChange>>(),
+        ),
+        (
+            "calendar-synced",
+            version_of::<crate::calendar::CalendarSyncReport>(),
+        ),
+        (
+            "capability-changed",
+            version_of::<crate::capabilities::CapabilityChange>(),
+        ),
+        (
+            "import-progress",
+            version_of::<crate::import::ImportCheckpoint>(),
+        ),
+        (
+            "language-backfill-progress",
+            version_of::<crate::language::BackfillProgress>(),
+        ),
+        ("query-delta", version_of::<crate::live_query::QueryDelta>()),
+        (
+            "notifications-flushed",
+            version_of::<crate::notifications::NotificationsFlushed>(),
+        ),
+        (
+            "plugin-suggestions",
+            version_of::<crate::plugins::PluginSuggestions>(),
+        ),
+        (
+            "plugin-failed",
+            version_of::<crate::plugins::PluginFailed>(),
+        ),
+        (
+            "privacy-mode-changed",
+            version_of::<crate::privacy::PrivacyMode>(),
+        ),
+        ("quota-warning", version_of::<crate::quota::QuotaWarning>()),
+        (
+            "read-only-mode",
+            version_of::<crate::storage::ReadOnlyNewerSchema>(),
+        ),
+        ("todo-updated", version_of::<crate::storage::TodoUpdated>()),
+        (
+            "todo-reordered",
+            version_of::<crate::storage::TodoReordered>(),
+        ),
+        (
+            "references-stale",
+            version_of::<crate::references::StaleReferences>(),
+        ),
+        ("data-reset", version_of::<crate::reset::ResetReport>()),
+        (
+            "power-mode-changed",
+            version_of::<crate::scheduler::PowerMode>(),
+        ),
+        (
+            "self-check-completed",
+            version_of::<Vec<crate::self_check::Finding>>(),
+        ),
+        (
+            "shutdown-progress",
+            version_of::<crate::shutdown::ShutdownProgress>(),
+        ),
+        (
+            "remote-list-updated",
+            version_of::<crate::sync::RemoteListUpdated>(),
+        ),
+    ];
+    for (payload, version) in versions {
+        assert!(
+            (OLDEST_SUPPORTED_VERSION..=PROTOCOL_VERSION).contains(&version),
+            "{} is marked with protocol {}",
+            payload,
+            version
+        );
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct Renamed {
+    status: &'static str,
+}
+
+impl EventPayload for Renamed {
+    const VERSION: u32 = 2;
+
+    fn shim(&self, payload: &mut Map<String, Value>) {
+        payload.insert("completed".to_string(), json!(self.status == "completed"));
+    }
+}
+
+#[test]
+fn test_object_payloads_get_the_marker_and_shims() {
+    assert_eq!(
+        to_value(&Renamed {
+            status: "completed"
+        })
+        .unwrap(),
+        json!({ "status": "completed", "completed": true, "protocolVersion": 2 })
+    );
+    assert_eq!(to_value(&3usize).unwrap(), json!(3));
+    assert_eq!(to_value(&()).unwrap(), Value::Null);
+}
+
+#[test]
+fn test_negotiation_keeps_common_features_and_flags_mismatches() {
+    let features = vec!["stickyEvents".to_string(), "fromTheFuture".to_string()];
+    let current = negotiate(PROTOCOL_VERSION, &features);
+    assert!(current.compatible);
+    assert_eq!(current.features, vec!["stickyEvents"]);
+
+    let newer = negotiate(PROTOCOL_VERSION + 1, &features);
+    assert!(newer.compatible);
+    assert_eq!(newer.features, vec!["stickyEvents"]);
+    let too_old = negotiate(OLDEST_SUPPORTED_VERSION - 1, &features);
+    assert!(!too_old.compatible);
+    assert!(too_old.features.is_empty());
+
+    let state = ProtocolState::default();
+    assert_eq!(state.capability(), Capability::Available);
+    state.windows().insert("main".to_string(), current);
+    assert_eq!(state.capability(), Capability::Available);
+    state.windows().insert("settings".to_string(), newer);
+    assert!(matches!(
+        state.capability(),
+        Capability::Degraded { detail } if detail.contains("settings speaks 2")
+    ));
+    state.windows().insert("old".to_string(), too_old);
+    assert!(matches!(
+        state.capability(),
+        Capability::Unsupported { reason } if reason.contains("'old'")
+    ));
+}
@@ -0,0 +1,825 @@
+//! Smart-filter query language.
+//!
+//! ```text
+//! priority:high AND tag:work AND due<tomorrow
+//! (tag:home OR tag:errands) NOT completed:true "call mom"
+//! ```
+//!
+//! Terms are `field op value`, a quoted phrase, or a bare word (both searched in
+//! title and description). Adjacent terms are implicitly ANDed. Custom fields
+//! are addressed as `cf.customer:acme` or `cf."estimate (hours)">3`.
+//! `lang:jpn` (or `lang:japanese`) matches todos whose title or description
+//! was detected as that language.
+
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::{params, OptionalExtension};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::cache::{CacheKind, Caches};
+use crate::clock::{Clock, ClockState};
+use crate::language;
+use crate::privacy::PrivacyState;
+use crate::search;
+use crate::storage::drafts::Drafts;
+use crate::storage::Storage;
+use crate::types::{FieldValue, Priority, Todo};
+
+#[cfg(test)]
+mod tests;
+
+/// Byte range of the text a token, error or reference refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct QueryError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.span.start)
+    }
+}
+
+/// Prefix addressing a custom field by name.
+const CUSTOM_FIELD_PREFIX: &str = "cf.";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Priority,
+    Tag,
+    Due,
+    Created,
+    Updated,
+    Completed,
+    Title,
+    Text,
+    Language,
+    Custom(String),
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        if let Some(custom) = name
+            .get(..CUSTOM_FIELD_PREFIX.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(CUSTOM_FIELD_PREFIX))
+            .map(|_| &name[CUSTOM_FIELD_PREFIX.len()..])
+        {
+            return (!custom.is_empty()).then(|| Field::Custom(custom.to_string()));
+        }
+        match name.to_ascii_lowercase().as_str() {
+            "priority" => Some(Field::Priority),
+            "tag" => Some(Field::Tag),
+            "due" => Some(Field::Due),
+            "created" => Some(Field::Created),
+            "updated" => Some(Field::Updated),
+            "completed" | "done" => Some(Field::Completed),
+            "title" => Some(Field::Title),
+            "text" => Some(Field::Text),
+            "lang" | "language" => Some(Field::Language),
+            _ => None,
+        }
+    }
+
+    fn is_ordered(&self) -> bool {
+        matches!(
+            self,
+            Field::Priority | Field::Due | Field::Created | Field::Updated | Field::Custom(_)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Date operand; relative keywords are resolved when the query is evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DateValue {
+    Yesterday,
+    Today,
+    Tomorrow,
+    On(NaiveDate),
+}
+
+impl DateValue {
+    fn resolve(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            DateValue::Yesterday => today - Duration::days(1),
+            DateValue::Today => today,
+            DateValue::Tomorrow => today + Duration::days(1),
+            DateValue::On(date) => date,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum Value {
+    Text(String),
+    Date(DateValue),
+    Priority(Priority),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum QueryAst {
+    And {
+        left: Box<QueryAst>,
+        right: Box<QueryAst>,
+    },
+    Or {
+        left: Box<QueryAst>,
+        right: Box<QueryAst>,
+    },
+    Not {
+        inner: Box<QueryAst>,
+    },
+    Term {
+        field: Field,
+        op: Op,
+        value: Value,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    LParen,
+    RParen,
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '(' | ')' | '"' | ':' | '<' | '>' | '=')
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, Span)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let (token, end) = match c {
+            '(' | ')' => {
+                chars.next();
+                let token = if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                };
+                (token, start + 1)
+            }
+            ':' | '=' => {
+                chars.next();
+                (Token::Op(Op::Eq), start + 1)
+            }
+            '<' | '>' => {
+                chars.next();
+                let inclusive = matches!(chars.peek(), Some(&(_, '=')));
+                if inclusive {
+                    chars.next();
+                }
+                let op = match (c, inclusive) {
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    _ => Op::Ge,
+                };
+                (Token::Op(op), start + if inclusive { 2 } else { 1 })
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                let mut end = None;
+                for (i, c) in chars.by_ref() {
+                    if c == '"' {
+                        end = Some(i + 1);
+                        break;
+                    }
+                    text.push(c);
+                }
+                let end = end.ok_or_else(|| {
+                    QueryError::new(
+                        Span {
+                            start,
+                            end: input.len(),
+                        },
+                        "Unterminated string",
+                    )
+                })?;
+                (Token::Quoted(text), end)
+            }
+            _ => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !is_word_char(c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                (Token::Word(input[start..end].to_string()), end)
+            }
+        };
+        tokens.push((token, Span { start, end }));
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(Token, Span)],
+    pos: usize,
+    input_len: usize,
+    /// Problems inside individual terms. These don't stop the parse, so all of
+    /// them can be reported at once; structural errors still abort it.
+    errors: Vec<QueryError>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == keyword)
+    }
+
+    fn span(&self) -> Span {
+        self.tokens
+            .get(self.pos)
+            .map(|(_, span)| *span)
+            .unwrap_or(Span {
+                start: self.input_len,
+                end: self.input_len,
+            })
+    }
+
+    fn next(&mut self) -> Option<(Token, Span)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<QueryAst, QueryError> {
+        let mut left = self.parse_and()?;
+        while self.peek_keyword("OR") {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = QueryAst::Or {
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<QueryAst, QueryError> {
+        let mut left = self.parse_not()?;
+        loop {
+            if self.peek_keyword("AND") {
+                self.pos += 1;
+            } else if self.peek().is_none()
+                || self.peek_keyword("OR")
+                || self.peek() == Some(&Token::RParen)
+            {
+                return Ok(left);
+            }
+            let right = self.parse_not()?;
+            left = QueryAst::And {
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+    }
+
+    fn parse_not(&mut self) -> Result<QueryAst, QueryError> {
+        if self.peek_keyword("NOT") {
+            self.pos += 1;
+            let inner = self.parse_not()?;
+            return Ok(QueryAst::Not {
+                inner: Box::new(inner),
+            });
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<QueryAst, QueryError> {
+        let span = self.span();
+        match self.next() {
+            Some((Token::LParen, _)) => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some((Token::RParen, _)) => Ok(inner),
+                    _ => Err(QueryError::new(span, "Unclosed parenthesis")),
+                }
+            }
+            Some((Token::Word(mut word), _)) => {
+                if word.eq_ignore_ascii_case(CUSTOM_FIELD_PREFIX) {
+                    if let Some(Token::Quoted(name)) = self.peek() {
+                        word.push_str(name);
+                        self.pos += 1;
+                    }
+                }
+                if let Some(Token::Op(op)) = self.peek().cloned() {
+                    self.pos += 1;
+                    self.parse_term(&word, span, op)
+                } else {
+                    Ok(text_term(word))
+                }
+            }
+            Some((Token::Quoted(text), _)) => Ok(text_term(text)),
+            Some((Token::RParen, _)) => Err(QueryError::new(span, "Unexpected ')'")),
+            Some((Token::Op(_), _)) => Err(QueryError::new(span, "Operator without a field")),
+            None => Err(QueryError::new(span, "Unexpected end of query")),
+        }
+    }
+
+    fn parse_term(&mut self, name: &str, field_span: Span, op: Op) -> Result<QueryAst, QueryError> {
+        let field = Field::parse(name);
+        if field.is_none() {
+            self.errors.push(QueryError::new(
+                field_span,
+                format!("Unknown field '{}'", name),
+            ));
+        }
+        let op_span = self.tokens[self.pos - 1].1;
+        if field
+            .as_ref()
+            .is_some_and(|f| op != Op::Eq && !f.is_ordered())
+        {
+            self.errors.push(QueryError::new(
+                op_span,
+                format!("Field '{}' only supports ':'", name),
+            ));
+        }
+
+        let value_span = self.span();
+        let raw = match self.next() {
+            Some((Token::Word(w), _)) | Some((Token::Quoted(w), _)) => w,
+            _ => return Err(QueryError::new(value_span, "Expected a value")),
+        };
+        let Some(field) = field else {
+            return Ok(text_term(raw));
+        };
+        match term_value(&field, &raw) {
+            Ok(value) => Ok(QueryAst::Term { field, op, value }),
+            Err(kind) => {
+                self.errors.push(QueryError::new(
+                    value_span,
+                    format!("Invalid {} '{}' for field '{}'", kind, raw, name),
+                ));
+                Ok(text_term(raw))
+            }
+        }
+    }
+}
+
+/// Interprets a term's operand for `field`, or names the kind of value expected.
+fn term_value(field: &Field, raw: &str) -> Result<Value, &'static str> {
+    match field {
+        Field::Priority => Priority::parse(&raw.to_ascii_lowercase())
+            .map(Value::Priority)
+            .ok_or("priority"),
+        Field::Due | Field::Created | Field::Updated => {
+            parse_date(raw).map(Value::Date).ok_or("date")
+        }
+        Field::Completed => match raw.to_ascii_lowercase().as_str() {
+            "true" | "yes" => Ok(Value::Bool(true)),
+            "false" | "no" => Ok(Value::Bool(false)),
+            _ => Err("boolean"),
+        },
+        Field::Language => language::parse_language(raw)
+            .map(|code| Value::Text(code.to_string()))
+            .ok_or("language"),
+        // Custom field types are only known at evaluation time.
+        Field::Tag | Field::Title | Field::Text | Field::Custom(_) => {
+            Ok(Value::Text(raw.to_string()))
+        }
+    }
+}
+
+fn text_term(text: String) -> QueryAst {
+    QueryAst::Term {
+        field: Field::Text,
+        op: Op::Eq,
+        value: Value::Text(text),
+    }
+}
+
+fn parse_date(raw: &str) -> Option<DateValue> {
+    match raw.to_ascii_lowercase().as_str() {
+        "yesterday" => Some(DateValue::Yesterday),
+        "today" => Some(DateValue::Today),
+        "tomorrow" => Some(DateValue::Tomorrow),
+        _ => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+            .ok()
+            .map(DateValue::On),
+    }
+}
+
+/// Parses `input`, collecting every error found, ordered by position.
+pub fn parse_query_all(input: &str) -> Result<QueryAst, Vec<QueryError>> {
+    let tokens = tokenize(input).map_err(|e| vec![e])?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        input_len: input.len(),
+        errors: Vec::new(),
+    };
+    let result = parser.parse_or();
+    let mut errors = std::mem::take(&mut parser.errors);
+    match result {
+        Err(e) => errors.push(e),
+        Ok(_) if parser.pos < tokens.len() => {
+            errors.push(QueryError::new(parser.span(), "Unexpected ')'"))
+        }
+        Ok(ast) if errors.is_empty() => return Ok(ast),
+        Ok(_) => {}
+    }
+    errors.sort_by_key(|e| e.span.start);
+    Err(errors)
+}
+
+/// Parses `input`, reporting only the first error.
+pub fn parse_query(input: &str) -> Result<QueryAst, QueryError> {
+    parse_query_all(input).map_err(|mut errors| errors.swap_remove(0))
+}
+
+fn holds(ordering: Ordering, op: Op) -> bool {
+    match op {
+        Op::Eq => ordering.is_eq(),
+        Op::Lt => ordering.is_lt(),
+        Op::Le => ordering.is_le(),
+        Op::Gt => ordering.is_gt(),
+        Op::Ge => ordering.is_ge(),
+    }
+}
+
+fn compare<T: Ord>(actual: T, op: Op, expected: T) -> bool {
+    holds(actual.cmp(&expected), op)
+}
+
+fn custom_value<'a>(todo: &'a Todo, name: &str) -> Option<&'a FieldValue> {
+    todo.fields
+        .iter()
+        .find(|(field, _)| field.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Compares a custom field value with a query operand, interpreting the
+/// operand according to the value's type.
+fn custom_field_matches(value: &FieldValue, op: Op, raw: &str, today: NaiveDate) -> bool {
+    match value {
+        FieldValue::Number(n) => raw
+            .parse::<f64>()
+            .is_ok_and(|expected| holds(n.total_cmp(&expected), op)),
+        FieldValue::Date(date) => {
+            parse_date(raw).is_some_and(|d| compare(*date, op, d.resolve(today)))
+        }
+        FieldValue::Text(text) => holds(text.to_lowercase().cmp(&raw.to_lowercase()), op),
+    }
+}
+
+/// Whether `todo` satisfies `ast`, resolving relative dates against `today`.
+pub fn matches(ast: &QueryAst, todo: &Todo, today: NaiveDate) -> bool {
+    match ast {
+        QueryAst::And { left, right } => matches(left, todo, today) && matches(right, todo, today),
+        QueryAst::Or { left, right } => matches(left, todo, today) || matches(right, todo, today),
+        QueryAst::Not { inner } => !matches(inner, todo, today),
+        QueryAst::Term { field, op, value } => match (field, value) {
+            (Field::Priority, Value::Priority(p)) => compare(todo.priority, *op, *p),
+            (Field::Tag, Value::Text(tag)) => todo.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            (Field::Completed, Value::Bool(b)) => todo.completed == *b,
+            (Field::Title, Value::Text(text)) => {
+                search::text_matches(&todo.title, todo.languages.title.as_deref(), text)
+            }
+            (Field::Text, Value::Text(text)) => {
+                search::text_matches(&todo.title, todo.languages.title.as_deref(), text)
+                    || todo.description.as_deref().is_some_and(|d| {
+                        search::text_matches(d, todo.languages.description.as_deref(), text)
+                    })
+            }
+            (Field::Language, Value::Text(code)) => todo.languages.contains(code),
+            (Field::Due, Value::Date(date)) => todo
+                .scheduled_for
+                .is_some_and(|due| compare(due.date_naive(), *op, date.resolve(today))),
+            (Field::Created, Value::Date(date)) => {
+                compare(todo.created_at.date_naive(), *op, date.resolve(today))
+            }
+            (Field::Updated, Value::Date(date)) => {
+                compare(todo.updated_at.date_naive(), *op, date.resolve(today))
+            }
+            (Field::Custom(name), Value::Text(raw)) => custom_value(todo, name)
+                .is_some_and(|value| custom_field_matches(value, *op, raw, today)),
+            // The parser never pairs a field with a value of another type.
+            _ => false,
+        },
+    }
+}
+
+pub fn filter_todos(
+    query: &str,
+    todos: Vec<Todo>,
+    today: NaiveDate,
+) -> Result<Vec<Todo>, QueryError> {
+    let ast = parse_query(query)?;
+    Ok(todos
+        .into_iter()
+        .filter(|todo| matches(&ast, todo, today))
+        .collect())
+}
+
+/// Checks a query for the live editor without evaluating it against any todos.
+#[tauri::command]
+pub fn validate_query(query: String) -> Result<QueryAst, Vec<QueryError>> {
+    parse_query_all(&query)
+}
+
+#[tauri::command]
+pub fn eval_query(
+    query: String,
+    todos: Vec<Todo>,
+    clock: State<'_, ClockState>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<Vec<Todo>, String> {
+    let todos = privacy.get().apply(todos).value;
+    filter_todos(&query, todos, clock.today()).map_err(|e| format!("Syntax error: {}", e))
+}
+
+/// Key a todo list can be sorted by.
+enum SortValue<'a> {
+    Priority(Priority),
+    Time(DateTime<Utc>),
+    Text(String),
+    Custom(&'a FieldValue),
+}
+
+impl SortValue<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortValue::Priority(a), SortValue::Priority(b)) => a.cmp(b),
+            (SortValue::Time(a), SortValue::Time(b)) => a.cmp(b),
+            (SortValue::Text(a), SortValue::Text(b)) => a.cmp(b),
+            (
+                SortValue::Custom(FieldValue::Number(a)),
+                SortValue::Custom(FieldValue::Number(b)),
+            ) => a.total_cmp(b),
+            (SortValue::Custom(FieldValue::Date(a)), SortValue::Custom(FieldValue::Date(b))) => {
+                a.cmp(b)
+            }
+            (SortValue::Custom(a), SortValue::Custom(b)) => a
+                .to_string()
+                .to_lowercase()
+                .cmp(&b.to_string().to_lowercase()),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+fn sort_value<'a>(field: &Field, todo: &'a Todo) -> Option<SortValue<'a>> {
+    match field {
+        Field::Priority => Some(SortValue::Priority(todo.priority)),
+        Field::Due => todo.scheduled_for.map(SortValue::Time),
+        Field::Created => Some(SortValue::Time(todo.created_at)),
+        Field::Updated => Some(SortValue::Time(todo.updated_at)),
+        Field::Title => Some(SortValue::Text(todo.title.to_lowercase())),
+        Field::Custom(name) => custom_value(todo, name).map(SortValue::Custom),
+        _ => None,
+    }
+}
+
+/// Order of `Storage::list_todos`: order key, then the server's order, then
+/// creation time, then id.
+pub fn cmp_list_order(a: &Todo, b: &Todo) -> Ordering {
+    a.rank
+        .cmp(&b.rank)
+        .then(a.order.cmp(&b.order))
+        .then(a.created_at.cmp(&b.created_at))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+/// Sort key for todo lists, by a field name as written in queries (`due`,
+/// `cf.estimate`, ...). Todos without a value for the key always go last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoOrder {
+    field: Option<Field>,
+    descending: bool,
+}
+
+impl TodoOrder {
+    pub fn new(sort_by: Option<&str>, descending: bool) -> Result<Self, String> {
+        let field = sort_by
+            .map(|sort_by| {
+                Field::parse(sort_by)
+                    .filter(|field| field.is_ordered() || *field == Field::Title)
+                    .ok_or_else(|| format!("Cannot sort by '{}'", sort_by))
+            })
+            .transpose()?;
+        Ok(Self { field, descending })
+    }
+
+    /// Compares by the key only; ties are left to the caller.
+    pub fn cmp_key(&self, a: &Todo, b: &Todo) -> Ordering {
+        let Some(field) = &self.field else {
+            return Ordering::Equal;
+        };
+        match (sort_value(field, a), sort_value(field, b)) {
+            (Some(a), Some(b)) if self.descending => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    /// Total order: the key, then list order. This is what a stable sort by
+    /// the key gives on a list in `list_todos` order.
+    pub fn cmp(&self, a: &Todo, b: &Todo) -> Ordering {
+        self.cmp_key(a, b).then_with(|| cmp_list_order(a, b))
+    }
+}
+
+/// Stable sort by a field name as written in queries (`due`, `cf.estimate`, ...).
+/// Todos without a value for the key always go last.
+pub fn sort_todos(todos: &mut [Todo], sort_by: &str, descending: bool) -> Result<(), String> {
+    let order = TodoOrder::new(Some(sort_by), descending)?;
+    todos.sort_by(|a, b| order.cmp_key(a, b));
+    Ok(())
+}
+
+/// Filters and sorts the local todo cache, including custom field values.
+#[tauri::command]
+pub fn query_todos(
+    query: Option<String>,
+    sort_by: Option<String>,
+    descending: Option<bool>,
+    storage: State<'_, Storage>,
+    drafts: State<'_, Drafts>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<Vec<Todo>, String> {
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    // Staged drafts included, so an editor's text isn't shown stale.
+    let todos = privacy.get().apply(drafts.overlay(todos)).value;
+    let mut todos = match query.as_deref().filter(|q| !q.trim().is_empty()) {
+        Some(query) => filter_todos(query, todos, storage.clock().today())
+            .map_err(|e| format!("Syntax error: {}", e))?,
+        None => todos,
+    };
+    if let Some(sort_by) = sort_by {
+        sort_todos(&mut todos, &sort_by, descending.unwrap_or(false))?;
+    }
+    Ok(todos)
+}
+
+/// A saved query that behaves like a list whose contents are always current.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartList {
+    pub id: String,
+    pub name: String,
+    pub query: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Saves a smart list after checking that its query parses.
+pub fn create_list(storage: &Storage, name: &str, query: &str) -> Result<SmartList, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Smart list name must not be empty".to_string());
+    }
+    parse_query(query).map_err(|e| format!("Syntax error: {}", e))?;
+
+    let list = SmartList {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        query: query.trim().to_string(),
+        created_at: storage.clock().now(),
+    };
+    storage
+        .conn()
+        .execute(
+            "INSERT INTO smart_lists (id, name, query, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![list.id, list.name, list.query, list.created_at],
+        )
+        .map_err(|e| format!("Failed to save smart list: {}", e))?;
+    Ok(list)
+}
+
+pub fn list_lists(storage: &Storage) -> rusqlite::Result<Vec<SmartList>> {
+    let conn = storage.conn();
+    let mut stmt =
+        conn.prepare("SELECT id, name, query, created_at FROM smart_lists ORDER BY created_at")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(SmartList {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            query: row.get(2)?,
+            created_at: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Evaluates a smart list against the current todo cache.
+pub fn smart_list_todos(
+    storage: &Storage,
+    list_id: &str,
+    today: NaiveDate,
+) -> Result<Vec<Todo>, String> {
+    let query: String = storage
+        .conn()
+        .query_row(
+            "SELECT query FROM smart_lists WHERE id = ?1",
+            [list_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to load smart list: {}", e))?
+        .ok_or_else(|| format!("Unknown smart list '{}'", list_id))?;
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    filter_todos(&query, todos, today).map_err(|e| format!("Syntax error: {}", e))
+}
+
+#[tauri::command]
+pub fn create_smart_list(
+    name: String,
+    query: String,
+    storage: State<'_, Storage>,
+) -> Result<SmartList, String> {
+    create_list(&storage, &name, &query)
+}
+
+#[tauri::command]
+pub fn list_smart_lists(storage: State<'_, Storage>) -> Result<Vec<SmartList>, String> {
+    list_lists(&storage).map_err(|e| format!("Failed to list smart lists: {}", e))
+}
+
+/// Contents of a smart list; call again on `todo-cache-updated`.
+#[tauri::command]
+pub fn get_smart_list_todos(
+    list_id: String,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+    caches: State<'_, Caches>,
+) -> Result<Vec<Todo>, String> {
+    let today = storage.clock().today();
+    let signature = format!("{}@{}", list_id, today);
+    let todos = caches.get_or_compute(&storage, CacheKind::SmartList, signature, || {
+        smart_list_todos(&storage, &list_id, today)
+    })?;
+    Ok(privacy.get().apply(todos).value)
+}
+
+#[tauri::command]
+pub fn delete_smart_list(
+    list_id: String,
+    storage: State<'_, Storage>,
+    caches: State<'_, Caches>,
+) -> Result<(), String> {
+    let deleted = storage
+        .conn()
+        .execute("DELETE FROM smart_lists WHERE id = ?1", [&list_id])
+        .map_err(|e| format!("Failed to delete smart list: {}", e))?;
+    if deleted == 0 {
+        return Err(format!("Unknown smart list '{}'", list_id));
+    }
+    // Smart lists aren't in the change feed.
+    caches.invalidate_kind(CacheKind::SmartList);
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use super::*;
+use chrono::{DateTime, TimeZone};
+
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 10).unwrap()
+}
+
+fn day(d: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 6, d, 12, 0, 0).unwrap()
+}
+
+fn todo(id: &str, title: &str, priority: Priority, tags: &[&str], due: Option<u32>) -> Todo {
+    Todo {
+        priority,
+        scheduled_for: due.map(day),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Todo::sample(id, title, day(1))
+    }
+}
+
+fn sample() -> Vec<Todo> {
+    vec![
+        todo("1", "Ship release", Priority::High, &["work"], Some(10)),
+        todo("2", "Plan offsite", Priority::High, &["work"], Some(20)),
+        todo("3", "Buy groceries", Priority::Low, &["home"], Some(9)),
+        todo("4", "Call mom", Priority::Medium, &["home", "family"], None),
+    ]
+}
+
+fn ids(query: &str) -> Vec<String> {
+    filter_todos(query, sample(), today())
+        .unwrap()
+        .into_iter()
+        .map(|t| t.id)
+        .collect()
+}
+
+#[test]
+fn test_compound_and_with_date_comparison() {
+    assert_eq!(
+        ids("priority:high AND tag:work AND due<tomorrow"),
+        vec!["1"]
+    );
+}
+
+#[test]
+fn test_or_not_and_grouping() {
+    assert_eq!(
+        ids("(tag:home OR priority:high) AND NOT due<=today"),
+        vec!["2", "4"]
+    );
+    assert_eq!(ids("NOT tag:work"), vec!["3", "4"]);
+}
+
+#[test]
+fn test_implicit_and_and_quoted_text() {
+    assert_eq!(ids("tag:home \"call mom\""), vec!["4"]);
+    assert_eq!(ids("release"), vec!["1"]);
+}
+
+#[test]
+fn test_priority_ordering_and_absolute_dates() {
+    assert_eq!(ids("priority>=medium"), vec!["1", "2", "4"]);
+    assert_eq!(ids("due>2024-06-10"), vec!["2"]);
+}
+
+#[test]
+fn test_syntax_error_reports_position() {
+    let error = parse_query("tag:work AND (priority:high").unwrap_err();
+    assert_eq!(error.span.start, 13);
+    assert!(error.to_string().contains("position 13"));
+
+    let error = filter_todos("tag:work AND", sample(), Utc::now().date_naive()).unwrap_err();
+    assert!(error.to_string().contains("position 12"), "{}", error);
+}
+
+#[test]
+fn test_ast_shape() {
+    assert_eq!(
+        parse_query("NOT done:true").unwrap(),
+        QueryAst::Not {
+            inner: Box::new(QueryAst::Term {
+                field: Field::Completed,
+                op: Op::Eq,
+                value: Value::Bool(true),
+            }),
+        }
+    );
+}
+
+fn with_fields(mut todo: Todo, fields: &[(&str, FieldValue)]) -> Todo {
+    for (name, value) in fields {
+        todo.fields.insert(name.to_string(), value.clone());
+    }
+    todo
+}
+
+fn custom_sample() -> Vec<Todo> {
+    vec![
+        with_fields(
+            todo("1", "Quote", Priority::Low, &[], None),
+            &[
+                ("estimate (hours)", FieldValue::Number(8.0)),
+                ("customer", FieldValue::Text("Acme".to_string())),
+            ],
+        ),
+        with_fields(
+            todo("2", "Invoice", Priority::Low, &[], None),
+            &[("estimate (hours)", FieldValue::Number(1.5))],
+        ),
+        todo("3", "Backlog", Priority::Low, &[], None),
+    ]
+}
+
+#[test]
+fn test_custom_field_terms() {
+    let ids = |query: &str| -> Vec<String> {
+        filter_todos(query, custom_sample(), today())
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect()
+    };
+    assert_eq!(ids("cf.customer:acme"), vec!["1"]);
+    assert_eq!(ids(r#"cf."estimate (hours)">2"#), vec!["1"]);
+    assert_eq!(ids(r#"cf."estimate (hours)"<=1.5"#), vec!["2"]);
+    assert_eq!(ids("NOT cf.customer:acme"), vec!["2", "3"]);
+}
+
+#[test]
+fn test_sort_by_custom_field_puts_missing_last() {
+    let sorted = |key: &str, descending: bool| -> Vec<String> {
+        let mut todos = custom_sample();
+        sort_todos(&mut todos, key, descending).unwrap();
+        todos.into_iter().map(|t| t.id).collect()
+    };
+    assert_eq!(sorted("cf.estimate (hours)", false), vec!["2", "1", "3"]);
+    assert_eq!(sorted("cf.estimate (hours)", true), vec!["1", "2", "3"]);
+    assert_eq!(sorted("title", false), vec!["3", "2", "1"]);
+    assert!(sort_todos(&mut custom_sample(), "tag", false).is_err());
+}
+
+fn validation_errors(query: &str) -> Vec<(usize, usize, String)> {
+    validate_query(query.to_string())
+        .unwrap_err()
+        .into_iter()
+        .map(|e| (e.span.start, e.span.end, e.message))
+        .collect()
+}
+
+#[test]
+fn test_validate_query_returns_ast() {
+    assert_eq!(
+        validate_query("priority:high tag:work".to_string()),
+        Ok(parse_query("priority:high AND tag:work").unwrap())
+    );
+}
+
+#[test]
+fn test_validate_unknown_field() {
+    assert_eq!(
+        validation_errors("tag:work AND owner:me"),
+        vec![(13, 18, "Unknown field 'owner'".to_string())]
+    );
+}
+
+#[test]
+fn test_validate_bad_operator() {
+    assert_eq!(
+        validation_errors("tag>work"),
+        vec![(3, 4, "Field 'tag' only supports ':'".to_string())]
+    );
+    assert_eq!(
+        validation_errors("priority:>high"),
+        vec![(9, 10, "Expected a value".to_string())]
+    );
+}
+
+#[test]
+fn test_validate_unterminated_string() {
+    assert_eq!(
+        validation_errors(r#"tag:work "call mom"#),
+        vec![(9, 18, "Unterminated string".to_string())]
+    );
+}
+
+#[test]
+fn test_validate_reports_every_term_error() {
+    assert_eq!(
+        validation_errors("priority:urgent OR due<someday (tag:x"),
+        vec![
+            (
+                9,
+                15,
+                "Invalid priority 'urgent' for field 'priority'".to_string()
+            ),
+            (23, 30, "Invalid date 'someday' for field 'due'".to_string()),
+            (31, 32, "Unclosed parenthesis".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_smart_list_tracks_cache_changes() {
+    crate::storage::for_each_backend(|storage| {
+        storage.replace_todos(&sample()).unwrap();
+        let list = create_list(storage, "Urgent work", "priority:high tag:work").unwrap();
+        let ids = |storage: &Storage| -> Vec<String> {
+            smart_list_todos(storage, &list.id, today())
+                .unwrap()
+                .into_iter()
+                .map(|t| t.id)
+                .collect()
+        };
+        assert_eq!(ids(storage), vec!["1", "2"]);
+
+        let mut todos = sample();
+        todos.push(todo("5", "Fix outage", Priority::High, &["work"], None));
+        todos.push(todo("6", "Water plants", Priority::High, &["home"], None));
+        storage.replace_todos(&todos).unwrap();
+        assert_eq!(ids(storage), vec!["1", "2", "5"]);
+        assert_eq!(list_lists(storage).unwrap(), vec![list.clone()]);
+    });
+}
+
+#[test]
+fn test_smart_list_query_is_validated_on_creation() {
+    let storage = Storage::open_in_memory().unwrap();
+    let error = create_list(&storage, "Broken", "owner:me").unwrap_err();
+    assert_eq!(error, "Syntax error: Unknown field 'owner' at position 0");
+    assert!(create_list(&storage, " ", "tag:work").is_err());
+    assert!(list_lists(&storage).unwrap().is_empty());
+}
+
+fn mixed() -> Vec<Todo> {
+    let mut japanese = todo("1", "会議の資料を準備する", Priority::Medium, &[], None);
+    japanese.description =
+        Some("Bring the slides to the planning meetings with the team".to_string());
+    japanese.languages =
+        crate::language::detect_todo(&japanese.title, japanese.description.as_deref());
+    let mut english = todo(
+        "2",
+        "Write the quarterly report for the team",
+        Priority::Medium,
+        &[],
+        None,
+    );
+    english.languages = crate::language::detect_todo(&english.title, None);
+    vec![
+        japanese,
+        english,
+        todo("3", "Draft", Priority::Medium, &[], None),
+    ]
+}
+
+fn mixed_ids(query: &str) -> Vec<String> {
+    filter_todos(query, mixed(), today())
+        .unwrap()
+        .into_iter()
+        .map(|t| t.id)
+        .collect()
+}
+
+#[test]
+fn test_language_filter_matches_title_or_description() {
+    assert_eq!(mixed_ids("lang:jpn"), vec!["1"]);
+    assert_eq!(mixed_ids("lang:english"), vec!["1", "2"]);
+    assert_eq!(mixed_ids("NOT lang:eng"), vec!["3"]);
+    assert!(parse_query("lang:klingon").is_err());
+}
+
+#[test]
+fn test_text_search_uses_each_parts_language() {
+    // Stemmed in the English description of a Japanese todo.
+    assert_eq!(mixed_ids("planned"), vec!["1"]);
+    assert_eq!(mixed_ids("title:reports"), vec!["2"]);
+    assert_eq!(mixed_ids("資料"), vec!["1"]);
+    assert!(mixed_ids("title:planned").is_empty());
+}
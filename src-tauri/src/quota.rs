@@ -0,0 +1,298 @@
+//! Limits on how much data the app takes on. Writes that would go past a hard
+//! limit fail with [`QuotaExceeded`]; crossing a soft limit emits
+//! `quota-warning` once, until usage drops back below it.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::events;
+use crate::file_access::PathError;
+use crate::protocol::EventPayload;
+use crate::settings::{QuotaSettings, SettingsReactions, SettingsState};
+use crate::storage::Storage;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum QuotaDimension {
+    Todos,
+    AttachmentBytes,
+}
+
+impl std::fmt::Display for QuotaDimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            QuotaDimension::Todos => "todos",
+            QuotaDimension::AttachmentBytes => "attachment bytes",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema, thiserror::Error)]
+#[serde(rename_all = "camelCase")]
+#[error("This would make {requested} {dimension}, over the limit of {limit}")]
+pub struct QuotaExceeded {
+    pub dimension: QuotaDimension,
+    pub limit: u64,
+    /// Usage the write would have resulted in.
+    pub requested: u64,
+}
+
+/// Error of commands that can run into a hard limit, serialized so the
+/// frontend can tell which limit apart from other failures.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema, thiserror::Error)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum WriteError {
+    #[error("This would make {requested} {dimension}, over the limit of {limit}")]
+    QuotaExceeded {
+        dimension: QuotaDimension,
+        limit: u64,
+        requested: u64,
+    },
+    #[error("{} is outside the directories import and export may use", path.display())]
+    #[serde(rename = "path-not-allowed")]
+    PathNotAllowed { path: PathBuf },
+    #[error("{message}")]
+    Failed { message: String },
+}
+
+impl From<QuotaExceeded> for WriteError {
+    fn from(e: QuotaExceeded) -> Self {
+        WriteError::QuotaExceeded {
+            dimension: e.dimension,
+            limit: e.limit,
+            requested: e.requested,
+        }
+    }
+}
+
+impl From<PathError> for WriteError {
+    fn from(e: PathError) -> Self {
+        match e {
+            PathError::PathNotAllowed { path } => WriteError::PathNotAllowed { path },
+            PathError::Failed { message } => WriteError::Failed { message },
+        }
+    }
+}
+
+impl trace::Annotate for WriteError {
+    fn annotate(self, id: &str) -> Self {
+        match self {
+            WriteError::Failed { message } => WriteError::Failed {
+                message: message.annotate(id),
+            },
+            // The frontend renders these itself from the fields.
+            e => e,
+        }
+    }
+}
+
+impl From<String> for WriteError {
+    fn from(message: String) -> Self {
+        WriteError::Failed { message }
+    }
+}
+
+pub fn check(dimension: QuotaDimension, requested: u64, limit: u64) -> Result<(), QuotaExceeded> {
+    if requested > limit {
+        return Err(QuotaExceeded {
+            dimension,
+            limit,
+            requested,
+        });
+    }
+    Ok(())
+}
+
+/// Fails if the cache would hold more than the store's hard limit of todos.
+pub fn check_todos(storage: &Storage, total: u64) -> Result<(), QuotaExceeded> {
+    check(QuotaDimension::Todos, total, storage.max_todos())
+}
+
+pub fn count_todos(conn: &Connection) -> rusqlite::Result<u64> {
+    conn.query_row("SELECT COUNT(*) FROM todos", [], |row| row.get(0))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaUsage {
+    pub todos: u64,
+    pub database_bytes: u64,
+    pub attachment_bytes: u64,
+}
+
+impl QuotaUsage {
+    fn get(&self, dimension: QuotaDimension) -> u64 {
+        match dimension {
+            QuotaDimension::Todos => self.todos,
+            QuotaDimension::AttachmentBytes => self.attachment_bytes,
+        }
+    }
+}
+
+fn limits(settings: &QuotaSettings, dimension: QuotaDimension) -> (u64, u64) {
+    match dimension {
+        QuotaDimension::Todos => (settings.soft_todos, settings.hard_todos),
+        QuotaDimension::AttachmentBytes => (
+            settings.soft_attachment_bytes,
+            settings.hard_attachment_bytes,
+        ),
+    }
+}
+
+const DIMENSIONS: [QuotaDimension; 2] = [QuotaDimension::Todos, QuotaDimension::AttachmentBytes];
+
+/// Total size of the files under `dir`; unreadable entries count as empty.
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map_or(0, |m| m.len()),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+pub fn usage(storage: &Storage) -> rusqlite::Result<QuotaUsage> {
+    let conn = storage.connect()?;
+    let database_bytes: u64 = conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(QuotaUsage {
+        todos: count_todos(&conn)?,
+        database_bytes,
+        attachment_bytes: dir_size(storage.files_dir()),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaStatus {
+    pub usage: QuotaUsage,
+    pub limits: QuotaSettings,
+    /// Dimensions past their soft limit.
+    pub warnings: Vec<QuotaDimension>,
+    /// Dimensions at or past their hard limit; further writes are refused.
+    pub exhausted: Vec<QuotaDimension>,
+}
+
+impl QuotaStatus {
+    pub fn new(usage: QuotaUsage, limits: QuotaSettings) -> Self {
+        let over = |pick: fn((u64, u64)) -> u64| {
+            DIMENSIONS
+                .into_iter()
+                .filter(|&dimension| usage.get(dimension) >= pick(self::limits(&limits, dimension)))
+                .collect()
+        };
+        Self {
+            warnings: over(|(soft, _)| soft),
+            exhausted: over(|(_, hard)| hard),
+            usage,
+            limits,
+        }
+    }
+}
+
+/// Payload of the `quota-warning` event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaWarning {
+    pub dimension: QuotaDimension,
+    pub usage: u64,
+    pub soft_limit: u64,
+    pub hard_limit: u64,
+}
+
+impl EventPayload for QuotaWarning {}
+
+/// Soft limits already warned about.
+#[derive(Default)]
+pub struct QuotaWatch(Mutex<BTreeSet<QuotaDimension>>);
+
+impl QuotaWatch {
+    /// Warnings for soft limits newly crossed in `status`. A dimension that
+    /// drops back below its soft limit warns again the next time it crosses.
+    pub fn observe(&self, status: &QuotaStatus) -> Vec<QuotaWarning> {
+        let mut warned = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        warned.retain(|dimension| status.warnings.contains(dimension));
+        status
+            .warnings
+            .iter()
+            .filter(|&&dimension| warned.insert(dimension))
+            .map(|&dimension| {
+                let (soft_limit, hard_limit) = limits(&status.limits, dimension);
+                QuotaWarning {
+                    dimension,
+                    usage: status.usage.get(dimension),
+                    soft_limit,
+                    hard_limit,
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn status(app: &AppHandle) -> Result<QuotaStatus, String> {
+    let usage = usage(&app.state::<Storage>())
+        .map_err(|e| format!("Failed to measure data usage: {}", e))?;
+    Ok(QuotaStatus::new(
+        usage,
+        app.state::<SettingsState>().get().quota,
+    ))
+}
+
+/// Re-measures usage after a write that can add data and emits
+/// `quota-warning` for soft limits it crossed.
+pub fn notify(app: &AppHandle) {
+    let Ok(status) = status(app) else {
+        return;
+    };
+    for warning in app.state::<QuotaWatch>().observe(&status) {
+        let _ = events::emit(app, "quota-warning", &warning);
+    }
+}
+
+/// Fails if adding `bytes` of files would pass the hard attachment limit.
+pub fn check_attachments(app: &AppHandle, bytes: u64) -> Result<(), QuotaExceeded> {
+    let current = dir_size(app.state::<Storage>().files_dir());
+    let limit = app
+        .state::<SettingsState>()
+        .get()
+        .quota
+        .hard_attachment_bytes;
+    check(QuotaDimension::AttachmentBytes, current + bytes, limit)
+}
+
+/// Applies changed `[backend.quota]` limits to the store.
+pub fn register_settings_reactions(app: &AppHandle, reactions: &SettingsReactions) {
+    let app = app.clone();
+    reactions.register("quota", &["quota"], move |_, new| {
+        app.state::<Storage>().set_max_todos(new.quota.hard_todos);
+        notify(&app);
+    });
+}
+
+#[tauri::command]
+pub fn get_data_quota_status(app: AppHandle) -> Result<QuotaStatus, String> {
+    status(&app)
+}
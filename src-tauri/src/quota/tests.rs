@@ -0,0 +1,91 @@
+use super::*;
+
+fn limits() -> QuotaSettings {
+    QuotaSettings {
+        soft_todos: 80,
+        hard_todos: 100,
+        soft_attachment_bytes: 800,
+        hard_attachment_bytes: 1000,
+    }
+}
+
+fn status(todos: u64, attachment_bytes: u64) -> QuotaStatus {
+    QuotaStatus::new(
+        QuotaUsage {
+            todos,
+            database_bytes: 0,
+            attachment_bytes,
+        },
+        limits(),
+    )
+}
+
+#[test]
+fn test_status_reports_soft_and_hard_limits() {
+    let below = status(79, 0);
+    assert!(below.warnings.is_empty() && below.exhausted.is_empty());
+
+    let soft = status(80, 999);
+    assert_eq!(
+        soft.warnings,
+        vec![QuotaDimension::Todos, QuotaDimension::AttachmentBytes]
+    );
+    assert!(soft.exhausted.is_empty());
+
+    assert_eq!(status(100, 0).exhausted, vec![QuotaDimension::Todos]);
+}
+
+#[test]
+fn test_soft_limit_warns_once_per_crossing() {
+    let watch = QuotaWatch::default();
+    assert!(watch.observe(&status(10, 0)).is_empty());
+
+    let warnings = watch.observe(&status(85, 0));
+    assert_eq!(
+        warnings,
+        vec![QuotaWarning {
+            dimension: QuotaDimension::Todos,
+            usage: 85,
+            soft_limit: 80,
+            hard_limit: 100,
+        }]
+    );
+    assert!(watch.observe(&status(90, 0)).is_empty());
+
+    let warnings = watch.observe(&status(90, 900));
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].dimension, QuotaDimension::AttachmentBytes);
+
+    assert!(watch.observe(&status(50, 900)).is_empty());
+    assert_eq!(watch.observe(&status(81, 900)).len(), 1);
+}
+
+#[test]
+fn test_hard_limit_errors_name_the_dimension() {
+    assert!(check(QuotaDimension::Todos, 100, 100).is_ok());
+    let error = check(QuotaDimension::AttachmentBytes, 1001, 1000).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "This would make 1001 attachment bytes, over the limit of 1000"
+    );
+    assert_eq!(
+        serde_json::to_value(WriteError::from(error)).unwrap(),
+        serde_json::json!({
+            "kind": "quotaExceeded",
+            "dimension": "attachmentBytes",
+            "limit": 1000,
+            "requested": 1001,
+        })
+    );
+}
+
+#[test]
+fn test_usage_counts_todos_and_files() {
+    let storage = Storage::open_in_memory().unwrap();
+    std::fs::create_dir_all(storage.files_dir().join("nested")).unwrap();
+    std::fs::write(storage.files_dir().join("a.bin"), [0u8; 10]).unwrap();
+    std::fs::write(storage.files_dir().join("nested/b.bin"), [0u8; 5]).unwrap();
+    let usage = usage(&storage).unwrap();
+    assert_eq!((usage.todos, usage.attachment_bytes), (0, 15));
+    assert!(usage.database_bytes > 0);
+}
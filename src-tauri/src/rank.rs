@@ -0,0 +1,145 @@
+//! Ordering keys for the manually ordered todo list. A key is a base-36
+//! fraction written without its leading "0.", so keys compare as plain
+//! strings (in Rust and in SQLite alike) and there is always room for
+//! another key between two neighbors: moving a todo rewrites only its own.
+//!
+//! Keys never end in `0`, so a key can always be placed before any other.
+//! Repeated inserts into the same gap make keys longer; past [`MAX_LEN`] the
+//! list is respread with [`spread`].
+
+#[cfg(test)]
+mod tests;
+
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const BASE: u32 = 36;
+/// Keys longer than this ask for the list to be rebalanced.
+pub const MAX_LEN: usize = 24;
+/// Digits a key appended after the last one is incremented at, which gives
+/// about a billion appends before keys need another digit.
+const APPEND_PRECISION: usize = 6;
+/// Key of the first todo in an empty list.
+const FIRST: &str = "i";
+
+fn value(digit: u8) -> Option<u32> {
+    DIGITS.iter().position(|&d| d == digit).map(|v| v as u32)
+}
+
+pub fn is_valid(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(|b| value(b).is_some()) && !key.ends_with('0')
+}
+
+fn check(key: &str) -> Result<(), String> {
+    if is_valid(key) {
+        Ok(())
+    } else {
+        Err(format!("Invalid order key '{}'", key))
+    }
+}
+
+/// Whether `key` has grown long enough that the list should be respread.
+pub fn needs_rebalance(key: &str) -> bool {
+    key.len() > MAX_LEN
+}
+
+/// A key strictly between `before` and `after`, where `None` stands for the
+/// start or the end of the list.
+pub fn between(before: Option<&str>, after: Option<&str>) -> Result<String, String> {
+    before.map(check).transpose()?;
+    after.map(check).transpose()?;
+    let key = match (before, after) {
+        (None, None) => FIRST.to_string(),
+        (Some(before), None) => append(before),
+        (before, Some(after)) => {
+            let before = before.unwrap_or("");
+            if before >= after {
+                return Err(format!(
+                    "Order keys '{}' and '{}' are out of order",
+                    before, after
+                ));
+            }
+            midpoint(before.as_bytes(), Some(after.as_bytes()))
+        }
+    };
+    Ok(key)
+}
+
+/// The next key after `key` at [`APPEND_PRECISION`] digits, so appends in a
+/// row stay short instead of halving the space above each time.
+fn append(key: &str) -> String {
+    let mut digits: Vec<u32> = key.bytes().filter_map(value).collect();
+    digits.resize(digits.len().max(APPEND_PRECISION), 0);
+    for position in (0..digits.len()).rev() {
+        if digits[position] + 1 < BASE {
+            digits[position] += 1;
+            digits.truncate(position + 1);
+            return encode(&digits);
+        }
+    }
+    // Every digit is already the largest; go one level deeper.
+    format!("{}{}", key, FIRST)
+}
+
+/// Halfway between `a` and `b` (`None` meaning 1), with `a < b`.
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> String {
+    if let Some(b) = b {
+        let common = (0..b.len())
+            .take_while(|&i| a.get(i).copied().unwrap_or(b'0') == b[i])
+            .count();
+        if common > 0 {
+            let prefix = String::from_utf8_lossy(&b[..common]).into_owned();
+            let rest = midpoint(a.get(common..).unwrap_or_default(), Some(&b[common..]));
+            return prefix + &rest;
+        }
+    }
+    let digit_a = a.first().and_then(|&d| value(d)).unwrap_or(0);
+    let digit_b = b
+        .and_then(|b| b.first())
+        .and_then(|&d| value(d))
+        .unwrap_or(BASE);
+    if digit_b - digit_a > 1 {
+        char_of((digit_a + digit_b).div_ceil(2)).to_string()
+    } else if let Some(b) = b.filter(|b| b.len() > 1) {
+        // `b` has more digits, so its first digit alone is already below it.
+        char::from(b[0]).to_string()
+    } else {
+        // Keep `a`'s digit, even a zero, and go halfway between the rest of
+        // `a` and the end of that digit.
+        char_of(digit_a).to_string() + &midpoint(a.get(1..).unwrap_or_default(), None)
+    }
+}
+
+fn char_of(digit: u32) -> char {
+    DIGITS[digit as usize] as char
+}
+
+/// Writes `digits` as a key, dropping trailing zeros (which don't change
+/// its value).
+fn encode(digits: &[u32]) -> String {
+    let end = digits.iter().rposition(|&d| d != 0).map_or(0, |i| i + 1);
+    digits[..end].iter().map(|&d| char_of(d)).collect()
+}
+
+/// `count` evenly spaced keys in ascending order, for ordering a whole list
+/// from scratch.
+pub fn spread(count: usize) -> Vec<String> {
+    let slots = count as u128 + 1;
+    // One digit of headroom beyond what tells the keys apart.
+    let mut width = 1;
+    let mut space = BASE as u128;
+    while space / slots < BASE as u128 {
+        width += 1;
+        space *= BASE as u128;
+    }
+    let step = space / slots;
+    (1..slots)
+        .map(|slot| {
+            let mut rest = slot * step;
+            let mut digits = vec![0; width];
+            for digit in digits.iter_mut().rev() {
+                *digit = (rest % BASE as u128) as u32;
+                rest /= BASE as u128;
+            }
+            encode(&digits)
+        })
+        .collect()
+}
@@ -0,0 +1,64 @@
+use super::*;
+
+fn key(before: Option<&str>, after: Option<&str>) -> String {
+    let key = between(before, after).unwrap();
+    assert!(is_valid(&key), "{:?}", key);
+    if let Some(before) = before {
+        assert!(before < key.as_str(), "{} !< {}", before, key);
+    }
+    if let Some(after) = after {
+        assert!(key.as_str() < after, "{} !< {}", key, after);
+    }
+    key
+}
+
+#[test]
+fn test_keys_fall_between_neighbors() {
+    assert_eq!(key(None, None), "i");
+    key(Some("i"), None);
+    key(None, Some("i"));
+    key(Some("1"), Some("2"));
+    key(Some("1"), Some("101"));
+    key(None, Some("001"));
+    key(Some("zz"), None);
+    key(Some("a"), Some("a1"));
+}
+
+#[test]
+fn test_invalid_or_unordered_neighbors_are_rejected() {
+    assert!(between(Some("b"), Some("a")).is_err());
+    assert!(between(Some("a"), Some("a")).is_err());
+    assert!(between(Some("a0"), None).is_err());
+    assert!(between(None, Some("A")).is_err());
+    assert!(between(Some(""), None).is_err());
+}
+
+#[test]
+fn test_appends_stay_short() {
+    let mut last = key(None, None);
+    for _ in 0..100_000 {
+        last = key(Some(&last), None);
+    }
+    assert!(last.len() <= APPEND_PRECISION, "{}", last);
+}
+
+#[test]
+fn test_repeated_inserts_in_one_gap_grow_slowly() {
+    let (low, mut high) = ("i".to_string(), "j".to_string());
+    for _ in 0..50 {
+        high = key(Some(&low), Some(&high));
+    }
+    assert!(high.len() <= 20, "{}", high);
+    assert!(!needs_rebalance(&high));
+}
+
+#[test]
+fn test_spread_is_ordered_and_valid() {
+    for count in [0, 1, 35, 36, 1000, 100_000] {
+        let keys = spread(count);
+        assert_eq!(keys.len(), count);
+        assert!(keys.iter().all(|k| is_valid(k)));
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+    }
+    assert!(spread(100_000).iter().all(|k| k.len() <= 5));
+}
@@ -0,0 +1,97 @@
+//! Read-only mode for a store written by a newer version of the app.
+//! Rather than refusing to start, the app opens the database read-only and
+//! refuses the commands that write to it, so queries, search and exports
+//! keep working and the data can be rescued until the app is updated.
+
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::events;
+use crate::storage::{ReadOnlyNewerSchema, Storage};
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// Commands refused while the store is read-only.
+pub const MUTATING_COMMANDS: &[&str] = &[
+    "add_connector_instance",
+    "add_export_schedule",
+    "add_rule",
+    "apply_bulk_edit",
+    "cancel_scheduled_command",
+    "create_smart_list",
+    "define_custom_field",
+    "delete_custom_field",
+    "delete_rule",
+    "delete_smart_list",
+    "flush_drafts",
+    "import_bookmarks",
+    "import_directory",
+    "import_legacy_server_db",
+    "import_todos",
+    "merge_todos",
+    "migrate_legacy_data",
+    "open_bulk_edit",
+    "publish_list",
+    "publish_static_site",
+    "remove_connector_instance",
+    "remove_export_schedule",
+    "reorder_todo",
+    "resume_import",
+    "retry_last_failed",
+    "run_connector_now",
+    "run_export_schedule_now",
+    "schedule_command",
+    "schedule_static_site",
+    "set_rule_enabled",
+    "set_todo_field_value",
+    "split_todo",
+    "start_focus_session",
+    "stop_focus_session",
+    "subscribe_remote_list",
+    "sync_to_system_calendar",
+    "sync_todo_cache",
+    "undo_bulk_edit",
+    "unschedule_static_site",
+    "unsubscribe_remote_list",
+    "update_reference_text",
+    "update_todo",
+    "update_todo_draft",
+];
+
+/// Fails if `command` writes to a store that is read-only.
+pub fn check(storage: &Storage, command: &str) -> Result<(), ReadOnlyNewerSchema> {
+    match storage.newer_schema() {
+        Some(newer) if MUTATING_COMMANDS.contains(&command) => Err(newer.clone()),
+        _ => Ok(()),
+    }
+}
+
+/// Wraps the invoke handler so writing commands fail with
+/// [`ReadOnlyNewerSchema`] while the store is read-only.
+pub fn guard<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let refused = invoke
+            .message
+            .webview()
+            .try_state::<Storage>()
+            .and_then(|storage| check(&storage, invoke.message.command()).err());
+        if let Some(error) = refused {
+            invoke.resolver.reject(error);
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+/// Emits the sticky `read-only-mode` event if the store is read-only, so
+/// every window shows the banner asking to update the app.
+pub fn announce_at_startup(app: &AppHandle) {
+    if let Some(newer) = app.state::<Storage>().newer_schema() {
+        trace::warn(newer);
+        let _ = events::emit_sticky(app, "read-only-mode", newer.clone());
+    }
+}
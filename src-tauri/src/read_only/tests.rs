@@ -0,0 +1,87 @@
+use super::*;
+
+use chrono::{TimeZone, Utc};
+use rusqlite::Connection;
+
+use crate::search::SearchIndex;
+use crate::storage::{self, StorageBackend};
+use crate::types::Todo;
+
+fn todo(id: &str, title: &str) -> Todo {
+    let at = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo::sample(id, title, at)
+}
+
+/// A store as a newer app leaves it: one migration ahead, with a column
+/// this build doesn't know.
+fn newer_store(dir: &std::path::Path) -> u32 {
+    let storage = Storage::open(StorageBackend::Disk(dir.to_path_buf())).unwrap();
+    storage
+        .replace_todos(&[todo("a", "Renew passport"), todo("b", "Book flights")])
+        .unwrap();
+    let conn = storage.conn();
+    let version: u32 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    conn.execute_batch(&format!(
+        "ALTER TABLE todos ADD COLUMN color TEXT DEFAULT 'red';
+         PRAGMA user_version = {};",
+        version + 1
+    ))
+    .unwrap();
+    version
+}
+
+#[test]
+fn test_newer_schema_opens_read_only_for_reads() {
+    let dir = tempfile::tempdir().unwrap();
+    let known = newer_store(dir.path());
+
+    let storage = Storage::open(StorageBackend::Disk(dir.path().to_path_buf())).unwrap();
+    let newer = storage.newer_schema().cloned().unwrap();
+    assert_eq!((newer.db_version, newer.app_version), (known + 1, known));
+
+    let todos = storage.list_todos().unwrap();
+    assert_eq!(todos.len(), 2);
+    let index = SearchIndex::default();
+    index.rebuild(&todos);
+    assert_eq!(index.similar("Renew pasport", 1)[0].id, "a");
+    let backup = storage::back_up(&storage, "rescue").unwrap();
+    assert_eq!(
+        Connection::open(backup)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM todos", [], |row| row.get::<_, i64>(0))
+            .unwrap(),
+        2
+    );
+
+    assert!(storage.save_todo(&todo("c", "Pack")).is_err());
+    assert!(storage
+        .connect()
+        .unwrap()
+        .execute("DELETE FROM todos", [])
+        .is_err());
+    let version: u32 = storage
+        .conn()
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(version, known + 1);
+}
+
+#[test]
+fn test_only_writing_commands_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    newer_store(dir.path());
+    let storage = Storage::open(StorageBackend::Disk(dir.path().to_path_buf())).unwrap();
+
+    let error = check(&storage, "update_todo").unwrap_err();
+    assert_eq!(
+        serde_json::to_value(&error).unwrap()["kind"],
+        "readOnlyNewerSchema"
+    );
+    assert!(check(&storage, "query_todos").is_ok());
+    assert!(check(&storage, "export_signed").is_ok());
+
+    let current = Storage::open_in_memory().unwrap();
+    assert!(check(&current, "update_todo").is_ok());
+}
@@ -0,0 +1,362 @@
+//! References between todos in descriptions: `[[Title]]` wiki links and
+//! `#<short-id>` id prefixes, and keeping them from rotting.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::events;
+use crate::protocol::EventPayload;
+use crate::query::Span;
+use crate::search;
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+/// Shorter `#` tokens are treated as hashtags or issue numbers, not ids.
+const MIN_SHORT_ID_LEN: usize = 6;
+/// Wiki links resolve to titles at least this similar when none match exactly.
+const FUZZY_TITLE_THRESHOLD: f32 = 0.6;
+/// Descriptions scanned per query by [`broken_references`].
+const SCAN_BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ReferenceTarget {
+    WikiLink { title: String },
+    ShortId { id: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoReference {
+    #[serde(flatten)]
+    pub target: ReferenceTarget,
+    /// Byte range of the whole token, brackets or `#` included.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum Resolution {
+    Resolved {
+        todo_id: String,
+    },
+    /// Several todos match; best match first.
+    Ambiguous {
+        candidates: Vec<String>,
+    },
+    Broken,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedReference {
+    pub reference: TodoReference,
+    pub resolution: Resolution,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The short id starting right after the `#` at `text[start..]`, if the token
+/// looks like one: hex digits and hyphens, at least one digit, and not glued
+/// to surrounding words.
+fn short_id_at(text: &str, start: usize) -> Option<&str> {
+    if text[..start].chars().next_back().is_some_and(is_word_char) {
+        return None;
+    }
+    let rest = &text[start + 1..];
+    let len = rest
+        .find(|c: char| !(c.is_ascii_hexdigit() || c == '-'))
+        .unwrap_or(rest.len());
+    let id = rest[..len].trim_end_matches('-');
+    let glued = rest[len..].chars().next().is_some_and(is_word_char);
+    (!glued && id.len() >= MIN_SHORT_ID_LEN && id.bytes().any(|b| b.is_ascii_digit())).then_some(id)
+}
+
+pub fn parse_references(text: &str) -> Vec<TodoReference> {
+    let mut references = Vec::new();
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        if text[i..].starts_with("[[") {
+            let inner_start = i + 2;
+            if let Some(len) = text[inner_start..].find("]]") {
+                let inner = &text[inner_start..inner_start + len];
+                if !inner.contains(['\n', '[']) && !inner.trim().is_empty() {
+                    let end = inner_start + len + 2;
+                    references.push(TodoReference {
+                        target: ReferenceTarget::WikiLink {
+                            title: inner.trim().to_string(),
+                        },
+                        span: Span { start: i, end },
+                    });
+                    i = end;
+                    continue;
+                }
+            }
+        } else if c == '#' {
+            if let Some(id) = short_id_at(text, i) {
+                let end = i + 1 + id.len();
+                references.push(TodoReference {
+                    target: ReferenceTarget::ShortId { id: id.to_string() },
+                    span: Span { start: i, end },
+                });
+                i = end;
+                continue;
+            }
+        }
+        i += c.len_utf8();
+    }
+    references
+}
+
+fn resolution_from(mut candidates: Vec<String>) -> Resolution {
+    match candidates.len() {
+        0 => Resolution::Broken,
+        1 => Resolution::Resolved {
+            todo_id: candidates.remove(0),
+        },
+        _ => Resolution::Ambiguous { candidates },
+    }
+}
+
+fn resolve_one(target: &ReferenceTarget, todos: &[Todo]) -> Resolution {
+    match target {
+        ReferenceTarget::ShortId { id } => {
+            let id = id.to_ascii_lowercase();
+            resolution_from(
+                todos
+                    .iter()
+                    .filter(|t| t.id.to_ascii_lowercase().starts_with(&id))
+                    .map(|t| t.id.clone())
+                    .collect(),
+            )
+        }
+        ReferenceTarget::WikiLink { title } => {
+            let normalized = search::normalize_title(title);
+            let exact: Vec<String> = todos
+                .iter()
+                .filter(|t| search::normalize_title(&t.title) == normalized)
+                .map(|t| t.id.clone())
+                .collect();
+            if !exact.is_empty() {
+                return resolution_from(exact);
+            }
+            let mut fuzzy: Vec<(f32, &Todo)> = todos
+                .iter()
+                .map(|t| (search::similarity(title, &t.title), t))
+                .filter(|(score, _)| *score >= FUZZY_TITLE_THRESHOLD)
+                .collect();
+            fuzzy.sort_by(|a, b| b.0.total_cmp(&a.0));
+            resolution_from(fuzzy.into_iter().map(|(_, t)| t.id.clone()).collect())
+        }
+    }
+}
+
+pub fn resolve_references(references: &[TodoReference], todos: &[Todo]) -> Vec<ResolvedReference> {
+    references
+        .iter()
+        .map(|reference| ResolvedReference {
+            reference: reference.clone(),
+            resolution: resolve_one(&reference.target, todos),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenReferences {
+    pub todo_id: String,
+    pub title: String,
+    pub references: Vec<TodoReference>,
+}
+
+/// Todos whose descriptions contain references that resolve to nothing.
+pub fn broken_references(storage: &Storage) -> rusqlite::Result<Vec<BrokenReferences>> {
+    let todos = storage.list_todos()?;
+    let mut report = Vec::new();
+    let mut offset = 0;
+    loop {
+        let batch: Vec<(String, String, String)> = {
+            let conn = storage.conn();
+            let mut stmt = conn.prepare(
+                "SELECT id, title, description FROM todos
+                 WHERE description LIKE '%[[%' OR description LIKE '%#%'
+                 ORDER BY id LIMIT ?1 OFFSET ?2",
+            )?;
+            let rows = stmt.query_map([SCAN_BATCH_SIZE, offset], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        let done = (batch.len() as i64) < SCAN_BATCH_SIZE;
+        offset += SCAN_BATCH_SIZE;
+
+        for (todo_id, title, description) in batch {
+            let broken: Vec<TodoReference> =
+                resolve_references(&parse_references(&description), &todos)
+                    .into_iter()
+                    .filter(|r| r.resolution == Resolution::Broken)
+                    .map(|r| r.reference)
+                    .collect();
+            if !broken.is_empty() {
+                report.push(BrokenReferences {
+                    todo_id,
+                    title,
+                    references: broken,
+                });
+            }
+        }
+        if done {
+            return Ok(report);
+        }
+    }
+}
+
+/// Payload of `references-stale`: todos whose references pointed at a todo
+/// that was just renamed or completed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleReferences {
+    pub todo_id: String,
+    pub renamed_from: Option<String>,
+    pub completed: bool,
+    /// Referencing todo id and its affected references.
+    pub referencing: Vec<(String, Vec<TodoReference>)>,
+}
+
+impl EventPayload for StaleReferences {}
+
+/// Finds references made stale by `previous` becoming `updated`. After a
+/// rename only wiki links need new text; after completion every reference
+/// points at a done item. References are resolved against `todos` with
+/// `previous` in place of `updated`, so links to the old title still match.
+pub fn stale_references(
+    todos: &[Todo],
+    previous: &Todo,
+    updated: &Todo,
+) -> Option<StaleReferences> {
+    let renamed = previous.title != updated.title;
+    let completed = !previous.completed && updated.completed;
+    if !renamed && !completed {
+        return None;
+    }
+    let todos: Vec<Todo> = todos
+        .iter()
+        .map(|t| if t.id == updated.id { previous } else { t })
+        .cloned()
+        .collect();
+    let referencing: Vec<(String, Vec<TodoReference>)> = todos
+        .iter()
+        .filter(|t| t.id != updated.id)
+        .filter_map(|t| {
+            let description = t.description.as_deref()?;
+            let affected: Vec<TodoReference> =
+                resolve_references(&parse_references(description), &todos)
+                    .into_iter()
+                    .filter(|r| {
+                        r.resolution
+                            == Resolution::Resolved {
+                                todo_id: updated.id.clone(),
+                            }
+                    })
+                    .map(|r| r.reference)
+                    .filter(|r| completed || matches!(r.target, ReferenceTarget::WikiLink { .. }))
+                    .collect();
+            (!affected.is_empty()).then(|| (t.id.clone(), affected))
+        })
+        .collect();
+    (!referencing.is_empty()).then(|| StaleReferences {
+        todo_id: updated.id.clone(),
+        renamed_from: renamed.then(|| previous.title.clone()),
+        completed,
+        referencing,
+    })
+}
+
+/// Replaces every reference in `text` whose target is written as `old` with
+/// one to `new`, working back to front so earlier spans stay valid. Returns
+/// `None` when no reference matched.
+pub fn rewrite_references(text: &str, old: &str, new: &str) -> Option<String> {
+    let mut rewritten = text.to_string();
+    let mut replaced = false;
+    for reference in parse_references(text).iter().rev() {
+        let replacement = match &reference.target {
+            ReferenceTarget::WikiLink { title } if title == old => format!("[[{}]]", new),
+            ReferenceTarget::ShortId { id } if id == old => format!("#{}", new),
+            _ => continue,
+        };
+        // Spans come from `char_indices`, so they always fall on char boundaries.
+        rewritten.replace_range(reference.span.start..reference.span.end, &replacement);
+        replaced = true;
+    }
+    replaced.then_some(rewritten)
+}
+
+#[tauri::command]
+pub fn parse_todo_references(text: String) -> Vec<TodoReference> {
+    parse_references(&text)
+}
+
+#[tauri::command]
+pub fn resolve_todo_references(
+    references: Vec<TodoReference>,
+    storage: State<'_, Storage>,
+) -> Result<Vec<ResolvedReference>, String> {
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    Ok(resolve_references(&references, &todos))
+}
+
+#[tauri::command]
+pub async fn broken_references_report(
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<Vec<BrokenReferences>, String> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        use tauri::Manager;
+        broken_references(&app.state::<Storage>())
+            .map_err(|e| format!("Failed to scan references: {}", e))
+    }))
+    .await
+    .map_err(|e| format!("Failed to scan references: {}", e))?
+}
+
+/// Rewrites the references to `old` in one todo's description.
+#[tauri::command]
+pub fn update_reference_text(
+    todo_id: String,
+    old: String,
+    new: String,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<Todo, String> {
+    let mut todo = storage
+        .get_todo(&todo_id)
+        .map_err(|e| format!("Failed to load todo: {}", e))?
+        .ok_or_else(|| format!("Unknown todo '{}'", todo_id))?;
+    let description = todo.description.as_deref().unwrap_or_default();
+    let rewritten = rewrite_references(description, &old, &new)
+        .ok_or_else(|| format!("No reference to '{}' in '{}'", old, todo.title))?;
+    todo.description = Some(rewritten);
+    todo.updated_at = storage.clock().now();
+    let update = storage::apply_update(&storage, todo, None)
+        .map_err(|e| format!("Failed to update references: {}", e))?
+        .ok_or_else(|| "Failed to update references: nothing changed".to_string())?;
+    let _ = events::emit(&app, "todo-updated", &update);
+    Ok(update.todo)
+}
+
+/// Emits `references-stale` if an update renamed or completed a todo that
+/// other descriptions refer to. `todos` is the cache after the update.
+pub fn notify_stale_references(app: &AppHandle, todos: &[Todo], previous: &Todo, updated: &Todo) {
+    if let Some(stale) = stale_references(todos, previous, updated) {
+        let _ = events::emit(app, "references-stale", &stale);
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+use chrono::Utc;
+
+fn todo(id: &str, title: &str, description: Option<&str>) -> Todo {
+    let now = Utc::now();
+    Todo {
+        description: description.map(str::to_string),
+        ..Todo::sample(id, title, now)
+    }
+}
+
+fn sample() -> Vec<Todo> {
+    vec![
+        todo("3f2a9c10-aaaa", "Prepare meeting agenda", None),
+        todo("3f2a9c20-bbbb", "Book flights", None),
+        todo("7b81e4d0-cccc", "Book hotel", None),
+        todo(
+            "91c0ffee-dddd",
+            "Write notes",
+            Some("See [[Prepare meeting agenda]] and #7b81e4d0"),
+        ),
+    ]
+}
+
+fn wiki(title: &str) -> ReferenceTarget {
+    ReferenceTarget::WikiLink {
+        title: title.to_string(),
+    }
+}
+
+fn short(id: &str) -> ReferenceTarget {
+    ReferenceTarget::ShortId { id: id.to_string() }
+}
+
+#[test]
+fn test_parse_finds_wiki_links_and_short_ids() {
+    let text = "See [[ Book hotel ]] and #3f2a9c10, not #urgent or #12 or a#3f2a9c10";
+    let references = parse_references(text);
+
+    let targets: Vec<_> = references.iter().map(|r| r.target.clone()).collect();
+    assert_eq!(targets, vec![wiki("Book hotel"), short("3f2a9c10")]);
+    assert_eq!(
+        &text[references[0].span.start..references[0].span.end],
+        "[[ Book hotel ]]"
+    );
+    assert_eq!(
+        &text[references[1].span.start..references[1].span.end],
+        "#3f2a9c10"
+    );
+}
+
+#[test]
+fn test_parse_spans_are_byte_offsets_around_non_ascii_text() {
+    let text = "日本語 [[会議の準備]] と #3f2a9c10 ✓";
+    let references = parse_references(text);
+
+    assert_eq!(references.len(), 2);
+    assert_eq!(
+        &text[references[0].span.start..references[0].span.end],
+        "[[会議の準備]]"
+    );
+    assert_eq!(
+        &text[references[1].span.start..references[1].span.end],
+        "#3f2a9c10"
+    );
+}
+
+#[test]
+fn test_parse_ignores_unclosed_and_multiline_links() {
+    assert!(parse_references("[[never closed").is_empty());
+    assert!(parse_references("[[split\nline]]").is_empty());
+    assert!(parse_references("[[  ]]").is_empty());
+}
+
+#[test]
+fn test_resolve_short_ids_by_prefix() {
+    let todos = sample();
+    let resolved = resolve_references(&parse_references("#7b81e4 #3f2a9c #abcdef1"), &todos);
+
+    assert_eq!(
+        resolved[0].resolution,
+        Resolution::Resolved {
+            todo_id: "7b81e4d0-cccc".to_string()
+        }
+    );
+    assert!(matches!(
+        &resolved[1].resolution,
+        Resolution::Ambiguous { candidates } if candidates.len() == 2
+    ));
+    assert_eq!(resolved[2].resolution, Resolution::Broken);
+}
+
+#[test]
+fn test_resolve_wiki_links_exactly_then_fuzzily() {
+    let todos = sample();
+    let resolved = resolve_references(
+        &parse_references("[[prepare  MEETING agenda]] [[Prepare meeting agendas]] [[Buy milk]]"),
+        &todos,
+    );
+
+    let agenda = Resolution::Resolved {
+        todo_id: "3f2a9c10-aaaa".to_string(),
+    };
+    assert_eq!(resolved[0].resolution, agenda);
+    assert_eq!(resolved[1].resolution, agenda);
+    assert_eq!(resolved[2].resolution, Resolution::Broken);
+}
+
+#[test]
+fn test_broken_references_scans_stored_descriptions() {
+    let storage = Storage::open_in_memory().unwrap();
+    let mut todos = sample();
+    todos.push(todo(
+        "00000000-eeee",
+        "Old",
+        Some("Blocked by [[Gone]] and #deadbeef1"),
+    ));
+    storage.replace_todos(&todos).unwrap();
+
+    let report = broken_references(&storage).unwrap();
+
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0].todo_id, "00000000-eeee");
+    let targets: Vec<_> = report[0]
+        .references
+        .iter()
+        .map(|r| r.target.clone())
+        .collect();
+    assert_eq!(targets, vec![wiki("Gone"), short("deadbeef1")]);
+}
+
+#[test]
+fn test_stale_references_after_rename_lists_wiki_links_only() {
+    let todos = sample();
+    let previous = todos[0].clone();
+    let mut renamed = previous.clone();
+    renamed.title = "Prepare board agenda".to_string();
+    let after: Vec<Todo> = todos
+        .iter()
+        .map(|t| {
+            if t.id == renamed.id {
+                renamed.clone()
+            } else {
+                t.clone()
+            }
+        })
+        .collect();
+
+    let stale = stale_references(&after, &previous, &renamed).unwrap();
+
+    assert_eq!(
+        stale.renamed_from.as_deref(),
+        Some("Prepare meeting agenda")
+    );
+    assert!(!stale.completed);
+    assert_eq!(stale.referencing.len(), 1);
+    assert_eq!(stale.referencing[0].0, "91c0ffee-dddd");
+    assert_eq!(
+        stale.referencing[0].1[0].target,
+        wiki("Prepare meeting agenda")
+    );
+}
+
+#[test]
+fn test_stale_references_after_completion_include_short_ids() {
+    let todos = sample();
+    let previous = todos[2].clone();
+    let mut done = previous.clone();
+    done.completed = true;
+
+    let stale = stale_references(&todos, &previous, &done).unwrap();
+
+    assert!(stale.completed);
+    assert_eq!(stale.referencing[0].1[0].target, short("7b81e4d0"));
+    assert!(stale_references(&todos, &previous, &previous).is_none());
+}
+
+#[test]
+fn test_rewrite_references_keeps_surrounding_non_ascii_text() {
+    let text = "前 [[会議]] 中 [[会議]] 後 #3f2a9c10 ✓";
+
+    assert_eq!(
+        rewrite_references(text, "会議", "定例会議の準備").as_deref(),
+        Some("前 [[定例会議の準備]] 中 [[定例会議の準備]] 後 #3f2a9c10 ✓")
+    );
+    assert_eq!(
+        rewrite_references(text, "3f2a9c10", "7b81e4d0").as_deref(),
+        Some("前 [[会議]] 中 [[会議]] 後 #7b81e4d0 ✓")
+    );
+    assert_eq!(rewrite_references(text, "missing", "x"), None);
+}
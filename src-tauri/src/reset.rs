@@ -0,0 +1,423 @@
+//! Targeted resets for troubleshooting, so "reset the app" no longer means
+//! hunting for directories. Each scope removes only its own files; the
+//! self-check's repairs use the same primitives.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::cache::Caches;
+use crate::events;
+use crate::integrations::connectors;
+use crate::local_api::{self, LocalApiState};
+use crate::protocol::EventPayload;
+use crate::quota;
+use crate::safety::{self, DestructionScope, DestructiveOperation, SafetyState};
+use crate::search::SearchIndex;
+use crate::settings::{
+    self, Settings, SettingsReactions, SettingsState, KEYBINDINGS_FILE, SETTINGS_FILE,
+};
+use crate::storage::{self, Storage};
+use crate::sync;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// Rendered card previews, under the cache directory.
+pub const THUMBNAILS_DIR: &str = "thumbnails";
+/// Window size and position, under the config directory.
+pub const WINDOW_STATE_FILE: &str = ".window-state.json";
+/// Left in the data directory by an `Everything` reset or a secure wipe; the
+/// next start wipes the app's directories before opening anything in them.
+const RESET_MARKER: &str = ".reset-pending";
+/// Contents of the marker when files must be overwritten before deletion.
+const SECURE_MARKER: &[u8] = b"secure";
+/// What the user types to confirm [`secure_wipe`].
+pub const SECURE_WIPE_PHRASE: &str = "WIPE ALL MY DATA";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ResetScope {
+    SearchIndex,
+    Thumbnails,
+    WindowState,
+    Settings,
+    Keybindings,
+    /// Everything that is rebuilt on demand: the search index, the cache
+    /// directory and the in-memory caches.
+    AllCaches,
+    /// All of the app's data, settings and caches; the app restarts into a
+    /// first run.
+    Everything,
+}
+
+impl ResetScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            ResetScope::SearchIndex => "search-index",
+            ResetScope::Thumbnails => "thumbnails",
+            ResetScope::WindowState => "window-state",
+            ResetScope::Settings => "settings",
+            ResetScope::Keybindings => "keybindings",
+            ResetScope::AllCaches => "all-caches",
+            ResetScope::Everything => "everything",
+        }
+    }
+}
+
+/// The app's directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResetPaths {
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf,
+    pub cache_dir: PathBuf,
+}
+
+impl ResetPaths {
+    pub fn resolve(app: &AppHandle) -> tauri::Result<Self> {
+        Ok(Self {
+            data_dir: storage::data_dir(app)?,
+            config_dir: settings::config_dir(app)?,
+            cache_dir: app.path().app_cache_dir()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Removed {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetReport {
+    pub scope: ResetScope,
+    pub removed: Vec<Removed>,
+    pub rebuilt_search_index: bool,
+    /// The removal happens when the app restarts, which it is about to do.
+    pub restarting: bool,
+}
+
+impl EventPayload for ResetReport {}
+
+/// Removes `path`, a file or a whole directory, or with `backup_at` renames
+/// it to `<path>.broken-<timestamp>` instead. Missing paths are not an error.
+pub fn discard(path: &Path, backup_at: Option<DateTime<Utc>>) -> io::Result<Option<Removed>> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let bytes = if metadata.is_dir() {
+        quota::dir_size(path)
+    } else {
+        metadata.len()
+    };
+    if let Some(backup_at) = backup_at {
+        let mut aside = path.as_os_str().to_owned();
+        aside.push(format!(".broken-{}", backup_at.format("%Y%m%d%H%M%S")));
+        fs::rename(path, aside)?;
+    } else if metadata.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(Some(Removed {
+        path: path.to_path_buf(),
+        bytes,
+    }))
+}
+
+/// Overwrites every file under `path` with random bytes, in place. Symbolic
+/// links are left alone rather than followed out of the app's directories.
+pub fn overwrite(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            overwrite(&entry?.path())?;
+        }
+        return Ok(());
+    }
+    if !metadata.is_file() {
+        return Ok(());
+    }
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut remaining = metadata.len();
+    while remaining > 0 {
+        let chunk = &mut buffer[..remaining.min(64 * 1024) as usize];
+        getrandom::getrandom(chunk).map_err(|e| io::Error::other(e.to_string()))?;
+        file.write_all(chunk)?;
+        remaining -= chunk.len() as u64;
+    }
+    file.sync_all()
+}
+
+/// Removes everything inside `dir`, keeping `dir` itself.
+pub fn clear_dir(dir: &Path) -> io::Result<Vec<Removed>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut removed = Vec::new();
+    for entry in entries {
+        removed.extend(discard(&entry?.path(), None)?);
+    }
+    Ok(removed)
+}
+
+/// What an `Everything` reset removes: the contents of every app directory.
+/// Directories nested in another (the config directory sits inside the data
+/// directory on Windows) are only counted once.
+fn everything(paths: &ResetPaths) -> Vec<&Path> {
+    let dirs = [&paths.data_dir, &paths.config_dir, &paths.cache_dir];
+    dirs.iter()
+        .filter(|dir| {
+            !dirs
+                .iter()
+                .any(|other| other != *dir && dir.starts_with(other))
+        })
+        .map(|dir| dir.as_path())
+        .collect()
+}
+
+/// Removes the files of `scope`. The search index lives in memory, so it has
+/// none; `Everything` is left to [`finish_pending`].
+pub fn remove_files(scope: ResetScope, paths: &ResetPaths) -> io::Result<Vec<Removed>> {
+    let file = |path: PathBuf| Ok(discard(&path, None)?.into_iter().collect());
+    match scope {
+        ResetScope::SearchIndex | ResetScope::Everything => Ok(Vec::new()),
+        ResetScope::Thumbnails => file(paths.cache_dir.join(THUMBNAILS_DIR)),
+        ResetScope::WindowState => file(paths.config_dir.join(WINDOW_STATE_FILE)),
+        ResetScope::Settings => file(paths.config_dir.join(SETTINGS_FILE)),
+        ResetScope::Keybindings => file(paths.config_dir.join(KEYBINDINGS_FILE)),
+        ResetScope::AllCaches => clear_dir(&paths.cache_dir),
+    }
+}
+
+/// Empties every app directory, with `secure` overwriting each file first.
+/// The marker goes last, so an interrupted wipe is picked up again.
+pub fn wipe(paths: &ResetPaths, secure: bool) -> io::Result<Vec<Removed>> {
+    let marker = paths.data_dir.join(RESET_MARKER);
+    let mut removed = Vec::new();
+    for dir in everything(paths) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path == marker {
+                continue;
+            }
+            if secure {
+                overwrite(&path)?;
+            }
+            removed.extend(discard(&path, None)?);
+        }
+    }
+    discard(&marker, None)?;
+    Ok(removed)
+}
+
+/// Lists what [`wipe`] would remove.
+fn pending_removals(paths: &ResetPaths) -> io::Result<Vec<Removed>> {
+    let mut removed = Vec::new();
+    for dir in everything(paths) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            removed.push(Removed {
+                bytes: if metadata.is_dir() {
+                    quota::dir_size(&entry.path())
+                } else {
+                    metadata.len()
+                },
+                path: entry.path(),
+            });
+        }
+    }
+    Ok(removed)
+}
+
+/// Marks the app's data for wiping at the next start.
+pub fn schedule_wipe(paths: &ResetPaths, secure: bool) -> io::Result<()> {
+    fs::create_dir_all(&paths.data_dir)?;
+    let marker: &[u8] = if secure { SECURE_MARKER } else { b"" };
+    fs::write(paths.data_dir.join(RESET_MARKER), marker)
+}
+
+/// Carries out a wipe scheduled by [`schedule_wipe`]. Runs before the store
+/// is opened, so nothing holds the files.
+pub fn finish_pending(paths: &ResetPaths) -> io::Result<Option<Vec<Removed>>> {
+    let marker = match fs::read(paths.data_dir.join(RESET_MARKER)) {
+        Ok(marker) => marker,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    wipe(paths, marker == SECURE_MARKER).map(Some)
+}
+
+/// Reads the settings and carries out a pending wipe. The marker is looked
+/// for in the data directory the settings pick over `paths.data_dir`; the
+/// settings are read again after a wipe, since it removed their file.
+pub fn finish_pending_with(
+    mut paths: ResetPaths,
+    load: impl Fn() -> Settings,
+) -> (Settings, io::Result<Option<Vec<Removed>>>) {
+    let settings = load();
+    if let Some(dir) = &settings.data_dir {
+        paths.data_dir = dir.clone();
+    }
+    match finish_pending(&paths) {
+        Ok(Some(removed)) => (load(), Ok(Some(removed))),
+        result => (settings, result),
+    }
+}
+
+/// Schedules a secure wipe, but only for the exact confirmation phrase.
+pub fn request_secure_wipe(confirm_phrase: &str, paths: &ResetPaths) -> Result<(), String> {
+    if confirm_phrase != SECURE_WIPE_PHRASE {
+        return Err(format!(
+            "Type \"{}\" exactly to confirm the wipe",
+            SECURE_WIPE_PHRASE
+        ));
+    }
+    schedule_wipe(paths, true).map_err(|e| e.to_string())
+}
+
+pub fn rebuild_search_index(storage: &Storage, index: &SearchIndex) -> Result<(), String> {
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    index.rebuild(&todos);
+    Ok(())
+}
+
+fn reset(app: &AppHandle, scope: ResetScope, paths: &ResetPaths) -> Result<ResetReport, String> {
+    if scope == ResetScope::Everything {
+        let removed = pending_removals(paths).map_err(|e| e.to_string())?;
+        schedule_wipe(paths, false).map_err(|e| e.to_string())?;
+        return Ok(ResetReport {
+            scope,
+            removed,
+            rebuilt_search_index: false,
+            restarting: true,
+        });
+    }
+
+    let removed = remove_files(scope, paths).map_err(|e| e.to_string())?;
+    let rebuilt_search_index = matches!(scope, ResetScope::SearchIndex | ResetScope::AllCaches);
+    if rebuilt_search_index {
+        rebuild_search_index(&app.state::<Storage>(), &app.state::<SearchIndex>())?;
+    }
+    if scope == ResetScope::AllCaches {
+        app.state::<Caches>().clear();
+    }
+    if scope == ResetScope::Settings {
+        // Back to defaults in the backend too; the frontend rewrites the file.
+        settings::reload_settings(
+            app.clone(),
+            app.state::<SettingsState>(),
+            app.state::<SettingsReactions>(),
+        )?;
+    }
+    Ok(ResetReport {
+        scope,
+        removed,
+        rebuilt_search_index,
+        restarting: false,
+    })
+}
+
+/// Resets `scope` after checking the confirmation token for it, which is
+/// requested as `resetData` with the scope's kebab-case name as target. An
+/// `Everything` reset restarts the app once the report is returned.
+#[tauri::command]
+pub fn reset_data(
+    scope: ResetScope,
+    confirmation_token: Option<String>,
+    app: AppHandle,
+    safety_state: State<'_, SafetyState>,
+    settings: State<'_, SettingsState>,
+    storage: State<'_, Storage>,
+) -> Result<ResetReport, String> {
+    safety::authorize(
+        &safety_state,
+        &settings.get().safety,
+        &storage,
+        confirmation_token.as_deref(),
+        None,
+        DestructiveOperation::ResetData,
+        &DestructionScope {
+            count: 1,
+            target: Some(scope.as_str().to_string()),
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    let paths = ResetPaths::resolve(&app)
+        .map_err(|e| format!("Failed to resolve app directories: {}", e))?;
+    let report = reset(&app, scope, &paths)
+        .map_err(|e| format!("Failed to reset {}: {}", scope.as_str(), e))?;
+    let _ = events::emit(&app, "data-reset", &report);
+    if report.restarting {
+        app.request_restart();
+    }
+    Ok(report)
+}
+
+/// Erases all app data for decommissioning a device: secrets in the keychain
+/// now, then every file overwritten with random bytes and deleted as the app
+/// restarts, which also brings back default settings.
+#[tauri::command]
+pub fn secure_wipe(
+    confirm_phrase: String,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<(), String> {
+    let paths = ResetPaths::resolve(&app)
+        .map_err(|e| format!("Failed to resolve app directories: {}", e))?;
+    if confirm_phrase != SECURE_WIPE_PHRASE {
+        return request_secure_wipe(&confirm_phrase, &paths);
+    }
+    app.state::<LocalApiState>().stop();
+    local_api::delete_token().map_err(|e| format!("Failed to wipe: {}", e))?;
+    sync::delete_publish_tokens(&storage).map_err(|e| format!("Failed to wipe: {}", e))?;
+    connectors::delete_credentials(&storage).map_err(|e| format!("Failed to wipe: {}", e))?;
+    request_secure_wipe(&confirm_phrase, &paths).map_err(|e| format!("Failed to wipe: {}", e))?;
+    trace::log("Secure wipe scheduled; restarting");
+    app.request_restart();
+    Ok(())
+}
+
+/// Wipes the app's directories if the last run asked for it, and returns the
+/// settings to start with. Called at startup before anything is opened.
+pub fn finish_pending_at_startup(app: &AppHandle, settings_file: &Path) -> tauri::Result<Settings> {
+    let paths = ResetPaths::resolve(app)?;
+    let (settings, result) = finish_pending_with(paths, || Settings::load(settings_file));
+    match result {
+        Ok(Some(removed)) => trace::log(format!(
+            "Reset all app data: removed {} entries",
+            removed.len()
+        )),
+        Ok(None) => {}
+        Err(e) => trace::log(format!("Failed to reset app data: {}", e)),
+    }
+    Ok(settings)
+}
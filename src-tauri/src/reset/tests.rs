@@ -0,0 +1,217 @@
+use super::*;
+
+use chrono::TimeZone;
+
+use crate::storage::StorageBackend;
+use crate::types::Todo;
+
+fn setup() -> (tempfile::TempDir, ResetPaths) {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = ResetPaths {
+        data_dir: dir.path().join("data"),
+        config_dir: dir.path().join("config"),
+        cache_dir: dir.path().join("cache"),
+    };
+    for dir in [&paths.data_dir, &paths.config_dir, &paths.cache_dir] {
+        fs::create_dir_all(dir).unwrap();
+    }
+    (dir, paths)
+}
+
+fn todo(id: &str, title: &str) -> Todo {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo::sample(id, title, created)
+}
+
+fn entries(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_search_index_reset_leaves_todos_alone() {
+    let (_dir, paths) = setup();
+    let storage = Storage::open(StorageBackend::Disk(paths.data_dir.clone())).unwrap();
+    storage
+        .replace_todos(&[todo("1", "Water the plants"), todo("2", "Pay rent")])
+        .unwrap();
+    let before = storage.list_todos().unwrap();
+    let index = SearchIndex::default();
+
+    assert_eq!(
+        remove_files(ResetScope::SearchIndex, &paths).unwrap(),
+        Vec::new()
+    );
+    rebuild_search_index(&storage, &index).unwrap();
+
+    assert_eq!(storage.list_todos().unwrap(), before);
+    assert_eq!(before.len(), 2);
+    assert!(paths.data_dir.join("yutodo.db").exists());
+    assert_eq!(index.similar("Water the plants", 1)[0].id, "1");
+}
+
+#[test]
+fn test_settings_reset_removes_only_settings() {
+    let (_dir, paths) = setup();
+    fs::write(paths.config_dir.join(SETTINGS_FILE), "[app]\n").unwrap();
+    fs::write(paths.config_dir.join(KEYBINDINGS_FILE), "[[bindings]]\n").unwrap();
+
+    let removed = remove_files(ResetScope::Settings, &paths).unwrap();
+
+    assert_eq!(
+        removed,
+        vec![Removed {
+            path: paths.config_dir.join(SETTINGS_FILE),
+            bytes: 6,
+        }]
+    );
+    assert_eq!(entries(&paths.config_dir), vec![KEYBINDINGS_FILE]);
+    // Already gone: nothing to report, and not an error.
+    assert!(remove_files(ResetScope::Settings, &paths)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_all_caches_empties_cache_dir_only() {
+    let (_dir, paths) = setup();
+    fs::create_dir_all(paths.cache_dir.join(THUMBNAILS_DIR)).unwrap();
+    fs::write(paths.cache_dir.join(THUMBNAILS_DIR).join("a.png"), b"png").unwrap();
+    fs::write(paths.cache_dir.join("other"), b"12345").unwrap();
+    fs::write(paths.config_dir.join(SETTINGS_FILE), "[app]\n").unwrap();
+
+    let mut removed = remove_files(ResetScope::AllCaches, &paths).unwrap();
+    removed.sort_by(|a, b| a.path.cmp(&b.path));
+
+    assert_eq!(
+        removed.iter().map(|r| r.bytes).collect::<Vec<_>>(),
+        vec![5, 3]
+    );
+    assert!(entries(&paths.cache_dir).is_empty());
+    assert_eq!(entries(&paths.config_dir), vec![SETTINGS_FILE]);
+}
+
+#[test]
+fn test_everything_leaves_no_files_behind() {
+    let (_dir, paths) = setup();
+    {
+        let storage = Storage::open(StorageBackend::Disk(paths.data_dir.clone())).unwrap();
+        storage.replace_todos(&[todo("1", "Pay rent")]).unwrap();
+        fs::write(storage.files_dir().join("recording.jsonl"), b"{}").unwrap();
+    }
+    fs::write(paths.config_dir.join(SETTINGS_FILE), "[app]\n").unwrap();
+    fs::create_dir_all(paths.config_dir.join("plugins").join("p")).unwrap();
+    fs::write(paths.config_dir.join(WINDOW_STATE_FILE), b"{}").unwrap();
+    fs::create_dir_all(paths.cache_dir.join(THUMBNAILS_DIR)).unwrap();
+
+    assert_eq!(finish_pending(&paths).unwrap(), None);
+    assert!(!entries(&paths.data_dir).is_empty());
+
+    schedule_wipe(&paths, false).unwrap();
+    let removed = finish_pending(&paths).unwrap().unwrap();
+
+    assert!(!removed.is_empty());
+    for dir in [&paths.data_dir, &paths.config_dir, &paths.cache_dir] {
+        assert_eq!(entries(dir), Vec::<String>::new(), "{}", dir.display());
+    }
+    assert_eq!(finish_pending(&paths).unwrap(), None);
+}
+
+#[test]
+fn test_nested_app_dirs_are_wiped_once() {
+    let (_dir, mut paths) = setup();
+    paths.config_dir = paths.data_dir.join("YuToDo");
+    fs::create_dir_all(&paths.config_dir).unwrap();
+    fs::write(paths.config_dir.join(SETTINGS_FILE), "[app]\n").unwrap();
+
+    let removed = wipe(&paths, false).unwrap();
+
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].path, paths.config_dir);
+    assert!(entries(&paths.data_dir).is_empty());
+}
+
+#[test]
+fn test_overwrite_replaces_contents_in_place() {
+    let (_dir, paths) = setup();
+    let file = paths.data_dir.join("secret.txt");
+    let secret = vec![b'x'; 100_000];
+    fs::write(&file, &secret).unwrap();
+
+    overwrite(&paths.data_dir).unwrap();
+
+    let contents = fs::read(&file).unwrap();
+    assert_eq!(contents.len(), secret.len());
+    assert_ne!(contents, secret);
+}
+
+#[test]
+fn test_secure_wipe_needs_exact_phrase() {
+    let (_dir, paths) = setup();
+    fs::write(paths.data_dir.join("yutodo.db"), b"todos").unwrap();
+
+    for phrase in ["", "wipe all my data", "WIPE ALL MY DATA "] {
+        assert!(request_secure_wipe(phrase, &paths).is_err());
+    }
+    assert_eq!(finish_pending(&paths).unwrap(), None);
+    assert_eq!(entries(&paths.data_dir), vec!["yutodo.db"]);
+}
+
+#[test]
+fn test_secure_wipe_removes_every_file() {
+    let (_dir, paths) = setup();
+    fs::write(paths.data_dir.join("yutodo.db"), b"todos").unwrap();
+    fs::create_dir_all(paths.data_dir.join("files")).unwrap();
+    fs::write(paths.data_dir.join("files").join("backup.json"), b"[]").unwrap();
+    fs::write(paths.config_dir.join(SETTINGS_FILE), "[app]\n").unwrap();
+    fs::write(paths.cache_dir.join("cached"), b"1").unwrap();
+
+    request_secure_wipe(SECURE_WIPE_PHRASE, &paths).unwrap();
+    let removed = finish_pending(&paths).unwrap().unwrap();
+
+    assert_eq!(removed.len(), 4);
+    for dir in [&paths.data_dir, &paths.config_dir, &paths.cache_dir] {
+        assert_eq!(entries(dir), Vec::<String>::new(), "{}", dir.display());
+    }
+}
+
+#[test]
+fn test_pending_wipe_in_a_configured_data_dir() {
+    let (dir, paths) = setup();
+    let elsewhere = dir.path().join("elsewhere");
+    fs::create_dir_all(&elsewhere).unwrap();
+    fs::write(elsewhere.join("yutodo.db"), b"todos").unwrap();
+    fs::write(paths.data_dir.join("yutodo.db"), b"unused").unwrap();
+    let settings_file = paths.config_dir.join(SETTINGS_FILE);
+    fs::write(
+        &settings_file,
+        format!("[backend]\ndata_dir = {:?}\n", elsewhere.to_str().unwrap()),
+    )
+    .unwrap();
+    let load = || {
+        settings::resolve(&settings::SettingsSources {
+            file: fs::read_to_string(&settings_file).ok(),
+            ..Default::default()
+        })
+        .settings
+    };
+    let configured = ResetPaths {
+        data_dir: elsewhere.clone(),
+        ..paths.clone()
+    };
+    schedule_wipe(&configured, false).unwrap();
+
+    let (settings, removed) = finish_pending_with(paths.clone(), load);
+
+    assert_eq!(removed.unwrap().unwrap().len(), 2);
+    assert_eq!(entries(&elsewhere), Vec::<String>::new());
+    assert!(!settings_file.exists());
+    assert_eq!(settings.data_dir, None);
+    assert_eq!(entries(&paths.data_dir), vec!["yutodo.db"]);
+    let (_, removed) = finish_pending_with(paths, load);
+    assert_eq!(removed.unwrap(), None);
+}
@@ -0,0 +1,177 @@
+//! Process resource usage, for spotting leaks over long sessions. Every
+//! metric is best effort and `None` where the platform doesn't expose it.
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::Child;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+
+use crate::sync::SyncState;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    pub rss_bytes: Option<u64>,
+    /// File descriptors on Unix, kernel handles on Windows.
+    pub open_handles: Option<u64>,
+    pub spawned_children: usize,
+    pub timers: usize,
+}
+
+/// Child processes started by the app, kept so they can be reaped and counted.
+#[derive(Default)]
+pub struct ChildTracker {
+    children: Mutex<Vec<Child>>,
+}
+
+impl ChildTracker {
+    pub fn track(&self, child: Child) {
+        self.children
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(child);
+    }
+
+    /// Reaps children that have exited and returns how many still run.
+    pub fn running(&self) -> usize {
+        let mut children = self.children.lock().unwrap_or_else(|e| e.into_inner());
+        children.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+        children.len()
+    }
+}
+
+/// Logs each line `child` writes to its piped stdout and stderr, prefixed
+/// with its PID, on reader threads that end when the pipes close.
+pub fn capture_output(child: &mut Child) -> Vec<JoinHandle<()>> {
+    forward_output(child, trace::log)
+}
+
+fn forward_output(
+    child: &mut Child,
+    write: impl Fn(String) + Clone + Send + 'static,
+) -> Vec<JoinHandle<()>> {
+    let pid = child.id();
+    let forward = |stream: Box<dyn Read + Send>| {
+        let write = write.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                match line {
+                    Ok(line) => write(format!("[pid {}] {}", pid, line)),
+                    Err(_) => break,
+                }
+            }
+        })
+    };
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(forward(Box::new(stdout)));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(forward(Box::new(stderr)));
+    }
+    readers
+}
+
+/// The `VmRSS` line of `/proc/<pid>/status`, in bytes.
+pub fn parse_status_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let mut parts = line["VmRSS:".len()..].split_whitespace();
+    let value: u64 = parts.next()?.parse().ok()?;
+    match parts.next() {
+        Some("kB") => Some(value * 1024),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn rss_bytes() -> Option<u64> {
+    parse_status_rss(&std::fs::read_to_string("/proc/self/status").ok()?)
+}
+
+#[cfg(target_os = "macos")]
+fn rss_bytes() -> Option<u64> {
+    let mut info: libc::proc_taskinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+    // SAFETY: `info` is a properly sized, writable proc_taskinfo.
+    let written = unsafe {
+        libc::proc_pidinfo(
+            std::process::id() as libc::c_int,
+            libc::PROC_PIDTASKINFO,
+            0,
+            &mut info as *mut _ as *mut libc::c_void,
+            size,
+        )
+    };
+    (written == size).then_some(info.pti_resident_size)
+}
+
+#[cfg(target_os = "windows")]
+fn rss_bytes() -> Option<u64> {
+    use windows_sys::Win32::System::ProcessStatus::{
+        GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS,
+    };
+    use windows_sys::Win32::System::Threading::GetCurrentProcess;
+
+    let mut counters: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+    counters.cb = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+    // SAFETY: `counters` is writable and `cb` holds its size.
+    let ok = unsafe { GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, counters.cb) };
+    (ok != 0).then_some(counters.WorkingSetSize as u64)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn rss_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn open_handles() -> Option<u64> {
+    let dir = if cfg!(target_os = "linux") {
+        "/proc/self/fd"
+    } else {
+        "/dev/fd"
+    };
+    // Reading the directory holds one descriptor itself.
+    let count = std::fs::read_dir(dir).ok()?.count() as u64;
+    Some(count.saturating_sub(1))
+}
+
+#[cfg(windows)]
+fn open_handles() -> Option<u64> {
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetProcessHandleCount};
+
+    let mut count = 0u32;
+    // SAFETY: `count` is a valid out pointer.
+    let ok = unsafe { GetProcessHandleCount(GetCurrentProcess(), &mut count) };
+    (ok != 0).then_some(count as u64)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn open_handles() -> Option<u64> {
+    None
+}
+
+pub fn usage(children: &ChildTracker, sync: &SyncState) -> ResourceUsage {
+    ResourceUsage {
+        rss_bytes: rss_bytes(),
+        open_handles: open_handles(),
+        spawned_children: children.running(),
+        timers: sync.active_pollers(),
+    }
+}
+
+#[tauri::command]
+pub fn resource_usage(
+    children: State<'_, ChildTracker>,
+    sync: State<'_, SyncState>,
+) -> ResourceUsage {
+    usage(&children, &sync)
+}
@@ -0,0 +1,65 @@
+use super::*;
+
+const STATUS: &str = "Name:\tyutodo
+Umask:\t0022
+State:\tS (sleeping)
+Pid:\t4242
+VmPeak:\t 3012345 kB
+VmSize:\t 2987654 kB
+VmHWM:\t  210000 kB
+VmRSS:\t  184320 kB
+RssAnon:\t   90000 kB
+Threads:\t27
+";
+
+#[test]
+fn test_parse_status_rss_reads_vmrss_in_bytes() {
+    assert_eq!(parse_status_rss(STATUS), Some(184_320 * 1024));
+}
+
+#[test]
+fn test_parse_status_rss_missing_or_malformed() {
+    assert_eq!(parse_status_rss("Name:\tkthreadd\nThreads:\t1\n"), None);
+    assert_eq!(parse_status_rss("VmRSS:\tlots kB\n"), None);
+    assert_eq!(parse_status_rss("VmRSS:\t100 pages\n"), None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_usage_reports_linux_metrics() {
+    let usage = usage(&ChildTracker::default(), &SyncState::default());
+
+    assert!(usage.rss_bytes.is_some_and(|rss| rss > 0));
+    assert!(usage.open_handles.is_some_and(|fds| fds > 0));
+    assert_eq!(usage.spawned_children, 0);
+    assert_eq!(usage.timers, 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_captured_output_is_prefixed_with_the_child_pid() {
+    let mut child = std::process::Command::new("sh")
+        .args(["-c", "echo captured-stdout; echo captured-stderr >&2"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pid = child.id();
+    let lines = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let sink = lines.clone();
+    let readers = forward_output(&mut child, move |line| sink.lock().unwrap().push(line));
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    child.wait().unwrap();
+
+    let mut lines = lines.lock().unwrap().clone();
+    lines.sort();
+    assert_eq!(
+        lines,
+        vec![
+            format!("[pid {}] captured-stderr", pid),
+            format!("[pid {}] captured-stdout", pid),
+        ]
+    );
+}
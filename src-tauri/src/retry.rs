@@ -0,0 +1,202 @@
+//! The last command that failed, so a transient failure such as a network
+//! blip can be retried with `retry_last_failed` instead of redoing the steps
+//! that led to it. Commands that can fail that way record their failures
+//! with [`record`]; only operations that leave the same result when run
+//! twice are re-run, the others are refused with the reason.
+
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::calendar::{self, CalendarConfig};
+use crate::export::ExportFormat;
+use crate::export_schedule;
+use crate::integrations::connectors;
+use crate::settings::SettingsState;
+use crate::site::{self, SiteConfig};
+use crate::sync;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// A command with the arguments it failed with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(
+    tag = "command",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum Operation {
+    PublishList {
+        url: String,
+        format: ExportFormat,
+    },
+    PublishStaticSite {
+        config: SiteConfig,
+    },
+    SyncToSystemCalendar {
+        config: CalendarConfig,
+    },
+    /// Imports add todos, so a retry after a partial failure could add them
+    /// twice; `resume_import` continues one instead.
+    ImportTodos {
+        path: PathBuf,
+    },
+    ImportDirectory {
+        folder: String,
+    },
+    /// Re-syncs update the todos they created before, so running one twice
+    /// adds nothing.
+    RunConnector {
+        id: String,
+    },
+    /// Exports replace the file they wrote or add one more, so a retry
+    /// leaves what a run that succeeded the first time would have.
+    RunExportSchedule {
+        id: String,
+    },
+}
+
+impl Operation {
+    pub fn command(&self) -> &'static str {
+        match self {
+            Operation::PublishList { .. } => "publish_list",
+            Operation::PublishStaticSite { .. } => "publish_static_site",
+            Operation::SyncToSystemCalendar { .. } => "sync_to_system_calendar",
+            Operation::ImportTodos { .. } => "import_todos",
+            Operation::ImportDirectory { .. } => "import_directory",
+            Operation::RunConnector { .. } => "run_connector_now",
+            Operation::RunExportSchedule { .. } => "run_export_schedule_now",
+        }
+    }
+
+    /// Why running it again is unsafe, or `None` if it is idempotent.
+    pub fn not_retryable(&self) -> Option<&'static str> {
+        match self {
+            Operation::PublishList { .. }
+            | Operation::PublishStaticSite { .. }
+            | Operation::SyncToSystemCalendar { .. }
+            | Operation::RunConnector { .. }
+            | Operation::RunExportSchedule { .. } => None,
+            Operation::ImportTodos { .. } | Operation::ImportDirectory { .. } => Some(
+                "running it again could import todos twice; resume the interrupted import instead",
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedOperation {
+    pub operation: Operation,
+    pub error: String,
+}
+
+/// Managed state holding the last failure.
+#[derive(Default)]
+pub struct LastFailed(Mutex<Option<FailedOperation>>);
+
+impl LastFailed {
+    fn last(&self) -> MutexGuard<'_, Option<FailedOperation>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self) -> Option<FailedOperation> {
+        self.last().clone()
+    }
+
+    pub fn record(&self, operation: Operation, error: impl Display) {
+        *self.last() = Some(FailedOperation {
+            operation,
+            error: error.to_string(),
+        });
+    }
+
+    /// Drops the record if it is of `operation`, e.g. once what it would
+    /// run is gone.
+    pub fn forget(&self, operation: &Operation) {
+        let mut last = self.last();
+        if last
+            .as_ref()
+            .is_some_and(|failed| failed.operation == *operation)
+        {
+            *last = None;
+        }
+    }
+
+    /// Runs the last failed operation again with `run`. Success clears the
+    /// record unless another failure replaced it meanwhile; failure keeps
+    /// it with the new error.
+    pub fn retry(&self, run: impl FnOnce(&Operation) -> Result<(), String>) -> Result<(), String> {
+        let failed = self
+            .get()
+            .ok_or_else(|| "Nothing to retry: no command has failed".to_string())?;
+        if let Some(reason) = failed.operation.not_retryable() {
+            return Err(format!(
+                "Can't retry {}: {}",
+                failed.operation.command(),
+                reason
+            ));
+        }
+        let result = run(&failed.operation);
+        let mut last = self.last();
+        if last.as_ref() == Some(&failed) {
+            *last = match &result {
+                Ok(()) => None,
+                Err(error) => Some(FailedOperation {
+                    error: error.clone(),
+                    ..failed
+                }),
+            };
+        }
+        result
+    }
+}
+
+/// Remembers that `operation` failed with `error`.
+pub fn record(app: &AppHandle, operation: Operation, error: impl Display) {
+    app.state::<LastFailed>().record(operation, error);
+}
+
+fn run(app: &AppHandle, operation: &Operation) -> Result<(), String> {
+    match operation {
+        Operation::PublishList { url, format } => {
+            let scope = app.state::<SettingsState>().get().sync_scope;
+            sync::publish_with_stored_token(&app.state(), url, *format, &scope)
+        }
+        Operation::PublishStaticSite { config } => {
+            site::publish_checked(app, &app.state(), config).map_err(|e| e.to_string())
+        }
+        Operation::SyncToSystemCalendar { config } => {
+            calendar::configure(app, config.clone()).map(|_| ())
+        }
+        Operation::RunConnector { id } => connectors::run_now(app, id).map(|_| ()),
+        Operation::RunExportSchedule { id } => export_schedule::run_now(app, id),
+        Operation::ImportTodos { .. } | Operation::ImportDirectory { .. } => {
+            Err(format!("Can't retry {}", operation.command()))
+        }
+    }
+}
+
+/// The failure `retry_last_failed` would retry, for the frontend to offer.
+#[tauri::command]
+pub fn last_failed_operation(last: State<'_, LastFailed>) -> Option<FailedOperation> {
+    last.get()
+}
+
+/// Runs the last failed command again with the same arguments, if that is
+/// safe.
+#[tauri::command]
+pub async fn retry_last_failed(request_id: Option<String>, app: AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        app.state::<LastFailed>()
+            .retry(|operation| run(&app, operation))
+    }))
+    .await
+    .map_err(|e| format!("Failed to retry: {}", e))?
+}
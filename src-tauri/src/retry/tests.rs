@@ -0,0 +1,70 @@
+use super::*;
+
+fn publish() -> Operation {
+    Operation::PublishList {
+        url: "https://lists.example.com/chores".to_string(),
+        format: ExportFormat::Json,
+    }
+}
+
+#[test]
+fn test_retryable_operation_is_rerun_and_cleared_on_success() {
+    let last = LastFailed::default();
+    last.record(publish(), "connection reset");
+
+    let mut runs = Vec::new();
+    let result = last.retry(|operation| {
+        runs.push(operation.clone());
+        Err("timed out".to_string())
+    });
+    assert_eq!(result, Err("timed out".to_string()));
+    assert_eq!(last.get().unwrap().error, "timed out");
+
+    last.retry(|operation| {
+        runs.push(operation.clone());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs, [publish(), publish()]);
+    assert_eq!(last.get(), None);
+    assert!(last
+        .retry(|_| Ok(()))
+        .unwrap_err()
+        .contains("Nothing to retry"));
+}
+
+#[test]
+fn test_non_idempotent_operation_is_refused() {
+    let last = LastFailed::default();
+    last.record(
+        Operation::ImportTodos {
+            path: PathBuf::from("/tmp/todos.csv"),
+        },
+        "disk full",
+    );
+    let error = last
+        .retry(|_| panic!("an import must not run again"))
+        .unwrap_err();
+    assert!(error.starts_with("Can't retry import_todos:"), "{}", error);
+    assert!(last.get().is_some());
+}
+
+#[test]
+fn test_operation_serializes_with_its_command_name() {
+    let json = serde_json::to_value(publish()).unwrap();
+    assert_eq!(json["command"], "publish_list");
+    assert_eq!(json["format"], "json");
+    assert_eq!(publish().command(), "publish_list");
+}
+
+#[test]
+fn test_forget_drops_only_that_operation() {
+    let last = LastFailed::default();
+    last.record(publish(), "connection reset");
+    last.forget(&Operation::RunExportSchedule {
+        id: "s1".to_string(),
+    });
+    assert!(last.get().is_some());
+    last.forget(&publish());
+    assert_eq!(last.get(), None);
+}
@@ -0,0 +1,209 @@
+//! Server-side confirmation for destructive commands. The frontend asks for a
+//! token describing exactly what it is about to destroy and passes it to the
+//! command, so a skipped dialog can't delete anything on its own.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::audit;
+use crate::clock::{Clock, ClockState};
+use crate::settings::{SafetySettings, SettingsState};
+use crate::storage::Storage;
+
+#[cfg(test)]
+mod tests;
+
+pub const TOKEN_LIFETIME: Duration = Duration::from_secs(60);
+const AUDIT_SOURCE: &str = "safety";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum DestructiveOperation {
+    /// Applying a bulk edit that deletes todos.
+    BulkDelete,
+    /// Deleting a custom field together with its values.
+    DeleteCustomField,
+    /// Resetting part or all of the app's data for troubleshooting.
+    ResetData,
+    /// Turning on developer mode, which opens the store to raw queries.
+    DeveloperMode,
+    /// Keeping published todos local, which removes them from the server.
+    ExcludeFromSync,
+}
+
+impl DestructiveOperation {
+    fn as_str(self) -> &'static str {
+        match self {
+            DestructiveOperation::BulkDelete => "bulk-delete",
+            DestructiveOperation::DeleteCustomField => "delete-custom-field",
+            DestructiveOperation::ResetData => "reset-data",
+            DestructiveOperation::DeveloperMode => "developer-mode",
+            DestructiveOperation::ExcludeFromSync => "exclude-from-sync",
+        }
+    }
+}
+
+/// What a token allows destroying: how many items, and optionally of what,
+/// e.g. a bulk edit session or field id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DestructionScope {
+    pub count: usize,
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+impl std::fmt::Display for DestructionScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} item(s)", self.count)?;
+        if let Some(target) = &self.target {
+            write!(f, " of '{}'", target)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DestructionToken {
+    pub token: String,
+    pub expires_in_secs: u64,
+    /// The user must type `scope.count` to confirm.
+    pub requires_typed_count: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum SafetyError {
+    #[error("This operation needs a confirmation token")]
+    Missing,
+    #[error("Confirmation token is unknown or was already used")]
+    Unknown,
+    #[error("Confirmation token has expired")]
+    Expired,
+    #[error("Confirmation token was issued for {issued}, not {requested}")]
+    ScopeMismatch { issued: String, requested: String },
+    #[error("Type the number of items ({0}) to confirm")]
+    TypedCountMismatch(usize),
+}
+
+struct Pending {
+    operation: DestructiveOperation,
+    scope: DestructionScope,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+pub struct SafetyState {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl SafetyState {
+    pub fn issue(
+        &self,
+        operation: DestructiveOperation,
+        scope: DestructionScope,
+        now: Instant,
+    ) -> String {
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, p| p.expires_at > now);
+        pending.insert(
+            token.clone(),
+            Pending {
+                operation,
+                scope,
+                expires_at: now + TOKEN_LIFETIME,
+            },
+        );
+        token
+    }
+
+    /// Uses up `token`. It is removed even when the check fails, so a token
+    /// can never be tried twice.
+    pub fn consume(
+        &self,
+        token: &str,
+        operation: DestructiveOperation,
+        scope: &DestructionScope,
+        now: Instant,
+    ) -> Result<(), SafetyError> {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(token)
+            .ok_or(SafetyError::Unknown)?;
+        if pending.expires_at <= now {
+            return Err(SafetyError::Expired);
+        }
+        if pending.operation != operation || pending.scope != *scope {
+            return Err(SafetyError::ScopeMismatch {
+                issued: format!("{} of {}", pending.operation.as_str(), pending.scope),
+                requested: format!("{} of {}", operation.as_str(), scope),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn requires_typed_count(settings: &SafetySettings, scope: &DestructionScope) -> bool {
+    settings
+        .require_typed_count_above
+        .is_some_and(|threshold| scope.count > threshold)
+}
+
+pub fn check_typed_count(
+    settings: &SafetySettings,
+    scope: &DestructionScope,
+    typed_count: Option<usize>,
+) -> Result<(), SafetyError> {
+    if requires_typed_count(settings, scope) && typed_count != Some(scope.count) {
+        return Err(SafetyError::TypedCountMismatch(scope.count));
+    }
+    Ok(())
+}
+
+/// Checks the typed count, consumes the token and writes the audit entry.
+/// Commands call this right before destroying anything.
+pub fn authorize(
+    safety: &SafetyState,
+    settings: &SafetySettings,
+    storage: &Storage,
+    confirmation_token: Option<&str>,
+    typed_count: Option<usize>,
+    operation: DestructiveOperation,
+    scope: &DestructionScope,
+) -> Result<(), SafetyError> {
+    let token = confirmation_token.ok_or(SafetyError::Missing)?;
+    // Checked first so a mistyped count doesn't burn the token.
+    check_typed_count(settings, scope, typed_count)?;
+    safety.consume(token, operation, scope, storage.clock().now_monotonic())?;
+    let _ = audit::record(
+        storage,
+        AUDIT_SOURCE,
+        operation.as_str(),
+        &scope.to_string(),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub fn request_destruction_token(
+    operation: DestructiveOperation,
+    scope: DestructionScope,
+    safety: State<'_, SafetyState>,
+    settings: State<'_, SettingsState>,
+    clock: State<'_, ClockState>,
+) -> DestructionToken {
+    let requires_typed_count = requires_typed_count(&settings.get().safety, &scope);
+    DestructionToken {
+        token: safety.issue(operation, scope, clock.now_monotonic()),
+        expires_in_secs: TOKEN_LIFETIME.as_secs(),
+        requires_typed_count,
+    }
+}
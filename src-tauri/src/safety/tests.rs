@@ -0,0 +1,134 @@
+use super::*;
+
+fn scope(count: usize) -> DestructionScope {
+    DestructionScope {
+        count,
+        target: Some("session-1".to_string()),
+    }
+}
+
+#[test]
+fn test_token_authorizes_matching_scope_once() {
+    let safety = SafetyState::default();
+    let now = Instant::now();
+    let token = safety.issue(DestructiveOperation::BulkDelete, scope(3), now);
+
+    assert_eq!(
+        safety.consume(&token, DestructiveOperation::BulkDelete, &scope(3), now),
+        Ok(())
+    );
+    assert_eq!(
+        safety.consume(&token, DestructiveOperation::BulkDelete, &scope(3), now),
+        Err(SafetyError::Unknown)
+    );
+}
+
+#[test]
+fn test_token_expires() {
+    let safety = SafetyState::default();
+    let now = Instant::now();
+    let token = safety.issue(DestructiveOperation::BulkDelete, scope(3), now);
+
+    assert_eq!(
+        safety.consume(
+            &token,
+            DestructiveOperation::BulkDelete,
+            &scope(3),
+            now + TOKEN_LIFETIME
+        ),
+        Err(SafetyError::Expired)
+    );
+}
+
+#[test]
+fn test_scope_mismatch_is_rejected_and_burns_the_token() {
+    let safety = SafetyState::default();
+    let now = Instant::now();
+    let token = safety.issue(DestructiveOperation::BulkDelete, scope(3), now);
+
+    let error = safety
+        .consume(&token, DestructiveOperation::BulkDelete, &scope(3000), now)
+        .unwrap_err();
+    assert!(matches!(error, SafetyError::ScopeMismatch { .. }));
+    assert!(error.to_string().contains("3 item(s)"), "{}", error);
+    assert_eq!(
+        safety.consume(&token, DestructiveOperation::BulkDelete, &scope(3), now),
+        Err(SafetyError::Unknown)
+    );
+}
+
+#[test]
+fn test_operation_mismatch_is_rejected() {
+    let safety = SafetyState::default();
+    let now = Instant::now();
+    let token = safety.issue(DestructiveOperation::BulkDelete, scope(3), now);
+
+    assert!(matches!(
+        safety.consume(
+            &token,
+            DestructiveOperation::DeleteCustomField,
+            &scope(3),
+            now
+        ),
+        Err(SafetyError::ScopeMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_typed_count_required_above_threshold() {
+    let settings = SafetySettings {
+        require_typed_count_above: Some(10),
+    };
+
+    assert_eq!(check_typed_count(&settings, &scope(10), None), Ok(()));
+    assert_eq!(
+        check_typed_count(&settings, &scope(11), Some(10)),
+        Err(SafetyError::TypedCountMismatch(11))
+    );
+    assert_eq!(check_typed_count(&settings, &scope(11), Some(11)), Ok(()));
+    assert_eq!(
+        check_typed_count(&SafetySettings::default(), &scope(5000), None),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_authorize_writes_audit_entry() {
+    let storage = Storage::open_in_memory().unwrap();
+    let safety = SafetyState::default();
+    let token = safety.issue(DestructiveOperation::BulkDelete, scope(2), Instant::now());
+
+    authorize(
+        &safety,
+        &SafetySettings::default(),
+        &storage,
+        Some(&token),
+        None,
+        DestructiveOperation::BulkDelete,
+        &scope(2),
+    )
+    .unwrap();
+
+    let entries = audit::recent(&storage, 10).unwrap();
+    assert_eq!(entries[0].source, "safety");
+    assert_eq!(entries[0].action, "bulk-delete");
+    assert_eq!(entries[0].detail, "2 item(s) of 'session-1'");
+}
+
+#[test]
+fn test_authorize_without_token_is_refused() {
+    let storage = Storage::open_in_memory().unwrap();
+
+    assert_eq!(
+        authorize(
+            &SafetyState::default(),
+            &SafetySettings::default(),
+            &storage,
+            None,
+            None,
+            DestructiveOperation::BulkDelete,
+            &scope(1),
+        ),
+        Err(SafetyError::Missing)
+    );
+}
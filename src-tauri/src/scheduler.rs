@@ -0,0 +1,338 @@
+//! The background scheduler loop. It wakes up periodically for housekeeping
+//! such as re-probing capabilities, and wakes up less often on battery.
+//! When a wake-up comes much later by the wall clock than it was due, the
+//! machine slept in between, and reminders that came due meanwhile go
+//! through [`notifications::catch_up`].
+
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::activity;
+use crate::automation;
+use crate::capabilities::{self, CapabilityRegistry};
+use crate::clock::{Clock, ClockState};
+use crate::events;
+use crate::export_schedule;
+use crate::integrations::connectors;
+use crate::language;
+use crate::notifications;
+use crate::protocol::EventPayload;
+use crate::settings::SettingsReactions;
+
+#[cfg(test)]
+mod tests;
+
+pub const AC_INTERVAL: Duration = Duration::from_secs(60);
+pub const BATTERY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Shortest interval a user override may ask for.
+const MIN_OVERRIDE: Duration = Duration::from_secs(5);
+/// A wake-up this many intervals late by the wall clock means a resume.
+const RESUME_INTERVALS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// No battery information, e.g. on desktops; treated like AC.
+    Unknown,
+}
+
+/// Payload of `power-mode-changed` and result of `get_power_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerMode {
+    pub source: PowerSource,
+    pub interval_secs: u64,
+    pub overridden: bool,
+}
+
+impl EventPayload for PowerMode {}
+
+/// The user override wins; otherwise battery power stretches the interval.
+pub fn poll_interval(source: PowerSource, user_override: Option<Duration>) -> Duration {
+    match (user_override, source) {
+        (Some(interval), _) => interval,
+        (None, PowerSource::Battery) => BATTERY_INTERVAL,
+        (None, PowerSource::Ac | PowerSource::Unknown) => AC_INTERVAL,
+    }
+}
+
+#[cfg(any(target_os = "linux", test))]
+/// One entry of `/sys/class/power_supply`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PowerSupply {
+    /// `type`, e.g. `Mains`, `USB` or `Battery`.
+    pub kind: String,
+    /// `online`, for mains and USB supplies.
+    pub online: Option<bool>,
+    /// `status`, for batteries, e.g. `Discharging`.
+    pub status: Option<String>,
+}
+
+#[cfg(any(target_os = "linux", test))]
+pub fn classify_supplies(supplies: &[PowerSupply]) -> PowerSource {
+    if supplies
+        .iter()
+        .any(|s| s.kind != "Battery" && s.online == Some(true))
+    {
+        return PowerSource::Ac;
+    }
+    let batteries: Vec<&PowerSupply> = supplies.iter().filter(|s| s.kind == "Battery").collect();
+    if batteries.is_empty() {
+        PowerSource::Unknown
+    } else if batteries
+        .iter()
+        .any(|b| b.status.as_deref() == Some("Discharging"))
+    {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    }
+}
+
+#[cfg(any(target_os = "macos", test))]
+/// Parses `pmset -g ps`, whose first line names the current source.
+pub fn parse_pmset(output: &str) -> PowerSource {
+    let first = output.lines().next().unwrap_or_default();
+    if first.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else if first.contains("'AC Power'") {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn detect_power_source() -> PowerSource {
+    let read = |path: std::path::PathBuf| {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim().to_string())
+    };
+    let supplies: Vec<PowerSupply> = std::fs::read_dir("/sys/class/power_supply")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| {
+            let dir = entry.path();
+            PowerSupply {
+                kind: read(dir.join("type")).unwrap_or_default(),
+                online: read(dir.join("online")).map(|online| online == "1"),
+                status: read(dir.join("status")),
+            }
+        })
+        .collect();
+    classify_supplies(&supplies)
+}
+
+#[cfg(target_os = "macos")]
+pub fn detect_power_source() -> PowerSource {
+    std::process::Command::new("pmset")
+        .args(["-g", "ps"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map_or(PowerSource::Unknown, |output| {
+            parse_pmset(&String::from_utf8_lossy(&output.stdout))
+        })
+}
+
+#[cfg(target_os = "windows")]
+pub fn detect_power_source() -> PowerSource {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    // SAFETY: `status` is a writable SYSTEM_POWER_STATUS.
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return PowerSource::Unknown;
+    }
+    match status.ACLineStatus {
+        0 => PowerSource::Battery,
+        1 => PowerSource::Ac,
+        _ => PowerSource::Unknown,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn detect_power_source() -> PowerSource {
+    PowerSource::Unknown
+}
+
+struct Schedule {
+    source: Option<PowerSource>,
+    user_override: Option<Duration>,
+    /// Bumped by every override, so a waiting loop notices it.
+    generation: u64,
+    stopped: bool,
+}
+
+impl Schedule {
+    fn mode(&self) -> PowerMode {
+        let source = self.source.unwrap_or(PowerSource::Unknown);
+        PowerMode {
+            source,
+            interval_secs: poll_interval(source, self.user_override).as_secs(),
+            overridden: self.user_override.is_some(),
+        }
+    }
+}
+
+pub struct SchedulerState {
+    schedule: Mutex<Schedule>,
+    wake: Condvar,
+}
+
+impl SchedulerState {
+    pub fn new(user_override: Option<Duration>) -> Self {
+        Self {
+            schedule: Mutex::new(Schedule {
+                source: None,
+                user_override,
+                generation: 0,
+                stopped: false,
+            }),
+            wake: Condvar::new(),
+        }
+    }
+
+    fn schedule(&self) -> std::sync::MutexGuard<'_, Schedule> {
+        self.schedule.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn mode(&self) -> PowerMode {
+        self.schedule().mode()
+    }
+
+    /// Records the detected source and returns the new mode if it switched.
+    /// The first detection is not a switch.
+    pub fn set_source(&self, source: PowerSource) -> Option<PowerMode> {
+        let mut schedule = self.schedule();
+        let previous = schedule.source.replace(source);
+        (previous.is_some_and(|previous| previous != source)).then(|| schedule.mode())
+    }
+
+    /// Replaces the user override and wakes the loop so it applies at once.
+    pub fn set_override(&self, user_override: Option<Duration>) -> PowerMode {
+        let mode = {
+            let mut schedule = self.schedule();
+            schedule.user_override = user_override;
+            schedule.generation += 1;
+            schedule.mode()
+        };
+        self.wake.notify_all();
+        mode
+    }
+
+    /// Makes a waiting loop look at the clock again, after it was moved.
+    #[cfg(any(test, feature = "simulated-clock"))]
+    pub fn nudge(&self) {
+        self.wake.notify_all();
+    }
+
+    /// Ends the loop at its next wake-up, which this brings forward.
+    pub fn stop(&self) {
+        self.schedule().stopped = true;
+        self.wake.notify_all();
+    }
+
+    /// Sleeps until `clock` has moved on by the current interval, or less if
+    /// the override changes. False once the loop should stop.
+    pub fn wait(&self, clock: &dyn Clock) -> bool {
+        let mut schedule = self.schedule();
+        let generation = schedule.generation;
+        let deadline = clock.now_monotonic() + Duration::from_secs(schedule.mode().interval_secs);
+        loop {
+            if schedule.stopped {
+                return false;
+            }
+            let now = clock.now_monotonic();
+            if now >= deadline || schedule.generation != generation {
+                return true;
+            }
+            schedule = self
+                .wake
+                .wait_timeout(schedule, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+/// Applies a changed `[backend.scheduler]` interval to the running loop.
+pub fn register_settings_reactions(app: &AppHandle, reactions: &SettingsReactions) {
+    let app = app.clone();
+    reactions.register("scheduler", &["scheduler.interval_secs"], move |_, new| {
+        let interval = new.scheduler.interval_secs.map(Duration::from_secs);
+        let mode = app.state::<SchedulerState>().set_override(interval);
+        let _ = events::emit(&app, "power-mode-changed", mode);
+    });
+}
+
+/// Whether `elapsed` wall-clock time since the last wake-up means the
+/// machine was suspended, given the interval the loop waited for.
+pub fn resumed(elapsed: chrono::Duration, interval: Duration) -> bool {
+    elapsed
+        .to_std()
+        .is_ok_and(|elapsed| elapsed > interval * RESUME_INTERVALS)
+}
+
+pub fn spawn(app: AppHandle) {
+    std::thread::spawn(move || {
+        app.state::<CapabilityRegistry>().snapshot();
+        let state = app.state::<SchedulerState>();
+        let clock = app.state::<ClockState>();
+        let mut last_wake = clock.now();
+        loop {
+            if let Some(mode) = state.set_source(detect_power_source()) {
+                let _ = events::emit(&app, "power-mode-changed", mode);
+            }
+            let interval = Duration::from_secs(state.mode().interval_secs);
+            if !state.wait(clock.inner()) {
+                break;
+            }
+            let now = clock.now();
+            if resumed(now - last_wake, interval) {
+                notifications::catch_up_since(&app, last_wake, now);
+            }
+            notifications::activation::remind_snoozed(&app, now);
+            last_wake = now;
+            capabilities::reprobe(&app);
+            activity::prune_expired(&app);
+            language::spawn_backfill(&app);
+            automation::run_due_commands(&app);
+            connectors::run_due(&app);
+            export_schedule::run_due_exports(&app);
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_power_mode(state: State<'_, SchedulerState>) -> PowerMode {
+    state.mode()
+}
+
+/// Sets how often the scheduler wakes up regardless of power source, or
+/// with `None` goes back to following it.
+#[tauri::command]
+pub fn set_scheduler_interval(
+    seconds: Option<u64>,
+    app: AppHandle,
+    state: State<'_, SchedulerState>,
+) -> Result<PowerMode, String> {
+    let interval = seconds.map(Duration::from_secs);
+    if interval.is_some_and(|interval| interval < MIN_OVERRIDE) {
+        return Err(format!(
+            "Scheduler interval must be at least {} seconds",
+            MIN_OVERRIDE.as_secs()
+        ));
+    }
+    let mode = state.set_override(interval);
+    let _ = events::emit(&app, "power-mode-changed", mode);
+    Ok(mode)
+}
@@ -0,0 +1,88 @@
+use super::*;
+
+fn supply(kind: &str, online: Option<bool>, status: Option<&str>) -> PowerSupply {
+    PowerSupply {
+        kind: kind.to_string(),
+        online,
+        status: status.map(str::to_string),
+    }
+}
+
+#[test]
+fn test_poll_interval_per_power_source() {
+    assert_eq!(poll_interval(PowerSource::Ac, None), AC_INTERVAL);
+    assert_eq!(poll_interval(PowerSource::Unknown, None), AC_INTERVAL);
+    assert_eq!(poll_interval(PowerSource::Battery, None), BATTERY_INTERVAL);
+}
+
+#[test]
+fn test_poll_interval_override_wins() {
+    let custom = Duration::from_secs(15);
+    for source in [PowerSource::Ac, PowerSource::Battery, PowerSource::Unknown] {
+        assert_eq!(poll_interval(source, Some(custom)), custom);
+    }
+}
+
+#[test]
+fn test_classify_supplies() {
+    let discharging = supply("Battery", None, Some("Discharging"));
+    let mains_off = supply("Mains", Some(false), None);
+
+    assert_eq!(classify_supplies(&[]), PowerSource::Unknown);
+    assert_eq!(
+        classify_supplies(&[mains_off.clone(), discharging.clone()]),
+        PowerSource::Battery
+    );
+    assert_eq!(
+        classify_supplies(&[supply("USB", Some(true), None), discharging]),
+        PowerSource::Ac
+    );
+    assert_eq!(
+        classify_supplies(&[mains_off, supply("Battery", None, Some("Full"))]),
+        PowerSource::Ac
+    );
+}
+
+#[test]
+fn test_parse_pmset() {
+    assert_eq!(
+        parse_pmset("Now drawing from 'Battery Power'\n -InternalBattery-0\t80%; discharging"),
+        PowerSource::Battery
+    );
+    assert_eq!(
+        parse_pmset("Now drawing from 'AC Power'\n"),
+        PowerSource::Ac
+    );
+    assert_eq!(parse_pmset(""), PowerSource::Unknown);
+}
+
+#[test]
+fn test_mode_switches_only_on_source_change() {
+    let state = SchedulerState::new(None);
+
+    assert_eq!(state.set_source(PowerSource::Ac), None);
+    assert_eq!(state.set_source(PowerSource::Ac), None);
+    let mode = state.set_source(PowerSource::Battery).unwrap();
+    assert_eq!(mode.interval_secs, BATTERY_INTERVAL.as_secs());
+    assert!(!mode.overridden);
+
+    let mode = state.set_override(Some(Duration::from_secs(20)));
+    assert_eq!(mode.source, PowerSource::Battery);
+    assert_eq!(mode.interval_secs, 20);
+    assert!(mode.overridden);
+    assert_eq!(
+        state.set_override(None).interval_secs,
+        BATTERY_INTERVAL.as_secs()
+    );
+}
+
+#[test]
+fn test_late_wake_up_means_resume() {
+    let minutes = chrono::Duration::minutes;
+    assert!(!resumed(minutes(1), AC_INTERVAL));
+    assert!(!resumed(minutes(2), AC_INTERVAL));
+    assert!(resumed(minutes(45), AC_INTERVAL));
+    assert!(!resumed(minutes(9), BATTERY_INTERVAL));
+    // The wall clock going backwards is no resume.
+    assert!(!resumed(minutes(-45), AC_INTERVAL));
+}
@@ -0,0 +1,392 @@
+//! JSON Schema of every command's arguments, result and error, so the
+//! frontend's bindings can be generated instead of written by hand.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use chrono::{NaiveDate, Weekday};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::activity::{ActivityPage, FeedCursor};
+use crate::audit::AuditEntry;
+use crate::automation::{
+    Action, Condition, Rule, RuleTestResult, Schedule, ScheduledCommand, ScheduledEntry, Trigger,
+};
+use crate::bulk_edit::BulkChange;
+use crate::cache::CacheStats;
+use crate::calendar::{CalendarConfig, CalendarSyncReport};
+use crate::capabilities::Capability;
+use crate::card::CardStyle;
+use crate::command_registry::{PaletteContext, PaletteEntry};
+use crate::crash::CrashReport;
+use crate::custom_fields::{CustomField, FieldType};
+use crate::developer::SqlResult;
+use crate::export::ExportFormat;
+use crate::export_schedule::{ExportRun, ExportSchedule, ExportScheduleConfig};
+use crate::file_access::PathError;
+use crate::focus::{FocusReport, FocusSession};
+use crate::import::{
+    ConflictPolicy, DirImportReport, ImportCheckpoint, ImportPreview, ImportSummary,
+};
+use crate::integrations::connectors::{ConnectorInstance, ConnectorKind, RunOutcome};
+use crate::language::Detection;
+use crate::legacy_server::LegacyServerReport;
+use crate::live_query::{QueryPage, QueryParams};
+use crate::local_api::LocalApiInfo;
+use crate::notifications::{Delivery, NotificationText};
+use crate::paths::BaseDir;
+use crate::plugins::PluginList;
+use crate::privacy::{PrivacyMode, PrivacyRules, Redacted};
+use crate::progress::OperationProgress;
+use crate::protocol::NegotiatedProtocol;
+use crate::query::{QueryAst, QueryError, SmartList};
+use crate::quota::{QuotaStatus, WriteError};
+use crate::references::{BrokenReferences, ResolvedReference, TodoReference};
+use crate::reset::{ResetReport, ResetScope};
+use crate::resources::ResourceUsage;
+use crate::retry::FailedOperation;
+use crate::safety::{DestructionScope, DestructionToken, DestructiveOperation};
+use crate::scheduler::PowerMode;
+use crate::search::{ScoredTodo, SimilarTodo};
+use crate::self_check::Finding;
+use crate::settings::{SettingSource, SyncScope};
+use crate::site::SiteConfig;
+use crate::stats::{EffortReport, TagCount, WeeklyReview};
+use crate::storage::changes::ChangesSince;
+use crate::storage::drafts::DraftPatch;
+use crate::storage::lanes::ContentionStats;
+use crate::storage::recovery::RecoveryOption;
+use crate::storage::{MigrationReport, StorageMode, TodoUpdated, UpdateError};
+use crate::sync::{RemoteSubscription, SyncScopeReport, TodoDiff};
+use crate::trust::{Feature, TrustScope, TrustStatus};
+use crate::types::{DateRange, FieldValue, Todo};
+use crate::validation_rules::{LintReport, LintScope};
+use crate::{CommandInfo, PingResponse, SpawnCapability};
+
+#[cfg(test)]
+mod tests;
+
+/// What a command takes and gives back. `params` are keyed by the camelCase
+/// names `invoke` expects; `error` is absent for commands that can't fail.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandSchema {
+    pub params: BTreeMap<String, Schema>,
+    pub output: Schema,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Schema>,
+}
+
+/// The commands' schemas, with the types they share under `definitions`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiSchema {
+    pub commands: BTreeMap<String, CommandSchema>,
+    pub definitions: BTreeMap<String, Schema>,
+}
+
+/// Tauri hands arguments over by their camelCase names.
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_');
+    let mut camel = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+/// Mirrors the command signatures: `name(args) -> Output | Error` for
+/// commands returning `Result<Output, Error>`, `name(args) -> Output` for
+/// the rest. A test checks these names against `list_commands`.
+macro_rules! signatures {
+    (@error $generator:ident) => {
+        None
+    };
+    (@error $generator:ident $error:ty) => {
+        Some($generator.subschema_for::<$error>())
+    };
+    ($(
+        $(#[cfg($($cfg:tt)*)])?
+        $name:ident($($param:ident: $ty:ty),* $(,)?) -> $output:ty $(| $error:ty)?;
+    )*) => {
+        fn command_schemas(generator: &mut SchemaGenerator) -> BTreeMap<String, CommandSchema> {
+            let mut commands = BTreeMap::new();
+            $(
+                $(#[cfg($($cfg)*)])?
+                commands.insert(
+                    stringify!($name).to_string(),
+                    CommandSchema {
+                        params: BTreeMap::from([
+                            $((camel_case(stringify!($param)), generator.subschema_for::<$ty>()),)*
+                        ]),
+                        output: generator.subschema_for::<$output>(),
+                        error: signatures!(@error generator $($error)?),
+                    },
+                );
+            )*
+            commands
+        }
+    };
+}
+
+signatures! {
+    greet(name: String) -> String;
+    ping(echo: String) -> PingResponse;
+    spawn_new_instance(
+        force: Option<bool>,
+        cwd: Option<PathBuf>,
+        env: Option<HashMap<String, String>>,
+        allow_unsafe_env: Option<bool>,
+    ) -> String | String;
+    can_spawn_instance() -> SpawnCapability;
+    get_activity_feed(before: Option<FeedCursor>, limit: Option<usize>) -> ActivityPage | String;
+    get_changes_since(cursor: Option<i64>, limit: Option<usize>) -> ChangesSince | String;
+    get_audit_log(limit: Option<usize>) -> Vec<AuditEntry> | String;
+    open_bulk_edit(ids: Option<Vec<String>>, filter: Option<String>) -> String | String;
+    apply_bulk_edit(
+        session_id: String,
+        confirmation_token: Option<String>,
+        typed_count: Option<usize>,
+    ) -> Vec<BulkChange> | String;
+    discard_bulk_edit(session_id: String) -> () | String;
+    undo_bulk_edit() -> Vec<Todo> | String;
+    get_capabilities() -> BTreeMap<String, Capability>;
+    negotiate_protocol(frontend_version: u32, features: Option<Vec<String>>) -> NegotiatedProtocol;
+    define_custom_field(
+        name: String,
+        field_type: FieldType,
+        required: Option<bool>,
+        default: Option<String>,
+    ) -> CustomField | String;
+    list_custom_fields() -> Vec<CustomField> | String;
+    set_todo_field_value(
+        todo_id: String,
+        field_id: String,
+        value: Option<String>,
+    ) -> Option<FieldValue> | String;
+    delete_custom_field(
+        field_id: String,
+        cascade: bool,
+        confirmation_token: Option<String>,
+        typed_count: Option<usize>,
+    ) -> () | String;
+    start_focus_session(todo_id: String) -> () | String;
+    stop_focus_session() -> FocusSession | String;
+    focus_report(range: DateRange) -> FocusReport | String;
+    effort_accuracy_report(range: DateRange) -> EffortReport | String;
+    list_tags() -> Vec<TagCount> | String;
+    weekly_review(
+        week_of: NaiveDate,
+        week_starts_on: Option<Weekday>,
+        utc_offset_minutes: Option<i32>,
+    ) -> WeeklyReview | String;
+    get_cache_stats() -> CacheStats;
+    clear_caches() -> ();
+    lint_existing_todos(scope: Option<LintScope>) -> LintReport | String;
+    export_focus_csv(range: DateRange, path: String) -> usize | PathError;
+    import_todos(
+        path: PathBuf,
+        policy: Option<ConflictPolicy>,
+        operation_id: Option<String>,
+        request_id: Option<String>,
+    ) -> ImportSummary | WriteError;
+    resume_import(
+        checkpoint_id: String,
+        operation_id: Option<String>,
+        request_id: Option<String>,
+    ) -> ImportSummary | WriteError;
+    list_interrupted_imports() -> Vec<ImportCheckpoint> | String;
+    cancel_import(checkpoint_id: String) -> () | String;
+    cancel_operation(operation_id: String) -> () | String;
+    list_operations() -> Vec<OperationProgress>;
+    schedule_command(cmd: ScheduledCommand, when: Schedule) -> String | PathError;
+    list_scheduled_commands() -> Vec<ScheduledEntry> | String;
+    cancel_scheduled_command(id: String) -> () | String;
+    add_rule(trigger: Trigger, conditions: Vec<Condition>, actions: Vec<Action>) -> Rule | String;
+    test_rule(rule: Rule) -> RuleTestResult | String;
+    list_rules() -> Vec<Rule> | String;
+    set_rule_enabled(id: String, enabled: bool) -> () | String;
+    delete_rule(id: String) -> () | String;
+    import_directory(
+        folder: String,
+        recursive: bool,
+        request_id: Option<String>,
+    ) -> DirImportReport | PathError;
+    import_bookmarks(path: String) -> Vec<Todo> | String;
+    list_connector_kinds() -> Vec<ConnectorKind>;
+    add_connector_instance(
+        kind: String,
+        name: String,
+        config: Value,
+        interval_minutes: u64,
+        credential: Option<String>,
+    ) -> ConnectorInstance | PathError;
+    list_connector_instances() -> Vec<ConnectorInstance> | String;
+    run_connector_now(id: String, request_id: Option<String>) -> RunOutcome | String;
+    remove_connector_instance(id: String) -> () | String;
+    add_export_schedule(config: ExportScheduleConfig) -> ExportSchedule | PathError;
+    list_export_schedules() -> Vec<ExportSchedule> | String;
+    run_export_schedule_now(id: String) -> () | String;
+    get_export_schedule_history(id: String) -> Vec<ExportRun> | String;
+    remove_export_schedule(id: String) -> () | String;
+    get_local_api_info() -> LocalApiInfo;
+    reload_settings() -> Vec<String> | String;
+    add_allowed_dir(path: PathBuf) -> PathBuf | String;
+    safe_path(input: String, base: BaseDir) -> PathBuf | String;
+    get_pending_restart_reasons() -> Vec<String>;
+    explain_settings() -> HashMap<String, SettingSource> | String;
+    set_notification_cooldown(seconds: u64) -> ();
+    claim_notification(todo_id: String) -> bool;
+    claim_overdue_notifications(todo_ids: Vec<String>) -> Delivery;
+    get_notification_text(todo_ids: Vec<String>) -> NotificationText | String;
+    show_reminder_notification(todo_id: String) -> bool | String;
+    register_notification_activation() -> () | String;
+    set_privacy_mode(enabled: bool, rules: PrivacyRules) -> PrivacyMode | String;
+    get_privacy_mode() -> PrivacyMode;
+    get_sticky_events() -> HashMap<String, Value>;
+    start_event_recording() -> String | WriteError;
+    stop_event_recording() -> String | String;
+    replay_event_log(path: String) -> usize | PathError;
+    request_destruction_token(
+        operation: DestructiveOperation,
+        scope: DestructionScope,
+    ) -> DestructionToken;
+    resource_usage() -> ResourceUsage;
+    enable_developer_mode(confirmation_token: String) -> () | String;
+    disable_developer_mode() -> ();
+    execute_readonly_sql(
+        query: String,
+        params: Option<Vec<Value>>,
+        limit: Option<usize>,
+    ) -> SqlResult | String;
+    subscribe_query(query_params: QueryParams) -> QueryPage | String;
+    unsubscribe_query(subscription_id: String) -> bool;
+    get_query_page(subscription_id: String) -> QueryPage | String;
+    get_power_mode() -> PowerMode;
+    set_scheduler_interval(seconds: Option<u64>) -> PowerMode | String;
+    render_todo_card(
+        todo_id: String,
+        style: Option<CardStyle>,
+        path: Option<String>,
+        copy_to_clipboard: Option<bool>,
+    ) -> Redacted<String> | PathError;
+    pending_crash_report() -> Option<CrashReport> | String;
+    dismiss_crash_report(id: String) -> () | String;
+    last_failed_operation() -> Option<FailedOperation>;
+    retry_last_failed(request_id: Option<String>) -> () | String;
+    sync_to_system_calendar(config: CalendarConfig) -> CalendarSyncReport | String;
+    get_calendar_sync() -> CalendarConfig | String;
+    export_calendar_ics(
+        path: String,
+        config: Option<CalendarConfig>,
+    ) -> Redacted<usize> | PathError;
+    #[cfg(feature = "simulated-clock")]
+    advance_clock(seconds: u64) -> chrono::DateTime<chrono::Utc> | String;
+    #[cfg(feature = "simulated-clock")]
+    set_clock(datetime: chrono::DateTime<chrono::Utc>) -> chrono::DateTime<chrono::Utc> | String;
+    list_palette_commands(context: Option<PaletteContext>) -> Vec<PaletteEntry>;
+    execute_command(
+        id: String,
+        args: Option<Value>,
+        task_selected: Option<bool>,
+        editing: Option<bool>,
+    ) -> Value | String;
+    run_self_check() -> Vec<Finding> | String;
+    repair(finding_id: String) -> Vec<Finding> | String;
+    reset_data(scope: ResetScope, confirmation_token: Option<String>) -> ResetReport | String;
+    secure_wipe(confirm_phrase: String) -> () | String;
+    get_storage_mode() -> StorageMode;
+    find_legacy_data() -> Option<PathBuf>;
+    migrate_legacy_data() -> MigrationReport | String;
+    find_legacy_databases() -> Vec<PathBuf>;
+    import_legacy_server_db(path: PathBuf, dry_run: bool) -> LegacyServerReport | PathError;
+    sync_todo_cache(todos: Vec<Todo>) -> () | WriteError;
+    update_todo(todo: Todo, base: Option<Todo>) -> TodoUpdated | UpdateError;
+    update_todo_draft(id: String, patch: DraftPatch) -> Todo | String;
+    flush_drafts() -> usize;
+    reorder_todo(
+        id: String,
+        before_id: Option<String>,
+        after_id: Option<String>,
+    ) -> String | String;
+    merge_todos(primary_id: String, duplicate_ids: Vec<String>) -> Todo | String;
+    split_todo(todo_id: String, as_subtasks: Option<bool>) -> Vec<Todo> | String;
+    get_contention_stats() -> ContentionStats;
+    apply_recovery(option: RecoveryOption) -> () | String;
+    check_similar_before_create(title: String, limit: Option<usize>) -> Vec<SimilarTodo>;
+    related_todos(todo_id: String, limit: usize) -> Vec<ScoredTodo> | String;
+    extract_action_items(text: String) -> Vec<Todo>;
+    subscribe_remote_list(url: String, interval_minutes: u64) -> () | String;
+    unsubscribe_remote_list(url: String) -> () | String;
+    list_remote_lists() -> Vec<RemoteSubscription> | String;
+    preview_remote_merge(url: String, request_id: Option<String>) -> TodoDiff | String;
+    publish_list(url: String, format: ExportFormat, request_id: Option<String>) -> () | String;
+    set_publish_token(url: String, token: Option<String>) -> () | String;
+    set_sync_scope(
+        scope: SyncScope,
+        confirmation_token: Option<String>,
+        typed_count: Option<usize>,
+    ) -> SyncScopeReport | String;
+    get_sync_scope_report() -> SyncScopeReport | String;
+    publish_static_site(config: SiteConfig) -> () | PathError;
+    preview_static_site(config: SiteConfig) -> String;
+    schedule_static_site(config: SiteConfig, interval_minutes: u32) -> ExportSchedule | PathError;
+    unschedule_static_site(output_dir: PathBuf) -> () | String;
+    list_static_sites() -> Vec<ExportSchedule> | String;
+    validate_query(query: String) -> QueryAst | Vec<QueryError>;
+    eval_query(query: String, todos: Vec<Todo>) -> Vec<Todo> | String;
+    query_todos(
+        query: Option<String>,
+        sort_by: Option<String>,
+        descending: Option<bool>,
+    ) -> Vec<Todo> | String;
+    detect_language(text: String) -> Detection;
+    create_smart_list(name: String, query: String) -> SmartList | String;
+    list_smart_lists() -> Vec<SmartList> | String;
+    get_smart_list_todos(list_id: String) -> Vec<Todo> | String;
+    delete_smart_list(list_id: String) -> () | String;
+    parse_todo_references(text: String) -> Vec<TodoReference>;
+    resolve_todo_references(references: Vec<TodoReference>) -> Vec<ResolvedReference> | String;
+    broken_references_report(request_id: Option<String>) -> Vec<BrokenReferences> | String;
+    update_reference_text(todo_id: String, old: String, new: String) -> Todo | String;
+    register_file_association() -> () | String;
+    unregister_file_association() -> () | String;
+    export_todos(
+        path: String,
+        format: ExportFormat,
+        stable: Option<bool>,
+    ) -> Redacted<usize> | PathError;
+    export_weekly_review(
+        week_of: NaiveDate,
+        path: String,
+        date_format: Option<String>,
+        week_starts_on: Option<Weekday>,
+        utc_offset_minutes: Option<i32>,
+    ) -> () | PathError;
+    export_signed(path: String, key_path: String) -> Redacted<usize> | PathError;
+    verify_signed_export(path: String, pubkey_path: String) -> bool | PathError;
+    take_opened_files() -> Vec<PathBuf>;
+    get_data_quota_status() -> QuotaStatus | String;
+    preview_import_file(path: PathBuf, request_id: Option<String>) -> ImportPreview | PathError;
+    list_plugins() -> PluginList;
+    reload_plugins() -> PluginList | String;
+    get_trust_status() -> TrustStatus;
+    grant_trust(scope: TrustScope, decisions: BTreeMap<Feature, bool>) -> TrustStatus | String;
+    list_commands() -> Vec<CommandInfo>;
+    api_schema() -> String;
+    quit_app() -> ();
+}
+
+pub fn generate() -> ApiSchema {
+    let mut generator = SchemaSettings::draft07().into_generator();
+    let commands = command_schemas(&mut generator);
+    ApiSchema {
+        commands,
+        definitions: generator.take_definitions().into_iter().collect(),
+    }
+}
@@ -0,0 +1,44 @@
+use super::*;
+
+use std::collections::BTreeSet;
+
+#[test]
+fn test_schema_covers_every_command() {
+    let schema = generate();
+    let described: BTreeSet<&str> = schema.commands.keys().map(String::as_str).collect();
+    let commands = crate::list_commands();
+    let registered: BTreeSet<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(described, registered);
+}
+
+#[test]
+fn test_schema_describes_params_and_output() {
+    let schema: Value = serde_json::from_str(&crate::api_schema()).unwrap();
+
+    let report = &schema["commands"]["focus_report"];
+    assert_eq!(report["params"]["range"]["$ref"], "#/definitions/DateRange");
+    assert_eq!(report["output"]["$ref"], "#/definitions/FocusReport");
+    assert_eq!(report["error"]["type"], "string");
+    let range = &schema["definitions"]["DateRange"]["properties"];
+    assert_eq!(range["start"]["format"], "date-time");
+    assert!(schema["definitions"]["FocusReport"]["properties"]
+        .as_object()
+        .unwrap()
+        .contains_key("perTodo"));
+
+    // Arguments go by the names `invoke` expects.
+    let apply = &schema["commands"]["apply_bulk_edit"]["params"];
+    assert!(apply.get("sessionId").is_some());
+    assert!(apply.get("session_id").is_none());
+
+    let update = &schema["commands"]["update_todo"];
+    assert_eq!(update["error"]["$ref"], "#/definitions/UpdateError");
+    assert!(schema["commands"]["list_commands"].get("error").is_none());
+}
+
+#[test]
+fn test_camel_case() {
+    assert_eq!(camel_case("range"), "range");
+    assert_eq!(camel_case("confirmation_token"), "confirmationToken");
+    assert_eq!(camel_case("allow_unsafe_env"), "allowUnsafeEnv");
+}
@@ -0,0 +1,351 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use rust_stemmers::{Algorithm, Stemmer};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::privacy::PrivacyState;
+use crate::storage::Storage;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+/// Candidates scoring below this are not considered similar.
+const SIMILARITY_THRESHOLD: f32 = 0.5;
+const DEFAULT_SIMILAR_LIMIT: usize = 5;
+/// Words too common in titles to relate two todos, left out of [`keywords`].
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "into", "is", "it", "my",
+    "of", "on", "or", "our", "the", "this", "to", "with",
+];
+
+/// Three characters packed into one integer (21 bits per `char`).
+type Trigram = u64;
+
+/// NFKC-folds, lowercases and collapses whitespace, so full-width and
+/// differently spaced variants of a title compare equal.
+pub fn normalize_title(title: &str) -> String {
+    let folded: String = title.nfkc().flat_map(char::to_lowercase).collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Distinct character trigrams of an already normalized title, padded so that
+/// very short titles still produce at least one.
+pub fn trigrams(normalized: &str) -> Vec<Trigram> {
+    let chars: Vec<char> = std::iter::once(' ')
+        .chain(normalized.chars())
+        .chain(std::iter::once(' '))
+        .collect();
+    let mut seen = HashSet::new();
+    chars
+        .windows(3)
+        .map(|w| ((w[0] as u64) << 42) | ((w[1] as u64) << 21) | w[2] as u64)
+        .filter(|t| seen.insert(*t))
+        .collect()
+}
+
+fn jaccard(shared: usize, a: usize, b: usize) -> f32 {
+    shared as f32 / (a + b - shared) as f32
+}
+
+/// Trigram similarity of two titles, from 0.0 to 1.0 for equal titles.
+pub fn similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (normalize_title(a), normalize_title(b));
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (trigrams(&a), trigrams(&b));
+    let b_set: HashSet<&Trigram> = b.iter().collect();
+    let shared = a.iter().filter(|t| b_set.contains(t)).count();
+    jaccard(shared, a.len(), b.len())
+}
+
+/// How much of `needle` occurs in `haystack`, from 0.0 to 1.0 when every
+/// trigram of the needle does. Unlike [`similarity`], a short query scores
+/// fully against a long title that contains it.
+pub fn containment(needle: &str, haystack: &str) -> f32 {
+    let (needle, haystack) = (normalize_title(needle), normalize_title(haystack));
+    if needle.is_empty() {
+        return 0.0;
+    }
+    if haystack.contains(&needle) {
+        return 1.0;
+    }
+    let needle = trigrams(&needle);
+    let haystack: HashSet<Trigram> = trigrams(&haystack).into_iter().collect();
+    let shared = needle.iter().filter(|t| haystack.contains(t)).count();
+    shared as f32 / needle.len() as f32
+}
+
+/// Snowball stemmer for a detected language (ISO 639-3), if there is one.
+fn stemmer(language: &str) -> Option<Stemmer> {
+    let algorithm = match language {
+        "ara" => Algorithm::Arabic,
+        "dan" => Algorithm::Danish,
+        "nld" => Algorithm::Dutch,
+        "eng" => Algorithm::English,
+        "fin" => Algorithm::Finnish,
+        "fra" => Algorithm::French,
+        "deu" => Algorithm::German,
+        "ell" => Algorithm::Greek,
+        "hun" => Algorithm::Hungarian,
+        "ita" => Algorithm::Italian,
+        "nob" => Algorithm::Norwegian,
+        "por" => Algorithm::Portuguese,
+        "ron" => Algorithm::Romanian,
+        "rus" => Algorithm::Russian,
+        "spa" => Algorithm::Spanish,
+        "swe" => Algorithm::Swedish,
+        "tam" => Algorithm::Tamil,
+        "tur" => Algorithm::Turkish,
+        _ => return None,
+    };
+    Some(Stemmer::create(algorithm))
+}
+
+fn stems(stemmer: &Stemmer, normalized: &str) -> Vec<String> {
+    normalized
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| stemmer.stem(word).into_owned())
+        .collect()
+}
+
+/// Whether `text`, written in `language`, contains `needle`.
+///
+/// Both sides are normalized like titles and compared as substrings, which
+/// suits Japanese and other text without spaces between words. In languages
+/// with a stemmer the words are also compared by stem, so "planning" finds
+/// "plans".
+pub fn text_matches(text: &str, language: Option<&str>, needle: &str) -> bool {
+    let (text, needle) = (normalize_title(text), normalize_title(needle));
+    if text.contains(&needle) {
+        return true;
+    }
+    let Some(stemmer) = language.and_then(stemmer) else {
+        return false;
+    };
+    let (text, needle) = (stems(&stemmer, &text), stems(&stemmer, &needle));
+    !needle.is_empty() && text.windows(needle.len()).any(|words| words == needle)
+}
+
+/// Distinct words of a title in `language`, normalized, without stopwords
+/// and stemmed where the language has a stemmer.
+pub fn keywords(title: &str, language: Option<&str>) -> HashSet<String> {
+    let stemmer = language.and_then(stemmer);
+    normalize_title(title)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !STOPWORDS.contains(word))
+        .map(|word| match &stemmer {
+            Some(stemmer) => stemmer.stem(word).into_owned(),
+            None => word.to_string(),
+        })
+        .collect()
+}
+
+/// A todo related to another, with the keywords they share.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoredTodo {
+    pub id: String,
+    pub title: String,
+    pub score: f32,
+    pub shared: Vec<String>,
+}
+
+/// Up to `limit` incomplete todos whose titles share keywords with the title
+/// of `todo_id`, best first. The score is a Jaccard overlap in which each
+/// keyword counts by how rare it is among `todos`, so sharing "invoice"
+/// says more than sharing "call".
+pub fn related(todos: &[Todo], todo_id: &str, limit: usize) -> Vec<ScoredTodo> {
+    let keyed: Vec<(&Todo, HashSet<String>)> = todos
+        .iter()
+        .map(|todo| (todo, keywords(&todo.title, todo.languages.title.as_deref())))
+        .collect();
+    let Some((_, target)) = keyed.iter().find(|(todo, _)| todo.id == todo_id) else {
+        return Vec::new();
+    };
+    let mut frequency: HashMap<&str, usize> = HashMap::new();
+    for word in keyed.iter().flat_map(|(_, words)| words) {
+        *frequency.entry(word.as_str()).or_default() += 1;
+    }
+    let weight = |word: &str| {
+        let count = frequency.get(word).copied().unwrap_or(1);
+        (1.0 + keyed.len() as f32 / count as f32).ln()
+    };
+
+    let mut matches: Vec<ScoredTodo> = keyed
+        .iter()
+        .filter(|(todo, _)| todo.id != todo_id && !todo.completed)
+        .filter_map(|(todo, words)| {
+            let mut shared: Vec<String> = target.intersection(words).cloned().collect();
+            if shared.is_empty() {
+                return None;
+            }
+            shared.sort();
+            let overlap: f32 = shared.iter().map(|word| weight(word.as_str())).sum();
+            let union: f32 = target.union(words).map(|word| weight(word.as_str())).sum();
+            Some(ScoredTodo {
+                id: todo.id.clone(),
+                title: todo.title.clone(),
+                score: overlap / union,
+                shared,
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+    matches.truncate(limit);
+    matches
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarTodo {
+    pub id: String,
+    pub title: String,
+    pub score: f32,
+    pub scheduled_for: Option<DateTime<Utc>>,
+}
+
+struct Entry {
+    id: String,
+    title: String,
+    normalized: String,
+    trigram_count: usize,
+    scheduled_for: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct IndexData {
+    entries: Vec<Entry>,
+    postings: HashMap<Trigram, Vec<u32>>,
+}
+
+/// In-memory trigram index over the titles of incomplete todos, so advisory
+/// lookups never touch SQLite.
+#[derive(Default)]
+pub struct SearchIndex {
+    data: RwLock<IndexData>,
+}
+
+impl SearchIndex {
+    pub fn rebuild(&self, todos: &[Todo]) {
+        let mut data = IndexData::default();
+        for todo in todos.iter().filter(|t| !t.completed) {
+            let normalized = normalize_title(&todo.title);
+            let grams = trigrams(&normalized);
+            let position = data.entries.len() as u32;
+            for gram in &grams {
+                data.postings.entry(*gram).or_default().push(position);
+            }
+            data.entries.push(Entry {
+                id: todo.id.clone(),
+                title: todo.title.clone(),
+                normalized,
+                trigram_count: grams.len(),
+                scheduled_for: todo.scheduled_for,
+            });
+        }
+        *self.data.write().unwrap_or_else(|e| e.into_inner()) = data;
+    }
+
+    /// How many open todos in `todos` the index lacks or has under an old
+    /// title, plus how many entries it keeps for todos that aren't open.
+    pub fn drift(&self, todos: &[Todo]) -> usize {
+        let data = self.data.read().unwrap_or_else(|e| e.into_inner());
+        let indexed: HashMap<&str, &str> = data
+            .entries
+            .iter()
+            .map(|entry| (entry.id.as_str(), entry.title.as_str()))
+            .collect();
+        let open: HashSet<&str> = todos
+            .iter()
+            .filter(|t| !t.completed)
+            .map(|t| t.id.as_str())
+            .collect();
+        let stale = todos
+            .iter()
+            .filter(|t| !t.completed && indexed.get(t.id.as_str()) != Some(&t.title.as_str()))
+            .count();
+        let gone = data
+            .entries
+            .iter()
+            .filter(|entry| !open.contains(entry.id.as_str()))
+            .count();
+        stale + gone
+    }
+
+    /// Up to `limit` indexed todos whose titles resemble `title`, best first.
+    pub fn similar(&self, title: &str, limit: usize) -> Vec<SimilarTodo> {
+        let normalized = normalize_title(title);
+        let grams = trigrams(&normalized);
+        let data = self.data.read().unwrap_or_else(|e| e.into_inner());
+
+        let mut shared = vec![0u32; data.entries.len()];
+        for gram in &grams {
+            for &position in data.postings.get(gram).into_iter().flatten() {
+                shared[position as usize] += 1;
+            }
+        }
+
+        let mut matches: Vec<SimilarTodo> = shared
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .filter_map(|(position, &count)| {
+                let entry = &data.entries[position];
+                let score = if entry.normalized == normalized {
+                    1.0
+                } else {
+                    jaccard(count as usize, grams.len(), entry.trigram_count)
+                };
+                (score >= SIMILARITY_THRESHOLD).then(|| SimilarTodo {
+                    id: entry.id.clone(),
+                    title: entry.title.clone(),
+                    score,
+                    scheduled_for: entry.scheduled_for,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// Advisory lookup for the add forms; it never fails so it can't block creation.
+#[tauri::command]
+pub fn check_similar_before_create(
+    title: String,
+    limit: Option<usize>,
+    index: State<'_, SearchIndex>,
+) -> Vec<SimilarTodo> {
+    index.similar(&title, limit.unwrap_or(DEFAULT_SIMILAR_LIMIT))
+}
+
+/// Todos related to `todo_id` by the keywords of their titles. Todos that
+/// privacy mode hides are never suggested.
+#[tauri::command]
+pub fn related_todos(
+    todo_id: String,
+    limit: usize,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<Vec<ScoredTodo>, String> {
+    let privacy = privacy.get();
+    let todos: Vec<Todo> = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?
+        .into_iter()
+        .filter(|todo| todo.id == todo_id || !privacy.hides(todo))
+        .collect();
+    if !todos.iter().any(|todo| todo.id == todo_id) {
+        return Err(format!("Unknown todo '{}'", todo_id));
+    }
+    Ok(related(&todos, &todo_id, limit))
+}
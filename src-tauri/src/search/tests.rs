@@ -0,0 +1,166 @@
+use super::*;
+use std::time::{Duration, Instant};
+
+fn todo(id: &str, title: &str, completed: bool) -> Todo {
+    let now = Utc::now();
+    Todo {
+        completed,
+        ..Todo::sample(id, title, now)
+    }
+}
+
+#[test]
+fn test_normalize_title_folds_width_case_and_spacing() {
+    assert_eq!(normalize_title("ＰＡＹ　ＲＥＮＴ"), "pay rent");
+    assert_eq!(normalize_title("  Pay   Rent "), "pay rent");
+}
+
+#[test]
+fn test_containment_scores_a_query_inside_a_longer_title() {
+    assert_eq!(containment("stop", "Stop focus session"), 1.0);
+    assert!(similarity("stop", "Stop focus session") < 0.5);
+    assert!(containment("sess", "Stop focus session") >= 0.75);
+    assert!(containment("milk", "Stop focus session") < 0.3);
+    assert_eq!(containment("", "Stop focus session"), 0.0);
+    // Mid-word, the needle's leading trigram is not in the haystack.
+    assert_eq!(containment("タスク", "新しいタスク"), 1.0);
+}
+
+#[test]
+fn test_similar_matches_full_width_input() {
+    let index = SearchIndex::default();
+    index.rebuild(&[todo("1", "pay rent", false), todo("2", "buy milk", false)]);
+
+    let matches = index.similar("ＰＡＹ　ＲＥＮＴ", 5);
+
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].id, "1");
+    assert_eq!(matches[0].score, 1.0);
+}
+
+#[test]
+fn test_similar_ranks_close_titles_and_skips_completed() {
+    let index = SearchIndex::default();
+    index.rebuild(&[
+        todo("1", "pay the rent", false),
+        todo("2", "pay rent", true),
+        todo("3", "pay rent now", false),
+        todo("4", "water plants", false),
+    ]);
+
+    let matches = index.similar("pay rent", 5);
+    let ids: Vec<&str> = matches.iter().map(|m| m.id.as_str()).collect();
+
+    assert_eq!(ids, vec!["3", "1"]);
+    assert!(matches[0].score >= matches[1].score);
+}
+
+#[test]
+fn test_similar_respects_limit() {
+    let index = SearchIndex::default();
+    let todos: Vec<Todo> = (0..10)
+        .map(|i| todo(&i.to_string(), "pay rent", false))
+        .collect();
+    index.rebuild(&todos);
+
+    assert_eq!(index.similar("pay rent", 3).len(), 3);
+}
+
+#[test]
+fn test_similar_latency_on_large_index() {
+    let index = SearchIndex::default();
+    let todos: Vec<Todo> = (0..50_000)
+        .map(|i| {
+            todo(
+                &i.to_string(),
+                &format!("task {} for project {}", i, i % 97),
+                false,
+            )
+        })
+        .collect();
+    index.rebuild(&todos);
+
+    // Warm up, then take the best of a few runs to keep the assertion stable on
+    // busy CI machines. Debug builds are several times slower than release.
+    index.similar("task 4242 for project 71", 5);
+    let best = (0..5)
+        .map(|_| {
+            let started = Instant::now();
+            let matches = index.similar("task 4242 for project 71", 5);
+            assert_eq!(matches[0].id, "4242");
+            started.elapsed()
+        })
+        .min()
+        .unwrap();
+
+    let budget = if cfg!(debug_assertions) {
+        Duration::from_millis(50)
+    } else {
+        Duration::from_millis(5)
+    };
+    assert!(best < budget, "lookup took {:?}", best);
+}
+
+#[test]
+fn test_text_matches_by_stem_in_languages_with_a_stemmer() {
+    let text = "Planning the quarterly reports";
+    assert!(text_matches(text, Some("eng"), "plans"));
+    assert!(text_matches(text, Some("eng"), "quarterly report"));
+    assert!(!text_matches(text, Some("eng"), "reports quarterly"));
+    // Unknown language: substrings only.
+    assert!(!text_matches(text, None, "plans"));
+    assert!(text_matches(text, None, "PLANNING"));
+}
+
+#[test]
+fn test_text_matches_japanese_substrings() {
+    let text = "会議の資料を準備する";
+    assert!(text_matches(text, Some("jpn"), "資料"));
+    assert!(text_matches(text, Some("jpn"), "準備"));
+    assert!(!text_matches(text, Some("jpn"), "報告"));
+    assert!(text_matches("ＰＤＦを送る", Some("jpn"), "pdf"));
+}
+
+#[test]
+fn test_keywords_drop_stopwords_and_stem() {
+    let words = keywords("Plan the Trip to Osaka", None);
+    let expected: HashSet<String> = ["plan", "trip", "osaka"].map(String::from).into();
+    assert_eq!(words, expected);
+    assert!(keywords("Planning trips", Some("eng")).contains("trip"));
+}
+
+#[test]
+fn test_related_ranks_by_rare_shared_keywords() {
+    let todos = vec![
+        todo("1", "Send invoice to Acme", false),
+        todo("2", "Acme invoice follow-up", false),
+        todo("3", "Send birthday card", false),
+        todo("4", "Send report", false),
+        todo("5", "Walk the dog", false),
+    ];
+
+    let found = related(&todos, "1", 10);
+    let ids: Vec<_> = found.iter().map(|r| r.id.as_str()).collect();
+    // "send" is in three titles, so sharing it counts for less, and the
+    // shorter title shares a larger part of its keywords.
+    assert_eq!(ids, vec!["2", "4", "3"]);
+    assert_eq!(found[0].shared, vec!["acme", "invoice"]);
+    assert_eq!(found[1].shared, vec!["send"]);
+    assert!(found[1].score < found[0].score);
+    assert!(found.iter().all(|r| r.score > 0.0 && r.score <= 1.0));
+}
+
+#[test]
+fn test_related_excludes_the_todo_itself_and_completed_ones() {
+    let todos = vec![
+        todo("1", "Renew passport", false),
+        todo("2", "Renew passport", true),
+        todo("3", "Passport photos", false),
+    ];
+
+    let found = related(&todos, "1", 10);
+    let ids: Vec<_> = found.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, vec!["3"]);
+    assert!(related(&todos, "missing", 10).is_empty());
+    assert!(related(&todos, "1", 0).is_empty());
+}
@@ -0,0 +1,423 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::clock::Clock;
+use crate::command_registry::{self, Category, CommandDescriptor, CommandRegistry, EnabledWhen};
+use crate::events;
+use crate::protocol::EventPayload;
+use crate::quota::{self, QuotaDimension, QuotaStatus};
+use crate::reset;
+use crate::search::SearchIndex;
+use crate::settings::{self, QuotaSettings, SettingsState, KEYBINDINGS_FILE, SETTINGS_FILE};
+use crate::storage::recovery::RecoveryState;
+use crate::storage::{self, Storage};
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// Free space below which the data directory is reported as critically full.
+const MIN_FREE_BYTES: u64 = 100 * 1024 * 1024;
+/// Open todos the search index may disagree with the store about, as while
+/// a write's rebuild is under way, before it is reported.
+const MAX_INDEX_DRIFT: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A fix the backend knows how to apply for a finding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RepairAction {
+    /// Rebuilds the search index from the store.
+    RebuildSearchIndex,
+    /// Moves the unparsable file aside so the frontend recreates it from defaults.
+    RepairSettings { file: PathBuf },
+    /// Copies the store to `to` and points the `data_dir` setting there,
+    /// which takes effect on the next start.
+    RelocateDataDir { to: PathBuf },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    pub id: String,
+    pub severity: Severity,
+    pub message: String,
+    pub repair: Option<RepairAction>,
+}
+
+impl EventPayload for Finding {}
+
+/// What the self-check inspects besides the store itself.
+pub struct CheckPaths {
+    pub config_dir: PathBuf,
+    pub quota: QuotaSettings,
+    /// Where the store can move when its data directory is unusable.
+    pub fallback_data_dir: Option<PathBuf>,
+}
+
+/// Findings from the most recent self-check run.
+#[derive(Default)]
+pub struct SelfCheckState {
+    findings: Mutex<Vec<Finding>>,
+}
+
+pub fn run_checks(storage: &Storage, paths: &CheckPaths) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    findings.extend(check_database(storage));
+    findings.extend(check_toml_file(
+        "settings-parse",
+        &paths.config_dir.join(SETTINGS_FILE),
+    ));
+    findings.extend(check_toml_file(
+        "keybindings-parse",
+        &paths.config_dir.join(KEYBINDINGS_FILE),
+    ));
+    findings.extend(check_writable(storage.files_dir(), paths));
+    findings.extend(check_disk_space(storage.files_dir(), paths));
+    findings.extend(check_quota(storage, &paths.quota));
+    findings
+}
+
+fn check_database(storage: &Storage) -> Option<Finding> {
+    // A separate connection keeps the check from holding up command traffic.
+    let result: rusqlite::Result<String> = storage
+        .connect()
+        .and_then(|conn| conn.query_row("PRAGMA quick_check", [], |row| row.get(0)));
+    let problem = match result {
+        Ok(status) if status == "ok" => return None,
+        Ok(status) => status,
+        Err(e) => e.to_string(),
+    };
+    Some(Finding {
+        id: "database-integrity".to_string(),
+        severity: Severity::Error,
+        message: format!("Database integrity check failed: {}", problem),
+        repair: None,
+    })
+}
+
+fn check_toml_file(id: &str, path: &Path) -> Option<Finding> {
+    // A missing file is fine: the frontend writes defaults on first run.
+    let content = fs::read_to_string(path).ok()?;
+    let error = content.parse::<toml::Table>().err()?;
+    Some(Finding {
+        id: id.to_string(),
+        severity: Severity::Warning,
+        message: format!("Failed to parse {}: {}", path.display(), error.message()),
+        repair: Some(RepairAction::RepairSettings {
+            file: path.to_path_buf(),
+        }),
+    })
+}
+
+fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(".write-probe");
+    fs::write(&probe, b"ok").and_then(|_| fs::remove_file(&probe))
+}
+
+/// The move to `paths.fallback_data_dir`, if it is free and has room.
+fn relocation(paths: &CheckPaths) -> Option<RepairAction> {
+    let to = paths.fallback_data_dir.as_ref()?;
+    if storage::holds_database(to) {
+        return None;
+    }
+    let existing = to.ancestors().find(|dir| dir.is_dir())?;
+    let room = fs2::available_space(existing).ok()? >= MIN_FREE_BYTES;
+    (room && probe_writable(existing).is_ok())
+        .then(|| RepairAction::RelocateDataDir { to: to.clone() })
+}
+
+fn check_writable(dir: &Path, paths: &CheckPaths) -> Option<Finding> {
+    let error = probe_writable(dir).err()?;
+    Some(Finding {
+        id: "data-dir-writable".to_string(),
+        severity: Severity::Error,
+        message: format!(
+            "Data directory {} is not writable: {}",
+            dir.display(),
+            error
+        ),
+        repair: relocation(paths),
+    })
+}
+
+fn check_disk_space(dir: &Path, paths: &CheckPaths) -> Option<Finding> {
+    let available = fs2::available_space(dir).ok()?;
+    if available >= MIN_FREE_BYTES {
+        return None;
+    }
+    Some(Finding {
+        id: "disk-space".to_string(),
+        severity: Severity::Warning,
+        message: format!(
+            "Only {} MB free in {}",
+            available / (1024 * 1024),
+            dir.display()
+        ),
+        repair: relocation(paths),
+    })
+}
+
+fn check_quota(storage: &Storage, limits: &QuotaSettings) -> Vec<Finding> {
+    let Ok(usage) = quota::usage(storage) else {
+        return Vec::new();
+    };
+    let status = QuotaStatus::new(usage, limits.clone());
+    status
+        .warnings
+        .iter()
+        .map(|&dimension| {
+            let (used, soft, hard) = match dimension {
+                QuotaDimension::Todos => (status.usage.todos, limits.soft_todos, limits.hard_todos),
+                QuotaDimension::AttachmentBytes => (
+                    status.usage.attachment_bytes,
+                    limits.soft_attachment_bytes,
+                    limits.hard_attachment_bytes,
+                ),
+            };
+            let exhausted = status.exhausted.contains(&dimension);
+            Finding {
+                id: format!("quota-{}", dimension.to_string().replace(' ', "-")),
+                severity: if exhausted {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                },
+                message: if exhausted {
+                    format!(
+                        "{} {} stored, at the limit of {}; new data is refused",
+                        used, dimension, hard
+                    )
+                } else {
+                    format!(
+                        "{} {} stored, past the warning threshold of {} (limit {})",
+                        used, dimension, soft, hard
+                    )
+                },
+                repair: None,
+            }
+        })
+        .collect()
+}
+
+fn check_search_index(storage: &Storage, index: &SearchIndex) -> Option<Finding> {
+    let drift = index.drift(&storage.list_todos().ok()?);
+    if drift <= MAX_INDEX_DRIFT {
+        return None;
+    }
+    Some(Finding {
+        id: "search-index-drift".to_string(),
+        severity: Severity::Warning,
+        message: format!("The search index is out of date for {} todos", drift),
+        repair: Some(RepairAction::RebuildSearchIndex),
+    })
+}
+
+/// What storage recovery did at startup, or that it waits for a decision.
+fn recovery_findings(recovery: &RecoveryState) -> Option<Finding> {
+    if recovery.steps().is_empty() {
+        return None;
+    }
+    let steps: Vec<String> = recovery
+        .steps()
+        .iter()
+        .map(|outcome| format!("{:?}: {}", outcome.step, outcome.detail))
+        .collect();
+    let recovered = recovery.steps().iter().any(|outcome| outcome.recovered);
+    Some(Finding {
+        id: "storage-recovery".to_string(),
+        severity: if recovered {
+            Severity::Warning
+        } else {
+            Severity::Error
+        },
+        message: format!(
+            "The database was damaged and {}: {}",
+            if recovered {
+                "recovered at startup"
+            } else {
+                "needs a decision"
+            },
+            steps.join("; ")
+        ),
+        repair: None,
+    })
+}
+
+/// Saves `dir` as the `data_dir` setting in `settings.toml`.
+fn save_data_dir_to_settings_file(settings_path: &Path, dir: &Path) -> Result<(), String> {
+    let content = match fs::read_to_string(settings_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read settings: {}", e)),
+    };
+    let mut table: toml::Table = content
+        .parse()
+        .map_err(|e| format!("Failed to parse settings: {}", e))?;
+    let mut backend = settings::take_table(&mut table, "backend")?;
+    backend.insert(
+        "data_dir".to_string(),
+        toml::Value::String(dir.to_string_lossy().into_owned()),
+    );
+    table.insert("backend".to_string(), backend.into());
+    let content =
+        toml::to_string(&table).map_err(|e| format!("Failed to write settings: {}", e))?;
+    fs::write(settings_path, content).map_err(|e| format!("Failed to write settings: {}", e))
+}
+
+pub fn apply_repair(
+    action: &RepairAction,
+    storage: &Storage,
+    index: &SearchIndex,
+    config_dir: &Path,
+) -> Result<(), String> {
+    match action {
+        RepairAction::RebuildSearchIndex => {
+            let todos = storage
+                .list_todos()
+                .map_err(|e| format!("Failed to load todos: {}", e))?;
+            index.rebuild(&todos);
+            Ok(())
+        }
+        RepairAction::RepairSettings { file } => reset::discard(file, Some(storage.clock().now()))
+            .map(|_| ())
+            .map_err(|e| format!("Failed to move {} aside: {}", file.display(), e)),
+        RepairAction::RelocateDataDir { to } => {
+            storage.copy_to(to)?;
+            save_data_dir_to_settings_file(&config_dir.join(SETTINGS_FILE), to)
+        }
+    }
+}
+
+/// Bindings in `keybindings.toml` whose command nothing registered, which
+/// would silently do nothing.
+fn check_keybinding_commands(registry: &CommandRegistry, path: &Path) -> Option<Finding> {
+    let unknown = command_registry::unknown_bindings(registry, path);
+    if unknown.is_empty() {
+        return None;
+    }
+    let bindings: Vec<String> = unknown
+        .iter()
+        .map(|(key, command)| format!("{} ({})", command, key))
+        .collect();
+    Some(Finding {
+        id: "keybindings-commands".to_string(),
+        severity: Severity::Warning,
+        message: format!(
+            "{} binds unknown commands: {}",
+            path.display(),
+            bindings.join(", ")
+        ),
+        repair: None,
+    })
+}
+
+fn check_paths(app: &AppHandle) -> Result<CheckPaths, String> {
+    // A store kept elsewhere through the `data_dir` setting can move back to
+    // the app data directory.
+    let default_data_dir = app.path().app_data_dir().ok();
+    let fallback_data_dir = app
+        .state::<Storage>()
+        .mode()
+        .data_dir
+        .and_then(|dir| default_data_dir.filter(|default| *default != dir));
+    Ok(CheckPaths {
+        config_dir: settings::config_dir(app)
+            .map_err(|e| format!("Failed to resolve config directory: {}", e))?,
+        quota: app
+            .try_state::<SettingsState>()
+            .map(|settings| settings.get().quota)
+            .unwrap_or_default(),
+        fallback_data_dir,
+    })
+}
+
+fn refresh(app: &AppHandle) -> Result<Vec<Finding>, String> {
+    let paths = check_paths(app)?;
+    let mut findings = run_checks(&app.state::<Storage>(), &paths);
+    findings.extend(check_keybinding_commands(
+        &app.state::<CommandRegistry>(),
+        &paths.config_dir.join(KEYBINDINGS_FILE),
+    ));
+    findings.extend(check_search_index(
+        &app.state::<Storage>(),
+        &app.state::<SearchIndex>(),
+    ));
+    findings.extend(recovery_findings(&app.state::<RecoveryState>()));
+    *app.state::<SelfCheckState>()
+        .findings
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = findings.clone();
+    let _ = events::emit(app, "self-check-completed", &findings);
+    Ok(findings)
+}
+
+/// Runs the self-check off the main thread so a slow or failing check never
+/// delays the window from opening.
+pub fn spawn_startup_check(app: AppHandle) {
+    std::thread::spawn(move || {
+        if let Err(e) = refresh(&app) {
+            trace::log(format!("Startup self-check failed: {}", e));
+        }
+    });
+}
+
+pub fn register_commands(registry: &CommandRegistry) {
+    registry.register(
+        CommandDescriptor {
+            id: "runSelfCheck",
+            title_key: "commandPalette.commands.settings.runSelfCheck.title",
+            default_title: "Run Self-Check",
+            category: Category::Settings,
+            default_binding: None,
+            enabled_when: EnabledWhen::Always,
+            command: Some("run_self_check"),
+        },
+        |app, _| {
+            let findings = refresh(app)?;
+            serde_json::to_value(findings).map_err(|e| e.to_string())
+        },
+    );
+}
+
+#[tauri::command]
+pub fn run_self_check(app: AppHandle) -> Result<Vec<Finding>, String> {
+    refresh(&app)
+}
+
+#[tauri::command]
+pub fn repair(
+    finding_id: String,
+    app: AppHandle,
+    state: State<'_, SelfCheckState>,
+) -> Result<Vec<Finding>, String> {
+    let action = state
+        .findings
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|f| f.id == finding_id)
+        .ok_or_else(|| format!("Failed to repair: unknown finding '{}'", finding_id))?
+        .repair
+        .clone()
+        .ok_or_else(|| format!("Failed to repair: '{}' has no automatic repair", finding_id))?;
+
+    apply_repair(
+        &action,
+        &app.state::<Storage>(),
+        &app.state::<SearchIndex>(),
+        &check_paths(&app)?.config_dir,
+    )?;
+    refresh(&app)
+}
@@ -0,0 +1,187 @@
+use super::*;
+use crate::storage::StorageBackend;
+use crate::types::Todo;
+
+fn setup() -> (tempfile::TempDir, CheckPaths) {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = CheckPaths {
+        config_dir: dir.path().join("config"),
+        quota: QuotaSettings::default(),
+        fallback_data_dir: None,
+    };
+    fs::create_dir_all(&paths.config_dir).unwrap();
+    (dir, paths)
+}
+
+#[test]
+fn test_healthy_setup_has_no_findings() {
+    let (_dir, paths) = setup();
+    fs::write(
+        paths.config_dir.join(SETTINGS_FILE),
+        "[app]\ntheme = \"dark\"\n",
+    )
+    .unwrap();
+    let storage = Storage::open_in_memory().unwrap();
+
+    let findings = run_checks(&storage, &paths);
+
+    assert!(
+        findings.iter().all(|f| f.id == "disk-space"),
+        "unexpected findings: {:?}",
+        findings
+    );
+}
+
+#[test]
+fn test_broken_settings_reported_and_repaired() {
+    let (_dir, paths) = setup();
+    let settings_path = paths.config_dir.join(SETTINGS_FILE);
+    fs::write(&settings_path, "[app\ntheme = ").unwrap();
+    let storage = Storage::open_in_memory().unwrap();
+
+    let findings = run_checks(&storage, &paths);
+    let finding = findings.iter().find(|f| f.id == "settings-parse").unwrap();
+    assert_eq!(finding.severity, Severity::Warning);
+    assert_eq!(
+        finding.repair,
+        Some(RepairAction::RepairSettings {
+            file: settings_path.clone()
+        })
+    );
+
+    apply_repair(
+        finding.repair.as_ref().unwrap(),
+        &storage,
+        &SearchIndex::default(),
+        &paths.config_dir,
+    )
+    .unwrap();
+
+    assert!(!settings_path.exists());
+    let moved_aside = fs::read_dir(&paths.config_dir)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .any(|e| {
+            e.file_name()
+                .to_string_lossy()
+                .starts_with("settings.toml.broken-")
+        });
+    assert!(moved_aside);
+    assert!(run_checks(&storage, &paths)
+        .iter()
+        .all(|f| f.id != "settings-parse"));
+}
+
+#[test]
+fn test_missing_data_dir_is_not_writable() {
+    let (dir, paths) = setup();
+    let storage = Storage::open(StorageBackend::Disk(dir.path().join("data"))).unwrap();
+    fs::remove_dir_all(storage.files_dir()).unwrap();
+
+    let findings = run_checks(&storage, &paths);
+
+    let finding = findings
+        .iter()
+        .find(|f| f.id == "data-dir-writable")
+        .unwrap();
+    assert_eq!(finding.severity, Severity::Error);
+    assert!(finding.repair.is_none());
+}
+
+#[test]
+fn test_unwritable_data_dir_is_relocated() {
+    let (dir, mut paths) = setup();
+    let storage = Storage::open(StorageBackend::Disk(dir.path().join("data"))).unwrap();
+    storage
+        .save_todo(&Todo::sample("a", "Survive the move", chrono::Utc::now()))
+        .unwrap();
+    fs::remove_dir_all(storage.files_dir()).unwrap();
+    let fallback = dir.path().join("fallback");
+    paths.fallback_data_dir = Some(fallback.clone());
+
+    let findings = run_checks(&storage, &paths);
+    let finding = findings
+        .iter()
+        .find(|f| f.id == "data-dir-writable")
+        .unwrap();
+    assert_eq!(
+        finding.repair,
+        Some(RepairAction::RelocateDataDir {
+            to: fallback.clone()
+        })
+    );
+
+    apply_repair(
+        finding.repair.as_ref().unwrap(),
+        &storage,
+        &SearchIndex::default(),
+        &paths.config_dir,
+    )
+    .unwrap();
+
+    let moved = Storage::open(StorageBackend::Disk(fallback.clone())).unwrap();
+    assert_eq!(moved.list_todos().unwrap().len(), 1);
+    let settings: toml::Table = fs::read_to_string(paths.config_dir.join(SETTINGS_FILE))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(
+        settings["backend"]["data_dir"].as_str(),
+        Some(fallback.to_str().unwrap())
+    );
+    // The copy is there now, so it isn't offered again.
+    assert_eq!(relocation(&paths), None);
+}
+
+#[test]
+fn test_search_index_drift_is_reported_and_rebuilt() {
+    let storage = Storage::open_in_memory().unwrap();
+    let index = SearchIndex::default();
+    let now = chrono::Utc::now();
+    for n in 0..MAX_INDEX_DRIFT {
+        storage
+            .save_todo(&Todo::sample(format!("t{}", n), format!("Todo {}", n), now))
+            .unwrap();
+    }
+    assert_eq!(check_search_index(&storage, &index), None);
+
+    storage
+        .save_todo(&Todo::sample("late", "Missed by the index", now))
+        .unwrap();
+    let finding = check_search_index(&storage, &index).unwrap();
+    assert_eq!(finding.repair, Some(RepairAction::RebuildSearchIndex));
+
+    let (_dir, paths) = setup();
+    apply_repair(
+        &RepairAction::RebuildSearchIndex,
+        &storage,
+        &index,
+        &paths.config_dir,
+    )
+    .unwrap();
+    assert_eq!(index.drift(&storage.list_todos().unwrap()), 0);
+    assert_eq!(check_search_index(&storage, &index), None);
+}
+
+#[test]
+fn test_keybindings_to_unknown_commands_are_reported() {
+    let (_dir, paths) = setup();
+    let path = paths.config_dir.join(KEYBINDINGS_FILE);
+    let registry = CommandRegistry::default();
+    command_registry::register_window_commands(&registry);
+    fs::write(
+        &path,
+        "[[keybindings]]\nkey = \"Ctrl+N\"\ncommand = \"newTask\"\n",
+    )
+    .unwrap();
+    assert_eq!(check_keybinding_commands(&registry, &path), None);
+
+    fs::write(
+        &path,
+        "[[keybindings]]\nkey = \"Ctrl+J\"\ncommand = \"jumpAround\"\n",
+    )
+    .unwrap();
+    let finding = check_keybinding_commands(&registry, &path).unwrap();
+    assert_eq!(finding.id, "keybindings-commands");
+    assert!(finding.message.contains("jumpAround (Ctrl+J)"));
+}
@@ -0,0 +1,664 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::trace::{self, LogLevel};
+use crate::validation_rules::ValidationRule;
+
+#[cfg(test)]
+mod tests;
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+pub const KEYBINDINGS_FILE: &str = "keybindings.toml";
+/// `YUTODO_LOCAL_API__PORT=9000` overrides `local_api.port`.
+pub const ENV_PREFIX: &str = "YUTODO_";
+/// `--setting=local_api.port=9000` overrides `local_api.port`.
+pub const SETTING_FLAG: &str = "--setting";
+
+/// Directory holding `settings.toml` and `keybindings.toml`.
+///
+/// Mirrors `SettingsManager.getConfigDirectory()` on the frontend so both sides
+/// read the same files.
+pub fn config_dir(app: &AppHandle) -> tauri::Result<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        Ok(app.path().app_data_dir()?.join("YuToDo"))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Ok(app
+            .path()
+            .home_dir()?
+            .join("Library")
+            .join("Application Support")
+            .join("YuToDo"))
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        Ok(app.path().config_dir()?.join("yutodo"))
+    }
+}
+
+/// Backend-owned settings, stored in the `[backend]` table of `settings.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Refuse to open new windows while a focus session is running.
+    pub block_spawn_during_focus: bool,
+    /// Run user plugins at all; each must also be in `enabled_plugins`.
+    pub allow_plugins: bool,
+    pub enabled_plugins: Vec<String>,
+    pub local_api: LocalApiSettings,
+    pub safety: SafetySettings,
+    pub scheduler: SchedulerSettings,
+    pub quota: QuotaSettings,
+    pub file_access: FileAccessSettings,
+    pub privacy: PrivacySettings,
+    pub import: ImportSettings,
+    pub activity: ActivitySettings,
+    pub notifications: NotificationSettings,
+    /// Sync server the frontend connects to, in place of the `serverUrl` of
+    /// its own settings. Must be an http(s) URL.
+    #[serde(deserialize_with = "http_url")]
+    pub server_url: Option<String>,
+    /// Where the database and attachments live instead of the app data
+    /// directory, e.g. a mounted volume. Must be absolute.
+    #[serde(deserialize_with = "absolute_path")]
+    pub data_dir: Option<PathBuf>,
+    pub log_level: LogLevel,
+    /// Log what instances started with `spawn_new_instance` print, instead
+    /// of leaving their output detached.
+    pub capture_child_output: bool,
+    /// The binary `spawn_new_instance` starts when neither the current
+    /// executable nor `argv[0]` can be resolved, as in some sandboxes.
+    /// Must be absolute.
+    #[serde(deserialize_with = "absolute_path")]
+    pub instance_binary: Option<PathBuf>,
+    /// Offer developer tools such as the SQL console. Each session must
+    /// still confirm before they open.
+    pub developer_mode: bool,
+    pub sync_scope: SyncScope,
+    /// Conventions todos are checked against; see [`crate::validation_rules`].
+    pub validation_rules: Vec<ValidationRule>,
+}
+
+fn http_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let Some(url) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    match url::Url::parse(url.trim()) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+            Ok(Some(url.trim().to_string()))
+        }
+        _ => Err(serde::de::Error::custom(format!(
+            "'{}' is not an http(s) URL",
+            url
+        ))),
+    }
+}
+
+fn absolute_path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+    match Option::<PathBuf>::deserialize(deserializer)? {
+        Some(path) if !path.is_absolute() => Err(serde::de::Error::custom(format!(
+            "'{}' is not an absolute path",
+            path.display()
+        ))),
+        path => Ok(path),
+    }
+}
+
+/// `[backend.local_api]`: the opt-in HTTP API for automation tools.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalApiSettings {
+    pub enabled: bool,
+    /// Port on 127.0.0.1; the API never listens on other interfaces.
+    pub port: u16,
+}
+
+impl Default for LocalApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 27183,
+        }
+    }
+}
+
+/// `[backend.safety]`: extra confirmation for destructive commands.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafetySettings {
+    /// Above this many items, the user must also type the item count.
+    pub require_typed_count_above: Option<usize>,
+}
+
+/// `[backend.scheduler]`: the background housekeeping loop.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerSettings {
+    /// Fixed wake-up interval; when unset it depends on the power source.
+    pub interval_secs: Option<u64>,
+}
+
+/// `[backend.quota]`: how much data the app takes on before refusing writes.
+/// The limits are generous; they exist so a runaway import fails with a clear
+/// message instead of freezing the UI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct QuotaSettings {
+    /// Crossing this many todos warns once.
+    pub soft_todos: u64,
+    /// Writes that would go past this many todos are refused.
+    pub hard_todos: u64,
+    pub soft_attachment_bytes: u64,
+    pub hard_attachment_bytes: u64,
+}
+
+impl Default for QuotaSettings {
+    fn default() -> Self {
+        const GIB: u64 = 1024 * 1024 * 1024;
+        Self {
+            soft_todos: 800_000,
+            hard_todos: 1_000_000,
+            soft_attachment_bytes: 8 * GIB,
+            hard_attachment_bytes: 10 * GIB,
+        }
+    }
+}
+
+/// `[backend.file_access]`: where import and export may read and write.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileAccessSettings {
+    /// Allowed on top of the app data, config, downloads and documents
+    /// directories, subdirectories included.
+    pub allowed_dirs: Vec<PathBuf>,
+}
+
+/// `[backend.import]`: how imports write to the store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportSettings {
+    /// Rows committed per transaction. Bigger batches import faster but
+    /// redo more rows after a crash.
+    pub batch_rows: u64,
+}
+
+impl Default for ImportSettings {
+    fn default() -> Self {
+        Self { batch_rows: 500 }
+    }
+}
+
+/// `[backend.activity]`: the activity feed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivitySettings {
+    /// Entries untouched for longer are pruned.
+    pub retention_days: u32,
+}
+
+impl Default for ActivitySettings {
+    fn default() -> Self {
+        Self { retention_days: 90 }
+    }
+}
+
+/// `[backend.notifications]`: when a burst of reminders collapses into one
+/// digest notification instead of firing one by one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// More notifications than this within `digest_window_secs` collapse.
+    pub digest_threshold: usize,
+    pub digest_window_secs: u64,
+    /// The highest-priority todos of a burst still shown on their own.
+    pub individual_top: usize,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            digest_threshold: 5,
+            digest_window_secs: 60,
+            individual_top: 3,
+        }
+    }
+}
+
+/// `[backend.privacy]`: privacy mode for screen sharing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacySettings {
+    /// Global shortcut that toggles privacy mode, e.g. `"CmdOrCtrl+Shift+P"`.
+    pub toggle_shortcut: Option<String>,
+}
+
+/// `[backend.sync_scope]`: which todos may leave the machine when the list
+/// is published. Lists are smart list ids and tags match case-insensitively;
+/// with nothing included, every todo that is not excluded is in scope.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SyncScope {
+    pub include_lists: Vec<String>,
+    pub exclude_lists: Vec<String>,
+    pub include_tags: Vec<String>,
+    pub exclude_tags: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct SettingsFile {
+    #[serde(default)]
+    backend: Settings,
+}
+
+impl Settings {
+    /// Reads the backend settings from `path`, this process's environment and
+    /// its command line. See [`resolve`] for how they combine.
+    pub fn load(path: &Path) -> Self {
+        let resolved = resolve(&SettingsSources::current(path));
+        for rejected in &resolved.rejected {
+            trace::warn(rejected);
+        }
+        resolved.settings
+    }
+}
+
+/// Where a setting's value came from, lowest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum SettingLayer {
+    Default,
+    File,
+    Env,
+    Cli,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingSource {
+    pub layer: SettingLayer,
+    pub value: serde_json::Value,
+}
+
+/// Everything settings are read from, before resolving.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsSources {
+    /// Contents of `settings.toml`, if it could be read.
+    pub file: Option<String>,
+    pub env: HashMap<String, String>,
+    pub args: Vec<String>,
+}
+
+impl SettingsSources {
+    pub fn current(path: &Path) -> Self {
+        Self {
+            file: fs::read_to_string(path).ok(),
+            env: std::env::vars()
+                .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+                .collect(),
+            args: std::env::args().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedSettings {
+    pub settings: Settings,
+    /// By dotted key, e.g. `local_api.port`.
+    pub sources: BTreeMap<String, SettingSource>,
+    /// Overrides that were ignored, with the reason.
+    pub rejected: Vec<String>,
+}
+
+/// Removes the table `key` from a parsed settings file to edit it, or starts
+/// an empty one.
+pub(crate) fn take_table(table: &mut toml::Table, key: &str) -> Result<toml::Table, String> {
+    match table.remove(key) {
+        None => Ok(toml::Table::new()),
+        Some(toml::Value::Table(section)) => Ok(section),
+        Some(_) => Err(format!("Settings key '{}' is not a table", key)),
+    }
+}
+
+/// Name of the environment variable that overrides `key`.
+pub fn env_var(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.replace('.', "__").to_uppercase())
+}
+
+/// Dotted paths of every leaf setting, in `serde_json` key order.
+fn leaf_keys(value: &serde_json::Value, prefix: &str, out: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                leaf_keys(value, &path, out);
+            }
+        }
+        _ => out.push(prefix.to_string()),
+    }
+}
+
+fn get_path<'a>(value: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+    key.split('.')
+        .try_fold(value, |value, segment| value.get(segment))
+}
+
+fn set_path(value: &mut serde_json::Value, key: &str, new: serde_json::Value) {
+    let mut target = value;
+    for segment in key.split('.') {
+        target = &mut target[segment];
+    }
+    *target = new;
+}
+
+/// An override from the environment or command line: JSON when it parses
+/// as JSON (`9000`, `true`, `["a"]`), otherwise the text itself.
+fn parse_override(raw: &str) -> Vec<serde_json::Value> {
+    let text = serde_json::Value::String(raw.to_string());
+    match serde_json::from_str::<serde_json::Value>(raw) {
+        Ok(value) if value != text => vec![value, text],
+        _ => vec![text],
+    }
+}
+
+/// `--setting=key=value` flags, last one winning.
+fn cli_overrides(args: &[String]) -> HashMap<String, String> {
+    args.iter()
+        .filter_map(|arg| arg.strip_prefix(SETTING_FLAG)?.strip_prefix('='))
+        .filter_map(|setting| setting.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .collect()
+}
+
+/// Combines the sources with the precedence CLI > env > file > default. An
+/// unparsable file is ignored as a whole, as before overrides existed (the
+/// self-check reports it); an override of the wrong type is ignored alone.
+pub fn resolve(sources: &SettingsSources) -> ResolvedSettings {
+    let mut value = serde_json::to_value(Settings::default()).unwrap_or_default();
+    let mut keys = Vec::new();
+    leaf_keys(&value, "", &mut keys);
+    let mut layers: BTreeMap<String, SettingLayer> = keys
+        .iter()
+        .map(|key| (key.clone(), SettingLayer::Default))
+        .collect();
+    let mut rejected = Vec::new();
+
+    // Values come from the parsed file; the raw table says which keys it sets.
+    let file = sources.file.as_deref().and_then(|content| {
+        let parsed = toml::from_str::<SettingsFile>(content).ok()?;
+        let table = toml::from_str::<toml::Table>(content).ok()?;
+        Some((
+            serde_json::to_value(parsed.backend).ok()?,
+            serde_json::to_value(table.get("backend")?).ok()?,
+        ))
+    });
+    let cli = cli_overrides(&sources.args);
+    for key in &keys {
+        if let Some((parsed, raw)) = &file {
+            if let (Some(from_file), Some(_)) = (get_path(parsed, key), get_path(raw, key)) {
+                set_path(&mut value, key, from_file.clone());
+                layers.insert(key.clone(), SettingLayer::File);
+            }
+        }
+        let overrides = [
+            (
+                SettingLayer::Env,
+                env_var(key),
+                sources.env.get(&env_var(key)),
+            ),
+            (
+                SettingLayer::Cli,
+                format!("{}={}", SETTING_FLAG, key),
+                cli.get(key),
+            ),
+        ];
+        for (layer, name, raw) in overrides {
+            let Some(raw) = raw else { continue };
+            let accepted = parse_override(raw).into_iter().find(|candidate| {
+                let mut tried = value.clone();
+                set_path(&mut tried, key, candidate.clone());
+                serde_json::from_value::<Settings>(tried).is_ok()
+            });
+            match accepted {
+                Some(accepted) => {
+                    set_path(&mut value, key, accepted);
+                    layers.insert(key.clone(), layer);
+                }
+                None => rejected.push(format!("Ignoring {}: invalid value '{}'", name, raw)),
+            }
+        }
+    }
+    for name in cli.keys().filter(|key| !layers.contains_key(*key)) {
+        rejected.push(format!(
+            "Ignoring {}={}: unknown setting",
+            SETTING_FLAG, name
+        ));
+    }
+
+    let settings = serde_json::from_value(value.clone()).unwrap_or_default();
+    let sources = layers
+        .into_iter()
+        .map(|(key, layer)| {
+            let value = get_path(&value, &key).cloned().unwrap_or_default();
+            (key, SettingSource { layer, value })
+        })
+        .collect();
+    ResolvedSettings {
+        settings,
+        sources,
+        rejected,
+    }
+}
+
+/// The settings currently in effect.
+#[derive(Default)]
+pub struct SettingsState(RwLock<Settings>);
+
+impl SettingsState {
+    pub fn new(settings: Settings) -> Self {
+        Self(RwLock::new(settings))
+    }
+
+    pub fn get(&self) -> Settings {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Swaps in `settings`, returning the ones they replace.
+    pub fn replace(&self, settings: Settings) -> Settings {
+        std::mem::replace(
+            &mut self.0.write().unwrap_or_else(|e| e.into_inner()),
+            settings,
+        )
+    }
+}
+
+/// Dotted paths of the leaf settings that differ, e.g. `local_api.port`.
+pub fn changed_keys(old: &Settings, new: &Settings) -> Vec<String> {
+    fn walk(prefix: &str, old: &serde_json::Value, new: &serde_json::Value, out: &mut Vec<String>) {
+        match (old, new) {
+            (serde_json::Value::Object(old), serde_json::Value::Object(new)) => {
+                for (key, value) in new {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    walk(
+                        &path,
+                        old.get(key).unwrap_or(&serde_json::Value::Null),
+                        value,
+                        out,
+                    );
+                }
+            }
+            (old, new) if old != new => out.push(prefix.to_string()),
+            _ => {}
+        }
+    }
+    let mut keys = Vec::new();
+    walk(
+        "",
+        &serde_json::to_value(old).unwrap_or_default(),
+        &serde_json::to_value(new).unwrap_or_default(),
+        &mut keys,
+    );
+    keys
+}
+
+/// Whether `key` is `prefix` itself or a setting nested under it.
+fn covers(prefix: &str, key: &str) -> bool {
+    key.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+type Reaction = Arc<dyn Fn(&Settings, &Settings) + Send + Sync>;
+
+struct Registration {
+    module: &'static str,
+    keys: Vec<&'static str>,
+    on_settings_changed: Reaction,
+}
+
+/// Lets backend modules apply settings changes while the app runs. A changed
+/// setting that no module reacts to, and that isn't read fresh on each use,
+/// only takes effect after a restart.
+pub struct SettingsReactions {
+    registrations: Mutex<Vec<Registration>>,
+    live: Mutex<Vec<&'static str>>,
+    /// What the app started with, to tell which changes still need a restart.
+    startup: Settings,
+}
+
+impl SettingsReactions {
+    pub fn new(startup: Settings) -> Self {
+        Self {
+            registrations: Mutex::new(Vec::new()),
+            live: Mutex::new(Vec::new()),
+            startup,
+        }
+    }
+
+    /// Calls `on_settings_changed(old, new)` whenever a setting under one of
+    /// `keys` changes.
+    pub fn register(
+        &self,
+        module: &'static str,
+        keys: &[&'static str],
+        on_settings_changed: impl Fn(&Settings, &Settings) + Send + Sync + 'static,
+    ) {
+        self.registrations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Registration {
+                module,
+                keys: keys.to_vec(),
+                on_settings_changed: Arc::new(on_settings_changed),
+            });
+    }
+
+    /// Marks settings that are read from [`SettingsState`] on every use, so
+    /// changing them needs no reaction.
+    pub fn register_live(&self, keys: &[&'static str]) {
+        self.live
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend_from_slice(keys);
+    }
+
+    /// Runs the reactions for whatever changed between `old` and `new`,
+    /// returning the modules that were notified.
+    pub fn apply(&self, old: &Settings, new: &Settings) -> Vec<&'static str> {
+        let changed = changed_keys(old, new);
+        // Reactions run unlocked; restarting a listener can take a moment.
+        let due: Vec<_> = self
+            .registrations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|registration| {
+                changed
+                    .iter()
+                    .any(|key| registration.keys.iter().any(|prefix| covers(prefix, key)))
+            })
+            .map(|registration| {
+                (
+                    registration.module,
+                    registration.on_settings_changed.clone(),
+                )
+            })
+            .collect();
+        due.into_iter()
+            .map(|(module, on_settings_changed)| {
+                on_settings_changed(old, new);
+                module
+            })
+            .collect()
+    }
+
+    /// Settings changed since startup that nothing could apply live.
+    pub fn pending_restart_reasons(&self, current: &Settings) -> Vec<String> {
+        let live = self.live.lock().unwrap_or_else(|e| e.into_inner());
+        let registrations = self.registrations.lock().unwrap_or_else(|e| e.into_inner());
+        changed_keys(&self.startup, current)
+            .into_iter()
+            .filter(|key| {
+                !live.iter().any(|prefix| covers(prefix, key))
+                    && !registrations.iter().any(|registration| {
+                        registration.keys.iter().any(|prefix| covers(prefix, key))
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Re-reads `settings.toml` after the frontend saved it or saw it change on
+/// disk, applies what changed, and returns what still needs a restart.
+#[tauri::command]
+pub fn reload_settings(
+    app: AppHandle,
+    state: State<'_, SettingsState>,
+    reactions: State<'_, SettingsReactions>,
+) -> Result<Vec<String>, String> {
+    let path = config_dir(&app)
+        .map_err(|e| format!("Failed to locate settings: {}", e))?
+        .join(SETTINGS_FILE);
+    let new = Settings::load(&path);
+    let old = state.replace(new.clone());
+    reactions.apply(&old, &new);
+    Ok(reactions.pending_restart_reasons(&new))
+}
+
+/// Where each setting's current value comes from, by dotted key. Reflects
+/// the sources as they are now, which may be ahead of the settings in effect
+/// until `reload_settings` runs.
+#[tauri::command]
+pub fn explain_settings(app: AppHandle) -> Result<HashMap<String, SettingSource>, String> {
+    let path = config_dir(&app)
+        .map_err(|e| format!("Failed to locate settings: {}", e))?
+        .join(SETTINGS_FILE);
+    Ok(resolve(&SettingsSources::current(&path))
+        .sources
+        .into_iter()
+        .collect())
+}
+
+#[tauri::command]
+pub fn get_pending_restart_reasons(
+    state: State<'_, SettingsState>,
+    reactions: State<'_, SettingsReactions>,
+) -> Vec<String> {
+    reactions.pending_restart_reasons(&state.get())
+}
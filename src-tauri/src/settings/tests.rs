@@ -0,0 +1,203 @@
+use super::*;
+
+fn recording_reactions(startup: Settings) -> (SettingsReactions, Arc<Mutex<Vec<&'static str>>>) {
+    let reactions = SettingsReactions::new(startup);
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    for (module, keys) in [
+        ("local_api", &["local_api"][..]),
+        ("scheduler", &["scheduler.interval_secs"][..]),
+    ] {
+        let calls = calls.clone();
+        reactions.register(module, keys, move |_, _| calls.lock().unwrap().push(module));
+    }
+    reactions.register_live(&["block_spawn_during_focus"]);
+    (reactions, calls)
+}
+
+#[test]
+fn test_changed_keys_are_dotted_leaf_paths() {
+    let old = Settings::default();
+    let mut new = old.clone();
+    new.local_api.port = 9000;
+    new.scheduler.interval_secs = Some(30);
+    assert_eq!(
+        changed_keys(&old, &new),
+        vec!["local_api.port", "scheduler.interval_secs"]
+    );
+    assert!(changed_keys(&new, &new).is_empty());
+}
+
+#[test]
+fn test_only_reactions_for_changed_keys_run() {
+    let (reactions, calls) = recording_reactions(Settings::default());
+    let old = Settings::default();
+
+    let mut new = old.clone();
+    new.local_api.enabled = true;
+    assert_eq!(reactions.apply(&old, &new), vec!["local_api"]);
+
+    let mut new = old.clone();
+    new.scheduler.interval_secs = Some(120);
+    assert_eq!(reactions.apply(&old, &new), vec!["scheduler"]);
+
+    let mut new = old.clone();
+    new.block_spawn_during_focus = true;
+    assert!(reactions.apply(&old, &new).is_empty());
+    assert!(reactions.apply(&old, &old).is_empty());
+
+    let mut new = old.clone();
+    new.local_api.port = 1;
+    new.scheduler.interval_secs = Some(5);
+    assert_eq!(reactions.apply(&old, &new), vec!["local_api", "scheduler"]);
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec!["local_api", "scheduler", "local_api", "scheduler"]
+    );
+}
+
+#[test]
+fn test_unhandled_changes_need_restart_until_reverted() {
+    let startup = Settings::default();
+    let (reactions, _) = recording_reactions(startup.clone());
+
+    let mut current = startup.clone();
+    current.local_api.port = 9000;
+    current.block_spawn_during_focus = true;
+    assert!(reactions.pending_restart_reasons(&current).is_empty());
+
+    current.safety.require_typed_count_above = Some(10);
+    assert_eq!(
+        reactions.pending_restart_reasons(&current),
+        vec!["safety.require_typed_count_above"]
+    );
+
+    current.safety = startup.safety.clone();
+    assert!(reactions.pending_restart_reasons(&current).is_empty());
+}
+
+#[test]
+fn test_key_prefixes_match_whole_segments() {
+    assert!(covers("local_api", "local_api"));
+    assert!(covers("local_api", "local_api.port"));
+    assert!(!covers("local_api", "local_api_extra"));
+    assert!(!covers("local_api.port", "local_api"));
+}
+
+fn sources(file: &str, env: &[(&str, &str)], args: &[&str]) -> SettingsSources {
+    SettingsSources {
+        file: Some(file.to_string()),
+        env: env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+    }
+}
+
+#[test]
+fn test_cli_beats_env_beats_file_beats_default() {
+    let file = "[backend.local_api]\nenabled = true\nport = 1000\n\n[backend.scheduler]\ninterval_secs = 60\n";
+    let resolved = resolve(&sources(
+        file,
+        &[
+            ("YUTODO_LOCAL_API__PORT", "2000"),
+            ("YUTODO_SCHEDULER__INTERVAL_SECS", "90"),
+        ],
+        &["yutodo", "--setting=local_api.port=3000"],
+    ));
+
+    assert_eq!(resolved.settings.local_api.port, 3000);
+    assert!(resolved.settings.local_api.enabled);
+    assert_eq!(resolved.settings.scheduler.interval_secs, Some(90));
+    let layer = |key: &str| resolved.sources[key].layer;
+    assert_eq!(layer("local_api.port"), SettingLayer::Cli);
+    assert_eq!(layer("scheduler.interval_secs"), SettingLayer::Env);
+    assert_eq!(layer("local_api.enabled"), SettingLayer::File);
+    assert_eq!(layer("quota.hard_todos"), SettingLayer::Default);
+    assert_eq!(resolved.sources["local_api.port"].value, 3000);
+    assert!(resolved.rejected.is_empty());
+}
+
+#[test]
+fn test_invalid_overrides_fall_back_to_the_next_layer() {
+    let resolved = resolve(&sources(
+        "[backend.local_api]\nport = 1000\n",
+        &[("YUTODO_LOCAL_API__PORT", "not a port")],
+        &["--setting=local_api.port=99999", "--setting=nope=1"],
+    ));
+    assert_eq!(resolved.settings.local_api.port, 1000);
+    assert_eq!(resolved.sources["local_api.port"].layer, SettingLayer::File);
+    assert_eq!(
+        resolved.rejected,
+        vec![
+            "Ignoring YUTODO_LOCAL_API__PORT: invalid value 'not a port'",
+            "Ignoring --setting=local_api.port: invalid value '99999'",
+            "Ignoring --setting=nope: unknown setting",
+        ]
+    );
+}
+
+#[test]
+fn test_overrides_apply_over_an_unparsable_file_and_parse_as_text() {
+    let resolved = resolve(&sources(
+        "[backend.local_api\nport = oops",
+        &[("YUTODO_PRIVACY__TOGGLE_SHORTCUT", "CmdOrCtrl+Shift+P")],
+        &["--setting=enabled_plugins=[\"a\",\"b\"]"],
+    ));
+    assert_eq!(resolved.settings.local_api, LocalApiSettings::default());
+    assert_eq!(
+        resolved.settings.privacy.toggle_shortcut.as_deref(),
+        Some("CmdOrCtrl+Shift+P")
+    );
+    assert_eq!(resolved.settings.enabled_plugins, ["a", "b"]);
+    assert_eq!(env_var("local_api.port"), "YUTODO_LOCAL_API__PORT");
+}
+
+#[test]
+fn test_env_overrides_server_url_data_dir_and_log_level() {
+    let resolved = resolve(&sources(
+        "[backend]\nlog_level = \"warn\"\n",
+        &[
+            ("YUTODO_SERVER_URL", "https://todo.example.com"),
+            ("YUTODO_DATA_DIR", "/var/lib/yutodo"),
+            ("YUTODO_LOG_LEVEL", "debug"),
+        ],
+        &[],
+    ));
+    assert_eq!(
+        resolved.settings.server_url.as_deref(),
+        Some("https://todo.example.com")
+    );
+    assert_eq!(
+        resolved.settings.data_dir.as_deref(),
+        Some(Path::new("/var/lib/yutodo"))
+    );
+    assert_eq!(resolved.settings.log_level, LogLevel::Debug);
+    for key in ["server_url", "data_dir", "log_level"] {
+        assert_eq!(resolved.sources[key].layer, SettingLayer::Env);
+    }
+    assert!(resolved.rejected.is_empty());
+}
+
+#[test]
+fn test_invalid_env_values_fall_back_to_file_or_default() {
+    let resolved = resolve(&sources(
+        "[backend]\nlog_level = \"warn\"\n",
+        &[
+            ("YUTODO_SERVER_URL", "ftp://todo.example.com"),
+            ("YUTODO_DATA_DIR", "relative/dir"),
+            ("YUTODO_LOG_LEVEL", "verbose"),
+        ],
+        &[],
+    ));
+    assert_eq!(resolved.settings.server_url, None);
+    assert_eq!(resolved.settings.data_dir, None);
+    assert_eq!(resolved.settings.log_level, LogLevel::Warn);
+    assert_eq!(resolved.sources["log_level"].layer, SettingLayer::File);
+    assert_eq!(resolved.sources["data_dir"].layer, SettingLayer::Default);
+    assert_eq!(resolved.rejected.len(), 3);
+    assert!(resolved
+        .rejected
+        .contains(&"Ignoring YUTODO_LOG_LEVEL: invalid value 'verbose'".to_string()));
+}
@@ -0,0 +1,286 @@
+//! Orderly shutdown. Modules register hooks for a [`Stage`], and quitting
+//! runs them stage by stage, each on its own thread: drafts are written
+//! before commands are refused, running operations stop before the
+//! background loops and servers, and files are released last.
+//!
+//! A hook that outlives its grace period is left running while the next one
+//! starts. Once the deadline passes the remaining hooks are skipped, the
+//! unfinished ones are logged, and the app exits anyway; a stuck hook must
+//! not leave the app looking hung.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::command_registry::{Category, CommandDescriptor, CommandRegistry, EnabledWhen};
+use crate::events;
+use crate::import::ImportState;
+use crate::local_api::LocalApiState;
+use crate::protocol::EventPayload;
+use crate::scheduler::SchedulerState;
+use crate::storage::{self, Storage};
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// How long quitting may take before the app exits regardless.
+pub const DEADLINE: Duration = Duration::from_secs(10);
+/// How long running imports get to stop after their current row.
+const IMPORT_GRACE: Duration = Duration::from_secs(5);
+/// What refused commands fail with.
+pub const SHUTTING_DOWN_ERROR: &str = "The app is shutting down";
+
+/// When a hook runs; stages run in declaration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stage {
+    FlushDrafts,
+    StopCommands,
+    /// Cancel or await running operations such as imports.
+    Operations,
+    /// Scheduler loops and watchers.
+    Background,
+    Servers,
+    /// Temporary files, locks and the like.
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HookState {
+    Running,
+    Finished,
+    /// Still running when its grace period or the deadline ran out.
+    TimedOut,
+    /// Panicked.
+    Failed,
+    /// Not started because the deadline had passed.
+    Skipped,
+}
+
+/// Payload of `shutdown-progress`, so the UI can say what it waits for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShutdownProgress {
+    pub hook: &'static str,
+    pub stage: Stage,
+    pub state: HookState,
+}
+
+impl EventPayload for ShutdownProgress {}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub finished: Vec<&'static str>,
+    pub unfinished: Vec<&'static str>,
+}
+
+struct Hook {
+    name: &'static str,
+    stage: Stage,
+    grace: Option<Duration>,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+/// Managed state holding the registered hooks.
+#[derive(Default)]
+pub struct Shutdown {
+    hooks: Mutex<Vec<Hook>>,
+    started: AtomicBool,
+    refusing: AtomicBool,
+}
+
+impl Shutdown {
+    fn hooks(&self) -> MutexGuard<'_, Vec<Hook>> {
+        self.hooks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a hook for `stage`; hooks of one stage run in registration
+    /// order. With a `grace` period, the next hook starts once it runs out
+    /// even if this one hasn't finished.
+    pub fn register(
+        &self,
+        name: &'static str,
+        stage: Stage,
+        grace: Option<Duration>,
+        run: impl FnOnce() + Send + 'static,
+    ) {
+        self.hooks().push(Hook {
+            name,
+            stage,
+            grace,
+            run: Box::new(run),
+        });
+    }
+
+    pub fn started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    /// Makes the invoke handler refuse commands from now on.
+    pub fn refuse_commands(&self) {
+        self.refusing.store(true, Ordering::SeqCst);
+    }
+
+    pub fn refusing_commands(&self) -> bool {
+        self.refusing.load(Ordering::SeqCst)
+    }
+
+    /// Runs the hooks, giving up on them `deadline` after the start. Only
+    /// the first call runs anything; later ones return an empty report.
+    pub fn run(
+        &self,
+        deadline: Duration,
+        mut on_progress: impl FnMut(ShutdownProgress),
+    ) -> ShutdownReport {
+        let mut report = ShutdownReport::default();
+        if self.started.swap(true, Ordering::SeqCst) {
+            return report;
+        }
+        let mut hooks = std::mem::take(&mut *self.hooks());
+        hooks.sort_by_key(|hook| hook.stage);
+        // Real time, not the app's clock: this bounds how long quitting takes.
+        let end = Instant::now() + deadline;
+
+        for hook in hooks {
+            let mut progress = |state| {
+                on_progress(ShutdownProgress {
+                    hook: hook.name,
+                    stage: hook.stage,
+                    state,
+                })
+            };
+            let left = end.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                progress(HookState::Skipped);
+                report.unfinished.push(hook.name);
+                continue;
+            }
+            progress(HookState::Running);
+            let (done, finished) = mpsc::channel();
+            let run = hook.run;
+            std::thread::spawn(trace::bind(move || {
+                run();
+                let _ = done.send(());
+            }));
+            let wait = hook.grace.map_or(left, |grace| grace.min(left));
+            match finished.recv_timeout(wait) {
+                Ok(()) => {
+                    progress(HookState::Finished);
+                    report.finished.push(hook.name);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    progress(HookState::TimedOut);
+                    report.unfinished.push(hook.name);
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    progress(HookState::Failed);
+                    report.unfinished.push(hook.name);
+                }
+            }
+        }
+        report
+    }
+}
+
+/// Registers what the app itself does on the way out.
+pub fn register_app_hooks(app: &AppHandle) {
+    let shutdown = app.state::<Shutdown>();
+    let handle = app.clone();
+    shutdown.register("drafts", Stage::FlushDrafts, None, move || {
+        storage::drafts::flush_all(&handle);
+    });
+    let handle = app.clone();
+    shutdown.register("commands", Stage::StopCommands, None, move || {
+        handle.state::<Shutdown>().refuse_commands()
+    });
+    let handle = app.clone();
+    shutdown.register(
+        "imports",
+        Stage::Operations,
+        Some(IMPORT_GRACE),
+        move || handle.state::<ImportState>().cancel_all(),
+    );
+    let handle = app.clone();
+    shutdown.register("scheduler", Stage::Background, None, move || {
+        handle.state::<SchedulerState>().stop()
+    });
+    let handle = app.clone();
+    shutdown.register("local-api", Stage::Servers, None, move || {
+        handle.state::<LocalApiState>().stop()
+    });
+    let handle = app.clone();
+    shutdown.register("temporary-files", Stage::Release, None, move || {
+        handle.state::<Storage>().discard_temporary_files()
+    });
+}
+
+/// Runs the shutdown hooks, emitting `shutdown-progress` as they go and
+/// logging the ones that didn't finish. Does nothing if shutdown already ran.
+pub fn run(app: &AppHandle) {
+    let report = app.state::<Shutdown>().run(DEADLINE, |progress| {
+        let _ = events::emit(app, "shutdown-progress", &progress);
+    });
+    if !report.unfinished.is_empty() {
+        trace::warn(format!(
+            "Exiting with unfinished shutdown hooks: {}",
+            report.unfinished.join(", ")
+        ));
+    }
+}
+
+/// Shuts down in the background, then exits.
+pub fn quit(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(trace::bind(move || {
+        run(&app);
+        app.exit(0);
+    }));
+}
+
+/// Wraps the invoke handler so commands are refused once shutdown has
+/// stopped accepting them.
+pub fn guard<R: Runtime>(
+    handler: impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let refusing = invoke
+            .message
+            .webview()
+            .try_state::<Shutdown>()
+            .is_some_and(|shutdown| shutdown.refusing_commands());
+        if refusing {
+            invoke.resolver.reject(SHUTTING_DOWN_ERROR);
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+pub fn register_commands(registry: &CommandRegistry) {
+    registry.register(
+        CommandDescriptor {
+            id: "quit",
+            title_key: "commandPalette.commands.file.quit.title",
+            default_title: "Quit",
+            category: Category::File,
+            default_binding: None,
+            enabled_when: EnabledWhen::Always,
+            command: Some("quit_app"),
+        },
+        |app, _| {
+            quit(app);
+            Ok(serde_json::Value::Null)
+        },
+    );
+}
+
+/// Quits the app through the shutdown hooks instead of exiting at once.
+#[tauri::command]
+pub fn quit_app(app: AppHandle) {
+    quit(&app);
+}
@@ -0,0 +1,91 @@
+use super::*;
+
+use std::sync::Arc;
+
+const SHORT: Duration = Duration::from_millis(100);
+
+fn log_to(log: &Arc<Mutex<Vec<&'static str>>>, name: &'static str) -> impl FnOnce() + Send {
+    let log = log.clone();
+    move || log.lock().unwrap().push(name)
+}
+
+#[test]
+fn test_hooks_run_by_stage_then_registration_order() {
+    let shutdown = Shutdown::default();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    shutdown.register("server", Stage::Servers, None, log_to(&log, "server"));
+    shutdown.register("drafts", Stage::FlushDrafts, None, log_to(&log, "drafts"));
+    shutdown.register("imports", Stage::Operations, None, log_to(&log, "imports"));
+    shutdown.register("backup", Stage::Operations, None, log_to(&log, "backup"));
+    shutdown.register("panics", Stage::Release, None, || panic!("hook failed"));
+
+    let mut states = Vec::new();
+    let report = shutdown.run(DEADLINE, |progress| {
+        if progress.state != HookState::Running {
+            states.push((progress.hook, progress.state));
+        }
+    });
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec!["drafts", "imports", "backup", "server"]
+    );
+    assert_eq!(
+        report.finished,
+        vec!["drafts", "imports", "backup", "server"]
+    );
+    assert_eq!(report.unfinished, vec!["panics"]);
+    assert_eq!(states.last(), Some(&("panics", HookState::Failed)));
+
+    assert!(shutdown.started());
+    assert_eq!(
+        shutdown.run(DEADLINE, |_| panic!("ran twice")),
+        ShutdownReport::default()
+    );
+}
+
+#[test]
+fn test_hung_hooks_give_way_after_grace_and_deadline() {
+    let shutdown = Shutdown::default();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (_release, hang) = mpsc::channel::<()>();
+    shutdown.register("slow import", Stage::Operations, Some(SHORT), || {
+        std::thread::sleep(Duration::from_secs(60))
+    });
+    shutdown.register(
+        "scheduler",
+        Stage::Background,
+        None,
+        log_to(&log, "scheduler"),
+    );
+    shutdown.register("stuck server", Stage::Servers, None, move || {
+        let _ = hang.recv();
+    });
+    shutdown.register(
+        "temp files",
+        Stage::Release,
+        None,
+        log_to(&log, "temp files"),
+    );
+
+    let started = Instant::now();
+    let mut states = Vec::new();
+    let report = shutdown.run(SHORT * 3, |progress| {
+        states.push((progress.hook, progress.state))
+    });
+    let took = started.elapsed();
+
+    assert!(
+        took >= SHORT * 3 && took < Duration::from_secs(5),
+        "{:?}",
+        took
+    );
+    assert_eq!(*log.lock().unwrap(), vec!["scheduler"]);
+    assert_eq!(report.finished, vec!["scheduler"]);
+    assert_eq!(
+        report.unfinished,
+        vec!["slow import", "stuck server", "temp files"]
+    );
+    assert!(states.contains(&("slow import", HookState::TimedOut)));
+    assert!(states.contains(&("stuck server", HookState::TimedOut)));
+    assert_eq!(states.last(), Some(&("temp files", HookState::Skipped)));
+}
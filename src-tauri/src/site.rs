@@ -0,0 +1,319 @@
+//! Read-only copies of the list as a static site: an `index.html` that
+//! reloads `data.json` on an interval, for any web server to serve to people
+//! without the app. Only what the page shows is written; ids, custom fields
+//! and settings stay behind.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::export_schedule::{self, ExportSchedule, ExportScheduleConfig};
+use crate::file_access::{self, PathError};
+use crate::query;
+use crate::retry;
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::{Priority, Todo};
+
+#[cfg(test)]
+mod tests;
+
+pub const INDEX_FILE: &str = "index.html";
+pub const DATA_FILE: &str = "data.json";
+const MIN_REFRESH_SECS: u64 = 5;
+
+const INDEX_TEMPLATE: &str = include_str!("site/index.html");
+
+pub(crate) fn default_title() -> String {
+    "Todos".to_string()
+}
+
+pub(crate) fn default_refresh_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfig {
+    pub output_dir: PathBuf,
+    #[serde(default = "default_title")]
+    pub title: String,
+    /// Smart lists to publish, one section each. Empty publishes every todo.
+    #[serde(default)]
+    pub smart_lists: Vec<String>,
+    /// How often an open page reloads `data.json`.
+    #[serde(default = "default_refresh_secs")]
+    pub refresh_secs: u64,
+}
+
+/// What a viewer sees of a todo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteTodo {
+    pub title: String,
+    pub description: Option<String>,
+    pub completed: bool,
+    pub priority: Priority,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+}
+
+impl From<&Todo> for SiteTodo {
+    fn from(todo: &Todo) -> Self {
+        Self {
+            title: todo.title.clone(),
+            description: todo.description.clone(),
+            completed: todo.completed,
+            priority: todo.priority,
+            scheduled_for: todo.scheduled_for,
+            tags: todo.tags.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteSection {
+    pub name: String,
+    pub todos: Vec<SiteTodo>,
+}
+
+/// Contents of `data.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteData {
+    pub title: String,
+    pub generated_at: DateTime<Utc>,
+    pub sections: Vec<SiteSection>,
+}
+
+pub fn site_data(
+    storage: &Storage,
+    config: &SiteConfig,
+    now: DateTime<Utc>,
+    today: NaiveDate,
+) -> Result<SiteData, String> {
+    let sections = if config.smart_lists.is_empty() {
+        let todos = storage
+            .list_todos()
+            .map_err(|e| format!("Failed to load todos: {}", e))?;
+        vec![SiteSection {
+            name: config.title.clone(),
+            todos: todos.iter().map(SiteTodo::from).collect(),
+        }]
+    } else {
+        let lists =
+            query::list_lists(storage).map_err(|e| format!("Failed to load smart lists: {}", e))?;
+        config
+            .smart_lists
+            .iter()
+            .map(|id| {
+                let list = lists
+                    .iter()
+                    .find(|list| &list.id == id)
+                    .ok_or_else(|| format!("Unknown smart list '{}'", id))?;
+                let todos = query::smart_list_todos(storage, id, today)?;
+                Ok(SiteSection {
+                    name: list.name.clone(),
+                    todos: todos.iter().map(SiteTodo::from).collect(),
+                })
+            })
+            .collect::<Result<_, String>>()?
+    };
+    Ok(SiteData {
+        title: config.title.clone(),
+        generated_at: now,
+        sections,
+    })
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The page itself. It holds no todos, so it only changes with the config.
+pub fn render_index(config: &SiteConfig) -> String {
+    INDEX_TEMPLATE
+        .replace("{{title}}", &escape_html(&config.title))
+        .replace(
+            "{{refresh_ms}}",
+            &(config.refresh_secs.max(MIN_REFRESH_SECS) * 1000).to_string(),
+        )
+        .replace("{{data_file}}", DATA_FILE)
+}
+
+/// Refuses relative paths and anything inside the app's data directory.
+pub fn check_output_dir(dir: &Path, data_dir: &Path) -> Result<(), String> {
+    if !dir.is_absolute() {
+        return Err(format!("{} is not an absolute path", dir.display()));
+    }
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if canonical(dir).starts_with(canonical(data_dir)) {
+        return Err(format!(
+            "{} is inside the app's data directory",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Replaces `dir/name` in one step, so a viewer reading it mid-refresh gets
+/// either the old file or the new one.
+fn write_atomic(dir: &Path, name: &str, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(contents)?;
+    file.as_file().sync_all()?;
+    file.persist(dir.join(name)).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Writes the site into `config.output_dir`, creating it if needed, and
+/// returns how many todos it shows.
+pub fn publish(storage: &Storage, config: &SiteConfig, data_dir: &Path) -> Result<usize, String> {
+    let dir = &config.output_dir;
+    check_output_dir(dir, data_dir)?;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let now = storage.clock().now();
+    let data = site_data(storage, config, now, now.date_naive())?;
+    let json = serde_json::to_vec_pretty(&data)
+        .map_err(|e| format!("Failed to write {}: {}", DATA_FILE, e))?;
+    write_atomic(dir, DATA_FILE, &json)
+        .map_err(|e| format!("Failed to write {}: {}", DATA_FILE, e))?;
+    write_atomic(dir, INDEX_FILE, render_index(config).as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", INDEX_FILE, e))?;
+    Ok(data
+        .sections
+        .iter()
+        .map(|section| section.todos.len())
+        .sum())
+}
+
+/// The export schedules that publish a site, the oldest first.
+pub fn list_schedules(storage: &Storage) -> rusqlite::Result<Vec<ExportSchedule>> {
+    Ok(export_schedule::list_schedules(&storage.conn())?
+        .into_iter()
+        .filter(|schedule| schedule.config.site().is_some())
+        .collect())
+}
+
+/// Removes the schedules publishing into `output_dir`; returns how many
+/// there were.
+fn remove_schedules(storage: &Storage, output_dir: &Path) -> Result<usize, String> {
+    let schedules =
+        list_schedules(storage).map_err(|e| format!("Failed to load static sites: {}", e))?;
+    let mut removed = 0;
+    for schedule in schedules {
+        if schedule.config.destination == output_dir {
+            export_schedule::remove_schedule(&storage.conn(), &schedule.id)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// [`publish`] after checking that the app may write to the output directory.
+pub fn publish_checked(
+    app: &AppHandle,
+    storage: &Storage,
+    config: &SiteConfig,
+) -> Result<(), PathError> {
+    file_access::check(app, &config.output_dir)?;
+    let data_dir = storage::data_dir(app).map_err(|e| e.to_string())?;
+    publish(storage, config, &data_dir).map_err(|e| {
+        format!(
+            "Failed to publish to {}: {}",
+            config.output_dir.display(),
+            e
+        )
+    })?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn publish_static_site(
+    config: SiteConfig,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<(), PathError> {
+    publish_checked(&app, &storage, &config)
+        .inspect_err(|e| retry::record(&app, retry::Operation::PublishStaticSite { config }, e))
+}
+
+/// The `index.html` that publishing `config` would write.
+#[tauri::command]
+pub fn preview_static_site(config: SiteConfig) -> String {
+    render_index(&config)
+}
+
+/// Publishes `config` now and then every `interval_minutes`, as an export
+/// schedule with its history and retries, replacing the schedule already
+/// publishing into the same folder.
+#[tauri::command]
+pub fn schedule_static_site(
+    config: SiteConfig,
+    interval_minutes: u32,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<ExportSchedule, PathError> {
+    file_access::check(&app, &config.output_dir)?;
+    let data_dir = storage::data_dir(&app).map_err(|e| e.to_string())?;
+    check_output_dir(&config.output_dir, &data_dir)
+        .map_err(|e| format!("Failed to schedule static site: {}", e))?;
+    remove_schedules(&storage, &config.output_dir)?;
+    let output_dir = config.output_dir.clone();
+    let schedule = export_schedule::add_schedule(
+        &storage.conn(),
+        ExportScheduleConfig::static_site(config, interval_minutes),
+        storage.clock().now(),
+        &Local,
+    )
+    .map_err(|e| format!("Failed to schedule static site: {}", e))?;
+    // A failed first run is announced and retried like any other.
+    if let Err(e) = export_schedule::run_now(&app, &schedule.id) {
+        trace::log(format!("Failed to publish {}: {}", output_dir.display(), e));
+    }
+    Ok(
+        export_schedule::find_schedule(&storage.conn(), &schedule.id)
+            .ok()
+            .flatten()
+            .unwrap_or(schedule),
+    )
+}
+
+/// Stops republishing into `output_dir`. Files already written stay.
+#[tauri::command]
+pub fn unschedule_static_site(
+    output_dir: PathBuf,
+    storage: State<'_, Storage>,
+) -> Result<(), String> {
+    match remove_schedules(&storage, &output_dir)? {
+        0 => Err(format!(
+            "Failed to unschedule: no static site in {}",
+            output_dir.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The schedules publishing a site; their runs are in
+/// `get_export_schedule_history`.
+#[tauri::command]
+pub fn list_static_sites(storage: State<'_, Storage>) -> Result<Vec<ExportSchedule>, String> {
+    list_schedules(&storage).map_err(|e| format!("Failed to list static sites: {}", e))
+}
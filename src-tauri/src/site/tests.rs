@@ -0,0 +1,176 @@
+use super::*;
+
+use chrono::TimeZone;
+
+use crate::types::FieldValue;
+
+fn todo(id: &str, title: &str, tags: &[&str]) -> Todo {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo {
+        schedule_id: Some("schedule-secret".to_string()),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Todo::sample(id, title, created)
+    }
+}
+
+fn storage() -> Storage {
+    let storage = Storage::open_in_memory().unwrap();
+    let mut dishes = todo("todo-id-1", "Do the dishes", &["chores"]);
+    dishes.fields.insert(
+        "budget".to_string(),
+        FieldValue::Text("private".to_string()),
+    );
+    storage
+        .replace_todos(&[dishes, todo("todo-id-2", "File taxes", &["admin"])])
+        .unwrap();
+    storage
+}
+
+fn config(dir: &Path) -> SiteConfig {
+    SiteConfig {
+        output_dir: dir.to_path_buf(),
+        title: "Family chores".to_string(),
+        smart_lists: Vec::new(),
+        refresh_secs: 30,
+    }
+}
+
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 2).unwrap()
+}
+
+#[test]
+fn test_site_data_leaves_out_ids_and_fields() {
+    let storage = storage();
+    let data = site_data(&storage, &config(Path::new("/srv")), Utc::now(), today()).unwrap();
+    assert_eq!(data.sections.len(), 1);
+    assert_eq!(data.sections[0].todos.len(), 2);
+
+    let json = serde_json::to_string(&data).unwrap();
+    assert!(json.contains("Do the dishes"));
+    for hidden in ["todo-id-1", "schedule-secret", "budget", "private"] {
+        assert!(!json.contains(hidden), "{} leaked", hidden);
+    }
+}
+
+#[test]
+fn test_site_data_splits_per_smart_list() {
+    let storage = storage();
+    let chores = query::create_list(&storage, "Chores", "tag:chores").unwrap();
+    let admin = query::create_list(&storage, "Admin", "tag:admin").unwrap();
+    let config = SiteConfig {
+        smart_lists: vec![admin.id, chores.id],
+        ..config(Path::new("/srv"))
+    };
+
+    let data = site_data(&storage, &config, Utc::now(), today()).unwrap();
+    let sections: Vec<(&str, Vec<&str>)> = data
+        .sections
+        .iter()
+        .map(|s| {
+            let titles = s.todos.iter().map(|t| t.title.as_str()).collect();
+            (s.name.as_str(), titles)
+        })
+        .collect();
+    assert_eq!(
+        sections,
+        vec![
+            ("Admin", vec!["File taxes"]),
+            ("Chores", vec!["Do the dishes"])
+        ]
+    );
+
+    let unknown = SiteConfig {
+        smart_lists: vec!["missing".to_string()],
+        ..config
+    };
+    assert!(site_data(&storage, &unknown, Utc::now(), today()).is_err());
+}
+
+#[test]
+fn test_index_escapes_title_and_clamps_refresh() {
+    let config = SiteConfig {
+        title: "<Chores & co>".to_string(),
+        refresh_secs: 0,
+        ..config(Path::new("/srv"))
+    };
+    let html = render_index(&config);
+    assert!(html.contains("<title>&lt;Chores &amp; co&gt;</title>"));
+    assert!(html.contains("const REFRESH_MS = 5000;"));
+    assert!(!html.contains("{{"));
+}
+
+#[test]
+fn test_publish_writes_only_the_site_files() {
+    let storage = storage();
+    let data_dir = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let config = config(&out.path().join("chores"));
+
+    publish(&storage, &config, data_dir.path()).unwrap();
+    publish(&storage, &config, data_dir.path()).unwrap();
+
+    let mut files: Vec<String> = std::fs::read_dir(&config.output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    assert_eq!(files, vec![DATA_FILE, INDEX_FILE]);
+    let data: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(config.output_dir.join(DATA_FILE)).unwrap()).unwrap();
+    assert_eq!(data["title"], "Family chores");
+}
+
+#[test]
+fn test_output_dir_must_be_absolute_and_outside_data_dir() {
+    let data_dir = tempfile::tempdir().unwrap();
+    assert!(check_output_dir(Path::new("site"), data_dir.path()).is_err());
+    assert!(check_output_dir(&data_dir.path().join("site"), data_dir.path()).is_err());
+
+    let out = tempfile::tempdir().unwrap();
+    assert_eq!(check_output_dir(out.path(), data_dir.path()), Ok(()));
+}
+
+#[test]
+fn test_scheduled_site_runs_as_an_export_schedule() {
+    let storage = storage();
+    let data_dir = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let config = config(&out.path().join("chores"));
+    let created = Utc.with_ymd_and_hms(2024, 6, 3, 12, 0, 0).unwrap();
+    let schedule = export_schedule::add_schedule(
+        &storage.conn(),
+        ExportScheduleConfig::static_site(config.clone(), 15),
+        created,
+        &Utc,
+    )
+    .unwrap();
+    assert_eq!(schedule.config.site(), Some(config.clone()));
+    assert_eq!(list_schedules(&storage).unwrap(), vec![schedule.clone()]);
+
+    let due = schedule.next_run.unwrap();
+    assert_eq!(due, created + chrono::Duration::minutes(15));
+    let ran = export_schedule::run_due(&storage, due, &Utc, |config, _| {
+        publish(&storage, &config.site().unwrap(), data_dir.path())
+    })
+    .unwrap();
+    let [(run, None)] = ran.as_slice() else {
+        panic!("expected one successful run, got {:?}", ran);
+    };
+    assert_eq!((&run.path, run.exported), (&config.output_dir, Some(2)));
+    assert!(config.output_dir.join(INDEX_FILE).exists());
+    let schedule = export_schedule::find_schedule(&storage.conn(), &schedule.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(schedule.next_run, Some(due + chrono::Duration::minutes(15)));
+
+    assert!(export_schedule::add_schedule(
+        &storage.conn(),
+        ExportScheduleConfig::static_site(config.clone(), 0),
+        created,
+        &Utc,
+    )
+    .is_err());
+    assert_eq!(remove_schedules(&storage, &config.output_dir), Ok(1));
+    assert!(list_schedules(&storage).unwrap().is_empty());
+}
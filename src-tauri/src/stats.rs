@@ -0,0 +1,304 @@
+//! How estimates compare with the effort todos actually took, so estimating
+//! can be calibrated over time, how todos spread over tags, and the weekly
+//! review digest. A ratio above 1 means it took longer than estimated.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Days, FixedOffset, Local, NaiveDate, TimeZone, Utc, Weekday};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+
+use crate::cache::{CacheKind, Caches};
+use crate::focus::{self, FocusReport};
+use crate::privacy::PrivacyState;
+use crate::storage::Storage;
+use crate::types::{DateRange, Todo};
+
+#[cfg(test)]
+mod tests;
+
+/// Tags listed in a weekly review.
+const TOP_TAGS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TagEffort {
+    pub tag: String,
+    pub todo_count: usize,
+    pub mean_ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EffortReport {
+    /// Todos that had both an estimate and an actual.
+    pub todo_count: usize,
+    /// Mean of actual / estimate, or `None` without any such todo.
+    pub mean_ratio: Option<f64>,
+    /// Ordered by tag.
+    pub per_tag: Vec<TagEffort>,
+}
+
+/// Actual over estimated minutes, if `todo` has both and a nonzero estimate.
+pub fn effort_ratio(todo: &Todo) -> Option<f64> {
+    match (todo.estimate_minutes, todo.actual_minutes) {
+        (Some(estimate), Some(actual)) if estimate > 0 => Some(actual as f64 / estimate as f64),
+        _ => None,
+    }
+}
+
+fn mean(ratios: &[f64]) -> Option<f64> {
+    (!ratios.is_empty()).then(|| ratios.iter().sum::<f64>() / ratios.len() as f64)
+}
+
+/// Compares effort for the todos completed in `range`.
+pub fn effort_report(todos: &[Todo], range: &DateRange) -> EffortReport {
+    let mut all = Vec::new();
+    let mut by_tag: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    let within = |at: DateTime<Utc>| at >= range.start && at < range.end;
+    for todo in todos {
+        if !todo.completed_at.is_some_and(within) {
+            continue;
+        }
+        let Some(ratio) = effort_ratio(todo) else {
+            continue;
+        };
+        all.push(ratio);
+        for tag in &todo.tags {
+            by_tag.entry(tag).or_default().push(ratio);
+        }
+    }
+    EffortReport {
+        todo_count: all.len(),
+        mean_ratio: mean(&all),
+        per_tag: by_tag
+            .into_iter()
+            .filter_map(|(tag, ratios)| {
+                Some(TagEffort {
+                    tag: tag.to_string(),
+                    todo_count: ratios.len(),
+                    mean_ratio: mean(&ratios)?,
+                })
+            })
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub todo_count: usize,
+}
+
+/// Each tag with the number of todos carrying it, most used first.
+pub fn tag_counts(todos: &[Todo]) -> Vec<TagCount> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for todo in todos {
+        for tag in &todo.tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<TagCount> = counts
+        .into_iter()
+        .map(|(tag, todo_count)| TagCount {
+            tag: tag.to_string(),
+            todo_count,
+        })
+        .collect();
+    counts.sort_by(|a, b| b.todo_count.cmp(&a.todo_count));
+    counts
+}
+
+fn load_todos(storage: &Storage) -> Result<Vec<Todo>, String> {
+    storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))
+}
+
+#[tauri::command]
+pub fn effort_accuracy_report(
+    range: DateRange,
+    storage: State<'_, Storage>,
+    caches: State<'_, Caches>,
+) -> Result<EffortReport, String> {
+    let signature = format!("{}/{}", range.start.to_rfc3339(), range.end.to_rfc3339());
+    caches.get_or_compute(&storage, CacheKind::EffortReport, signature, || {
+        Ok(effort_report(&load_todos(&storage)?, &range))
+    })
+}
+
+#[tauri::command]
+pub fn list_tags(
+    storage: State<'_, Storage>,
+    caches: State<'_, Caches>,
+) -> Result<Vec<TagCount>, String> {
+    caches.get_or_compute(&storage, CacheKind::TagCounts, String::new(), || {
+        Ok(tag_counts(&load_todos(&storage)?))
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyReview {
+    /// First day of the week, in the timezone it was reviewed in.
+    pub starts_on: NaiveDate,
+    pub range: DateRange,
+    /// Completed during the week.
+    pub completed: usize,
+    /// Still open and due before the week ended.
+    pub overdue_carry_over: usize,
+    /// The todos behind `completed`, in list order.
+    pub completed_todos: Vec<ReviewItem>,
+    /// The todos behind `overdue_carry_over`, soonest due first.
+    pub carried_over: Vec<ReviewItem>,
+    pub created: usize,
+    pub focus_secs: i64,
+    pub focus_sessions: usize,
+    /// Most used tags of the todos completed during the week.
+    pub top_tags: Vec<TagCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewItem {
+    pub id: String,
+    pub title: String,
+    pub scheduled_for: Option<DateTime<Utc>>,
+}
+
+impl From<&Todo> for ReviewItem {
+    fn from(todo: &Todo) -> Self {
+        Self {
+            id: todo.id.clone(),
+            title: todo.title.clone(),
+            scheduled_for: todo.scheduled_for,
+        }
+    }
+}
+
+/// Local midnight starting `date` in `tz`, or the first instant after it
+/// where a daylight saving change skips midnight.
+fn start_of_day<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    (0..=2)
+        .find_map(|hours| {
+            tz.from_local_datetime(&(midnight + chrono::Duration::hours(hours)))
+                .earliest()
+        })
+        .map_or_else(
+            || Utc.from_utc_datetime(&midnight),
+            |start| start.with_timezone(&Utc),
+        )
+}
+
+/// The first day of the week containing `week_of`.
+pub fn week_start(week_of: NaiveDate, first_day: Weekday) -> NaiveDate {
+    let offset =
+        (7 + week_of.weekday().num_days_from_monday() - first_day.num_days_from_monday()) % 7;
+    week_of - Days::new(offset.into())
+}
+
+/// The week containing `week_of`, starting on `first_day` at midnight in `tz`.
+pub fn week_range<Tz: TimeZone>(week_of: NaiveDate, first_day: Weekday, tz: &Tz) -> DateRange {
+    let start = week_start(week_of, first_day);
+    DateRange {
+        start: start_of_day(start, tz),
+        end: start_of_day(start + Days::new(7), tz),
+    }
+}
+
+/// Assembles the review of the week starting on `starts_on` and spanning
+/// `range` from the todos and its focus report.
+pub fn assemble_review(
+    todos: &[Todo],
+    focus: &FocusReport,
+    starts_on: NaiveDate,
+    range: DateRange,
+) -> WeeklyReview {
+    let within = |at: DateTime<Utc>| at >= range.start && at < range.end;
+    let completed: Vec<Todo> = todos
+        .iter()
+        .filter(|todo| todo.completed_at.is_some_and(within))
+        .cloned()
+        .collect();
+    let mut carried_over: Vec<&Todo> = todos
+        .iter()
+        .filter(|todo| !todo.completed && todo.scheduled_for.is_some_and(|due| due < range.end))
+        .collect();
+    carried_over.sort_by_key(|todo| todo.scheduled_for);
+    let mut top_tags = tag_counts(&completed);
+    top_tags.truncate(TOP_TAGS);
+    WeeklyReview {
+        starts_on,
+        range,
+        completed: completed.len(),
+        overdue_carry_over: carried_over.len(),
+        completed_todos: completed.iter().map(ReviewItem::from).collect(),
+        carried_over: carried_over.into_iter().map(ReviewItem::from).collect(),
+        created: todos.iter().filter(|todo| within(todo.created_at)).count(),
+        focus_secs: focus.total_secs,
+        focus_sessions: focus.session_count,
+        top_tags,
+    }
+}
+
+/// The review of the week containing `week_of`, from `todos` and the
+/// recorded focus sessions.
+pub fn review_week<Tz: TimeZone>(
+    storage: &Storage,
+    todos: &[Todo],
+    week_of: NaiveDate,
+    first_day: Weekday,
+    tz: &Tz,
+) -> Result<WeeklyReview, String> {
+    let range = week_range(week_of, first_day, tz);
+    let sessions = focus::sessions_in_range(storage, &range)
+        .map_err(|e| format!("Failed to load focus sessions: {}", e))?;
+    Ok(assemble_review(
+        todos,
+        &focus::summarize(&sessions),
+        week_start(week_of, first_day),
+        range,
+    ))
+}
+
+/// [`review_week`] of the cached todos privacy mode shows. Weeks start on
+/// Monday in the local timezone unless `week_starts_on` or
+/// `utc_offset_minutes` say otherwise.
+pub fn review_visible_week(
+    storage: &Storage,
+    privacy: &PrivacyState,
+    week_of: NaiveDate,
+    week_starts_on: Option<Weekday>,
+    utc_offset_minutes: Option<i32>,
+) -> Result<WeeklyReview, String> {
+    let todos = privacy.get().apply(load_todos(storage)?).value;
+    let first_day = week_starts_on.unwrap_or(Weekday::Mon);
+    match utc_offset_minutes {
+        Some(minutes) => {
+            let offset = FixedOffset::east_opt(minutes * 60)
+                .ok_or_else(|| format!("Invalid UTC offset: {} minutes", minutes))?;
+            review_week(storage, &todos, week_of, first_day, &offset)
+        }
+        None => review_week(storage, &todos, week_of, first_day, &Local),
+    }
+}
+
+#[tauri::command]
+pub fn weekly_review(
+    week_of: NaiveDate,
+    week_starts_on: Option<Weekday>,
+    utc_offset_minutes: Option<i32>,
+    storage: State<'_, Storage>,
+    privacy: State<'_, PrivacyState>,
+) -> Result<WeeklyReview, String> {
+    review_visible_week(
+        &storage,
+        &privacy,
+        week_of,
+        week_starts_on,
+        utc_offset_minutes,
+    )
+}
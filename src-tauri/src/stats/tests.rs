@@ -0,0 +1,211 @@
+use super::*;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+fn at(day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap()
+}
+
+fn todo(
+    id: &str,
+    tags: &[&str],
+    estimate_minutes: Option<u32>,
+    actual_minutes: Option<u32>,
+) -> Todo {
+    Todo {
+        completed: true,
+        updated_at: at(2),
+        completed_at: Some(at(2)),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        estimate_minutes,
+        actual_minutes,
+        ..Todo::sample(id, id, at(1))
+    }
+}
+
+fn may() -> DateRange {
+    DateRange {
+        start: at(1),
+        end: at(31),
+    }
+}
+
+#[test]
+fn test_report_averages_ratios_overall_and_per_tag() {
+    let todos = [
+        todo("a", &["work"], Some(30), Some(60)),
+        todo("b", &["work", "home"], Some(60), Some(30)),
+        todo("c", &["home"], Some(20), Some(20)),
+    ];
+
+    let report = effort_report(&todos, &may());
+    assert_eq!(report.todo_count, 3);
+    assert_eq!(report.mean_ratio, Some((2.0 + 0.5 + 1.0) / 3.0));
+    assert_eq!(
+        report.per_tag,
+        vec![
+            TagEffort {
+                tag: "home".to_string(),
+                todo_count: 2,
+                mean_ratio: 0.75,
+            },
+            TagEffort {
+                tag: "work".to_string(),
+                todo_count: 2,
+                mean_ratio: 1.25,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_report_ignores_incomplete_data() {
+    let open = Todo {
+        completed: false,
+        completed_at: None,
+        ..todo("open", &["work"], Some(10), Some(50))
+    };
+    // Completed in April, edited since.
+    let last_month = Todo {
+        completed_at: Some(Utc.with_ymd_and_hms(2024, 4, 30, 12, 0, 0).unwrap()),
+        ..todo("april", &["work"], Some(10), Some(50))
+    };
+    let todos = [
+        todo("estimate only", &["work"], Some(30), None),
+        todo("actual only", &["work"], None, Some(30)),
+        todo("zero estimate", &["work"], Some(0), Some(30)),
+        open,
+        last_month,
+        todo("counted", &[], Some(40), Some(10)),
+    ];
+
+    let report = effort_report(&todos, &may());
+    assert_eq!(report.todo_count, 1);
+    assert_eq!(report.mean_ratio, Some(0.25));
+    assert!(report.per_tag.is_empty());
+
+    assert_eq!(effort_report(&[], &may()), EffortReport::default());
+}
+
+fn day(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+}
+
+#[test]
+fn test_week_range_starts_on_the_given_day_in_the_given_timezone() {
+    let week = week_range(day(31), Weekday::Mon, &Utc);
+    assert_eq!(
+        week.start,
+        Utc.with_ymd_and_hms(2024, 5, 27, 0, 0, 0).unwrap()
+    );
+    assert_eq!(week.end, Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap());
+    assert_eq!(week_range(day(27), Weekday::Mon, &Utc), week);
+    assert_eq!(
+        week_range(
+            NaiveDate::from_ymd_opt(2024, 6, 2).unwrap(),
+            Weekday::Mon,
+            &Utc
+        ),
+        week
+    );
+
+    let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+    let week = week_range(day(31), Weekday::Sun, &tokyo);
+    assert_eq!(
+        week.start,
+        Utc.with_ymd_and_hms(2024, 5, 25, 15, 0, 0).unwrap()
+    );
+    assert_eq!(
+        week.end,
+        Utc.with_ymd_and_hms(2024, 6, 1, 15, 0, 0).unwrap()
+    );
+}
+
+#[test]
+fn test_review_counts_the_week_from_stubbed_todos_and_focus() {
+    let range = week_range(day(15), Weekday::Mon, &Utc);
+    let created_this_week = Todo {
+        created_at: at(14),
+        updated_at: at(15),
+        completed_at: Some(at(15)),
+        ..todo("new", &["home"], None, None)
+    };
+    let overdue = Todo {
+        completed: false,
+        scheduled_for: Some(at(10)),
+        ..todo("overdue", &["work"], None, None)
+    };
+    let due_next_week = Todo {
+        completed: false,
+        scheduled_for: Some(at(21)),
+        ..todo("later", &["work"], None, None)
+    };
+    // Edited this week, after it was done.
+    let completed_last_week = Todo {
+        completed_at: Some(at(12)),
+        updated_at: at(14),
+        ..todo("old", &["work"], None, None)
+    };
+    let todos = [
+        Todo {
+            completed_at: Some(at(13)),
+            ..todo("a", &["work", "home"], None, None)
+        },
+        created_this_week,
+        overdue,
+        due_next_week,
+        completed_last_week,
+    ];
+    let focus = focus::summarize(&[focus::FocusSession {
+        id: 1,
+        todo_id: "a".to_string(),
+        started_at: at(13),
+        duration_secs: 1500,
+    }]);
+
+    let review = assemble_review(&todos, &focus, day(13), range);
+    assert_eq!(review.range, range);
+    assert_eq!(review.completed, 2);
+    assert_eq!(review.overdue_carry_over, 1);
+    let titles: Vec<_> = review
+        .completed_todos
+        .iter()
+        .map(|t| t.title.as_str())
+        .collect();
+    assert_eq!(titles, vec!["a", "new"]);
+    assert_eq!(review.carried_over[0].title, "overdue");
+    assert_eq!(review.created, 1);
+    assert_eq!((review.focus_secs, review.focus_sessions), (1500, 1));
+    assert_eq!(
+        review.top_tags,
+        vec![
+            TagCount {
+                tag: "home".to_string(),
+                todo_count: 2,
+            },
+            TagCount {
+                tag: "work".to_string(),
+                todo_count: 1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_review_week_reads_focus_sessions_of_that_week_only() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage
+        .save_todo(&Todo {
+            completed_at: Some(at(14)),
+            ..todo("a", &["work"], None, None)
+        })
+        .unwrap();
+    focus::record_session(&storage, "a", at(14), 600).unwrap();
+    focus::record_session(&storage, "a", at(20), 900).unwrap();
+
+    let todos = storage.list_todos().unwrap();
+    let review = review_week(&storage, &todos, day(15), Weekday::Mon, &Utc).unwrap();
+    assert_eq!(review.starts_on, day(13));
+    assert_eq!(review.completed, 1);
+    assert_eq!((review.focus_secs, review.focus_sessions), (600, 1));
+}
@@ -0,0 +1,1748 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row, ToSql};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+use tempfile::TempDir;
+
+use crate::activity;
+use crate::clock::{self, Clock, SharedClock};
+use crate::custom_fields;
+use crate::events;
+use crate::language;
+use crate::nlp;
+use crate::progress::ProgressRegistry;
+use crate::protocol::EventPayload;
+use crate::quota::{self, WriteError};
+use crate::rank;
+use crate::references;
+use crate::search::SearchIndex;
+use crate::settings::SettingsState;
+use crate::trace;
+use crate::types::{Priority, TextLanguages, Todo};
+use crate::validation_rules::{self, RuleSet, ValidationRule, Violation};
+use changes::ChangeFeed;
+use lanes::Lanes;
+
+pub mod changes;
+pub mod drafts;
+pub mod lanes;
+pub mod recovery;
+#[cfg(test)]
+mod tests;
+
+/// CLI flag that starts the app with a throwaway in-memory store.
+pub const EPHEMERAL_FLAG: &str = "--ephemeral";
+
+const DATABASE_FILE: &str = "yutodo.db";
+const FILES_DIR: &str = "files";
+/// Copies of the database taken before risky writes, under the files directory.
+const BACKUPS_DIR: &str = "backups";
+/// Directory, under the OS data directory, where versions before the Rust
+/// store kept `todos.db`.
+pub(crate) const LEGACY_DIR: &str = "YuToDo";
+pub(crate) const LEGACY_DATABASE_FILE: &str = "todos.db";
+/// Columns every legacy `todos` table has; the current schema has none of
+/// the camelCase ones.
+const LEGACY_COLUMNS: &[&str] = &["id", "title", "completed", "createdAt", "updatedAt"];
+
+/// Schema migrations, applied in order. `PRAGMA user_version` records how many have run.
+const MIGRATIONS: &[&str] = &[
+    // 1: focus sessions
+    "CREATE TABLE focus_sessions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        todo_id TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        duration_secs INTEGER NOT NULL
+    );
+    CREATE INDEX idx_focus_sessions_started_at ON focus_sessions(started_at);",
+    // 2: local todo cache
+    "CREATE TABLE todos (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        description TEXT,
+        completed INTEGER NOT NULL DEFAULT 0,
+        priority TEXT NOT NULL DEFAULT 'medium',
+        scheduled_for TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        sort_order INTEGER,
+        schedule_id TEXT
+    );",
+    // 3: todo tags, stored as a JSON array
+    "ALTER TABLE todos ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';",
+    // 4: custom field definitions (type as JSON) and per-todo values in canonical text form
+    "CREATE TABLE custom_fields (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        field_type TEXT NOT NULL,
+        required INTEGER NOT NULL DEFAULT 0,
+        default_value TEXT
+    );
+    CREATE TABLE todo_field_values (
+        todo_id TEXT NOT NULL,
+        field_id TEXT NOT NULL REFERENCES custom_fields(id),
+        value TEXT NOT NULL,
+        PRIMARY KEY (todo_id, field_id)
+    );",
+    // 5: audit log
+    "CREATE TABLE audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at TEXT NOT NULL,
+        source TEXT NOT NULL,
+        action TEXT NOT NULL,
+        detail TEXT NOT NULL
+    );",
+    // 6: saved smart-filter queries
+    "CREATE TABLE smart_lists (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        query TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );",
+    // 7: progress of unfinished imports, so they can be resumed
+    "CREATE TABLE import_checkpoints (
+        id TEXT PRIMARY KEY,
+        source_path TEXT NOT NULL,
+        format TEXT NOT NULL,
+        source_hash TEXT NOT NULL,
+        policy TEXT NOT NULL,
+        byte_offset INTEGER NOT NULL DEFAULT 0,
+        rows_committed INTEGER NOT NULL DEFAULT 0,
+        decisions TEXT NOT NULL DEFAULT '{}',
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );",
+    // 8: remote lists polled in the background
+    "CREATE TABLE remote_subscriptions (
+        id TEXT PRIMARY KEY,
+        url TEXT NOT NULL UNIQUE,
+        interval_minutes INTEGER NOT NULL,
+        list_tag TEXT NOT NULL,
+        etag TEXT,
+        fingerprint TEXT,
+        last_checked_at TEXT,
+        created_at TEXT NOT NULL
+    );",
+    // 9: optimistic concurrency for todo writes
+    "ALTER TABLE todos ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;",
+    // 10: where the list was published, for If-Match on the next upload
+    "CREATE TABLE publish_targets (
+        url TEXT PRIMARY KEY,
+        etag TEXT,
+        published_at TEXT NOT NULL
+    );",
+    // 11: recurring exports, and the outcome of each run; see
+    // `export_schedule`
+    "CREATE TABLE export_schedules (
+        id TEXT PRIMARY KEY,
+        config TEXT NOT NULL,
+        next_run TEXT,
+        retry_at TEXT,
+        retries INTEGER NOT NULL DEFAULT 0,
+        last_run TEXT,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE export_runs (
+        schedule_id TEXT NOT NULL,
+        ran_at TEXT NOT NULL,
+        path TEXT NOT NULL,
+        retry INTEGER NOT NULL,
+        exported INTEGER,
+        error TEXT
+    );
+    CREATE INDEX idx_export_runs_schedule ON export_runs (schedule_id, ran_at);",
+    // 12: folders republished as static sites on an interval
+    "CREATE TABLE static_sites (
+        output_dir TEXT PRIMARY KEY,
+        config TEXT NOT NULL,
+        interval_minutes INTEGER NOT NULL,
+        published_at TEXT
+    );",
+    // 13: fractional order keys, filled in by `assign_missing_ranks`
+    "ALTER TABLE todos ADD COLUMN rank TEXT;
+     CREATE INDEX idx_todos_rank ON todos (rank);",
+    // 14: detected languages; rows from before are detected by `language::spawn_backfill`
+    "ALTER TABLE todos ADD COLUMN title_language TEXT;
+     ALTER TABLE todos ADD COLUMN description_language TEXT;
+     ALTER TABLE todos ADD COLUMN languages_pending INTEGER NOT NULL DEFAULT 0;
+     UPDATE todos SET languages_pending = 1;",
+    // 15: legacy databases already merged, so `migrate_legacy` runs once per source
+    "CREATE TABLE legacy_migrations (
+        source TEXT PRIMARY KEY,
+        migrated_at TEXT NOT NULL,
+        imported INTEGER NOT NULL
+    );",
+    // 16: one-way sync into the OS calendar; `calendar_events` maps todos to
+    // the events created for them
+    "CREATE TABLE calendar_sync (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        config TEXT NOT NULL,
+        calendar_id TEXT
+    );
+    CREATE TABLE calendar_events (
+        todo_id TEXT PRIMARY KEY,
+        event_id TEXT NOT NULL,
+        fingerprint TEXT NOT NULL
+    );",
+    // 17: the activity feed; `rollup_key` coalesces entries, e.g. per day
+    "CREATE TABLE activity_feed (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        summary_key TEXT NOT NULL,
+        count INTEGER NOT NULL,
+        params TEXT NOT NULL,
+        rollup_key TEXT UNIQUE,
+        started_at TEXT NOT NULL,
+        at TEXT NOT NULL
+    );
+    CREATE INDEX idx_activity_feed_at ON activity_feed (at, id);",
+    // 18: change data capture; see `changes`
+    "CREATE TABLE changes (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        tx INTEGER NOT NULL,
+        entity TEXT NOT NULL,
+        entity_id TEXT NOT NULL,
+        op TEXT NOT NULL,
+        changed_fields TEXT NOT NULL,
+        revision INTEGER
+    );
+    CREATE TABLE change_consumers (
+        name TEXT PRIMARY KEY,
+        cursor INTEGER NOT NULL
+    );",
+    // 19: recurring schedules; `rrule` is an RFC 5545 RRULE value and
+    // `starts_at` the floating local time of the first occurrence
+    "CREATE TABLE schedules (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        description TEXT,
+        priority TEXT NOT NULL,
+        starts_at TEXT NOT NULL,
+        rrule TEXT NOT NULL,
+        excluded_dates TEXT NOT NULL,
+        active INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );",
+    // 20: commands the background scheduler runs later; see `automation`
+    "CREATE TABLE scheduled_commands (
+        id TEXT PRIMARY KEY,
+        command TEXT NOT NULL,
+        schedule TEXT NOT NULL,
+        next_run TEXT,
+        last_run TEXT,
+        last_error TEXT,
+        created_at TEXT NOT NULL
+    );",
+    // 21: rules that act on todo changes, run in `position` order; see
+    // `automation`
+    "CREATE TABLE rules (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        trigger TEXT NOT NULL,
+        conditions TEXT NOT NULL,
+        actions TEXT NOT NULL,
+        enabled INTEGER NOT NULL,
+        created_at TEXT NOT NULL
+    );",
+    // 22: effort estimates and actuals; see `stats`
+    "ALTER TABLE todos ADD COLUMN estimate_minutes INTEGER;
+     ALTER TABLE todos ADD COLUMN actual_minutes INTEGER;",
+    // 23: sources re-synced in the background, and the todos each created by
+    // the source's dedup key; see `integrations::connectors`
+    "CREATE TABLE connector_instances (
+        id TEXT PRIMARY KEY,
+        kind TEXT NOT NULL,
+        name TEXT NOT NULL,
+        config TEXT NOT NULL,
+        interval_minutes INTEGER NOT NULL,
+        cursor TEXT,
+        last_run_at TEXT,
+        last_error TEXT,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE connector_items (
+        instance_id TEXT NOT NULL,
+        item_key TEXT NOT NULL,
+        todo_id TEXT NOT NULL,
+        PRIMARY KEY (instance_id, item_key)
+    );",
+    // 24: static sites become export schedules, which run them from the
+    // background scheduler; they publish again at its next wake-up
+    "INSERT INTO export_schedules (id, config, next_run, created_at)
+     SELECT lower(hex(randomblob(16))),
+            json_object(
+                'target', json_set(json_remove(config, '$.outputDir'), '$.kind', 'staticSite'),
+                'format', 'json',
+                'stable', json('false'),
+                'destination', output_dir,
+                'filename', '',
+                'interval', json_object('kind', 'every', 'minutes', interval_minutes),
+                'naming', 'overwrite'
+            ),
+            strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'),
+            strftime('%Y-%m-%d %H:%M:%f+00:00', 'now')
+     FROM static_sites;
+     DROP TABLE static_sites;",
+    // 25: when each todo was completed; todos completed before count from
+    // their last update
+    "ALTER TABLE todos ADD COLUMN completed_at TEXT;
+     UPDATE todos SET completed_at = updated_at WHERE completed;",
+    // 26: reminders snoozed from a notification, until they show again; see
+    // `notifications::activation`
+    "CREATE TABLE snoozes (
+        todo_id TEXT PRIMARY KEY,
+        until TEXT NOT NULL
+    );",
+];
+
+pub const TODO_COLUMNS: &str = "id, title, description, completed, priority, scheduled_for, \
+     created_at, updated_at, sort_order, schedule_id, tags, rank, title_language, \
+     description_language, estimate_minutes, actual_minutes, revision, completed_at";
+
+/// Whether `dir` holds a store's database.
+pub fn holds_database(dir: &Path) -> bool {
+    dir.join(DATABASE_FILE).exists()
+}
+
+/// Copies `from` into `to` recursively; a missing `from` copies nothing.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !from.is_dir() {
+        return Ok(());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Directory holding the database and other backend-owned data: the one the
+/// open store uses, else the app data directory. The `data_dir` setting is
+/// applied when the store is opened at startup.
+pub fn data_dir(app: &AppHandle) -> tauri::Result<PathBuf> {
+    match app
+        .try_state::<Storage>()
+        .and_then(|storage| storage.mode().data_dir)
+    {
+        Some(dir) => Ok(dir),
+        None => app.path().app_data_dir(),
+    }
+}
+
+/// The store was written by a newer version of the app, so it is open
+/// read-only: this build can't know what writing it would break.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[serde(tag = "kind", rename = "readOnlyNewerSchema", rename_all = "camelCase")]
+#[error(
+    "The database belongs to a newer version of YuToDo (schema {db_version}, this version \
+     knows {app_version}); it is read-only until the app is updated"
+)]
+pub struct ReadOnlyNewerSchema {
+    pub db_version: u32,
+    /// Schema version this build writes.
+    pub app_version: u32,
+}
+
+impl EventPayload for ReadOnlyNewerSchema {}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Where the store keeps its data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// Database and files under the given data directory.
+    Disk(PathBuf),
+    /// Nothing survives the process: SQLite `:memory:` plus a temporary files directory.
+    InMemory,
+}
+
+impl StorageBackend {
+    /// Picks the backend for this launch from the command line.
+    pub fn from_args(args: impl IntoIterator<Item = String>, data_dir: PathBuf) -> Self {
+        if args.into_iter().any(|arg| arg == EPHEMERAL_FLAG) {
+            Self::InMemory
+        } else {
+            Self::Disk(data_dir)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageMode {
+    pub ephemeral: bool,
+    pub data_dir: Option<PathBuf>,
+}
+
+/// Local SQLite store owned by the Rust side of the app.
+pub struct Storage {
+    conn: Mutex<Connection>,
+    backend: StorageBackend,
+    /// SQLite URI (in-memory) or file path (disk) used to open further connections.
+    location: String,
+    files_dir: PathBuf,
+    /// Owns the files directory of an in-memory store so it is removed with it.
+    temp_files: Mutex<Option<TempDir>>,
+    /// Hard limit on cached todos, from `[backend.quota]`.
+    max_todos: AtomicU64,
+    /// Source of the timestamps written to the store.
+    clock: SharedClock,
+    /// Set when the store is open read-only for a newer app.
+    newer_schema: Option<ReadOnlyNewerSchema>,
+    /// Committed changes to todos; see [`changes`].
+    changes: Arc<ChangeFeed>,
+    /// Who waits for `conn`; see [`lanes`].
+    lanes: Lanes,
+}
+
+impl Storage {
+    pub fn open(backend: StorageBackend) -> Result<Self, StorageError> {
+        let (location, files_dir, temp_files) = match &backend {
+            StorageBackend::Disk(dir) => {
+                let files_dir = dir.join(FILES_DIR);
+                fs::create_dir_all(&files_dir)?;
+                let location = dir.join(DATABASE_FILE).to_string_lossy().into_owned();
+                (location, files_dir, None)
+            }
+            StorageBackend::InMemory => {
+                // Each store gets its own named shared-cache database, so extra
+                // connections see the same data while separate stores stay isolated.
+                static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+                let location = format!(
+                    "file:yutodo-{}-{}?mode=memory&cache=shared",
+                    std::process::id(),
+                    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+                );
+                let temp = tempfile::Builder::new()
+                    .prefix("yutodo-ephemeral-")
+                    .tempdir()?;
+                (location, temp.path().to_path_buf(), Some(temp))
+            }
+        };
+
+        let feed = ChangeFeed::new();
+        let mut conn = connect(&location, false)?;
+        let newer_schema = newer_schema(&conn)?;
+        if newer_schema.is_some() {
+            conn = connect(&location, true)?;
+        } else {
+            changes::install(&conn, &feed)?;
+            changes::spawn_dispatcher(&feed, connect(&location, true)?)?;
+            migrate(&conn, &feed, MIGRATIONS)?;
+            assign_missing_ranks(&mut conn)?;
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+            backend,
+            location,
+            files_dir,
+            temp_files: Mutex::new(temp_files),
+            max_todos: AtomicU64::new(u64::MAX),
+            clock: clock::system(),
+            newer_schema,
+            changes: feed,
+            lanes: Lanes::default(),
+        })
+    }
+
+    /// Replaces the real clock, e.g. with the app's simulated one.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::open(StorageBackend::InMemory)
+    }
+
+    /// The connection, for interactive work; see [`lanes`].
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.lanes.interactive(&self.conn)
+    }
+
+    /// The connection, for one chunk of batch work. Waits while interactive
+    /// callers do, so release it between chunks.
+    pub fn batch_conn(&self) -> MutexGuard<'_, Connection> {
+        self.lanes.batch(&self.conn)
+    }
+
+    /// Set while interactive callers wait for the connection held by batch
+    /// work, which should then end its chunk.
+    pub fn should_yield(&self) -> bool {
+        self.lanes.interactive_waiting()
+    }
+
+    /// Opens another connection to the same database. Its writes are
+    /// captured like those of the main one.
+    pub fn connect(&self) -> rusqlite::Result<Connection> {
+        let conn = connect(&self.location, self.newer_schema.is_some())?;
+        if self.newer_schema.is_none() {
+            changes::install(&conn, &self.changes)?;
+        }
+        Ok(conn)
+    }
+
+    /// Opens a connection to the same database that can only read.
+    pub fn connect_read_only(&self) -> rusqlite::Result<Connection> {
+        connect(&self.location, true)
+    }
+
+    /// Why the store is read-only, if it is.
+    pub fn newer_schema(&self) -> Option<&ReadOnlyNewerSchema> {
+        self.newer_schema.as_ref()
+    }
+
+    pub fn max_todos(&self) -> u64 {
+        self.max_todos.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_todos(&self, max_todos: u64) {
+        self.max_todos.store(max_todos, Ordering::Relaxed);
+    }
+
+    /// Directory for attachment-like files that belong to this store.
+    pub fn files_dir(&self) -> &Path {
+        &self.files_dir
+    }
+
+    /// Copies the database and files to `dir`, for the store to open there
+    /// from the next start. Refuses a directory that already holds one.
+    pub fn copy_to(&self, dir: &Path) -> Result<(), String> {
+        if holds_database(dir) {
+            return Err(format!("{} already holds a database", dir.display()));
+        }
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        self.conn()
+            .execute(
+                "VACUUM INTO ?1",
+                [dir.join(DATABASE_FILE).to_string_lossy()],
+            )
+            .map_err(|e| format!("Failed to copy the database: {}", e))?;
+        copy_dir(&self.files_dir, &dir.join(FILES_DIR))
+            .map_err(|e| format!("Failed to copy {}: {}", self.files_dir.display(), e))
+    }
+
+    pub fn mode(&self) -> StorageMode {
+        match &self.backend {
+            StorageBackend::Disk(dir) => StorageMode {
+                ephemeral: false,
+                data_dir: Some(dir.clone()),
+            },
+            StorageBackend::InMemory => StorageMode {
+                ephemeral: true,
+                data_dir: None,
+            },
+        }
+    }
+
+    /// Deletes the temporary files directory of an in-memory store. Managed state
+    /// is not dropped on exit, so the app calls this explicitly.
+    pub fn discard_temporary_files(&self) {
+        if let Some(temp) = self
+            .temp_files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            let _ = temp.close();
+        }
+    }
+}
+
+impl Storage {
+    /// All cached todos with their custom field values filled in.
+    pub fn list_todos(&self) -> rusqlite::Result<Vec<Todo>> {
+        list_todos_with(&self.conn())
+    }
+
+    pub fn get_todo(&self, id: &str) -> rusqlite::Result<Option<Todo>> {
+        get_todo_with(&self.conn(), id)
+    }
+
+    /// Inserts or updates a single cached todo without a revision check, and
+    /// returns its new revision.
+    pub fn save_todo(&self, todo: &Todo) -> rusqlite::Result<u64> {
+        insert_todo(&self.conn(), todo)
+    }
+
+    /// Replaces the whole cache with `todos` in one transaction. The server is
+    /// authoritative, so revisions are not checked; unchanged todos keep theirs.
+    /// New todos without an order key are appended in the order given.
+    pub fn replace_todos(&self, todos: &[Todo]) -> rusqlite::Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let existing: HashMap<String, Todo> = {
+            let mut stmt = tx.prepare(&format!("SELECT {} FROM todos", TODO_COLUMNS))?;
+            let rows = stmt.query_map([], todo_from_row)?;
+            rows.map(|todo| todo.map(|t| (t.id.clone(), t)))
+                .collect::<rusqlite::Result<_>>()?
+        };
+        let ids: HashSet<&str> = todos.iter().map(|t| t.id.as_str()).collect();
+        for id in existing.keys().filter(|id| !ids.contains(id.as_str())) {
+            tx.execute("DELETE FROM todos WHERE id = ?1", [id])?;
+        }
+        for todo in todos {
+            let unchanged = existing.get(&todo.id).is_some_and(|old| {
+                old.updated_at == todo.updated_at && todo.changed_fields(old).is_empty()
+            });
+            if !unchanged {
+                insert_todo(&tx, todo)?;
+            }
+        }
+        tx.execute(
+            "DELETE FROM todo_field_values WHERE todo_id NOT IN (SELECT id FROM todos)",
+            [],
+        )?;
+        tx.commit()
+    }
+
+    /// Moves todo `id` between `before_id` and `after_id` (`None` for the
+    /// start or end of the list) and returns its new order key. Only that
+    /// todo's row is written, and its revision stays the same.
+    pub fn reorder_todo(
+        &self,
+        id: &str,
+        before_id: Option<&str>,
+        after_id: Option<&str>,
+    ) -> Result<String, String> {
+        let conn = self.conn();
+        let rank_of = |id: &str| {
+            conn.query_row("SELECT rank FROM todos WHERE id = ?1", [id], |row| {
+                row.get::<_, Option<String>>(0)
+            })
+            .optional()
+            .map_err(|e| format!("Failed to load todo: {}", e))?
+            .ok_or_else(|| format!("Unknown todo '{}'", id))
+        };
+        let mut neighbors = [None, None];
+        for (neighbor, neighbor_id) in neighbors.iter_mut().zip([before_id, after_id]) {
+            if let Some(neighbor_id) = neighbor_id {
+                if neighbor_id == id {
+                    return Err(format!("Todo '{}' can't be its own neighbor", id));
+                }
+                *neighbor = rank_of(neighbor_id)?;
+            }
+        }
+        rank_of(id)?;
+        let [before, after] = neighbors;
+        let rank = rank::between(before.as_deref(), after.as_deref())?;
+        conn.execute(
+            "UPDATE todos SET rank = ?2 WHERE id = ?1",
+            params![id, rank],
+        )
+        .map_err(|e| format!("Failed to reorder todo: {}", e))?;
+        Ok(rank)
+    }
+
+    /// Splits the checklist of todo `id` into new todos placed right after
+    /// it, in one transaction; see [`split_checklist`]. Returns the new todos.
+    pub fn split_todo(&self, id: &str, as_subtasks: bool) -> Result<Vec<Todo>, String> {
+        let failed = |e: rusqlite::Error| format!("Failed to split todo: {}", e);
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(failed)?;
+        let todo = get_todo_with(&tx, id)
+            .map_err(failed)?
+            .ok_or_else(|| format!("Unknown todo '{}'", id))?;
+        let (parent, mut children) = split_checklist(&todo, as_subtasks, self.clock().now())?;
+        let total = quota::count_todos(&tx).map_err(failed)? + children.len() as u64;
+        quota::check_todos(self, total).map_err(|e| e.to_string())?;
+
+        let next: Option<String> = tx
+            .query_row(
+                "SELECT MIN(rank) FROM todos WHERE rank > ?1",
+                [&todo.rank],
+                |row| row.get(0),
+            )
+            .map_err(failed)?;
+        let mut before = todo.rank.clone();
+        for child in &mut children {
+            let rank = rank::between(before.as_deref(), next.as_deref())?;
+            child.rank = Some(rank.clone());
+            before = Some(rank);
+        }
+        let result: rusqlite::Result<()> = (|| {
+            insert_todo(&tx, &parent)?;
+            for child in &children {
+                insert_todo(&tx, child)?;
+            }
+            Ok(())
+        })();
+        result.map_err(failed)?;
+        let children = children
+            .iter()
+            .map(|child| Ok(get_todo_with(&tx, &child.id)?.unwrap_or_else(|| child.clone())))
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(failed)?;
+        tx.commit().map_err(failed)?;
+        Ok(children)
+    }
+
+    /// Folds the todos `duplicate_ids` into `primary_id` (see
+    /// [`fold_duplicates`]) and deletes them, in one transaction. Field
+    /// values the primary lacks are taken from the duplicates, and
+    /// references to a duplicate in any description now point at the
+    /// primary. Returns the merged todo.
+    pub fn merge_todos(&self, primary_id: &str, duplicate_ids: &[String]) -> Result<Todo, String> {
+        let mut merged_ids: Vec<&str> = Vec::new();
+        for id in duplicate_ids {
+            if id == primary_id {
+                return Err(format!("Todo '{}' can't be merged into itself", id));
+            }
+            if !merged_ids.contains(&id.as_str()) {
+                merged_ids.push(id);
+            }
+        }
+        if merged_ids.is_empty() {
+            return Err(format!("No todos to merge into '{}'", primary_id));
+        }
+        let failed = |e: rusqlite::Error| format!("Failed to merge todos: {}", e);
+        let now = self.clock().now();
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(failed)?;
+        let todos = list_todos_with(&tx).map_err(failed)?;
+        let find = |id: &str| -> Result<&Todo, String> {
+            todos
+                .iter()
+                .find(|todo| todo.id == id)
+                .ok_or_else(|| format!("Unknown todo '{}'", id))
+        };
+        let primary = find(primary_id)?;
+        let duplicates = merged_ids
+            .iter()
+            .map(|&id| find(id).cloned())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut merged = fold_duplicates(primary, &duplicates);
+        let repoint = |todo: &Todo| {
+            let description = todo.description.as_deref()?;
+            repoint_references(description, &todos, &merged_ids, primary)
+        };
+        if let Some(description) = repoint(&merged) {
+            merged.description = Some(description);
+        }
+        let referencing: Vec<Todo> = todos
+            .iter()
+            .filter(|todo| todo.id != primary_id && !merged_ids.contains(&todo.id.as_str()))
+            .filter_map(|todo| {
+                let description = repoint(todo)?;
+                Some(Todo {
+                    description: Some(description),
+                    updated_at: now,
+                    ..todo.clone()
+                })
+            })
+            .collect();
+        let result: rusqlite::Result<()> = (|| {
+            insert_todo(&tx, &merged)?;
+            for &id in &merged_ids {
+                tx.execute(
+                    "INSERT OR IGNORE INTO todo_field_values (todo_id, field_id, value)
+                     SELECT ?1, field_id, value FROM todo_field_values WHERE todo_id = ?2",
+                    [primary_id, id],
+                )?;
+                tx.execute("DELETE FROM todos WHERE id = ?1", [id])?;
+                tx.execute("DELETE FROM todo_field_values WHERE todo_id = ?1", [id])?;
+            }
+            for todo in &referencing {
+                insert_todo(&tx, todo)?;
+            }
+            Ok(())
+        })();
+        result.map_err(failed)?;
+        let merged = get_todo_with(&tx, primary_id)
+            .map_err(failed)?
+            .ok_or_else(|| format!("Unknown todo '{}'", primary_id))?;
+        tx.commit().map_err(failed)?;
+        Ok(merged)
+    }
+}
+
+/// Splits the unchecked `- [ ]` lines of `parent`'s note into new todos,
+/// which take its priority, schedule and tags. Returns the parent with its
+/// new note and the new todos in note order. As subtasks, each line is left
+/// pointing at its todo with a `#id` reference, so the parent still lists
+/// them; as siblings, the lines are removed.
+pub fn split_checklist(
+    parent: &Todo,
+    as_subtasks: bool,
+    now: DateTime<Utc>,
+) -> Result<(Todo, Vec<Todo>), String> {
+    let note = parent.description.as_deref().unwrap_or_default();
+    if note.trim().is_empty() {
+        return Err(format!("Todo '{}' has no note to split", parent.title));
+    }
+    let mut lines = Vec::new();
+    let mut children = Vec::new();
+    for line in note.lines() {
+        let Some(text) = nlp::checkbox(line.trim()) else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let child = Todo {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: text.to_string(),
+            description: None,
+            completed: false,
+            created_at: now,
+            updated_at: now,
+            completed_at: None,
+            order: None,
+            rank: None,
+            schedule_id: None,
+            languages: Default::default(),
+            fields: Default::default(),
+            estimate_minutes: None,
+            actual_minutes: None,
+            revision: 0,
+            ..parent.clone()
+        };
+        if as_subtasks {
+            let start = line.find(text).unwrap_or(line.len());
+            lines.push(format!("{}#{}", &line[..start], child.id));
+        }
+        children.push(child);
+    }
+    if children.is_empty() {
+        return Err(format!("Todo '{}' has no checklist lines", parent.title));
+    }
+    let note = lines.join("\n");
+    let parent = Todo {
+        description: (!note.trim().is_empty()).then_some(note),
+        updated_at: now,
+        ..parent.clone()
+    };
+    Ok((parent, children))
+}
+
+/// `primary` with `duplicates` folded in: the union of their tags, the
+/// earliest creation and the latest update. The primary's title and other
+/// fields win, except that a description or schedule it lacks comes from
+/// the first duplicate that has one.
+pub fn fold_duplicates(primary: &Todo, duplicates: &[Todo]) -> Todo {
+    let mut merged = primary.clone();
+    for duplicate in duplicates {
+        for tag in &duplicate.tags {
+            if !merged.tags.contains(tag) {
+                merged.tags.push(tag.clone());
+            }
+        }
+        merged.created_at = merged.created_at.min(duplicate.created_at);
+        merged.updated_at = merged.updated_at.max(duplicate.updated_at);
+        if merged.description.as_deref().unwrap_or_default().is_empty() {
+            merged.description = duplicate.description.clone();
+        }
+        if merged.scheduled_for.is_none() {
+            merged.scheduled_for = duplicate.scheduled_for;
+        }
+        // Done as soon as any copy was.
+        if merged.completed {
+            merged.completed_at = merged
+                .completed_at
+                .into_iter()
+                .chain(duplicate.completed_at)
+                .min();
+        }
+    }
+    merged
+}
+
+/// `description` with the references that resolve among `todos` to one of
+/// `merged_ids` rewritten to `primary`, or `None` if there are none.
+fn repoint_references(
+    description: &str,
+    todos: &[Todo],
+    merged_ids: &[&str],
+    primary: &Todo,
+) -> Option<String> {
+    let mut rewritten: Option<String> = None;
+    let references = references::parse_references(description);
+    for resolved in references::resolve_references(&references, todos) {
+        let references::Resolution::Resolved { todo_id } = &resolved.resolution else {
+            continue;
+        };
+        if !merged_ids.contains(&todo_id.as_str()) {
+            continue;
+        }
+        let (old, new) = match &resolved.reference.target {
+            references::ReferenceTarget::ShortId { id } => (id, &primary.id),
+            references::ReferenceTarget::WikiLink { title } => (title, &primary.title),
+        };
+        let text = rewritten.as_deref().unwrap_or(description);
+        if let Some(text) = references::rewrite_references(text, old, new) {
+            rewritten = Some(text);
+        }
+    }
+    rewritten
+}
+
+/// All cached todos on `conn` with their custom field values filled in.
+pub(crate) fn list_todos_with(conn: &Connection) -> rusqlite::Result<Vec<Todo>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM todos ORDER BY rank, sort_order, created_at, id",
+        TODO_COLUMNS
+    ))?;
+    let mut todos = stmt
+        .query_map([], todo_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    custom_fields::attach_field_values(conn, &mut todos)?;
+    Ok(todos)
+}
+
+pub(crate) fn get_todo_with(conn: &Connection, id: &str) -> rusqlite::Result<Option<Todo>> {
+    let todo = conn
+        .query_row(
+            &format!("SELECT {} FROM todos WHERE id = ?1", TODO_COLUMNS),
+            [id],
+            todo_from_row,
+        )
+        .optional()?;
+    let mut todos: Vec<Todo> = todo.into_iter().collect();
+    custom_fields::attach_field_values(conn, &mut todos)?;
+    Ok(todos.pop())
+}
+
+/// Writes `todo`, ignoring its `revision`, and returns the bumped revision.
+///
+/// A cached todo keeps its order key, which only [`Storage::reorder_todo`]
+/// changes. A new one takes the key it came with, or goes last. When the text
+/// changed, its languages are left for [`language::spawn_backfill`]. A
+/// completed todo keeps the time it was completed, or takes its `updated_at`
+/// if it wasn't completed before; an open one has none.
+pub fn insert_todo(conn: &Connection, todo: &Todo) -> rusqlite::Result<u64> {
+    let rank = rank_for(conn, todo)?;
+    let (languages, languages_pending) = languages_for(conn, todo)?;
+    conn.query_row(
+        &format!(
+            "INSERT OR REPLACE INTO todos ({}, languages_pending)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                 COALESCE((SELECT revision FROM todos WHERE id = ?1), 0) + 1,
+                 CASE WHEN ?4 THEN
+                     COALESCE(?18, (SELECT completed_at FROM todos WHERE id = ?1), ?8)
+                 END,
+                 ?17)
+             RETURNING revision",
+            TODO_COLUMNS
+        ),
+        params![
+            todo.id,
+            todo.title,
+            todo.description,
+            todo.completed,
+            todo.priority,
+            todo.scheduled_for,
+            todo.created_at,
+            todo.updated_at,
+            todo.order,
+            todo.schedule_id,
+            serde_json::to_string(&todo.tags).unwrap_or_else(|_| "[]".to_string()),
+            rank,
+            languages.title,
+            languages.description,
+            todo.estimate_minutes,
+            todo.actual_minutes,
+            languages_pending,
+            todo.completed_at,
+        ],
+        |row| row.get::<_, i64>(0).map(|revision| revision as u64),
+    )
+}
+
+fn rank_for(conn: &Connection, todo: &Todo) -> rusqlite::Result<String> {
+    let cached: Option<String> = conn
+        .query_row("SELECT rank FROM todos WHERE id = ?1", [&todo.id], |row| {
+            row.get(0)
+        })
+        .optional()?
+        .flatten();
+    if let Some(rank) = cached.or_else(|| todo.rank.clone().filter(|r| rank::is_valid(r))) {
+        return Ok(rank);
+    }
+    let last: Option<String> =
+        conn.query_row("SELECT MAX(rank) FROM todos", [], |row| row.get(0))?;
+    rank::between(last.as_deref(), None)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))
+}
+
+/// The cached languages and whether they are still to be detected, which
+/// is the case for new todos and changed text.
+fn languages_for(conn: &Connection, todo: &Todo) -> rusqlite::Result<(TextLanguages, bool)> {
+    let cached = conn
+        .query_row(
+            "SELECT title, description, title_language, description_language, languages_pending
+             FROM todos WHERE id = ?1",
+            [&todo.id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    TextLanguages {
+                        title: row.get(2)?,
+                        description: row.get(3)?,
+                    },
+                    row.get::<_, bool>(4)?,
+                ))
+            },
+        )
+        .optional()?;
+    Ok(match cached {
+        Some((title, description, languages, pending))
+            if title == todo.title && description == todo.description =>
+        {
+            (languages, pending)
+        }
+        _ => (TextLanguages::default(), true),
+    })
+}
+
+/// Respreads the order keys of every cached todo, keeping their order, so
+/// keys are short again. Todos without a key go last. Returns how many todos
+/// were reordered.
+pub fn rebalance_ranks(conn: &mut Connection) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+    let ids = {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM todos ORDER BY rank IS NULL, rank, sort_order, created_at, id",
+        )?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    {
+        let mut stmt = tx.prepare("UPDATE todos SET rank = ?2 WHERE id = ?1")?;
+        for (id, rank) in ids.iter().zip(rank::spread(ids.len())) {
+            stmt.execute(params![id, rank])?;
+        }
+    }
+    tx.commit()?;
+    Ok(ids.len())
+}
+
+/// Gives todos cached before order keys existed a key, in their old order.
+fn assign_missing_ranks(conn: &mut Connection) -> rusqlite::Result<()> {
+    let missing: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM todos WHERE rank IS NULL)",
+        [],
+        |row| row.get(0),
+    )?;
+    if missing {
+        rebalance_ranks(conn)?;
+    }
+    Ok(())
+}
+
+pub fn todo_from_row(row: &Row<'_>) -> rusqlite::Result<Todo> {
+    Ok(Todo {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        completed: row.get(3)?,
+        priority: row.get(4)?,
+        scheduled_for: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        completed_at: row.get(17)?,
+        order: row.get(8)?,
+        schedule_id: row.get(9)?,
+        tags: serde_json::from_str(&row.get::<_, String>(10)?).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(10, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        rank: row.get(11)?,
+        languages: TextLanguages {
+            title: row.get(12)?,
+            description: row.get(13)?,
+        },
+        fields: Default::default(),
+        estimate_minutes: row.get(14)?,
+        actual_minutes: row.get(15)?,
+        revision: row.get::<_, i64>(16)? as u64,
+    })
+}
+
+impl ToSql for Priority {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for Priority {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let text = value.as_str()?;
+        Priority::parse(text)
+            .ok_or_else(|| FromSqlError::Other(format!("invalid priority '{}'", text).into()))
+    }
+}
+
+fn connect(location: &str, read_only: bool) -> rusqlite::Result<Connection> {
+    let access = if read_only {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+    };
+    Connection::open_with_flags(
+        location,
+        access | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+}
+
+/// Set if a newer app has run migrations this build doesn't have. Reads
+/// name their columns, so the ones it added are simply not seen.
+fn newer_schema(conn: &Connection) -> rusqlite::Result<Option<ReadOnlyNewerSchema>> {
+    let applied: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let known = MIGRATIONS.len() as u32;
+    Ok((applied > known as i64).then_some(ReadOnlyNewerSchema {
+        db_version: applied as u32,
+        app_version: known,
+    }))
+}
+
+/// Applies the `migrations` past `user_version`. Once one has created the
+/// change log, what the following ones write is captured.
+fn migrate(conn: &Connection, feed: &ChangeFeed, migrations: &[&str]) -> rusqlite::Result<()> {
+    let applied: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, sql) in migrations.iter().enumerate().skip(applied as usize) {
+        changes::install_triggers(conn, feed)?;
+        conn.execute_batch(sql)?;
+        conn.pragma_update(None, "user_version", (index + 1) as i64)?;
+    }
+    changes::install_triggers(conn, feed)
+}
+
+/// Mirrors the todo list the frontend received from the server into the local cache.
+///
+/// Emits `todo-cache-updated` so views derived from the cache, such as smart
+/// lists, can refresh.
+#[tauri::command]
+pub fn sync_todo_cache(
+    todos: Vec<Todo>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    index: State<'_, SearchIndex>,
+) -> Result<(), WriteError> {
+    quota::check_todos(&storage, todos.len() as u64)?;
+    storage
+        .replace_todos(&todos)
+        .map_err(|e| format!("Failed to update todo cache: {}", e))?;
+    index.rebuild(&todos);
+    activity::cache_synced(&storage, todos.len());
+    let _ = events::emit(&app, "todo-cache-updated", ());
+    quota::notify(&app);
+    Ok(())
+}
+
+/// Splits a todo's checklist into todos of their own, as subtasks that the
+/// parent's note refers to unless `as_subtasks` is `false`. Emits
+/// `todo-cache-updated`.
+#[tauri::command]
+pub fn split_todo(
+    todo_id: String,
+    as_subtasks: Option<bool>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    index: State<'_, SearchIndex>,
+) -> Result<Vec<Todo>, String> {
+    let todos = storage.split_todo(&todo_id, as_subtasks.unwrap_or(true))?;
+    if let Ok(all) = storage.list_todos() {
+        index.rebuild(&all);
+    }
+    let _ = events::emit(&app, "todo-cache-updated", ());
+    quota::notify(&app);
+    Ok(todos)
+}
+
+/// Merges duplicate todos into one and emits `todo-cache-updated`.
+#[tauri::command]
+pub fn merge_todos(
+    primary_id: String,
+    duplicate_ids: Vec<String>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    index: State<'_, SearchIndex>,
+) -> Result<Todo, String> {
+    let merged = storage.merge_todos(&primary_id, &duplicate_ids)?;
+    if let Ok(todos) = storage.list_todos() {
+        index.rebuild(&todos);
+    }
+    let _ = events::emit(&app, "todo-cache-updated", ());
+    Ok(merged)
+}
+
+/// Payload of the `todo-reordered` event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoReordered {
+    pub id: String,
+    pub rank: String,
+}
+
+impl EventPayload for TodoReordered {}
+
+/// Set while a rebalance runs, so long keys from several reorders start only one.
+static REBALANCING: AtomicBool = AtomicBool::new(false);
+
+/// Moves a todo between two neighbors, writing only that todo, and emits
+/// `todo-reordered`.
+///
+/// When the new key has grown past [`rank::MAX_LEN`], every key is respread
+/// in a background transaction, after which `todo-ranks-rebalanced` carries
+/// the number of todos.
+#[tauri::command]
+pub fn reorder_todo(
+    id: String,
+    before_id: Option<String>,
+    after_id: Option<String>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<String, String> {
+    let rank = storage.reorder_todo(&id, before_id.as_deref(), after_id.as_deref())?;
+    let _ = events::emit(
+        &app,
+        "todo-reordered",
+        TodoReordered {
+            id,
+            rank: rank.clone(),
+        },
+    );
+    if rank::needs_rebalance(&rank) && !REBALANCING.swap(true, Ordering::AcqRel) {
+        std::thread::spawn(trace::bind(move || {
+            let storage = app.state::<Storage>();
+            match storage
+                .connect()
+                .and_then(|mut conn| rebalance_ranks(&mut conn))
+            {
+                Ok(count) => {
+                    let _ = events::emit(&app, "todo-ranks-rebalanced", count);
+                }
+                Err(e) => trace::log(format!("Failed to rebalance order keys: {}", e)),
+            }
+            REBALANCING.store(false, Ordering::Release);
+        }));
+    }
+    Ok(rank)
+}
+
+/// Payload of the `todo-updated` event.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoUpdated {
+    pub todo: Todo,
+    pub changed_fields: Vec<String>,
+    /// Validation rules the todo breaks without being blocked.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Violation>,
+}
+
+impl EventPayload for TodoUpdated {}
+
+/// Error of [`update_todo`], serialized so the frontend can tell a conflict
+/// apart from other failures.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema, thiserror::Error)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum UpdateError {
+    /// The todo was written since the caller read it; `current` is the newer copy.
+    #[error("Todo '{}' was changed elsewhere (now at revision {})", current.id, current.revision)]
+    RevisionConflict { current: Box<Todo> },
+    /// The todo breaks a blocking validation rule; warnings are listed too.
+    #[error(
+        "Todo breaks validation rules: {}",
+        validation_rules::describe(violations)
+    )]
+    ValidationFailed { violations: Vec<Violation> },
+    #[error("{message}")]
+    Failed { message: String },
+}
+
+impl From<String> for UpdateError {
+    fn from(message: String) -> Self {
+        UpdateError::Failed { message }
+    }
+}
+
+/// Saves `todo` over its cached copy if `todo.revision` is still current.
+///
+/// With `base` (the copy the edit started from), a stale edit is merged
+/// field by field into the current copy instead of failing, unless both
+/// sides changed the same field. Returns `None`, without writing, when
+/// nothing but `updatedAt` differs.
+pub fn apply_update(
+    storage: &Storage,
+    todo: Todo,
+    base: Option<&Todo>,
+) -> Result<Option<TodoUpdated>, UpdateError> {
+    apply_checked_update(storage, todo, base, &RuleSet::default())
+}
+
+/// Like [`apply_update`], but checks the todo as it would be written
+/// against `rules` first. Blocking violations fail the update; the others
+/// come back as its warnings.
+pub fn apply_checked_update(
+    storage: &Storage,
+    todo: Todo,
+    base: Option<&Todo>,
+    rules: &RuleSet,
+) -> Result<Option<TodoUpdated>, UpdateError> {
+    let conn = storage.conn();
+    let mut current = get_todo_with(&conn, &todo.id)
+        .map_err(|e| format!("Failed to load todo: {}", e))?
+        .ok_or_else(|| format!("Unknown todo '{}'", todo.id))?;
+    let mut todo = match base {
+        Some(base) if todo.revision != current.revision => todo
+            .merge_into(base, &current)
+            .map_err(|_| UpdateError::RevisionConflict {
+                current: Box::new(current.clone()),
+            })?,
+        _ => todo,
+    };
+    if todo.revision != current.revision {
+        return Err(UpdateError::RevisionConflict {
+            current: Box::new(current),
+        });
+    }
+    // Custom field values are written through their own commands.
+    todo.fields = std::mem::take(&mut current.fields);
+    let changed_fields = todo.changed_fields(&current);
+    if changed_fields.is_empty() {
+        return Ok(None);
+    }
+    let warnings = rules.check(&todo);
+    if validation_rules::is_blocking(&warnings) {
+        return Err(UpdateError::ValidationFailed {
+            violations: warnings,
+        });
+    }
+    insert_todo(&conn, &todo)
+        .and_then(|_| language::detect_now(&conn, &todo.id))
+        .map_err(|e| format!("Failed to update todo: {}", e))?;
+    // Read back what the cache derived: revision, order key, languages and
+    // completion time.
+    let todo = get_todo_with(&conn, &todo.id)
+        .map_err(|e| format!("Failed to load todo: {}", e))?
+        .ok_or_else(|| format!("Unknown todo '{}'", todo.id))?;
+    Ok(Some(TodoUpdated {
+        todo,
+        changed_fields,
+        warnings,
+    }))
+}
+
+/// Tells the search index and every window about a written update;
+/// `previous` is the todo it replaced. Live queries and the activity feed
+/// hear about it through [`changes`].
+pub fn announce_update(
+    app: &AppHandle,
+    storage: &Storage,
+    previous: Option<&Todo>,
+    update: &TodoUpdated,
+) {
+    if let Ok(todos) = storage.list_todos() {
+        app.state::<SearchIndex>().rebuild(&todos);
+        if let Some(previous) = previous {
+            references::notify_stale_references(app, &todos, previous, &update.todo);
+        }
+    }
+    let _ = events::emit(app, "todo-updated", update);
+}
+
+/// Writes an update the way [`update_todo`] does, checked against `rules`:
+/// the todo's staged draft goes first, and every write is announced.
+/// Requires `Storage`, `Drafts` and `SearchIndex`.
+pub fn update_and_announce(
+    app: &AppHandle,
+    rules: &[ValidationRule],
+    todo: Todo,
+    base: Option<&Todo>,
+) -> Result<Option<TodoUpdated>, UpdateError> {
+    let storage = app.state::<Storage>();
+    let checked = RuleSet::load(&storage, rules)?;
+    // A staged draft is written first, so this update merges over it.
+    for (previous, update) in app
+        .state::<drafts::Drafts>()
+        .flush_todo(&storage, rules, &todo.id)
+    {
+        validation_rules::note_warnings(&storage, &update.warnings);
+        announce_update(app, &storage, Some(&previous), &update);
+    }
+    let previous = storage.get_todo(&todo.id).ok().flatten();
+    let Some(update) = apply_checked_update(&storage, todo, base, &checked)? else {
+        return Ok(None);
+    };
+    validation_rules::note_warnings(&storage, &update.warnings);
+    announce_update(app, &storage, previous.as_ref(), &update);
+    Ok(Some(update))
+}
+
+/// Updates a cached todo and emits `todo-updated` with the names of the
+/// changed fields and the new revision, so every window can patch its copy.
+#[tauri::command]
+pub fn update_todo(
+    todo: Todo,
+    base: Option<Todo>,
+    app: AppHandle,
+    settings: State<'_, SettingsState>,
+) -> Result<TodoUpdated, UpdateError> {
+    let rules = settings.get().validation_rules;
+    let update = update_and_announce(&app, &rules, todo.clone(), base.as_ref())?;
+    Ok(update.unwrap_or(TodoUpdated {
+        todo,
+        changed_fields: Vec::new(),
+        warnings: Vec::new(),
+    }))
+}
+
+#[tauri::command]
+pub fn get_storage_mode(storage: State<'_, Storage>) -> StorageMode {
+    storage.mode()
+}
+
+/// Where older versions kept their data, whether or not it is still there.
+pub fn legacy_data_dir(app: &AppHandle) -> tauri::Result<PathBuf> {
+    Ok(app.path().data_dir()?.join(LEGACY_DIR))
+}
+
+/// The database in `dir` if it looks like one older versions wrote: a
+/// `todos.db` whose `todos` table has their camelCase columns. It is only
+/// opened read-only, so an unrelated file of the same name is left alone.
+pub fn detect_legacy_database(dir: &Path) -> Option<PathBuf> {
+    let path = dir.join(LEGACY_DATABASE_FILE);
+    is_legacy_database(&path).then_some(path)
+}
+
+/// Whether `path` is a database older versions wrote, under any name.
+pub(crate) fn is_legacy_database(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    let Ok(conn) = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return false;
+    };
+    legacy_columns(&conn).is_ok_and(|columns| {
+        LEGACY_COLUMNS
+            .iter()
+            .all(|column| columns.contains(*column))
+    })
+}
+
+pub(crate) fn legacy_columns(conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('todos')")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Reads the todos of a legacy database. Its priorities were numbers (0 to
+/// 2) before they were strings, and its timestamps either RFC 3339 or
+/// SQLite's `CURRENT_TIMESTAMP` format. Rows without an id or title are
+/// left out, and missing timestamps become `now`.
+pub fn read_legacy_todos(path: &Path, now: DateTime<Utc>) -> rusqlite::Result<Vec<Todo>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let columns = legacy_columns(&conn)?;
+    let optional = |column: &str| {
+        if columns.contains(column) {
+            column.to_string()
+        } else {
+            format!("NULL AS {}", column)
+        }
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, title, {}, completed, {}, {}, createdAt, updatedAt, {} FROM todos
+         ORDER BY {}, createdAt",
+        optional("description"),
+        optional("priority"),
+        optional("scheduledFor"),
+        optional("order_index"),
+        if columns.contains("order_index") {
+            "order_index"
+        } else {
+            "rowid"
+        },
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        let (Some(id), Some(title)) = (
+            row.get::<_, Option<String>>(0)?,
+            row.get::<_, Option<String>>(1)?,
+        ) else {
+            return Ok(None);
+        };
+        let created_at = legacy_timestamp(row.get_ref(6)?).unwrap_or(now);
+        Ok(Some(Todo {
+            id,
+            title,
+            description: row.get(2)?,
+            completed: match row.get_ref(3)? {
+                ValueRef::Integer(completed) => completed != 0,
+                ValueRef::Text(completed) => completed == b"true",
+                _ => false,
+            },
+            priority: legacy_priority(row.get_ref(4)?),
+            scheduled_for: legacy_timestamp(row.get_ref(5)?),
+            created_at,
+            updated_at: legacy_timestamp(row.get_ref(7)?).unwrap_or(created_at),
+            completed_at: None,
+            order: row.get(8)?,
+            rank: None,
+            schedule_id: None,
+            tags: Vec::new(),
+            languages: TextLanguages::default(),
+            fields: Default::default(),
+            estimate_minutes: None,
+            actual_minutes: None,
+            revision: 0,
+        }))
+    })?;
+    Ok(rows
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+pub(crate) fn legacy_priority(value: ValueRef<'_>) -> Priority {
+    match value {
+        ValueRef::Integer(0) => Priority::Low,
+        ValueRef::Integer(2) => Priority::High,
+        ValueRef::Text(text) => std::str::from_utf8(text)
+            .ok()
+            .and_then(Priority::parse)
+            .unwrap_or(Priority::Medium),
+        _ => Priority::Medium,
+    }
+}
+
+pub(crate) fn legacy_timestamp(value: ValueRef<'_>) -> Option<DateTime<Utc>> {
+    let ValueRef::Text(text) = value else {
+        return None;
+    };
+    let text = std::str::from_utf8(text).ok()?;
+    DateTime::parse_from_rfc3339(text)
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|time| time.and_utc())
+        })
+}
+
+/// Result of `migrate_legacy_data`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    pub source: PathBuf,
+    /// Copy of the store taken before anything was merged.
+    pub backup: Option<PathBuf>,
+    pub imported: usize,
+    /// Legacy todos whose id the store already had; those are kept as is.
+    pub skipped: usize,
+    /// The source was merged before, so nothing was read this time.
+    pub already_migrated: bool,
+}
+
+/// Whether `source` was merged by an earlier [`migrate_legacy`].
+pub fn legacy_migrated(conn: &Connection, source: &Path) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM legacy_migrations WHERE source = ?1",
+        [source.to_string_lossy()],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|row| row.is_some())
+}
+
+/// Copies the database to `<files>/backups/<name>-<timestamp>.db`.
+pub fn back_up(storage: &Storage, name: &str) -> Result<PathBuf, String> {
+    let dir = storage.files_dir().join(BACKUPS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let stem = format!("{}-{}", name, storage.clock().now().format("%Y%m%dT%H%M%S"));
+    // Numbered when several backups are taken within a second.
+    let path = (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("{}.db", stem)),
+            n => dir.join(format!("{}-{}.db", stem, n)),
+        })
+        .find(|path| !path.exists())
+        .unwrap_or_default();
+    storage
+        .conn()
+        .execute("VACUUM INTO ?1", [path.to_string_lossy()])
+        .map_err(|e| format!("Failed to back up the database: {}", e))?;
+    activity::backup_created(storage, name);
+    Ok(path)
+}
+
+/// Inserts the todos the store doesn't have yet, records `source` as
+/// migrated and returns how many todos the store now holds.
+fn merge_legacy(
+    tx: &Connection,
+    todos: &[Todo],
+    source: &Path,
+    now: DateTime<Utc>,
+    report: &mut MigrationReport,
+) -> rusqlite::Result<u64> {
+    for todo in todos {
+        if get_todo_with(tx, &todo.id)?.is_some() {
+            report.skipped += 1;
+        } else {
+            insert_todo(tx, todo)?;
+            report.imported += 1;
+        }
+    }
+    tx.execute(
+        "INSERT INTO legacy_migrations (source, migrated_at, imported) VALUES (?1, ?2, ?3)",
+        params![source.to_string_lossy(), now, report.imported as i64],
+    )?;
+    quota::count_todos(tx)
+}
+
+/// Merges the todos of the legacy database at `source` into the store,
+/// after backing the store up. Todos the store already has are skipped,
+/// and the source is recorded so later calls do nothing.
+pub fn migrate_legacy(storage: &Storage, source: &Path) -> Result<MigrationReport, String> {
+    let mut report = MigrationReport {
+        source: source.to_path_buf(),
+        backup: None,
+        imported: 0,
+        skipped: 0,
+        already_migrated: false,
+    };
+    if legacy_migrated(&storage.conn(), source)
+        .map_err(|e| format!("Failed to read migrations: {}", e))?
+    {
+        report.already_migrated = true;
+        return Ok(report);
+    }
+    let todos = read_legacy_todos(source, storage.clock().now())
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    report.backup = Some(back_up(storage, "before-legacy-migration")?);
+
+    let failed = |e: rusqlite::Error| format!("Failed to merge {}: {}", source.display(), e);
+    let mut conn = storage.conn();
+    let tx = conn.transaction().map_err(failed)?;
+    let total =
+        merge_legacy(&tx, &todos, source, storage.clock().now(), &mut report).map_err(failed)?;
+    quota::check_todos(storage, total).map_err(|e| e.to_string())?;
+    tx.commit().map_err(failed)?;
+    Ok(report)
+}
+
+/// Legacy data found at startup that hasn't been merged yet.
+#[derive(Default)]
+pub struct LegacyData {
+    found: Mutex<Option<PathBuf>>,
+}
+
+impl LegacyData {
+    fn found(&self) -> MutexGuard<'_, Option<PathBuf>> {
+        self.found.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Looks for data older versions left behind and, unless it was merged
+/// already, remembers it for `find_legacy_data` and emits
+/// `legacy-data-found`. Requires `Storage` to be managed.
+pub fn detect_legacy_data_at_startup(app: &AppHandle) {
+    let storage = app.state::<Storage>();
+    if storage.mode().ephemeral {
+        return;
+    }
+    let Some(source) = legacy_data_dir(app)
+        .ok()
+        .and_then(|dir| detect_legacy_database(&dir))
+    else {
+        return;
+    };
+    let migrated = legacy_migrated(&storage.conn(), &source);
+    match migrated {
+        Ok(true) => {}
+        Ok(false) => {
+            trace::log(format!(
+                "Found data of an older version: {}",
+                source.display()
+            ));
+            *app.state::<LegacyData>().found() = Some(source.clone());
+            let _ = events::emit(app, "legacy-data-found", &source);
+        }
+        Err(e) => trace::log(format!("Failed to read migrations: {}", e)),
+    }
+}
+
+/// The legacy database waiting to be merged, if any.
+#[tauri::command]
+pub fn find_legacy_data(legacy: State<'_, LegacyData>) -> Option<PathBuf> {
+    legacy.found().clone()
+}
+
+/// Merges the data older versions left behind into the store; see
+/// [`migrate_legacy`]. Calling it again reports `alreadyMigrated`.
+#[tauri::command]
+pub fn migrate_legacy_data(
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    index: State<'_, SearchIndex>,
+    legacy: State<'_, LegacyData>,
+    progress: State<'_, ProgressRegistry>,
+) -> Result<MigrationReport, String> {
+    let found = legacy.found().clone();
+    let source = found
+        .or_else(|| {
+            legacy_data_dir(&app)
+                .ok()
+                .and_then(|dir| detect_legacy_database(&dir))
+        })
+        .ok_or_else(|| "No data of an older version was found".to_string())?;
+    let _progress = progress.register(
+        "migrate-legacy-data",
+        "migration",
+        None,
+        storage.clock().now(),
+    )?;
+    let report = migrate_legacy(&storage, &source)?;
+    *legacy.found() = None;
+    if report.imported > 0 {
+        if let Ok(todos) = storage.list_todos() {
+            index.rebuild(&todos);
+        }
+        let _ = events::emit(&app, "todo-cache-updated", ());
+        quota::notify(&app);
+    }
+    Ok(report)
+}
+
+/// Runs `test` once against each backend, for backend parity tests.
+#[cfg(test)]
+pub fn for_each_backend(mut test: impl FnMut(&Storage)) {
+    let dir = tempfile::tempdir().unwrap();
+    for backend in [
+        StorageBackend::InMemory,
+        StorageBackend::Disk(dir.path().to_path_buf()),
+    ] {
+        test(&Storage::open(backend).unwrap());
+    }
+}
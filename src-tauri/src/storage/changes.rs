@@ -0,0 +1,636 @@
+//! Change data capture for the todo cache. Every committed write to a todo
+//! or one of its field values leaves a compact [`Change`] in the `changes`
+//! table, however it was written: single updates, bulk edits and their
+//! undo, imports, syncs and data migrations alike. Consumers follow the
+//! stream with their own cursor instead of each hooking the write paths.
+//!
+//! Triggers on each writing connection append the records, numbered by
+//! `seq` and grouped by the transaction that wrote them. When a transaction
+//! commits, a dispatcher thread reads its records back and broadcasts them.
+//! A consumer that falls behind misses broadcasts rather than slowing the
+//! writers down, and catches up from the table. Registered consumers keep
+//! their cursor in `change_consumers`; records every one of them has passed
+//! are pruned, and at most [`RING_SIZE`] are kept for the rest.
+
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{params, Connection, Row};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use super::Storage;
+use crate::activity;
+use crate::automation;
+use crate::cache::Caches;
+use crate::completion;
+use crate::events;
+use crate::live_query;
+use crate::protocol::EventPayload;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// Records kept for unregistered readers, such as windows, that fell behind.
+pub const RING_SIZE: i64 = 10_000;
+/// Most records read or broadcast at once.
+const BATCH_SIZE: usize = 1_000;
+/// Broadcasts a consumer may have queued before it misses some.
+const BACKLOG: usize = 64;
+/// How long the dispatcher waits for a commit to become readable.
+const VISIBILITY_TIMEOUT: Duration = Duration::from_millis(200);
+/// Distinct todos above which live queries are recomputed from scratch.
+const LIVE_QUERY_RESET_THRESHOLD: usize = 100;
+/// SQL function the triggers call for the writing transaction's id.
+const TX_FUNCTION: &str = "yutodo_change_tx";
+
+/// Todo fields as named in the API, with the columns that hold them.
+const TODO_FIELDS: &[(&str, &[&str])] = &[
+    ("title", &["title"]),
+    ("description", &["description"]),
+    ("completed", &["completed"]),
+    ("priority", &["priority"]),
+    ("scheduledFor", &["scheduled_for"]),
+    ("createdAt", &["created_at"]),
+    ("updatedAt", &["updated_at"]),
+    ("completedAt", &["completed_at"]),
+    ("order", &["sort_order"]),
+    ("scheduleId", &["schedule_id"]),
+    ("tags", &["tags"]),
+    ("rank", &["rank"]),
+    ("languages", &["title_language", "description_language"]),
+    ("estimateMinutes", &["estimate_minutes"]),
+    ("actualMinutes", &["actual_minutes"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl ChangeOp {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "insert" => Some(ChangeOp::Insert),
+            "update" => Some(ChangeOp::Update),
+            "delete" => Some(ChangeOp::Delete),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    /// Position in the stream; a cursor is the last `seq` consumed.
+    pub seq: i64,
+    /// Shared by the changes committed together.
+    pub tx: i64,
+    pub entity: String,
+    pub id: String,
+    pub op: ChangeOp,
+    /// Fields an update changed; `fields` for custom field values.
+    pub changed_fields: Vec<String>,
+    /// Revision after the change, or before it for a delete.
+    pub revision: Option<u64>,
+}
+
+/// Changes after a cursor; the payload of `todos-changed`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangesSince {
+    pub changes: Vec<Change>,
+    /// Pass back to continue after these changes.
+    pub cursor: i64,
+    /// Changes after the given cursor were pruned; reload everything.
+    pub truncated: bool,
+}
+
+impl EventPayload for ChangesSince {}
+
+type Broadcast = Arc<Vec<Change>>;
+
+/// Owned by [`Storage`]: hands out transaction ids to the triggers and
+/// broadcasts committed changes to the subscribers.
+pub struct ChangeFeed {
+    last_tx: AtomicI64,
+    committed: Sender<i64>,
+    dispatch: Mutex<Option<Receiver<i64>>>,
+    /// Each with the flag set when a broadcast didn't fit its backlog.
+    subscribers: Mutex<Vec<(SyncSender<Broadcast>, Arc<AtomicBool>)>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl ChangeFeed {
+    pub fn new() -> Arc<Self> {
+        let (committed, dispatch) = mpsc::channel();
+        Arc::new(Self {
+            last_tx: AtomicI64::new(0),
+            committed,
+            dispatch: Mutex::new(Some(dispatch)),
+            subscribers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Receives every committed change from now on, in order. A subscriber
+    /// more than [`BACKLOG`] broadcasts behind misses some and gets the
+    /// returned flag set; [`Follower`] then reads them from the table.
+    fn subscribe(&self) -> (Receiver<Broadcast>, Arc<AtomicBool>) {
+        let (sender, receiver) = mpsc::sync_channel(BACKLOG);
+        let lagged = Arc::new(AtomicBool::new(false));
+        lock(&self.subscribers).push((sender, lagged.clone()));
+        (receiver, lagged)
+    }
+
+    fn broadcast(&self, changes: Broadcast) {
+        lock(&self.subscribers).retain(|(subscriber, lagged)| {
+            match subscriber.try_send(changes.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    lagged.store(true, Ordering::SeqCst);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+}
+
+/// Lets `conn` capture changes into `feed`: registers the transaction id
+/// function and commit hooks, and the triggers once the `changes` table
+/// exists.
+pub(super) fn install(conn: &Connection, feed: &Arc<ChangeFeed>) -> rusqlite::Result<()> {
+    // The transaction writing through this connection, if it wrote a change.
+    let open = Arc::new(Mutex::new(None::<i64>));
+
+    let (tx_feed, tx_open) = (feed.clone(), open.clone());
+    conn.create_scalar_function(TX_FUNCTION, 0, FunctionFlags::SQLITE_UTF8, move |_| {
+        Ok(*lock(&tx_open)
+            .get_or_insert_with(|| tx_feed.last_tx.fetch_add(1, Ordering::SeqCst) + 1))
+    })?;
+    let (commit_feed, committed) = (feed.clone(), open.clone());
+    conn.commit_hook(Some(move || {
+        if let Some(tx) = lock(&committed).take() {
+            let _ = commit_feed.committed.send(tx);
+        }
+        false
+    }));
+    conn.rollback_hook(Some(move || {
+        lock(&open).take();
+    }));
+    install_triggers(conn, feed)
+}
+
+/// SQL for the JSON array of the fields that differ between `old` and `new`.
+fn changed_fields_sql(old: &str, new: &str) -> String {
+    let selects: Vec<String> = TODO_FIELDS
+        .iter()
+        .map(|(field, columns)| {
+            let differs: Vec<String> = columns
+                .iter()
+                .map(|column| format!("{old}.{column} IS NOT {new}.{column}"))
+                .collect();
+            format!("SELECT '{}' AS field WHERE {}", field, differs.join(" OR "))
+        })
+        .collect();
+    format!(
+        "(SELECT json_group_array(field) FROM ({}))",
+        selects.join(" UNION ALL ")
+    )
+}
+
+/// Creates this connection's capture triggers if the `changes` table
+/// exists, so a migration can't run them before it does. Temporary
+/// triggers leave other tools writing to the database unaffected.
+pub(super) fn install_triggers(conn: &Connection, feed: &ChangeFeed) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'changes')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(());
+    }
+    let last_tx: i64 = conn.query_row("SELECT COALESCE(MAX(tx), 0) FROM changes", [], |row| {
+        row.get(0)
+    })?;
+    feed.last_tx.fetch_max(last_tx, Ordering::SeqCst);
+
+    let record = "INSERT INTO changes (tx, entity, entity_id, op, changed_fields, revision)";
+    // `INSERT OR REPLACE` deletes the old row without running delete
+    // triggers, so the insert trigger compares with the row it replaces.
+    conn.execute_batch(&format!(
+        "CREATE TEMP TRIGGER IF NOT EXISTS capture_todo_insert
+         BEFORE INSERT ON main.todos
+         BEGIN
+             {record}
+             SELECT {TX_FUNCTION}(), 'todo', NEW.id,
+                 CASE WHEN prior.id IS NULL THEN 'insert' ELSE 'update' END,
+                 CASE WHEN prior.id IS NULL THEN '[]' ELSE {replaced} END,
+                 NEW.revision
+             FROM (SELECT 1) LEFT JOIN main.todos AS prior ON prior.id = NEW.id;
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS capture_todo_update
+         AFTER UPDATE ON main.todos
+         BEGIN
+             {record}
+             VALUES ({TX_FUNCTION}(), 'todo', NEW.id, 'update', {updated}, NEW.revision);
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS capture_todo_delete
+         AFTER DELETE ON main.todos
+         BEGIN
+             {record}
+             VALUES ({TX_FUNCTION}(), 'todo', OLD.id, 'delete', '[]', OLD.revision);
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS capture_field_value_insert
+         AFTER INSERT ON main.todo_field_values
+         BEGIN
+             {record}
+             SELECT {TX_FUNCTION}(), 'todo', id, 'update', '[\"fields\"]', revision
+             FROM main.todos WHERE id = NEW.todo_id;
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS capture_field_value_update
+         AFTER UPDATE ON main.todo_field_values
+         BEGIN
+             {record}
+             SELECT {TX_FUNCTION}(), 'todo', id, 'update', '[\"fields\"]', revision
+             FROM main.todos WHERE id = NEW.todo_id;
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS capture_field_value_delete
+         AFTER DELETE ON main.todo_field_values
+         BEGIN
+             {record}
+             SELECT {TX_FUNCTION}(), 'todo', id, 'update', '[\"fields\"]', revision
+             FROM main.todos WHERE id = OLD.todo_id;
+         END;",
+        replaced = changed_fields_sql("prior", "NEW"),
+        updated = changed_fields_sql("OLD", "NEW"),
+    ))
+}
+
+fn change_from_row(row: &Row<'_>) -> rusqlite::Result<Change> {
+    let op: String = row.get(4)?;
+    Ok(Change {
+        seq: row.get(0)?,
+        tx: row.get(1)?,
+        entity: row.get(2)?,
+        id: row.get(3)?,
+        op: ChangeOp::parse(&op).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                4,
+                rusqlite::types::Type::Text,
+                format!("unknown change op '{}'", op).into(),
+            )
+        })?,
+        changed_fields: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+        revision: row
+            .get::<_, Option<i64>>(6)?
+            .map(|revision| revision as u64),
+    })
+}
+
+/// The `seq` of the last change ever recorded, pruned or not.
+pub fn head(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(seq), 0) FROM sqlite_sequence WHERE name = 'changes'",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Up to `limit` changes after `cursor`, in order.
+pub fn changes_since(
+    conn: &Connection,
+    cursor: i64,
+    limit: usize,
+) -> rusqlite::Result<ChangesSince> {
+    let oldest: Option<i64> =
+        conn.query_row("SELECT MIN(seq) FROM changes", [], |row| row.get(0))?;
+    let head = head(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT seq, tx, entity, entity_id, op, changed_fields, revision FROM changes
+         WHERE seq > ?1 ORDER BY seq LIMIT ?2",
+    )?;
+    let changes = stmt
+        .query_map(params![cursor, limit as i64], change_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(ChangesSince {
+        cursor: changes.last().map_or(cursor.max(head), |change| change.seq),
+        truncated: cursor < oldest.unwrap_or(head + 1) - 1,
+        changes,
+    })
+}
+
+/// Registers `consumer` at the current head unless it already is, and
+/// returns its cursor.
+pub fn register(conn: &Connection, consumer: &str) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT OR IGNORE INTO change_consumers (name, cursor) VALUES (?1, ?2)",
+        params![consumer, head(conn)?],
+    )?;
+    conn.query_row(
+        "SELECT cursor FROM change_consumers WHERE name = ?1",
+        [consumer],
+        |row| row.get(0),
+    )
+}
+
+/// Records that `consumer` has handled everything up to `cursor`, then
+/// prunes what no registered consumer still needs.
+pub fn acknowledge(conn: &Connection, consumer: &str, cursor: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE change_consumers SET cursor = MAX(cursor, ?2) WHERE name = ?1",
+        params![consumer, cursor],
+    )?;
+    prune(conn)?;
+    Ok(())
+}
+
+/// Deletes the changes every registered consumer has passed, and all but
+/// the last [`RING_SIZE`]. Returns how many were deleted.
+pub fn prune(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM changes
+         WHERE seq <= (SELECT MIN(cursor) FROM change_consumers) OR seq <= ?1",
+        [head(conn)? - RING_SIZE],
+    )
+}
+
+/// Broadcasts what each commit wrote. Reads with its own connection and
+/// ends once the feed is dropped with the store.
+pub(super) fn spawn_dispatcher(feed: &Arc<ChangeFeed>, conn: Connection) -> rusqlite::Result<()> {
+    let Some(committed) = lock(&feed.dispatch).take() else {
+        return Ok(());
+    };
+    // In-memory stores share a cache with table-level locks, where this
+    // reader would otherwise make the writers fail with "table is locked".
+    conn.pragma_update(None, "read_uncommitted", true)?;
+    let feed = Arc::downgrade(feed);
+    std::thread::spawn(move || dispatch(&feed, &committed, &conn));
+    Ok(())
+}
+
+fn dispatch(feed: &Weak<ChangeFeed>, committed: &Receiver<i64>, conn: &Connection) {
+    let mut cursor = head(conn).unwrap_or(0);
+    let mut last_tx = 0;
+    while let Ok(mut tx) = committed.recv() {
+        while let Ok(next) = committed.try_recv() {
+            tx = tx.max(next);
+        }
+        if tx <= last_tx {
+            // Already broadcast along with an earlier commit.
+            continue;
+        }
+        // The commit hook runs just before the commit lands; a transaction
+        // whose only statement failed never shows up, hence the timeout.
+        // Real time, not the app's clock: this waits for SQLite.
+        let deadline = Instant::now() + VISIBILITY_TIMEOUT;
+        loop {
+            // Transactions write one at a time, so the committed changes
+            // are a prefix of what an uncommitted read sees.
+            let batch = changes_since(conn, cursor, BATCH_SIZE).map(|mut batch| {
+                batch.changes.retain(|change| change.tx <= tx);
+                batch
+            });
+            match batch {
+                Ok(batch) if !batch.changes.is_empty() => {
+                    cursor = batch.changes.last().map_or(cursor, |change| change.seq);
+                    last_tx = last_tx.max(batch.changes.iter().map(|c| c.tx).max().unwrap_or(0));
+                    let Some(feed) = feed.upgrade() else {
+                        return;
+                    };
+                    feed.broadcast(Arc::new(batch.changes));
+                    if last_tx >= tx {
+                        break;
+                    }
+                }
+                Ok(_) | Err(_) if Instant::now() >= deadline => break,
+                _ => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    }
+}
+
+/// Follows the stream from a cursor, getting broadcasts while it keeps up
+/// and reading the table after it fell behind.
+pub struct Follower {
+    consumer: Option<&'static str>,
+    cursor: i64,
+    broadcasts: Receiver<Broadcast>,
+    lagged: Arc<AtomicBool>,
+    /// Set while the table has changes the broadcasts won't repeat.
+    behind: bool,
+}
+
+impl Follower {
+    /// Follows as registered `consumer` from its stored cursor, or from
+    /// the current head for a consumer that only cares about what comes.
+    pub fn new(storage: &Storage, consumer: Option<&'static str>) -> rusqlite::Result<Self> {
+        let (broadcasts, lagged) = storage.changes.subscribe();
+        let conn = storage.conn();
+        let cursor = match consumer {
+            Some(consumer) => register(&conn, consumer)?,
+            None => head(&conn)?,
+        };
+        Ok(Self {
+            consumer,
+            cursor,
+            broadcasts,
+            lagged,
+            behind: cursor < head(&conn)?,
+        })
+    }
+
+    fn catch_up(&mut self, storage: &Storage) -> rusqlite::Result<ChangesSince> {
+        let changes = changes_since(&storage.conn(), self.cursor, BATCH_SIZE)?;
+        self.behind = changes.changes.len() == BATCH_SIZE;
+        Ok(changes)
+    }
+
+    /// Waits for the changes after the cursor and moves past them. `None`
+    /// once the store is closed.
+    pub fn next(&mut self, storage: &Storage) -> Option<ChangesSince> {
+        loop {
+            let changes = if self.behind || self.lagged.swap(false, Ordering::SeqCst) {
+                self.catch_up(storage)
+            } else {
+                let broadcast = self.broadcasts.recv().ok()?;
+                match (broadcast.first(), broadcast.last()) {
+                    (_, Some(last)) if last.seq <= self.cursor => continue,
+                    (Some(first), Some(last)) if first.seq <= self.cursor + 1 => Ok(ChangesSince {
+                        changes: broadcast
+                            .iter()
+                            .filter(|change| change.seq > self.cursor)
+                            .cloned()
+                            .collect(),
+                        cursor: last.seq,
+                        truncated: false,
+                    }),
+                    // Broadcasts were missed while this follower lagged.
+                    _ => self.catch_up(storage),
+                }
+            };
+            match changes {
+                Ok(changes) if changes.changes.is_empty() && !changes.truncated => {
+                    self.cursor = changes.cursor;
+                }
+                Ok(changes) => {
+                    self.cursor = changes.cursor;
+                    return Some(changes);
+                }
+                Err(e) => {
+                    trace::log(format!("Failed to read changes: {}", e));
+                    self.behind = false;
+                }
+            }
+        }
+    }
+
+    /// Stores the cursor of a registered consumer, so it resumes there.
+    pub fn acknowledge(&self, storage: &Storage) {
+        if let Some(consumer) = self.consumer {
+            if let Err(e) = acknowledge(&storage.conn(), consumer, self.cursor) {
+                trace::log(format!("Failed to store the {} cursor: {}", consumer, e));
+            }
+        }
+    }
+}
+
+/// Runs `handle` on its own thread for every batch of changes. A registered
+/// `consumer` resumes where it left off after a restart.
+fn consume(
+    app: &AppHandle,
+    consumer: Option<&'static str>,
+    mut handle: impl FnMut(&AppHandle, &Storage, &ChangesSince) + Send + 'static,
+) {
+    let mut follower = match Follower::new(&app.state::<Storage>(), consumer) {
+        Ok(follower) => follower,
+        Err(e) => {
+            trace::log(format!("Failed to follow changes: {}", e));
+            return;
+        }
+    };
+    let app = app.clone();
+    std::thread::spawn(trace::bind(move || {
+        let storage = app.state::<Storage>();
+        while let Some(changes) = follower.next(&storage) {
+            handle(&app, &storage, &changes);
+            follower.acknowledge(&storage);
+        }
+    }));
+}
+
+fn changed_ids(changes: &ChangesSince) -> Vec<&str> {
+    let mut ids: Vec<&str> = Vec::new();
+    for change in &changes.changes {
+        if !ids.contains(&change.id.as_str()) {
+            ids.push(&change.id);
+        }
+    }
+    ids
+}
+
+fn update_live_queries(app: &AppHandle, storage: &Storage, changes: &ChangesSince) {
+    let ids = changed_ids(changes);
+    let reset = changes.truncated
+        || ids.len() > LIVE_QUERY_RESET_THRESHOLD
+        || changes.changes.iter().any(|c| c.op == ChangeOp::Delete);
+    if reset {
+        if let Ok(todos) = storage.list_todos() {
+            live_query::notify_reset(app, &todos);
+        }
+        return;
+    }
+    let todos: Vec<_> = ids
+        .into_iter()
+        .filter_map(|id| storage.get_todo(id).ok().flatten())
+        .collect();
+    live_query::notify_upserted(app, &todos);
+}
+
+/// Counts each completion on the day it was written, and announces those
+/// written after `resumed_at`. Older ones are caught up on after a restart,
+/// with no window that saw them happen.
+fn count_completions(app: &AppHandle, storage: &Storage, changes: &ChangesSince, resumed_at: i64) {
+    for change in &changes.changes {
+        let completing =
+            change.op == ChangeOp::Update && change.changed_fields.iter().any(|f| f == "completed");
+        if !completing {
+            continue;
+        }
+        let Ok(Some(todo)) = storage.get_todo(&change.id) else {
+            continue;
+        };
+        // Reopened since.
+        let Some(completed_at) = todo.completed_at else {
+            continue;
+        };
+        activity::todo_completed(storage, completed_at);
+        if change.seq > resumed_at {
+            completion::announce(app, storage, &todo, completed_at);
+        }
+    }
+}
+
+/// Starts the app's consumers: live queries (and through them the calendar
+/// sync), the activity feed and `todo-completed`, the `todos-changed` event
+/// for windows, the rules and the caches.
+pub fn start_consumers(app: &AppHandle) {
+    if app.state::<Storage>().newer_schema().is_some() {
+        return;
+    }
+    // Everything up to here was written before this run.
+    let resumed_at = match head(&app.state::<Storage>().conn()) {
+        Ok(head) => {
+            app.state::<Caches>().reset(head);
+            head
+        }
+        Err(_) => 0,
+    };
+    consume(app, None, |app, _, changes| {
+        app.state::<Caches>().invalidate(changes);
+    });
+    consume(app, None, update_live_queries);
+    consume(app, Some("activity"), move |app, storage, changes| {
+        count_completions(app, storage, changes, resumed_at)
+    });
+    consume(app, None, |app, _, changes| {
+        let _ = events::emit(app, "todos-changed", changes);
+    });
+    consume(app, Some("rules"), automation::run_rules);
+}
+
+/// Changes after `cursor`, for a window that missed `todos-changed` events.
+/// Without a cursor, returns none and the current one.
+#[tauri::command]
+pub fn get_changes_since(
+    cursor: Option<i64>,
+    limit: Option<usize>,
+    storage: State<'_, Storage>,
+) -> Result<ChangesSince, String> {
+    let conn = storage.conn();
+    let result = match cursor {
+        Some(cursor) => changes_since(
+            &conn,
+            cursor,
+            limit.unwrap_or(BATCH_SIZE).clamp(1, BATCH_SIZE),
+        ),
+        None => head(&conn).map(|head| ChangesSince {
+            changes: Vec::new(),
+            cursor: head,
+            truncated: false,
+        }),
+    };
+    result.map_err(|e| format!("Failed to read changes: {}", e))
+}
@@ -0,0 +1,196 @@
+use super::*;
+use crate::bulk_edit::{apply_changes, undo_changes, BulkChange};
+use crate::import;
+use crate::storage::{apply_update, for_each_backend, insert_todo, migrate, MIGRATIONS};
+use crate::types::Todo;
+use crate::validation_rules::RuleSet;
+
+fn todo(id: &str) -> Todo {
+    let now = chrono::Utc::now();
+    Todo::sample(id, format!("Task {}", id), now)
+}
+
+fn all_changes(storage: &Storage) -> Vec<Change> {
+    changes_since(&storage.conn(), 0, usize::MAX)
+        .unwrap()
+        .changes
+}
+
+fn summary(changes: &[Change]) -> Vec<(&str, ChangeOp, Vec<&str>)> {
+    changes
+        .iter()
+        .map(|c| {
+            let fields = c.changed_fields.iter().map(String::as_str).collect();
+            (c.id.as_str(), c.op, fields)
+        })
+        .collect()
+}
+
+#[test]
+fn test_single_writes_are_captured_with_changed_fields() {
+    for_each_backend(|storage| {
+        storage.save_todo(&todo("a")).unwrap();
+        let mut edited = storage.get_todo("a").unwrap().unwrap();
+        edited.title = "Renamed".to_string();
+        let update = apply_update(storage, edited, None).unwrap().unwrap();
+        storage.replace_todos(&[todo("a"), todo("b")]).unwrap();
+        storage.reorder_todo("b", None, Some("a")).unwrap();
+        storage.replace_todos(&[todo("b")]).unwrap();
+
+        let changes = all_changes(storage);
+        assert_eq!(changes[1].revision, Some(update.todo.revision));
+        let changes = summary(&changes);
+        assert_eq!(changes[0], ("a", ChangeOp::Insert, vec![]));
+        assert_eq!(changes[1], ("a", ChangeOp::Update, vec!["title"]));
+        assert!(changes.contains(&("b", ChangeOp::Update, vec!["rank"])));
+        assert!(changes.contains(&("a", ChangeOp::Delete, vec![])));
+    });
+}
+
+#[test]
+fn test_bulk_writes_share_a_transaction_in_input_order() {
+    let storage = Storage::open_in_memory().unwrap();
+    let todos: Vec<Todo> = ["c", "a", "b"].into_iter().map(todo).collect();
+    storage.replace_todos(&todos).unwrap();
+
+    let changes = all_changes(&storage);
+    let ids: Vec<&str> = changes.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, ["c", "a", "b"]);
+    assert!(changes.iter().all(|c| c.tx == changes[0].tx));
+
+    storage.save_todo(&todo("d")).unwrap();
+    assert!(all_changes(&storage).last().unwrap().tx > changes[0].tx);
+}
+
+#[test]
+fn test_bulk_edit_and_undo_are_captured() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a"), todo("b")]).unwrap();
+    let before = storage.get_todo("a").unwrap().unwrap();
+    let mut after = before.clone();
+    after.completed = true;
+    let edits = [
+        BulkChange::Update {
+            before: Box::new(before),
+            after: Box::new(after),
+            changed_fields: vec!["completed".to_string()],
+        },
+        BulkChange::Delete {
+            todo: Box::new(storage.get_todo("b").unwrap().unwrap()),
+        },
+    ];
+    let cursor = head(&storage.conn()).unwrap();
+    let previous = apply_changes(&storage, &edits, &RuleSet::default()).unwrap();
+    undo_changes(&storage, &previous).unwrap();
+
+    let changes = changes_since(&storage.conn(), cursor, usize::MAX)
+        .unwrap()
+        .changes;
+    assert_eq!(
+        summary(&changes),
+        [
+            (
+                "a",
+                ChangeOp::Update,
+                vec!["completed", "updatedAt", "completedAt"]
+            ),
+            ("b", ChangeOp::Delete, vec![]),
+            (
+                "a",
+                ChangeOp::Update,
+                vec!["completed", "updatedAt", "completedAt"]
+            ),
+            ("b", ChangeOp::Insert, vec![]),
+        ]
+    );
+    assert_eq!(changes[0].tx, changes[1].tx);
+    assert_ne!(changes[1].tx, changes[2].tx);
+}
+
+#[test]
+fn test_imports_and_migrations_are_captured() {
+    let storage = Storage::open_in_memory().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todos.csv");
+    std::fs::write(&path, "title\nOne\nTwo\n").unwrap();
+    let checkpoint = import::begin_import(&storage, &path, import::ConflictPolicy::Skip).unwrap();
+    import::run_import(
+        &storage,
+        checkpoint,
+        &crate::validation_rules::RuleSet::default(),
+        1000,
+        |_| false,
+        |_| {},
+    )
+    .unwrap();
+
+    let imported = all_changes(&storage);
+    assert_eq!(imported.len(), 2);
+    assert!(imported.iter().all(|c| c.op == ChangeOp::Insert));
+
+    let migrations = [MIGRATIONS, &["UPDATE todos SET title = title || '!'"]].concat();
+    migrate(&storage.conn(), &storage.changes, &migrations).unwrap();
+    let migrated = &all_changes(&storage)[2..];
+    assert_eq!(
+        summary(migrated),
+        [
+            (imported[0].id.as_str(), ChangeOp::Update, vec!["title"]),
+            (imported[1].id.as_str(), ChangeOp::Update, vec!["title"]),
+        ]
+    );
+}
+
+#[test]
+fn test_rolled_back_writes_leave_no_changes() {
+    let storage = Storage::open_in_memory().unwrap();
+    {
+        let mut conn = storage.conn();
+        let tx = conn.transaction().unwrap();
+        insert_todo(&tx, &todo("a")).unwrap();
+        tx.rollback().unwrap();
+    }
+    assert!(all_changes(&storage).is_empty());
+    assert_eq!(head(&storage.conn()).unwrap(), 0);
+}
+
+#[test]
+fn test_lagging_follower_catches_up_in_order() {
+    let storage = Storage::open_in_memory().unwrap();
+    let mut follower = Follower::new(&storage, None).unwrap();
+    // More commits than the broadcast backlog holds; writers never wait.
+    let count = BACKLOG * 2;
+    for i in 0..count {
+        storage.save_todo(&todo(&i.to_string())).unwrap();
+    }
+
+    let mut seen = Vec::new();
+    while seen.len() < count {
+        let changes = follower.next(&storage).unwrap();
+        assert!(!changes.truncated);
+        seen.extend(changes.changes.into_iter().map(|c| c.id));
+    }
+    let expected: Vec<String> = (0..count).map(|i| i.to_string()).collect();
+    assert_eq!(seen, expected);
+    assert_eq!(follower.cursor, head(&storage.conn()).unwrap());
+}
+
+#[test]
+fn test_pruning_waits_for_every_registered_consumer() {
+    let storage = Storage::open_in_memory().unwrap();
+    let conn = storage.conn();
+    register(&conn, "first").unwrap();
+    register(&conn, "second").unwrap();
+    drop(conn);
+    storage.replace_todos(&[todo("a"), todo("b")]).unwrap();
+
+    let conn = storage.conn();
+    let head = head(&conn).unwrap();
+    acknowledge(&conn, "first", head).unwrap();
+    assert_eq!(changes_since(&conn, 0, 10).unwrap().changes.len(), 2);
+
+    acknowledge(&conn, "second", head).unwrap();
+    let after_prune = changes_since(&conn, 0, 10).unwrap();
+    assert!(after_prune.changes.is_empty() && after_prune.truncated);
+    assert_eq!(after_prune.cursor, head);
+    assert!(!changes_since(&conn, head, 10).unwrap().truncated);
+}
@@ -0,0 +1,445 @@
+//! Write coalescing for edits that arrive per keystroke, such as autosave in
+//! the description field. `update_todo_draft` stages a patch; a worker writes
+//! the merged patch once the todo has been quiet for [`QUIET_PERIOD`], and at
+//! the latest [`MAX_DELAY`] after its first patch. Each write is an ordinary
+//! update, so `todo-updated` (and with it plugin hooks), live queries and the
+//! search index hear about a burst of typing once.
+//!
+//! Patches go to a journal file before they are acknowledged, and the
+//! journal is rewritten after every flush. After a crash the next start
+//! writes what is left and emits `draft-recovered` with the todo ids.
+//! Replaying a patch that was already written changes nothing, since a patch
+//! only sets values.
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use super::{announce_update, apply_checked_update, Storage, TodoUpdated, UpdateError};
+use crate::events;
+use crate::search::SearchIndex;
+use crate::settings::SettingsState;
+use crate::trace;
+use crate::types::Todo;
+use crate::validation_rules::{self, RuleSet, ValidationRule};
+
+#[cfg(test)]
+mod tests;
+
+/// How long a todo must go without patches before its draft is written.
+pub const QUIET_PERIOD: Duration = Duration::from_millis(500);
+/// Longest a draft waits while patches keep coming.
+pub const MAX_DELAY: Duration = Duration::from_secs(5);
+/// Under the data directory; in-memory stores keep no journal.
+pub const JOURNAL_FILE: &str = "drafts.journal";
+
+/// Todo fields by their camelCase names, e.g. `{"description": "..."}`.
+pub type DraftPatch = serde_json::Map<String, serde_json::Value>;
+
+/// Fields the cache derives or that have their own commands.
+const READ_ONLY_FIELDS: &[&str] = &[
+    "id",
+    "createdAt",
+    "updatedAt",
+    "languages",
+    "fields",
+    "rank",
+    "revision",
+];
+
+struct Draft {
+    patch: DraftPatch,
+    first: Instant,
+    last: Instant,
+}
+
+impl Draft {
+    fn due(&self) -> Instant {
+        (self.last + QUIET_PERIOD).min(self.first + MAX_DELAY)
+    }
+}
+
+/// One line of the journal.
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    id: String,
+    patch: DraftPatch,
+}
+
+#[derive(Default)]
+struct Pending {
+    drafts: HashMap<String, Draft>,
+    /// Whether the flush worker is running.
+    worker: bool,
+}
+
+/// Staged drafts, managed as state.
+#[derive(Default)]
+pub struct Drafts {
+    pending: Mutex<Pending>,
+    journal: Option<PathBuf>,
+}
+
+/// `todo` with `patch` applied.
+fn patched(todo: &Todo, patch: &DraftPatch) -> Result<Todo, String> {
+    if let Some(field) = patch
+        .keys()
+        .find(|key| READ_ONLY_FIELDS.contains(&key.as_str()))
+    {
+        return Err(format!("Field '{}' can't be set through a draft", field));
+    }
+    let mut value = serde_json::to_value(todo).map_err(|e| e.to_string())?;
+    if let Some(object) = value.as_object_mut() {
+        object.extend(patch.clone());
+    }
+    serde_json::from_value(value).map_err(|e| format!("Invalid draft: {}", e))
+}
+
+/// Writes `patch` over the cached todo. `None` when it changed nothing.
+fn write_back(
+    storage: &Storage,
+    rules: &RuleSet,
+    id: &str,
+    patch: &DraftPatch,
+) -> Result<Option<(Todo, TodoUpdated)>, UpdateError> {
+    let current = storage
+        .get_todo(id)
+        .map_err(|e| format!("Failed to load todo: {}", e))?
+        .ok_or_else(|| format!("Unknown todo '{}'", id))?;
+    let mut todo = patched(&current, patch)?;
+    todo.updated_at = storage.clock().now();
+    // With the copy as base, a write that got in between is merged.
+    Ok(apply_checked_update(storage, todo, Some(&current), rules)?.map(|update| (current, update)))
+}
+
+/// [`write_back`] with rules loaded once per batch. Rules that don't
+/// compile refuse the write, as they do in `update_todo`.
+fn checked_write_back(
+    storage: &Storage,
+    rules: &Result<RuleSet, String>,
+    id: &str,
+    patch: &DraftPatch,
+) -> Result<Option<(Todo, TodoUpdated)>, UpdateError> {
+    match rules {
+        Ok(rules) => write_back(storage, rules, id, patch),
+        Err(e) => Err(UpdateError::from(e.clone())),
+    }
+}
+
+impl Drafts {
+    pub fn new(journal: Option<PathBuf>) -> Self {
+        Self {
+            pending: Mutex::default(),
+            journal,
+        }
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn append(&self, entry: &JournalEntry) -> Result<(), String> {
+        let Some(path) = &self.journal else {
+            return Ok(());
+        };
+        let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("Failed to journal draft: {}", e))
+    }
+
+    /// Replaces the journal with the drafts still pending.
+    fn rewrite_journal(&self, pending: &Pending) -> Result<(), String> {
+        let Some(path) = &self.journal else {
+            return Ok(());
+        };
+        let result = if pending.drafts.is_empty() {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                other => other,
+            }
+        } else {
+            let mut text = String::new();
+            for (id, draft) in &pending.drafts {
+                let entry = JournalEntry {
+                    id: id.clone(),
+                    patch: draft.patch.clone(),
+                };
+                text.push_str(&serde_json::to_string(&entry).map_err(|e| e.to_string())?);
+                text.push('\n');
+            }
+            let temp = path.with_extension("tmp");
+            fs::write(&temp, text).and_then(|()| fs::rename(&temp, path))
+        };
+        result.map_err(|e| format!("Failed to rewrite draft journal: {}", e))
+    }
+
+    /// Adds `patch` to the draft of todo `id` and returns the todo as it
+    /// will be written.
+    pub fn stage(
+        &self,
+        storage: &Storage,
+        id: &str,
+        patch: DraftPatch,
+        now: Instant,
+    ) -> Result<Todo, String> {
+        let mut pending = self.pending();
+        let current = storage
+            .get_todo(id)
+            .map_err(|e| format!("Failed to load todo: {}", e))?
+            .ok_or_else(|| format!("Unknown todo '{}'", id))?;
+        let mut merged = pending
+            .drafts
+            .get(id)
+            .map(|draft| draft.patch.clone())
+            .unwrap_or_default();
+        merged.extend(patch.clone());
+        let todo = patched(&current, &merged)?;
+        self.append(&JournalEntry {
+            id: id.to_string(),
+            patch,
+        })?;
+        let draft = pending.drafts.entry(id.to_string()).or_insert(Draft {
+            patch: DraftPatch::new(),
+            first: now,
+            last: now,
+        });
+        draft.patch = merged;
+        draft.last = now;
+        Ok(todo)
+    }
+
+    /// `todos` as they will be once every draft is written.
+    pub fn overlay(&self, todos: Vec<Todo>) -> Vec<Todo> {
+        let pending = self.pending();
+        todos
+            .into_iter()
+            .map(|todo| match pending.drafts.get(&todo.id) {
+                Some(draft) => patched(&todo, &draft.patch).unwrap_or(todo),
+                None => todo,
+            })
+            .collect()
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.pending().drafts.values().map(Draft::due).min()
+    }
+
+    /// Writes the drafts due at `now`, or all of them when `now` is `None`,
+    /// and returns each write with the todo it replaced. A draft that can't
+    /// be written, e.g. because it breaks a blocking validation rule, is
+    /// logged and dropped.
+    pub fn flush(
+        &self,
+        storage: &Storage,
+        rules: &[ValidationRule],
+        now: Option<Instant>,
+    ) -> Vec<(Todo, TodoUpdated)> {
+        self.flush_where(storage, rules, |_, draft| {
+            now.is_none_or(|now| draft.due() <= now)
+        })
+    }
+
+    /// Writes the draft of todo `id`, if it has one.
+    pub fn flush_todo(
+        &self,
+        storage: &Storage,
+        rules: &[ValidationRule],
+        id: &str,
+    ) -> Vec<(Todo, TodoUpdated)> {
+        self.flush_where(storage, rules, |draft_id, _| draft_id == id)
+    }
+
+    fn flush_where(
+        &self,
+        storage: &Storage,
+        rules: &[ValidationRule],
+        due: impl Fn(&str, &Draft) -> bool,
+    ) -> Vec<(Todo, TodoUpdated)> {
+        // Drafts stay staged until written, so reads never fall back to the
+        // old text in between.
+        let due: Vec<(String, DraftPatch)> = self
+            .pending()
+            .drafts
+            .iter()
+            .filter(|(id, draft)| due(id, draft))
+            .map(|(id, draft)| (id.clone(), draft.patch.clone()))
+            .collect();
+        if due.is_empty() {
+            return Vec::new();
+        }
+        let rules = RuleSet::load(storage, rules);
+        let mut written = Vec::new();
+        for (id, patch) in &due {
+            match checked_write_back(storage, &rules, id, patch) {
+                Ok(Some(update)) => written.push(update),
+                Ok(None) => {}
+                Err(e) => trace::log(format!("Failed to write draft of '{}': {}", id, e)),
+            }
+        }
+        let mut pending = self.pending();
+        for (id, patch) in due {
+            // A draft that grew meanwhile is written again in full later.
+            if pending
+                .drafts
+                .get(&id)
+                .is_some_and(|draft| draft.patch == patch)
+            {
+                pending.drafts.remove(&id);
+            }
+        }
+        // Only now: a crash before this point replays the journal instead.
+        if let Err(e) = self.rewrite_journal(&pending) {
+            trace::log(e);
+        }
+        written
+    }
+
+    /// Writes what the journal holds from a run that ended before flushing
+    /// it, and returns the ids of the todos that changed. A line cut short
+    /// by the crash is skipped.
+    pub fn recover(
+        &self,
+        storage: &Storage,
+        rules: &[ValidationRule],
+    ) -> Result<Vec<String>, String> {
+        let Some(path) = &self.journal else {
+            return Ok(Vec::new());
+        };
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read draft journal: {}", e)),
+        };
+        let mut order = Vec::new();
+        let mut patches: HashMap<String, DraftPatch> = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Failed to read draft journal: {}", e))?;
+            let Ok(entry) = serde_json::from_str::<JournalEntry>(&line) else {
+                continue;
+            };
+            if !patches.contains_key(&entry.id) {
+                order.push(entry.id.clone());
+            }
+            patches.entry(entry.id).or_default().extend(entry.patch);
+        }
+        let rules = RuleSet::load(storage, rules);
+        let mut recovered = Vec::new();
+        for id in order {
+            match checked_write_back(storage, &rules, &id, &patches[&id]) {
+                Ok(Some(_)) => recovered.push(id),
+                Ok(None) => {}
+                Err(e) => trace::log(format!("Failed to recover draft of '{}': {}", id, e)),
+            }
+        }
+        self.rewrite_journal(&self.pending())?;
+        Ok(recovered)
+    }
+}
+
+/// Writes the due drafts, or all of them, and announces each write the way
+/// `update_todo` does.
+fn flush_and_announce(app: &AppHandle, now: Option<Instant>) -> usize {
+    let (Some(drafts), Some(storage), Some(settings)) = (
+        app.try_state::<Drafts>(),
+        app.try_state::<Storage>(),
+        app.try_state::<SettingsState>(),
+    ) else {
+        return 0;
+    };
+    let written = drafts.flush(&storage, &settings.get().validation_rules, now);
+    for (previous, update) in &written {
+        validation_rules::note_warnings(&storage, &update.warnings);
+        announce_update(app, &storage, Some(previous), update);
+    }
+    written.len()
+}
+
+/// Writes every draft now: on window close, app exit and screen lock, which
+/// usually comes before the machine suspends.
+pub fn flush_all(app: &AppHandle) -> usize {
+    flush_and_announce(app, None)
+}
+
+/// Starts the flush worker unless it is running.
+fn schedule(app: &AppHandle) {
+    let drafts = app.state::<Drafts>();
+    let mut pending = drafts.pending();
+    if pending.worker {
+        return;
+    }
+    pending.worker = true;
+    let app = app.clone();
+    std::thread::spawn(trace::bind(move || run_worker(&app)));
+}
+
+/// Flushes drafts as they come due until none is left, then exits.
+fn run_worker(app: &AppHandle) {
+    let drafts = app.state::<Drafts>();
+    loop {
+        let Some(due) = drafts.next_due() else {
+            let mut pending = drafts.pending();
+            // A draft staged since `next_due` still has this worker.
+            if pending.drafts.is_empty() {
+                pending.worker = false;
+                return;
+            }
+            continue;
+        };
+        // Real time, not the app's clock: this only gathers keystrokes into
+        // one write.
+        std::thread::sleep(due.saturating_duration_since(Instant::now()));
+        flush_and_announce(app, Some(Instant::now()));
+    }
+}
+
+/// Writes drafts left over by a crash, checked against `rules`. Requires
+/// `Storage` and `Drafts`.
+pub fn recover_at_startup(app: &AppHandle, rules: &[ValidationRule]) {
+    let recovered = app
+        .state::<Drafts>()
+        .recover(&app.state::<Storage>(), rules);
+    match recovered {
+        Ok(ids) if ids.is_empty() => {}
+        Ok(ids) => {
+            trace::log(format!("Recovered unsaved edits of {} todo(s)", ids.len()));
+            if let Ok(todos) = app.state::<Storage>().list_todos() {
+                app.state::<SearchIndex>().rebuild(&todos);
+            }
+            let _ = events::emit(app, "draft-recovered", ids);
+        }
+        Err(e) => trace::log(e),
+    }
+}
+
+/// Stages an edit of todo `id` to be written with the ones around it, and
+/// returns the todo as it will be written.
+#[tauri::command]
+pub fn update_todo_draft(
+    id: String,
+    patch: DraftPatch,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    drafts: State<'_, Drafts>,
+) -> Result<Todo, String> {
+    let todo = drafts.stage(&storage, &id, patch, Instant::now())?;
+    schedule(&app);
+    Ok(todo)
+}
+
+/// Writes every staged draft now, e.g. when an editor loses focus, and
+/// returns how many todos changed.
+#[tauri::command]
+pub fn flush_drafts(app: AppHandle) -> usize {
+    flush_all(&app)
+}
@@ -0,0 +1,205 @@
+use super::*;
+
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+
+fn todo(id: &str) -> Todo {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo::sample(id, "Write report", created)
+}
+
+fn patch(value: serde_json::Value) -> DraftPatch {
+    value.as_object().unwrap().clone()
+}
+
+fn description(storage: &Storage, id: &str) -> Option<String> {
+    storage.get_todo(id).unwrap().unwrap().description
+}
+
+#[test]
+fn test_drafts_coalesce_until_quiet_and_show_in_reads() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a")]).unwrap();
+    let drafts = Drafts::default();
+    let start = Instant::now();
+
+    for (i, text) in ["D", "Dr", "Dra"].into_iter().enumerate() {
+        let at = start + Duration::from_millis(100 * i as u64);
+        drafts
+            .stage(&storage, "a", patch(json!({ "description": text })), at)
+            .unwrap();
+    }
+    assert_eq!(description(&storage, "a"), None);
+    let overlaid = drafts.overlay(storage.list_todos().unwrap());
+    assert_eq!(overlaid[0].description.as_deref(), Some("Dra"));
+
+    assert!(drafts
+        .flush(&storage, &[], Some(start + Duration::from_millis(600)))
+        .is_empty());
+    let written = drafts.flush(&storage, &[], Some(start + Duration::from_millis(700)));
+    assert_eq!(written.len(), 1);
+    assert_eq!(written[0].1.changed_fields, ["description"]);
+    assert_eq!(description(&storage, "a").as_deref(), Some("Dra"));
+    assert!(drafts.flush(&storage, &[], None).is_empty());
+}
+
+#[test]
+fn test_drafts_are_written_after_max_delay_while_typing() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a")]).unwrap();
+    let drafts = Drafts::default();
+    let start = Instant::now();
+    let mut at = start;
+    while at < start + MAX_DELAY {
+        drafts
+            .stage(&storage, "a", patch(json!({ "title": "Typing" })), at)
+            .unwrap();
+        assert!(drafts.flush(&storage, &[], Some(at)).is_empty());
+        at += Duration::from_millis(400);
+    }
+    assert_eq!(drafts.flush(&storage, &[], Some(at)).len(), 1);
+}
+
+#[test]
+fn test_drafts_breaking_a_blocking_rule_are_dropped() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a")]).unwrap();
+    let drafts = Drafts::default();
+    let rules = [ValidationRule {
+        name: "Capitalized".to_string(),
+        list: None,
+        tag: None,
+        check: validation_rules::RuleCheck::TitleMatches {
+            pattern: "^[A-Z]".to_string(),
+        },
+        severity: validation_rules::Severity::Block,
+    }];
+    drafts
+        .stage(
+            &storage,
+            "a",
+            patch(json!({ "title": "lowercase" })),
+            Instant::now(),
+        )
+        .unwrap();
+
+    assert!(drafts.flush(&storage, &rules, None).is_empty());
+    let overlaid = drafts.overlay(storage.list_todos().unwrap());
+    assert_eq!(overlaid[0].title, "Write report");
+}
+
+#[test]
+fn test_invalid_patches_are_refused() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a")]).unwrap();
+    let drafts = Drafts::default();
+    let now = Instant::now();
+    assert!(drafts
+        .stage(&storage, "a", patch(json!({ "revision": 7 })), now)
+        .is_err());
+    assert!(drafts
+        .stage(&storage, "a", patch(json!({ "completed": "yes" })), now)
+        .is_err());
+    assert!(drafts
+        .stage(&storage, "missing", patch(json!({ "title": "x" })), now)
+        .is_err());
+    assert!(drafts.flush(&storage, &[], None).is_empty());
+}
+
+#[test]
+fn test_crash_between_journal_and_flush_is_recovered() {
+    let dir = tempfile::tempdir().unwrap();
+    let journal = dir.path().join(JOURNAL_FILE);
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a"), todo("b")]).unwrap();
+
+    // The app dies with drafts staged and journaled but not written.
+    let drafts = Drafts::new(Some(journal.clone()));
+    let now = Instant::now();
+    drafts
+        .stage(&storage, "a", patch(json!({ "description": "Half" })), now)
+        .unwrap();
+    drafts
+        .stage(
+            &storage,
+            "a",
+            patch(json!({ "description": "Half done" })),
+            now,
+        )
+        .unwrap();
+    drafts
+        .stage(&storage, "b", patch(json!({ "title": "Renamed" })), now)
+        .unwrap();
+    drop(drafts);
+    // ...halfway through a line.
+    let mut file = OpenOptions::new().append(true).open(&journal).unwrap();
+    file.write_all(b"{\"id\":\"a\",\"pat").unwrap();
+
+    let recovered = Drafts::new(Some(journal.clone()))
+        .recover(&storage, &[])
+        .unwrap();
+    assert_eq!(recovered, ["a", "b"]);
+    assert_eq!(description(&storage, "a").as_deref(), Some("Half done"));
+    assert_eq!(storage.get_todo("b").unwrap().unwrap().title, "Renamed");
+    assert!(!journal.exists());
+}
+
+#[test]
+fn test_crash_after_write_before_journal_rewrite_replays_harmlessly() {
+    let dir = tempfile::tempdir().unwrap();
+    let journal = dir.path().join(JOURNAL_FILE);
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a")]).unwrap();
+
+    let drafts = Drafts::new(Some(journal.clone()));
+    drafts
+        .stage(
+            &storage,
+            "a",
+            patch(json!({ "description": "Done" })),
+            Instant::now(),
+        )
+        .unwrap();
+    // The write lands, but the journal still holds the patch.
+    write_back(&storage, "a", &patch(json!({ "description": "Done" }))).unwrap();
+    let revision = storage.get_todo("a").unwrap().unwrap().revision;
+    drop(drafts);
+
+    let recovered = Drafts::new(Some(journal.clone()))
+        .recover(&storage, &[])
+        .unwrap();
+    assert!(recovered.is_empty());
+    assert_eq!(storage.get_todo("a").unwrap().unwrap().revision, revision);
+    assert!(!journal.exists());
+}
+
+#[test]
+fn test_flush_rewrites_the_journal_with_what_is_left() {
+    let dir = tempfile::tempdir().unwrap();
+    let journal = dir.path().join(JOURNAL_FILE);
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[todo("a"), todo("b")]).unwrap();
+    let drafts = Drafts::new(Some(journal.clone()));
+    let start = Instant::now();
+    drafts
+        .stage(&storage, "a", patch(json!({ "title": "First" })), start)
+        .unwrap();
+    drafts
+        .stage(
+            &storage,
+            "b",
+            patch(json!({ "title": "Second" })),
+            start + Duration::from_secs(1),
+        )
+        .unwrap();
+
+    assert_eq!(
+        drafts
+            .flush(&storage, &[], Some(start + QUIET_PERIOD))
+            .len(),
+        1
+    );
+    let left = fs::read_to_string(&journal).unwrap();
+    assert_eq!(left.lines().count(), 1);
+    assert!(left.contains("Second"));
+}
@@ -0,0 +1,150 @@
+//! Priority lanes in front of the store's connection. Interactive commands
+//! take it through [`Storage::conn`]; batch work (import chunks, language
+//! backfill) takes it through [`Storage::batch_conn`], which backs off while
+//! interactive callers are waiting, so a long import doesn't make completing
+//! a todo feel broken.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+
+use super::Storage;
+
+#[cfg(test)]
+mod tests;
+
+/// Waits kept per lane for the percentiles.
+const SAMPLES: usize = 1024;
+/// How long batch work first sleeps while interactive callers wait.
+const BACKOFF: Duration = Duration::from_millis(1);
+const MAX_BACKOFF: Duration = Duration::from_millis(16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    Interactive,
+    Batch,
+}
+
+/// Owned by [`Storage`]: who waits for the connection, and for how long
+/// each lane waited recently.
+#[derive(Default)]
+pub struct Lanes {
+    interactive_waiting: AtomicUsize,
+    interactive: Mutex<VecDeque<Duration>>,
+    batch: Mutex<VecDeque<Duration>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panic while holding the lock leaves the value itself usable.
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Lanes {
+    fn waits(&self, lane: Lane) -> MutexGuard<'_, VecDeque<Duration>> {
+        match lane {
+            Lane::Interactive => lock(&self.interactive),
+            Lane::Batch => lock(&self.batch),
+        }
+    }
+
+    fn record(&self, lane: Lane, waited: Duration) {
+        let mut waits = self.waits(lane);
+        if waits.len() == SAMPLES {
+            waits.pop_front();
+        }
+        waits.push_back(waited);
+    }
+
+    /// Whether interactive callers are waiting, so batch work holding the
+    /// connection should end its chunk early.
+    pub fn interactive_waiting(&self) -> bool {
+        self.interactive_waiting.load(Ordering::SeqCst) > 0
+    }
+
+    /// Locks `conn` for the interactive lane, ahead of batch work.
+    pub(super) fn interactive<'a>(
+        &self,
+        conn: &'a Mutex<Connection>,
+    ) -> MutexGuard<'a, Connection> {
+        // Real time, not the app's clock: this measures lock contention.
+        let started = Instant::now();
+        self.interactive_waiting.fetch_add(1, Ordering::SeqCst);
+        let guard = lock(conn);
+        self.interactive_waiting.fetch_sub(1, Ordering::SeqCst);
+        self.record(Lane::Interactive, started.elapsed());
+        guard
+    }
+
+    /// Locks `conn` for the batch lane once no interactive caller waits.
+    pub(super) fn batch<'a>(&self, conn: &'a Mutex<Connection>) -> MutexGuard<'a, Connection> {
+        let started = Instant::now();
+        let mut backoff = BACKOFF;
+        loop {
+            while self.interactive_waiting.load(Ordering::SeqCst) > 0 {
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            let guard = lock(conn);
+            // Someone may have started waiting while this blocked on the lock.
+            if self.interactive_waiting.load(Ordering::SeqCst) == 0 {
+                self.record(Lane::Batch, started.elapsed());
+                return guard;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> ContentionStats {
+        ContentionStats {
+            interactive: LaneStats::of(&self.waits(Lane::Interactive)),
+            batch: LaneStats::of(&self.waits(Lane::Batch)),
+        }
+    }
+}
+
+/// Recent waits for the connection in one lane.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LaneStats {
+    pub samples: usize,
+    pub p50_micros: u64,
+    pub p95_micros: u64,
+    pub p99_micros: u64,
+    pub max_micros: u64,
+}
+
+impl LaneStats {
+    fn of(waits: &VecDeque<Duration>) -> Self {
+        let mut micros: Vec<u64> = waits.iter().map(|d| d.as_micros() as u64).collect();
+        micros.sort_unstable();
+        let percentile = |p: usize| match micros.len() {
+            0 => 0,
+            len => micros[(len * p).div_ceil(100).clamp(1, len) - 1],
+        };
+        Self {
+            samples: micros.len(),
+            p50_micros: percentile(50),
+            p95_micros: percentile(95),
+            p99_micros: percentile(99),
+            max_micros: micros.last().copied().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentionStats {
+    pub interactive: LaneStats,
+    pub batch: LaneStats,
+}
+
+/// Wait-time percentiles per lane, for the diagnostics panel.
+#[tauri::command]
+pub fn get_contention_stats(storage: State<'_, Storage>) -> ContentionStats {
+    storage.lanes.stats()
+}
@@ -0,0 +1,81 @@
+use super::*;
+use crate::import::{self, ConflictPolicy};
+use crate::storage::apply_update;
+use crate::types::Todo;
+
+/// How long completing a todo may take at the 95th percentile while a
+/// large import runs.
+const INTERACTIVE_BUDGET: Duration = Duration::from_millis(150);
+
+fn todo(id: &str) -> Todo {
+    let now = chrono::Utc::now();
+    Todo::sample(id, "Reply to mail", now)
+}
+
+fn p95(latencies: &mut [Duration]) -> Duration {
+    latencies.sort_unstable();
+    latencies[(latencies.len() * 95).div_ceil(100) - 1]
+}
+
+#[test]
+fn test_percentiles() {
+    let waits: VecDeque<Duration> = (1..=100).map(Duration::from_micros).collect();
+    let stats = LaneStats::of(&waits);
+    assert_eq!(
+        stats,
+        LaneStats {
+            samples: 100,
+            p50_micros: 50,
+            p95_micros: 95,
+            p99_micros: 99,
+            max_micros: 100,
+        }
+    );
+    assert_eq!(LaneStats::of(&VecDeque::new()), LaneStats::default());
+}
+
+#[test]
+fn test_completes_stay_responsive_during_a_large_import() {
+    const ROWS: usize = 200_000;
+    let storage = Storage::open_in_memory().unwrap();
+    storage.save_todo(&todo("interactive")).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large.csv");
+    let mut csv = String::from("title\n");
+    for i in 0..ROWS {
+        csv.push_str(&format!("Imported {}\n", i));
+    }
+    std::fs::write(&path, csv).unwrap();
+
+    let mut latencies = Vec::new();
+    std::thread::scope(|scope| {
+        let importer = scope.spawn(|| {
+            let checkpoint = import::begin_import(&storage, &path, ConflictPolicy::Skip).unwrap();
+            import::run_import(
+                &storage,
+                checkpoint,
+                &crate::validation_rules::RuleSet::default(),
+                1000,
+                |_| false,
+                |_| {},
+            )
+            .unwrap()
+        });
+        while !importer.is_finished() {
+            let started = Instant::now();
+            let mut current = storage.get_todo("interactive").unwrap().unwrap();
+            current.completed = !current.completed;
+            apply_update(&storage, current, None).unwrap();
+            latencies.push(started.elapsed());
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(importer.join().unwrap().finished);
+    });
+
+    assert_eq!(storage.list_todos().unwrap().len(), ROWS + 1);
+    assert!(latencies.len() >= 20, "only {} completes", latencies.len());
+    let p95 = p95(&mut latencies);
+    assert!(p95 < INTERACTIVE_BUDGET, "p95 {:?}", p95);
+    let stats = storage.lanes.stats();
+    assert!(stats.interactive.samples > 0 && stats.batch.samples > 0);
+}
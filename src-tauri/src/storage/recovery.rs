@@ -0,0 +1,500 @@
+//! Recovery for a store that won't open, such as "database disk image is
+//! malformed" after the machine was switched off mid-write. [`open`] tries
+//! the steps below in order, cheapest first. It stops at the first one that
+//! leaves a store passing SQLite's quick check:
+//!
+//! 1. remove a stale `-shm` file and retry;
+//! 2. checkpoint the WAL on a fresh connection and retry;
+//! 3. salvage the readable rows into a new database. The damaged one is
+//!    kept as `corrupt-<timestamp>.db` next to it.
+//!
+//! Restoring the latest backup replaces everything since, so it needs the
+//! user's go-ahead. The app then starts on an ephemeral store and emits
+//! `storage-recovery-required`. `apply_recovery` applies the chosen option
+//! and restarts. Every step is logged and shows up in the self-check.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tempfile::NamedTempFile;
+
+use super::{Storage, StorageBackend, StorageError, BACKUPS_DIR, DATABASE_FILE, FILES_DIR};
+use crate::clock::{Clock, ClockState, SharedClock};
+use crate::events;
+use crate::protocol::EventPayload;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// Derived from the todos and rebuilt as they change; not worth salvaging.
+const SKIPPED_TABLES: &[&str] = &["changes", "change_consumers"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecoveryStep {
+    RemoveStaleShm,
+    CheckpointWal,
+    Salvage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepOutcome {
+    pub step: RecoveryStep,
+    /// Whether the store opened after this step.
+    pub recovered: bool,
+    pub detail: String,
+}
+
+/// How much of a table the salvage could read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SalvagedTable {
+    pub table: String,
+    pub rows: u64,
+    /// False when reading stopped at a damaged page.
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum RecoveryOption {
+    /// Replaces the damaged store with this backup.
+    RestoreBackup { path: PathBuf },
+    /// Starts with an empty store.
+    StartEmpty,
+}
+
+/// Payload of `storage-recovery-required`: the automatic steps failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryRequired {
+    pub data_dir: PathBuf,
+    pub error: String,
+    pub steps: Vec<StepOutcome>,
+    /// The damaged database is kept aside with either option.
+    pub options: Vec<RecoveryOption>,
+}
+
+impl EventPayload for RecoveryRequired {}
+
+/// Managed state: what recovery did at startup, and what is left to decide.
+#[derive(Default)]
+pub struct RecoveryState {
+    steps: Vec<StepOutcome>,
+    pending: Mutex<Option<RecoveryRequired>>,
+}
+
+impl RecoveryState {
+    pub fn steps(&self) -> &[StepOutcome] {
+        &self.steps
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Option<RecoveryRequired>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether the store still waits for a recovery option, such as
+    /// restoring a backup, to be applied.
+    pub fn is_pending(&self) -> bool {
+        self.pending().is_some()
+    }
+}
+
+/// Whether `error` means the database is damaged, rather than e.g. locked
+/// or unreadable, which recovering would only make worse.
+pub fn is_corruption(error: &StorageError) -> bool {
+    matches!(
+        error,
+        StorageError::Sqlite(e)
+            if matches!(e.sqlite_error_code(), Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase))
+    )
+}
+
+fn corrupt(message: String) -> StorageError {
+    StorageError::Sqlite(rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+        Some(message),
+    ))
+}
+
+/// Opens the store in `dir` and runs the quick check, which finds damage a
+/// plain open doesn't touch.
+fn open_checked(dir: &Path) -> Result<Storage, StorageError> {
+    let storage = Storage::open(StorageBackend::Disk(dir.to_path_buf()))?;
+    let status: String = storage
+        .conn()
+        .query_row("PRAGMA quick_check(1)", [], |row| row.get(0))?;
+    if status != "ok" {
+        return Err(corrupt(status));
+    }
+    Ok(storage)
+}
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn log(outcome: &StepOutcome) {
+    trace::log(format!(
+        "Storage recovery, {:?}: {} ({})",
+        outcome.step,
+        if outcome.recovered {
+            "recovered"
+        } else {
+            "not recovered"
+        },
+        outcome.detail
+    ));
+}
+
+/// Moves the database in `dir`, with its `-wal` and `-shm` files, to
+/// `corrupt-<timestamp>.db` and returns the new path.
+pub fn set_aside(dir: &Path, now: DateTime<Utc>) -> std::io::Result<PathBuf> {
+    let database = dir.join(DATABASE_FILE);
+    let stem = format!("corrupt-{}", now.format("%Y%m%dT%H%M%S"));
+    let aside = (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("{}.db", stem)),
+            n => dir.join(format!("{}-{}.db", stem, n)),
+        })
+        .find(|path| !path.exists())
+        .unwrap_or_default();
+    fs::rename(&database, &aside)?;
+    for suffix in ["-wal", "-shm"] {
+        let file = sidecar(&database, suffix);
+        if file.exists() {
+            fs::rename(&file, sidecar(&aside, suffix))?;
+        }
+    }
+    Ok(aside)
+}
+
+fn columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let rows = stmt.query_map([table], |row| row.get(0))?;
+    rows.collect()
+}
+
+fn copy_table(from: &Connection, to: &Connection, table: &str) -> rusqlite::Result<SalvagedTable> {
+    let target = columns(to, table)?;
+    let shared: Vec<String> = columns(from, table)?
+        .into_iter()
+        .filter(|column| target.contains(column))
+        .map(|column| format!("\"{}\"", column.replace('"', "\"\"")))
+        .collect();
+    let mut salvaged = SalvagedTable {
+        table: table.to_string(),
+        rows: 0,
+        complete: true,
+    };
+    if shared.is_empty() {
+        return Ok(salvaged);
+    }
+    let list = shared.join(", ");
+    let placeholders = vec!["?"; shared.len()].join(", ");
+    let mut insert = to.prepare(&format!(
+        "INSERT OR IGNORE INTO \"{}\" ({}) VALUES ({})",
+        table, list, placeholders
+    ))?;
+    let mut select = from.prepare(&format!("SELECT {} FROM \"{}\"", list, table))?;
+    let mut rows = select.query([])?;
+    loop {
+        match rows.next() {
+            Ok(Some(row)) => {
+                let values = (0..shared.len())
+                    .map(|i| row.get::<_, Value>(i))
+                    .collect::<rusqlite::Result<Vec<_>>>();
+                match values {
+                    Ok(values) => {
+                        salvaged.rows += insert.execute(rusqlite::params_from_iter(values))? as u64;
+                    }
+                    Err(_) => {
+                        salvaged.complete = false;
+                        break;
+                    }
+                }
+            }
+            Ok(None) => break,
+            // Past a damaged page the cursor can't go on.
+            Err(_) => {
+                salvaged.complete = false;
+                break;
+            }
+        }
+    }
+    Ok(salvaged)
+}
+
+/// A copy of `damaged` padded to the size its header claims. SQLite
+/// refuses a file shorter than that outright, while a zeroed page only
+/// stops the reads that reach it.
+fn padded_copy(damaged: &Path) -> std::io::Result<NamedTempFile> {
+    let copy = NamedTempFile::new_in(damaged.parent().unwrap_or(Path::new(".")))?;
+    fs::copy(damaged, copy.path())?;
+    let mut header = [0u8; 100];
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(copy.path())?;
+    if file.read_exact(&mut header).is_ok() && header.starts_with(b"SQLite format 3\0") {
+        let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+            1 => 65_536,
+            size => size as u64,
+        };
+        let pages = u32::from_be_bytes([header[28], header[29], header[30], header[31]]) as u64;
+        if page_size * pages > file.metadata()?.len() {
+            file.set_len(page_size * pages)?;
+        }
+    }
+    Ok(copy)
+}
+
+/// Copies the rows that can still be read from the database at `damaged`
+/// into `to`, table by table, into the columns both have.
+pub fn salvage_rows(
+    damaged: &Path,
+    to: &mut Connection,
+) -> Result<Vec<SalvagedTable>, StorageError> {
+    let copy = padded_copy(damaged)?;
+    let from = Connection::open_with_flags(copy.path(), OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let tables: Vec<String> = {
+        let mut stmt = from.prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+    let tx = to.transaction()?;
+    let mut salvaged = Vec::new();
+    for table in tables {
+        if SKIPPED_TABLES.contains(&table.as_str()) || columns(&tx, &table)?.is_empty() {
+            continue;
+        }
+        salvaged.push(copy_table(&from, &tx, &table).unwrap_or(SalvagedTable {
+            table,
+            rows: 0,
+            complete: false,
+        }));
+    }
+    tx.commit()?;
+    Ok(salvaged)
+}
+
+/// Step 3: moves the damaged database aside and salvages it into a new one.
+/// When nothing could be read, puts it back so the next start tries again.
+fn salvage(dir: &Path, now: DateTime<Utc>) -> Result<(Storage, String), String> {
+    let aside = set_aside(dir, now).map_err(|e| format!("Failed to move it aside: {}", e))?;
+    let result = open_checked(dir)
+        .map_err(|e| e.to_string())
+        .and_then(|storage| {
+            let mut conn = Connection::open(dir.join(DATABASE_FILE)).map_err(|e| e.to_string())?;
+            let tables = salvage_rows(&aside, &mut conn).map_err(|e| e.to_string())?;
+            Ok((storage, tables))
+        });
+    match result {
+        Ok((storage, tables)) => {
+            let rows: u64 = tables.iter().map(|table| table.rows).sum();
+            let partial: Vec<&str> = tables
+                .iter()
+                .filter(|table| !table.complete)
+                .map(|table| table.table.as_str())
+                .collect();
+            let mut detail = format!("{} rows saved, original kept at {}", rows, aside.display());
+            if !partial.is_empty() {
+                detail.push_str(&format!("; partly unreadable: {}", partial.join(", ")));
+            }
+            Ok((storage, detail))
+        }
+        Err(e) => {
+            let database = dir.join(DATABASE_FILE);
+            for suffix in ["", "-wal", "-shm", "-journal"] {
+                let _ = fs::remove_file(sidecar(&database, suffix));
+            }
+            for suffix in ["", "-wal", "-shm"] {
+                let from = sidecar(&aside, suffix);
+                if from.exists() {
+                    let _ = fs::rename(&from, sidecar(&database, suffix));
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// The newest backup of the store in `dir`, if any.
+pub fn latest_backup(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir.join(FILES_DIR).join(BACKUPS_DIR))
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "db"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Opens the store in `dir`, going through the recovery steps when it is
+/// damaged. Errors other than damage are returned as they are.
+fn open_with_steps(
+    dir: &Path,
+    now: DateTime<Utc>,
+    steps: &mut Vec<StepOutcome>,
+) -> Result<Result<Storage, RecoveryRequired>, StorageError> {
+    let mut error = match open_checked(dir) {
+        Ok(storage) => return Ok(Ok(storage)),
+        Err(e) if is_corruption(&e) => e,
+        Err(e) => return Err(e),
+    };
+    trace::log(format!("The store failed to open: {}", error));
+    let database = dir.join(DATABASE_FILE);
+    let mut record = |step, result: Result<Storage, StorageError>, detail: String| {
+        let outcome = StepOutcome {
+            step,
+            recovered: result.is_ok(),
+            detail: match &result {
+                Ok(_) => detail,
+                Err(e) => format!("{}: {}", detail, e),
+            },
+        };
+        log(&outcome);
+        steps.push(outcome);
+        result
+    };
+
+    let shm = sidecar(&database, "-shm");
+    if shm.exists() {
+        let result = fs::remove_file(&shm)
+            .map_err(StorageError::from)
+            .and_then(|_| open_checked(dir));
+        match record(RecoveryStep::RemoveStaleShm, result, "removed".to_string()) {
+            Ok(storage) => return Ok(Ok(storage)),
+            Err(e) => error = e,
+        }
+    }
+
+    if sidecar(&database, "-wal").exists() {
+        let result = Connection::open(&database)
+            .and_then(|conn| {
+                conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+                    row.get::<_, i64>(0)
+                })
+            })
+            .map_err(StorageError::from)
+            .and_then(|_| open_checked(dir));
+        match record(
+            RecoveryStep::CheckpointWal,
+            result,
+            "checkpointed".to_string(),
+        ) {
+            Ok(storage) => return Ok(Ok(storage)),
+            Err(e) => error = e,
+        }
+    }
+
+    match salvage(dir, now) {
+        Ok((storage, detail)) => record(RecoveryStep::Salvage, Ok(storage), detail).map(Ok),
+        Err(e) => {
+            let _ = record(
+                RecoveryStep::Salvage,
+                Err(corrupt(e)),
+                "nothing could be read".to_string(),
+            );
+            let mut options = Vec::new();
+            if let Some(path) = latest_backup(dir) {
+                options.push(RecoveryOption::RestoreBackup { path });
+            }
+            options.push(RecoveryOption::StartEmpty);
+            Ok(Err(RecoveryRequired {
+                data_dir: dir.to_path_buf(),
+                error: error.to_string(),
+                steps: steps.clone(),
+                options,
+            }))
+        }
+    }
+}
+
+/// Opens the store for this launch on `clock`. When recovery needs a
+/// decision the app runs on an ephemeral store until [`apply_recovery`]
+/// restarts it.
+pub fn open(
+    backend: StorageBackend,
+    clock: SharedClock,
+) -> Result<(Storage, RecoveryState), StorageError> {
+    let StorageBackend::Disk(dir) = &backend else {
+        return Ok((
+            Storage::open(backend)?.with_clock(clock),
+            RecoveryState::default(),
+        ));
+    };
+    let mut steps = Vec::new();
+    let (storage, pending) = match open_with_steps(dir, clock.now(), &mut steps)? {
+        Ok(storage) => (storage, None),
+        Err(required) => (Storage::open(StorageBackend::InMemory)?, Some(required)),
+    };
+    Ok((
+        storage.with_clock(clock),
+        RecoveryState {
+            steps,
+            pending: Mutex::new(pending),
+        },
+    ))
+}
+
+/// Emits `storage-recovery-required` if recovery needs a decision.
+pub fn announce_at_startup(app: &AppHandle) {
+    let pending = app.state::<RecoveryState>().pending().clone();
+    if let Some(required) = pending {
+        let _ = events::emit_sticky(app, "storage-recovery-required", required);
+    }
+}
+
+/// Applies `option` to the damaged store in `dir`, which is kept aside.
+pub fn apply(dir: &Path, option: &RecoveryOption, now: DateTime<Utc>) -> Result<(), String> {
+    let aside =
+        set_aside(dir, now).map_err(|e| format!("Failed to move the store aside: {}", e))?;
+    trace::log(format!("Damaged store kept at {}", aside.display()));
+    if let RecoveryOption::RestoreBackup { path } = option {
+        fs::copy(path, dir.join(DATABASE_FILE))
+            .map_err(|e| format!("Failed to restore {}: {}", path.display(), e))?;
+        trace::log(format!("Store restored from {}", path.display()));
+    }
+    Ok(())
+}
+
+/// Applies one of the options of `storage-recovery-required` and restarts.
+#[tauri::command]
+pub fn apply_recovery(
+    option: RecoveryOption,
+    app: AppHandle,
+    state: State<'_, RecoveryState>,
+    clock: State<'_, ClockState>,
+) -> Result<(), String> {
+    let mut pending = state.pending();
+    let Some(required) = pending.as_ref() else {
+        return Err("Failed to recover: the store needs no recovery".to_string());
+    };
+    if !required.options.contains(&option) {
+        return Err("Failed to recover: not one of the offered options".to_string());
+    }
+    apply(&required.data_dir, &option, clock.now())?;
+    *pending = None;
+    drop(pending);
+    app.request_restart();
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use super::*;
+use crate::clock;
+use crate::storage;
+use crate::types::Todo;
+
+fn todo(n: usize) -> Todo {
+    let created = chrono::Utc::now();
+    Todo {
+        description: Some("Something long enough to fill a few pages. ".repeat(8)),
+        order: Some(n as i64),
+        tags: vec!["work".to_string()],
+        ..Todo::sample(
+            format!("todo-{:04}", n),
+            format!("Todo number {}", n),
+            created,
+        )
+    }
+}
+
+/// A store in a temporary directory holding `count` todos, closed again.
+fn store_with_todos(count: usize) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::open(StorageBackend::Disk(dir.path().to_path_buf())).unwrap();
+    let todos: Vec<Todo> = (0..count).map(todo).collect();
+    storage.replace_todos(&todos).unwrap();
+    drop(storage);
+    dir
+}
+
+fn truncate(path: &Path, keep: f64) {
+    let len = fs::metadata(path).unwrap().len();
+    let file = fs::OpenOptions::new().write(true).open(path).unwrap();
+    file.set_len((len as f64 * keep) as u64).unwrap();
+}
+
+fn set_aside_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("corrupt-"))
+        })
+        .collect()
+}
+
+#[test]
+fn test_healthy_store_opens_without_steps() {
+    let dir = store_with_todos(3);
+    let (storage, recovery) = open(
+        StorageBackend::Disk(dir.path().to_path_buf()),
+        clock::system(),
+    )
+    .unwrap();
+
+    assert!(recovery.steps().is_empty());
+    assert!(recovery.pending().is_none());
+    assert_eq!(storage.list_todos().unwrap().len(), 3);
+}
+
+#[test]
+fn test_truncated_store_is_salvaged() {
+    let dir = store_with_todos(400);
+    truncate(&dir.path().join(DATABASE_FILE), 0.6);
+
+    let (storage, recovery) = open(
+        StorageBackend::Disk(dir.path().to_path_buf()),
+        clock::system(),
+    )
+    .unwrap();
+
+    let last = recovery.steps().last().unwrap();
+    assert_eq!(last.step, RecoveryStep::Salvage);
+    assert!(last.recovered, "{}", last.detail);
+    assert!(recovery.pending().is_none());
+    assert!(!storage.mode().ephemeral);
+    // What was readable survives; the rest was on the cut pages.
+    let salvaged = storage.list_todos().unwrap();
+    assert!(!salvaged.is_empty() && salvaged.len() < 400);
+    assert!(salvaged
+        .iter()
+        .all(|todo| todo.title.starts_with("Todo number")));
+    // The damaged original is kept, and the new store is whole.
+    assert_eq!(set_aside_files(dir.path()).len(), 1);
+    drop(storage);
+    assert!(open_checked(dir.path()).is_ok());
+}
+
+#[test]
+fn test_salvage_reports_tables_it_could_only_partly_read() {
+    let dir = store_with_todos(400);
+    let damaged = dir.path().join(DATABASE_FILE);
+    truncate(&damaged, 0.6);
+    let target = tempfile::tempdir().unwrap();
+    drop(Storage::open(StorageBackend::Disk(target.path().to_path_buf())).unwrap());
+    let mut conn = Connection::open(target.path().join(DATABASE_FILE)).unwrap();
+
+    let tables = salvage_rows(&damaged, &mut conn).unwrap();
+
+    let todos = tables.iter().find(|table| table.table == "todos").unwrap();
+    assert!(!todos.complete);
+    assert!(todos.rows > 0 && todos.rows < 400);
+    assert!(tables.iter().all(|table| table.table != "changes"));
+}
+
+#[test]
+fn test_unreadable_store_waits_for_a_decision() {
+    let dir = store_with_todos(3);
+    {
+        let storage = Storage::open(StorageBackend::Disk(dir.path().to_path_buf())).unwrap();
+        storage::back_up(&storage, "scheduled").unwrap();
+    }
+    let database = dir.path().join(DATABASE_FILE);
+    fs::write(&database, vec![0x5a; 8192]).unwrap();
+
+    let (storage, recovery) = open(
+        StorageBackend::Disk(dir.path().to_path_buf()),
+        clock::system(),
+    )
+    .unwrap();
+
+    // The app runs on an ephemeral store until the user decides.
+    assert!(storage.mode().ephemeral);
+    let required = recovery.pending().clone().unwrap();
+    assert_eq!(
+        required
+            .steps
+            .iter()
+            .map(|outcome| (outcome.step, outcome.recovered))
+            .collect::<Vec<_>>(),
+        [(RecoveryStep::Salvage, false)]
+    );
+    let backup = latest_backup(dir.path()).unwrap();
+    assert_eq!(
+        required.options,
+        [
+            RecoveryOption::RestoreBackup {
+                path: backup.clone()
+            },
+            RecoveryOption::StartEmpty,
+        ]
+    );
+    // Nothing was changed without the user.
+    assert_eq!(fs::read(&database).unwrap(), vec![0x5a; 8192]);
+    assert!(set_aside_files(dir.path()).is_empty());
+
+    apply(
+        dir.path(),
+        &RecoveryOption::RestoreBackup { path: backup },
+        chrono::Utc::now(),
+    )
+    .unwrap();
+    let restored = open_checked(dir.path()).unwrap();
+    assert_eq!(restored.list_todos().unwrap().len(), 3);
+    assert_eq!(set_aside_files(dir.path()).len(), 1);
+}
+
+#[test]
+fn test_only_damage_is_recovered() {
+    let locked = StorageError::Sqlite(rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+        None,
+    ));
+    assert!(!is_corruption(&locked));
+    assert!(!is_corruption(&StorageError::Io(std::io::Error::other(
+        "denied"
+    ))));
+    assert!(is_corruption(&corrupt("malformed".to_string())));
+}
@@ -0,0 +1,741 @@
+use super::*;
+
+#[test]
+fn test_backend_from_args() {
+    let dir = PathBuf::from("/data");
+    assert_eq!(
+        StorageBackend::from_args(["yutodo".to_string()], dir.clone()),
+        StorageBackend::Disk(dir.clone())
+    );
+    assert_eq!(
+        StorageBackend::from_args(["yutodo".to_string(), EPHEMERAL_FLAG.to_string()], dir),
+        StorageBackend::InMemory
+    );
+}
+
+#[test]
+fn test_migrations_applied_on_every_backend() {
+    for_each_backend(|storage| {
+        let version: i64 = storage
+            .conn()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+    });
+}
+
+#[test]
+fn test_additional_connections_share_data() {
+    for_each_backend(|storage| {
+        storage
+            .conn()
+            .execute(
+                "INSERT INTO focus_sessions (todo_id, started_at, duration_secs) VALUES ('a', '2024-01-01', 60)",
+                [],
+            )
+            .unwrap();
+
+        let other = storage.connect().unwrap();
+        let count: i64 = other
+            .query_row("SELECT COUNT(*) FROM focus_sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    });
+}
+
+#[test]
+fn test_in_memory_stores_are_isolated() {
+    let first = Storage::open_in_memory().unwrap();
+    let second = Storage::open_in_memory().unwrap();
+    first
+        .conn()
+        .execute(
+            "INSERT INTO focus_sessions (todo_id, started_at, duration_secs) VALUES ('a', '2024-01-01', 60)",
+            [],
+        )
+        .unwrap();
+
+    let count: i64 = second
+        .conn()
+        .query_row("SELECT COUNT(*) FROM focus_sessions", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn test_in_memory_store_leaves_no_files_behind() {
+    let storage = Storage::open_in_memory().unwrap();
+    assert!(storage.mode().ephemeral);
+    let files_dir = storage.files_dir().to_path_buf();
+    fs::write(files_dir.join("attachment.bin"), b"data").unwrap();
+
+    storage.discard_temporary_files();
+
+    assert!(!files_dir.exists());
+}
+
+#[test]
+fn test_disk_store_reports_its_location() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::open(StorageBackend::Disk(dir.path().to_path_buf())).unwrap();
+
+    assert_eq!(
+        storage.mode(),
+        StorageMode {
+            ephemeral: false,
+            data_dir: Some(dir.path().to_path_buf()),
+        }
+    );
+    assert!(dir.path().join(DATABASE_FILE).exists());
+    assert!(storage.files_dir().starts_with(dir.path()));
+}
+
+fn cached_todo() -> Todo {
+    let now = chrono::Utc::now();
+    Todo {
+        order: Some(1),
+        tags: vec!["work".to_string()],
+        ..Todo::sample("a", "Draft", now)
+    }
+}
+
+#[test]
+fn test_update_reports_changed_fields() {
+    for_each_backend(|storage| {
+        storage.replace_todos(&[cached_todo()]).unwrap();
+        let old = storage.get_todo("a").unwrap().unwrap();
+
+        let mut new = old.clone();
+        new.title = "Final".to_string();
+        new.updated_at += chrono::Duration::minutes(1);
+        let update = apply_update(storage, new.clone(), None).unwrap().unwrap();
+        assert_eq!(update.changed_fields, vec!["title"]);
+        assert_eq!(storage.get_todo("a").unwrap().unwrap().title, "Final");
+
+        new.completed = true;
+        new.description = Some("notes".to_string());
+        new.revision = update.todo.revision;
+        let update = apply_update(storage, new, None).unwrap().unwrap();
+        assert_eq!(update.changed_fields, vec!["completed", "description"]);
+    });
+}
+
+#[test]
+fn test_completion_time_is_kept_until_reopened() {
+    for_each_backend(|storage| {
+        storage.replace_todos(&[cached_todo()]).unwrap();
+        let mut todo = storage.get_todo("a").unwrap().unwrap();
+        assert_eq!(todo.completed_at, None);
+
+        let completed_at = todo.updated_at + chrono::Duration::minutes(1);
+        todo.completed = true;
+        todo.updated_at = completed_at;
+        todo = apply_update(storage, todo, None).unwrap().unwrap().todo;
+        assert_eq!(todo.completed_at, Some(completed_at));
+
+        // A later edit, even from a copy that lacks it, keeps the time.
+        todo.title = "Edited".to_string();
+        todo.updated_at += chrono::Duration::hours(1);
+        todo.completed_at = None;
+        todo = apply_update(storage, todo, None).unwrap().unwrap().todo;
+        assert_eq!(todo.completed_at, Some(completed_at));
+
+        todo.completed = false;
+        todo.updated_at += chrono::Duration::hours(1);
+        todo = apply_update(storage, todo, None).unwrap().unwrap().todo;
+        assert_eq!(todo.completed_at, None);
+    });
+}
+
+#[test]
+fn test_identical_update_emits_nothing() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.replace_todos(&[cached_todo()]).unwrap();
+    let todo = storage.get_todo("a").unwrap().unwrap();
+    assert_eq!(apply_update(&storage, todo.clone(), None).unwrap(), None);
+
+    let mut touched = todo;
+    touched.updated_at += chrono::Duration::seconds(5);
+    assert_eq!(apply_update(&storage, touched, None).unwrap(), None);
+    assert!(apply_update(
+        &storage,
+        Todo {
+            id: "missing".to_string(),
+            ..cached_todo()
+        },
+        None
+    )
+    .is_err());
+}
+
+#[test]
+fn test_every_write_bumps_revision() {
+    for_each_backend(|storage| {
+        let todo = cached_todo();
+        assert_eq!(storage.save_todo(&todo).unwrap(), 1);
+        assert_eq!(storage.save_todo(&todo).unwrap(), 2);
+
+        // Re-syncing identical server data leaves revisions alone.
+        let cached = storage.get_todo("a").unwrap().unwrap();
+        storage
+            .replace_todos(std::slice::from_ref(&cached))
+            .unwrap();
+        assert_eq!(storage.get_todo("a").unwrap().unwrap().revision, 2);
+
+        let mut renamed = cached;
+        renamed.title = "Renamed on the server".to_string();
+        storage.replace_todos(&[renamed]).unwrap();
+        assert_eq!(storage.get_todo("a").unwrap().unwrap().revision, 3);
+    });
+}
+
+#[test]
+fn test_stale_update_returns_current_copy() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.save_todo(&cached_todo()).unwrap();
+    let read = storage.get_todo("a").unwrap().unwrap();
+
+    let mut first = read.clone();
+    first.title = "First".to_string();
+    apply_update(&storage, first, None).unwrap().unwrap();
+
+    let mut stale = read;
+    stale.title = "Second".to_string();
+    match apply_update(&storage, stale, None) {
+        Err(UpdateError::RevisionConflict { current }) => {
+            assert_eq!(current.title, "First");
+            assert_eq!(current.revision, 2);
+        }
+        other => panic!("expected a revision conflict, got {:?}", other),
+    }
+    assert_eq!(storage.get_todo("a").unwrap().unwrap().title, "First");
+}
+
+#[test]
+fn test_two_windows_editing_different_fields_merge() {
+    for_each_backend(|storage| {
+        storage.save_todo(&cached_todo()).unwrap();
+        // Both windows read revision 1.
+        let window_a = storage.get_todo("a").unwrap().unwrap();
+        let window_b = window_a.clone();
+
+        let mut edit_a = window_a.clone();
+        edit_a.title = "Retitled in A".to_string();
+        let update_a = apply_update(storage, edit_a, Some(&window_a))
+            .unwrap()
+            .unwrap();
+        assert_eq!(update_a.todo.revision, 2);
+
+        let mut edit_b = window_b.clone();
+        edit_b.priority = Priority::High;
+        edit_b.tags.push("urgent".to_string());
+        // Without the base, B's stale copy is refused rather than clobbering A.
+        assert!(matches!(
+            apply_update(storage, edit_b.clone(), None),
+            Err(UpdateError::RevisionConflict { .. })
+        ));
+        let update_b = apply_update(storage, edit_b, Some(&window_b))
+            .unwrap()
+            .unwrap();
+        assert_eq!(update_b.changed_fields, vec!["priority", "tags"]);
+        assert_eq!(update_b.todo.revision, 3);
+
+        let stored = storage.get_todo("a").unwrap().unwrap();
+        assert_eq!(stored.title, "Retitled in A");
+        assert_eq!(stored.priority, Priority::High);
+        assert_eq!(stored.tags, vec!["work", "urgent"]);
+        assert_eq!(stored.revision, 3);
+    });
+}
+
+#[test]
+fn test_two_windows_editing_same_field_conflict() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage.save_todo(&cached_todo()).unwrap();
+    let base = storage.get_todo("a").unwrap().unwrap();
+
+    let mut edit_a = base.clone();
+    edit_a.title = "From A".to_string();
+    apply_update(&storage, edit_a, Some(&base)).unwrap();
+
+    let mut edit_b = base.clone();
+    edit_b.title = "From B".to_string();
+    let error = apply_update(&storage, edit_b, Some(&base)).unwrap_err();
+    assert!(matches!(error, UpdateError::RevisionConflict { .. }));
+    assert_eq!(
+        serde_json::to_value(&error).unwrap()["kind"],
+        "revisionConflict"
+    );
+    assert_eq!(storage.get_todo("a").unwrap().unwrap().title, "From A");
+}
+
+#[test]
+fn test_update_breaking_a_blocking_rule_is_refused() {
+    use crate::validation_rules::{RequiredField, RuleCheck, Severity, ValidationRule};
+
+    let storage = Storage::open_in_memory().unwrap();
+    storage.save_todo(&cached_todo()).unwrap();
+    let rules = RuleSet::load(
+        &storage,
+        &[ValidationRule {
+            name: "described".to_string(),
+            list: None,
+            tag: None,
+            check: RuleCheck::Require {
+                field: RequiredField::Description,
+            },
+            severity: Severity::Block,
+        }],
+    )
+    .unwrap();
+
+    let mut edit = storage.get_todo("a").unwrap().unwrap();
+    edit.title = "Retitled".to_string();
+    edit.description = None;
+    let error = apply_checked_update(&storage, edit.clone(), None, &rules).unwrap_err();
+    assert!(matches!(error, UpdateError::ValidationFailed { .. }));
+    assert_eq!(
+        serde_json::to_value(&error).unwrap()["kind"],
+        "validationFailed"
+    );
+    assert_ne!(storage.get_todo("a").unwrap().unwrap().title, "Retitled");
+
+    edit.description = Some("Why".to_string());
+    let updated = apply_checked_update(&storage, edit, None, &rules)
+        .unwrap()
+        .unwrap();
+    assert!(updated.warnings.is_empty());
+}
+
+fn todo_with_id(id: &str) -> Todo {
+    Todo {
+        id: id.to_string(),
+        order: None,
+        ..cached_todo()
+    }
+}
+
+fn listed_ids(storage: &Storage) -> Vec<String> {
+    storage
+        .list_todos()
+        .unwrap()
+        .into_iter()
+        .map(|t| t.id)
+        .collect()
+}
+
+#[test]
+fn test_new_todos_are_appended_in_input_order() {
+    for_each_backend(|storage| {
+        let todos: Vec<Todo> = ["c", "a", "b"].into_iter().map(todo_with_id).collect();
+        storage.replace_todos(&todos).unwrap();
+        assert_eq!(listed_ids(storage), ["c", "a", "b"]);
+
+        let revision = storage.get_todo("b").unwrap().unwrap().revision;
+        let rank = storage.reorder_todo("b", None, Some("c")).unwrap();
+        assert_eq!(listed_ids(storage), ["b", "c", "a"]);
+        let moved = storage.get_todo("b").unwrap().unwrap();
+        assert_eq!((moved.rank, moved.revision), (Some(rank), revision));
+
+        // The server doesn't know the keys; a refresh keeps them.
+        storage.replace_todos(&todos).unwrap();
+        assert_eq!(listed_ids(storage), ["b", "c", "a"]);
+    });
+}
+
+#[test]
+fn test_reorder_rejects_bad_neighbors() {
+    let storage = Storage::open_in_memory().unwrap();
+    let todos: Vec<Todo> = ["a", "b", "c"].into_iter().map(todo_with_id).collect();
+    storage.replace_todos(&todos).unwrap();
+
+    assert!(storage.reorder_todo("x", None, None).is_err());
+    assert!(storage.reorder_todo("a", Some("x"), None).is_err());
+    assert!(storage.reorder_todo("a", Some("a"), None).is_err());
+    assert!(storage.reorder_todo("a", Some("c"), Some("b")).is_err());
+    assert_eq!(listed_ids(&storage), ["a", "b", "c"]);
+}
+
+#[test]
+fn test_update_keeps_order_key() {
+    let storage = Storage::open_in_memory().unwrap();
+    storage
+        .replace_todos(&[todo_with_id("a"), todo_with_id("b")])
+        .unwrap();
+    let stale = storage.get_todo("a").unwrap().unwrap();
+    storage.reorder_todo("a", Some("b"), None).unwrap();
+
+    let mut edit = stale;
+    edit.title = "Final".to_string();
+    let update = apply_update(&storage, edit, None).unwrap().unwrap();
+
+    assert_eq!(update.changed_fields, vec!["title"]);
+    assert_eq!(listed_ids(&storage), ["b", "a"]);
+    assert_eq!(
+        update.todo.rank,
+        storage.get_todo("a").unwrap().unwrap().rank
+    );
+}
+
+#[test]
+fn test_rebalance_keeps_order_and_shortens_keys() {
+    let storage = Storage::open_in_memory().unwrap();
+    let todos: Vec<Todo> = (0..10).map(|i| todo_with_id(&i.to_string())).collect();
+    storage.replace_todos(&todos).unwrap();
+    // Keep dropping todos right behind "0" until the keys run long.
+    let mut order: Vec<String> = listed_ids(&storage);
+    let mut next = 1;
+    loop {
+        let id = order.remove(next);
+        let rank = storage
+            .reorder_todo(&id, Some("0"), Some(&order[1]))
+            .unwrap();
+        order.insert(1, id);
+        next = next % 9 + 1;
+        if rank::needs_rebalance(&rank) {
+            break;
+        }
+    }
+    assert_eq!(listed_ids(&storage), order);
+
+    assert_eq!(rebalance_ranks(&mut storage.conn()).unwrap(), 10);
+
+    assert_eq!(listed_ids(&storage), order);
+    let todos = storage.list_todos().unwrap();
+    assert!(todos.iter().all(|t| t.rank.as_ref().unwrap().len() <= 2));
+}
+
+#[test]
+fn test_todos_cached_before_order_keys_get_them_on_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let backend = StorageBackend::Disk(dir.path().to_path_buf());
+    {
+        let storage = Storage::open(backend.clone()).unwrap();
+        for (id, order) in [("a", 3), ("b", 1), ("c", 2)] {
+            storage
+                .save_todo(&Todo {
+                    order: Some(order),
+                    ..todo_with_id(id)
+                })
+                .unwrap();
+        }
+        storage
+            .conn()
+            .execute("UPDATE todos SET rank = NULL", [])
+            .unwrap();
+    }
+
+    let storage = Storage::open(backend).unwrap();
+
+    assert_eq!(listed_ids(&storage), ["b", "c", "a"]);
+    assert!(storage
+        .list_todos()
+        .unwrap()
+        .iter()
+        .all(|t| t.rank.is_some()));
+}
+
+#[test]
+fn test_random_reorders_write_one_row_each() {
+    const TODOS: usize = 100_000;
+    const REORDERS: usize = 10_000;
+    let storage = Storage::open_in_memory().unwrap();
+    let todos: Vec<Todo> = (0..TODOS)
+        .map(|i| todo_with_id(&format!("{:06}", i)))
+        .collect();
+    storage.replace_todos(&todos).unwrap();
+
+    // Deterministic xorshift, so a failure can be replayed.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % bound as u64) as usize
+    };
+    let mut order: Vec<u32> = (0..TODOS as u32).collect();
+    let id = |index: u32| format!("{:06}", index);
+    let changes_before = storage.conn().total_changes();
+    let mut longest = 0;
+    for _ in 0..REORDERS {
+        let moved = order.remove(random(TODOS));
+        let position = random(TODOS);
+        let before = position.checked_sub(1).map(|i| id(order[i]));
+        let after = order.get(position).map(|&i| id(i));
+        let rank = storage
+            .reorder_todo(&id(moved), before.as_deref(), after.as_deref())
+            .unwrap();
+        longest = longest.max(rank.len());
+        order.insert(position, moved);
+    }
+
+    // Each reorder writes its todo row and the change log entry for it.
+    let changes = storage.conn().total_changes() - changes_before;
+    assert_eq!(changes, 2 * REORDERS as u64);
+    assert!(longest <= rank::MAX_LEN, "{}", longest);
+    let expected: Vec<String> = order.into_iter().map(id).collect();
+    assert_eq!(listed_ids(&storage), expected);
+}
+
+/// A `todos.db` as the Node server wrote it, with one numeric and one
+/// string priority.
+fn write_legacy_database(dir: &Path) -> PathBuf {
+    let path = dir.join(LEGACY_DATABASE_FILE);
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE todos (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                description TEXT,
+                completed BOOLEAN DEFAULT FALSE,
+                priority INTEGER DEFAULT 0,
+                scheduledFor DATETIME,
+                createdAt DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updatedAt DATETIME DEFAULT CURRENT_TIMESTAMP,
+                order_index INTEGER DEFAULT 0
+            );
+            INSERT INTO todos VALUES ('a', 'Pay rent', NULL, 1, 2,
+                '2024-06-03T09:00:00.000Z', '2024-05-01 08:00:00', '2024-05-02 08:00:00', 1);
+            INSERT INTO todos VALUES ('b', 'Water the plants', 'Balcony', 0, 'low',
+                NULL, '2024-05-01 09:00:00', '2024-05-01 09:00:00', 0);",
+        )
+        .unwrap();
+    path
+}
+
+#[test]
+fn test_legacy_database_detection() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(detect_legacy_database(&dir.path().join("missing")), None);
+    assert_eq!(detect_legacy_database(dir.path()), None);
+
+    // Not SQLite, and SQLite with the current schema: neither is legacy data.
+    fs::write(dir.path().join(LEGACY_DATABASE_FILE), "not a database").unwrap();
+    assert_eq!(detect_legacy_database(dir.path()), None);
+    fs::remove_file(dir.path().join(LEGACY_DATABASE_FILE)).unwrap();
+    let conn = Connection::open(dir.path().join(LEGACY_DATABASE_FILE)).unwrap();
+    migrate(&conn, &ChangeFeed::new(), MIGRATIONS).unwrap();
+    assert_eq!(detect_legacy_database(dir.path()), None);
+    fs::remove_file(dir.path().join(LEGACY_DATABASE_FILE)).unwrap();
+
+    let path = write_legacy_database(dir.path());
+    assert_eq!(detect_legacy_database(dir.path()), Some(path));
+}
+
+#[test]
+fn test_legacy_todos_are_converted() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_legacy_database(dir.path());
+
+    let todos = read_legacy_todos(&path, Utc::now()).unwrap();
+
+    assert_eq!(todos.len(), 2);
+    let (rent, plants) = (&todos[1], &todos[0]);
+    assert_eq!(plants.id, "b");
+    assert_eq!(plants.priority, Priority::Low);
+    assert_eq!(plants.description.as_deref(), Some("Balcony"));
+    assert!(!plants.completed);
+    assert_eq!(rent.priority, Priority::High);
+    assert!(rent.completed);
+    assert_eq!(
+        rent.scheduled_for.unwrap().to_rfc3339(),
+        "2024-06-03T09:00:00+00:00"
+    );
+    assert_eq!(rent.updated_at.to_rfc3339(), "2024-05-02T08:00:00+00:00");
+    assert_eq!(rent.order, Some(1));
+}
+
+#[test]
+fn test_legacy_migration_runs_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = write_legacy_database(dir.path());
+    let storage = Storage::open(StorageBackend::Disk(dir.path().join("data"))).unwrap();
+    storage
+        .save_todo(&Todo {
+            title: "Changed since".to_string(),
+            ..todo_with_id("a")
+        })
+        .unwrap();
+
+    let report = migrate_legacy(&storage, &source).unwrap();
+
+    assert_eq!((report.imported, report.skipped), (1, 1));
+    assert!(!report.already_migrated);
+    assert!(report.backup.as_ref().unwrap().is_file());
+    let todos = storage.list_todos().unwrap();
+    assert_eq!(todos.len(), 2);
+    assert_eq!(
+        storage.get_todo("a").unwrap().unwrap().title,
+        "Changed since"
+    );
+
+    let again = migrate_legacy(&storage, &source).unwrap();
+    assert!(again.already_migrated);
+    assert_eq!((again.imported, again.backup), (0, None));
+    assert_eq!(storage.list_todos().unwrap(), todos);
+
+    // Without the record, ids still keep todos from being imported twice.
+    storage
+        .conn()
+        .execute("DELETE FROM legacy_migrations", [])
+        .unwrap();
+    let repeated = migrate_legacy(&storage, &source).unwrap();
+    assert_eq!((repeated.imported, repeated.skipped), (0, 2));
+    assert_eq!(storage.list_todos().unwrap().len(), 2);
+}
+
+#[test]
+fn test_fold_duplicates_combines_tags_and_dates() {
+    let primary = Todo {
+        title: "Buy milk".to_string(),
+        tags: vec!["shopping".to_string()],
+        ..todo_with_id("a")
+    };
+    let earlier = Todo {
+        title: "buy milk!".to_string(),
+        description: Some("2 liters".to_string()),
+        tags: vec!["errand".to_string(), "shopping".to_string()],
+        created_at: primary.created_at - chrono::Duration::days(2),
+        ..todo_with_id("b")
+    };
+    let later = Todo {
+        tags: vec!["home".to_string()],
+        updated_at: primary.updated_at + chrono::Duration::hours(1),
+        description: Some("oat".to_string()),
+        ..todo_with_id("c")
+    };
+
+    let merged = fold_duplicates(&primary, &[earlier.clone(), later.clone()]);
+    assert_eq!(merged.id, "a");
+    assert_eq!(merged.title, "Buy milk");
+    assert_eq!(merged.tags, vec!["shopping", "errand", "home"]);
+    assert_eq!(merged.created_at, earlier.created_at);
+    assert_eq!(merged.updated_at, later.updated_at);
+    assert_eq!(merged.description.as_deref(), Some("2 liters"));
+    assert_eq!(merged.completed_at, None);
+
+    // A completed todo was done when its first copy was.
+    let completed = |todo: Todo, completed_at| Todo {
+        completed: true,
+        completed_at: Some(completed_at),
+        ..todo
+    };
+    let merged = fold_duplicates(
+        &completed(primary.clone(), later.updated_at),
+        &[completed(earlier.clone(), earlier.created_at), later],
+    );
+    assert_eq!(merged.completed_at, Some(earlier.created_at));
+}
+
+#[test]
+fn test_merge_deletes_duplicates_and_repoints_references() {
+    for_each_backend(|storage| {
+        storage
+            .replace_todos(&[
+                Todo {
+                    title: "Buy milk".to_string(),
+                    ..todo_with_id("1111aaaa")
+                },
+                Todo {
+                    title: "Buy milk!".to_string(),
+                    tags: vec!["errand".to_string()],
+                    ..todo_with_id("2222bbbb")
+                },
+                Todo {
+                    title: "Bake".to_string(),
+                    description: Some("After #2222bb and [[Buy milk!]]".to_string()),
+                    ..todo_with_id("3333cccc")
+                },
+            ])
+            .unwrap();
+
+        let merged = storage
+            .merge_todos("1111aaaa", &["2222bbbb".to_string()])
+            .unwrap();
+        assert_eq!(merged.tags, vec!["work", "errand"]);
+        assert_eq!(listed_ids(storage), vec!["1111aaaa", "3333cccc"]);
+        let referencing = storage.get_todo("3333cccc").unwrap().unwrap();
+        assert_eq!(
+            referencing.description.as_deref(),
+            Some("After #1111aaaa and [[Buy milk]]")
+        );
+    });
+}
+
+#[test]
+fn test_merge_rejects_bad_ids_without_writing() {
+    for_each_backend(|storage| {
+        storage
+            .replace_todos(&[todo_with_id("a"), todo_with_id("b")])
+            .unwrap();
+
+        assert!(storage.merge_todos("a", &["a".to_string()]).is_err());
+        assert!(storage.merge_todos("a", &[]).is_err());
+        assert_eq!(
+            storage.merge_todos("a", &["b".to_string(), "zzz".to_string()]),
+            Err("Unknown todo 'zzz'".to_string())
+        );
+        assert_eq!(listed_ids(storage), vec!["a", "b"]);
+    });
+}
+
+#[test]
+fn test_split_turns_checklist_into_subtasks() {
+    for_each_backend(|storage| {
+        storage
+            .replace_todos(&[
+                Todo {
+                    title: "Trip".to_string(),
+                    description: Some(
+                        "Pack:\n- [ ] Passport\n- [x] Tickets\n  - [ ] Charger".to_string(),
+                    ),
+                    ..todo_with_id("trip")
+                },
+                todo_with_id("later"),
+            ])
+            .unwrap();
+
+        let children = storage.split_todo("trip", true).unwrap();
+        let titles: Vec<_> = children.iter().map(|todo| todo.title.as_str()).collect();
+        assert_eq!(titles, vec!["Passport", "Charger"]);
+        assert!(children.iter().all(|todo| todo.tags == vec!["work"]));
+        assert_eq!(
+            listed_ids(storage),
+            vec![
+                "trip",
+                children[0].id.as_str(),
+                children[1].id.as_str(),
+                "later"
+            ]
+        );
+
+        let parent = storage.get_todo("trip").unwrap().unwrap();
+        assert_eq!(
+            parent.description,
+            Some(format!(
+                "Pack:\n- [ ] #{}\n- [x] Tickets\n  - [ ] #{}",
+                children[0].id, children[1].id
+            ))
+        );
+    });
+}
+
+#[test]
+fn test_split_rejects_an_empty_note() {
+    for_each_backend(|storage| {
+        storage
+            .replace_todos(&[
+                todo_with_id("bare"),
+                Todo {
+                    description: Some("just words".to_string()),
+                    ..todo_with_id("prose")
+                },
+            ])
+            .unwrap();
+
+        assert!(storage.split_todo("bare", true).is_err());
+        assert!(storage.split_todo("prose", false).is_err());
+        assert_eq!(listed_ids(storage), vec!["bare", "prose"]);
+    });
+}
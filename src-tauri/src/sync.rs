@@ -0,0 +1,947 @@
+//! Shared todo lists over HTTP: lists published at a URL are polled in the
+//! background and merged into a dedicated list in the cache, and the local
+//! list can be published to a URL in turn. Only todos in the configured
+//! [`SyncScope`] are ever written into what is published.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::clock::{self, ClockState};
+use crate::events;
+use crate::export::{self, ExportFormat};
+use crate::import::{self, ImportError, ImportFormat};
+use crate::local_api::KEYCHAIN_SERVICE;
+use crate::protocol::EventPayload;
+use crate::query::{self, QueryAst};
+use crate::quota::{self, QuotaExceeded};
+use crate::retry;
+use crate::safety::{self, DestructionScope, DestructiveOperation, SafetyState};
+use crate::search::SearchIndex;
+use crate::settings::{self, SettingsState, SyncScope, SETTINGS_FILE};
+use crate::storage::{self, Storage};
+use crate::trace;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Larger responses are rejected rather than merged.
+const MAX_BODY_BYTES: u64 = 16 * 1024 * 1024;
+/// How often a polling thread checks whether it was stopped.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    /// Rejected input; the message is shown to the user as is.
+    #[error("{0}")]
+    Invalid(String),
+    #[error("request failed: {0}")]
+    Http(String),
+    #[error("network error: {0}")]
+    Network(String),
+    #[error("not authorized (server answered {0}); check the access token")]
+    Unauthorized(u16),
+    #[error("the list on the server changed since it was last published")]
+    PreconditionFailed,
+    #[error(transparent)]
+    Import(#[from] ImportError),
+    #[error(transparent)]
+    Quota(#[from] QuotaExceeded),
+    #[error("database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteSubscription {
+    pub id: String,
+    pub url: String,
+    pub interval_minutes: u64,
+    /// Tag carried by every todo of this list; its smart list filters on it.
+    pub list_tag: String,
+    pub etag: Option<String>,
+    /// SHA-256 of the last merged response body.
+    pub fingerprint: Option<String>,
+    pub last_checked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "camelCase")]
+pub enum PollOutcome {
+    /// The server answered 304 to our ETag.
+    NotModified,
+    /// The body was downloaded but is identical to the last one merged.
+    Unchanged,
+    Updated {
+        added: usize,
+        updated: usize,
+        removed: usize,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RemoteListUpdated {
+    subscription_id: String,
+    url: String,
+    added: usize,
+    updated: usize,
+    removed: usize,
+}
+
+impl EventPayload for RemoteListUpdated {}
+
+fn subscription_from_row(row: &Row<'_>) -> rusqlite::Result<RemoteSubscription> {
+    Ok(RemoteSubscription {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        interval_minutes: row.get::<_, i64>(2)? as u64,
+        list_tag: row.get(3)?,
+        etag: row.get(4)?,
+        fingerprint: row.get(5)?,
+        last_checked_at: row.get(6)?,
+    })
+}
+
+const SUBSCRIPTION_COLUMNS: &str =
+    "id, url, interval_minutes, list_tag, etag, fingerprint, last_checked_at";
+
+pub fn list_subscriptions(storage: &Storage) -> rusqlite::Result<Vec<RemoteSubscription>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM remote_subscriptions ORDER BY created_at",
+        SUBSCRIPTION_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], subscription_from_row)?;
+    rows.collect()
+}
+
+fn find_subscription(conn: &Connection, url: &str) -> rusqlite::Result<Option<RemoteSubscription>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM remote_subscriptions WHERE url = ?1",
+            SUBSCRIPTION_COLUMNS
+        ),
+        [url],
+        subscription_from_row,
+    )
+    .optional()
+}
+
+pub(crate) fn parse_url(url: &str) -> Result<url::Url, SyncError> {
+    url::Url::parse(url.trim())
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .ok_or_else(|| SyncError::Invalid(format!("'{}' is not an http(s) URL", url)))
+}
+
+/// Saves a subscription, or changes the interval of an existing one for the
+/// same URL. A new subscription also gets a smart list showing its todos.
+pub fn subscribe(
+    storage: &Storage,
+    url: &str,
+    interval_minutes: u64,
+) -> Result<RemoteSubscription, SyncError> {
+    let parsed = parse_url(url)?;
+    if interval_minutes == 0 {
+        return Err(SyncError::Invalid(
+            "Polling interval must be at least one minute".to_string(),
+        ));
+    }
+    let url = parsed.as_str();
+
+    let conn = storage.conn();
+    if let Some(mut existing) = find_subscription(&conn, url)? {
+        conn.execute(
+            "UPDATE remote_subscriptions SET interval_minutes = ?2 WHERE id = ?1",
+            params![existing.id, interval_minutes as i64],
+        )?;
+        existing.interval_minutes = interval_minutes;
+        return Ok(existing);
+    }
+
+    let host = parsed.host_str().unwrap_or("remote");
+    let base_tag = format!("remote-{}", host);
+    let taken: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM remote_subscriptions WHERE list_tag = ?1 OR list_tag LIKE ?2",
+        params![base_tag, format!("{}-%", base_tag)],
+        |row| row.get(0),
+    )?;
+    let list_tag = match taken {
+        0 => base_tag,
+        n => format!("{}-{}", base_tag, n + 1),
+    };
+    let subscription = RemoteSubscription {
+        id: uuid::Uuid::new_v4().to_string(),
+        url: url.to_string(),
+        interval_minutes,
+        list_tag,
+        etag: None,
+        fingerprint: None,
+        last_checked_at: None,
+    };
+    conn.execute(
+        "INSERT INTO remote_subscriptions (id, url, interval_minutes, list_tag, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            subscription.id,
+            subscription.url,
+            interval_minutes as i64,
+            subscription.list_tag,
+            storage.clock().now(),
+        ],
+    )?;
+    drop(conn);
+
+    query::create_list(storage, host, &format!("tag:{}", subscription.list_tag))
+        .map_err(SyncError::Invalid)?;
+    Ok(subscription)
+}
+
+/// Removes a subscription together with the todos it brought in. The smart
+/// list stays, since the user may have renamed or reused it. Returns the id
+/// of the removed subscription.
+pub fn unsubscribe(storage: &Storage, url: &str) -> Result<String, SyncError> {
+    let mut conn = storage.conn();
+    let subscription = find_subscription(&conn, parse_url(url)?.as_str())?
+        .ok_or_else(|| SyncError::Invalid(format!("Not subscribed to '{}'", url)))?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM todos WHERE id LIKE ?1",
+        [format!("{}%", id_prefix(&subscription))],
+    )?;
+    tx.execute(
+        "DELETE FROM todo_field_values WHERE todo_id NOT IN (SELECT id FROM todos)",
+        [],
+    )?;
+    tx.execute(
+        "DELETE FROM remote_subscriptions WHERE id = ?1",
+        [&subscription.id],
+    )?;
+    tx.commit()?;
+    Ok(subscription.id)
+}
+
+/// Cache ids of this list's todos start with this, keeping them apart from
+/// other lists that use the same remote ids.
+fn id_prefix(subscription: &RemoteSubscription) -> String {
+    format!("remote-{}-", &subscription.id[..8])
+}
+
+/// Guesses the format from the URL, then the content type, then the body.
+fn detect_format(url: &str, content_type: &str, body: &[u8]) -> ImportFormat {
+    let path = url::Url::parse(url)
+        .map(|u| u.path().to_string())
+        .unwrap_or_default();
+    if let Ok(format) = ImportFormat::from_path(Path::new(&path)) {
+        return format;
+    }
+    if content_type.contains("json") {
+        return ImportFormat::Json;
+    }
+    if content_type.contains("csv") {
+        return ImportFormat::Csv;
+    }
+    match body.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'[' | b'{') => ImportFormat::Json,
+        _ => ImportFormat::Csv,
+    }
+}
+
+/// A todo present on both sides whose content differs.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoChange {
+    pub before: Todo,
+    pub after: Todo,
+    pub changed_fields: Vec<String>,
+}
+
+/// What turning `local` into `remote` would change, matched by id.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoDiff {
+    pub added: Vec<Todo>,
+    pub removed: Vec<Todo>,
+    pub modified: Vec<TodoChange>,
+}
+
+pub fn diff_todos(local: &[Todo], remote: &[Todo]) -> TodoDiff {
+    let by_id: HashMap<&str, &Todo> = local.iter().map(|t| (t.id.as_str(), t)).collect();
+    let remote_ids: HashSet<&str> = remote.iter().map(|t| t.id.as_str()).collect();
+    let mut diff = TodoDiff::default();
+    for todo in remote {
+        match by_id.get(todo.id.as_str()) {
+            None => diff.added.push(todo.clone()),
+            Some(before) => {
+                let changed_fields = todo.changed_fields(before);
+                if !changed_fields.is_empty() {
+                    diff.modified.push(TodoChange {
+                        before: (*before).clone(),
+                        after: todo.clone(),
+                        changed_fields,
+                    });
+                }
+            }
+        }
+    }
+    diff.removed = local
+        .iter()
+        .filter(|t| !remote_ids.contains(t.id.as_str()))
+        .cloned()
+        .collect();
+    diff
+}
+
+/// The cached todos that belong to the subscription's list.
+fn list_todos_of(
+    conn: &Connection,
+    subscription: &RemoteSubscription,
+) -> rusqlite::Result<Vec<Todo>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM todos WHERE id LIKE ?1 ORDER BY id",
+        storage::TODO_COLUMNS
+    ))?;
+    let rows = stmt.query_map(
+        [format!("{}%", id_prefix(subscription))],
+        storage::todo_from_row,
+    )?;
+    rows.collect()
+}
+
+/// Turns downloaded todos into the cached todos they merge into: ids get the
+/// list's prefix and the list tag is added. Local-only state, such as the sort
+/// order, is kept from the cached copy. The first of duplicate ids wins.
+fn prepare_remote(
+    subscription: Option<&RemoteSubscription>,
+    remote: Vec<Todo>,
+    local: &[Todo],
+) -> Vec<Todo> {
+    let by_id: HashMap<&str, &Todo> = local.iter().map(|t| (t.id.as_str(), t)).collect();
+    let mut seen = HashSet::new();
+    let mut prepared = Vec::new();
+    for mut todo in remote {
+        if let Some(subscription) = subscription {
+            todo.id = format!("{}{}", id_prefix(subscription), todo.id);
+            if !todo.tags.contains(&subscription.list_tag) {
+                todo.tags.push(subscription.list_tag.clone());
+            }
+        }
+        if !seen.insert(todo.id.clone()) {
+            continue;
+        }
+        if let Some(current) = by_id.get(todo.id.as_str()) {
+            todo.order = current.order;
+            todo.rank = current.rank.clone();
+            todo.schedule_id = current.schedule_id.clone();
+            todo.created_at = current.created_at;
+            todo.revision = current.revision;
+        }
+        prepared.push(todo);
+    }
+    prepared
+}
+
+/// Makes the list's todos in the cache match `remote`, in one transaction.
+fn merge(
+    storage: &Storage,
+    subscription: &RemoteSubscription,
+    remote: Vec<Todo>,
+) -> Result<PollOutcome, SyncError> {
+    let mut conn = storage.conn();
+    let local = list_todos_of(&conn, subscription)?;
+    let diff = diff_todos(&local, &prepare_remote(Some(subscription), remote, &local));
+    let total = quota::count_todos(&conn)? + diff.added.len() as u64;
+    quota::check_todos(storage, total - diff.removed.len() as u64)?;
+
+    let tx = conn.transaction()?;
+    for todo in diff
+        .added
+        .iter()
+        .chain(diff.modified.iter().map(|change| &change.after))
+    {
+        storage::insert_todo(&tx, todo)?;
+    }
+    for todo in &diff.removed {
+        tx.execute("DELETE FROM todos WHERE id = ?1", [&todo.id])?;
+        tx.execute(
+            "DELETE FROM todo_field_values WHERE todo_id = ?1",
+            [&todo.id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(PollOutcome::Updated {
+        added: diff.added.len(),
+        updated: diff.modified.len(),
+        removed: diff.removed.len(),
+    })
+}
+
+fn request_error(error: ureq::Error) -> SyncError {
+    match error {
+        ureq::Error::Status(status @ (401 | 403), _) => SyncError::Unauthorized(status),
+        ureq::Error::Status(412, _) => SyncError::PreconditionFailed,
+        ureq::Error::Status(status, _) => SyncError::Http(format!("server answered {}", status)),
+        ureq::Error::Transport(e) => SyncError::Network(e.to_string()),
+    }
+}
+
+struct Download {
+    etag: Option<String>,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+/// `None` means the server confirmed our ETag is still current.
+fn download(
+    url: &str,
+    etag: Option<&str>,
+    token: Option<&str>,
+) -> Result<Option<Download>, SyncError> {
+    let mut request = ureq::get(url).timeout(FETCH_TIMEOUT);
+    if let Some(token) = token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    if let Some(etag) = etag {
+        request = request.set("If-None-Match", etag);
+    }
+    let response = request.call().map_err(request_error)?;
+    if response.status() == 304 {
+        return Ok(None);
+    }
+    let etag = response.header("ETag").map(str::to_string);
+    let content_type = response.content_type().to_ascii_lowercase();
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_BODY_BYTES + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| SyncError::Network(e.to_string()))?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err(SyncError::Http(format!(
+            "response is larger than {} MiB",
+            MAX_BODY_BYTES / 1024 / 1024
+        )));
+    }
+    Ok(Some(Download {
+        etag,
+        content_type,
+        body,
+    }))
+}
+
+fn read_download(
+    storage: &Storage,
+    url: &str,
+    download: &Download,
+) -> Result<Vec<Todo>, SyncError> {
+    let format = detect_format(url, &download.content_type, &download.body);
+    let mut file =
+        tempfile::NamedTempFile::new_in(storage.files_dir()).map_err(ImportError::from)?;
+    file.write_all(&download.body).map_err(ImportError::from)?;
+    Ok(import::read_todos(file.path(), format)?)
+}
+
+/// The todos of the list at `url` with its new ETag, sent with `token` as
+/// bearer token. `None` means `etag` is still current.
+pub(crate) fn fetch_list(
+    storage: &Storage,
+    url: &str,
+    etag: Option<&str>,
+    token: Option<&str>,
+) -> Result<Option<(Option<String>, Vec<Todo>)>, SyncError> {
+    let url = parse_url(url)?;
+    match download(url.as_str(), etag, token)? {
+        None => Ok(None),
+        Some(download) => {
+            let todos = read_download(storage, url.as_str(), &download)?;
+            Ok(Some((download.etag, todos)))
+        }
+    }
+}
+
+/// Fetches the list once and merges it if it changed since the last poll.
+pub fn poll(
+    storage: &Storage,
+    subscription: &mut RemoteSubscription,
+) -> Result<PollOutcome, SyncError> {
+    let fetched = download(&subscription.url, subscription.etag.as_deref(), None)?;
+    subscription.last_checked_at = Some(storage.clock().now());
+
+    let outcome = match fetched {
+        None => PollOutcome::NotModified,
+        Some(download) => {
+            subscription.etag = download.etag.clone();
+            let fingerprint = format!("{:x}", Sha256::digest(&download.body));
+            if subscription.fingerprint.as_deref() == Some(fingerprint.as_str()) {
+                PollOutcome::Unchanged
+            } else {
+                let todos = read_download(storage, &subscription.url, &download)?;
+                let outcome = merge(storage, subscription, todos)?;
+                subscription.fingerprint = Some(fingerprint);
+                outcome
+            }
+        }
+    };
+
+    storage.conn().execute(
+        "UPDATE remote_subscriptions SET etag = ?2, fingerprint = ?3, last_checked_at = ?4
+         WHERE id = ?1",
+        params![
+            subscription.id,
+            subscription.etag,
+            subscription.fingerprint,
+            subscription.last_checked_at,
+        ],
+    )?;
+    Ok(outcome)
+}
+
+/// Downloads the list at `url` and diffs it against the cache without
+/// changing anything. For a URL that is not subscribed yet, everything is new.
+pub fn preview(storage: &Storage, url: &str) -> Result<TodoDiff, SyncError> {
+    let url = parse_url(url)?;
+    let download = download(url.as_str(), None, None)?
+        .ok_or_else(|| SyncError::Http("server answered 304 without an ETag".to_string()))?;
+    let remote = read_download(storage, url.as_str(), &download)?;
+
+    let subscription = find_subscription(&storage.conn(), url.as_str())?;
+    let local = match &subscription {
+        Some(subscription) => list_todos_of(&storage.conn(), subscription)?,
+        None => Vec::new(),
+    };
+    Ok(diff_todos(
+        &local,
+        &prepare_remote(subscription.as_ref(), remote, &local),
+    ))
+}
+
+/// A [`SyncScope`] with the queries of its lists parsed.
+struct ResolvedScope<'a> {
+    scope: &'a SyncScope,
+    include: Vec<QueryAst>,
+    exclude: Vec<QueryAst>,
+    today: NaiveDate,
+}
+
+impl<'a> ResolvedScope<'a> {
+    /// Fails on a list that doesn't exist, rather than letting todos it
+    /// was meant to keep local through.
+    fn new(conn: &Connection, scope: &'a SyncScope, today: NaiveDate) -> Result<Self, SyncError> {
+        let parse = |ids: &[String]| {
+            ids.iter()
+                .map(|id| {
+                    let query: String = conn
+                        .query_row("SELECT query FROM smart_lists WHERE id = ?1", [id], |row| {
+                            row.get(0)
+                        })
+                        .optional()?
+                        .ok_or_else(|| {
+                            SyncError::Invalid(format!("Sync scope names unknown list '{}'", id))
+                        })?;
+                    query::parse_query(&query)
+                        .map_err(|e| SyncError::Invalid(format!("Syntax error: {}", e)))
+                })
+                .collect::<Result<Vec<_>, SyncError>>()
+        };
+        Ok(Self {
+            scope,
+            include: parse(&scope.include_lists)?,
+            exclude: parse(&scope.exclude_lists)?,
+            today,
+        })
+    }
+
+    fn allows(&self, todo: &Todo) -> bool {
+        let tagged = |tags: &[String]| {
+            todo.tags
+                .iter()
+                .any(|tag| tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+        };
+        let listed = |lists: &[QueryAst]| {
+            lists
+                .iter()
+                .any(|ast| query::matches(ast, todo, self.today))
+        };
+        let included = (self.scope.include_tags.is_empty() && self.include.is_empty())
+            || tagged(&self.scope.include_tags)
+            || listed(&self.include);
+        included && !tagged(&self.scope.exclude_tags) && !listed(&self.exclude)
+    }
+}
+
+/// Splits `todos` into those `scope` lets leave the machine and those it
+/// keeps local.
+pub fn partition_scope(
+    conn: &Connection,
+    scope: &SyncScope,
+    todos: Vec<Todo>,
+    today: NaiveDate,
+) -> Result<(Vec<Todo>, Vec<Todo>), SyncError> {
+    let resolved = ResolvedScope::new(conn, scope, today)?;
+    Ok(todos.into_iter().partition(|todo| resolved.allows(todo)))
+}
+
+/// Todos in scope under `old` that `new` would keep local. Publishing
+/// replaces the whole list, so the next publish removes them remotely.
+pub fn newly_excluded(
+    storage: &Storage,
+    old: &SyncScope,
+    new: &SyncScope,
+) -> Result<Vec<Todo>, SyncError> {
+    let todos = storage.list_todos()?;
+    let today = storage.clock().today();
+    let conn = storage.conn();
+    let (shared, _) = partition_scope(&conn, old, todos, today)?;
+    let (_, excluded) = partition_scope(&conn, new, shared, today)?;
+    Ok(excluded)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncScopeReport {
+    pub in_scope: usize,
+    pub local_only: usize,
+}
+
+pub fn scope_report(storage: &Storage, scope: &SyncScope) -> Result<SyncScopeReport, SyncError> {
+    let todos = storage.list_todos()?;
+    let (in_scope, local_only) =
+        partition_scope(&storage.conn(), scope, todos, storage.clock().today())?;
+    Ok(SyncScopeReport {
+        in_scope: in_scope.len(),
+        local_only: local_only.len(),
+    })
+}
+
+/// Replaces `[backend.sync_scope]` in the settings file, keeping everything
+/// else in it.
+pub fn save_scope_to_settings_file(settings_path: &Path, scope: &SyncScope) -> Result<(), String> {
+    let content = match fs::read_to_string(settings_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read settings: {}", e)),
+    };
+    let mut table: toml::Table = content
+        .parse()
+        .map_err(|e| format!("Failed to parse settings: {}", e))?;
+    let mut backend = settings::take_table(&mut table, "backend")?;
+    let scope =
+        toml::Value::try_from(scope).map_err(|e| format!("Failed to write settings: {}", e))?;
+    backend.insert("sync_scope".to_string(), scope);
+    table.insert("backend".to_string(), backend.into());
+    let content =
+        toml::to_string(&table).map_err(|e| format!("Failed to write settings: {}", e))?;
+    fs::write(settings_path, content).map_err(|e| format!("Failed to write settings: {}", e))
+}
+
+/// PUTs the todos in `scope` to `url`; the others are never serialized.
+/// Sends `If-Match` with the ETag from the last publish (or download) of the
+/// same URL, so a list someone else updated in the meantime is not
+/// overwritten.
+pub fn publish(
+    storage: &Storage,
+    url: &str,
+    format: ExportFormat,
+    token: Option<&str>,
+    scope: &SyncScope,
+) -> Result<(), SyncError> {
+    let url = parse_url(url)?;
+    let url = url.as_str();
+    let todos = storage.list_todos()?;
+    let (todos, _) = partition_scope(&storage.conn(), scope, todos, storage.clock().today())?;
+    let mut body = Vec::new();
+    export::write_todos(&todos, format, false, &mut body).map_err(SyncError::Invalid)?;
+    let etag: Option<String> = storage.conn().query_row(
+        "SELECT COALESCE(
+                 (SELECT etag FROM publish_targets WHERE url = ?1),
+                 (SELECT etag FROM remote_subscriptions WHERE url = ?1))",
+        [url],
+        |row| row.get(0),
+    )?;
+
+    let mut request = ureq::put(url)
+        .timeout(FETCH_TIMEOUT)
+        .set("Content-Type", format.content_type());
+    if let Some(token) = token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    if let Some(etag) = &etag {
+        request = request.set("If-Match", etag);
+    }
+    let response = request.send_bytes(&body).map_err(request_error)?;
+
+    storage.conn().execute(
+        "INSERT OR REPLACE INTO publish_targets (url, etag, published_at) VALUES (?1, ?2, ?3)",
+        params![url, response.header("ETag"), storage.clock().now()],
+    )?;
+    Ok(())
+}
+
+fn publish_token_entry(url: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, &format!("publish-token:{}", url))
+        .map_err(|e| format!("Failed to open keychain: {}", e))
+}
+
+/// Publishes with the bearer token stored for `url`, if there is one.
+pub fn publish_with_stored_token(
+    storage: &Storage,
+    url: &str,
+    format: ExportFormat,
+    scope: &SyncScope,
+) -> Result<(), String> {
+    let token = match publish_token_entry(url)?.get_password() {
+        Ok(token) => Some(token),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => return Err(format!("Failed to read publish token from keychain: {}", e)),
+    };
+    publish(storage, url, format, token.as_deref(), scope)
+        .map_err(|e| format!("Failed to publish to {}: {}", url, e))
+}
+
+/// Removes the publish tokens of every list published so far.
+pub fn delete_publish_tokens(storage: &Storage) -> Result<(), String> {
+    let urls: Vec<String> = {
+        let conn = storage.conn();
+        let mut stmt = conn
+            .prepare("SELECT url FROM publish_targets")
+            .map_err(|e| e.to_string())?;
+        let urls = stmt
+            .query_map([], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(|e| e.to_string())?;
+        urls
+    };
+    for url in urls {
+        match publish_token_entry(&url)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(format!("Failed to remove publish token: {}", e)),
+        }
+    }
+    Ok(())
+}
+
+/// Stop flags of the polling threads, by subscription id.
+#[derive(Default)]
+pub struct SyncState {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl SyncState {
+    fn running(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<AtomicBool>>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Number of subscriptions currently being polled.
+    pub fn active_pollers(&self) -> usize {
+        self.running().len()
+    }
+}
+
+/// Polls one subscription until it is stopped. Restarting replaces a thread
+/// that is already running for it, e.g. after the interval changed.
+fn spawn_poller(app: &AppHandle, mut subscription: RemoteSubscription) {
+    let stop = Arc::new(AtomicBool::new(false));
+    if let Some(previous) = app
+        .state::<SyncState>()
+        .running()
+        .insert(subscription.id.clone(), stop.clone())
+    {
+        previous.store(true, Ordering::SeqCst);
+    }
+
+    let app = app.clone();
+    let clock = app.state::<ClockState>().shared();
+    std::thread::spawn(move || {
+        let interval = Duration::from_secs(subscription.interval_minutes * 60);
+        clock::run_every(clock.as_ref(), interval, &stop, STOP_CHECK_INTERVAL, || {
+            let storage = app.state::<Storage>();
+            match poll(&storage, &mut subscription) {
+                Ok(PollOutcome::Updated {
+                    added,
+                    updated,
+                    removed,
+                }) => {
+                    if let Ok(todos) = storage.list_todos() {
+                        app.state::<SearchIndex>().rebuild(&todos);
+                    }
+                    let _ = events::emit(&app, "todo-cache-updated", ());
+                    quota::notify(&app);
+                    let _ = events::emit(
+                        &app,
+                        "remote-list-updated",
+                        &RemoteListUpdated {
+                            subscription_id: subscription.id.clone(),
+                            url: subscription.url.clone(),
+                            added,
+                            updated,
+                            removed,
+                        },
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => trace::log(format!("Failed to refresh {}: {}", subscription.url, e)),
+            }
+        });
+    });
+}
+
+/// Starts polling every saved subscription. Requires `Storage` to be managed.
+pub fn resume_subscriptions(app: &AppHandle) {
+    match list_subscriptions(&app.state::<Storage>()) {
+        Ok(subscriptions) => {
+            for subscription in subscriptions {
+                spawn_poller(app, subscription);
+            }
+        }
+        Err(e) => trace::log(format!("Failed to load remote lists: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub fn subscribe_remote_list(
+    url: String,
+    interval_minutes: u64,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<(), String> {
+    let subscription = subscribe(&storage, &url, interval_minutes)
+        .map_err(|e| format!("Failed to subscribe to {}: {}", url, e))?;
+    spawn_poller(&app, subscription);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn unsubscribe_remote_list(
+    url: String,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    state: State<'_, SyncState>,
+) -> Result<(), String> {
+    let id = unsubscribe(&storage, &url)
+        .map_err(|e| format!("Failed to unsubscribe from {}: {}", url, e))?;
+    if let Some(stop) = state.running().remove(&id) {
+        stop.store(true, Ordering::SeqCst);
+    }
+    let _ = events::emit(&app, "todo-cache-updated", ());
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn preview_remote_merge(
+    url: String,
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<TodoDiff, String> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        preview(&app.state::<Storage>(), &url)
+            .map_err(|e| format!("Failed to preview {}: {}", url, e))
+    }))
+    .await
+    .map_err(|e| format!("Failed to preview: {}", e))?
+}
+
+#[tauri::command]
+pub async fn publish_list(
+    url: String,
+    format: ExportFormat,
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(trace::bind_request(request_id, move || {
+        let scope = app.state::<SettingsState>().get().sync_scope;
+        publish_with_stored_token(&app.state::<Storage>(), &url, format, &scope)
+            .inspect_err(|e| retry::record(&app, retry::Operation::PublishList { url, format }, e))
+    }))
+    .await
+    .map_err(|e| format!("Failed to publish: {}", e))?
+}
+
+/// Stores the bearer token sent when publishing to `url`, or removes it.
+#[tauri::command]
+pub fn set_publish_token(url: String, token: Option<String>) -> Result<(), String> {
+    let entry = publish_token_entry(&url)?;
+    match token.filter(|t| !t.trim().is_empty()) {
+        Some(token) => entry
+            .set_password(token.trim())
+            .map_err(|e| format!("Failed to store publish token in keychain: {}", e)),
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to remove publish token: {}", e)),
+        },
+    }
+}
+
+/// Saves which todos publishing may send. Keeping local todos that were in
+/// scope before takes them off the server at the next publish, so once
+/// anything was published that needs a confirmation token for
+/// [`DestructiveOperation::ExcludeFromSync`] scoped to their count.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn set_sync_scope(
+    scope: SyncScope,
+    confirmation_token: Option<String>,
+    typed_count: Option<usize>,
+    app: AppHandle,
+    storage: State<'_, Storage>,
+    safety: State<'_, SafetyState>,
+    state: State<'_, SettingsState>,
+) -> Result<SyncScopeReport, String> {
+    let failed = |e: SyncError| format!("Failed to set sync scope: {}", e);
+    let mut settings = state.get();
+    let excluded = newly_excluded(&storage, &settings.sync_scope, &scope).map_err(failed)?;
+    let published: i64 = storage
+        .conn()
+        .query_row("SELECT COUNT(*) FROM publish_targets", [], |row| row.get(0))
+        .map_err(|e| failed(e.into()))?;
+    if !excluded.is_empty() && published > 0 {
+        safety::authorize(
+            &safety,
+            &settings.safety,
+            &storage,
+            confirmation_token.as_deref(),
+            typed_count,
+            DestructiveOperation::ExcludeFromSync,
+            &DestructionScope {
+                count: excluded.len(),
+                target: None,
+            },
+        )
+        .map_err(|e| format!("Failed to set sync scope: {}", e))?;
+    }
+    let config_dir =
+        settings::config_dir(&app).map_err(|e| format!("Failed to locate settings: {}", e))?;
+    fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to write settings: {}", e))?;
+    save_scope_to_settings_file(&config_dir.join(SETTINGS_FILE), &scope)?;
+    let report = scope_report(&storage, &scope).map_err(failed)?;
+    settings.sync_scope = scope;
+    state.replace(settings);
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn get_sync_scope_report(
+    storage: State<'_, Storage>,
+    state: State<'_, SettingsState>,
+) -> Result<SyncScopeReport, String> {
+    scope_report(&storage, &state.get().sync_scope)
+        .map_err(|e| format!("Failed to report sync scope: {}", e))
+}
+
+#[tauri::command]
+pub fn list_remote_lists(storage: State<'_, Storage>) -> Result<Vec<RemoteSubscription>, String> {
+    list_subscriptions(&storage).map_err(|e| format!("Failed to list remote lists: {}", e))
+}
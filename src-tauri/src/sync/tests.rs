@@ -0,0 +1,430 @@
+use super::*;
+
+use tiny_http::{Header, Response, Server};
+
+/// What the fake list server currently publishes.
+#[derive(Default)]
+struct Published {
+    body: String,
+    etag: Option<String>,
+    /// `If-None-Match` values received, one per request.
+    seen_etags: Vec<Option<String>>,
+}
+
+fn serve_list(published: Arc<Mutex<Published>>) -> String {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/team/todos", server.server_addr());
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let if_none_match = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("If-None-Match"))
+                .map(|h| h.value.as_str().to_string());
+            let mut published = published.lock().unwrap();
+            published.seen_etags.push(if_none_match.clone());
+            let response = match &published.etag {
+                Some(etag) if if_none_match.as_ref() == Some(etag) => {
+                    Response::from_string("").with_status_code(304)
+                }
+                Some(etag) => Response::from_string(published.body.clone())
+                    .with_header(Header::from_bytes("ETag", etag.as_str()).unwrap()),
+                None => Response::from_string(published.body.clone()),
+            };
+            let _ = request.respond(response);
+        }
+    });
+    url
+}
+
+fn list_titles(storage: &Storage) -> Vec<String> {
+    let mut titles: Vec<_> = storage
+        .list_todos()
+        .unwrap()
+        .into_iter()
+        .map(|t| t.title)
+        .collect();
+    titles.sort();
+    titles
+}
+
+#[test]
+fn test_matching_etag_skips_download() {
+    let published = Arc::new(Mutex::new(Published {
+        body: r#"[{"id": "1", "title": "Book venue"}]"#.to_string(),
+        etag: Some("\"v1\"".to_string()),
+        ..Default::default()
+    }));
+    let url = serve_list(published.clone());
+    let storage = Storage::open_in_memory().unwrap();
+    let mut subscription = subscribe(&storage, &url, 15).unwrap();
+
+    assert_eq!(
+        poll(&storage, &mut subscription).unwrap(),
+        PollOutcome::Updated {
+            added: 1,
+            updated: 0,
+            removed: 0
+        }
+    );
+    assert_eq!(subscription.etag.as_deref(), Some("\"v1\""));
+    assert_eq!(
+        poll(&storage, &mut subscription).unwrap(),
+        PollOutcome::NotModified
+    );
+    assert_eq!(
+        published.lock().unwrap().seen_etags,
+        vec![None, Some("\"v1\"".to_string())]
+    );
+
+    // The ETag survives a restart.
+    let mut reloaded = list_subscriptions(&storage).unwrap().remove(0);
+    assert_eq!(reloaded.etag.as_deref(), Some("\"v1\""));
+    assert_eq!(
+        poll(&storage, &mut reloaded).unwrap(),
+        PollOutcome::NotModified
+    );
+    assert_eq!(list_titles(&storage), vec!["Book venue"]);
+}
+
+#[test]
+fn test_only_changed_content_is_merged() {
+    let published = Arc::new(Mutex::new(Published {
+        body: "id,title\n1,Book venue\n2,Send invites\n".to_string(),
+        ..Default::default()
+    }));
+    let url = serve_list(published.clone());
+    let storage = Storage::open_in_memory().unwrap();
+    let mut subscription = subscribe(&storage, &url, 15).unwrap();
+
+    assert_eq!(
+        poll(&storage, &mut subscription).unwrap(),
+        PollOutcome::Updated {
+            added: 2,
+            updated: 0,
+            removed: 0
+        }
+    );
+    assert_eq!(
+        poll(&storage, &mut subscription).unwrap(),
+        PollOutcome::Unchanged
+    );
+
+    published.lock().unwrap().body = r#"[
+        {"id": "1", "title": "Book venue", "completed": true},
+        {"id": "3", "title": "Order cake"}
+    ]"#
+    .to_string();
+    assert_eq!(
+        poll(&storage, &mut subscription).unwrap(),
+        PollOutcome::Updated {
+            added: 1,
+            updated: 1,
+            removed: 1
+        }
+    );
+    assert_eq!(list_titles(&storage), vec!["Book venue", "Order cake"]);
+
+    let todos = storage.list_todos().unwrap();
+    assert!(todos
+        .iter()
+        .all(|t| t.tags == vec![subscription.list_tag.clone()]));
+    let list = query::list_lists(&storage).unwrap().remove(0);
+    assert_eq!(list.name, "127.0.0.1");
+    assert_eq!(
+        query::smart_list_todos(&storage, &list.id, Utc::now().date_naive())
+            .unwrap()
+            .len(),
+        2
+    );
+
+    unsubscribe(&storage, &url).unwrap();
+    assert!(storage.list_todos().unwrap().is_empty());
+}
+
+#[test]
+fn test_subscribe_rejects_bad_input() {
+    let storage = Storage::open_in_memory().unwrap();
+    assert!(subscribe(&storage, "ftp://example.com/list.csv", 15).is_err());
+    assert!(subscribe(&storage, "https://example.com/list.csv", 0).is_err());
+}
+
+/// Server side of the publish tests: accepts a PUT only with the right token
+/// and, once it has a version, only with a matching `If-Match`.
+struct Upload {
+    version: u32,
+    body: Vec<u8>,
+    if_match: Vec<Option<String>>,
+}
+
+fn header(request: &tiny_http::Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+fn serve_upload(upload: Arc<Mutex<Upload>>, token: &'static str) -> String {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/shared/list.json", server.server_addr());
+    std::thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let authorization = header(&request, "Authorization");
+            let if_match = header(&request, "If-Match");
+            let mut body = Vec::new();
+            request.as_reader().read_to_end(&mut body).unwrap();
+
+            let mut upload = upload.lock().unwrap();
+            upload.if_match.push(if_match.clone());
+            let current = format!("\"v{}\"", upload.version);
+            let status = if authorization != Some(format!("Bearer {}", token)) {
+                401
+            } else if if_match.is_some_and(|etag| etag != current) {
+                412
+            } else {
+                upload.version += 1;
+                upload.body = body;
+                204
+            };
+            let etag = format!("\"v{}\"", upload.version);
+            let response = Response::empty(status)
+                .with_header(Header::from_bytes("ETag", etag.as_str()).unwrap());
+            let _ = request.respond(response);
+        }
+    });
+    url
+}
+
+fn sample_storage() -> Storage {
+    let storage = Storage::open_in_memory().unwrap();
+    let now = Utc::now();
+    storage
+        .save_todo(&Todo::sample("a", "Book venue", now))
+        .unwrap();
+    storage
+}
+
+#[test]
+fn test_publish_uploads_with_last_etag() {
+    let upload = Arc::new(Mutex::new(Upload {
+        version: 1,
+        body: Vec::new(),
+        if_match: Vec::new(),
+    }));
+    let url = serve_upload(upload.clone(), "secret");
+    let storage = sample_storage();
+
+    publish(
+        &storage,
+        &url,
+        ExportFormat::Json,
+        Some("secret"),
+        &SyncScope::default(),
+    )
+    .unwrap();
+    publish(
+        &storage,
+        &url,
+        ExportFormat::Json,
+        Some("secret"),
+        &SyncScope::default(),
+    )
+    .unwrap();
+
+    let upload = upload.lock().unwrap();
+    assert_eq!(upload.if_match, vec![None, Some("\"v2\"".to_string())]);
+    let published: Vec<Todo> = serde_json::from_slice(&upload.body).unwrap();
+    assert_eq!(published[0].title, "Book venue");
+}
+
+#[test]
+fn test_publish_distinguishes_failures() {
+    let upload = Arc::new(Mutex::new(Upload {
+        version: 1,
+        body: Vec::new(),
+        if_match: Vec::new(),
+    }));
+    let url = serve_upload(upload.clone(), "secret");
+    let storage = sample_storage();
+
+    assert!(matches!(
+        publish(
+            &storage,
+            &url,
+            ExportFormat::Csv,
+            Some("wrong"),
+            &SyncScope::default()
+        ),
+        Err(SyncError::Unauthorized(401))
+    ));
+    publish(
+        &storage,
+        &url,
+        ExportFormat::Csv,
+        Some("secret"),
+        &SyncScope::default(),
+    )
+    .unwrap();
+
+    // Someone else publishes in between.
+    upload.lock().unwrap().version += 1;
+    assert!(matches!(
+        publish(
+            &storage,
+            &url,
+            ExportFormat::Csv,
+            Some("secret"),
+            &SyncScope::default()
+        ),
+        Err(SyncError::PreconditionFailed)
+    ));
+
+    let closed = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/list.csv", listener.local_addr().unwrap())
+    };
+    assert!(matches!(
+        publish(
+            &storage,
+            &closed,
+            ExportFormat::Csv,
+            None,
+            &SyncScope::default()
+        ),
+        Err(SyncError::Network(_))
+    ));
+}
+
+#[test]
+fn test_preview_shows_delta_without_applying() {
+    let published = Arc::new(Mutex::new(Published {
+        body: "id,title\n1,Book venue\n2,Send invites\n".to_string(),
+        ..Default::default()
+    }));
+    let url = serve_list(published.clone());
+    let storage = Storage::open_in_memory().unwrap();
+
+    let diff = preview(&storage, &url).unwrap();
+    assert_eq!(diff.added.len(), 2);
+    assert!(diff.removed.is_empty() && diff.modified.is_empty());
+
+    let mut subscription = subscribe(&storage, &url, 15).unwrap();
+    poll(&storage, &mut subscription).unwrap();
+    let before = storage.list_todos().unwrap();
+
+    published.lock().unwrap().body =
+        "id,title,priority\n1,Book bigger venue,medium\n2,Send invites,medium\n3,Order cake,low\n"
+            .to_string();
+    let diff = preview(&storage, &url).unwrap();
+    let titles = |todos: &[Todo]| todos.iter().map(|t| t.title.clone()).collect::<Vec<_>>();
+    assert_eq!(titles(&diff.added), vec!["Order cake"]);
+    assert!(diff.removed.is_empty());
+    assert_eq!(diff.modified.len(), 1);
+    assert_eq!(diff.modified[0].before.title, "Book venue");
+    assert_eq!(diff.modified[0].after.title, "Book bigger venue");
+    assert_eq!(diff.modified[0].changed_fields, vec!["title"]);
+
+    assert_eq!(storage.list_todos().unwrap(), before);
+}
+
+fn scoped_storage() -> Storage {
+    let storage = sample_storage();
+    let book = storage.get_todo("a").unwrap().unwrap();
+    for (id, title, tag) in [("b", "Dentist", "Personal"), ("c", "Standup", "work")] {
+        storage
+            .save_todo(&Todo {
+                id: id.to_string(),
+                title: title.to_string(),
+                tags: vec![tag.to_string()],
+                ..book.clone()
+            })
+            .unwrap();
+    }
+    storage
+}
+
+#[test]
+fn test_publish_never_sends_todos_outside_the_scope() {
+    let upload = Arc::new(Mutex::new(Upload {
+        version: 1,
+        body: Vec::new(),
+        if_match: Vec::new(),
+    }));
+    let url = serve_upload(upload.clone(), "secret");
+    let storage = scoped_storage();
+    let scope = SyncScope {
+        exclude_tags: vec!["personal".to_string()],
+        ..Default::default()
+    };
+
+    publish(&storage, &url, ExportFormat::Json, Some("secret"), &scope).unwrap();
+
+    let published: Vec<Todo> = serde_json::from_slice(&upload.lock().unwrap().body).unwrap();
+    let ids: Vec<_> = published.iter().map(|t| t.id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "c"]);
+    assert!(!String::from_utf8_lossy(&upload.lock().unwrap().body).contains("Dentist"));
+    assert_eq!(
+        scope_report(&storage, &scope).unwrap(),
+        SyncScopeReport {
+            in_scope: 2,
+            local_only: 1,
+        }
+    );
+}
+
+#[test]
+fn test_scope_includes_lists_and_rejects_unknown_ones() {
+    let storage = scoped_storage();
+    let work = query::create_list(&storage, "Work", "tag:work").unwrap();
+    let scope = SyncScope {
+        include_lists: vec![work.id],
+        ..Default::default()
+    };
+    let (shared, local) = partition_scope(
+        &storage.conn(),
+        &scope,
+        storage.list_todos().unwrap(),
+        storage.clock().today(),
+    )
+    .unwrap();
+    assert_eq!(shared.len(), 1);
+    assert_eq!(shared[0].id, "c");
+    assert_eq!(local.len(), 2);
+
+    let unknown = SyncScope {
+        exclude_lists: vec!["gone".to_string()],
+        ..Default::default()
+    };
+    assert!(matches!(
+        scope_report(&storage, &unknown),
+        Err(SyncError::Invalid(_))
+    ));
+}
+
+#[test]
+fn test_newly_excluded_are_only_todos_that_were_shared() {
+    let storage = scoped_storage();
+    let old = SyncScope {
+        exclude_tags: vec!["personal".to_string()],
+        ..Default::default()
+    };
+    let new = SyncScope {
+        exclude_tags: vec!["personal".to_string(), "work".to_string()],
+        ..Default::default()
+    };
+
+    let excluded = newly_excluded(&storage, &old, &new).unwrap();
+    assert_eq!(excluded.len(), 1);
+    assert_eq!(excluded[0].id, "c");
+    // Moving back into scope takes nothing away; the next publish adds it.
+    assert!(newly_excluded(&storage, &new, &old).unwrap().is_empty());
+}
+
+#[test]
+fn test_change_feed_is_not_limited_to_the_sync_scope() {
+    let storage = scoped_storage();
+    let changes = storage::changes::changes_since(&storage.conn(), 0, 100).unwrap();
+    let ids: HashSet<_> = changes.changes.iter().map(|c| c.id.as_str()).collect();
+    assert!(ids.contains("b"));
+}
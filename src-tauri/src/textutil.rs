@@ -0,0 +1,85 @@
+//! Line wrapping for mixed Latin and CJK text. Latin words break at spaces,
+//! CJK text between any two characters, except before closing punctuation.
+
+#[cfg(test)]
+mod tests;
+
+/// Characters that may not start a line (simplified kinsoku shori).
+const NO_LINE_START: &str = "、。，．・：；？！）」』】〕〉》”’ー～…,.!?:;)]}";
+
+/// Whether a line may break before and after `c` without a space: CJK
+/// ideographs, kana, hangul, full-width forms and emoji.
+pub fn breaks_anywhere(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x115F
+            | 0x2E80..=0x303F
+            | 0x3040..=0x30FF
+            | 0x3100..=0x31FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xAC00..=0xD7AF
+            | 0xF900..=0xFAFF
+            | 0xFF00..=0xFFEF
+            | 0x1F300..=0x1FAFF
+            | 0x20000..=0x3FFFF
+    )
+}
+
+/// Splits a paragraph into the smallest pieces that must stay together.
+fn segments(paragraph: &str) -> Vec<&str> {
+    let mut segments: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut chars = paragraph.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let end = i + c.len_utf8();
+        let next = chars.peek().map(|&(_, next)| next);
+        let boundary = match next {
+            None => true,
+            Some(next) if NO_LINE_START.contains(next) => false,
+            Some(next) => {
+                c.is_whitespace() != next.is_whitespace()
+                    || breaks_anywhere(c)
+                    || breaks_anywhere(next)
+            }
+        };
+        if boundary {
+            segments.push(&paragraph[start..end]);
+            start = end;
+        }
+    }
+    segments
+}
+
+/// Wraps `text` into lines no wider than `max_width` as measured by
+/// `measure`. Newlines always break; a piece wider than a whole line is split
+/// by characters. Spaces at a break are dropped.
+pub fn wrap(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for segment in segments(paragraph) {
+            let candidate = format!("{}{}", line, segment);
+            if measure(candidate.trim_end()) <= max_width || line.trim().is_empty() {
+                line = candidate;
+            } else {
+                lines.push(line.trim_end().to_string());
+                line = segment.trim_start().to_string();
+            }
+            // A single overlong piece, e.g. a URL, is split where it must be.
+            while measure(line.trim_end()) > max_width && line.chars().count() > 1 {
+                let mut head = String::new();
+                for c in line.chars() {
+                    if !head.is_empty() && measure(&format!("{}{}", head, c)) > max_width {
+                        break;
+                    }
+                    head.push(c);
+                }
+                line = line[head.len()..].trim_start().to_string();
+                lines.push(head);
+            }
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
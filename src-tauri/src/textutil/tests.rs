@@ -0,0 +1,57 @@
+use super::*;
+
+/// One unit per character, two for CJK, like a terminal.
+fn columns(s: &str) -> f32 {
+    s.chars()
+        .map(|c| if breaks_anywhere(c) { 2.0 } else { 1.0 })
+        .sum()
+}
+
+#[test]
+fn test_latin_breaks_at_spaces() {
+    assert_eq!(
+        wrap("pay the rent before friday", 12.0, columns),
+        vec!["pay the rent", "before", "friday"]
+    );
+}
+
+#[test]
+fn test_cjk_breaks_between_characters() {
+    assert_eq!(
+        wrap("会議の資料を準備する", 8.0, columns),
+        vec!["会議の資", "料を準備", "する"]
+    );
+}
+
+#[test]
+fn test_closing_punctuation_stays_on_the_line() {
+    assert_eq!(
+        wrap("資料を準備。送付する", 10.0, columns),
+        vec!["資料を準", "備。送付す", "る"]
+    );
+}
+
+#[test]
+fn test_mixed_text_and_newlines() {
+    assert_eq!(
+        wrap("Review 設計書\nthen ship", 10.0, columns),
+        vec!["Review 設", "計書", "then ship"]
+    );
+}
+
+#[test]
+fn test_overlong_word_is_split() {
+    assert_eq!(
+        wrap("see https://example.com/x", 8.0, columns),
+        vec!["see", "https://", "example.", "com/x"]
+    );
+}
+
+#[test]
+fn test_empty_and_emoji_only_text() {
+    assert_eq!(wrap("", 10.0, columns), vec![""]);
+    assert_eq!(
+        wrap("🎉🎉🎉🎉🎉🎉", 5.0, columns),
+        vec!["🎉🎉", "🎉🎉", "🎉🎉"]
+    );
+}
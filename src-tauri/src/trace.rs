@@ -0,0 +1,189 @@
+//! Request ids tying a command's log lines, events and errors together, so
+//! an error toast can be matched with the log bundle a user submits.
+//!
+//! The frontend may pass `requestId` with any invoke; [`wrap`] wraps every
+//! command and makes it current for the command's thread. Without one, an id
+//! is only generated once something asks for it, so untraced commands cost a
+//! thread-local write and nothing else. Work that leaves the thread carries
+//! the id along with [`bind`].
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::Runtime;
+
+#[cfg(test)]
+mod tests;
+
+/// Invoke argument carrying the frontend's request id.
+pub const REQUEST_ID_ARG: &str = "requestId";
+/// Field added to object payloads of events emitted during a request.
+pub const EVENT_FIELD: &str = "requestId";
+
+/// `None` while no request is being handled; `Some(None)` inside one whose
+/// id has not been needed yet.
+type Current = Option<Option<String>>;
+
+thread_local! {
+    static CURRENT: RefCell<Current> = const { RefCell::new(None) };
+}
+
+pub fn new_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..12].to_string()
+}
+
+/// Restores the outer request when a scope ends, even by unwinding.
+struct Restore(Current);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Runs `f` as part of request `id`, or of a new request if `None`.
+pub fn scope<T>(id: Option<String>, f: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.with(|current| current.replace(Some(id)));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// The id of the request being handled on this thread, if any.
+pub fn current() -> Option<String> {
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        let id = current.as_mut()?;
+        Some(id.get_or_insert_with(new_id).clone())
+    })
+}
+
+/// Wraps `f` to run as part of the current request, wherever it runs.
+pub fn bind<T>(f: impl FnOnce() -> T + Send) -> impl FnOnce() -> T + Send {
+    let id = current();
+    move || match id {
+        Some(id) => scope(Some(id), f),
+        None => f(),
+    }
+}
+
+/// Errors that can carry the request id to the user.
+pub trait Annotate {
+    fn annotate(self, id: &str) -> Self;
+}
+
+impl Annotate for String {
+    fn annotate(self, id: &str) -> Self {
+        format!("{} (error id: {})", self, id)
+    }
+}
+
+/// Appends the current request id to an error shown to the user.
+pub fn annotate<E: Annotate>(error: E) -> E {
+    match current() {
+        Some(id) => error.annotate(&id),
+        None => error,
+    }
+}
+
+/// The body of an async command, run as part of request `id` with its error
+/// annotated. Async bodies run after [`wrap`] has returned, so those commands
+/// take `request_id` themselves and pass it here.
+pub fn bind_request<T, E: Annotate>(
+    id: Option<String>,
+    f: impl FnOnce() -> Result<T, E> + Send,
+) -> impl FnOnce() -> Result<T, E> + Send {
+    move || scope(id, || f().map_err(annotate))
+}
+
+/// How much gets logged; the `log_level` setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// How many of the last log lines crash reports include.
+pub const TAIL_LINES: usize = 100;
+
+static TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// The last [`TAIL_LINES`] lines written, oldest first. Empty if another
+/// thread holds the buffer, so a panic hook never waits on it.
+pub fn tail() -> Vec<String> {
+    match TAIL.try_lock() {
+        Ok(tail) => tail.iter().cloned().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+fn write(level: LogLevel, message: impl Display) {
+    if level as u8 > LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    let line = match current() {
+        Some(id) => format!("[{}] {}", id, message),
+        None => message.to_string(),
+    };
+    eprintln!("{}", line);
+    let mut tail = TAIL.lock().unwrap_or_else(|e| e.into_inner());
+    if tail.len() == TAIL_LINES {
+        tail.pop_front();
+    }
+    tail.push_back(line);
+}
+
+/// Writes a log line, prefixed with the request id inside a request.
+pub fn log(message: impl Display) {
+    write(LogLevel::Info, message);
+}
+
+/// Like [`log`], but still written at the `warn` level.
+pub fn warn(message: impl Display) {
+    write(LogLevel::Warn, message);
+}
+
+/// Adds the request id to an event payload that is a JSON object.
+pub fn tag_payload(payload: &mut serde_json::Value) {
+    if let (Some(object), Some(id)) = (payload.as_object_mut(), current()) {
+        object.insert(EVENT_FIELD.to_string(), serde_json::Value::String(id));
+    }
+}
+
+/// Whether a request is being handled on this thread.
+pub fn in_request() -> bool {
+    CURRENT.with(|current| current.borrow().is_some())
+}
+
+fn request_id_of(body: &InvokeBody) -> Option<String> {
+    match body {
+        InvokeBody::Json(args) => args.get(REQUEST_ID_ARG)?.as_str().map(str::to_string),
+        InvokeBody::Raw(_) => None,
+    }
+}
+
+/// Wraps the app's invoke handler so every command runs as a request.
+pub fn wrap<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let id = request_id_of(invoke.message.payload());
+        scope(id, || handler(invoke))
+    }
+}
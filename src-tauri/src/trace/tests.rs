@@ -0,0 +1,66 @@
+use super::*;
+
+#[test]
+fn test_no_request_outside_scope() {
+    assert!(!in_request());
+    assert_eq!(current(), None);
+}
+
+#[test]
+fn test_scope_uses_supplied_id_and_restores_outer() {
+    scope(Some("outer".to_string()), || {
+        scope(Some("inner".to_string()), || {
+            assert_eq!(current().as_deref(), Some("inner"));
+        });
+        assert_eq!(current().as_deref(), Some("outer"));
+    });
+    assert_eq!(current(), None);
+}
+
+#[test]
+fn test_generated_id_is_stable_within_scope() {
+    scope(None, || {
+        assert!(in_request());
+        let id = current().unwrap();
+        assert_eq!(id.len(), 12);
+        assert_eq!(current(), Some(id));
+    });
+}
+
+#[test]
+fn test_bind_carries_id_to_another_thread() {
+    let (id, seen) = scope(None, || {
+        let id = current();
+        let handle = std::thread::spawn(bind(current));
+        (id, handle.join().unwrap())
+    });
+    assert!(id.is_some());
+    assert_eq!(seen, id);
+}
+
+#[test]
+fn test_annotate_only_inside_request() {
+    assert_eq!(annotate("boom".to_string()), "boom");
+    let annotated = scope(Some("abc".to_string()), || annotate("boom".to_string()));
+    assert_eq!(annotated, "boom (error id: abc)");
+}
+
+#[test]
+fn test_bind_request_annotates_error() {
+    let run = bind_request(Some("abc".to_string()), || Err::<(), _>("boom".to_string()));
+    assert_eq!(run(), Err("boom (error id: abc)".to_string()));
+    assert_eq!(current(), None);
+}
+
+#[test]
+fn test_tag_payload_only_tags_objects() {
+    scope(Some("abc".to_string()), || {
+        let mut object = serde_json::json!({ "id": 1 });
+        tag_payload(&mut object);
+        assert_eq!(object, serde_json::json!({ "id": 1, "requestId": "abc" }));
+
+        let mut number = serde_json::json!(1);
+        tag_payload(&mut number);
+        assert_eq!(number, serde_json::json!(1));
+    });
+}
@@ -0,0 +1,435 @@
+//! Trust for profiles that came from someone else. A profile is the config
+//! directory holding `settings.toml` and the plugins; copying one from
+//! another machine could otherwise run its plugins or open its local API
+//! here. Every profile carries an `origin.toml` naming the installation
+//! that created it. A profile from another origin keeps the features that
+//! run or expose something inert until the user grants them, one by one.
+//!
+//! Grants live in `trust.json` in the app's local data directory, which no
+//! setting can move, so a profile can't arrive already trusted.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::audit;
+use crate::capabilities::{self, Capability, CapabilityRegistry};
+use crate::events;
+use crate::local_api::{self, LocalApiState};
+use crate::protocol::EventPayload;
+use crate::settings::{Settings, SettingsReactions, SettingsState};
+use crate::storage::Storage;
+use crate::trace;
+
+#[cfg(test)]
+mod tests;
+
+/// In the profile: which installation created it.
+pub const ORIGIN_FILE: &str = "origin.toml";
+/// In the local data directory, outside any profile.
+pub const TRUST_FILE: &str = "trust.json";
+const AUDIT_SOURCE: &str = "trust";
+const UNTRUSTED: &str = "untrusted profile";
+
+/// Features a foreign profile can't use until they're granted.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Feature {
+    /// Running the plugins under `plugins/`.
+    Plugins,
+    /// Listening on the port of `[backend.local_api]`.
+    LocalApi,
+    /// Starting the `instance_binary` of the settings.
+    InstanceBinary,
+}
+
+impl Feature {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Feature::Plugins => "plugins",
+            Feature::LocalApi => "localApi",
+            Feature::InstanceBinary => "instanceBinary",
+        }
+    }
+}
+
+/// The features `settings` would turn on.
+pub fn wanted(settings: &Settings) -> Vec<Feature> {
+    let mut wanted = Vec::new();
+    if settings.allow_plugins && !settings.enabled_plugins.is_empty() {
+        wanted.push(Feature::Plugins);
+    }
+    if settings.local_api.enabled {
+        wanted.push(Feature::LocalApi);
+    }
+    if settings.instance_binary.is_some() {
+        wanted.push(Feature::InstanceBinary);
+    }
+    wanted
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Origin {
+    /// Created by this installation.
+    Local,
+    /// Created elsewhere; `None` when its marker is missing.
+    Foreign { origin: Option<String> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OriginFile {
+    origin: String,
+}
+
+/// What grants can cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum TrustScope {
+    /// This profile, as long as it stays where it is with the same origin.
+    Profile,
+    /// Every profile from the same origin.
+    Origin,
+}
+
+/// The contents of `trust.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TrustStore {
+    pub installation_id: String,
+    /// Decisions per profile key, see [`profile_key`].
+    pub profiles: BTreeMap<String, BTreeMap<Feature, bool>>,
+    /// Decisions per origin.
+    pub origins: BTreeMap<String, BTreeMap<Feature, bool>>,
+}
+
+impl TrustStore {
+    /// Reads `path`, or starts a store with a new installation id. The flag
+    /// tells whether the store is new.
+    pub fn load(path: &Path) -> (Self, bool) {
+        let store = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<TrustStore>(&text).ok())
+            .filter(|store| !store.installation_id.is_empty());
+        match store {
+            Some(store) => (store, false),
+            None => (
+                TrustStore {
+                    installation_id: uuid::Uuid::new_v4().to_string(),
+                    ..Default::default()
+                },
+                true,
+            ),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Whether `feature` may run for a profile of `origin` at `key`. The
+    /// decision for the profile wins over the one for its origin.
+    pub fn allows(&self, origin: &Origin, key: &str, feature: Feature) -> bool {
+        let Origin::Foreign { origin } = origin else {
+            return true;
+        };
+        let decided = |decisions: Option<&BTreeMap<Feature, bool>>| {
+            decisions.and_then(|decisions| decisions.get(&feature).copied())
+        };
+        decided(self.profiles.get(key))
+            .or_else(|| decided(origin.as_ref().and_then(|o| self.origins.get(o))))
+            .unwrap_or(false)
+    }
+}
+
+/// Identifies a profile by where it is and where it came from, so another
+/// profile copied over it isn't covered by the earlier grants.
+pub fn profile_key(config_dir: &Path, origin: &Origin) -> String {
+    let origin = match origin {
+        Origin::Local => "local",
+        Origin::Foreign { origin } => origin.as_deref().unwrap_or("unknown"),
+    };
+    format!("{}#{}", config_dir.display(), origin)
+}
+
+/// Reads the origin of the profile in `config_dir`. A profile without a
+/// marker is taken as this installation's, and marked, only when it has no
+/// settings yet or trust is new here, which is the upgrade to this version.
+pub fn read_origin(config_dir: &Path, installation_id: &str, new_store: bool) -> Origin {
+    let marker = fs::read_to_string(config_dir.join(ORIGIN_FILE))
+        .ok()
+        .and_then(|text| toml::from_str::<OriginFile>(&text).ok());
+    match marker {
+        Some(marker) if marker.origin == installation_id => Origin::Local,
+        Some(marker) => Origin::Foreign {
+            origin: Some(marker.origin),
+        },
+        None if new_store || !config_dir.join(crate::settings::SETTINGS_FILE).exists() => {
+            if let Err(e) = write_origin(config_dir, installation_id) {
+                trace::log(e);
+            }
+            Origin::Local
+        }
+        None => Origin::Foreign { origin: None },
+    }
+}
+
+pub fn write_origin(config_dir: &Path, installation_id: &str) -> Result<(), String> {
+    fs::create_dir_all(config_dir)
+        .map_err(|e| format!("Failed to create {}: {}", config_dir.display(), e))?;
+    let text = toml::to_string(&OriginFile {
+        origin: installation_id.to_string(),
+    })
+    .map_err(|e| e.to_string())?;
+    let path = config_dir.join(ORIGIN_FILE);
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Managed state: the origin of the current profile and the grants.
+pub struct TrustState {
+    pub config_dir: PathBuf,
+    pub origin: Origin,
+    store_path: PathBuf,
+    store: Mutex<TrustStore>,
+    /// Features already announced as blocked, so each is asked about once.
+    announced: Mutex<BTreeSet<Feature>>,
+}
+
+impl TrustState {
+    pub fn open(config_dir: PathBuf, store_path: PathBuf) -> Self {
+        let (store, new_store) = TrustStore::load(&store_path);
+        let origin = read_origin(&config_dir, &store.installation_id, new_store);
+        if new_store {
+            if let Err(e) = store.save(&store_path) {
+                trace::log(e);
+            }
+        }
+        Self {
+            config_dir,
+            origin,
+            store_path,
+            store: Mutex::new(store),
+            announced: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    fn store(&self) -> MutexGuard<'_, TrustStore> {
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn key(&self) -> String {
+        profile_key(&self.config_dir, &self.origin)
+    }
+
+    pub fn allows(&self, feature: Feature) -> bool {
+        self.store().allows(&self.origin, &self.key(), feature)
+    }
+
+    /// What `settings` turn on that this profile may not use.
+    pub fn blocked(&self, settings: &Settings) -> Vec<Feature> {
+        wanted(settings)
+            .into_iter()
+            .filter(|feature| !self.allows(*feature))
+            .collect()
+    }
+
+    /// Records `decisions` and saves them.
+    pub fn decide(
+        &self,
+        scope: TrustScope,
+        decisions: &BTreeMap<Feature, bool>,
+    ) -> Result<(), String> {
+        let key = match (scope, &self.origin) {
+            (TrustScope::Profile, _) => self.key(),
+            (
+                TrustScope::Origin,
+                Origin::Foreign {
+                    origin: Some(origin),
+                },
+            ) => origin.clone(),
+            (TrustScope::Origin, _) => {
+                return Err("Failed to grant trust: this profile has no origin".to_string())
+            }
+        };
+        let mut store = self.store();
+        let granted = match scope {
+            TrustScope::Profile => store.profiles.entry(key).or_default(),
+            TrustScope::Origin => store.origins.entry(key).or_default(),
+        };
+        granted.extend(decisions);
+        store.save(&self.store_path)
+    }
+}
+
+/// Whether `feature` may run; false until the app has opened the profile.
+pub fn allows(app: &AppHandle, feature: Feature) -> bool {
+    app.try_state::<TrustState>()
+        .is_some_and(|trust| trust.allows(feature))
+}
+
+/// `settings` without the paths this profile may not run.
+pub fn restrict(app: &AppHandle, mut settings: Settings) -> Settings {
+    if !allows(app, Feature::InstanceBinary) {
+        settings.instance_binary = None;
+    }
+    settings
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustDecisionRequired {
+    pub profile: PathBuf,
+    pub origin: Origin,
+    /// Exactly what the settings turn on and is held back.
+    pub blocked: Vec<Feature>,
+}
+
+impl EventPayload for TrustDecisionRequired {}
+
+/// Emits `trust-decision-required` for features the settings turn on that
+/// the profile may not use and that weren't announced before.
+pub fn announce_blocked(app: &AppHandle) {
+    let (Some(trust), Some(settings)) = (
+        app.try_state::<TrustState>(),
+        app.try_state::<SettingsState>(),
+    ) else {
+        return;
+    };
+    let blocked = trust.blocked(&settings.get());
+    let fresh: Vec<Feature> = {
+        let mut announced = trust.announced.lock().unwrap_or_else(|e| e.into_inner());
+        blocked
+            .iter()
+            .copied()
+            .filter(|feature| announced.insert(*feature))
+            .collect()
+    };
+    if fresh.is_empty() {
+        return;
+    }
+    trace::log(format!(
+        "Holding back {:?} of a profile from elsewhere until trusted",
+        fresh
+    ));
+    let _ = events::emit(
+        app,
+        "trust-decision-required",
+        TrustDecisionRequired {
+            profile: trust.config_dir.clone(),
+            origin: trust.origin.clone(),
+            blocked,
+        },
+    );
+}
+
+/// Asks again when a settings change turns on something held back.
+pub fn register_settings_reactions(app: &AppHandle, reactions: &SettingsReactions) {
+    let app = app.clone();
+    reactions.register(
+        "trust",
+        &[
+            "allow_plugins",
+            "enabled_plugins",
+            "local_api",
+            "instance_binary",
+        ],
+        move |_, _| {
+            announce_blocked(&app);
+            capabilities::reprobe(&app);
+        },
+    );
+}
+
+/// Reports the held-back features as degraded, so they stay visible.
+pub fn register_capabilities(app: &AppHandle, registry: &CapabilityRegistry) {
+    let app = app.clone();
+    registry.register("plugins", move || {
+        let Some(settings) = app.try_state::<SettingsState>().map(|s| s.get()) else {
+            return Capability::Available;
+        };
+        if !wanted(&settings).contains(&Feature::Plugins) {
+            Capability::Unsupported {
+                reason: "Disabled in settings".to_string(),
+            }
+        } else if !allows(&app, Feature::Plugins) {
+            untrusted()
+        } else {
+            Capability::Available
+        }
+    });
+}
+
+pub fn untrusted() -> Capability {
+    Capability::Degraded {
+        detail: UNTRUSTED.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustStatus {
+    pub profile: PathBuf,
+    pub origin: Origin,
+    pub blocked: Vec<Feature>,
+}
+
+#[tauri::command]
+pub fn get_trust_status(
+    trust: State<'_, TrustState>,
+    settings: State<'_, SettingsState>,
+) -> TrustStatus {
+    TrustStatus {
+        profile: trust.config_dir.clone(),
+        origin: trust.origin.clone(),
+        blocked: trust.blocked(&settings.get()),
+    }
+}
+
+/// Allows (`true`) or keeps blocking (`false`) each feature for this
+/// profile or its whole origin. Every decision goes to the audit log, and
+/// a granted local API starts right away.
+#[tauri::command]
+pub fn grant_trust(
+    scope: TrustScope,
+    decisions: BTreeMap<Feature, bool>,
+    app: AppHandle,
+    trust: State<'_, TrustState>,
+    storage: State<'_, Storage>,
+    settings: State<'_, SettingsState>,
+) -> Result<TrustStatus, String> {
+    trust.decide(scope, &decisions)?;
+    for (feature, allowed) in &decisions {
+        let _ = audit::record(
+            &storage,
+            AUDIT_SOURCE,
+            if *allowed { "grant" } else { "deny" },
+            &format!(
+                "{} for {} ({:?})",
+                feature.as_str(),
+                trust.config_dir.display(),
+                scope
+            ),
+        );
+    }
+    let settings_now = settings.get();
+    if decisions.get(&Feature::LocalApi) == Some(&true) && trust.allows(Feature::LocalApi) {
+        app.state::<LocalApiState>().stop();
+        if let Err(e) = local_api::start(&app, &settings_now.local_api) {
+            trace::log(e);
+        }
+    }
+    capabilities::reprobe(&app);
+    Ok(get_trust_status(trust, settings))
+}
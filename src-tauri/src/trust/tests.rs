@@ -0,0 +1,128 @@
+use super::*;
+
+fn foreign(origin: &str) -> Origin {
+    Origin::Foreign {
+        origin: Some(origin.to_string()),
+    }
+}
+
+#[test]
+fn a_new_profile_is_marked_as_local() {
+    let dir = tempfile::tempdir().unwrap();
+    let profile = dir.path().join("profile");
+
+    assert_eq!(read_origin(&profile, "here", false), Origin::Local);
+    // Marked now, so it stays local even once it has settings.
+    fs::write(profile.join(crate::settings::SETTINGS_FILE), "").unwrap();
+    assert_eq!(read_origin(&profile, "here", false), Origin::Local);
+    assert_eq!(read_origin(&profile, "elsewhere", false), foreign("here"));
+}
+
+#[test]
+fn an_unmarked_profile_with_settings_is_foreign_unless_trust_is_new() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(crate::settings::SETTINGS_FILE), "").unwrap();
+
+    assert_eq!(
+        read_origin(dir.path(), "here", false),
+        Origin::Foreign { origin: None }
+    );
+    // The first start with trust: the profile predates the markers.
+    assert_eq!(read_origin(dir.path(), "here", true), Origin::Local);
+    assert_eq!(read_origin(dir.path(), "here", false), Origin::Local);
+}
+
+#[test]
+fn foreign_profiles_need_a_grant_and_the_profile_decision_wins() {
+    let origin = foreign("there");
+    let key = profile_key(Path::new("/profile"), &origin);
+    let mut store = TrustStore::default();
+
+    assert!(store.allows(&Origin::Local, "any", Feature::Plugins));
+    assert!(!store.allows(&origin, &key, Feature::Plugins));
+
+    store.origins.insert(
+        "there".to_string(),
+        BTreeMap::from([(Feature::Plugins, true)]),
+    );
+    assert!(store.allows(&origin, &key, Feature::Plugins));
+    assert!(!store.allows(&origin, &key, Feature::LocalApi));
+
+    store
+        .profiles
+        .insert(key.clone(), BTreeMap::from([(Feature::Plugins, false)]));
+    assert!(!store.allows(&origin, &key, Feature::Plugins));
+    // Another profile from the same origin still has the origin's grant.
+    let other = profile_key(Path::new("/other"), &origin);
+    assert!(store.allows(&origin, &other, Feature::Plugins));
+}
+
+#[test]
+fn a_profile_replaced_by_one_from_elsewhere_loses_its_grants() {
+    let dir = Path::new("/profile");
+    let mut store = TrustStore::default();
+    store.profiles.insert(
+        profile_key(dir, &foreign("there")),
+        BTreeMap::from([(Feature::LocalApi, true)]),
+    );
+
+    let replaced = foreign("somewhere else");
+    assert!(!store.allows(&replaced, &profile_key(dir, &replaced), Feature::LocalApi));
+}
+
+#[test]
+fn decisions_are_saved_outside_the_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let profile = dir.path().join("profile");
+    fs::create_dir_all(&profile).unwrap();
+    write_origin(&profile, "there").unwrap();
+    let store_path = dir.path().join("local").join(TRUST_FILE);
+
+    let trust = TrustState::open(profile.clone(), store_path.clone());
+    assert_eq!(trust.origin, foreign("there"));
+    assert!(!trust.allows(Feature::LocalApi));
+    assert!(trust
+        .decide(
+            TrustScope::Origin,
+            &BTreeMap::from([(Feature::LocalApi, true)])
+        )
+        .is_ok());
+    assert!(trust.allows(Feature::LocalApi));
+
+    let reopened = TrustState::open(profile, store_path);
+    assert!(reopened.allows(Feature::LocalApi));
+    assert!(!reopened.allows(Feature::Plugins));
+}
+
+#[test]
+fn only_what_the_settings_turn_on_is_blocked() {
+    let dir = tempfile::tempdir().unwrap();
+    let profile = dir.path().join("profile");
+    write_origin(&profile, "there").unwrap();
+    let trust = TrustState::open(profile, dir.path().join(TRUST_FILE));
+    let mut settings = Settings::default();
+    assert!(trust.blocked(&settings).is_empty());
+
+    settings.local_api.enabled = true;
+    settings.allow_plugins = true;
+    assert_eq!(trust.blocked(&settings), vec![Feature::LocalApi]);
+    settings.enabled_plugins = vec!["tagger".to_string()];
+    assert_eq!(
+        trust.blocked(&settings),
+        vec![Feature::Plugins, Feature::LocalApi]
+    );
+}
+
+#[test]
+fn a_local_profile_cannot_grant_by_origin() {
+    let dir = tempfile::tempdir().unwrap();
+    let trust = TrustState::open(dir.path().join("profile"), dir.path().join(TRUST_FILE));
+
+    assert_eq!(trust.origin, Origin::Local);
+    assert!(trust
+        .decide(
+            TrustScope::Origin,
+            &BTreeMap::from([(Feature::Plugins, true)])
+        )
+        .is_err());
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Half-open time range `[start, end)` used by reporting commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DateRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl Priority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "low" => Some(Priority::Low),
+            "medium" => Some(Priority::Medium),
+            "high" => Some(Priority::High),
+            _ => None,
+        }
+    }
+}
+
+/// Value of a user-defined custom field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Number(f64),
+    Date(NaiveDate),
+    /// Text, URL and select fields.
+    Text(String),
+}
+
+/// Languages of a todo's title and description, as ISO 639-3 codes such as
+/// `jpn`. A part is `None` when detection wasn't confident.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TextLanguages {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl TextLanguages {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none()
+    }
+
+    /// Whether the title or the description is in `language`.
+    pub fn contains(&self, language: &str) -> bool {
+        [&self.title, &self.description]
+            .into_iter()
+            .any(|detected| detected.as_deref() == Some(language))
+    }
+}
+
+/// Mirrors `Todo` in `src/types/todo.ts`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Todo {
+    pub id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub completed: bool,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Set by the cache on the write that completes the todo and cleared on
+    /// the one that reopens it; see [`crate::storage::insert_todo`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+    /// Position in the manually ordered list, an order key from
+    /// [`crate::rank`]. The cache assigns one when a todo arrives without it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Expected and recorded effort; see [`crate::stats`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate_minutes: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual_minutes: Option<u32>,
+    /// Set by the cache from the text; see [`crate::language`].
+    #[serde(default, skip_serializing_if = "TextLanguages::is_empty")]
+    pub languages: TextLanguages,
+    /// Custom field values keyed by field name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, FieldValue>,
+    /// Bumped by the cache on every write. Updates pass the revision they
+    /// read, so a stale copy can't silently overwrite a newer one.
+    #[serde(default)]
+    pub revision: u64,
+}
+
+impl Todo {
+    /// Serialized names of the fields that differ from `old`, e.g. `["title"]`.
+    ///
+    /// `updatedAt` and `revision` are ignored, as are custom field values,
+    /// which change through their own commands.
+    pub fn changed_fields(&self, old: &Todo) -> Vec<String> {
+        let (new, old) = (self.as_map(), old.as_map());
+        let mut changed: Vec<String> = new
+            .keys()
+            .chain(old.keys())
+            .filter(|key| {
+                !UNTRACKED_FIELDS.contains(&key.as_str()) && new.get(*key) != old.get(*key)
+            })
+            .cloned()
+            .collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+
+    /// Field-level three-way merge of an edit (`self`, made on a copy of
+    /// `base`) into `current`. Fails with the names of the fields that both
+    /// sides changed to different values.
+    pub fn merge_into(&self, base: &Todo, current: &Todo) -> Result<Todo, Vec<String>> {
+        let (mine, ancestor) = (self.as_map(), base.as_map());
+        let mut merged = current.as_map();
+        let mut conflicts = Vec::new();
+        for key in self.changed_fields(base) {
+            let theirs = merged.get(&key);
+            if theirs != ancestor.get(&key) && theirs != mine.get(&key) {
+                conflicts.push(key);
+                continue;
+            }
+            match mine.get(&key) {
+                Some(value) => merged.insert(key, value.clone()),
+                None => merged.remove(&key),
+            };
+        }
+        if !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+        let mut merged: Todo = serde_json::from_value(serde_json::Value::Object(merged))
+            .map_err(|e| vec![e.to_string()])?;
+        merged.updated_at = self.updated_at.max(current.updated_at);
+        Ok(merged)
+    }
+
+    fn as_map(&self) -> serde_json::Map<String, serde_json::Value> {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        }
+    }
+}
+
+/// Test fixtures build on this, so a new field only needs a default here.
+#[cfg(test)]
+impl Todo {
+    /// An open todo created (and last updated) at `created_at`, with every
+    /// optional field empty.
+    pub fn sample(
+        id: impl Into<String>,
+        title: impl Into<String>,
+        created_at: DateTime<Utc>,
+    ) -> Self {
+        Todo {
+            id: id.into(),
+            title: title.into(),
+            description: None,
+            completed: false,
+            priority: Priority::default(),
+            scheduled_for: None,
+            created_at,
+            updated_at: created_at,
+            completed_at: None,
+            order: None,
+            rank: None,
+            schedule_id: None,
+            tags: Vec::new(),
+            estimate_minutes: None,
+            actual_minutes: None,
+            languages: TextLanguages::default(),
+            fields: BTreeMap::new(),
+            revision: 0,
+        }
+    }
+}
+
+/// Serialized fields that [`Todo::changed_fields`] does not report. Like
+/// custom fields, `rank` changes through its own command; `languages`
+/// follow from the text.
+const UNTRACKED_FIELDS: &[&str] = &[
+    "id",
+    "updatedAt",
+    "completedAt",
+    "languages",
+    "fields",
+    "rank",
+    "revision",
+];
+
+/// A recurring schedule in the store. `rrule` is an RFC 5545 RRULE value
+/// such as `FREQ=WEEKLY;BYDAY=MO,FR`, applied from `starts_at`, which is a
+/// floating local time like the server's schedules used.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    pub id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub priority: Priority,
+    pub starts_at: NaiveDateTime,
+    pub rrule: String,
+    /// Days on which the rule doesn't fire.
+    #[serde(default)]
+    pub excluded_dates: Vec<NaiveDate>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,308 @@
+//! Team conventions for todo content, such as "every todo in the Work list
+//! needs a due date and an estimate". Rules are read from
+//! `[[backend.validation_rules]]` in `settings.toml`, so a team shares them
+//! along with the rest of its settings.
+//!
+//! The rules are compiled once into a [`RuleSet`] per command or import and
+//! checked when a todo is updated, created through the local API or
+//! imported. A `block` violation refuses the write; `warn` violations are
+//! returned with the result and counted in the activity feed.
+
+use chrono::NaiveDate;
+use regex::Regex;
+use rusqlite::{Connection, OptionalExtension};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::activity;
+use crate::query::{self, QueryAst};
+use crate::settings::SettingsState;
+use crate::storage::Storage;
+use crate::types::Todo;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequiredField {
+    Description,
+    DueDate,
+    Estimate,
+    Tags,
+}
+
+impl RequiredField {
+    fn is_set(self, todo: &Todo) -> bool {
+        match self {
+            RequiredField::Description => todo
+                .description
+                .as_deref()
+                .is_some_and(|d| !d.trim().is_empty()),
+            RequiredField::DueDate => todo.scheduled_for.is_some(),
+            RequiredField::Estimate => todo.estimate_minutes.is_some(),
+            RequiredField::Tags => !todo.tags.is_empty(),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            RequiredField::Description => "a description",
+            RequiredField::DueDate => "a due date",
+            RequiredField::Estimate => "an estimate",
+            RequiredField::Tags => "a tag",
+        }
+    }
+}
+
+/// What a rule checks, e.g. `check = { kind = "require", field = "due_date" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleCheck {
+    Require {
+        field: RequiredField,
+    },
+    /// The title must match this regular expression.
+    TitleMatches {
+        pattern: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[schemars(rename = "RuleSeverity")]
+pub enum Severity {
+    #[default]
+    Warn,
+    Block,
+}
+
+/// One `[[backend.validation_rules]]` entry. It applies to every todo unless
+/// limited to a smart list, a tag, or both.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationRule {
+    pub name: String,
+    /// Smart list id.
+    #[serde(default)]
+    pub list: Option<String>,
+    /// Matched case-insensitively.
+    #[serde(default)]
+    pub tag: Option<String>,
+    pub check: RuleCheck,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Violation {
+    pub rule: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+pub fn is_blocking(violations: &[Violation]) -> bool {
+    violations.iter().any(|v| v.severity == Severity::Block)
+}
+
+/// One line naming each broken rule, for error messages.
+pub fn describe(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(|v| format!("{} ({})", v.rule, v.message))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Which todos a rule, or a lint run, looks at.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LintScope {
+    pub list: Option<String>,
+    pub tag: Option<String>,
+}
+
+struct CompiledScope {
+    list: Option<QueryAst>,
+    tag: Option<String>,
+}
+
+impl CompiledScope {
+    fn new(conn: &Connection, list: Option<&str>, tag: Option<&str>) -> Result<Self, String> {
+        let list = match list {
+            Some(id) => {
+                let query: String = conn
+                    .query_row("SELECT query FROM smart_lists WHERE id = ?1", [id], |row| {
+                        row.get(0)
+                    })
+                    .optional()
+                    .map_err(|e| format!("Failed to load smart list: {}", e))?
+                    .ok_or_else(|| format!("Unknown smart list '{}'", id))?;
+                Some(query::parse_query(&query).map_err(|e| format!("Syntax error: {}", e))?)
+            }
+            None => None,
+        };
+        Ok(Self {
+            list,
+            tag: tag.map(str::to_string),
+        })
+    }
+
+    fn contains(&self, todo: &Todo, today: NaiveDate) -> bool {
+        self.tag
+            .as_ref()
+            .is_none_or(|tag| todo.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+            && self
+                .list
+                .as_ref()
+                .is_none_or(|ast| query::matches(ast, todo, today))
+    }
+}
+
+struct CompiledRule {
+    rule: ValidationRule,
+    scope: CompiledScope,
+    pattern: Option<Regex>,
+}
+
+/// Rules with their lists and patterns compiled, ready to check todos.
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+    today: NaiveDate,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            today: NaiveDate::MIN,
+        }
+    }
+}
+
+impl RuleSet {
+    /// Fails on a rule naming a list that doesn't exist or an invalid pattern,
+    /// so a broken rule can't silently let todos through.
+    pub fn compile(
+        conn: &Connection,
+        rules: &[ValidationRule],
+        today: NaiveDate,
+    ) -> Result<Self, String> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let pattern = match &rule.check {
+                    RuleCheck::TitleMatches { pattern } => {
+                        Some(Regex::new(pattern).map_err(|e| {
+                            format!(
+                                "Validation rule '{}' has an invalid pattern: {}",
+                                rule.name, e
+                            )
+                        })?)
+                    }
+                    RuleCheck::Require { .. } => None,
+                };
+                Ok(CompiledRule {
+                    rule: rule.clone(),
+                    scope: CompiledScope::new(conn, rule.list.as_deref(), rule.tag.as_deref())?,
+                    pattern,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules, today })
+    }
+
+    /// Compiles the rules in `settings.toml` against the store.
+    pub fn load(storage: &Storage, rules: &[ValidationRule]) -> Result<Self, String> {
+        Self::compile(&storage.conn(), rules, storage.clock().today())
+    }
+
+    pub fn check(&self, todo: &Todo) -> Vec<Violation> {
+        self.rules
+            .iter()
+            .filter(|compiled| compiled.scope.contains(todo, self.today))
+            .filter_map(|compiled| {
+                let message = match (&compiled.rule.check, &compiled.pattern) {
+                    (RuleCheck::Require { field }, _) if !field.is_set(todo) => {
+                        format!("needs {}", field.label())
+                    }
+                    (RuleCheck::TitleMatches { pattern }, Some(regex))
+                        if !regex.is_match(&todo.title) =>
+                    {
+                        format!("title must match '{}'", pattern)
+                    }
+                    _ => return None,
+                };
+                Some(Violation {
+                    rule: compiled.rule.name.clone(),
+                    severity: compiled.rule.severity,
+                    message,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Counts warnings that were let through in the activity feed.
+pub fn note_warnings(storage: &Storage, violations: &[Violation]) {
+    let warnings = violations
+        .iter()
+        .filter(|v| v.severity == Severity::Warn)
+        .count();
+    if warnings > 0 {
+        activity::validation_warned(storage, warnings as u64);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoViolations {
+    pub todo_id: String,
+    pub title: String,
+    pub violations: Vec<Violation>,
+}
+
+/// Result of checking existing todos, in list order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LintReport {
+    pub checked: usize,
+    pub todos: Vec<TodoViolations>,
+}
+
+/// Checks the cached todos in `scope` against `rules`.
+pub fn lint(storage: &Storage, rules: &RuleSet, scope: &LintScope) -> Result<LintReport, String> {
+    let todos = storage
+        .list_todos()
+        .map_err(|e| format!("Failed to load todos: {}", e))?;
+    let scope = CompiledScope::new(&storage.conn(), scope.list.as_deref(), scope.tag.as_deref())?;
+    let mut report = LintReport::default();
+    for todo in todos
+        .into_iter()
+        .filter(|todo| scope.contains(todo, rules.today))
+    {
+        report.checked += 1;
+        let violations = rules.check(&todo);
+        if !violations.is_empty() {
+            report.todos.push(TodoViolations {
+                todo_id: todo.id,
+                title: todo.title,
+                violations,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Checks the current todos against the validation rules, e.g. after the
+/// rules changed.
+#[tauri::command]
+pub fn lint_existing_todos(
+    scope: Option<LintScope>,
+    storage: State<'_, Storage>,
+    settings: State<'_, SettingsState>,
+) -> Result<LintReport, String> {
+    let rules = RuleSet::load(&storage, &settings.get().validation_rules)?;
+    lint(&storage, &rules, &scope.unwrap_or_default())
+}
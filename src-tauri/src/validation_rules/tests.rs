@@ -0,0 +1,158 @@
+use super::*;
+
+use chrono::{TimeZone, Utc};
+
+use crate::query;
+
+fn todo(id: &str, title: &str, tags: &[&str]) -> Todo {
+    let created = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+    Todo {
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        ..Todo::sample(id, title, created)
+    }
+}
+
+fn rule(name: &str, tag: Option<&str>, check: RuleCheck, severity: Severity) -> ValidationRule {
+    ValidationRule {
+        name: name.to_string(),
+        list: None,
+        tag: tag.map(str::to_string),
+        check,
+        severity,
+    }
+}
+
+fn compile(storage: &Storage, rules: &[ValidationRule]) -> Result<RuleSet, String> {
+    RuleSet::load(storage, rules)
+}
+
+#[test]
+fn test_rules_apply_to_their_tag_with_their_severity() {
+    let storage = Storage::open_in_memory().unwrap();
+    let rules = compile(
+        &storage,
+        &[
+            rule(
+                "work-due",
+                Some("Work"),
+                RuleCheck::Require {
+                    field: RequiredField::DueDate,
+                },
+                Severity::Block,
+            ),
+            rule(
+                "ticket",
+                None,
+                RuleCheck::TitleMatches {
+                    pattern: r"^[A-Z]+-\d+ ".to_string(),
+                },
+                Severity::Warn,
+            ),
+        ],
+    )
+    .unwrap();
+
+    let violations = rules.check(&todo("1", "Write notes", &["work"]));
+    assert_eq!(
+        violations,
+        vec![
+            Violation {
+                rule: "work-due".to_string(),
+                severity: Severity::Block,
+                message: "needs a due date".to_string(),
+            },
+            Violation {
+                rule: "ticket".to_string(),
+                severity: Severity::Warn,
+                message: r"title must match '^[A-Z]+-\d+ '".to_string(),
+            },
+        ]
+    );
+    assert!(is_blocking(&violations));
+
+    let home = rules.check(&todo("2", "Water plants", &["home"]));
+    assert_eq!(home.len(), 1);
+    assert!(!is_blocking(&home));
+    assert!(rules
+        .check(&todo("3", "OPS-12 Rotate keys", &[]))
+        .is_empty());
+}
+
+#[test]
+fn test_broken_rules_fail_to_compile() {
+    let storage = Storage::open_in_memory().unwrap();
+    let mut unknown_list = rule(
+        "estimate",
+        None,
+        RuleCheck::Require {
+            field: RequiredField::Estimate,
+        },
+        Severity::Warn,
+    );
+    unknown_list.list = Some("missing".to_string());
+    assert!(compile(&storage, &[unknown_list])
+        .err()
+        .unwrap()
+        .contains("Unknown smart list 'missing'"));
+
+    let bad_pattern = rule(
+        "ticket",
+        None,
+        RuleCheck::TitleMatches {
+            pattern: "(".to_string(),
+        },
+        Severity::Block,
+    );
+    assert!(compile(&storage, &[bad_pattern])
+        .err()
+        .unwrap()
+        .contains("Validation rule 'ticket' has an invalid pattern"));
+}
+
+#[test]
+fn test_lint_reports_todos_in_scope_breaking_list_rules() {
+    let storage = Storage::open_in_memory().unwrap();
+    let list = query::create_list(&storage, "Work", "tag:work").unwrap();
+    let mut estimated = todo("2", "Review plan", &["work"]);
+    estimated.estimate_minutes = Some(30);
+    storage
+        .replace_todos(&[
+            todo("1", "Write notes", &["work"]),
+            estimated,
+            todo("3", "Water plants", &["home"]),
+        ])
+        .unwrap();
+    let mut estimate = rule(
+        "work-estimate",
+        None,
+        RuleCheck::Require {
+            field: RequiredField::Estimate,
+        },
+        Severity::Warn,
+    );
+    estimate.list = Some(list.id);
+    let rules = compile(&storage, &[estimate]).unwrap();
+
+    let report = lint(&storage, &rules, &LintScope::default()).unwrap();
+    assert_eq!(report.checked, 3);
+    assert_eq!(
+        report.todos,
+        vec![TodoViolations {
+            todo_id: "1".to_string(),
+            title: "Write notes".to_string(),
+            violations: vec![Violation {
+                rule: "work-estimate".to_string(),
+                severity: Severity::Warn,
+                message: "needs an estimate".to_string(),
+            }],
+        }]
+    );
+
+    let scope = LintScope {
+        list: None,
+        tag: Some("home".to_string()),
+    };
+    let report = lint(&storage, &rules, &scope).unwrap();
+    assert_eq!(report.checked, 1);
+    assert!(report.todos.is_empty());
+}